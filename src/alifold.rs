@@ -0,0 +1,176 @@
+//! Consensus folding with RNAalifold (ViennaRNA), run as an external program.
+//!
+//! `:alifold` pipes the alignment to RNAalifold as Clustal and reads back the
+//! consensus structure and its energy, plus, with `:set alifold.mis=on`, the
+//! most informative sequence (MIS): the IUPAC consensus of every residue more
+//! frequent in its column than in the alignment as a whole.
+
+use std::io::{self, Write};
+use std::process::{Command, Stdio};
+
+use thiserror::Error;
+
+/// Program run by `:alifold` unless `:set alifold.program` names another.
+pub const DEFAULT_PROGRAM: &str = "RNAalifold";
+
+/// Options passed to RNAalifold (`:set alifold.*`).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Options {
+    /// Program to run, found on the PATH unless given as a path.
+    pub program: String,
+    /// Score covariation with RIBOSUM matrices (`-r`).
+    pub ribosum: bool,
+    /// Print the most informative sequence as the consensus (`--mis`), which
+    /// `:alifold` adds as a `#=GC MIS` row.
+    pub mis: bool,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Self {
+            program: DEFAULT_PROGRAM.to_string(),
+            ribosum: false,
+            mis: false,
+        }
+    }
+}
+
+impl Options {
+    /// Command-line arguments for these options. `--noPS` keeps RNAalifold from
+    /// drawing `alirna.ps` into the working directory.
+    pub fn args(&self) -> Vec<&'static str> {
+        let mut args = vec!["--noPS"];
+        if self.ribosum {
+            args.push("-r");
+        }
+        if self.mis {
+            args.push("--mis");
+        }
+        args
+    }
+}
+
+/// What RNAalifold predicted for an alignment.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Prediction {
+    /// Consensus sequence, the MIS with `--mis`.
+    pub consensus: String,
+    /// Consensus structure in dot-bracket notation, one character per column.
+    pub structure: String,
+    /// Free energy of the structure, in kcal/mol.
+    pub energy: f64,
+}
+
+#[derive(Error, Debug)]
+pub enum AlifoldError {
+    #[error("Could not run {program}: {source}")]
+    Spawn { program: String, source: io::Error },
+    #[error("IO error: {0}")]
+    Io(#[from] io::Error),
+    #[error("{program} failed: {message}")]
+    Failed { program: String, message: String },
+    #[error("No consensus structure in the RNAalifold output")]
+    Output,
+}
+
+/// Run RNAalifold on an alignment written as Clustal.
+pub fn run(options: &Options, clustal: &[u8]) -> Result<Prediction, AlifoldError> {
+    let mut child = Command::new(&options.program)
+        .args(options.args())
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|source| AlifoldError::Spawn {
+            program: options.program.clone(),
+            source,
+        })?;
+    // RNAalifold reads the whole alignment before it writes anything. If it
+    // stops early it closes its stdin, and its exit status then says why.
+    if let Some(mut stdin) = child.stdin.take()
+        && let Err(e) = stdin.write_all(clustal)
+        && e.kind() != io::ErrorKind::BrokenPipe
+    {
+        return Err(e.into());
+    }
+    let output = child.wait_with_output()?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(AlifoldError::Failed {
+            program: options.program.clone(),
+            message: stderr
+                .lines()
+                .find(|l| !l.trim().is_empty())
+                .map_or_else(|| output.status.to_string(), str::to_string),
+        });
+    }
+    parse(&String::from_utf8_lossy(&output.stdout))
+}
+
+/// Read RNAalifold's output: the consensus sequence on one line, then the
+/// structure followed by its energy, as in `((...)) (-1.20 = -1.00 +  -0.20)`.
+pub fn parse(output: &str) -> Result<Prediction, AlifoldError> {
+    let lines: Vec<&str> = output
+        .lines()
+        .map(str::trim)
+        .filter(|l| !l.is_empty() && !l.starts_with('>'))
+        .collect();
+    for pair in lines.windows(2) {
+        let consensus = pair[0];
+        let Some((structure, energy)) = pair[1].split_once(char::is_whitespace) else {
+            continue;
+        };
+        let energy = energy
+            .trim()
+            .strip_prefix('(')
+            .and_then(|e| e.split(['=', ')']).next())
+            .and_then(|e| e.trim().parse().ok());
+        let dot_bracket = structure.chars().all(|c| "().[]{}<>".contains(c));
+        if let Some(energy) = energy
+            && dot_bracket
+            && structure.chars().count() == consensus.chars().count()
+        {
+            return Ok(Prediction {
+                consensus: consensus.to_string(),
+                structure: structure.to_string(),
+                energy,
+            });
+        }
+    }
+    Err(AlifoldError::Output)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_args() {
+        let mut options = Options::default();
+        assert_eq!(options.args(), ["--noPS"]);
+        options.ribosum = true;
+        options.mis = true;
+        assert_eq!(options.args(), ["--noPS", "-r", "--mis"]);
+    }
+
+    #[test]
+    fn test_parse() {
+        let output = "GGGAAAUCCC\n(((....))) ( -1.20 =  -1.00 +  -0.20)\n";
+        let prediction = parse(output).unwrap();
+        assert_eq!(prediction.consensus, "GGGAAAUCCC");
+        assert_eq!(prediction.structure, "(((....)))");
+        assert_eq!(prediction.energy, -1.2);
+
+        // A named alignment adds a header line; MIS consensus has IUPAC codes
+        let output = ">tRNA\nGSCUWNNAGC\n((......)) (-10.50 = -9.80 +  -0.70)\n";
+        let prediction = parse(output).unwrap();
+        assert_eq!(prediction.consensus, "GSCUWNNAGC");
+        assert_eq!(prediction.energy, -10.5);
+
+        assert!(matches!(parse("ACGU\n"), Err(AlifoldError::Output)));
+        assert!(matches!(
+            parse("ACGU\n((.)) (-1.00)\n"),
+            Err(AlifoldError::Output)
+        ));
+    }
+}
@@ -1,14 +1,38 @@
 //! Application state and main loop.
 
+use std::cell::RefCell;
+use std::collections::{BTreeMap, HashMap};
 use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, mpsc};
+use std::time::{Duration, Instant};
 
+use ratatui::buffer::Buffer;
+use ratatui::crossterm::event::{KeyCode, KeyEvent};
 use strum::AsRefStr;
 
-use crate::color::Theme;
+use crate::alifold::{self, AlifoldError, Prediction};
+use crate::clustering::ClusterResult;
+use crate::color::{ColumnStats, Theme, column_stats, get_consensus_char};
+use crate::config::EditorConfig;
 use crate::editor::History;
+use crate::formats::fasta::FastaError;
+use crate::formats::{Format, FormatError, LineEnding, WriteOptions};
 use crate::history::InputHistory;
-use crate::stockholm::{Alignment, SequenceType};
-use crate::structure::StructureCache;
+use crate::hits::{self, AlignError, Hit};
+use crate::palette::{Action, Palette};
+use crate::script;
+use crate::search::{REGEX_PREFIX, SearchKind, SearchPattern, Substitution};
+use crate::shape::{Shape, ShapeHistory};
+use crate::stockholm::writer::StockholmStyle;
+use crate::stockholm::{
+    Alignment, ColumnAnnotation, DEFAULT_GAP_CHARS, DetachedSequence, MaskStyle, SequenceEnd,
+    SequenceType, ShiftDirection, TRACK_MAX, coords_length, parse_coords, short_id, track_level,
+};
+use crate::structure::fold::{DEFAULT_SHUFFLES, FoldZScore, WEAK_FOLD_Z, mfe_zscore};
+use crate::structure::{
+    CompensatoryChange, SsConflict, StructureCache, analyze_compensatory, ss_conflicts,
+};
 
 /// Search state for pattern matching in sequences.
 #[derive(Debug, Clone, Default)]
@@ -16,6 +40,7 @@ pub struct SearchState {
     /// Current search pattern.
     pub pattern: String,
     /// All match positions (row, start_col, end_col) - end_col is exclusive.
+    /// An empty span at column 0 is a match in the sequence's ID.
     pub matches: Vec<(usize, usize, usize)>,
     /// Current match index in matches.
     pub match_index: Option<usize>,
@@ -55,6 +80,17 @@ impl SearchState {
         }
     }
 
+    /// Check if a row's ID is a search match, as `is_match` does for cells.
+    pub fn is_id_match(&self, row: usize) -> Option<bool> {
+        if !self.has_matches() {
+            return None;
+        }
+        self.matches
+            .iter()
+            .position(|&(match_row, start_col, end_col)| match_row == row && start_col == end_col)
+            .map(|idx| self.match_index == Some(idx))
+    }
+
     /// Check if a position is part of a search match.
     /// Returns Some(true) if it's the current match, Some(false) if it's another match, None if not a match.
     pub fn is_match(&self, row: usize, col: usize) -> Option<bool> {
@@ -95,6 +131,8 @@ pub enum Mode {
     Search,
     /// Visual block selection mode.
     Visual,
+    /// Editing the values of a numeric #=GC track (`:track`).
+    Track,
 }
 
 /// Color scheme for the alignment display.
@@ -113,6 +151,8 @@ pub enum ColorScheme {
     Compensatory,
     /// Color by per-residue posterior probability (#=GR PP).
     PP,
+    /// Color where a sequence's own #=GR SS disagrees with SS_cons.
+    SsDiff,
 }
 
 impl ColorScheme {
@@ -124,9 +164,132 @@ impl ColorScheme {
             "conservation" | "cons" => Some(ColorScheme::Conservation),
             "compensatory" | "comp" => Some(ColorScheme::Compensatory),
             "pp" | "probability" => Some(ColorScheme::PP),
+            "ssdiff" | "grss" => Some(ColorScheme::SsDiff),
             _ => None,
         }
     }
+
+    /// Parse a `:color` argument: a scheme, optionally with an overlay layer
+    /// after `+` (e.g. `base+cons`).
+    pub fn parse_layers(s: &str) -> Option<(Self, Option<ColorOverlay>)> {
+        match s.split_once('+') {
+            Some((scheme, overlay)) => Some((
+                Self::from_str(scheme)?,
+                Some(ColorOverlay::from_str(overlay)?),
+            )),
+            None => Some((Self::from_str(s)?, None)),
+        }
+    }
+}
+
+/// Second coloring layer stacked on the color scheme with `:color <scheme>+<overlay>`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ColorOverlay {
+    /// Color residue letters with another scheme, keeping the scheme's background.
+    Foreground(ColorScheme),
+    /// Dim the background where the column is poorly conserved.
+    Conservation,
+    /// Dim the background where the residue's #=GR PP is low.
+    PP,
+    /// Dim the background where the column is mostly gaps.
+    Gaps,
+}
+
+impl ColorOverlay {
+    pub fn from_str(s: &str) -> Option<Self> {
+        if matches!(s.to_lowercase().as_str(), "gaps" | "gap" | "occupancy") {
+            return Some(ColorOverlay::Gaps);
+        }
+        match ColorScheme::from_str(s)? {
+            ColorScheme::None => None,
+            ColorScheme::Conservation => Some(ColorOverlay::Conservation),
+            ColorScheme::PP => Some(ColorOverlay::PP),
+            scheme => Some(ColorOverlay::Foreground(scheme)),
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        match self {
+            ColorOverlay::Foreground(scheme) => scheme.as_ref(),
+            ColorOverlay::Conservation => "conservation",
+            ColorOverlay::PP => "pp",
+            ColorOverlay::Gaps => "gaps",
+        }
+    }
+}
+
+/// Part of the alignment the color scheme is limited to with `:focus`; cells
+/// outside it are drawn monochrome.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ColorFocus {
+    /// A block of sequences, by ID (sorted) so that it follows them when rows
+    /// move, and an inclusive column range.
+    Region {
+        ids: Vec<String>,
+        cols: (usize, usize),
+    },
+    /// SS_cons helices, each by its outermost 5' column so that the focus
+    /// survives helices being renumbered.
+    Helices(Vec<usize>),
+}
+
+/// Columns that edits move along with the residues: the pins and the `:focus`.
+/// Each undo step saves them with the alignment.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ColumnState {
+    pub pinned_columns: Vec<usize>,
+    pub color_focus: Option<ColorFocus>,
+}
+
+/// A cursor position set with `m{a-z}`, saved in the jump list or, as the
+/// top-left of a region to show, with `:bookmark`: the sequence, by actual row
+/// and by ID to find it again after rows move, and the column.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Mark {
+    pub row: usize,
+    pub id: String,
+    pub col: usize,
+}
+
+/// An edit made with a key that `.` repeats at the cursor.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Edit {
+    DeleteGap,
+    InsertGapColumn,
+    DeleteGapColumn,
+    ShiftLeft,
+    ShiftRight,
+    ThrowLeft,
+    ThrowRight,
+    // Visual mode, repeated on a selection of the same size
+    InsertSelectionGapColumns,
+    DeleteSelectionGapColumns,
+    InsertBlockGaps,
+    CloseBlockGaps,
+    Uppercase,
+    Lowercase,
+}
+
+/// The last edit made with a key, for `.`.
+#[derive(Debug, Clone, Copy)]
+pub struct LastEdit {
+    pub edit: Edit,
+    /// Count it was given (`3>`), 1 without one.
+    pub count: usize,
+    /// Rows and columns of the visual selection it was made on, and whether
+    /// that was a block (`Ctrl-v`) selection.
+    pub selection: Option<(usize, usize, bool)>,
+}
+
+/// Presentation mode state (`:present`).
+#[derive(Debug, Clone)]
+pub struct Presentation {
+    /// Index of the bookmark on screen, if one has been shown.
+    pub slide: Option<usize>,
+    /// Advance to the next bookmark after this long.
+    pub auto_advance: Option<Duration>,
+    /// When the current bookmark was shown.
+    pub shown_at: Instant,
 }
 
 /// Split screen mode.
@@ -146,6 +309,503 @@ pub enum ActivePane {
     Secondary,
 }
 
+/// Most times a counted edit (`10x`, `5I`) is repeated, so a mistyped count
+/// can't hang the editor.
+const MAX_REPEAT: usize = 10_000;
+
+/// Positions kept in the jump list.
+const MAX_JUMPS: usize = 100;
+
+/// How often `:cluster <N>` sends the clusters so far to the `:clusters` panel.
+const CLUSTER_PROGRESS_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Commands `--preview` can show the result of before they change anything
+/// (besides `:s`, `:%s` and `:confidence`/`:track mask`).
+const PREVIEW_COMMANDS: &[&str] = &[
+    "trim",
+    "trimleft",
+    "trimright",
+    "filter-cols",
+    "colset",
+    "dedup",
+    "mask",
+    "fixann",
+    "fixcoords",
+    "upper",
+    "uppercase",
+    "lower",
+    "lowercase",
+    "t2u",
+    "u2t",
+    "pad5",
+    "pad3",
+    "truncate5",
+    "truncate3",
+    "throwcol",
+    "mergecol",
+    "mergecol!",
+    "splitcol",
+];
+
+/// `:set` options listed by `:settings`, with what each controls.
+pub const SETTING_KEYS: [(&str, &str); 21] = [
+    ("gap", "Gap character inserted by edits"),
+    ("endgap", "Gap before the first and after the last residue"),
+    ("gapchars", "Every character read as a gap"),
+    ("force-delete", "x deletes residues without asking (on|off)"),
+    (
+        "rf-snap",
+        "I inserts next to the RF match column ahead (on|off)",
+    ),
+    ("mask", "What :mask pp<N does to residues (lower|gap)"),
+    ("search", "How / reads patterns (literal|iupac|regex)"),
+    ("search-gaps", "/ skips or matches gaps (skip|exact)"),
+    ("format", "Save format (auto = from the file extension)"),
+    ("style", "Stockholm layout (pfam|stockholm)"),
+    (
+        "wrap",
+        "Stockholm block width (off = one line per sequence)",
+    ),
+    ("eol", "Line endings when saving (lf|crlf)"),
+    ("backup", "Keep the previous file as <file>~ (on|off)"),
+    ("readonly", "Refuse all edits (on|off; not undoable)"),
+    ("hits.sfetch", "Program :hits align fetches hits with"),
+    (
+        "hits.aligner",
+        "Program :hits align aligns hits with (cmalign|hmmalign)",
+    ),
+    ("alifold.program", "RNAalifold program :alifold runs"),
+    (
+        "alifold.ribosum",
+        "RNAalifold scores covariation with RIBOSUM (on|off)",
+    ),
+    (
+        "alifold.mis",
+        "RNAalifold adds the MIS as #=GC MIS (on|off)",
+    ),
+    ("paranoid", "Check row widths after every edit (on|off)"),
+    ("hover", "Show cell info under the mouse pointer (on|off)"),
+];
+
+/// Options changed with `:set`. `:set` saves them in the undo history, so `u`
+/// reverts a setting like an edit.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Settings {
+    pub gap_char: char,
+    pub gap_chars: Vec<char>,
+    pub end_gap_char: char,
+    pub save_format: Option<Format>,
+    pub write_options: WriteOptions,
+    pub force_delete: bool,
+    pub rf_snap: bool,
+    pub paranoid: bool,
+    pub hover: bool,
+    pub mask_style: MaskStyle,
+    pub alifold: alifold::Options,
+    pub search_kind: SearchKind,
+    pub search_skip_gaps: bool,
+    pub hit_programs: hits::Programs,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            gap_char: '.',
+            gap_chars: DEFAULT_GAP_CHARS.to_vec(),
+            end_gap_char: '~',
+            save_format: None,
+            write_options: WriteOptions::default(),
+            force_delete: false,
+            rf_snap: false,
+            paranoid: false,
+            hover: false,
+            mask_style: MaskStyle::default(),
+            alifold: alifold::Options::default(),
+            search_kind: SearchKind::default(),
+            search_skip_gaps: true,
+            hit_programs: hits::Programs::default(),
+        }
+    }
+}
+
+impl Settings {
+    /// Value of a `:set` key as it would be typed (None for unknown keys and
+    /// `readonly`, which is not kept here).
+    pub fn value(&self, key: &str) -> Option<String> {
+        let on_off = |on: bool| if on { "on" } else { "off" }.to_string();
+        Some(match key {
+            "gap" => self.gap_char.to_string(),
+            "endgap" => self.end_gap_char.to_string(),
+            "gapchars" => self.gap_chars.iter().collect(),
+            "force-delete" => on_off(self.force_delete),
+            "rf-snap" => on_off(self.rf_snap),
+            "paranoid" => on_off(self.paranoid),
+            "hover" => on_off(self.hover),
+            "mask" => match self.mask_style {
+                MaskStyle::Lowercase => "lower".to_string(),
+                MaskStyle::Gap => "gap".to_string(),
+            },
+            "search" => self.search_kind.name().to_string(),
+            "search-gaps" => if self.search_skip_gaps {
+                "skip"
+            } else {
+                "exact"
+            }
+            .to_string(),
+            "format" => self
+                .save_format
+                .map_or_else(|| "auto".to_string(), |f| f.name().to_string()),
+            "style" => match self.write_options.style {
+                StockholmStyle::Stockholm => "stockholm".to_string(),
+                StockholmStyle::Pfam => "pfam".to_string(),
+            },
+            "wrap" => self
+                .write_options
+                .wrap
+                .map_or_else(|| "off".to_string(), |w| w.to_string()),
+            "eol" => self.write_options.line_ending.name().to_ascii_lowercase(),
+            "backup" => on_off(self.write_options.backup),
+            "hits.sfetch" => self.hit_programs.sfetch.clone(),
+            "hits.aligner" => self.hit_programs.aligner.clone(),
+            "alifold.program" => self.alifold.program.clone(),
+            "alifold.ribosum" => on_off(self.alifold.ribosum),
+            "alifold.mis" => on_off(self.alifold.mis),
+            _ => return None,
+        })
+    }
+
+    /// The settings of `other` that differ from these, as `key=value`.
+    pub fn changes(&self, other: &Settings) -> String {
+        SETTING_KEYS
+            .iter()
+            .filter_map(|(key, _)| {
+                let value = other.value(key)?;
+                (self.value(key)? != value).then(|| format!("{key}={value}"))
+            })
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+}
+
+/// State of the `:settings` panel.
+#[derive(Debug, Clone, Default)]
+pub struct SettingsPanel {
+    /// Selected row of `SETTING_KEYS`.
+    pub cursor: usize,
+    /// Value being typed for the selected setting (None = not editing).
+    pub editing: Option<String>,
+}
+
+/// A read-only text report shown in an overlay.
+#[derive(Debug, Clone)]
+pub struct Report {
+    pub title: String,
+    pub lines: Vec<String>,
+}
+
+/// esl-sfetch and the aligner running for `:hits align` on a background thread.
+#[derive(Debug)]
+pub struct HitAlignJob {
+    /// Receives the aligner's Stockholm output once it has finished.
+    pub result: mpsc::Receiver<Result<Vec<u8>, AlignError>>,
+    /// `edits` when it started, to tell whether the alignment changed since.
+    pub edits: usize,
+    /// The command as typed, for the session script.
+    pub command: String,
+}
+
+/// RNAalifold running for `:alifold` on a background thread.
+#[derive(Debug)]
+pub struct AlifoldJob {
+    /// Receives RNAalifold's prediction once it has finished.
+    pub result: mpsc::Receiver<Result<Prediction, AlifoldError>>,
+    /// `edits` when it started, to tell whether the alignment changed since.
+    pub edits: usize,
+    /// Whether the consensus is the MIS, to be added as `#=GC MIS`.
+    pub mis: bool,
+}
+
+/// Sequences being grouped for `:cluster <N>` on a background thread.
+#[derive(Debug)]
+pub struct ClusterJob {
+    /// Receives the clusters as they grow, then the finished result.
+    pub updates: mpsc::Receiver<ClusterUpdate>,
+    /// Set to stop clustering.
+    pub cancel: Arc<AtomicBool>,
+    pub threshold: f64,
+    /// `edits` when it started, to tell whether the alignment changed since.
+    pub edits: usize,
+    /// Clusters so far, for the `:clusters` panel.
+    pub groups: Vec<(usize, Vec<usize>)>,
+    /// Sequences in `groups`.
+    pub clustered: usize,
+}
+
+/// What a [`ClusterJob`] sends back.
+#[derive(Debug)]
+pub enum ClusterUpdate {
+    Progress {
+        groups: Vec<(usize, Vec<usize>)>,
+        clustered: usize,
+    },
+    Done {
+        groups: Vec<(usize, Vec<usize>)>,
+        result: ClusterResult,
+    },
+}
+
+/// Sequences being folded for `:zscore` on a background thread.
+#[derive(Debug)]
+pub struct FoldJob {
+    /// Receives the scores by sequence ID once every sequence is folded.
+    pub results: mpsc::Receiver<Vec<(String, Option<FoldZScore>)>>,
+    /// Set to stop folding further sequences.
+    pub cancel: Arc<AtomicBool>,
+    pub shuffles: usize,
+}
+
+/// Residue cells drawn in one strip of the last frame, for finding the cell
+/// under the mouse.
+#[derive(Debug, Clone)]
+pub struct DrawnCells {
+    pub x: u16,
+    pub y: u16,
+    /// Screen columns each residue takes.
+    pub cell_width: u16,
+    /// Display row drawn on line `y`.
+    pub first_row: usize,
+    pub rows: usize,
+    /// Alignment column drawn in each cell, left to right.
+    pub cols: Vec<usize>,
+}
+
+/// The cell shown in the hover popup, and where the mouse was.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Hover {
+    pub x: u16,
+    pub y: u16,
+    pub display_row: usize,
+    pub col: usize,
+}
+
+/// What a `:grepall` match was found in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum QuickfixKind {
+    /// Residues of a sequence (gaps ignored).
+    Sequence,
+    /// A sequence ID.
+    Id,
+    /// A `#=GF` line.
+    FileAnnotation,
+}
+
+/// A location in the quickfix list, filled by `:grepall`.
+#[derive(Debug, Clone)]
+pub struct QuickfixEntry {
+    /// Index into the argument list (None = the open alignment, with no list).
+    pub file: Option<usize>,
+    /// Alignment within a multi-record file (None for a single-alignment file).
+    pub record: Option<usize>,
+    pub kind: QuickfixKind,
+    /// Actual sequence row, or `#=GF` line index.
+    pub row: usize,
+    pub col: usize,
+    /// Text shown in the list.
+    pub text: String,
+}
+
+/// Distinct residues of one column with the sequences carrying them (`:pileup`).
+#[derive(Debug, Clone)]
+pub struct Pileup {
+    pub col: usize,
+    /// (character, display rows, sequence count), most common first.
+    pub groups: Vec<(char, Vec<usize>, usize)>,
+    /// Whether each group lists its sequence IDs.
+    pub expanded: Vec<bool>,
+    /// Selected line, indexing `lines()`.
+    pub cursor: usize,
+}
+
+impl Pileup {
+    /// Lines shown, as (group, member): None for a group's own line, otherwise
+    /// the index of one of its rows when the group is expanded.
+    pub fn lines(&self) -> Vec<(usize, Option<usize>)> {
+        let mut lines = Vec::new();
+        for (i, (_, rows, _)) in self.groups.iter().enumerate() {
+            lines.push((i, None));
+            if self.expanded[i] {
+                lines.extend((0..rows.len()).map(|m| (i, Some(m))));
+            }
+        }
+        lines
+    }
+}
+
+/// One helix with each sequence's 5' strand over its reversed 3' strand, so
+/// that every base pair is a column (`:helix`).
+#[derive(Debug, Clone)]
+pub struct HelixView {
+    pub helix: usize,
+    /// First display row shown.
+    pub top: usize,
+}
+
+/// Criterion restricting which sequences are displayed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RowFilter {
+    /// Only sequences carrying a curation flag.
+    Flagged,
+}
+
+/// Contents of a register, put back with `p`/`P`.
+#[derive(Debug, Clone)]
+pub enum Register {
+    /// Rectangular block of characters (visual `y`).
+    Block(Vec<Vec<char>>),
+    /// Whole sequences with their annotations (`yy`, `dd`), in alignment order.
+    Sequences(Vec<DetachedSequence>),
+}
+
+impl Register {
+    /// One-line description for `:registers`.
+    fn describe(&self) -> String {
+        match self {
+            Register::Block(block) => {
+                let cols = block.first().map_or(0, Vec::len);
+                let first: String = block
+                    .first()
+                    .map(|row| row.iter().collect())
+                    .unwrap_or_default();
+                format!("{}x{cols} block  {first}", block.len())
+            }
+            Register::Sequences(sequences) => {
+                let ids: Vec<&str> = sequences.iter().map(|d| d.sequence.id.as_str()).collect();
+                format!("{} sequence(s)  {}", sequences.len(), ids.join(" "))
+            }
+        }
+    }
+}
+
+/// A block picked up in visual mode, waiting to be dropped at the cursor column.
+#[derive(Debug, Clone)]
+pub struct BlockMove {
+    /// Actual rows of the block.
+    pub rows: Vec<usize>,
+    /// Columns the block occupies.
+    pub cols: std::ops::Range<usize>,
+}
+
+/// Destructive action waiting for a yes/no answer.
+#[derive(Debug, Clone)]
+pub enum Confirm {
+    /// Delete the residue at an actual row and column.
+    DeleteResidue { row: usize, col: usize },
+    /// Open an unaligned FASTA file, padding its sequences with gaps to the
+    /// longest one.
+    PadAlignment {
+        alignment: Box<Alignment>,
+        path: PathBuf,
+    },
+    /// Run a command shown by `--preview`, with the visual selection it was
+    /// previewed on.
+    ApplyCommand {
+        command: String,
+        selection: Option<(usize, usize)>,
+    },
+}
+
+/// What [`App::load_file`] did with a file it could read.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Loaded {
+    /// The alignment is open.
+    Opened,
+    /// Nothing is open yet: the file is an unaligned FASTA, and the user is
+    /// being asked whether to pad it ([`Confirm::PadAlignment`]).
+    PadPrompt,
+}
+
+/// Keys typed since the last complete keyboard command, for the session script.
+#[derive(Debug, Clone)]
+pub struct KeyRecord {
+    /// The keys, as `:normal` takes them.
+    pub keys: String,
+    /// False once a key without a name in that notation was typed.
+    pub named: bool,
+    /// Sequence ID and 1-based column of the cursor at the first key.
+    pub position: Option<(String, usize)>,
+    /// `edits` and the script length at the first key.
+    pub edits: usize,
+    pub logged: usize,
+}
+
+/// Column edit waiting on per-sequence confirmation (`:mergecol`, `:splitcol`).
+#[derive(Debug, Clone)]
+pub struct ColumnPrompt {
+    pub op: ColumnOp,
+    /// Actual rows still to confirm, in order.
+    pub pending: Vec<usize>,
+    /// Actual rows confirmed so far.
+    pub accepted: Vec<usize>,
+}
+
+/// `:s` replacement waiting on confirmation of each match (the `c` flag).
+#[derive(Debug, Clone)]
+pub struct SubstitutePrompt {
+    pub sub: Substitution,
+    /// Matches (actual row, start column) still to confirm, in order.
+    pub pending: Vec<(usize, usize)>,
+    /// Matches confirmed so far.
+    pub accepted: Vec<(usize, usize)>,
+}
+
+/// Column edit applied once a [`ColumnPrompt`] is answered.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ColumnOp {
+    /// Move residues from column `from` into the adjacent column `into`;
+    /// `conflicts` rows have residues in both and stay put.
+    Merge {
+        from: usize,
+        into: usize,
+        conflicts: usize,
+    },
+    /// Move residues from column `col` into a new column on the given side.
+    Split {
+        col: usize,
+        direction: ShiftDirection,
+    },
+}
+
+/// What `:sort` orders the sequences by.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SortKey {
+    /// Sequence ID, ignoring case.
+    Name,
+    /// Number of residues.
+    Length,
+    /// Fraction of the row that is gaps.
+    Gaps,
+    /// Identity to the `:ref` sequence (or the consensus).
+    Identity,
+}
+
+impl SortKey {
+    pub fn from_str(s: &str) -> Option<Self> {
+        match s.to_lowercase().as_str() {
+            "name" => Some(SortKey::Name),
+            "length" | "len" => Some(SortKey::Length),
+            "gaps" | "gap" => Some(SortKey::Gaps),
+            "identity" | "ident" => Some(SortKey::Identity),
+            _ => None,
+        }
+    }
+
+    /// Whether the sort runs largest first unless `asc` or `desc` is given:
+    /// the longest and most similar sequences come first.
+    pub fn descending_by_default(self) -> bool {
+        matches!(self, SortKey::Length | SortKey::Identity)
+    }
+}
+
 /// Terminal color theme (detected at startup).
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
 pub enum TerminalTheme {
@@ -163,6 +823,19 @@ pub struct App {
     pub alignment: Alignment,
     /// File path (if loaded from file).
     pub file_path: Option<PathBuf>,
+    /// Files given on the command line, stepped through with `:n`/`:N`.
+    pub(crate) arg_files: Vec<PathBuf>,
+    /// Index of the current file in `arg_files`.
+    pub(crate) arg_index: usize,
+    /// Format to read `arg_files` as (`--format`; None = detect).
+    pub(crate) arg_format: Option<Format>,
+    /// Every alignment of a multi-record file (empty for a single alignment). The
+    /// open one lives in `alignment`; its slot here is a placeholder.
+    pub(crate) records: Vec<Alignment>,
+    /// Index of the open alignment in `records`.
+    pub(crate) record_index: usize,
+    /// Selected row in the alignment picker.
+    pub(crate) record_cursor: usize,
     /// Structure cache.
     pub structure_cache: StructureCache,
 
@@ -171,8 +844,50 @@ pub struct App {
     pub gap_char: char,
     /// Characters considered as gaps.
     pub gap_chars: Vec<char>,
+    /// Gap character for positions before the first or after the last residue.
+    pub end_gap_char: char,
+    /// Format used when saving (None = from the file extension).
+    pub(crate) save_format: Option<Format>,
+    /// Layout options for saved files (e.g. Stockholm line wrapping).
+    pub(crate) write_options: WriteOptions,
+    /// Delete residues with `x` without asking first.
+    pub(crate) force_delete: bool,
+    /// Move `I` to the insert-state boundary given by the #=GC RF line.
+    pub(crate) rf_snap: bool,
+    /// Check row widths after every edit in release builds too.
+    pub(crate) paranoid: bool,
+    /// Rows found the wrong width by the last edits, reported once the key
+    /// that made them is handled.
+    pub(crate) integrity_problems: Vec<String>,
+    /// Inside `repeat_edit`: whether an edit has been made, for a single
+    /// `mark_modified` once the repeats are done.
+    repeat_modified: Option<bool>,
+    /// Editing commands run on this alignment, for `:w session.aform`.
+    pub(crate) script_log: Vec<script::Entry>,
+    /// Keys of a keyboard command still being typed.
+    pub(crate) key_record: Option<KeyRecord>,
+    /// Rows the alignment view showed at the last draw, for paging in `:normal`.
+    pub(crate) page_rows: usize,
+    /// Refuse every edit, for browsing alignments safely (`aform -R`).
+    pub(crate) read_only: bool,
+    /// What `:mask pp<N` does to low-confidence residues.
+    pub(crate) mask_style: MaskStyle,
+    /// How `/` reads a pattern: as typed, with IUPAC codes or as a regex.
+    pub(crate) search_kind: SearchKind,
+    /// Whether `/` looks past gaps inside a motif, or matches the aligned text.
+    pub(crate) search_skip_gaps: bool,
+    /// Keep #=GC/#=GR rows padded/truncated to the alignment width after edits
+    /// (off while a loaded file's rows are left mismatched).
+    pub(crate) lock_annotation_widths: bool,
     /// Color scheme.
     pub color_scheme: ColorScheme,
+    /// Second coloring layer stacked on the scheme (`:color base+cons`).
+    pub color_overlay: Option<ColorOverlay>,
+    /// Limit coloring to a block or to helices (`:focus`).
+    pub color_focus: Option<ColorFocus>,
+    /// Columns kept at the left of the alignment while scrolling (`:pin`),
+    /// sorted.
+    pub pinned_columns: Vec<usize>,
     /// Show help overlay.
     pub show_help: bool,
     /// Show position ruler at top.
@@ -181,8 +896,10 @@ pub struct App {
     pub show_row_numbers: bool,
     /// Show short IDs (strip coordinate suffix like /10000-20000).
     pub show_short_ids: bool,
-    /// Reference sequence index for compensatory coloring.
+    /// Reference sequence index for compensatory coloring and difference motions.
     pub reference_seq: usize,
+    /// Difference motions compare against the consensus instead of the reference.
+    pub compare_to_consensus: bool,
     /// Split screen mode (None = single pane).
     pub split_mode: Option<SplitMode>,
     /// Which pane is active in split mode.
@@ -193,10 +910,22 @@ pub struct App {
     pub(crate) command_buffer: String,
     /// Should quit.
     pub(crate) should_quit: bool,
+    /// File to write the next rendered frame to (`:screenshot`).
+    pub(crate) screenshot_path: Option<PathBuf>,
+    /// Hand the terminal back to the shell after this frame (Ctrl-Z).
+    pub(crate) suspend_requested: bool,
+    /// Session log file (`aform --log`), shown by `:log`.
+    pub(crate) log_path: Option<PathBuf>,
 
     // === Internal state (crate-visible for impl App blocks) ===
     /// Whether the alignment has been modified.
     pub(crate) modified: bool,
+    /// Edits made since starting, to tell when cached statistics are stale
+    /// and which commands changed the alignment.
+    pub(crate) edits: usize,
+    /// Statistics of the cursor column for the status bar, with the `edits`
+    /// and column they were computed for.
+    pub(crate) column_stats_cache: RefCell<Option<(usize, usize, ColumnStats)>>,
     /// Current cursor row (sequence index).
     pub(crate) cursor_row: usize,
     /// Current cursor column.
@@ -215,6 +944,8 @@ pub struct App {
     pub(crate) completion: Option<CompletionState>,
     /// Status message.
     pub(crate) status_message: Option<String>,
+    /// Failure of the command being run, set by `set_error`.
+    command_error: Option<String>,
     /// Undo/redo history.
     pub(crate) history: History,
     /// Numeric count buffer for vim-style count prefixes (e.g., 50|).
@@ -227,8 +958,21 @@ pub struct App {
     // === Visual selection state ===
     /// Selection anchor point (row, col) - set when entering visual mode.
     pub(crate) selection_anchor: Option<(usize, usize)>,
-    /// Clipboard for yanked block (rectangular selection).
-    pub(crate) clipboard: Option<Vec<Vec<char>>>,
+    /// The selection was made with `Ctrl-v`: gap column edits apply to its
+    /// sequences only.
+    pub(crate) block_selection: bool,
+    /// Edit `.` repeats.
+    pub(crate) last_edit: Option<LastEdit>,
+    /// Unnamed register: the last yanked or deleted block or sequences.
+    pub(crate) register: Option<Register>,
+    /// Named registers a-z, kept across files.
+    pub(crate) named_registers: BTreeMap<char, Register>,
+    /// Register chosen with `"a` for the next yank, delete or put.
+    pub(crate) pending_register: Option<char>,
+    /// Sequence added with `:new-seq` whose residues insert mode is typing.
+    pub(crate) typing_sequence: Option<String>,
+    /// Block being moved (visual `m`), dropped at the cursor column.
+    pub(crate) block_move: Option<BlockMove>,
 
     // === Clustering state ===
     /// Cluster-based display ordering (indices into alignment.sequences).
@@ -252,14 +996,77 @@ pub struct App {
     pub(crate) collapse_identical: bool,
     /// Mapping from display row to (representative_index, all_group_indices).
     pub(crate) collapse_groups: Vec<(usize, Vec<usize>)>,
+    /// Identity threshold for grouping (None = group identical sequences only).
+    pub(crate) identity_threshold: Option<f64>,
+
+    // === Search results ===
+    /// Hits loaded from a cmsearch/nhmmer `--tblout` table, best score first.
+    pub(crate) hits: Vec<Hit>,
+    /// Hits marked for fetching (parallel to `hits`).
+    pub(crate) hit_marks: Vec<bool>,
+    /// Alignment row already covering each hit (parallel to `hits`).
+    pub(crate) hit_members: Vec<Option<usize>>,
+    /// Selected row in the hit viewer.
+    pub(crate) hit_cursor: usize,
+    /// Programs `:hits align` runs (`:set hits.*`).
+    pub(crate) hit_programs: hits::Programs,
+    /// Marked hits still being fetched and aligned by `:hits align`.
+    pub(crate) hit_align_job: Option<HitAlignJob>,
+    /// Locations found by `:grepall`.
+    pub(crate) quickfix: Vec<QuickfixEntry>,
+    /// Selected quickfix entry.
+    pub(crate) quickfix_cursor: usize,
+
+    /// `:cluster <N>` still grouping sequences in the background.
+    pub(crate) cluster_job: Option<ClusterJob>,
+
+    // === Fold analysis ===
+    /// MFE z-scores by sequence ID (from `:zscore`).
+    pub(crate) fold_zscores: HashMap<String, FoldZScore>,
+    /// Show the z-score column in the ID gutter.
+    pub show_zscores: bool,
+    /// `:zscore` folding still running in the background.
+    pub(crate) fold_job: Option<FoldJob>,
+    /// Options `:alifold` runs RNAalifold with (`:set alifold.*`).
+    pub(crate) alifold: alifold::Options,
+    /// RNAalifold still running for `:alifold`.
+    pub(crate) alifold_job: Option<AlifoldJob>,
+
+    // === Row filter state ===
+    /// Active row filter (None = show all sequences).
+    pub(crate) row_filter: Option<RowFilter>,
+    /// Sequence indices passing the active filter, in display order.
+    pub(crate) filtered_rows: Vec<usize>,
 
     // === Annotation bar state ===
     /// Show consensus sequence bar.
     pub show_consensus: bool,
     /// Show conservation bar.
     pub show_conservation_bar: bool,
+    /// Show column confidence bar.
+    pub show_confidence_bar: bool,
+    /// Pairwise-consistency confidence per column (from `:confidence`).
+    pub(crate) column_confidence: Option<Vec<Option<f64>>>,
+    /// Tag of the numeric #=GC track shown as a graph and edited in track mode.
+    pub track: Option<String>,
     /// Show RF (reference) annotation bar.
     pub show_rf_bar: bool,
+    /// Regions saved with `:bookmark`, in the order they were added.
+    pub bookmarks: Vec<Mark>,
+    /// Positions set with `m{a-z}`.
+    pub marks: BTreeMap<char, Mark>,
+    /// Positions jumped away from, oldest first (`Ctrl-o`/`Ctrl-i`).
+    pub(crate) jumps: Vec<Mark>,
+    /// Entry of `jumps` the cursor is on while stepping through them;
+    /// `jumps.len()` when not stepping.
+    pub(crate) jump_index: usize,
+    /// Width and sequence count after each change since loading (`:history`).
+    pub(crate) shape_history: ShapeHistory,
+    /// Large-cell presentation view (`:present`).
+    pub presentation: Option<Presentation>,
+    /// Index of the current tutorial lesson (`--tutor`); past the last one once
+    /// the tutorial is finished.
+    pub tutor: Option<usize>,
     /// Show PP_cons (posterior probability consensus) bar.
     pub show_pp_cons: bool,
     /// Conservation threshold for uppercase in consensus (0.0-1.0).
@@ -268,6 +1075,41 @@ pub struct App {
     // === Info overlay ===
     /// Show file info overlay.
     pub show_info: bool,
+    /// Show popup listing every row's character at the cursor column.
+    pub show_column_popup: bool,
+    /// First row listed in the column popup.
+    pub column_popup_top: usize,
+    /// Show panel listing sequence clusters and their sizes.
+    pub show_clusters: bool,
+    /// Report overlay (None = hidden).
+    pub report: Option<Report>,
+    /// Show the popup for whatever cell the mouse moves over (`:set hover=on`),
+    /// not only on Ctrl-click.
+    pub(crate) hover_on_move: bool,
+    /// Cell info popup under the mouse (None = hidden).
+    pub hover: Option<Hover>,
+    /// Where the last frame drew residues; filled while rendering.
+    pub(crate) drawn_cells: RefCell<Vec<DrawnCells>>,
+    /// Settings panel (None = hidden).
+    pub settings_panel: Option<SettingsPanel>,
+    /// Column pileup view (None = hidden).
+    pub pileup: Option<Pileup>,
+    /// Helix view (None = hidden).
+    pub helix_view: Option<HelixView>,
+    /// Show the search hit viewer.
+    pub show_hits: bool,
+    /// Show the quickfix list.
+    pub show_quickfix: bool,
+    /// Show the alignment picker of a multi-record file.
+    pub show_record_list: bool,
+    /// Command palette (Ctrl-Space; None = closed).
+    pub palette: Option<Palette>,
+    /// Pending `:mergecol`/`:splitcol` confirmation (None = no prompt).
+    pub(crate) column_prompt: Option<ColumnPrompt>,
+    /// Pending `:s///c` confirmation (None = no prompt).
+    pub(crate) substitute_prompt: Option<SubstitutePrompt>,
+    /// Pending yes/no confirmation (None = no prompt).
+    pub(crate) confirm: Option<Confirm>,
 
     // === Sequence type ===
     /// Detected sequence type (RNA, DNA, or Protein).
@@ -285,10 +1127,24 @@ pub struct App {
 
 impl Default for App {
     fn default() -> Self {
+        let settings = Settings::default();
         Self {
             alignment: Alignment::new(),
             file_path: None,
+            arg_files: Vec::new(),
+            arg_index: 0,
+            arg_format: None,
+            records: Vec::new(),
+            record_index: 0,
+            record_cursor: 0,
+            show_record_list: false,
+            palette: None,
+            pileup: None,
+            helix_view: None,
+            settings_panel: None,
             modified: false,
+            edits: 0,
+            column_stats_cache: RefCell::new(None),
             cursor_row: 0,
             cursor_col: 0,
             viewport_row: 0,
@@ -299,24 +1155,54 @@ impl Default for App {
             search: SearchState::new(),
             completion: None,
             status_message: None,
-            gap_char: '.',
-            gap_chars: vec!['.', '-', '_', '~', ':'],
+            command_error: None,
+            gap_char: settings.gap_char,
+            gap_chars: settings.gap_chars,
+            end_gap_char: settings.end_gap_char,
+            save_format: settings.save_format,
+            write_options: settings.write_options,
+            lock_annotation_widths: true,
+            force_delete: settings.force_delete,
+            rf_snap: settings.rf_snap,
+            paranoid: settings.paranoid,
+            integrity_problems: Vec::new(),
+            repeat_modified: None,
+            script_log: Vec::new(),
+            key_record: None,
+            page_rows: 20,
+            read_only: false,
+            mask_style: settings.mask_style,
+            search_kind: settings.search_kind,
+            search_skip_gaps: settings.search_skip_gaps,
             color_scheme: ColorScheme::None,
+            color_overlay: None,
+            color_focus: None,
+            pinned_columns: Vec::new(),
             structure_cache: StructureCache::new(),
             history: History::new(),
             should_quit: false,
+            screenshot_path: None,
+            suspend_requested: false,
+            log_path: None,
             show_help: false,
             show_ruler: true,
             show_row_numbers: true,
             show_short_ids: false,
             reference_seq: 0,
+            compare_to_consensus: false,
             count_buffer: String::new(),
             split_mode: None,
             active_pane: ActivePane::Primary,
             secondary_viewport_row: 0,
             secondary_viewport_col: 0,
             selection_anchor: None,
-            clipboard: None,
+            block_selection: false,
+            last_edit: None,
+            register: None,
+            named_registers: BTreeMap::new(),
+            pending_register: None,
+            typing_sequence: None,
+            block_move: None,
             cluster_order: None,
             cluster_tree: None,
             tree_width: 0,
@@ -326,12 +1212,51 @@ impl Default for App {
             theme: Theme::default(),
             collapse_identical: false,
             collapse_groups: Vec::new(),
+            identity_threshold: None,
+            hits: Vec::new(),
+            hit_marks: Vec::new(),
+            hit_members: Vec::new(),
+            hit_cursor: 0,
+            hit_programs: settings.hit_programs.clone(),
+            hit_align_job: None,
+            quickfix: Vec::new(),
+            quickfix_cursor: 0,
+            show_quickfix: false,
+            cluster_job: None,
+            fold_zscores: HashMap::new(),
+            show_zscores: false,
+            fold_job: None,
+            alifold: settings.alifold.clone(),
+            alifold_job: None,
+            row_filter: None,
+            filtered_rows: Vec::new(),
             show_consensus: false,
             show_conservation_bar: false,
+            show_confidence_bar: false,
+            track: None,
+            bookmarks: Vec::new(),
+            marks: BTreeMap::new(),
+            jumps: Vec::new(),
+            jump_index: 0,
+            shape_history: ShapeHistory::default(),
+            presentation: None,
+            tutor: None,
+            column_confidence: None,
             show_rf_bar: false,
             show_pp_cons: false,
             consensus_threshold: 0.7,
             show_info: false,
+            show_column_popup: false,
+            column_popup_top: 0,
+            show_clusters: false,
+            report: None,
+            hover_on_move: settings.hover,
+            hover: None,
+            drawn_cells: RefCell::new(Vec::new()),
+            show_hits: false,
+            column_prompt: None,
+            substitute_prompt: None,
+            confirm: None,
             sequence_type: SequenceType::RNA,
             highlight_gap_columns: false,
             hide_gap_columns: false,
@@ -347,22 +1272,347 @@ impl App {
     }
 
     /// Load an alignment from a file.
-    pub fn load_file(&mut self, path: &Path) -> Result<(), String> {
-        let alignment = crate::stockholm::parser::parse_file(path)
+    ///
+    /// An unaligned FASTA file (sequences of different lengths) is not loaded yet:
+    /// the user is asked whether to pad it with gaps first.
+    pub fn load_file(&mut self, path: &Path) -> Result<Loaded, String> {
+        self.load_file_as(path, None)
+    }
+
+    /// Load an alignment from a file in `format`, or else the detected format.
+    pub fn load_file_as(&mut self, path: &Path, format: Option<Format>) -> Result<Loaded, String> {
+        let start = Instant::now();
+        let (alignments, line_ending) = match crate::formats::read_file(path, format) {
+            Ok((alignments, read_format, line_ending)) => {
+                tracing::info!(
+                    path = ?path,
+                    format = read_format.name(),
+                    alignments = alignments.len(),
+                    sequences = alignments[0].num_sequences(),
+                    elapsed_ms = start.elapsed().as_millis(),
+                    "read file"
+                );
+                (alignments, line_ending)
+            }
+            Err(FormatError::Fasta(FastaError::InconsistentLengths)) => {
+                tracing::info!(path = ?path, "unaligned FASTA, offering to pad");
+                return self.offer_padding(path);
+            }
+            Err(e) => {
+                tracing::warn!(path = ?path, error = e.to_string().as_str(), "read failed");
+                return Err(format!("Failed to parse file: {e}"));
+            }
+        };
+        self.load_records(alignments, Some(path.to_path_buf()));
+        // Saves keep the file's line endings unless :set eol= says otherwise
+        self.write_options.line_ending = line_ending;
+        if line_ending == LineEnding::Crlf {
+            let message = self.status_message.take().unwrap_or_default();
+            self.set_status(format!("{message} [CRLF]"));
+        }
+        Ok(Loaded::Opened)
+    }
+
+    /// Ask whether to pad the sequences of an unaligned FASTA file into an
+    /// alignment (see [`Confirm::PadAlignment`]).
+    fn offer_padding(&mut self, path: &Path) -> Result<Loaded, String> {
+        let alignment = crate::formats::read_unaligned_fasta(path)
             .map_err(|e| format!("Failed to parse file: {e}"))?;
+        let lengths = alignment.sequences.iter().map(|s| s.len());
+        let width = lengths.clone().max().unwrap_or(0);
+        let short = lengths.filter(|&len| len < width).count();
+        self.set_status(format!(
+            "{}: {short} of {} sequences are shorter than {width} (unaligned FASTA). Pad them with gaps? (y/n)",
+            path.display(),
+            alignment.num_sequences()
+        ));
+        self.confirm = Some(Confirm::PadAlignment {
+            alignment: Box::new(alignment),
+            path: path.to_path_buf(),
+        });
+        Ok(Loaded::PadPrompt)
+    }
+
+    /// Load an unaligned file padded into an alignment, as modified.
+    pub(crate) fn load_padded(&mut self, mut alignment: Alignment, path: PathBuf) {
+        let padded = alignment.pad_to_longest(self.end_gap_char);
+        let width = alignment.width();
+        self.load_records(vec![alignment], Some(path));
+        self.modified = true;
+        let message = self.status_message.take().unwrap_or_default();
+        self.set_status(format!(
+            "{message}; padded {padded} sequences to {width} columns"
+        ));
+    }
+
+    /// Load the alignments of a file (or stdin), showing the first.
+    pub(crate) fn load_records(
+        &mut self,
+        mut alignments: Vec<Alignment>,
+        file_path: Option<PathBuf>,
+    ) {
+        let first = std::mem::take(&mut alignments[0]);
+        self.records = if alignments.len() > 1 {
+            alignments
+        } else {
+            Vec::new()
+        };
+        self.record_index = 0;
+        self.show_record_list = false;
+        self.load_alignment(first, file_path);
+        if self.records.len() > 1 {
+            let message = self.status_message.take().unwrap_or_default();
+            self.set_status(format!(
+                "{message} [alignment 1 of {}; :next-msa, :msa lists]",
+                self.records.len()
+            ));
+        }
+    }
+
+    /// Switch to alignment `index` of a multi-record file, keeping any edits to the
+    /// current one in memory. Undo history does not carry across the switch.
+    pub fn goto_record(&mut self, index: usize) {
+        if self.records.len() < 2 {
+            self.set_error("File has a single alignment");
+            return;
+        }
+        if index >= self.records.len() {
+            self.set_error(format!("No alignment {}", index + 1));
+            return;
+        }
+        self.show_record_list = false;
+        if index == self.record_index {
+            return;
+        }
+        self.records[self.record_index] = std::mem::take(&mut self.alignment);
+        let next = std::mem::take(&mut self.records[index]);
+        self.record_index = index;
+        let modified = self.modified;
+        self.load_alignment(next, self.file_path.clone());
+        self.modified = modified;
+        self.set_status(format!(
+            "Alignment {} of {}: {}",
+            index + 1,
+            self.records.len(),
+            self.record_summary(&self.alignment)
+        ));
+    }
+
+    /// Step through the alignments of a multi-record file (`:next-msa`/`:prev-msa`).
+    fn step_record(&mut self, offset: isize) {
+        match self.record_index.checked_add_signed(offset) {
+            Some(index) if index < self.records.len() => self.goto_record(index),
+            _ if self.records.len() < 2 => self.set_error("File has a single alignment"),
+            _ if offset > 0 => self.set_error("Already at the last alignment"),
+            _ => self.set_error("Already at the first alignment"),
+        }
+    }
+
+    /// Open the alignment picker of a multi-record file (`:msa`).
+    pub fn open_record_list(&mut self) {
+        if self.records.len() < 2 {
+            self.set_error("File has a single alignment");
+            return;
+        }
+        self.record_cursor = self.record_index;
+        self.show_record_list = true;
+    }
+
+    /// Move the alignment picker selection by `delta` rows.
+    pub fn move_record_cursor(&mut self, delta: isize) {
+        let last = self.records.len().saturating_sub(1);
+        self.record_cursor = self.record_cursor.saturating_add_signed(delta).min(last);
+    }
+
+    /// Open the command palette (Ctrl-Space).
+    pub fn open_palette(&mut self) {
+        self.palette = Some(Palette::new());
+    }
+
+    /// Close the palette and run its selected entry: a command runs as if typed,
+    /// one needing an argument opens the command line for it.
+    pub fn run_palette_entry(&mut self) {
+        let Some(entry) = self.palette.take().and_then(|p| p.selected()) else {
+            return;
+        };
+        match entry.action {
+            Action::Command(command) => {
+                self.command_buffer = command.to_string();
+                self.execute_command();
+            }
+            Action::Prompt(text) => {
+                self.enter_command_mode();
+                self.command_buffer = text.to_string();
+            }
+            Action::Run(run) => run(self),
+        }
+    }
+
+    /// Alignment `index` of a multi-record file (the open one comes from `alignment`).
+    pub fn record(&self, index: usize) -> &Alignment {
+        if index == self.record_index {
+            &self.alignment
+        } else {
+            &self.records[index]
+        }
+    }
+
+    /// One-line description of an alignment: `#=GF ID`/`AC`, sequences and width.
+    pub fn record_summary(&self, alignment: &Alignment) -> String {
+        let gf = |tag: &str| {
+            alignment
+                .file_annotations
+                .iter()
+                .find(|a| a.tag == tag)
+                .map(|a| a.value.as_str())
+        };
+        let name = match (gf("ID"), gf("AC")) {
+            (Some(id), Some(ac)) => format!("{id} ({ac})"),
+            (Some(name), None) | (None, Some(name)) => name.to_string(),
+            (None, None) => "(no ID)".to_string(),
+        };
+        format!(
+            "{name}, {} seqs, {} columns",
+            alignment.num_sequences(),
+            alignment.width()
+        )
+    }
 
+    /// Open the files given on the command line, loading the first one.
+    pub fn load_arg_files(&mut self, paths: Vec<PathBuf>) {
+        self.arg_files = paths;
+        self.arg_index = 0;
+        if !self.arg_files.is_empty() {
+            self.goto_arg_file(0, true);
+        }
+    }
+
+    /// Load file `index` of the argument list. Refuses to drop unsaved changes
+    /// unless `force` is set.
+    pub fn goto_arg_file(&mut self, index: usize, force: bool) {
+        if self.modified && !force {
+            self.set_error("No write since last change (add ! to discard changes)");
+            return;
+        }
+        let Some(path) = self.arg_files.get(index).cloned() else {
+            return;
+        };
+        // Move on even if the file fails to load, so one bad file can be skipped
+        self.arg_index = index;
+        if let Err(e) = self.load_file_as(&path, self.arg_format) {
+            self.set_error(format!("Error: {}: {e}", path.display()));
+        }
+        if self.arg_files.len() > 1 {
+            let message = self.status_message.take().unwrap_or_default();
+            self.set_status(format!(
+                "[{}/{}] {message}",
+                index + 1,
+                self.arg_files.len()
+            ));
+        }
+    }
+
+    /// Step through the argument list by `offset` files (`:n` / `:N`).
+    fn step_arg_file(&mut self, offset: isize, force: bool) {
+        if self.arg_files.is_empty() {
+            self.set_error("No file list (open several files from the command line)");
+            return;
+        }
+        match self.arg_index.checked_add_signed(offset) {
+            Some(index) if index < self.arg_files.len() => self.goto_arg_file(index, force),
+            _ if offset > 0 => self.set_error("Already at the last file"),
+            _ => self.set_error("Already at the first file"),
+        }
+    }
+
+    /// Show the argument list with the current file in brackets (`:args`).
+    fn show_arg_files(&mut self) {
+        if self.arg_files.is_empty() {
+            self.set_error("No file list");
+            return;
+        }
+        let list: Vec<String> = self
+            .arg_files
+            .iter()
+            .enumerate()
+            .map(|(i, path)| {
+                if i == self.arg_index {
+                    format!("[{}]", path.display())
+                } else {
+                    path.display().to_string()
+                }
+            })
+            .collect();
+        self.set_status(list.join(" "));
+    }
+
+    /// Load the alignments read from standard input. They have no file path, so
+    /// `:w` needs one.
+    pub fn load_stdin(&mut self, alignments: Vec<Alignment>) {
+        self.load_records(alignments, None);
+    }
+
+    /// Open the tutorial's toy alignment and show the first lesson.
+    pub fn start_tutor(&mut self) {
+        match crate::stockholm::parser::parse(crate::tutor::ALIGNMENT.as_bytes()) {
+            Ok(alignment) => {
+                self.load_records(vec![alignment], None);
+                self.tutor = Some(0);
+                self.set_status("Tutorial: follow the panel on the right");
+            }
+            Err(e) => self.set_error(format!("Error: tutorial alignment: {e}")),
+        }
+    }
+
+    /// Move the tutorial on if the current lesson's check passes (called after
+    /// every key).
+    pub fn check_tutor(&mut self) {
+        let Some(index) = self.tutor else {
+            return;
+        };
+        let Some(lesson) = crate::tutor::LESSONS.get(index) else {
+            return;
+        };
+        if (lesson.done)(self) {
+            self.tutor = Some(index + 1);
+            self.set_status(format!("Lesson {} done: {}", index + 1, lesson.title));
+        }
+    }
+
+    /// Replace the open alignment and reset all per-file state.
+    fn load_alignment(&mut self, alignment: Alignment, file_path: Option<PathBuf>) {
         self.alignment = alignment;
-        self.file_path = Some(path.to_path_buf());
+        self.file_path = file_path;
         self.modified = false;
         self.cursor_row = 0;
         self.cursor_col = 0;
         self.viewport_row = 0;
         self.viewport_col = 0;
         self.history.clear();
+        self.column_stats_cache.take();
 
-        // Reset collapse state
+        // Reset collapse and filter state
         self.collapse_identical = false;
         self.collapse_groups.clear();
+        self.identity_threshold = None;
+        self.cancel_cluster_job();
+        self.clear_row_filter();
+        self.hit_align_job = None;
+        self.fold_zscores.clear();
+        self.show_zscores = false;
+        self.cancel_fold_job();
+        self.alifold_job = None;
+        self.column_confidence = None;
+        self.show_confidence_bar = false;
+        self.color_focus = None;
+        self.pinned_columns.clear();
+        self.bookmarks.clear();
+        self.marks.clear();
+        self.jumps.clear();
+        self.jump_index = 0;
+        self.shape_history.reset(self.shape());
+        self.script_log.clear();
+        self.key_record = None;
 
         // Update structure cache (warn on parse errors)
         if let Some(ss) = self.alignment.ss_cons()
@@ -375,44 +1625,302 @@ impl App {
         self.detect_sequence_type();
         self.precompute_collapse_groups();
 
-        self.set_status(format!(
+        let source = self
+            .file_path
+            .as_ref()
+            .map_or_else(|| "stdin".to_string(), |p| p.display().to_string());
+        let mut message = format!(
             "Loaded {} ({} seqs, {:?}, SS_cons: {})",
-            path.display(),
+            source,
             self.alignment.num_sequences(),
             self.sequence_type,
             self.alignment.ss_cons().is_some()
+        );
+        let mismatches = self.alignment.annotation_length_mismatches();
+        self.lock_annotation_widths = mismatches.is_empty();
+        if let Some((label, len)) = mismatches.first() {
+            message = format!(
+                "Warning: {} annotation rows differ from width {} ({label}: {len}); :fixann pads/truncates them",
+                mismatches.len(),
+                self.alignment.width()
+            );
+        }
+        self.set_status(message);
+    }
+
+    /// Pad or truncate #=GC/#=GR rows to the alignment width and keep them there.
+    pub fn fix_annotation_lengths(&mut self) {
+        self.lock_annotation_widths = true;
+        if self.alignment.annotation_length_mismatches().is_empty() {
+            self.set_status("Annotation rows already match the alignment width");
+            return;
+        }
+        if !self.begin_edit() {
+            return;
+        }
+        let changed = self.alignment.fit_annotations_to_width();
+        self.mark_modified();
+        self.update_structure_cache();
+        self.set_status(format!(
+            "Fitted {changed} annotation rows to width {}",
+            self.alignment.width()
         ));
-        Ok(())
     }
 
     /// Save the alignment to a file.
     pub fn save_file(&mut self) -> Result<(), String> {
+        if self.read_only {
+            return Err("Read-only: not saved (:set noreadonly, or :w <path> for a copy)".into());
+        }
         let path = self.file_path.as_ref().ok_or("No file path set")?;
-        crate::stockholm::writer::write_file(&self.alignment, path)
-            .map_err(|e| format!("Failed to save file: {e}"))?;
+        let format = self.write_alignment_file(path)?;
         self.modified = false;
-        self.set_status(format!("Saved {}", path.display()));
+        let message = self.saved_message(path, format);
+        self.set_status(message);
         Ok(())
     }
 
     /// Save the alignment to a new file.
     pub fn save_file_as(&mut self, path: PathBuf) -> Result<(), String> {
-        crate::stockholm::writer::write_file(&self.alignment, &path)
-            .map_err(|e| format!("Failed to save file: {e}"))?;
-        self.file_path = Some(path.clone());
+        let format = self.write_alignment_file(&path)?;
         self.modified = false;
-        self.set_status(format!("Saved {}", path.display()));
+        let message = self.saved_message(&path, format);
+        self.file_path = Some(path);
+        self.set_status(message);
         Ok(())
     }
 
-    /// Set a status message.
-    pub fn set_status(&mut self, message: impl Into<String>) {
-        self.status_message = Some(message.into());
-    }
-
-    /// Clear the status message.
-    pub fn clear_status(&mut self) {
-        self.status_message = None;
+    /// Write the alignment (or every alignment of a multi-record file) to `path`,
+    /// logging the outcome.
+    fn write_alignment_file(&self, path: &Path) -> Result<Format, String> {
+        let start = Instant::now();
+        let result = self.write_alignment_records(path);
+        match &result {
+            Ok(format) => tracing::info!(
+                path = ?path,
+                format = format.name(),
+                sequences = self.alignment.num_sequences(),
+                elapsed_ms = start.elapsed().as_millis(),
+                "wrote file"
+            ),
+            Err(e) => tracing::warn!(path = ?path, error = e.as_str(), "write failed"),
+        }
+        result
+    }
+
+    fn write_alignment_records(&self, path: &Path) -> Result<Format, String> {
+        if self.records.len() < 2 {
+            return crate::formats::write_file(
+                &self.alignment,
+                path,
+                self.save_format,
+                &self.write_options,
+            )
+            .map_err(|e| format!("Failed to save file: {e}"));
+        }
+        let format = self
+            .save_format
+            .or_else(|| Format::from_path(path))
+            .unwrap_or(Format::Stockholm);
+        if format != Format::Stockholm {
+            return Err(format!(
+                "{} holds one alignment; save this multi-alignment file as Stockholm (or :export one)",
+                format.name()
+            ));
+        }
+        let alignments: Vec<&Alignment> = (0..self.records.len()).map(|i| self.record(i)).collect();
+        crate::formats::write_records_file(&alignments, path, &self.write_options)
+            .map_err(|e| format!("Failed to save file: {e}"))?;
+        Ok(format)
+    }
+
+    /// The alignment as drawn for an HTML, SVG or ANSI export, or None (showing
+    /// why) if it is too large.
+    fn export_figure(&mut self) -> Option<Buffer> {
+        match crate::ui::render_alignment_buffer(self, None) {
+            Ok(buffer) => Some(buffer),
+            Err(e) => {
+                self.set_error(format!("Cannot export: {e}"));
+                None
+            }
+        }
+    }
+
+    /// Write the alignment in another format, leaving the current file unchanged.
+    pub fn export_alignment(&mut self, format: &str, path: &Path) {
+        let (name, result) = if format.eq_ignore_ascii_case("nexus") {
+            let result = crate::formats::write_nexus_file(
+                &self.alignment,
+                self.sequence_type,
+                &self.write_options,
+                path,
+            );
+            ("NEXUS", result)
+        } else if format.eq_ignore_ascii_case("json") {
+            (
+                "JSON",
+                crate::formats::write_json_file(&self.alignment, path),
+            )
+        } else if format.eq_ignore_ascii_case("dbn") {
+            let Some(ss) = self.alignment.ss_cons() else {
+                self.set_error("No SS_cons to export");
+                return;
+            };
+            let (sequence, structure) =
+                match crate::formats::dbn::dot_bracket(&self.alignment, ss, &self.gap_chars) {
+                    Ok(consensus) => consensus,
+                    Err(e) => {
+                        self.set_error(format!("Cannot export SS_cons: {e}"));
+                        return;
+                    }
+                };
+            let name = self
+                .alignment
+                .get_file_annotation("ID")
+                .map(str::to_string)
+                .or_else(|| {
+                    let stem = self.file_path.as_ref()?.file_stem()?;
+                    Some(stem.to_string_lossy().into_owned())
+                })
+                .unwrap_or_else(|| "consensus".to_string());
+            let result = crate::formats::write_dbn_file(&name, &sequence, &structure, path);
+            ("dot-bracket", result)
+        } else if format.eq_ignore_ascii_case("html") {
+            let Some(buffer) = self.export_figure() else {
+                return;
+            };
+            let title = self
+                .file_path
+                .as_ref()
+                .map_or_else(|| "alignment".to_string(), |p| p.display().to_string());
+            let dark = self.terminal_theme == TerminalTheme::Dark;
+            let result = crate::screenshot::write_html(&buffer, &title, dark, path);
+            ("HTML", result)
+        } else if format.eq_ignore_ascii_case("svg") {
+            let Some(buffer) = self.export_figure() else {
+                return;
+            };
+            let dark = self.terminal_theme == TerminalTheme::Dark;
+            ("SVG", crate::screenshot::write_svg(&buffer, dark, path))
+        } else if format.eq_ignore_ascii_case("ansi") {
+            let Some(buffer) = self.export_figure() else {
+                return;
+            };
+            ("ANSI text", crate::screenshot::write_ansi(&buffer, path))
+        } else if let Some(format) = Format::from_name(format) {
+            let result = crate::formats::write_file(
+                &self.alignment,
+                path,
+                Some(format),
+                &self.write_options,
+            );
+            (format.name(), result.map(|_| ()))
+        } else {
+            self.set_error(format!("Unknown export format: {format}"));
+            return;
+        };
+        match result {
+            Ok(()) => self.set_status(format!("Exported {name} to {}", path.display())),
+            Err(e) => self.set_error(format!("Failed to export: {e}")),
+        }
+    }
+
+    /// Write the visual selection (its sequences and columns) as a new alignment,
+    /// renumbering `/start-end` IDs and unpairing SS_cons brackets cut in half.
+    pub fn export_region(&mut self, path: &Path) {
+        let Some((_, min_col, _, max_col)) = self.get_selection_bounds() else {
+            self.set_error("No selection (select a block with v, then :export region <path>)");
+            return;
+        };
+        let rows = self.target_rows();
+        self.write_region(path, &rows, min_col, max_col);
+    }
+
+    /// Write the columns of the visual selection, in every sequence, as a new
+    /// alignment (`:export columns`).
+    pub fn export_columns(&mut self, path: &Path) {
+        let Some((_, min_col, _, max_col)) = self.get_selection_bounds() else {
+            self.set_error("No selection (select columns with v, then :export columns <path>)");
+            return;
+        };
+        let rows: Vec<usize> = (0..self.alignment.num_sequences()).collect();
+        self.write_region(path, &rows, min_col, max_col);
+    }
+
+    /// Write sequences `rows` (actual rows) cut to columns `min_col..=max_col`.
+    fn write_region(&mut self, path: &Path, rows: &[usize], min_col: usize, max_col: usize) {
+        let mut region = self
+            .alignment
+            .select_region(rows, min_col..max_col + 1, &self.gap_chars);
+
+        let cut = self.cut_pairs(min_col, max_col);
+        if let Some(ss) = region.ss_cons_mut()
+            && !cut.is_empty()
+        {
+            let mut chars: Vec<char> = ss.chars().collect();
+            for col in cut {
+                if let Some(c) = chars.get_mut(col - min_col) {
+                    *c = '.';
+                }
+            }
+            *ss = chars.into_iter().collect();
+        }
+
+        match crate::formats::write_file(&region, path, self.save_format, &self.write_options) {
+            Ok(_) => self.set_status(format!(
+                "Wrote region ({} seqs x {} columns) to {}",
+                region.num_sequences(),
+                region.width(),
+                path.display()
+            )),
+            Err(e) => self.set_error(format!("Failed to write region: {e}")),
+        }
+    }
+
+    /// Columns from `min_col` to `max_col` whose base pair partner lies outside
+    /// that range.
+    pub(crate) fn cut_pairs(&self, min_col: usize, max_col: usize) -> Vec<usize> {
+        (min_col..=max_col)
+            .filter(|&col| {
+                self.structure_cache
+                    .get_pair(col)
+                    .is_some_and(|partner| partner < min_col || partner > max_col)
+            })
+            .collect()
+    }
+
+    /// Status message after saving, warning when the format drops annotations.
+    fn saved_message(&self, path: &Path, format: Format) -> String {
+        let a = &self.alignment;
+        let has_annotations = !a.file_annotations.is_empty()
+            || !a.column_annotations.is_empty()
+            || !a.residue_annotations.is_empty();
+        if has_annotations && !format.keeps_annotations() {
+            format!(
+                "Saved {} as {} (#=GF/#=GC/#=GR annotations not written)",
+                path.display(),
+                format.name()
+            )
+        } else {
+            format!("Saved {}", path.display())
+        }
+    }
+
+    /// Set a status message.
+    pub fn set_status(&mut self, message: impl Into<String>) {
+        self.status_message = Some(message.into());
+    }
+
+    /// Show why a command failed; `run_command` returns it as its error.
+    pub fn set_error(&mut self, message: impl Into<String>) {
+        let message = message.into();
+        self.command_error = Some(message.clone());
+        self.status_message = Some(message);
+    }
+
+    /// Clear the status message.
+    pub fn clear_status(&mut self) {
+        self.status_message = None;
     }
 
     /// Get the current character under the cursor.
@@ -508,6 +2016,364 @@ impl App {
         }
     }
 
+    /// Jump to a base pair given as `<helix>:<n>` (optionally `P<helix>:<n>`, as in
+    /// papers): the nth pair of the helix counted from its outer end, both 1-based.
+    /// A bare helix number goes to its outermost pair.
+    pub fn goto_helix_pair(&mut self, spec: &str) {
+        let count = self.structure_cache.num_helices();
+        if count == 0 {
+            self.set_error("No helices in SS_cons");
+            return;
+        }
+        let spec = spec.trim_start_matches(['P', 'p']);
+        let (helix, index) = spec.split_once(':').unwrap_or((spec, "1"));
+        let helix = match helix.parse::<usize>() {
+            Ok(h) if (1..=count).contains(&h) => h - 1,
+            _ => {
+                self.set_error(format!("Invalid helix: {helix} (1-{count})"));
+                return;
+            }
+        };
+        let len = self.structure_cache.helix_len(helix);
+        let pair = index
+            .parse::<usize>()
+            .ok()
+            .and_then(|i| i.checked_sub(1))
+            .and_then(|i| self.structure_cache.helix_pair(helix, i));
+        let Some((left, right)) = pair else {
+            self.set_error(format!(
+                "Invalid pair: {index} (helix {} has {len})",
+                helix + 1
+            ));
+            return;
+        };
+        self.cursor_col = left;
+        self.set_status(format!(
+            "P{}:{index} of {len}: columns {}-{}",
+            helix + 1,
+            left + 1,
+            right + 1
+        ));
+    }
+
+    /// Show the session log (`aform --log`), newest entries first.
+    pub fn log_report(&mut self) {
+        let Some(path) = self.log_path.clone() else {
+            self.set_error("Logging is off (start aform with --log)");
+            return;
+        };
+        match crate::logging::read_recent(&path) {
+            Ok(lines) => {
+                self.report = Some(Report {
+                    title: format!("Session log {} (newest first)", path.display()),
+                    lines,
+                });
+            }
+            Err(e) => self.set_error(format!("Cannot read {}: {e}", path.display())),
+        }
+    }
+
+    /// Current width and sequence count of the alignment.
+    fn shape(&self) -> Shape {
+        Shape {
+            width: self.alignment.width(),
+            sequences: self.alignment.num_sequences(),
+        }
+    }
+
+    /// Note the alignment's size after a key is handled, for `:history`.
+    pub fn record_shape(&mut self) {
+        let shape = self.shape();
+        self.shape_history.record(shape);
+    }
+
+    /// Graph the alignment width and sequence count since loading (`:history`).
+    pub fn shape_history_report(&mut self) {
+        self.record_shape();
+        if self.shape_history.changes() == 0 {
+            self.set_status("No width or sequence count changes since loading");
+            return;
+        }
+        self.report = Some(Report {
+            title: "Alignment size this session".to_string(),
+            lines: self.shape_history.report_lines(),
+        });
+    }
+
+    /// Report sequences whose own structure (`#=GR SS`) disagrees with SS_cons:
+    /// pairs missing from SS_cons, SS_cons pairs they don't form, and pairs with a
+    /// different partner. Worst first; `:color ssdiff` shows the columns.
+    pub fn ss_diff_report(&mut self) {
+        let Some(ss) = self.alignment.ss_cons() else {
+            self.set_error("No SS_cons to compare against");
+            return;
+        };
+        let mut cache = StructureCache::new();
+        if let Err(e) = cache.update(ss) {
+            self.set_status(format!("SS_cons is unbalanced: {e}"));
+            return;
+        }
+
+        let mut with_ss = 0;
+        let mut unbalanced = Vec::new();
+        // (id, extra, missing, partner, first column)
+        let mut disagree: Vec<(&str, usize, usize, usize, usize)> = Vec::new();
+        for seq in &self.alignment.sequences {
+            let Some(own) = self
+                .alignment
+                .residue_annotations
+                .get(&seq.id)
+                .and_then(|anns| anns.iter().find(|ann| ann.tag == "SS"))
+            else {
+                continue;
+            };
+            with_ss += 1;
+            match ss_conflicts(seq.chars(), &own.data, &cache, &self.gap_chars) {
+                Ok(conflicts) if !conflicts.is_empty() => {
+                    let count = |kind| conflicts.iter().filter(|(_, c)| *c == kind).count();
+                    disagree.push((
+                        &seq.id,
+                        count(SsConflict::Extra),
+                        count(SsConflict::Missing),
+                        count(SsConflict::Partner),
+                        conflicts[0].0,
+                    ));
+                }
+                Ok(_) => {}
+                Err(e) => unbalanced.push(format!("{}: {e}", seq.id)),
+            }
+        }
+        if with_ss == 0 {
+            self.set_error("No sequence has #=GR SS");
+            return;
+        }
+        if disagree.is_empty() && unbalanced.is_empty() {
+            self.set_status(format!(
+                "All {with_ss} #=GR SS structures agree with SS_cons"
+            ));
+            return;
+        }
+
+        disagree.sort_by_key(|&(_, extra, missing, partner, _)| {
+            std::cmp::Reverse(extra + missing + partner)
+        });
+        let width = disagree
+            .iter()
+            .map(|(id, ..)| id.len())
+            .max()
+            .unwrap_or(0)
+            .max("sequence".len());
+        let mut lines = vec![
+            format!(
+                "{} of {with_ss} sequences with #=GR SS disagree with SS_cons",
+                disagree.len() + unbalanced.len()
+            ),
+            String::new(),
+            format!("{:width$}  extra  missing  partner  first", "sequence"),
+        ];
+        lines.extend(disagree.iter().map(|(id, extra, missing, partner, first)| {
+            format!(
+                "{id:width$}  {extra:>5}  {missing:>7}  {partner:>7}  {:>5}",
+                first + 1
+            )
+        }));
+        if !unbalanced.is_empty() {
+            lines.push(String::new());
+            lines.push("Unbalanced #=GR SS:".to_string());
+            lines.extend(unbalanced);
+        }
+        self.report = Some(Report {
+            title: "#=GR SS vs SS_cons".to_string(),
+            lines,
+        });
+        self.set_status(
+            ":color ssdiff marks extra (orange), missing (blue) and re-partnered (red) pairs",
+        );
+    }
+
+    /// Compare each sequence's `/start-end` coordinates with its ungapped length:
+    /// a report of the inconsistent ones, or with `path` a TSV of every sequence
+    /// (`id`, `start`, `end`, declared and ungapped length, `consistent`).
+    pub fn coords_report(&mut self, path: Option<&Path>) {
+        // (id, start, end, declared, ungapped) for sequences with coordinates
+        let mut checked: Vec<(&str, usize, usize, usize, usize)> = Vec::new();
+        let mut tsv = vec!["id\tstart\tend\tdeclared\tungapped\tconsistent".to_string()];
+        for seq in &self.alignment.sequences {
+            let ungapped = seq.ungapped_len(&self.gap_chars);
+            match parse_coords(&seq.id).zip(coords_length(&seq.id)) {
+                Some(((_, start, end), declared)) => {
+                    checked.push((&seq.id, start, end, declared, ungapped));
+                    let consistent = if declared == ungapped { "yes" } else { "no" };
+                    tsv.push(format!(
+                        "{}\t{start}\t{end}\t{declared}\t{ungapped}\t{consistent}",
+                        seq.id
+                    ));
+                }
+                None => tsv.push(format!("{}\t\t\t\t{ungapped}\t", seq.id)),
+            }
+        }
+        let wrong: Vec<_> = checked
+            .iter()
+            .filter(|(.., declared, ungapped)| declared != ungapped)
+            .collect();
+
+        if let Some(path) = path {
+            match std::fs::write(path, tsv.join("\n") + "\n") {
+                Ok(()) => self.set_status(format!(
+                    "Wrote coordinates of {} sequences to {} ({} inconsistent)",
+                    self.alignment.num_sequences(),
+                    path.display(),
+                    wrong.len()
+                )),
+                Err(e) => self.set_error(format!("Failed to write file: {e}")),
+            }
+            return;
+        }
+        if checked.is_empty() {
+            self.set_error("No sequence has /start-end coordinates");
+            return;
+        }
+        if wrong.is_empty() {
+            self.set_status(format!(
+                "All {} /start-end coordinates match the ungapped lengths",
+                checked.len()
+            ));
+            return;
+        }
+
+        let width = wrong
+            .iter()
+            .map(|(id, ..)| id.len())
+            .max()
+            .unwrap_or(0)
+            .max("sequence".len());
+        let mut lines = vec![
+            format!(
+                "{} of {} sequences have /start-end coordinates that disagree with their residues",
+                wrong.len(),
+                checked.len()
+            ),
+            String::new(),
+            format!("{:width$}  declared  ungapped  difference", "sequence"),
+        ];
+        lines.extend(wrong.iter().map(|(id, _, _, declared, ungapped)| {
+            let difference = *ungapped as isize - *declared as isize;
+            format!("{id:width$}  {declared:>8}  {ungapped:>8}  {difference:>+10}")
+        }));
+        self.report = Some(Report {
+            title: "Coordinates".to_string(),
+            lines,
+        });
+        self.set_status(":coords <path> writes every sequence's coordinates as TSV");
+    }
+
+    /// Jump along the current sequence to the next (or previous) column where it
+    /// differs from the reference sequence, or from the consensus if so configured.
+    ///
+    /// Residues are compared case-insensitively and all gap characters count as equal.
+    pub fn goto_difference(&mut self, forward: bool) {
+        let actual_row = self.display_to_actual_row(self.cursor_row);
+        if !self.compare_to_consensus && actual_row == self.reference_seq {
+            self.set_error("Current sequence is the reference (:ref consensus to compare)");
+            return;
+        }
+        let Some(seq) = self.alignment.sequences.get(actual_row) else {
+            return;
+        };
+        let reference = self.alignment.sequences.get(self.reference_seq);
+
+        let differs = |col: usize| {
+            let Some(a) = seq.get(col) else {
+                return false;
+            };
+            let b = if self.compare_to_consensus {
+                get_consensus_char(col, &self.alignment, &self.gap_chars)
+            } else {
+                match reference.and_then(|r| r.get(col)) {
+                    Some(b) => b,
+                    None => return false,
+                }
+            };
+            let (a_gap, b_gap) = (self.gap_chars.contains(&a), self.gap_chars.contains(&b));
+            if a_gap || b_gap {
+                a_gap != b_gap
+            } else {
+                !a.eq_ignore_ascii_case(&b)
+            }
+        };
+
+        let width = self.alignment.width();
+        let found = if forward {
+            (self.cursor_col + 1..width).find(|&col| differs(col))
+        } else {
+            (0..self.cursor_col).rev().find(|&col| differs(col))
+        };
+
+        match found {
+            Some(col) => self.cursor_col = col,
+            None => {
+                let target = if self.compare_to_consensus {
+                    "consensus"
+                } else {
+                    "reference"
+                };
+                self.set_status(format!("No more differences from {target}"));
+            }
+        }
+    }
+
+    /// Jump to the next (or previous) base pair where the current sequence has a
+    /// compensatory double change relative to the reference sequence.
+    ///
+    /// Stops on the 5' column of each pair.
+    pub fn goto_compensatory(&mut self, forward: bool) {
+        if self.structure_cache.pairs().is_empty() {
+            self.set_error("No SS_cons base pairs");
+            return;
+        }
+        let actual_row = self.display_to_actual_row(self.cursor_row);
+        if actual_row == self.reference_seq {
+            self.set_error("Current sequence is the reference (:ref to change)");
+            return;
+        }
+        let (Some(query), Some(reference)) = (
+            self.alignment.sequences.get(actual_row),
+            self.alignment.sequences.get(self.reference_seq),
+        ) else {
+            return;
+        };
+        let (query, reference) = (query.data(), reference.data());
+
+        let is_compensatory = |col: usize| {
+            self.structure_cache
+                .get_pair(col)
+                .is_some_and(|pair| col < pair)
+                && analyze_compensatory(
+                    &reference,
+                    &query,
+                    col,
+                    &self.structure_cache,
+                    &self.gap_chars,
+                ) == CompensatoryChange::DoubleCompatible
+        };
+
+        let width = self.alignment.width();
+        let found = if forward {
+            (self.cursor_col + 1..width).find(|&col| is_compensatory(col))
+        } else {
+            (0..self.cursor_col).rev().find(|&col| is_compensatory(col))
+        };
+
+        match found {
+            Some(col) => {
+                let pair = self.structure_cache.get_pair(col).unwrap_or(col);
+                self.cursor_col = col;
+                self.set_status(format!("Compensatory pair {}-{}", col + 1, pair + 1));
+            }
+            None => self.set_status("No more compensatory changes"),
+        }
+    }
+
     /// Jump to a specific column (1-indexed, like vim).
     pub fn goto_column(&mut self, col: usize) {
         if self.hide_gap_columns && !self.visible_columns.is_empty() {
@@ -542,14 +2408,107 @@ impl App {
         count
     }
 
-    /// Add a digit to the count buffer.
+    /// Add a digit to the count buffer, showing the count typed so far.
     pub fn push_count_digit(&mut self, digit: char) {
         self.count_buffer.push(digit);
+        self.set_status(self.count_buffer.clone());
     }
 
-    /// Clear the count buffer.
-    pub fn clear_count(&mut self) {
-        self.count_buffer.clear();
+    /// Run an edit up to `count` times (at most `MAX_REPEAT`) as a single undo
+    /// step, stopping at the first one that fails. Returns how many succeeded.
+    pub fn repeat_edit(&mut self, count: usize, mut edit: impl FnMut(&mut Self) -> bool) -> usize {
+        self.history.begin_group();
+        self.repeat_modified = Some(false);
+        let mut done = 0;
+        while done < count.min(MAX_REPEAT) && edit(self) {
+            done += 1;
+        }
+        if self.repeat_modified.take() == Some(true) {
+            self.mark_modified();
+        }
+        self.history.end_group();
+        done
+    }
+
+    /// Make an edit from its key, `count` times where that means something, and
+    /// remember it for `.` if it changed the alignment.
+    pub fn run_edit(&mut self, edit: Edit, count: usize) {
+        let selection = self
+            .get_selection_bounds()
+            .map(|(min_row, min_col, max_row, max_col)| {
+                (
+                    max_row - min_row + 1,
+                    max_col - min_col + 1,
+                    self.block_selection,
+                )
+            });
+        let edits = self.edits;
+        self.apply_edit(edit, count);
+        if self.edits != edits {
+            self.last_edit = Some(LastEdit {
+                edit,
+                count,
+                selection,
+            });
+        }
+    }
+
+    /// Repeat the last edit at the cursor (`.`). A count replaces the one the
+    /// edit was made with; an edit made on a visual selection applies to a
+    /// selection of the same size from the cursor.
+    pub fn repeat_last_edit(&mut self, count: Option<usize>) {
+        let Some(last) = &mut self.last_edit else {
+            self.set_error("No edit to repeat");
+            return;
+        };
+        if let Some(count) = count {
+            last.count = count;
+        }
+        let last = *last;
+        let Some((rows, cols, block)) = last.selection else {
+            self.apply_edit(last.edit, last.count);
+            return;
+        };
+        let start = (self.cursor_row, self.cursor_col);
+        let was_block = self.block_selection;
+        self.selection_anchor = Some(start);
+        self.block_selection = block;
+        self.cursor_row = (start.0 + rows - 1).min(self.visible_sequence_count().saturating_sub(1));
+        self.cursor_col = (start.1 + cols - 1).min(self.alignment.width().saturating_sub(1));
+        self.apply_edit(last.edit, last.count);
+        self.exit_visual_mode();
+        self.block_selection = was_block;
+        (self.cursor_row, self.cursor_col) = start;
+        self.clamp_cursor();
+    }
+
+    fn apply_edit(&mut self, edit: Edit, count: usize) {
+        match edit {
+            // A count stops at a residue, asking about it as usual
+            Edit::DeleteGap => {
+                self.repeat_edit(count, Self::delete_gap);
+            }
+            Edit::InsertGapColumn => {
+                self.repeat_edit(count, Self::insert_gap_column);
+            }
+            Edit::DeleteGapColumn => {
+                self.repeat_edit(count, Self::delete_gap_column);
+            }
+            Edit::ShiftLeft => {
+                self.shift_sequence(ShiftDirection::Left, count.min(MAX_REPEAT));
+            }
+            Edit::ShiftRight => {
+                self.shift_sequence(ShiftDirection::Right, count.min(MAX_REPEAT));
+            }
+            Edit::ThrowLeft => self.throw_sequence_left(),
+            Edit::ThrowRight => self.throw_sequence_right(),
+            Edit::InsertSelectionGapColumns => self.insert_selection_gap_columns(),
+            Edit::DeleteSelectionGapColumns => self.delete_selection_gap_columns(),
+            Edit::InsertBlockGaps => self.insert_block_gaps(),
+            Edit::CloseBlockGaps => self.close_block_gaps(),
+            Edit::Uppercase => self.uppercase_alignment(),
+            Edit::Lowercase => self.lowercase_alignment(),
+        }
     }
 
     /// Page down.
@@ -603,7 +2562,9 @@ impl App {
 
     /// Enter insert mode.
     pub fn enter_insert_mode(&mut self) {
-        self.mode = Mode::Insert;
+        if self.check_writable() {
+            self.mode = Mode::Insert;
+        }
     }
 
     /// Enter command mode.
@@ -617,6 +2578,8 @@ impl App {
     pub fn enter_normal_mode(&mut self) {
         self.mode = Mode::Normal;
         self.command_buffer.clear();
+        self.selection_anchor = None;
+        self.typing_sequence = None;
     }
 
     /// Enter search mode.
@@ -629,6 +2592,19 @@ impl App {
     pub fn enter_visual_mode(&mut self) {
         self.mode = Mode::Visual;
         self.selection_anchor = Some((self.cursor_row, self.cursor_col));
+        self.block_selection = false;
+    }
+
+    /// Enter visual block mode (`Ctrl-v`), whose gap edits leave sequences
+    /// outside the selection alone.
+    pub fn enter_visual_block_mode(&mut self) {
+        self.enter_visual_mode();
+        self.block_selection = true;
+    }
+
+    /// Switch between visual and visual block mode, keeping the selection.
+    pub fn toggle_visual_block(&mut self) {
+        self.block_selection = !self.block_selection;
     }
 
     /// Exit visual mode without taking action.
@@ -650,7 +2626,8 @@ impl App {
 
     /// Check if a cell is within the current selection.
     pub fn is_selected(&self, row: usize, col: usize) -> bool {
-        if self.mode != Mode::Visual {
+        // A selection stays visible while typing a command that applies to it
+        if !matches!(self.mode, Mode::Visual | Mode::Command) {
             return false;
         }
         if let Some((min_row, min_col, max_row, max_col)) = self.get_selection_bounds() {
@@ -660,6 +2637,24 @@ impl App {
         }
     }
 
+    /// Actual row indices a sequence command applies to: every row of a visual
+    /// selection carried into command mode, otherwise the cursor row.
+    pub(crate) fn target_rows(&self) -> Vec<usize> {
+        if self.alignment.sequences.is_empty() {
+            return Vec::new();
+        }
+        let (min_row, max_row) = match self.get_selection_bounds() {
+            Some((min_row, _, max_row, _)) => (min_row, max_row),
+            None => (self.cursor_row, self.cursor_row),
+        };
+        let mut rows: Vec<usize> = (min_row..=max_row)
+            .map(|display_row| self.display_to_actual_row(display_row))
+            .collect();
+        rows.sort_unstable();
+        rows.dedup();
+        rows
+    }
+
     /// Get selection dimensions as a string for status bar.
     pub fn selection_info(&self) -> Option<String> {
         if self.mode != Mode::Visual {
@@ -671,7 +2666,7 @@ impl App {
         Some(format!("{rows}x{cols}"))
     }
 
-    /// Yank (copy) the selected block to clipboard.
+    /// Yank (copy) the selected block to the register.
     pub fn yank_selection(&mut self) {
         let Some((min_row, min_col, max_row, max_col)) = self.get_selection_bounds() else {
             return;
@@ -689,9 +2684,55 @@ impl App {
 
         let rows = block.len();
         let cols = if block.is_empty() { 0 } else { block[0].len() };
-        self.clipboard = Some(block);
+        let into = self.store_register(Register::Block(block));
         self.exit_visual_mode();
-        self.set_status(format!("Yanked {rows}x{cols} block"));
+        self.set_status(format!("Yanked {rows}x{cols} block{into}"));
+    }
+
+    /// Keep `register` as the unnamed register and, if one was chosen with `"a`,
+    /// in that named register. Returns " into register a" for the status, or "".
+    pub(crate) fn store_register(&mut self, register: Register) -> String {
+        let into = match self.pending_register.take() {
+            Some(name) => {
+                self.named_registers.insert(name, register.clone());
+                format!(" into register {name}")
+            }
+            None => String::new(),
+        };
+        self.register = Some(register);
+        into
+    }
+
+    /// Use register `name` (a-z) for the next yank, delete or put (`"a`).
+    pub fn select_register(&mut self, name: char) {
+        if !name.is_ascii_lowercase() {
+            self.set_error("Registers are a-z");
+            return;
+        }
+        self.pending_register = Some(name);
+        self.set_status(format!("\"{name}"));
+    }
+
+    /// List the unnamed and named registers (`:registers`).
+    pub fn registers_report(&mut self) {
+        let mut lines: Vec<String> = self
+            .register
+            .iter()
+            .map(|register| format!("\"  {}", register.describe()))
+            .collect();
+        lines.extend(
+            self.named_registers
+                .iter()
+                .map(|(name, register)| format!("{name}  {}", register.describe())),
+        );
+        if lines.is_empty() {
+            self.set_error("Registers are empty (yy yanks a sequence)");
+            return;
+        }
+        self.report = Some(Report {
+            title: "Registers".to_string(),
+            lines,
+        });
     }
 
     /// Delete the selected block (replace with gaps).
@@ -699,10 +2740,9 @@ impl App {
         let Some((min_row, min_col, max_row, max_col)) = self.get_selection_bounds() else {
             return;
         };
-
-        // Save for undo
-        self.history
-            .save(&self.alignment, self.cursor_row, self.cursor_col);
+        if !self.begin_edit() {
+            return;
+        }
 
         // Replace selected region with gaps
         for row in min_row..=max_row {
@@ -718,23 +2758,53 @@ impl App {
 
         let rows = max_row - min_row + 1;
         let cols = max_col - min_col + 1;
-        self.modified = true;
+        self.mark_modified();
         self.exit_visual_mode();
         self.set_status(format!("Deleted {rows}x{cols} block"));
     }
 
-    /// Paste the clipboard at the cursor position.
-    pub fn paste(&mut self) {
-        let Some(ref block) = self.clipboard else {
-            self.set_status("Nothing to paste");
-            return;
+    /// Paste the register (the one chosen with `"a`, or the unnamed one) at the
+    /// cursor: a block over the cursor position, or sequences below (`above`
+    /// false) or above the cursor row. Sequences still in the alignment are put
+    /// as copies under a new ID.
+    pub fn paste(&mut self, above: bool) {
+        let register = match self.pending_register.take() {
+            Some(name) => match self.named_registers.get(&name) {
+                Some(register) => register.clone(),
+                None => {
+                    self.set_error(format!("Register {name} is empty"));
+                    return;
+                }
+            },
+            None => match &self.register {
+                Some(register) => register.clone(),
+                None => {
+                    self.set_error("Nothing to paste");
+                    return;
+                }
+            },
+        };
+        let block = match register {
+            Register::Block(block) => block,
+            Register::Sequences(mut sequences) => {
+                let mut renamed = Vec::new();
+                for detached in &mut sequences {
+                    let id = self.alignment.unused_id(&detached.sequence.id);
+                    if id != detached.sequence.id {
+                        std::rc::Rc::make_mut(&mut detached.sequence).id = id.clone();
+                        renamed.push(id);
+                    }
+                }
+                if self.put_sequences(sequences, above) && !renamed.is_empty() {
+                    self.set_status(format!("Put copy as {}", renamed.join(", ")));
+                }
+                return;
+            }
         };
 
-        // Save for undo
-        self.history
-            .save(&self.alignment, self.cursor_row, self.cursor_col);
-
-        let block = block.clone();
+        if !self.begin_edit() {
+            return;
+        }
         for (row_offset, row_data) in block.iter().enumerate() {
             let target_row = self.cursor_row + row_offset;
             if let Some(seq_rc) = self.alignment.sequences.get_mut(target_row) {
@@ -750,29 +2820,130 @@ impl App {
 
         let rows = block.len();
         let cols = if block.is_empty() { 0 } else { block[0].len() };
-        self.modified = true;
+        self.mark_modified();
         self.set_status(format!("Pasted {rows}x{cols} block"));
     }
 
-    /// Clear search highlighting.
-    pub fn clear_search(&mut self) {
-        self.search.clear();
+    /// Pick up the selected block to move it sideways within the same rows.
+    pub fn pick_up_block(&mut self) {
+        if !self.check_writable() {
+            return;
+        }
+        let Some((min_row, min_col, max_row, max_col)) = self.get_selection_bounds() else {
+            return;
+        };
+        let rows: Vec<usize> = (min_row..=max_row)
+            .map(|display_row| self.display_to_actual_row(display_row))
+            .collect();
+        let count = rows.len();
+        self.block_move = Some(BlockMove {
+            rows,
+            cols: min_col..max_col + 1,
+        });
+        self.exit_visual_mode();
+        self.cursor_col = min_col;
+        self.set_status(format!(
+            "Moving {count}x{} block: p/Enter drops at cursor, Esc cancels",
+            max_col - min_col + 1
+        ));
     }
 
-    /// Execute the current search pattern.
-    pub fn execute_search(&mut self) {
-        if self.search.pattern.is_empty() {
-            self.enter_normal_mode();
+    /// Drop the picked-up block so it starts at the cursor column.
+    pub fn drop_block(&mut self) {
+        let Some(block) = self.block_move.take() else {
+            return;
+        };
+        let to = self.cursor_col;
+        if to == block.cols.start {
+            self.set_error("Block not moved");
+            return;
+        }
+        if let Some(row) =
+            self.alignment
+                .block_move_conflict(&block.rows, block.cols.clone(), to, &self.gap_chars)
+        {
+            let id = self
+                .alignment
+                .sequences
+                .get(row)
+                .map_or("alignment end", |s| s.id.as_str());
+            self.set_error(format!(
+                "Cannot move block to column {}: residues in the way ({id})",
+                to + 1
+            ));
+            self.block_move = Some(block);
+            return;
+        }
+
+        if !self.begin_edit() {
+            return;
+        }
+        self.alignment
+            .move_block(&block.rows, block.cols.clone(), to, self.gap_char);
+        let (start, end) = (
+            block.cols.start.min(to),
+            block.cols.end.max(to + block.cols.len()),
+        );
+        for col in start..end {
+            self.alignment
+                .fix_end_gaps(col, self.gap_char, self.end_gap_char, &self.gap_chars);
+        }
+        self.mark_modified();
+
+        let shift = to as isize - block.cols.start as isize;
+        self.set_status(format!(
+            "Moved {}x{} block {} by {}",
+            block.rows.len(),
+            block.cols.len(),
+            if shift < 0 { "left" } else { "right" },
+            shift.unsigned_abs()
+        ));
+    }
+
+    /// Put a picked-up block back without moving it.
+    pub fn cancel_block_move(&mut self) {
+        if self.block_move.take().is_some() {
+            self.set_status("Block move cancelled");
+        }
+    }
+
+    /// Whether a cell lies where the picked-up block would be dropped.
+    pub fn in_block_move(&self, row: usize, col: usize) -> bool {
+        self.block_move.as_ref().is_some_and(|block| {
+            block.rows.contains(&row)
+                && col >= self.cursor_col
+                && col < self.cursor_col + block.cols.len()
+        })
+    }
+
+    /// Clear search highlighting.
+    pub fn clear_search(&mut self) {
+        self.search.clear();
+    }
+
+    /// Execute the current search pattern.
+    pub fn execute_search(&mut self) {
+        if self.search.pattern.is_empty() {
+            self.enter_normal_mode();
             return;
         }
 
         // Add to history (InputHistory handles deduplication)
         self.search.history.push(self.search.pattern.clone());
 
-        self.search.matches = self.find_matches(&self.search.pattern.clone());
+        let motif = match self.compile_search(&self.search.pattern) {
+            Ok(motif) => motif,
+            Err(e) => {
+                self.set_error(format!("Invalid pattern: {e}"));
+                self.search.matches.clear();
+                self.search.match_index = None;
+                return;
+            }
+        };
+        self.search.matches = self.find_matches(&self.alignment, &motif);
 
         if self.search.matches.is_empty() {
-            self.set_status("Pattern not found (ignoring gaps)");
+            self.set_error(format!("Pattern not found{}", self.search_note()));
             self.search.match_index = None;
         } else {
             // Find first match at or after cursor position
@@ -794,11 +2965,20 @@ impl App {
     pub fn search_next(&mut self) {
         if self.search.matches.is_empty() {
             if !self.search.pattern.is_empty() {
-                self.set_status("Pattern not found");
+                self.set_error("Pattern not found");
             }
             return;
         }
 
+        // Step from the current match while the cursor is on it, so matches
+        // starting at the same cell (an ID and its first residue) all get a turn
+        let len = self.search.matches.len();
+        if let Some(idx) = self.match_at_cursor() {
+            self.search.match_index = Some((idx + 1) % len);
+            self.jump_to_current_match();
+            return;
+        }
+
         // Find first match strictly after current cursor position
         let cursor_pos = (self.cursor_row, self.cursor_col);
         let next_idx = self
@@ -816,11 +2996,18 @@ impl App {
     pub fn search_prev(&mut self) {
         if self.search.matches.is_empty() {
             if !self.search.pattern.is_empty() {
-                self.set_status("Pattern not found");
+                self.set_error("Pattern not found");
             }
             return;
         }
 
+        let len = self.search.matches.len();
+        if let Some(idx) = self.match_at_cursor() {
+            self.search.match_index = Some((idx + len - 1) % len);
+            self.jump_to_current_match();
+            return;
+        }
+
         // Find last match strictly before current cursor position
         let cursor_pos = (self.cursor_row, self.cursor_col);
         let prev_idx = self
@@ -828,85 +3015,82 @@ impl App {
             .matches
             .iter()
             .rposition(|&(row, start_col, _)| (row, start_col) < cursor_pos)
-            .unwrap_or(self.search.matches.len() - 1); // Wrap to last match if none before cursor
+            .unwrap_or(len - 1); // Wrap to last match if none before cursor
 
         self.search.match_index = Some(prev_idx);
         self.jump_to_current_match();
     }
 
-    /// Find all matches of a pattern in the alignment.
-    /// Case-insensitive, U/T tolerant (RNA/DNA equivalent), and ignores gap characters.
-    /// Returns (row, start_col, end_col) where end_col is exclusive.
-    fn find_matches(&self, pattern: &str) -> Vec<(usize, usize, usize)> {
-        let pattern_normalized = Self::normalize_for_search(pattern);
-        let pattern_chars: Vec<char> = pattern_normalized.chars().collect();
-        let mut matches = Vec::new();
+    /// The current match, if the cursor is still at its start.
+    fn match_at_cursor(&self) -> Option<usize> {
+        let idx = self.search.match_index?;
+        let &(row, start_col, _) = self.search.matches.get(idx)?;
+        ((row, start_col) == (self.cursor_row, self.cursor_col)).then_some(idx)
+    }
 
-        if pattern_chars.is_empty() {
-            return matches;
+    /// Find the matches of the current pattern again, e.g. after `:set
+    /// search-gaps` changed what counts as a match.
+    fn refresh_search(&mut self) {
+        if self.search.pattern.is_empty() {
+            return;
         }
+        self.search.matches = match self.compile_search(&self.search.pattern) {
+            Ok(motif) => self.find_matches(&self.alignment, &motif),
+            Err(_) => Vec::new(),
+        };
+        let last = self.search.matches.len().checked_sub(1);
+        self.search.match_index = self.search.match_index.zip(last).map(|(i, l)| i.min(l));
+    }
 
-        for (row, seq) in self.alignment.sequences.iter().enumerate() {
-            let seq_chars: Vec<char> = seq.chars().to_vec();
-
-            // Try matching starting at each position
-            let mut col = 0;
-            while col < seq_chars.len() {
-                if let Some(end_col) = self.try_match_at(&seq_chars, col, &pattern_chars) {
-                    matches.push((row, col, end_col));
-                    // Move past the first non-gap character to find overlapping matches
-                    col += 1;
-                    while col < seq_chars.len() && self.gap_chars.contains(&seq_chars[col]) {
-                        col += 1;
-                    }
-                } else {
-                    col += 1;
-                }
-            }
+    /// How the search reads patterns and treats gaps, for the status line.
+    fn search_note(&self) -> String {
+        let gaps = if self.search_skip_gaps {
+            "ignoring gaps"
+        } else {
+            "gaps included"
+        };
+        let kind = if self.search.pattern.starts_with(REGEX_PREFIX) {
+            SearchKind::Regex
+        } else {
+            self.search_kind
+        };
+        match kind {
+            SearchKind::Literal => format!(" ({gaps})"),
+            kind => format!(" ({}, {gaps})", kind.name()),
         }
-
-        matches
     }
 
-    /// Try to match pattern starting at given column, skipping gaps.
-    /// Returns the end column (exclusive) if match found, None otherwise.
-    fn try_match_at(&self, seq: &[char], start_col: usize, pattern: &[char]) -> Option<usize> {
-        let mut seq_idx = start_col;
-        let mut pat_idx = 0;
-
-        while pat_idx < pattern.len() {
-            // Skip gaps in sequence
-            while seq_idx < seq.len() && self.gap_chars.contains(&seq[seq_idx]) {
-                seq_idx += 1;
-            }
+    /// Compile a `/` pattern with the current `:set search` and `search-gaps`.
+    fn compile_search(&self, pattern: &str) -> Result<SearchPattern, String> {
+        SearchPattern::new(
+            pattern,
+            self.search_kind,
+            self.search_skip_gaps,
+            &self.gap_chars,
+        )
+    }
 
-            if seq_idx >= seq.len() {
-                return None; // Ran out of sequence
+    /// Find all matches of a compiled pattern in an alignment, including
+    /// sequence IDs for regular expressions (as an empty span at column 0).
+    /// Returns (row, start_col, end_col) where end_col is exclusive.
+    fn find_matches(
+        &self,
+        alignment: &Alignment,
+        motif: &SearchPattern,
+    ) -> Vec<(usize, usize, usize)> {
+        let mut matches = Vec::new();
+        if motif.is_empty() {
+            return matches;
+        }
+        for (row, seq) in alignment.sequences.iter().enumerate() {
+            if motif.matches_id(&seq.id) == Some(true) {
+                matches.push((row, 0, 0));
             }
-
-            let seq_char = Self::normalize_char(seq[seq_idx]);
-            if seq_char != pattern[pat_idx] {
-                return None; // Mismatch
+            for (start, end) in motif.find(seq.chars()) {
+                matches.push((row, start, end));
             }
-
-            seq_idx += 1;
-            pat_idx += 1;
-        }
-
-        Some(seq_idx)
-    }
-
-    /// Normalize a single character for search: uppercase and T→U.
-    fn normalize_char(c: char) -> char {
-        match c.to_ascii_uppercase() {
-            'T' => 'U',
-            other => other,
         }
-    }
-
-    /// Normalize a string for search: uppercase and T→U for RNA/DNA equivalence.
-    fn normalize_for_search(s: &str) -> String {
-        s.to_uppercase().replace('T', "U")
+        matches
     }
 
     /// Check if a position is part of a search match.
@@ -915,21 +3099,37 @@ impl App {
         self.search.is_match(row, col)
     }
 
+    /// Check if a sequence's ID is a search match (regex searches only).
+    pub fn is_search_id_match(&self, row: usize) -> Option<bool> {
+        self.search.is_id_match(row)
+    }
+
     /// Jump to the current match and update status.
     fn jump_to_current_match(&mut self) {
         if let Some(idx) = self.search.match_index
-            && let Some(&(row, start_col, _end_col)) = self.search.matches.get(idx)
+            && let Some(&(row, start_col, end_col)) = self.search.matches.get(idx)
         {
             self.cursor_row = row;
             self.cursor_col = start_col;
+            let place = if start_col == end_col { " in ID" } else { "" };
             self.set_status(format!(
-                "Match {}/{} (ignoring gaps)",
+                "Match {}/{}{place}{}",
                 idx + 1,
-                self.search.matches.len()
+                self.search.matches.len(),
+                self.search_note()
             ));
         }
     }
 
+    /// Suspend to the shell like Ctrl-Z does elsewhere; `fg` resumes.
+    pub fn suspend(&mut self) {
+        if cfg!(unix) {
+            self.suspend_requested = true;
+        } else {
+            self.set_error("Suspending is only supported on Unix");
+        }
+    }
+
     /// Execute a command from command mode.
     pub fn execute_command(&mut self) {
         let command = self.command_buffer.trim().to_string();
@@ -943,28 +3143,404 @@ impl App {
         // Add to history (InputHistory handles deduplication)
         self.command_history.push(command.clone());
 
+        let start = Instant::now();
+        match command.strip_suffix("--preview") {
+            Some(previewed) => self.preview_command(previewed.trim_end()),
+            None => {
+                // The error is already on the status line
+                let _ = self.run_recorded(&command);
+            }
+        }
+
+        // A selection carried over from visual mode applies to this command only
+        self.selection_anchor = None;
+
+        tracing::info!(
+            command = command.as_str(),
+            elapsed_ms = start.elapsed().as_millis(),
+            status = self.status_message.as_deref().unwrap_or(""),
+            "command"
+        );
+    }
+
+    /// Run a command and, if it edited the alignment, add it to the session
+    /// script with where the cursor and any selection were.
+    pub(crate) fn run_recorded(&mut self, command: &str) -> Result<(), String> {
+        let edits = self.edits;
+        let position = self.script_position(self.cursor_row, self.cursor_col);
+        let anchor = self
+            .selection_anchor
+            .and_then(|(row, col)| self.script_position(row, col));
+        let result = self.run_command(command);
+        if self.edits != edits
+            && let Some((id, column)) = position
+        {
+            self.script_log.push(script::Entry {
+                command: command.to_string(),
+                id,
+                column,
+                anchor,
+            });
+        }
+        result
+    }
+
+    /// Note a key typed at the keyboard, before it is handled.
+    pub fn begin_key(&mut self, key: &KeyEvent) {
+        if self.key_record.is_none() {
+            self.key_record = Some(KeyRecord {
+                keys: String::new(),
+                named: true,
+                position: self.script_position(self.cursor_row, self.cursor_col),
+                edits: self.edits,
+                logged: self.script_log.len(),
+            });
+        }
+        if let Some(record) = &mut self.key_record {
+            match script::key_name(key) {
+                Some(name) => record.keys.push_str(&name),
+                None => record.named = false,
+            }
+        }
+    }
+
+    /// Once the keys typed so far make a complete command, add them to the
+    /// session script as `:normal <keys>` if they edited the alignment (and
+    /// were not a command line, which is recorded as itself).
+    pub fn end_key(&mut self) {
+        if self.awaiting_keys() {
+            return;
+        }
+        let Some(record) = self.key_record.take() else {
+            return;
+        };
+        if self.edits != record.edits
+            && self.script_log.len() == record.logged
+            && record.named
+            && let Some((id, column)) = record.position
+        {
+            self.script_log.push(script::Entry {
+                command: format!("normal {}", record.keys),
+                id,
+                column,
+                anchor: None,
+            });
+        }
+    }
+
+    /// Whether the keys typed so far wait for more: a count, register or the
+    /// first key of `dd` or `m{a-z}`, another mode, a question or a block
+    /// being moved.
+    fn awaiting_keys(&self) -> bool {
+        self.mode != Mode::Normal
+            || !self.count_buffer.is_empty()
+            || self.pending_register.is_some()
+            || self.block_move.is_some()
+            || self.confirm.is_some()
+            || self.column_prompt.is_some()
+            || self.substitute_prompt.is_some()
+            || self.palette.is_some()
+            || self
+                .status_message
+                .as_deref()
+                .is_some_and(|s| s.ends_with("..."))
+    }
+
+    /// Type `keys`, written as in the session script (`:normal`). An
+    /// unfinished insert or visual selection is left as if by Esc.
+    fn run_keys(&mut self, keys: &str) {
+        let keys = match script::parse_keys(keys) {
+            Ok(keys) => keys,
+            Err(e) => {
+                self.set_error(e);
+                return;
+            }
+        };
+        // Commands typed here are part of this one in the script
+        let logged = self.script_log.len();
+        let error = self.command_error.take();
+        for key in keys {
+            crate::input::handle_key(self, key, self.page_rows);
+            if self.should_quit {
+                break;
+            }
+        }
+        if matches!(self.mode, Mode::Insert | Mode::Visual | Mode::Track) {
+            crate::input::handle_key(self, KeyEvent::from(KeyCode::Esc), self.page_rows);
+        }
+        self.mode = Mode::Normal;
+        self.script_log.truncate(logged);
+        self.command_error = self.command_error.take().or(error);
+    }
+
+    /// Sequence ID and 1-based column of a display position.
+    fn script_position(&self, display_row: usize, col: usize) -> Option<(String, usize)> {
+        let row = self.display_to_actual_row(display_row);
+        self.alignment
+            .sequences
+            .get(row)
+            .map(|seq| (seq.id.clone(), col + 1))
+    }
+
+    /// Write the editing commands run on this alignment as a script
+    /// (`:w session.aform`).
+    fn write_script(&mut self, path: &Path) {
+        if self.script_log.is_empty() {
+            self.set_error("No editing commands to write yet");
+            return;
+        }
+        let source = self
+            .file_path
+            .as_ref()
+            .map_or_else(|| "stdin".to_string(), |p| p.display().to_string());
+        match std::fs::write(path, script::render(&self.script_log, &source)) {
+            Ok(()) => self.set_status(format!(
+                "Wrote {} commands to {}",
+                self.script_log.len(),
+                path.display()
+            )),
+            Err(e) => self.set_error(format!("Failed to write file: {e}")),
+        }
+    }
+
+    /// Replay a session script (`aform --script`), stopping at the first command
+    /// that fails or asks a question. Returns how many commands ran.
+    pub fn run_script(&mut self, path: &Path) -> Result<usize, String> {
+        let text = std::fs::read_to_string(path)
+            .map_err(|e| format!("Cannot read {}: {e}", path.display()))?;
+        let mut count = 0;
+        for (line, command) in script::parse(&text) {
+            self.clear_status();
+            let anchor = command.starts_with("anchor ");
+            let mut result = self.run_recorded(&command);
+            if result.is_ok() && self.alifold_job.is_some() {
+                // Wait for RNAalifold, so the commands after see its structure
+                self.finish_alifold_job(true);
+                result = self.command_error.take().map_or(Ok(()), Err);
+            }
+            if result.is_ok() && self.cluster_job.is_some() {
+                // Wait for the clusters, so the commands after see them
+                self.finish_cluster_job(true);
+                result = self.command_error.take().map_or(Ok(()), Err);
+            }
+            if result.is_ok() && self.hit_align_job.is_some() {
+                // Wait for the aligner, so the commands after see the hits
+                self.finish_hit_align_job(true);
+                result = self.command_error.take().map_or(Ok(()), Err);
+            }
+            if !anchor {
+                self.selection_anchor = None;
+            }
+            let prompted = self.confirm.take().is_some()
+                || self.column_prompt.take().is_some()
+                || self.substitute_prompt.take().is_some();
+            let failure = match result {
+                Err(e) => Some(e),
+                Ok(()) if prompted => Some(format!(":{command} asks for confirmation")),
+                Ok(()) => None,
+            };
+            if let Some(reason) = failure {
+                return Err(format!("{}:{line}: {reason}", path.display()));
+            }
+            count += 1;
+            if self.should_quit {
+                break;
+            }
+        }
+        Ok(count)
+    }
+
+    /// Move the cursor to a sequence, by ID (with or without its `/start-end`)
+    /// or row number, and optionally a column (`:goto`).
+    fn goto_target(&mut self, target: &str, column: Option<&str>) {
+        let row = match self.find_display_row(target) {
+            Ok(row) => row,
+            Err(e) => {
+                self.set_error(e);
+                return;
+            }
+        };
+        let col = match column.map(|c| c.parse::<usize>()) {
+            None => self.cursor_col,
+            Some(Ok(col)) if col > 0 => (col - 1).min(self.alignment.width().saturating_sub(1)),
+            Some(_) => {
+                self.set_error(format!("Invalid column: {}", column.unwrap_or_default()));
+                return;
+            }
+        };
+        self.cursor_row = row;
+        self.cursor_col = col;
+    }
+
+    /// Set the other corner of a selection for the next command of a script
+    /// (`:anchor <id> <column>`).
+    fn set_anchor(&mut self, target: &str, column: &str) {
+        let row = match self.find_display_row(target) {
+            Ok(row) => row,
+            Err(e) => {
+                self.set_error(e);
+                return;
+            }
+        };
+        match column.parse::<usize>() {
+            Ok(col) if col > 0 => {
+                let col = (col - 1).min(self.alignment.width().saturating_sub(1));
+                self.selection_anchor = Some((row, col));
+            }
+            _ => self.set_error(format!("Invalid column: {column}")),
+        }
+    }
+
+    /// Display row of a sequence given by ID, short ID or row number.
+    fn find_display_row(&self, target: &str) -> Result<usize, String> {
+        let sequences = &self.alignment.sequences;
+        let actual = sequences
+            .iter()
+            .position(|seq| seq.id == target)
+            .or_else(|| {
+                sequences
+                    .iter()
+                    .position(|seq| short_id(&seq.id) == short_id(target))
+            });
+        match actual {
+            Some(row) => self
+                .actual_to_display_row(row)
+                .ok_or_else(|| format!("{target} is hidden by a filter or collapsed")),
+            None => match target.parse::<usize>() {
+                Ok(row) if row > 0 && row <= self.visible_sequence_count() => Ok(row - 1),
+                _ => Err(format!("No sequence {target}")),
+            },
+        }
+    }
+
+    /// Run a command typed on the command line (without the `:`). Fails with
+    /// the message the command put on the status line when it could not run.
+    pub(crate) fn run_command(&mut self, command: &str) -> Result<(), String> {
+        self.command_error = None;
         let parts: Vec<&str> = command.split_whitespace().collect();
 
         // Try each command category in order
-        if self.execute_file_command(&parts, &command) {
+        let handled = self.execute_substitute_command(command)
+            || self.execute_file_command(&parts, command)
+            || self.execute_display_command(&parts)
+            || self.execute_transform_command(&parts)
+            || self.execute_clustering_command(&parts);
+
+        // Fallback: check for line number or unknown command
+        if !handled {
+            if let Ok(line_num) = command.parse::<usize>() {
+                self.goto_row(line_num);
+            } else {
+                self.set_error(format!("Unknown command: {command}"));
+            }
+        }
+        match self.command_error.take() {
+            Some(e) => Err(e),
+            None => Ok(()),
+        }
+    }
+
+    /// Run an editing command on the alignment, then put everything back and
+    /// list what it changed, asking whether to apply it (`:trim --preview`).
+    fn preview_command(&mut self, command: &str) {
+        let parts: Vec<&str> = command.split_whitespace().collect();
+        let Some(&name) = parts.first() else {
+            self.set_error("Usage: :<command> --preview");
+            return;
+        };
+        let previewable = PREVIEW_COMMANDS.contains(&name)
+            || matches!(
+                parts.as_slice(),
+                ["confidence" | "conf" | "track", "mask", _]
+            )
+            || command.starts_with("s")
+                && command[1..].starts_with(|c: char| c.is_ascii_punctuation())
+            || command.starts_with("%s");
+        if !previewable {
+            self.set_error(format!(":{name} has no preview (only editing commands do)"));
             return;
         }
-        if self.execute_display_command(&parts) {
+
+        let selection = self.selection_anchor;
+        let before = self.alignment.clone();
+        let (cursor_row, cursor_col, modified) = (self.cursor_row, self.cursor_col, self.modified);
+        let edits = self.edits;
+        // Column edits move pins and the focus, and drop the confidence scores
+        let pinned_columns = self.pinned_columns.clone();
+        let color_focus = self.color_focus.clone();
+        let confidence = (self.column_confidence.clone(), self.show_confidence_bar);
+        let history = std::mem::replace(&mut self.history, History::new());
+        let result = self.run_command(command);
+        let prompted = self.confirm.take().is_some()
+            || self.column_prompt.take().is_some()
+            || self.substitute_prompt.take().is_some();
+        let after = std::mem::replace(&mut self.alignment, before);
+        self.history = history;
+        self.cursor_row = cursor_row;
+        self.cursor_col = cursor_col;
+        self.modified = modified;
+        self.edits = edits;
+        self.pinned_columns = pinned_columns;
+        self.color_focus = color_focus;
+        (self.column_confidence, self.show_confidence_bar) = confidence;
+        self.update_structure_cache();
+        self.refresh_row_filter();
+        self.clamp_cursor();
+
+        let message = self.status_message.take().unwrap_or_default();
+        if let Err(e) = result {
+            self.set_error(e);
             return;
         }
-        if self.execute_transform_command(&parts) {
+        if prompted {
+            self.set_error(format!(
+                ":{command} asks before each change itself; run it without --preview"
+            ));
             return;
         }
-        if self.execute_clustering_command(&parts) {
+        let changes = crate::editor::describe_changes(&self.alignment, &after, &self.gap_chars);
+        if changes.is_empty() {
+            self.set_error(format!(":{command} would change nothing ({message})"));
             return;
         }
+        let mut lines = vec![message, String::new()];
+        lines.extend(changes);
+        lines.push(String::new());
+        lines.push("y applies it, any other key cancels".to_string());
+        self.report = Some(Report {
+            title: format!("Preview of :{command}"),
+            lines,
+        });
+        self.confirm = Some(Confirm::ApplyCommand {
+            command: command.to_string(),
+            selection,
+        });
+        self.set_status(format!("Apply :{command}? (y/n)"));
+    }
 
-        // Fallback: check for line number or unknown command
-        if let Ok(line_num) = command.parse::<usize>() {
-            self.goto_row(line_num);
-        } else {
-            self.set_status(format!("Unknown command: {command}"));
+    /// Execute `:s/from/to/` (current sequence or selection) and `:%s/from/to/`
+    /// (every sequence). Returns true if handled.
+    fn execute_substitute_command(&mut self, command: &str) -> bool {
+        let (spec, all) = match command.strip_prefix("%s") {
+            Some(spec) => (spec, true),
+            None => match command.strip_prefix('s') {
+                Some(spec) => (spec, false),
+                None => return false,
+            },
+        };
+        // `:s/...`, not a command that starts with s such as `:set`
+        if !spec
+            .chars()
+            .next()
+            .is_some_and(|c| !c.is_alphanumeric() && !c.is_whitespace())
+        {
+            return false;
         }
+        match Substitution::parse(spec) {
+            Ok(sub) => self.substitute(sub, all),
+            Err(e) => self.set_error(e),
+        }
+        true
     }
 
     /// Execute file-related commands (quit, write, edit). Returns true if handled.
@@ -974,7 +3550,7 @@ impl App {
                 if self.split_mode.is_some() {
                     self.close_split();
                 } else if self.modified {
-                    self.set_status("No write since last change (use :q! to force)");
+                    self.set_error("No write since last change (use :q! to force)");
                 } else {
                     self.should_quit = true;
                 }
@@ -990,40 +3566,148 @@ impl App {
             }
             ["w" | "write"] => {
                 if let Err(e) = self.save_file() {
-                    self.set_status(e);
+                    self.set_error(e);
                 }
                 true
             }
+            ["w" | "w!", path]
+                if Path::new(path).extension() == Some(script::EXTENSION.as_ref()) =>
+            {
+                self.write_script(Path::new(path));
+                true
+            }
             ["w", path] => {
                 if let Err(e) = self.save_file_as(PathBuf::from(*path)) {
-                    self.set_status(e);
+                    self.set_error(e);
                 }
                 true
             }
+            ["w!", path] => {
+                // With a selection carried over from visual mode, write just that block
+                if self.selection_anchor.is_some() {
+                    self.export_region(Path::new(path));
+                } else if let Err(e) = self.save_file_as(PathBuf::from(*path)) {
+                    self.set_error(e);
+                }
+                true
+            }
+            ["screenshot", path] => {
+                // Captured by the main loop after the next draw; clear the message
+                // line so it doesn't end up in the picture
+                self.screenshot_path = Some(PathBuf::from(*path));
+                self.clear_status();
+                true
+            }
+            ["export", "region", path] => {
+                self.export_region(Path::new(path));
+                true
+            }
+            ["export", "columns", path] => {
+                self.export_columns(Path::new(path));
+                true
+            }
+            ["export", format, path] => {
+                self.export_alignment(format, Path::new(path));
+                true
+            }
             ["wq"] => {
                 if let Err(e) = self.save_file() {
-                    self.set_status(e);
+                    self.set_error(e);
                 } else {
                     self.should_quit = true;
                 }
                 true
             }
+            ["args"] => {
+                self.show_arg_files();
+                true
+            }
+            [cmd @ ("n" | "next" | "n!" | "next!")] => {
+                self.step_arg_file(1, cmd.ends_with('!'));
+                true
+            }
+            [cmd @ ("N" | "prev" | "previous" | "N!" | "prev!" | "previous!")] => {
+                self.step_arg_file(-1, cmd.ends_with('!'));
+                true
+            }
+            ["next-msa"] => {
+                self.step_record(1);
+                true
+            }
+            ["prev-msa"] => {
+                self.step_record(-1);
+                true
+            }
+            ["msa"] => {
+                self.open_record_list();
+                true
+            }
+            ["msa", n] => {
+                match n.parse::<usize>() {
+                    Ok(n) if n > 0 => self.goto_record(n - 1),
+                    _ => self.set_error("Usage: :msa [N]"),
+                }
+                true
+            }
+            ["grepall"] => {
+                self.set_error("Usage: :grepall <pattern>");
+                true
+            }
+            ["grepall", ..] => {
+                let pattern = command["grepall".len()..].trim().to_string();
+                self.grep_all(&pattern);
+                true
+            }
+            ["copen" | "cope" | "cw" | "cwindow"] => {
+                self.open_quickfix();
+                true
+            }
+            ["cn" | "cnext"] => {
+                self.step_quickfix(1);
+                true
+            }
+            ["cp" | "cprev" | "cprevious" | "cN" | "cNext"] => {
+                self.step_quickfix(-1);
+                true
+            }
             ["e" | "edit"] => {
-                self.set_status("Usage: :e <path> (Tab to complete)");
+                self.set_error("Usage: :e <path> (Tab to complete)");
+                true
+            }
+            ["r" | "read", path] => {
+                self.read_sequences(Path::new(path));
+                true
+            }
+            ["e" | "edit", option, path] if option.starts_with("++ft=") => {
+                let name = &option["++ft=".len()..];
+                match Format::from_name(name) {
+                    Some(format) => {
+                        if let Err(e) = self.load_file_as(Path::new(path), Some(format)) {
+                            self.set_error(e);
+                        }
+                    }
+                    None => self.set_error(format!(
+                        "Unknown format: {name} (stockholm, fasta, clustal, a2m, phylip, msf or selex)"
+                    )),
+                }
                 true
             }
             ["e" | "edit", path] => {
                 if let Err(e) = self.load_file(Path::new(path)) {
-                    self.set_status(e);
+                    self.set_error(e);
                 }
                 true
             }
+            ["suspend" | "sus" | "stop" | "st"] => {
+                self.suspend();
+                true
+            }
             ["noh" | "nohlsearch"] => {
                 self.clear_search();
                 true
             }
             _ if command.starts_with('!') => {
-                self.set_status("Shell commands not supported");
+                self.set_error("Shell commands not supported");
                 true
             }
             _ => false,
@@ -1081,6 +3765,48 @@ impl App {
                 ));
                 true
             }
+            ["confidence"] | ["conf"] => {
+                if self.show_confidence_bar {
+                    self.show_confidence_bar = false;
+                    self.set_status("Confidence bar: off");
+                } else {
+                    self.compute_column_confidence();
+                }
+                true
+            }
+            ["confidence" | "conf", "mask", threshold] => {
+                match threshold.parse::<f64>() {
+                    // Accept a fraction or a percentage
+                    Ok(t) if (0.0..=100.0).contains(&t) => {
+                        self.mask_low_confidence(if t > 1.0 { t / 100.0 } else { t })
+                    }
+                    _ => self.set_error("Usage: :confidence mask <threshold 0-1 or %>"),
+                }
+                true
+            }
+            ["track"] => {
+                match self.track.clone() {
+                    Some(tag) => self.open_track(&tag),
+                    None => self.set_error("Usage: :track <tag> (e.g. :track SCORE)"),
+                }
+                true
+            }
+            ["track", "off"] => {
+                self.track = None;
+                self.set_status("Track graph: off");
+                true
+            }
+            ["track", "mask", threshold] => {
+                match threshold.parse::<u8>() {
+                    Ok(t) if t <= TRACK_MAX => self.mask_track(t),
+                    _ => self.set_error("Usage: :track mask <value 0-10>"),
+                }
+                true
+            }
+            ["track", tag] => {
+                self.open_track(tag);
+                true
+            }
             ["rf"] => {
                 self.show_rf_bar = !self.show_rf_bar;
                 self.set_status(format!(
@@ -1131,44 +3857,245 @@ impl App {
                 true
             }
             ["color", scheme] => {
-                if let Some(s) = ColorScheme::from_str(scheme) {
+                if let Some((s, overlay)) = ColorScheme::parse_layers(scheme) {
                     self.color_scheme = s;
-                    self.set_status(format!("Color scheme: {}", s.as_ref()));
+                    self.color_overlay = overlay;
+                    self.set_status(format!("Color scheme: {}", self.color_name()));
                 } else {
-                    self.set_status(format!("Unknown color scheme: {scheme}"));
+                    self.set_error(format!("Unknown color scheme: {scheme}"));
                 }
                 true
             }
-            ["type"] => {
-                self.set_status(format!("Sequence type: {:?}", self.sequence_type));
+            ["ssdiff"] => {
+                self.ss_diff_report();
                 true
             }
-            ["type", t] => {
-                self.execute_type_command(t);
+            ["log"] => {
+                self.log_report();
                 true
             }
-            ["set", setting] => {
-                self.execute_set_command(setting);
+            ["history"] => {
+                self.shape_history_report();
                 true
             }
-            ["split" | "sp"] => {
-                self.horizontal_split();
+            ["goto", target] => {
+                self.goto_target(target, None);
                 true
             }
-            ["vsplit" | "vs" | "vsp"] => {
-                self.vertical_split();
+            ["goto", target, column] => {
+                self.goto_target(target, Some(column));
                 true
             }
-            ["only"] => {
-                self.close_split();
+            ["anchor", target, column] => {
+                self.set_anchor(target, column);
                 true
             }
-            _ => false,
-        }
-    }
-
-    /// Execute sequence type command.
-    fn execute_type_command(&mut self, t: &str) {
+            ["normal", keys @ ..] => {
+                self.run_keys(&keys.concat());
+                true
+            }
+            ["marks"] => {
+                self.marks_report();
+                true
+            }
+            ["reg" | "registers"] => {
+                self.registers_report();
+                true
+            }
+            ["coords"] => {
+                self.coords_report(None);
+                true
+            }
+            ["coords", path] => {
+                self.coords_report(Some(Path::new(path)));
+                true
+            }
+            ["sort", "tree"] => {
+                self.sort_by_tree(None);
+                true
+            }
+            ["sort", "tree", path] => {
+                self.sort_by_tree(Some(Path::new(path)));
+                true
+            }
+            ["sort", key, rest @ ..] if rest.len() <= 1 => {
+                let Some(key) = SortKey::from_str(key) else {
+                    self.set_error(format!(
+                        "Unknown sort key: {key} (name|length|gaps|identity)"
+                    ));
+                    return true;
+                };
+                let descending = match rest.first().copied() {
+                    None => key.descending_by_default(),
+                    Some("asc") => false,
+                    Some("desc") => true,
+                    Some(other) => {
+                        self.set_error(format!("Unknown sort order: {other} (asc|desc)"));
+                        return true;
+                    }
+                };
+                self.sort_sequences(key, descending);
+                true
+            }
+            ["sort", ..] => {
+                self.set_error(
+                    "Usage: :sort name|length|gaps|identity [asc|desc], :sort tree [file]",
+                );
+                true
+            }
+            ["sortcol"] => {
+                self.sort_by_column();
+                true
+            }
+            ["fixcoords"] => {
+                self.fix_coords(None);
+                true
+            }
+            ["fixcoords", path] => {
+                self.fix_coords(Some(Path::new(path)));
+                true
+            }
+            ["tutor"] => {
+                if self.modified {
+                    self.set_error("Unsaved changes (save with :w first)");
+                } else {
+                    self.start_tutor();
+                }
+                true
+            }
+            ["tutor", "off"] => {
+                self.tutor = None;
+                self.clear_status();
+                true
+            }
+            ["bookmark"] => {
+                self.add_bookmark();
+                true
+            }
+            ["bookmark", "clear"] => {
+                self.bookmarks.clear();
+                self.set_status("Bookmarks cleared");
+                true
+            }
+            ["present"] => {
+                self.start_presentation(None);
+                true
+            }
+            ["present", "off"] => {
+                self.stop_presentation();
+                true
+            }
+            ["present", seconds] => {
+                match seconds.parse() {
+                    Ok(seconds) => self.start_presentation(Some(seconds)),
+                    Err(_) => self.set_error(format!("Invalid interval: {seconds}")),
+                }
+                true
+            }
+            ["focus"] | ["focus", "selection"] => {
+                self.focus_selection();
+                true
+            }
+            ["focus", "off"] => {
+                self.color_focus = None;
+                self.set_status("Color focus: off");
+                true
+            }
+            ["pair", spec] => {
+                self.goto_helix_pair(spec);
+                true
+            }
+            ["helix"] => {
+                self.open_helix_view(None);
+                true
+            }
+            ["helix", helix] => {
+                self.open_helix_view(Some(helix));
+                true
+            }
+            ["focus", "helix", numbers @ ..] => {
+                self.focus_helices(numbers);
+                true
+            }
+            ["pin", "off"] | ["unpin"] => {
+                self.pinned_columns.clear();
+                self.set_status("Unpinned all columns");
+                true
+            }
+            ["pin", specs @ ..] => {
+                self.pin_columns(specs);
+                true
+            }
+            ["unpin", specs @ ..] => {
+                self.unpin_columns(specs);
+                true
+            }
+            ["type"] => {
+                self.set_status(format!("Sequence type: {:?}", self.sequence_type));
+                true
+            }
+            ["type", t] => {
+                self.execute_type_command(t);
+                true
+            }
+            ["fixann"] => {
+                self.fix_annotation_lengths();
+                true
+            }
+            ["set", setting] => {
+                self.execute_set_command(setting);
+                true
+            }
+            ["set" | "settings"] => {
+                self.open_settings();
+                true
+            }
+            ["ref"] => {
+                let actual_row = self.display_to_actual_row(self.cursor_row);
+                if let Some(seq) = self.alignment.sequences.get(actual_row) {
+                    let status = format!("Reference: {}", seq.id);
+                    self.reference_seq = actual_row;
+                    self.compare_to_consensus = false;
+                    self.set_status(status);
+                }
+                true
+            }
+            ["ref", "consensus" | "cons"] => {
+                self.compare_to_consensus = true;
+                self.set_status("Reference: consensus");
+                true
+            }
+            ["filter", "flagged"] => {
+                self.set_row_filter(RowFilter::Flagged);
+                true
+            }
+            ["filter", "off" | "none"] | ["nofilter"] => {
+                self.clear_row_filter();
+                self.set_status("Filter off");
+                true
+            }
+            ["filter", ..] => {
+                self.set_error("Usage: :filter flagged | :filter off");
+                true
+            }
+            ["split" | "sp"] => {
+                self.horizontal_split();
+                true
+            }
+            ["vsplit" | "vs" | "vsp"] => {
+                self.vertical_split();
+                true
+            }
+            ["only"] => {
+                self.close_split();
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Execute sequence type command.
+    fn execute_type_command(&mut self, t: &str) {
         match t.to_lowercase().as_str() {
             "rna" => {
                 self.sequence_type = SequenceType::RNA;
@@ -1187,7 +4114,7 @@ impl App {
                 self.set_status(format!("Detected sequence type: {:?}", self.sequence_type));
             }
             _ => {
-                self.set_status(format!(
+                self.set_error(format!(
                     "Unknown sequence type: {} (use rna, dna, protein, or auto)",
                     t
                 ));
@@ -1197,42 +4124,502 @@ impl App {
 
     /// Execute set command (key=value settings).
     fn execute_set_command(&mut self, setting: &str) {
+        let before = self.settings();
+        self.set_option(setting);
+        if self.settings() != before {
+            let columns = self.column_state();
+            self.history.save_settings(
+                &self.alignment,
+                &columns,
+                self.cursor_row,
+                self.cursor_col,
+                before,
+            );
+        }
+    }
+
+    /// The options `:set` changes.
+    pub fn settings(&self) -> Settings {
+        Settings {
+            gap_char: self.gap_char,
+            gap_chars: self.gap_chars.clone(),
+            end_gap_char: self.end_gap_char,
+            save_format: self.save_format,
+            write_options: self.write_options.clone(),
+            force_delete: self.force_delete,
+            rf_snap: self.rf_snap,
+            paranoid: self.paranoid,
+            hover: self.hover_on_move,
+            mask_style: self.mask_style,
+            alifold: self.alifold.clone(),
+            search_kind: self.search_kind,
+            search_skip_gaps: self.search_skip_gaps,
+            hit_programs: self.hit_programs.clone(),
+        }
+    }
+
+    /// Restore options saved by `settings`.
+    pub(crate) fn apply_settings(&mut self, settings: Settings) {
+        self.gap_char = settings.gap_char;
+        self.gap_chars = settings.gap_chars;
+        self.column_stats_cache.take();
+        self.end_gap_char = settings.end_gap_char;
+        self.save_format = settings.save_format;
+        self.write_options = settings.write_options;
+        self.force_delete = settings.force_delete;
+        self.rf_snap = settings.rf_snap;
+        self.paranoid = settings.paranoid;
+        self.hover_on_move = settings.hover;
+        self.mask_style = settings.mask_style;
+        self.alifold = settings.alifold;
+        self.search_kind = settings.search_kind;
+        self.search_skip_gaps = settings.search_skip_gaps;
+        self.refresh_search();
+        self.hit_programs = settings.hit_programs;
+    }
+
+    /// Current value of a `:set` key, as listed by `:settings`.
+    pub fn setting_value(&self, key: &str) -> String {
+        if key == "readonly" {
+            return if self.read_only { "on" } else { "off" }.to_string();
+        }
+        self.settings().value(key).unwrap_or_default()
+    }
+
+    /// Open the settings panel (`:set` alone, `:settings`).
+    pub fn open_settings(&mut self) {
+        self.settings_panel = Some(SettingsPanel::default());
+    }
+
+    /// Move the settings panel selection by `delta` rows.
+    pub fn move_settings_cursor(&mut self, delta: isize) {
+        if let Some(panel) = &mut self.settings_panel {
+            panel.cursor = panel
+                .cursor
+                .saturating_add_signed(delta)
+                .min(SETTING_KEYS.len() - 1);
+        }
+    }
+
+    /// Start typing a new value for the selected setting, from its current one.
+    pub fn edit_setting(&mut self) {
+        let Some(panel) = &self.settings_panel else {
+            return;
+        };
+        let value = self.setting_value(SETTING_KEYS[panel.cursor].0);
+        if let Some(panel) = &mut self.settings_panel {
+            panel.editing = Some(value);
+        }
+    }
+
+    /// Apply the value typed in the settings panel, as `:set key=value`.
+    pub fn apply_setting_edit(&mut self) {
+        let Some(panel) = &mut self.settings_panel else {
+            return;
+        };
+        let Some(value) = panel.editing.take() else {
+            return;
+        };
+        let key = SETTING_KEYS[panel.cursor].0;
+        self.execute_set_command(&format!("{key}={value}"));
+    }
+
+    /// Refuse a character that can't mark gaps: a letter, digit or space.
+    fn check_gap_char(c: char) -> Result<(), String> {
+        if c.is_alphanumeric() || c.is_whitespace() {
+            Err(format!("'{c}' can't be a gap character"))
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Set the gap character inserted by edits (`:set gap=`), or with `end`
+    /// the end gap character (`:set endgap=`), adding it to the gap characters.
+    fn set_gap_char(&mut self, value: &str, end: bool) -> Result<String, String> {
+        let key = if end { "endgap" } else { "gap" };
+        let mut chars = value.chars();
+        let (Some(c), None) = (chars.next(), chars.next()) else {
+            return Err(format!("Usage: :set {key}=<char>"));
+        };
+        Self::check_gap_char(c)?;
+        if end {
+            self.end_gap_char = c;
+        } else {
+            self.gap_char = c;
+        }
+        let set: String = self.gap_chars.iter().collect();
+        self.store_gap_chars(&set);
+        Ok(if end {
+            format!("End gap character: '{c}'")
+        } else {
+            format!("Gap character: '{c}'")
+        })
+    }
+
+    /// Replace the characters read as gaps (`:set gapchars=`). The gap and
+    /// end gap characters always stay in the set.
+    fn set_gap_chars(&mut self, value: &str) -> Result<String, String> {
+        value.chars().try_for_each(Self::check_gap_char)?;
+        self.store_gap_chars(value);
+        let kept = self.gap_chars.iter().any(|c| !value.contains(*c));
+        let set: String = self.gap_chars.iter().collect();
+        Ok(if kept {
+            format!("Gap characters: {set} (keeping the gap and end gap characters)")
+        } else {
+            format!("Gap characters: {set}")
+        })
+    }
+
+    /// Make `value` and the gap and end gap characters the gap characters,
+    /// both for editing and for the formats that write a single gap symbol.
+    fn store_gap_chars(&mut self, value: &str) {
+        let mut chars: Vec<char> = Vec::new();
+        for c in value.chars().chain([self.gap_char, self.end_gap_char]) {
+            if !chars.contains(&c) {
+                chars.push(c);
+            }
+        }
+        self.write_options.gap_chars = chars.clone();
+        self.gap_chars = chars;
+        self.column_stats_cache.take();
+    }
+
+    /// Apply the `[editor]` section of the config file, returning a message
+    /// for any value that was refused.
+    pub fn apply_editor_config(&mut self, editor: &EditorConfig) -> Option<String> {
+        let mut errors = Vec::new();
+        for (c, end) in [(editor.gap, false), (editor.endgap, true)] {
+            if let Some(c) = c
+                && let Err(e) = self.set_gap_char(&c.to_string(), end)
+            {
+                errors.push(e);
+            }
+        }
+        if let Some(chars) = &editor.gapchars
+            && let Err(e) = self.set_gap_chars(chars)
+        {
+            errors.push(e);
+        }
+        (!errors.is_empty()).then(|| format!("aform.toml: {}", errors.join("; ")))
+    }
+
+    /// Apply one `:set` option.
+    fn set_option(&mut self, setting: &str) {
         if let Some((key, value)) = setting.split_once('=') {
             match key {
-                "gap" => {
-                    if let Some(c) = value.chars().next() {
-                        self.gap_char = c;
-                        self.set_status(format!("Gap character: '{c}'"));
+                "gap" | "endgap" => match self.set_gap_char(value, key == "endgap") {
+                    Ok(status) => self.set_status(status),
+                    Err(e) => self.set_error(e),
+                },
+                "gapchars" => match self.set_gap_chars(value) {
+                    Ok(status) => self.set_status(status),
+                    Err(e) => self.set_error(e),
+                },
+                "hits.sfetch" => {
+                    if value.is_empty() {
+                        self.set_error("Usage: :set hits.sfetch=<program>");
+                    } else {
+                        self.hit_programs.sfetch = value.to_string();
+                        self.set_status(format!(":hits align fetches hits with {value}"));
+                    }
+                }
+                "hits.aligner" => {
+                    if value.is_empty() {
+                        self.set_error("Usage: :set hits.aligner=<program>");
+                    } else {
+                        self.hit_programs.aligner = value.to_string();
+                        self.set_status(format!(":hits align aligns hits with {value}"));
+                    }
+                }
+                "alifold.program" => {
+                    if value.is_empty() {
+                        self.set_error("Usage: :set alifold.program=<program>");
+                    } else {
+                        self.alifold.program = value.to_string();
+                        self.set_status(format!(":alifold runs {value}"));
+                    }
+                }
+                "alifold.ribosum" => match value {
+                    "on" | "true" | "1" => {
+                        self.alifold.ribosum = true;
+                        self.set_status("RNAalifold scores covariation with RIBOSUM matrices");
+                    }
+                    "off" | "false" | "0" => {
+                        self.alifold.ribosum = false;
+                        self.set_status("RNAalifold scores covariation with its default model");
+                    }
+                    _ => self.set_error("Usage: :set alifold.ribosum=on|off"),
+                },
+                "alifold.mis" => match value {
+                    "on" | "true" | "1" => {
+                        self.alifold.mis = true;
+                        self.set_status(":alifold adds the most informative sequence as #=GC MIS");
+                    }
+                    "off" | "false" | "0" => {
+                        self.alifold.mis = false;
+                        self.set_status(":alifold sets SS_cons only");
+                    }
+                    _ => self.set_error("Usage: :set alifold.mis=on|off"),
+                },
+                "force-delete" => match value {
+                    "on" | "true" | "1" => {
+                        self.force_delete = true;
+                        self.set_status("x deletes residues without asking");
+                    }
+                    "off" | "false" | "0" => {
+                        self.force_delete = false;
+                        self.set_status("x asks before deleting residues");
+                    }
+                    _ => self.set_error("Usage: :set force-delete=on|off"),
+                },
+                "rf-snap" => match value {
+                    "on" | "true" | "1" => {
+                        self.rf_snap = true;
+                        self.set_status("I inserts before the next RF match column");
+                    }
+                    "off" | "false" | "0" => {
+                        self.rf_snap = false;
+                        self.set_status("I inserts at the cursor");
+                    }
+                    _ => self.set_error("Usage: :set rf-snap=on|off"),
+                },
+                "paranoid" => match value {
+                    "on" | "true" | "1" => {
+                        self.paranoid = true;
+                        self.set_status("Row widths are checked after every edit");
+                    }
+                    "off" | "false" | "0" => {
+                        self.paranoid = false;
+                        self.set_status("Row widths are checked when saving");
+                    }
+                    _ => self.set_error("Usage: :set paranoid=on|off"),
+                },
+                "hover" => match value {
+                    "on" | "true" | "1" => {
+                        self.hover_on_move = true;
+                        self.set_status("Moving the mouse over a residue shows its info");
+                    }
+                    "off" | "false" | "0" => {
+                        self.hover_on_move = false;
+                        self.hover = None;
+                        self.set_status("Ctrl-click a residue to show its info");
+                    }
+                    _ => self.set_error("Usage: :set hover=on|off"),
+                },
+                "search" => match SearchKind::from_str(value) {
+                    Some(kind) => {
+                        self.search_kind = kind;
+                        self.refresh_search();
+                        self.set_status(match kind {
+                            SearchKind::Literal => "Search matches residues as typed",
+                            SearchKind::Iupac => "Search reads IUPAC codes (e.g. R = A/G, N = any)",
+                            SearchKind::Regex => "Search patterns are regular expressions",
+                        });
+                    }
+                    None => self.set_error("Usage: :set search=literal|iupac|regex"),
+                },
+                "search-gaps" => match value {
+                    "skip" | "on" => {
+                        self.search_skip_gaps = true;
+                        self.refresh_search();
+                        self.set_status("Search skips gaps in sequences");
+                    }
+                    "exact" | "off" => {
+                        self.search_skip_gaps = false;
+                        self.refresh_search();
+                        self.set_status("Search matches the aligned text, gaps included");
+                    }
+                    _ => self.set_error("Usage: :set search-gaps=skip|exact"),
+                },
+                "readonly" | "ro" => match value {
+                    "on" | "true" | "1" => self.set_read_only(true),
+                    "off" | "false" | "0" => self.set_read_only(false),
+                    _ => self.set_error("Usage: :set readonly | :set noreadonly"),
+                },
+                "backup" => match value {
+                    "on" | "true" | "1" => {
+                        self.write_options.backup = true;
+                        self.set_status("Saving keeps the previous file as <file>~");
+                    }
+                    "off" | "false" | "0" => {
+                        self.write_options.backup = false;
+                        self.set_status("Saving keeps no backup");
+                    }
+                    _ => self.set_error("Usage: :set backup=on|off"),
+                },
+                "mask" => match value {
+                    "lower" | "lowercase" => {
+                        self.mask_style = MaskStyle::Lowercase;
+                        self.set_status("Masking lowercases residues");
+                    }
+                    "gap" | "gaps" => {
+                        self.mask_style = MaskStyle::Gap;
+                        self.set_status("Masking replaces residues with gaps");
+                    }
+                    _ => self.set_error("Usage: :set mask=lower|gap"),
+                },
+                "wrap" => match value {
+                    "0" | "off" => {
+                        self.write_options.wrap = None;
+                        self.set_status("Stockholm output: one line per sequence");
+                    }
+                    _ => match value.parse::<usize>() {
+                        Ok(width) => {
+                            self.write_options.wrap = Some(width);
+                            let note = match self.write_options.style {
+                                StockholmStyle::Pfam => " (not in Pfam style)",
+                                StockholmStyle::Stockholm => "",
+                            };
+                            self.set_status(format!(
+                                "Stockholm output: blocks of {width} columns{note}"
+                            ));
+                        }
+                        Err(_) => self.set_error("Usage: :set wrap=<columns>|off"),
+                    },
+                },
+                "eol" => match LineEnding::from_name(value) {
+                    Some(line_ending) => {
+                        self.write_options.line_ending = line_ending;
+                        self.set_status(format!("Line endings: {}", line_ending.name()));
+                    }
+                    None => self.set_error("Usage: :set eol=lf|crlf"),
+                },
+                "style" => match StockholmStyle::from_name(value) {
+                    Some(style) => {
+                        self.write_options.style = style;
+                        self.set_status(match style {
+                            StockholmStyle::Stockholm => {
+                                "Stockholm output: classic (see :set wrap)"
+                            }
+                            StockholmStyle::Pfam => "Stockholm output: Pfam, one line per sequence",
+                        });
+                    }
+                    None => self.set_error("Usage: :set style=pfam|stockholm"),
+                },
+                "format" => {
+                    if value == "auto" {
+                        self.save_format = None;
+                        self.set_status("Save format: from file extension");
+                    } else if let Some(format) = Format::from_name(value) {
+                        self.save_format = Some(format);
+                        self.set_status(format!("Save format: {}", format.name()));
+                    } else {
+                        self.set_error(format!("Unknown format: {value}"));
                     }
                 }
                 _ => {
-                    self.set_status(format!("Unknown setting: {key}"));
+                    self.set_error(format!("Unknown setting: {key}"));
                 }
             }
+        } else {
+            match setting {
+                "readonly" | "ro" => self.set_read_only(true),
+                "noreadonly" | "noro" => self.set_read_only(false),
+                _ => self.set_error(format!("Usage: :set {setting}=<value>")),
+            }
         }
     }
 
+    /// Turn read-only mode on or off (`:set readonly`, `:set noreadonly`).
+    pub fn set_read_only(&mut self, read_only: bool) {
+        self.read_only = read_only;
+        self.set_status(if read_only {
+            "Read-only: navigation, coloring and search only (:set noreadonly to edit)"
+        } else {
+            "Editing is on"
+        });
+    }
+
     /// Execute alignment transformation commands. Returns true if handled.
     fn execute_transform_command(&mut self, parts: &[&str]) -> bool {
         match parts {
             ["upper" | "uppercase"] => {
                 self.uppercase_alignment();
-                self.set_status("Converted to uppercase");
                 true
             }
             ["lower" | "lowercase"] => {
                 self.lowercase_alignment();
-                self.set_status("Converted to lowercase");
+                true
+            }
+            ["colset", value, specs @ ..] => {
+                let mut chars = value.chars();
+                let ch = match (chars.next(), chars.next()) {
+                    (Some(ch), None) if ch.is_ascii_graphic() => ch,
+                    _ => {
+                        self.set_error("Usage: :colset <char> [rows], e.g. :colset N 3-7");
+                        return true;
+                    }
+                };
+                let rows = if !specs.is_empty() {
+                    self.parse_row_specs(specs)
+                } else if self.selection_anchor.is_some() {
+                    Ok(self.target_rows())
+                } else {
+                    Ok((0..self.alignment.num_sequences()).collect())
+                };
+                match rows {
+                    Ok(rows) => self.set_column(ch, &rows),
+                    Err(e) => self.set_error(e),
+                }
+                true
+            }
+            ["filter-cols", spec] | ["filter-cols", spec, "mask"] => {
+                match spec.strip_prefix("gaps>").map(str::parse::<f64>) {
+                    Some(Ok(t)) if (0.0..1.0).contains(&t) => {
+                        self.filter_gappy_columns(t, parts.len() == 3);
+                    }
+                    _ => self.set_error("Usage: :filter-cols gaps>F [mask] (F from 0 to 1)"),
+                }
+                true
+            }
+            ["mask", spec] => {
+                match spec
+                    .strip_prefix("pp<")
+                    .or_else(|| spec.strip_prefix("PP<"))
+                    .map(str::parse::<u8>)
+                {
+                    Some(Ok(t)) if t <= TRACK_MAX => self.mask_low_pp(t),
+                    _ => self.set_error("Usage: :mask pp<N (N from 0 to 10, where * = 10)"),
+                }
                 true
             }
             ["t2u"] => {
                 self.convert_t_to_u();
-                self.set_status("Converted T to U");
                 true
             }
             ["u2t"] => {
                 self.convert_u_to_t();
-                self.set_status("Converted U to T");
+                true
+            }
+            ["rename", new] => {
+                self.rename_id(None, new);
+                true
+            }
+            ["rename", old, new] => {
+                self.rename_id(Some(old), new);
+                true
+            }
+            ["rename", ..] => {
+                self.set_error("Usage: :rename [old] <new>");
+                true
+            }
+            ["move", row] => {
+                match row.parse::<usize>() {
+                    Ok(row) if row > 0 => self.move_sequence(row - 1),
+                    _ => self.set_error(format!("Invalid row: {row}")),
+                }
+                true
+            }
+            ["move", ..] => {
+                self.set_error("Usage: :move <row>");
+                true
+            }
+            ["new-seq"] => {
+                self.set_error("Usage: :new-seq <id> [residues]");
+                true
+            }
+            ["new-seq", id, residues @ ..] => {
+                self.new_sequence(id, &residues.concat());
                 true
             }
             ["trimleft"] => {
@@ -1247,45 +4634,392 @@ impl App {
                 self.trim();
                 true
             }
-            _ => false,
-        }
-    }
-
-    /// Execute clustering-related commands. Returns true if handled.
-    fn execute_clustering_command(&mut self, parts: &[&str]) -> bool {
-        match parts {
-            ["cluster"] => {
-                self.cluster_sequences();
-                self.set_status(format!(
-                    "Clustered {} sequences by similarity",
-                    self.alignment.num_sequences()
-                ));
+            ["throwcol", dir] | ["throwcol", dir, _] => {
+                let direction = match *dir {
+                    "left" | "l" => ShiftDirection::Left,
+                    "right" | "r" => ShiftDirection::Right,
+                    _ => {
+                        self.set_error("Usage: throwcol left|right [column]");
+                        return true;
+                    }
+                };
+                let col = match parts.get(2) {
+                    Some(n) => match n.parse::<usize>() {
+                        Ok(n) if n > 0 => n - 1,
+                        _ => {
+                            self.set_error(format!("Invalid column: {n}"));
+                            return true;
+                        }
+                    },
+                    None => self.cursor_col,
+                };
+                self.throw_column(col, direction);
                 true
             }
-            ["uncluster"] => {
-                self.uncluster();
-                self.set_status("Clustering disabled");
+            [cmd @ ("mergecol" | "mergecol!" | "splitcol"), rest @ ..] => {
+                let direction = match rest {
+                    [] | ["right" | "r"] => ShiftDirection::Right,
+                    ["left" | "l"] => ShiftDirection::Left,
+                    _ => {
+                        self.set_error(format!("Usage: {cmd} [left|right]"));
+                        return true;
+                    }
+                };
+                match *cmd {
+                    "splitcol" => self.split_column(direction),
+                    _ => self.merge_columns(direction, *cmd == "mergecol"),
+                }
                 true
             }
-            ["tree"] => {
-                self.toggle_tree();
-                if self.show_tree {
-                    self.set_status("Tree visible");
-                } else if self.cluster_tree.is_some() {
-                    self.set_status("Tree hidden");
+            [cmd @ ("pad5" | "pad3" | "truncate5" | "truncate3"), n] => {
+                let Ok(n) = n.parse::<usize>() else {
+                    self.set_error(format!("Usage: {cmd} <count>"));
+                    return true;
+                };
+                let end = if cmd.ends_with('5') {
+                    SequenceEnd::FivePrime
+                } else {
+                    SequenceEnd::ThreePrime
+                };
+                if cmd.starts_with("pad") {
+                    self.pad_sequences(end, n);
+                } else {
+                    self.truncate_sequences(end, n);
                 }
                 true
             }
-            ["collapse"] => {
-                self.toggle_collapse_identical();
+            [cmd @ ("pad5" | "pad3" | "truncate5" | "truncate3")] => {
+                self.set_error(format!("Usage: {cmd} <count>"));
                 true
             }
-            _ => false,
-        }
-    }
-
-    /// Toggle help display.
-    pub fn toggle_help(&mut self) {
+            ["hits"] => {
+                self.open_hit_viewer();
+                true
+            }
+            ["hits", "fetch", path] => {
+                self.write_hit_fetch_list(Path::new(path));
+                true
+            }
+            ["hits", "align", seqdb, model] => {
+                self.start_hit_alignment(Path::new(seqdb), Path::new(model));
+                true
+            }
+            ["hits", "align", "off"] => {
+                if self.hit_align_job.take().is_some() {
+                    self.set_status("Aligned hits will be ignored");
+                } else {
+                    self.set_error(":hits align is not running");
+                }
+                true
+            }
+            ["hits", path] => {
+                self.load_hits(Path::new(path));
+                true
+            }
+            ["contam", tblout] => {
+                self.screen_contamination(Path::new(tblout), None);
+                true
+            }
+            ["contam", tblout, clanin] => {
+                self.screen_contamination(Path::new(tblout), Some(Path::new(clanin)));
+                true
+            }
+            ["thresholds", path] => {
+                self.suggest_thresholds(Path::new(path));
+                true
+            }
+            ["flag"] => {
+                self.flag_sequence("TODO");
+                true
+            }
+            ["flag", label @ ..] => {
+                self.flag_sequence(&label.join(" "));
+                true
+            }
+            ["unflag"] => {
+                self.unflag_sequence();
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Execute clustering-related commands. Returns true if handled.
+    fn execute_clustering_command(&mut self, parts: &[&str]) -> bool {
+        match parts {
+            ["cluster"] => {
+                self.cancel_cluster_job();
+                self.cluster_sequences();
+                self.set_status(format!(
+                    "Clustered {} sequences by similarity",
+                    self.alignment.num_sequences()
+                ));
+                true
+            }
+            ["dedup"] => {
+                self.dedup(None);
+                true
+            }
+            ["dedup", pct] => {
+                match pct.trim_end_matches('%').parse::<f64>() {
+                    Ok(pct) if pct > 0.0 && pct <= 100.0 => self.dedup(Some(pct / 100.0)),
+                    _ => self.set_error("Usage: :dedup [identity%], e.g. :dedup 95"),
+                }
+                true
+            }
+            ["cluster", pct] => {
+                match pct.trim_end_matches('%').parse::<f64>() {
+                    Ok(pct) if pct > 0.0 && pct <= 100.0 => self.cluster_by_identity(pct / 100.0),
+                    _ => self.set_error(format!("Invalid identity: {pct} (use 1-100)")),
+                }
+                true
+            }
+            ["uncluster"] => {
+                let cancelled = self.cancel_cluster_job();
+                self.uncluster();
+                if self.identity_threshold.take().is_some() {
+                    self.precompute_collapse_groups();
+                    self.clamp_cursor();
+                }
+                self.set_status(if cancelled {
+                    "Clustering cancelled"
+                } else {
+                    "Clustering disabled"
+                });
+                true
+            }
+            ["clusters"] => {
+                self.show_clusters = !self.show_clusters;
+                true
+            }
+            ["pileup"] => {
+                self.open_pileup(self.cursor_col);
+                true
+            }
+            ["zscore"] => {
+                self.compute_fold_zscores(DEFAULT_SHUFFLES);
+                true
+            }
+            ["alifold"] => {
+                self.start_alifold();
+                true
+            }
+            ["alifold", "off"] => {
+                if self.alifold_job.take().is_some() {
+                    self.set_status("RNAalifold result will be ignored");
+                }
+                true
+            }
+            ["zscore", "off"] => {
+                if self.cancel_fold_job() {
+                    self.set_status("Folding cancelled");
+                }
+                self.show_zscores = false;
+                true
+            }
+            ["zscore", n] => {
+                match n.parse::<usize>() {
+                    Ok(n) if n >= 2 => self.compute_fold_zscores(n),
+                    _ => self.set_error("Usage: :zscore [shuffles >= 2] | off"),
+                }
+                true
+            }
+            ["wreps", path] => {
+                self.write_representatives(Path::new(path));
+                true
+            }
+            ["tree"] => {
+                self.toggle_tree();
+                if self.show_tree {
+                    self.set_status("Tree visible");
+                } else if self.cluster_tree.is_some() {
+                    self.set_status("Tree hidden");
+                }
+                true
+            }
+            ["collapse"] => {
+                self.toggle_collapse_identical();
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Group visible rows by their character at column `col`.
+    ///
+    /// Returns (character, display rows, sequence count) sorted by descending count, then
+    /// by character. Counts include collapsed duplicates.
+    pub fn column_groups(&self, col: usize) -> Vec<(char, Vec<usize>, usize)> {
+        let mut groups: Vec<(char, Vec<usize>, usize)> = Vec::new();
+        for display_row in 0..self.visible_sequence_count() {
+            let actual_row = self.display_to_actual_row(display_row);
+            let ch = self.alignment.get_char(actual_row, col).unwrap_or(' ');
+            let count = self.get_collapse_count(display_row);
+            match groups.iter_mut().find(|(c, _, _)| *c == ch) {
+                Some((_, rows, total)) => {
+                    rows.push(display_row);
+                    *total += count;
+                }
+                None => groups.push((ch, vec![display_row], count)),
+            }
+        }
+        groups.sort_by(|a, b| b.2.cmp(&a.2).then_with(|| a.0.cmp(&b.0)));
+        groups
+    }
+
+    /// Open the pileup view of a column, with every group folded.
+    pub fn open_pileup(&mut self, col: usize) {
+        if self.alignment.width() == 0 {
+            return;
+        }
+        let col = col.min(self.alignment.width() - 1);
+        let groups = self.column_groups(col);
+        self.pileup = Some(Pileup {
+            col,
+            expanded: vec![false; groups.len()],
+            groups,
+            cursor: 0,
+        });
+    }
+
+    /// Show the pileup of the column `delta` columns away, keeping it open.
+    pub fn shift_pileup(&mut self, delta: isize) {
+        if let Some(pileup) = &self.pileup {
+            let col = pileup.col.saturating_add_signed(delta);
+            self.open_pileup(col);
+        }
+    }
+
+    /// Move the pileup selection by `delta` lines.
+    pub fn move_pileup_cursor(&mut self, delta: isize) {
+        if let Some(pileup) = &mut self.pileup {
+            let last = pileup.lines().len().saturating_sub(1);
+            pileup.cursor = pileup.cursor.saturating_add_signed(delta).min(last);
+        }
+    }
+
+    /// Fold or unfold the ID list of the selected group (or of the group the
+    /// selected ID belongs to, selecting the group again).
+    pub fn toggle_pileup_group(&mut self) {
+        let Some(pileup) = &mut self.pileup else {
+            return;
+        };
+        let Some(&(group, _)) = pileup.lines().get(pileup.cursor) else {
+            return;
+        };
+        pileup.expanded[group] = !pileup.expanded[group];
+        pileup.cursor = pileup
+            .lines()
+            .iter()
+            .position(|&line| line == (group, None))
+            .unwrap_or(0);
+    }
+
+    /// Unfold every group, or fold them all if all are unfolded.
+    pub fn toggle_pileup_all(&mut self) {
+        if let Some(pileup) = &mut self.pileup {
+            let expand = !pileup.expanded.iter().all(|&e| e);
+            let group = pileup.lines().get(pileup.cursor).map_or(0, |l| l.0);
+            pileup.expanded.fill(expand);
+            pileup.cursor = pileup
+                .lines()
+                .iter()
+                .position(|&line| line == (group, None))
+                .unwrap_or(0);
+        }
+    }
+
+    /// Enter in the pileup view: jump to the selected sequence at the pileup
+    /// column, or fold/unfold the selected group.
+    pub fn pileup_enter(&mut self) {
+        let Some(pileup) = &self.pileup else {
+            return;
+        };
+        match pileup.lines().get(pileup.cursor) {
+            Some(&(group, Some(member))) => {
+                self.cursor_row = pileup.groups[group].1[member];
+                self.cursor_col = pileup.col;
+                self.pileup = None;
+                self.clamp_cursor();
+            }
+            Some(_) => self.toggle_pileup_group(),
+            None => {}
+        }
+    }
+
+    /// Open the helix view of a helix given as a 1-based number (`3` or `P3`),
+    /// or of the helix at the cursor, starting at the cursor's sequence.
+    pub fn open_helix_view(&mut self, helix: Option<&str>) {
+        let count = self.structure_cache.num_helices();
+        if count == 0 {
+            self.set_error("No helices in SS_cons");
+            return;
+        }
+        let helix = match helix {
+            None => match self.structure_cache.get_helix(self.cursor_col) {
+                Some(helix) => helix,
+                None => {
+                    self.set_error("Cursor is not on a helix (:helix N opens helix N)");
+                    return;
+                }
+            },
+            Some(spec) => match spec.trim_start_matches(['P', 'p']).parse::<usize>() {
+                Ok(h) if (1..=count).contains(&h) => h - 1,
+                _ => {
+                    self.set_error(format!("Invalid helix: {spec} (1-{count})"));
+                    return;
+                }
+            },
+        };
+        self.helix_view = Some(HelixView {
+            helix,
+            top: self.cursor_row,
+        });
+    }
+
+    /// Show the helix `delta` helices away in the helix view.
+    pub fn shift_helix_view(&mut self, delta: isize) {
+        let last = self.structure_cache.num_helices().saturating_sub(1);
+        if let Some(view) = &mut self.helix_view {
+            view.helix = view.helix.saturating_add_signed(delta).min(last);
+        }
+    }
+
+    /// Scroll the helix view by `delta` sequences.
+    pub fn scroll_helix_view(&mut self, delta: isize) {
+        let last = self.visible_sequence_count().saturating_sub(1);
+        if let Some(view) = &mut self.helix_view {
+            view.top = view.top.saturating_add_signed(delta).min(last);
+        }
+    }
+
+    /// Enter in the helix view: go to the first sequence shown, at the helix's
+    /// outermost pair, and close the view.
+    pub fn helix_view_enter(&mut self) {
+        let Some(view) = self.helix_view.take() else {
+            return;
+        };
+        if let Some((left, _)) = self.structure_cache.helix_pair(view.helix, 0) {
+            self.cursor_row = view.top;
+            self.cursor_col = left;
+            self.clamp_cursor();
+        }
+    }
+
+    /// Toggle the cursor column popup.
+    pub fn toggle_column_popup(&mut self) {
+        self.show_column_popup = !self.show_column_popup;
+        self.column_popup_top = 0;
+    }
+
+    /// Scroll the rows listed in the column popup by `delta`.
+    pub fn scroll_column_popup(&mut self, delta: isize) {
+        let last = self.visible_sequence_count().saturating_sub(1);
+        self.column_popup_top = self.column_popup_top.saturating_add_signed(delta).min(last);
+    }
+
+    /// Toggle help display.
+    pub fn toggle_help(&mut self) {
         self.show_help = !self.show_help;
     }
 
@@ -1356,142 +5090,2054 @@ impl App {
         self.search.history_next();
     }
 
-    /// Mark the alignment as modified.
-    pub fn mark_modified(&mut self) {
-        self.modified = true;
+    /// Score every column by pairwise consistency and show the confidence bar.
+    pub fn compute_column_confidence(&mut self) {
+        if self.alignment.num_sequences() < 2 {
+            self.set_error("Column confidence needs at least two sequences");
+            return;
+        }
+        let sequences: Vec<&[char]> = self.alignment.sequences.iter().map(|s| s.chars()).collect();
+        let scores = crate::confidence::column_confidence(&sequences, &self.gap_chars);
+
+        let scored: Vec<f64> = scores.iter().flatten().copied().collect();
+        let low = scored.iter().filter(|&&s| s < 0.5).count();
+        let mean = scored.iter().sum::<f64>() / scored.len().max(1) as f64;
+        self.column_confidence = Some(scores);
+        self.show_confidence_bar = true;
+        self.set_status(format!(
+            "Column confidence: mean {:.0}%, {low} of {} columns below 50%",
+            mean * 100.0,
+            scored.len()
+        ));
     }
 
-    /// Update the structure cache if needed.
-    pub fn update_structure_cache(&mut self) {
-        if let Some(ss) = self.alignment.ss_cons()
-            && !self.structure_cache.is_valid_for(ss)
-            && let Err(e) = self.structure_cache.update(ss)
-        {
-            // Structure parsing failed - show status to user
-            self.set_status(format!("Warning: SS_cons parse error: {e}"));
-        }
+    /// Forget the column confidence scores, which no longer match the columns
+    /// after an edit or undo; `:confidence` scores them again.
+    pub(crate) fn clear_column_confidence(&mut self) {
+        self.column_confidence = None;
+        self.show_confidence_bar = false;
     }
 
-    /// Ensure cursor is within bounds.
-    pub fn clamp_cursor(&mut self) {
-        let max_row = self.visible_sequence_count().saturating_sub(1);
-        self.cursor_row = self.cursor_row.min(max_row);
+    /// Confidence of a column, if computed.
+    pub fn column_confidence(&self, col: usize) -> Option<f64> {
+        self.column_confidence.as_ref()?.get(col).copied().flatten()
+    }
 
-        if self.hide_gap_columns && !self.visible_columns.is_empty() {
-            // Ensure cursor is on a visible column
-            let max_display_col = self.visible_columns.len().saturating_sub(1);
-            if self.actual_to_display_col(self.cursor_col).is_none() {
-                // Snap to nearest visible column
-                self.cursor_col = self
-                    .visible_columns
-                    .first()
-                    .copied()
-                    .unwrap_or(0)
-                    .min(self.display_to_actual_col(max_display_col));
+    /// Bookmark the visual selection's top-left corner, or else the top-left of
+    /// the view.
+    pub fn add_bookmark(&mut self) {
+        let (display_row, col) = match self.get_selection_bounds() {
+            Some((min_row, min_col, _, _)) => (min_row, min_col),
+            None => {
+                let col = if self.hide_gap_columns && !self.visible_columns.is_empty() {
+                    self.visible_columns
+                        .get(self.viewport_col)
+                        .copied()
+                        .unwrap_or(self.cursor_col)
+                } else {
+                    self.viewport_col
+                };
+                (self.viewport_row, col)
             }
+        };
+        let row = self.display_to_actual_row(display_row);
+        let Some(id) = self.alignment.sequences.get(row).map(|s| s.id.clone()) else {
+            self.set_error("No sequences to bookmark");
+            return;
+        };
+        self.set_status(format!(
+            "Bookmark {} at {id}, column {}",
+            self.bookmarks.len() + 1,
+            col + 1
+        ));
+        self.bookmarks.push(Mark { row, id, col });
+    }
+
+    /// Show a bookmark at the top-left of the view, with the cursor on it.
+    fn show_bookmark(&mut self, index: usize) {
+        let Some(bookmark) = self.bookmarks.get(index).cloned() else {
+            return;
+        };
+        self.goto_mark_position(&bookmark, false);
+        self.viewport_row = self.cursor_row;
+        self.viewport_col = if self.hide_gap_columns && !self.visible_columns.is_empty() {
+            self.actual_to_display_col(self.cursor_col).unwrap_or(0)
         } else {
-            let max_col = self.alignment.width().saturating_sub(1);
-            self.cursor_col = self.cursor_col.min(max_col);
+            self.cursor_col
+        };
+        if let Some(presentation) = &mut self.presentation {
+            presentation.slide = Some(index);
+            presentation.shown_at = Instant::now();
         }
     }
 
-    /// Adjust viewport to keep cursor visible.
-    pub fn adjust_viewport(&mut self, visible_rows: usize, visible_cols: usize) {
-        // Vertical scrolling
-        if self.cursor_row < self.viewport_row {
-            self.viewport_row = self.cursor_row;
-        } else if self.cursor_row >= self.viewport_row + visible_rows {
-            self.viewport_row = self.cursor_row - visible_rows + 1;
+    /// Go to the next (or previous) bookmark, wrapping around.
+    pub fn step_bookmark(&mut self, forward: bool) {
+        let count = self.bookmarks.len();
+        if count == 0 {
+            self.set_error("No bookmarks (:bookmark saves the view)");
+            return;
         }
+        let current = self
+            .presentation
+            .as_ref()
+            .and_then(|p| p.slide)
+            .or_else(|| {
+                let here = self.cursor_mark()?;
+                self.bookmarks.iter().position(|b| *b == here)
+            });
+        let next = match (current, forward) {
+            (None, true) => 0,
+            (None, false) => count - 1,
+            (Some(i), true) => (i + 1) % count,
+            (Some(i), false) => (i + count - 1) % count,
+        };
+        self.show_bookmark(next);
+        self.set_status(format!("Bookmark {}/{count}", next + 1));
+    }
 
-        // Horizontal scrolling
-        if self.hide_gap_columns && !self.visible_columns.is_empty() {
-            // When hiding, viewport_col is in display column space
-            if let Some(cursor_display_col) = self.actual_to_display_col(self.cursor_col) {
-                if cursor_display_col < self.viewport_col {
-                    self.viewport_col = cursor_display_col;
-                } else if cursor_display_col >= self.viewport_col + visible_cols {
-                    self.viewport_col = cursor_display_col - visible_cols + 1;
-                }
-            }
+    /// The cursor position as a mark, if the alignment has sequences.
+    pub(crate) fn cursor_mark(&self) -> Option<Mark> {
+        let row = self.display_to_actual_row(self.cursor_row);
+        let seq = self.alignment.sequences.get(row)?;
+        Some(Mark {
+            row,
+            id: seq.id.clone(),
+            col: self.cursor_col,
+        })
+    }
+
+    /// Display row of a mark's sequence: its row if the ID still matches, or
+    /// else wherever that ID is now. None if the sequence is gone or hidden.
+    fn mark_display_row(&self, mark: &Mark) -> Option<usize> {
+        let sequences = &self.alignment.sequences;
+        let row = if sequences.get(mark.row).is_some_and(|s| s.id == mark.id) {
+            mark.row
         } else {
-            // Normal mode - viewport_col is actual column
-            if self.cursor_col < self.viewport_col {
-                self.viewport_col = self.cursor_col;
-            } else if self.cursor_col >= self.viewport_col + visible_cols {
-                self.viewport_col = self.cursor_col - visible_cols + 1;
+            sequences.iter().position(|s| s.id == mark.id)?
+        };
+        self.actual_to_display_row(row)
+    }
+
+    /// Move the cursor to a mark (its column too unless `keep_col`), or say why
+    /// not if the mark's sequence is gone or hidden.
+    fn goto_mark_position(&mut self, mark: &Mark, keep_col: bool) {
+        let Some(row) = self.mark_display_row(mark) else {
+            self.set_error(format!("{} is deleted or hidden", mark.id));
+            return;
+        };
+        self.cursor_row = row;
+        if !keep_col {
+            self.cursor_col = mark.col;
+        }
+        self.clamp_cursor();
+    }
+
+    /// Set mark `name` (`m{a-z}`) at the cursor.
+    pub fn set_mark(&mut self, name: char) {
+        if !name.is_ascii_lowercase() {
+            self.set_error("Marks are a-z");
+            return;
+        }
+        let Some(mark) = self.cursor_mark() else {
+            return;
+        };
+        self.set_status(format!("Mark {name}: {} column {}", mark.id, mark.col + 1));
+        self.marks.insert(name, mark);
+    }
+
+    /// Jump to mark `name`: its row and column (`` `a ``), or with `row_only` its
+    /// row in the cursor column (`'a`).
+    pub fn goto_mark(&mut self, name: char, row_only: bool) {
+        let Some(mark) = self.marks.get(&name).cloned() else {
+            self.set_error(format!("Mark {name} not set"));
+            return;
+        };
+        self.goto_mark_position(&mark, row_only);
+    }
+
+    /// Remember `from` in the jump list if the cursor has since moved away from
+    /// it, dropping any positions stepped back over with `Ctrl-o`.
+    pub(crate) fn record_jump(&mut self, from: Mark) {
+        if self.cursor_mark().is_none_or(|now| now == from) {
+            return;
+        }
+        self.jumps.truncate(self.jump_index);
+        self.jumps.retain(|jump| *jump != from);
+        self.jumps.push(from);
+        if self.jumps.len() > MAX_JUMPS {
+            self.jumps.remove(0);
+        }
+        self.jump_index = self.jumps.len();
+    }
+
+    /// Go back to the position before the last jump (`Ctrl-o`).
+    pub fn jump_back(&mut self) {
+        if self.jump_index == 0 {
+            self.set_error("Already at the oldest jump");
+            return;
+        }
+        // Save where we are, so that Ctrl-i can come back to it
+        if self.jump_index == self.jumps.len()
+            && let Some(here) = self.cursor_mark()
+        {
+            self.jumps.push(here);
+        }
+        self.jump_index -= 1;
+        let mark = self.jumps[self.jump_index].clone();
+        self.goto_mark_position(&mark, false);
+    }
+
+    /// Go forward again through positions left with `Ctrl-o` (`Ctrl-i`/Tab).
+    pub fn jump_forward(&mut self) {
+        if self.jump_index + 1 >= self.jumps.len() {
+            self.set_error("Already at the newest jump");
+            return;
+        }
+        self.jump_index += 1;
+        let mark = self.jumps[self.jump_index].clone();
+        self.goto_mark_position(&mark, false);
+        // Back at the newest position: no longer stepping through the list
+        if self.jump_index + 1 == self.jumps.len() {
+            self.jumps.pop();
+            self.jump_index = self.jumps.len();
+        }
+    }
+
+    /// List the marks set with `m{a-z}` (`:marks`).
+    pub fn marks_report(&mut self) {
+        if self.marks.is_empty() {
+            self.set_error("No marks set (m{a-z} sets one)");
+            return;
+        }
+        let width = self.marks.values().map(|m| m.id.len()).max().unwrap_or(0);
+        let lines = self
+            .marks
+            .iter()
+            .map(|(name, mark)| {
+                let hidden = if self.mark_display_row(mark).is_some() {
+                    ""
+                } else {
+                    "  (deleted or hidden)"
+                };
+                format!(
+                    "{name}  {:width$}  column {}{hidden}",
+                    mark.id,
+                    mark.col + 1
+                )
+            })
+            .collect();
+        self.report = Some(Report {
+            title: "Marks".to_string(),
+            lines,
+        });
+    }
+
+    /// Enter presentation mode, showing the first bookmark if there are any and
+    /// advancing every `seconds` if given.
+    pub fn start_presentation(&mut self, seconds: Option<u64>) {
+        self.presentation = Some(Presentation {
+            slide: None,
+            auto_advance: seconds.filter(|&s| s > 0).map(Duration::from_secs),
+            shown_at: Instant::now(),
+        });
+        if !self.bookmarks.is_empty() {
+            self.show_bookmark(0);
+        }
+    }
+
+    /// Leave presentation mode.
+    pub fn stop_presentation(&mut self) {
+        self.presentation = None;
+        self.set_status("Presentation mode off");
+    }
+
+    /// Advance the presentation if its auto-advance interval has passed (called
+    /// from the main loop).
+    pub fn tick_presentation(&mut self) {
+        let due = self.presentation.as_ref().is_some_and(|p| {
+            p.auto_advance
+                .is_some_and(|interval| p.shown_at.elapsed() >= interval)
+        });
+        if due && self.mode == Mode::Normal && !self.bookmarks.is_empty() {
+            self.step_bookmark(true);
+        }
+    }
+
+    /// Show a numeric #=GC track as a graph and start editing it, creating the row
+    /// (with no values) if the alignment has none.
+    pub fn open_track(&mut self, tag: &str) {
+        if self.alignment.num_sequences() == 0 {
+            self.set_error("No alignment loaded");
+            return;
+        }
+        match self.alignment.column_annotation(tag) {
+            Some(data) => {
+                let numeric = data
+                    .chars()
+                    .all(|c| track_level(c).is_some() || self.gap_chars.contains(&c));
+                if !numeric {
+                    self.set_error(format!(
+                        "#=GC {tag} is not a numeric track (values 0-9 and *)"
+                    ));
+                    return;
+                }
+            }
+            None => {
+                if !self.begin_edit() {
+                    return;
+                }
+                self.alignment.column_annotations.push(ColumnAnnotation {
+                    tag: tag.to_string(),
+                    data: ".".repeat(self.alignment.width()),
+                });
+                self.mark_modified();
+            }
+        }
+        self.track = Some(tag.to_string());
+        self.mode = Mode::Track;
+        self.set_status(format!(
+            "-- TRACK {tag} -- +/- change, 0-9/* set, . clear, Esc leaves"
+        ));
+    }
+
+    /// Tag and data of the numeric track being shown, if its row exists.
+    pub fn track_row(&self) -> Option<(&str, &str)> {
+        let tag = self.track.as_deref()?;
+        Some((tag, self.alignment.column_annotation(tag)?))
+    }
+
+    /// Value of the numeric track at a column (None for gaps or no track).
+    pub fn track_value(&self, col: usize) -> Option<u8> {
+        let (_, data) = self.track_row()?;
+        data.chars().nth(col).and_then(track_level)
+    }
+
+    /// Remove columns whose track value is below `threshold` (columns with no
+    /// value are kept).
+    pub fn mask_track(&mut self, threshold: u8) {
+        let Some((tag, data)) = self.track_row() else {
+            self.set_error("No track shown (use :track <tag>)");
+            return;
+        };
+        let tag = tag.to_string();
+        let cols: Vec<usize> = data
+            .chars()
+            .enumerate()
+            .filter(|&(_, c)| track_level(c).is_some_and(|level| level < threshold))
+            .map(|(col, _)| col)
+            .collect();
+        if cols.is_empty() {
+            self.set_status(format!("No columns with {tag} below {threshold}"));
+            return;
+        }
+        self.remove_masked_columns(&cols);
+        self.set_status(format!(
+            "Masked {} columns with {tag} below {threshold}",
+            cols.len()
+        ));
+    }
+
+    /// Remove columns scoring below `threshold`, unpairing any SS_cons partners kept.
+    pub fn mask_low_confidence(&mut self, threshold: f64) {
+        let Some(scores) = self.column_confidence.as_ref() else {
+            self.set_error("No confidence scores (run :confidence first)");
+            return;
+        };
+        let cols: Vec<usize> = scores
+            .iter()
+            .enumerate()
+            .filter(|(_, s)| s.is_some_and(|s| s < threshold))
+            .map(|(col, _)| col)
+            .collect();
+        if cols.is_empty() {
+            self.set_status(format!(
+                "No columns below {:.0}% confidence",
+                threshold * 100.0
+            ));
+            return;
+        }
+        self.remove_masked_columns(&cols);
+        self.set_status(format!(
+            "Masked {} columns below {:.0}% confidence",
+            cols.len(),
+            threshold * 100.0
+        ));
+    }
+
+    /// Remove the sorted columns `cols`, unpairing any SS_cons partners kept.
+    fn remove_masked_columns(&mut self, cols: &[usize]) {
+        if !self.begin_edit() {
+            return;
+        }
+        let partners: Vec<usize> = cols
+            .iter()
+            .filter_map(|&col| self.structure_cache.get_pair(col))
+            .filter(|partner| cols.binary_search(partner).is_err())
+            .collect();
+        if let Some(ss) = self.alignment.ss_cons_mut()
+            && !partners.is_empty()
+        {
+            let mut chars: Vec<char> = ss.chars().collect();
+            for partner in partners {
+                if let Some(c) = chars.get_mut(partner) {
+                    *c = '.';
+                }
+            }
+            *ss = chars.into_iter().collect();
+        }
+        self.alignment.remove_columns(cols);
+        self.columns_removed(cols);
+        // Scores of the columns kept still hold, so the bar stays up
+        let kept = self.column_confidence.take().map(|mut scores| {
+            let mut col = 0;
+            scores.retain(|_| {
+                col += 1;
+                cols.binary_search(&(col - 1)).is_err()
+            });
+            scores
+        });
+        self.mark_modified();
+        if kept.is_some() {
+            self.column_confidence = kept;
+            self.show_confidence_bar = true;
+        }
+        self.update_structure_cache();
+        self.clamp_cursor();
+    }
+
+    /// The display row and column of the residue drawn at screen cell `(x, y)`
+    /// in the last frame.
+    pub fn cell_at(&self, x: u16, y: u16) -> Option<(usize, usize)> {
+        self.drawn_cells.borrow().iter().find_map(|strip| {
+            if x < strip.x || y < strip.y {
+                return None;
+            }
+            let line = usize::from(y - strip.y);
+            let col = strip
+                .cols
+                .get(usize::from((x - strip.x) / strip.cell_width.max(1)))?;
+            (line < strip.rows).then_some((strip.first_row + line, *col))
+        })
+    }
+
+    /// Handle the mouse at `(x, y)`: a Ctrl- or Alt-click (`clicked`), or with
+    /// `:set hover=on` any move, shows the popup for the residue there. Moving
+    /// off the residue hides it.
+    pub fn mouse_hover(&mut self, x: u16, y: u16, clicked: bool) {
+        let cell = self.cell_at(x, y);
+        let same_cell = self
+            .hover
+            .is_some_and(|h| Some((h.display_row, h.col)) == cell);
+        if clicked || self.hover_on_move {
+            self.hover = cell.map(|(display_row, col)| Hover {
+                x,
+                y,
+                display_row,
+                col,
+            });
+        } else if !same_cell {
+            self.hover = None;
+        }
+    }
+
+    /// Lines of the hover popup: the sequence, the residue's ungapped position
+    /// (and source coordinate for `/start-end` IDs), the column, its pair partner
+    /// and its conservation.
+    pub fn hover_lines(&self, hover: &Hover) -> Vec<String> {
+        let Some(seq) = self
+            .alignment
+            .sequences
+            .get(self.display_to_actual_row(hover.display_row))
+        else {
+            return Vec::new();
+        };
+        let mut lines = vec![seq.id.clone()];
+        let ch = seq.get(hover.col).unwrap_or(' ');
+        if self.gap_chars.contains(&ch) {
+            lines.push(format!("gap '{ch}'"));
+        } else {
+            let position = seq.chars()[..hover.col]
+                .iter()
+                .filter(|c| !self.gap_chars.contains(c))
+                .count()
+                + 1;
+            let coordinate = match parse_coords(&seq.id) {
+                Some((_, start, end)) if start <= end => {
+                    format!(" (coordinate {})", start + position - 1)
+                }
+                Some((_, start, _)) => {
+                    format!(" (coordinate {})", start.saturating_sub(position - 1))
+                }
+                None => String::new(),
+            };
+            lines.push(format!("residue {position}: {ch}{coordinate}"));
+        }
+        lines.push(format!("column {}", hover.col + 1));
+        if let Some(partner) = self.structure_cache.get_pair(hover.col) {
+            let partner_ch = seq.get(partner).unwrap_or(' ');
+            lines.push(format!("pairs with column {} ({partner_ch})", partner + 1));
+        } else if self.alignment.ss_cons().is_some() {
+            lines.push("unpaired".to_string());
+        }
+        let stats = column_stats(hover.col, &self.alignment, &self.gap_chars);
+        lines.push(format!(
+            "conservation {:.0}% ({})",
+            stats.conservation * 100.0,
+            stats.consensus
+        ));
+        lines
+    }
+
+    /// Mark the alignment as modified, keeping annotation rows at the alignment
+    /// width unless a loaded file's mismatched rows are being left alone.
+    /// Inside `repeat_edit` this happens once, when the repeats are done.
+    pub fn mark_modified(&mut self) {
+        if let Some(modified) = &mut self.repeat_modified {
+            *modified = true;
+            return;
+        }
+        self.mark_residues_modified();
+        // Checked before annotation rows are fitted, which would hide an edit
+        // that left them the wrong width
+        if (cfg!(debug_assertions) || self.paranoid) && self.integrity_problems.is_empty() {
+            self.integrity_problems = self.alignment.width_problems(self.lock_annotation_widths);
+        }
+        if self.lock_annotation_widths {
+            self.alignment.fit_annotations_to_width();
+        }
+    }
+
+    /// Mark the alignment as modified by an edit that leaves every row its
+    /// width, such as a shift, without the width checks of `mark_modified`.
+    pub(crate) fn mark_residues_modified(&mut self) {
+        if let Some(modified) = &mut self.repeat_modified {
+            *modified = true;
+            return;
+        }
+        self.modified = true;
+        self.edits += 1;
+        self.clear_column_confidence();
+    }
+
+    /// Report rows the last edits left the wrong width (`:set paranoid=on`);
+    /// debug builds assert there are none.
+    pub fn check_integrity(&mut self) {
+        if self.integrity_problems.is_empty() {
+            return;
+        }
+        let problems = std::mem::take(&mut self.integrity_problems);
+        let width = self.alignment.width();
+        for problem in &problems {
+            tracing::error!(problem = problem.as_str(), width, "integrity check failed");
+        }
+        debug_assert!(
+            self.paranoid,
+            "rows left the wrong width (alignment {width}): {problems:?}"
+        );
+        let more = match problems.len() {
+            1 => String::new(),
+            n => format!(" (+{} more)", n - 1),
+        };
+        self.set_status(format!(
+            "Integrity check: {}, the alignment {width}{more}; u undoes the edit",
+            problems[0]
+        ));
+    }
+
+    /// Name of the coloring, e.g. `base` or `base+conservation`.
+    pub fn color_name(&self) -> String {
+        match &self.color_overlay {
+            Some(overlay) => format!("{}+{}", self.color_scheme.as_ref(), overlay.name()),
+            None => self.color_scheme.as_ref().to_string(),
+        }
+    }
+
+    /// Whether a cell gets the color scheme: always without a `:focus`, otherwise
+    /// only inside the focused block or helices.
+    pub fn in_color_focus(&self, actual_row: usize, col: usize) -> bool {
+        match &self.color_focus {
+            None => true,
+            Some(ColorFocus::Region { ids, cols }) => {
+                col >= cols.0
+                    && col <= cols.1
+                    && self
+                        .alignment
+                        .sequences
+                        .get(actual_row)
+                        .is_some_and(|seq| ids.binary_search(&seq.id).is_ok())
+            }
+            Some(ColorFocus::Helices(starts)) => {
+                self.structure_cache.get_helix(col).is_some_and(|helix| {
+                    starts
+                        .iter()
+                        .any(|&start| self.structure_cache.get_helix(start) == Some(helix))
+                })
+            }
+        }
+    }
+
+    /// Limit coloring to the visual selection carried into command mode.
+    pub fn focus_selection(&mut self) {
+        let Some((_, min_col, _, max_col)) = self.get_selection_bounds() else {
+            self.set_error("No selection (select a block with v, then :focus)");
+            return;
+        };
+        let mut ids: Vec<String> = self
+            .target_rows()
+            .into_iter()
+            .map(|row| self.alignment.sequences[row].id.clone())
+            .collect();
+        ids.sort_unstable();
+        ids.dedup();
+        self.set_status(format!(
+            "Coloring {} seqs x {} columns (:focus off to color everything)",
+            ids.len(),
+            max_col - min_col + 1
+        ));
+        self.color_focus = Some(ColorFocus::Region {
+            ids,
+            cols: (min_col, max_col),
+        });
+    }
+
+    /// Limit coloring to SS_cons helices, given as 1-based numbers in 5' order;
+    /// with none given, toggle the helix at the cursor in the focused set.
+    pub fn focus_helices(&mut self, numbers: &[&str]) {
+        let count = self.structure_cache.num_helices();
+        if count == 0 {
+            self.set_error("No helices in SS_cons");
+            return;
+        }
+        let mut helices: Vec<usize> = match &self.color_focus {
+            Some(ColorFocus::Helices(starts)) if numbers.is_empty() => starts
+                .iter()
+                .filter_map(|&start| self.structure_cache.get_helix(start))
+                .collect(),
+            _ => Vec::new(),
+        };
+        if numbers.is_empty() {
+            let Some(helix) = self.structure_cache.get_helix(self.cursor_col) else {
+                self.set_error("Cursor is not on a helix");
+                return;
+            };
+            match helices.iter().position(|&h| h == helix) {
+                Some(i) => {
+                    helices.remove(i);
+                }
+                None => helices.push(helix),
+            }
+        } else {
+            for number in numbers {
+                match number.parse::<usize>() {
+                    Ok(n) if (1..=count).contains(&n) => helices.push(n - 1),
+                    _ => {
+                        self.set_error(format!("Invalid helix: {number} (1-{count})"));
+                        return;
+                    }
+                }
+            }
+        }
+        helices.sort_unstable();
+        helices.dedup();
+        if helices.is_empty() {
+            self.color_focus = None;
+            self.set_status("Color focus: off");
+            return;
+        }
+        let list: Vec<String> = helices.iter().map(|h| (h + 1).to_string()).collect();
+        self.set_status(format!("Coloring helix {}", list.join(", ")));
+        let starts = helices
+            .iter()
+            .filter_map(|&helix| self.structure_cache.helix_pair(helix, 0))
+            .map(|(left, _)| left)
+            .collect();
+        self.color_focus = Some(ColorFocus::Helices(starts));
+    }
+
+    /// Pin columns, given as 1-based numbers or `N-M` ranges, so they stay at
+    /// the left of the alignment while the rest scrolls; with none given, pin
+    /// the selected columns or toggle the cursor column.
+    pub fn pin_columns(&mut self, specs: &[&str]) {
+        let cols = if !specs.is_empty() {
+            match self.parse_column_specs(specs) {
+                Ok(cols) => cols,
+                Err(message) => {
+                    self.set_error(message);
+                    return;
+                }
+            }
+        } else if let Some((_, min_col, _, max_col)) = self.get_selection_bounds() {
+            (min_col..=max_col).collect()
+        } else if self.pinned_columns.contains(&self.cursor_col) {
+            let cursor_col = self.cursor_col;
+            self.pinned_columns.retain(|&col| col != cursor_col);
+            self.report_pinned();
+            return;
+        } else {
+            vec![self.cursor_col]
+        };
+        self.pinned_columns.extend(cols);
+        self.pinned_columns.sort_unstable();
+        self.pinned_columns.dedup();
+        self.report_pinned();
+    }
+
+    /// Unpin columns given as in `pin_columns`.
+    pub fn unpin_columns(&mut self, specs: &[&str]) {
+        let cols = match self.parse_column_specs(specs) {
+            Ok(cols) => cols,
+            Err(message) => {
+                self.set_error(message);
+                return;
+            }
+        };
+        self.pinned_columns.retain(|col| !cols.contains(col));
+        self.report_pinned();
+    }
+
+    /// The pins and the `:focus`, for the undo history.
+    pub(crate) fn column_state(&self) -> ColumnState {
+        ColumnState {
+            pinned_columns: self.pinned_columns.clone(),
+            color_focus: self.color_focus.clone(),
+        }
+    }
+
+    /// Keep pins and the `:focus` on their columns when `n` columns are
+    /// inserted before `col`.
+    pub(crate) fn columns_inserted(&mut self, col: usize, n: usize) {
+        let shift = |c: &mut usize| {
+            if *c >= col {
+                *c += n;
+            }
+        };
+        self.pinned_columns.iter_mut().for_each(shift);
+        match &mut self.color_focus {
+            // Columns inserted inside the region become part of it
+            Some(ColorFocus::Region { cols, .. }) => {
+                shift(&mut cols.0);
+                shift(&mut cols.1);
+            }
+            Some(ColorFocus::Helices(starts)) => starts.iter_mut().for_each(shift),
+            None => {}
+        }
+    }
+
+    /// Unpin the removed columns `cols`, sorted, and move the pins and the
+    /// `:focus` after them left.
+    pub(crate) fn columns_removed(&mut self, cols: &[usize]) {
+        let before = |c: usize| cols.partition_point(|&removed| removed < c);
+        self.pinned_columns
+            .retain(|col| cols.binary_search(col).is_err());
+        for pin in &mut self.pinned_columns {
+            *pin -= before(*pin);
+        }
+        match &mut self.color_focus {
+            Some(ColorFocus::Region { cols: range, .. }) => {
+                let (start, end) = (range.0 - before(range.0), range.1 + 1 - before(range.1 + 1));
+                if end > start {
+                    *range = (start, end - 1);
+                } else {
+                    self.color_focus = None;
+                }
+            }
+            // A helix whose 5' column is removed is found again by the next one
+            Some(ColorFocus::Helices(starts)) => {
+                for start in starts.iter_mut() {
+                    *start -= before(*start);
+                }
+            }
+            None => {}
+        }
+    }
+
+    /// 0-based columns from 1-based numbers and `N-M` ranges.
+    fn parse_column_specs(&self, specs: &[&str]) -> Result<Vec<usize>, String> {
+        let width = self.alignment.width();
+        let parse = |s: &str| match s.parse::<usize>() {
+            Ok(n) if (1..=width).contains(&n) => Some(n - 1),
+            _ => None,
+        };
+        let mut cols = Vec::new();
+        for spec in specs {
+            let range = match spec.split_once('-') {
+                Some((start, end)) => parse(start).zip(parse(end)),
+                None => parse(spec).map(|col| (col, col)),
+            };
+            match range {
+                Some((start, end)) if start <= end => cols.extend(start..=end),
+                _ => return Err(format!("Invalid column: {spec} (1-{width})")),
+            }
+        }
+        Ok(cols)
+    }
+
+    /// Actual rows of display row numbers and ranges such as `3` or `5-9`.
+    fn parse_row_specs(&self, specs: &[&str]) -> Result<Vec<usize>, String> {
+        let count = self.visible_sequence_count();
+        let parse = |s: &str| match s.parse::<usize>() {
+            Ok(n) if (1..=count).contains(&n) => Some(n - 1),
+            _ => None,
+        };
+        let mut rows = Vec::new();
+        for spec in specs {
+            let range = match spec.split_once('-') {
+                Some((start, end)) => parse(start).zip(parse(end)),
+                None => parse(spec).map(|row| (row, row)),
+            };
+            match range {
+                Some((start, end)) if start <= end => {
+                    rows.extend((start..=end).map(|row| self.display_to_actual_row(row)));
+                }
+                _ => return Err(format!("Invalid row: {spec} (1-{count})")),
+            }
+        }
+        rows.sort_unstable();
+        rows.dedup();
+        Ok(rows)
+    }
+
+    /// Show the pinned columns in the status line.
+    fn report_pinned(&mut self) {
+        if self.pinned_columns.is_empty() {
+            self.set_status("Unpinned all columns");
+            return;
+        }
+        let list: Vec<String> = self
+            .pinned_columns
+            .iter()
+            .map(|col| (col + 1).to_string())
+            .collect();
+        self.set_status(format!(
+            "Pinned column {} (:pin off to unpin)",
+            list.join(", ")
+        ));
+    }
+
+    /// Screen columns taken by the pinned columns and their separator.
+    pub fn pinned_display_width(&self) -> usize {
+        let width = self.alignment.width();
+        match self
+            .pinned_columns
+            .iter()
+            .filter(|&&col| col < width)
+            .count()
+        {
+            0 => 0,
+            n => n + 1,
+        }
+    }
+
+    /// Update the structure cache if needed.
+    pub fn update_structure_cache(&mut self) {
+        if let Some(ss) = self.alignment.ss_cons()
+            && !self.structure_cache.is_valid_for(ss)
+            && let Err(e) = self.structure_cache.update(ss)
+        {
+            // Structure parsing failed - show status to user
+            self.set_status(format!("Warning: SS_cons parse error: {e}"));
+        }
+    }
+
+    /// Ensure cursor is within bounds.
+    pub fn clamp_cursor(&mut self) {
+        let max_row = self.visible_sequence_count().saturating_sub(1);
+        self.cursor_row = self.cursor_row.min(max_row);
+
+        if self.hide_gap_columns && !self.visible_columns.is_empty() {
+            // Ensure cursor is on a visible column
+            let max_display_col = self.visible_columns.len().saturating_sub(1);
+            if self.actual_to_display_col(self.cursor_col).is_none() {
+                // Snap to nearest visible column
+                self.cursor_col = self
+                    .visible_columns
+                    .first()
+                    .copied()
+                    .unwrap_or(0)
+                    .min(self.display_to_actual_col(max_display_col));
+            }
+        } else {
+            let max_col = self.alignment.width().saturating_sub(1);
+            self.cursor_col = self.cursor_col.min(max_col);
+        }
+    }
+
+    /// Adjust viewport to keep cursor visible.
+    pub fn adjust_viewport(&mut self, visible_rows: usize, visible_cols: usize) {
+        // A terminal too small for any rows or columns still scrolls one at a time
+        let visible_rows = visible_rows.max(1);
+        let visible_cols = visible_cols.max(1);
+        self.page_rows = visible_rows;
+
+        // Vertical scrolling
+        if self.cursor_row < self.viewport_row {
+            self.viewport_row = self.cursor_row;
+        } else if self.cursor_row >= self.viewport_row + visible_rows {
+            self.viewport_row = self.cursor_row - visible_rows + 1;
+        }
+
+        // Horizontal scrolling
+        if self.hide_gap_columns && !self.visible_columns.is_empty() {
+            // When hiding, viewport_col is in display column space
+            if let Some(cursor_display_col) = self.actual_to_display_col(self.cursor_col) {
+                if cursor_display_col < self.viewport_col {
+                    self.viewport_col = cursor_display_col;
+                } else if cursor_display_col >= self.viewport_col + visible_cols {
+                    self.viewport_col = cursor_display_col - visible_cols + 1;
+                }
+            }
+        } else {
+            // Normal mode - viewport_col is actual column
+            if self.cursor_col < self.viewport_col {
+                self.viewport_col = self.cursor_col;
+            } else if self.cursor_col >= self.viewport_col + visible_cols {
+                self.viewport_col = self.cursor_col - visible_cols + 1;
+            }
+        }
+    }
+
+    /// Fit the viewports to a resized terminal: pull them back so a larger
+    /// window doesn't show empty space past the last sequence or column, then
+    /// bring the cursor into view.
+    pub fn clamp_viewport(&mut self, visible_rows: usize, visible_cols: usize) {
+        let max_row = self.visible_sequence_count().saturating_sub(visible_rows);
+        let width = if self.hide_gap_columns && !self.visible_columns.is_empty() {
+            self.visible_columns.len()
+        } else {
+            self.alignment.width()
+        };
+        let max_col = width.saturating_sub(visible_cols);
+        self.viewport_row = self.viewport_row.min(max_row);
+        self.viewport_col = self.viewport_col.min(max_col);
+        self.secondary_viewport_row = self.secondary_viewport_row.min(max_row);
+        self.secondary_viewport_col = self.secondary_viewport_col.min(max_col);
+        self.adjust_viewport(visible_rows, visible_cols);
+    }
+
+    // === Clustering methods ===
+
+    /// Map display row to actual sequence index.
+    /// When a row filter is active, maps through the filtered rows. When collapse is active,
+    /// maps to representative. When clustering is active, uses cluster order.
+    pub fn display_to_actual_row(&self, display_row: usize) -> usize {
+        if self.row_filter.is_some() {
+            // Filtering takes priority over collapse
+            self.filtered_rows
+                .get(display_row)
+                .copied()
+                .unwrap_or(display_row)
+        } else if self.collapse_identical && !self.collapse_groups.is_empty() {
+            // When clustering is also enabled, use group_order to find correct group
+            let group_idx = if let Some(ref group_order) = self.cluster_group_order {
+                group_order.get(display_row).copied().unwrap_or(display_row)
+            } else {
+                display_row
+            };
+
+            // Get representative from the group
+            if group_idx < self.collapse_groups.len() {
+                self.collapse_groups[group_idx].0
+            } else {
+                display_row
+            }
+        } else if let Some(ref order) = self.cluster_order {
+            // Only clustering, no collapse
+            order.get(display_row).copied().unwrap_or(display_row)
+        } else {
+            // No collapse, no clustering
+            display_row
+        }
+    }
+
+    /// Display row currently showing an actual sequence index, if it is visible.
+    pub fn actual_to_display_row(&self, row: usize) -> Option<usize> {
+        (0..self.visible_sequence_count()).find(|&d| self.display_to_actual_row(d) == row)
+    }
+
+    /// Get the number of visible sequences (accounts for row filter and collapse).
+    pub fn visible_sequence_count(&self) -> usize {
+        if self.row_filter.is_some() {
+            self.filtered_rows.len()
+        } else if self.collapse_identical && !self.collapse_groups.is_empty() {
+            self.collapse_groups.len()
+        } else {
+            self.alignment.num_sequences()
+        }
+    }
+
+    /// Cluster sequences by similarity using hierarchical clustering.
+    /// Uses precomputed collapse groups to avoid redundant distance calculations.
+    pub fn cluster_sequences(&mut self) {
+        if self.alignment.sequences.is_empty() {
+            return;
+        }
+
+        // Get sequence chars for clustering
+        let seq_chars: Vec<Vec<char>> = self
+            .alignment
+            .sequences
+            .iter()
+            .map(|s| s.chars().to_vec())
+            .collect();
+
+        // Compute cluster order and tree using UPGMA
+        // Use collapse groups to cluster only unique sequences (optimization)
+        let result = crate::clustering::cluster_sequences_with_collapse(
+            &seq_chars,
+            &self.gap_chars,
+            &self.collapse_groups,
+        );
+        self.apply_cluster_result(result);
+    }
+
+    /// Show the sequences in the order of a clustering, with its tree.
+    fn apply_cluster_result(&mut self, result: ClusterResult) {
+        self.cluster_order = Some(result.order);
+        self.cluster_tree = Some(result.tree_lines);
+        self.tree_width = result.tree_width;
+        self.cluster_group_order = result.group_order;
+
+        // Keep filtered rows in the new display order
+        self.refresh_row_filter();
+
+        // Clamp cursor to valid range
+        if self.cursor_row >= self.visible_sequence_count() {
+            self.cursor_row = self.visible_sequence_count().saturating_sub(1);
+        }
+    }
+
+    /// Group sequences at an identity threshold (0.0 to 1.0) and order them by
+    /// similarity, on a background thread that `poll_cluster_job` collects from.
+    /// The `:clusters` panel opens and fills in as clusters are found.
+    ///
+    /// Clusters replace the identical-sequence groups, so `:collapse` shows one
+    /// representative per cluster.
+    pub fn cluster_by_identity(&mut self, threshold: f64) {
+        if self.alignment.sequences.is_empty() {
+            return;
+        }
+        let seq_chars: Vec<Vec<char>> = self
+            .alignment
+            .sequences
+            .iter()
+            .map(|s| s.chars().to_vec())
+            .collect();
+        let gap_chars = self.gap_chars.clone();
+
+        self.cancel_cluster_job();
+        let cancel = Arc::new(AtomicBool::new(false));
+        let (sender, updates) = mpsc::channel();
+        let stop = Arc::clone(&cancel);
+        std::thread::spawn(move || {
+            let mut sent = Instant::now();
+            let groups = crate::clustering::greedy_identity_clusters_with_progress(
+                &seq_chars,
+                &gap_chars,
+                threshold,
+                |groups, clustered| {
+                    if stop.load(Ordering::Relaxed) {
+                        return false;
+                    }
+                    if sent.elapsed() < CLUSTER_PROGRESS_INTERVAL {
+                        return true;
+                    }
+                    sent = Instant::now();
+                    let groups = groups.to_vec();
+                    // The job is gone if the receiver was dropped
+                    sender
+                        .send(ClusterUpdate::Progress { groups, clustered })
+                        .is_ok()
+                },
+            );
+            let Some(groups) = groups else {
+                return;
+            };
+            let result =
+                crate::clustering::cluster_sequences_with_collapse(&seq_chars, &gap_chars, &groups);
+            if !stop.load(Ordering::Relaxed) {
+                let _ = sender.send(ClusterUpdate::Done { groups, result });
+            }
+        });
+        self.cluster_job = Some(ClusterJob {
+            updates,
+            cancel,
+            threshold,
+            edits: self.edits,
+            groups: Vec::new(),
+            clustered: 0,
+        });
+        self.show_clusters = true;
+        self.set_status(format!(
+            "Clustering {} sequences at {:.0}% identity (:uncluster cancels)",
+            self.alignment.num_sequences(),
+            threshold * 100.0
+        ));
+    }
+
+    /// Take the clusters found so far by `:cluster <N>`, and apply them once
+    /// it has finished (called from the main loop).
+    pub fn poll_cluster_job(&mut self) {
+        self.finish_cluster_job(false);
+    }
+
+    /// Apply the clusters of `:cluster <N>` once it has finished (or, with
+    /// `wait`, when it does), keeping the latest progress until then.
+    fn finish_cluster_job(&mut self, wait: bool) {
+        let Some(job) = &mut self.cluster_job else {
+            return;
+        };
+        let (groups, result) = loop {
+            let update = if wait {
+                job.updates
+                    .recv()
+                    .map_err(|_| mpsc::TryRecvError::Disconnected)
+            } else {
+                job.updates.try_recv()
+            };
+            match update {
+                Ok(ClusterUpdate::Progress { groups, clustered }) => {
+                    job.groups = groups;
+                    job.clustered = clustered;
+                }
+                Ok(ClusterUpdate::Done { groups, result }) => break (groups, result),
+                Err(mpsc::TryRecvError::Empty) => return,
+                Err(mpsc::TryRecvError::Disconnected) => {
+                    self.cluster_job = None;
+                    self.set_error("Clustering failed");
+                    return;
+                }
+            }
+        };
+        let (threshold, edits) = (job.threshold, job.edits);
+        self.cluster_job = None;
+        if self.edits != edits {
+            self.set_error("The alignment changed while clustering; :cluster again");
+            return;
+        }
+        self.identity_threshold = Some(threshold);
+        self.collapse_groups = groups;
+        self.apply_cluster_result(result);
+        self.clamp_cursor();
+        self.set_status(format!(
+            "Clustered {} sequences into {} clusters at {:.0}% identity (:clusters, :collapse)",
+            self.alignment.num_sequences(),
+            self.collapse_groups.len(),
+            threshold * 100.0
+        ));
+    }
+
+    /// Stop a running `:cluster <N>`. Returns whether one was running.
+    fn cancel_cluster_job(&mut self) -> bool {
+        match self.cluster_job.take() {
+            Some(job) => {
+                job.cancel.store(true, Ordering::Relaxed);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Fold sequences and score their MFE against dinucleotide shuffles, on a
+    /// background thread that `poll_fold_job` collects from.
+    ///
+    /// Scores the selected sequences in visual mode, otherwise every sequence.
+    pub fn compute_fold_zscores(&mut self, shuffles: usize) {
+        if self.sequence_type == SequenceType::Protein {
+            self.set_error("MFE z-scores need RNA or DNA sequences");
+            return;
+        }
+        let rows = if self.selection_anchor.is_some() {
+            self.target_rows()
+        } else {
+            (0..self.alignment.num_sequences()).collect()
+        };
+        let inputs: Vec<(String, String)> = rows
+            .iter()
+            .map(|&row| {
+                let seq = &self.alignment.sequences[row];
+                let residues: String = seq
+                    .chars()
+                    .iter()
+                    .filter(|c| !self.gap_chars.contains(c))
+                    .collect();
+                (seq.id.clone(), residues)
+            })
+            .collect();
+
+        self.cancel_fold_job();
+        let cancel = Arc::new(AtomicBool::new(false));
+        let (sender, results) = mpsc::channel();
+        let stop = Arc::clone(&cancel);
+        let count = inputs.len();
+        std::thread::spawn(move || {
+            let scores = fold_in_parallel(&inputs, shuffles, &stop);
+            if !stop.load(Ordering::Relaxed) {
+                // The job is gone if the receiver was dropped
+                let _ = sender.send(scores);
+            }
+        });
+        self.fold_job = Some(FoldJob {
+            results,
+            cancel,
+            shuffles,
+        });
+        self.set_status(format!(
+            "Folding {count} sequences with {shuffles} shuffles (:zscore off cancels)"
+        ));
+    }
+
+    /// Take the scores of a finished `:zscore` (called from the main loop).
+    pub fn poll_fold_job(&mut self) {
+        let Some(job) = &self.fold_job else {
+            return;
+        };
+        let results = match job.results.try_recv() {
+            Ok(results) => results,
+            Err(mpsc::TryRecvError::Empty) => return,
+            Err(mpsc::TryRecvError::Disconnected) => {
+                self.fold_job = None;
+                self.set_error("Folding failed");
+                return;
+            }
+        };
+        let shuffles = job.shuffles;
+        self.fold_job = None;
+
+        let mut scored = 0;
+        let mut weak = 0;
+        let total = results.len();
+        for (id, result) in results {
+            match result {
+                Some(z) => {
+                    scored += 1;
+                    if z.z > WEAK_FOLD_Z {
+                        weak += 1;
+                    }
+                    self.fold_zscores.insert(id, z);
+                }
+                None => {
+                    self.fold_zscores.remove(&id);
+                }
+            }
+        }
+        self.show_zscores = true;
+        let skipped = total - scored;
+        let skipped_info = if skipped > 0 {
+            format!(", {skipped} too short/long to fold")
+        } else {
+            String::new()
+        };
+        self.set_status(format!(
+            "MFE z-scores for {scored} sequences ({shuffles} shuffles): {weak} above {WEAK_FOLD_Z}{skipped_info}"
+        ));
+    }
+
+    /// Run RNAalifold with the `:set alifold.*` options on a background thread
+    /// that `poll_alifold_job` collects the consensus structure from.
+    pub fn start_alifold(&mut self) {
+        if self.sequence_type == SequenceType::Protein {
+            self.set_error("RNAalifold needs RNA or DNA sequences");
+            return;
+        }
+        if self.alignment.sequences.is_empty() {
+            self.set_error("No sequences to fold");
+            return;
+        }
+        if !self.check_writable() {
+            return;
+        }
+        let mut clustal = Vec::new();
+        let options = WriteOptions {
+            gap_chars: self.gap_chars.clone(),
+            ..WriteOptions::default()
+        };
+        if let Err(e) = Format::Clustal.write(&self.alignment, &options, &mut clustal) {
+            self.set_error(format!("Could not write the alignment for RNAalifold: {e}"));
+            return;
+        }
+        let (sender, result) = mpsc::channel();
+        let alifold = self.alifold.clone();
+        std::thread::spawn(move || {
+            // The job is gone if the receiver was dropped
+            let _ = sender.send(alifold::run(&alifold, &clustal));
+        });
+        self.alifold_job = Some(AlifoldJob {
+            result,
+            edits: self.edits,
+            mis: self.alifold.mis,
+        });
+        self.set_status(format!(
+            "Running {} on {} sequences (:alifold off to ignore the result)",
+            self.alifold.program,
+            self.alignment.num_sequences()
+        ));
+    }
+
+    /// Take the result of a finished `:alifold` (called from the main loop).
+    pub fn poll_alifold_job(&mut self) {
+        self.finish_alifold_job(false);
+    }
+
+    /// Put the consensus structure of `:alifold` into SS_cons, and the MIS into
+    /// `#=GC MIS` if asked for, once RNAalifold has finished (or, with `wait`,
+    /// when it does). Applying it is logged for the session script.
+    fn finish_alifold_job(&mut self, wait: bool) {
+        let Some(job) = &self.alifold_job else {
+            return;
+        };
+        let result = if wait {
+            job.result.recv().unwrap_or(Err(AlifoldError::Output))
+        } else {
+            match job.result.try_recv() {
+                Ok(result) => result,
+                Err(mpsc::TryRecvError::Empty) => return,
+                Err(mpsc::TryRecvError::Disconnected) => Err(AlifoldError::Output),
+            }
+        };
+        let (edits, mis) = (job.edits, job.mis);
+        self.alifold_job = None;
+        let prediction = match result {
+            Ok(prediction) => prediction,
+            Err(e) => {
+                self.set_error(e.to_string());
+                return;
+            }
+        };
+        if self.edits != edits {
+            self.set_error("The alignment changed while RNAalifold ran; :alifold again");
+            return;
+        }
+        if prediction.structure.chars().count() != self.alignment.width() {
+            self.set_error(format!(
+                "RNAalifold returned {} columns for an alignment of {}",
+                prediction.structure.chars().count(),
+                self.alignment.width()
+            ));
+            return;
+        }
+        if !self.begin_edit() {
+            return;
+        }
+        let mut rows = vec![("SS_cons", prediction.structure)];
+        if mis {
+            rows.push(("MIS", prediction.consensus));
+        }
+        for (tag, data) in rows {
+            match self.alignment.column_annotation_mut(tag) {
+                Some(row) => *row = data,
+                None => self.alignment.column_annotations.push(ColumnAnnotation {
+                    tag: tag.to_string(),
+                    data,
+                }),
+            }
+        }
+        self.mark_modified();
+        self.update_structure_cache();
+        if let Some((id, column)) = self.script_position(self.cursor_row, self.cursor_col) {
+            self.script_log.push(script::Entry {
+                command: "alifold".to_string(),
+                id,
+                column,
+                anchor: None,
+            });
+        }
+        let mis = if mis { " and #=GC MIS" } else { "" };
+        self.set_status(format!(
+            "RNAalifold: {:.2} kcal/mol, {} base pairs in SS_cons{mis} (u restores)",
+            prediction.energy,
+            self.structure_cache.pairs().len()
+        ));
+    }
+
+    /// Stop a running `:zscore`. Returns whether one was running.
+    fn cancel_fold_job(&mut self) -> bool {
+        match self.fold_job.take() {
+            Some(job) => {
+                job.cancel.store(true, Ordering::Relaxed);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// MFE z-score of the sequence at a display row, if computed.
+    pub fn fold_zscore(&self, display_row: usize) -> Option<&FoldZScore> {
+        let seq = self
+            .alignment
+            .sequences
+            .get(self.display_to_actual_row(display_row))?;
+        self.fold_zscores.get(&seq.id)
+    }
+
+    /// Write an alignment with only one representative per collapse group.
+    pub fn write_representatives(&mut self, path: &Path) {
+        let reps: Vec<usize> = self.collapse_groups.iter().map(|(rep, _)| *rep).collect();
+        let subset = self.alignment.select_rows(&reps);
+        match crate::formats::write_file(&subset, path, self.save_format, &self.write_options) {
+            Ok(_) => self.set_status(format!(
+                "Wrote {} representatives to {}",
+                reps.len(),
+                path.display()
+            )),
+            Err(e) => self.set_error(format!("Failed to write file: {e}")),
+        }
+    }
+
+    // === Search results ===
+
+    /// Load a `--tblout` table into the hit viewer.
+    pub fn load_hits(&mut self, path: &Path) {
+        match crate::hits::parse_tblout_file(path) {
+            Ok(hits) => {
+                self.set_hits(hits);
+                self.show_hits = true;
+                let members = self.hit_members.iter().flatten().count();
+                self.set_status(format!(
+                    "Loaded {} hits ({members} already in alignment)",
+                    self.hits.len()
+                ));
+            }
+            Err(e) => self.set_error(format!("Failed to read {}: {e}", path.display())),
+        }
+    }
+
+    /// Replace the loaded hits, sorted by score, clearing marks.
+    fn set_hits(&mut self, mut hits: Vec<Hit>) {
+        hits.sort_by(|a, b| b.score.total_cmp(&a.score));
+        self.hit_marks = vec![false; hits.len()];
+        self.hits = hits;
+        self.hit_cursor = 0;
+        self.refresh_hit_members();
+    }
+
+    /// Recompute which alignment row (if any) covers each hit.
+    pub fn refresh_hit_members(&mut self) {
+        self.hit_members = self
+            .hits
+            .iter()
+            .map(|hit| {
+                self.alignment
+                    .sequences
+                    .iter()
+                    .position(|seq| hit.matches_id(&seq.id))
+            })
+            .collect();
+    }
+
+    /// Open the hit viewer on previously loaded hits.
+    pub fn open_hit_viewer(&mut self) {
+        if self.hits.is_empty() {
+            self.set_error("No hits loaded (use :hits <tblout>)");
+            return;
+        }
+        self.refresh_hit_members();
+        self.show_hits = true;
+    }
+
+    /// Move the hit viewer selection by `delta` rows.
+    pub fn move_hit_cursor(&mut self, delta: isize) {
+        let last = self.hits.len().saturating_sub(1);
+        self.hit_cursor = self.hit_cursor.saturating_add_signed(delta).min(last);
+    }
+
+    /// Toggle the fetch mark on the selected hit and advance.
+    pub fn toggle_hit_mark(&mut self) {
+        if let Some(mark) = self.hit_marks.get_mut(self.hit_cursor) {
+            *mark = !*mark;
+            self.move_hit_cursor(1);
+        }
+    }
+
+    /// Mark every included hit not yet in the alignment.
+    pub fn mark_new_hits(&mut self) {
+        let mut count = 0;
+        for (i, hit) in self.hits.iter().enumerate() {
+            if hit.included && self.hit_members[i].is_none() {
+                self.hit_marks[i] = true;
+                count += 1;
+            }
+        }
+        self.set_status(format!("Marked {count} new included hits"));
+    }
+
+    /// Jump to the alignment sequence covering the selected hit.
+    pub fn goto_hit_sequence(&mut self) {
+        let Some(row) = self.hit_members.get(self.hit_cursor).copied().flatten() else {
+            self.set_error("Hit is not in the alignment");
+            return;
+        };
+        match self.actual_to_display_row(row) {
+            Some(display_row) => {
+                self.cursor_row = display_row;
+                self.show_hits = false;
+            }
+            None => self.set_error("Sequence is hidden by the current view"),
+        }
+    }
+
+    // === Quickfix ===
+
+    /// Search every file in the argument list (or just the open alignment) for
+    /// `pattern` in sequences, IDs and `#=GF` lines, filling the quickfix list.
+    pub fn grep_all(&mut self, pattern: &str) {
+        let motif = match self.compile_search(pattern) {
+            Ok(motif) => motif,
+            Err(e) => {
+                self.set_error(format!("Invalid pattern: {e}"));
+                return;
+            }
+        };
+        let mut entries = Vec::new();
+        let mut failed = Vec::new();
+        if self.arg_files.is_empty() {
+            entries = self.grep_open_file(None, pattern, &motif);
+        }
+        for (i, path) in self.arg_files.iter().enumerate() {
+            // The open file is searched as edited, the others as saved
+            if i == self.arg_index {
+                entries.extend(self.grep_open_file(Some(i), pattern, &motif));
+                continue;
+            }
+            match crate::formats::read_file(path, None) {
+                Ok((alignments, ..)) => {
+                    let multi = alignments.len() > 1;
+                    for (r, alignment) in alignments.iter().enumerate() {
+                        let record = multi.then_some(r);
+                        entries.extend(self.grep_alignment(
+                            alignment,
+                            Some(i),
+                            record,
+                            pattern,
+                            &motif,
+                        ));
+                    }
+                }
+                Err(_) => failed.push(path.display().to_string()),
+            }
+        }
+
+        let files = entries
+            .iter()
+            .map(|e| e.file)
+            .collect::<std::collections::HashSet<_>>()
+            .len();
+        let mut message = format!(
+            "{} matches for '{pattern}' in {files} file(s)",
+            entries.len()
+        );
+        if !failed.is_empty() {
+            message.push_str(&format!(" (could not read {})", failed.join(", ")));
+        }
+        self.quickfix = entries;
+        self.quickfix_cursor = 0;
+        self.show_quickfix = !self.quickfix.is_empty();
+        self.set_status(message);
+    }
+
+    /// Quickfix entries for `pattern` in every alignment of the open file.
+    fn grep_open_file(
+        &self,
+        file: Option<usize>,
+        pattern: &str,
+        motif: &SearchPattern,
+    ) -> Vec<QuickfixEntry> {
+        if self.records.len() < 2 {
+            return self.grep_alignment(&self.alignment, file, None, pattern, motif);
+        }
+        (0..self.records.len())
+            .flat_map(|r| self.grep_alignment(self.record(r), file, Some(r), pattern, motif))
+            .collect()
+    }
+
+    /// Quickfix entries for `pattern` in one alignment.
+    fn grep_alignment(
+        &self,
+        alignment: &Alignment,
+        file: Option<usize>,
+        record: Option<usize>,
+        pattern: &str,
+        motif: &SearchPattern,
+    ) -> Vec<QuickfixEntry> {
+        let needle = pattern.to_lowercase();
+        let mut entries = Vec::new();
+
+        for (row, ann) in alignment.file_annotations.iter().enumerate() {
+            let line = format!("#=GF {} {}", ann.tag, ann.value);
+            if line.to_lowercase().contains(&needle) {
+                entries.push(QuickfixEntry {
+                    file,
+                    record,
+                    kind: QuickfixKind::FileAnnotation,
+                    row,
+                    col: 0,
+                    text: line,
+                });
+            }
+        }
+        for (row, seq) in alignment.sequences.iter().enumerate() {
+            let hit = motif
+                .matches_id(&seq.id)
+                .unwrap_or_else(|| seq.id.to_lowercase().contains(&needle));
+            if hit {
+                entries.push(QuickfixEntry {
+                    file,
+                    record,
+                    kind: QuickfixKind::Id,
+                    row,
+                    col: 0,
+                    text: seq.id.clone(),
+                });
+            }
+        }
+        for (row, start, end) in self.find_matches(alignment, motif) {
+            if start == end {
+                // ID matches are listed above
+                continue;
+            }
+            let seq = &alignment.sequences[row];
+            let residues: String = seq.chars()[start..end].iter().collect();
+            entries.push(QuickfixEntry {
+                file,
+                record,
+                kind: QuickfixKind::Sequence,
+                row,
+                col: start,
+                text: format!("{} {residues}", seq.id),
+            });
+        }
+        entries
+    }
+
+    /// Location of a quickfix entry as `file:row:col` (1-based), or `file:GF` for
+    /// `#=GF` lines. Alignments of a multi-record file are numbered as `file[2]`.
+    pub fn quickfix_location(&self, entry: &QuickfixEntry) -> String {
+        let mut file = entry
+            .file
+            .and_then(|i| self.arg_files.get(i))
+            .or(self.file_path.as_ref())
+            .map_or_else(|| "[No file]".to_string(), |p| p.display().to_string());
+        if let Some(record) = entry.record {
+            file.push_str(&format!("[{}]", record + 1));
+        }
+        match entry.kind {
+            QuickfixKind::FileAnnotation => format!("{file}:GF"),
+            _ => format!("{file}:{}:{}", entry.row + 1, entry.col + 1),
+        }
+    }
+
+    /// Open the quickfix list (`:copen`).
+    pub fn open_quickfix(&mut self) {
+        if self.quickfix.is_empty() {
+            self.set_error("Quickfix list is empty (use :grepall <pattern>)");
+            return;
+        }
+        self.show_quickfix = true;
+    }
+
+    /// Move the quickfix selection by `delta` entries.
+    pub fn move_quickfix_cursor(&mut self, delta: isize) {
+        let last = self.quickfix.len().saturating_sub(1);
+        self.quickfix_cursor = self.quickfix_cursor.saturating_add_signed(delta).min(last);
+    }
+
+    /// Jump to the next/previous quickfix entry (`:cnext`/`:cprev`).
+    pub fn step_quickfix(&mut self, delta: isize) {
+        if self.quickfix.is_empty() {
+            self.set_error("Quickfix list is empty (use :grepall <pattern>)");
+            return;
+        }
+        let target = self.quickfix_cursor.saturating_add_signed(delta);
+        if target >= self.quickfix.len() || (delta < 0 && self.quickfix_cursor == 0) {
+            self.set_status("No more items");
+            return;
+        }
+        self.quickfix_cursor = target;
+        self.goto_quickfix_entry();
+    }
+
+    /// Jump to the selected quickfix entry, switching files if needed.
+    pub fn goto_quickfix_entry(&mut self) {
+        let Some(entry) = self.quickfix.get(self.quickfix_cursor).cloned() else {
+            return;
+        };
+        self.show_quickfix = false;
+        if let Some(file) = entry.file
+            && file != self.arg_index
+        {
+            self.goto_arg_file(file, false);
+            if self.arg_index != file {
+                return;
             }
         }
+        if let Some(record) = entry.record
+            && record != self.record_index
+        {
+            self.goto_record(record);
+        }
+        let position = format!("({}/{})", self.quickfix_cursor + 1, self.quickfix.len());
+        if entry.kind != QuickfixKind::FileAnnotation {
+            match self.actual_to_display_row(entry.row) {
+                Some(display_row) => {
+                    self.cursor_row = display_row;
+                    self.cursor_col = entry.col;
+                    self.clamp_cursor();
+                }
+                None => {
+                    self.set_status(format!("{position} Sequence is hidden by the current view"));
+                    return;
+                }
+            }
+        }
+        self.set_status(format!("{position} {}", entry.text));
     }
 
-    // === Clustering methods ===
+    /// Write marked hits as an `esl-sfetch -Cf` list (`newname from to source`).
+    pub fn write_hit_fetch_list(&mut self, path: &Path) {
+        let count = self.hit_marks.iter().filter(|m| **m).count();
+        if count == 0 {
+            self.set_error("No hits marked (Space in :hits to mark)");
+            return;
+        }
+        match std::fs::write(path, hits::fetch_list(self.marked_hits())) {
+            Ok(()) => self.set_status(format!(
+                "Wrote {count} hits to {} (esl-sfetch -Cf <db> {})",
+                path.display(),
+                path.display()
+            )),
+            Err(e) => self.set_error(format!("Failed to write file: {e}")),
+        }
+    }
 
-    /// Map display row to actual sequence index.
-    /// When collapse is active, maps to representative. When clustering is active, uses cluster order.
-    pub fn display_to_actual_row(&self, display_row: usize) -> usize {
-        if self.collapse_identical && !self.collapse_groups.is_empty() {
-            // When clustering is also enabled, use group_order to find correct group
-            let group_idx = if let Some(ref group_order) = self.cluster_group_order {
-                group_order.get(display_row).copied().unwrap_or(display_row)
-            } else {
-                display_row
-            };
+    /// Hits marked in the hit viewer.
+    fn marked_hits(&self) -> impl Iterator<Item = &Hit> {
+        self.hits
+            .iter()
+            .zip(&self.hit_marks)
+            .filter(|(_, marked)| **marked)
+            .map(|(hit, _)| hit)
+    }
 
-            // Get representative from the group
-            if group_idx < self.collapse_groups.len() {
-                self.collapse_groups[group_idx].0
-            } else {
-                display_row
-            }
-        } else if let Some(ref order) = self.cluster_order {
-            // Only clustering, no collapse
-            order.get(display_row).copied().unwrap_or(display_row)
-        } else {
-            // No collapse, no clustering
-            display_row
+    /// Fetch the marked hits from `seqdb` and align them into the family with
+    /// the model `model` (`cmalign`/`hmmalign --mapali`) on a background thread
+    /// that `poll_hit_align_job` collects the alignment from.
+    pub fn start_hit_alignment(&mut self, seqdb: &Path, model: &Path) {
+        let list = hits::fetch_list(self.marked_hits());
+        if list.is_empty() {
+            self.set_error("No hits marked (Space in :hits to mark)");
+            return;
+        }
+        if !self.check_writable() {
+            return;
         }
+        let mut family = Vec::new();
+        let options = WriteOptions {
+            gap_chars: self.gap_chars.clone(),
+            ..WriteOptions::default()
+        };
+        if let Err(e) = Format::Stockholm.write(&self.alignment, &options, &mut family) {
+            self.set_error(format!("Could not write the family for alignment: {e}"));
+            return;
+        }
+        let count = list.lines().count();
+        let command = format!("hits align {} {}", seqdb.display(), model.display());
+        let (sender, result) = mpsc::channel();
+        let programs = self.hit_programs.clone();
+        let (seqdb, model) = (seqdb.to_path_buf(), model.to_path_buf());
+        std::thread::spawn(move || {
+            // The job is gone if the receiver was dropped
+            let _ = sender.send(hits::fetch_and_align(
+                &programs, &seqdb, &model, &list, &family,
+            ));
+        });
+        self.hit_align_job = Some(HitAlignJob {
+            result,
+            edits: self.edits,
+            command,
+        });
+        self.set_status(format!(
+            "Aligning {count} hits with {} (:hits align off to ignore the result)",
+            self.hit_programs.aligner
+        ));
     }
 
-    /// Get the number of visible sequences (accounts for collapse).
-    pub fn visible_sequence_count(&self) -> usize {
-        if self.collapse_identical && !self.collapse_groups.is_empty() {
-            self.collapse_groups.len()
+    /// Take the alignment of a finished `:hits align` (called from the main loop).
+    pub fn poll_hit_align_job(&mut self) {
+        self.finish_hit_align_job(false);
+    }
+
+    /// Replace the alignment with the family and the marked hits as aligned by
+    /// `:hits align`, once the aligner has finished (or, with `wait`, when it
+    /// does). The family keeps its #=GF lines and the #=GS lines of its
+    /// sequences, which the aligner drops.
+    fn finish_hit_align_job(&mut self, wait: bool) {
+        let Some(job) = &self.hit_align_job else {
+            return;
+        };
+        let gone = || Err(AlignError::Output(self.hit_programs.aligner.clone()));
+        let result = if wait {
+            job.result.recv().unwrap_or_else(|_| gone())
         } else {
-            self.alignment.num_sequences()
+            match job.result.try_recv() {
+                Ok(result) => result,
+                Err(mpsc::TryRecvError::Empty) => return,
+                Err(mpsc::TryRecvError::Disconnected) => gone(),
+            }
+        };
+        let edits = job.edits;
+        let command = job.command.clone();
+        self.hit_align_job = None;
+        let output = match result {
+            Ok(output) => output,
+            Err(e) => {
+                self.set_error(e.to_string());
+                return;
+            }
+        };
+        if self.edits != edits {
+            self.set_error("The alignment changed while the hits were aligned; :hits align again");
+            return;
+        }
+        let mut aligned = match crate::stockholm::parser::parse(output.as_slice()) {
+            Ok(aligned) => aligned,
+            Err(e) => {
+                self.set_error(format!(
+                    "Could not read the output of {}: {e}",
+                    self.hit_programs.aligner
+                ));
+                return;
+            }
+        };
+        if !self.begin_edit() {
+            return;
+        }
+        let added = aligned
+            .num_sequences()
+            .saturating_sub(self.alignment.num_sequences());
+        aligned.source = None;
+        aligned.file_annotations = std::mem::take(&mut self.alignment.file_annotations);
+        for (id, annotations) in std::mem::take(&mut self.alignment.sequence_annotations) {
+            aligned
+                .sequence_annotations
+                .entry(id)
+                .or_insert(annotations);
         }
+        self.alignment = aligned;
+        self.mark_modified();
+        self.update_structure_cache();
+        if self.cluster_order.is_some() {
+            self.precompute_collapse_groups();
+            self.cluster_sequences();
+        }
+        self.refresh_row_filter();
+        self.clamp_cursor();
+        self.refresh_hit_members();
+        for (mark, member) in self.hit_marks.iter_mut().zip(&self.hit_members) {
+            *mark &= member.is_none();
+        }
+        if let Some((id, column)) = self.script_position(self.cursor_row, self.cursor_col) {
+            self.script_log.push(script::Entry {
+                command,
+                id,
+                column,
+                anchor: None,
+            });
+        }
+        self.set_status(format!(
+            "Aligned {added} hits into the family, now {} columns (u restores)",
+            self.alignment.width()
+        ));
     }
 
-    /// Cluster sequences by similarity using hierarchical clustering.
-    /// Uses precomputed collapse groups to avoid redundant distance calculations.
-    pub fn cluster_sequences(&mut self) {
-        if self.alignment.sequences.is_empty() {
+    /// Load a `--tblout` table and record suggested GA/TC/NC thresholds as `#=GF` lines.
+    ///
+    /// Hits overlapping alignment sequences count as family members; the score
+    /// distribution and suggestions are shown in a report.
+    pub fn suggest_thresholds(&mut self, path: &Path) {
+        let hits = match crate::hits::parse_tblout_file(path) {
+            Ok(hits) => hits,
+            Err(e) => {
+                self.set_error(format!("Failed to read {}: {e}", path.display()));
+                return;
+            }
+        };
+
+        // Restrict to this family's model when the table covers several (e.g. cmscan)
+        let family = self.alignment.get_file_annotation("ID").map(str::to_string);
+        let hits: Vec<Hit> = match family {
+            Some(id) if hits.iter().any(|h| h.query == id) => {
+                hits.into_iter().filter(|h| h.query == id).collect()
+            }
+            _ => hits,
+        };
+
+        let is_member = |hit: &Hit| {
+            self.alignment
+                .sequences
+                .iter()
+                .any(|seq| hit.matches_id(&seq.id))
+        };
+        let (members, others): (Vec<&Hit>, Vec<&Hit>) = hits.iter().partition(|h| is_member(h));
+        let member_scores: Vec<f64> = members.iter().map(|h| h.score).collect();
+        let other_scores: Vec<f64> = others.iter().map(|h| h.score).collect();
+
+        let Some(thresholds) = crate::hits::suggest_thresholds(&member_scores, &other_scores)
+        else {
+            self.set_status(format!(
+                "None of {} hits match alignment sequences",
+                hits.len()
+            ));
+            return;
+        };
+
+        if !self.begin_edit() {
             return;
         }
+        self.alignment
+            .set_file_annotation("GA", &format!("{:.2}", thresholds.ga));
+        if let Some(tc) = thresholds.tc {
+            self.alignment
+                .set_file_annotation("TC", &format!("{tc:.2}"));
+        }
+        if let Some(nc) = thresholds.nc {
+            self.alignment
+                .set_file_annotation("NC", &format!("{nc:.2}"));
+        }
+        self.mark_modified();
 
-        // Get sequence chars for clustering
-        let seq_chars: Vec<Vec<char>> = self
+        let unmatched = self
             .alignment
             .sequences
             .iter()
-            .map(|s| s.chars().to_vec())
+            .filter(|seq| !members.iter().any(|h| h.matches_id(&seq.id)))
+            .count();
+        let mut lines = vec![
+            format!(
+                "{} hits: {} members, {} others",
+                hits.len(),
+                members.len(),
+                others.len()
+            ),
+            format!("{unmatched} alignment sequences have no hit"),
+            String::new(),
+            "    bits  members  others".to_string(),
+        ];
+        lines.extend(score_histogram(&member_scores, &other_scores, 10));
+        lines.push(String::new());
+        lines.push(format!("GA {:.2}  (lowest member)", thresholds.ga));
+        lines.push(match thresholds.tc {
+            Some(tc) => format!("TC {tc:.2}  (lowest member > NC)"),
+            None => "TC not set: no member scores above NC".to_string(),
+        });
+        lines.push(match thresholds.nc {
+            Some(nc) => format!("NC {nc:.2}  (highest non-member)"),
+            None => "NC not set: every hit is a member".to_string(),
+        });
+        self.report = Some(Report {
+            title: format!("Score thresholds ({})", path.display()),
+            lines,
+        });
+        self.set_hits(hits);
+        self.set_status("Recorded GA/TC/NC in #=GF lines");
+    }
+
+    /// Flag sequences that another family scores better, from a cmscan/cmsearch table.
+    ///
+    /// With an `Rfam.clanin` file, the report also notes competitors in the same clan.
+    pub fn screen_contamination(&mut self, tblout: &Path, clanin: Option<&Path>) {
+        let hits = match crate::hits::parse_tblout_file(tblout) {
+            Ok(hits) => hits,
+            Err(e) => {
+                self.set_error(format!("Failed to read {}: {e}", tblout.display()));
+                return;
+            }
+        };
+        let clans = match clanin.map(|path| {
+            std::fs::File::open(path)
+                .map_err(crate::hits::HitTableError::from)
+                .and_then(crate::hits::parse_clanin)
+        }) {
+            Some(Ok(clans)) => clans,
+            Some(Err(e)) => {
+                self.set_error(format!("Failed to read clan file: {e}"));
+                return;
+            }
+            None => HashMap::new(),
+        };
+
+        let family: Vec<&str> = ["ID", "AC"]
+            .iter()
+            .filter_map(|tag| self.alignment.get_file_annotation(tag))
             .collect();
+        if family.is_empty() {
+            self.set_error("Alignment has no #=GF ID or AC to screen against");
+            return;
+        }
+        let own_clan = self
+            .alignment
+            .get_file_annotation("AC")
+            .and_then(|ac| clans.get(ac));
 
-        // Compute cluster order and tree using UPGMA
-        // Use collapse groups to cluster only unique sequences (optimization)
-        let result = crate::clustering::cluster_sequences_with_collapse(
-            &seq_chars,
-            &self.gap_chars,
-            &self.collapse_groups,
-        );
-        self.cluster_order = Some(result.order);
-        self.cluster_tree = Some(result.tree_lines);
-        self.tree_width = result.tree_width;
-        self.cluster_group_order = result.group_order;
+        let competitors: Vec<(String, crate::hits::Competitor)> = self
+            .alignment
+            .sequences
+            .iter()
+            .filter_map(|seq| {
+                crate::hits::better_family(&hits, &seq.id, &family).map(|c| (seq.id.clone(), c))
+            })
+            .collect();
+        if competitors.is_empty() {
+            self.set_status(format!(
+                "No sequence scores better against another family ({} hits)",
+                hits.len()
+            ));
+            return;
+        }
 
-        // Clamp cursor to valid range
-        if self.cursor_row >= self.visible_sequence_count() {
-            self.cursor_row = self.visible_sequence_count().saturating_sub(1);
+        let mut lines = vec![
+            format!(
+                "{} of {} sequences score better against another family",
+                competitors.len(),
+                self.alignment.num_sequences()
+            ),
+            String::new(),
+        ];
+        let mut flags = Vec::new();
+        for (id, c) in &competitors {
+            let own = c
+                .own_score
+                .map_or("no hit".to_string(), |s| format!("{s:.1}"));
+            let same_clan = match (own_clan, clans.get(&c.accession)) {
+                (Some(ours), Some(theirs)) if ours == theirs => format!("  same clan {ours}"),
+                _ => String::new(),
+            };
+            lines.push(format!(
+                "{id}  {} {} {:.1} vs own {own}{same_clan}",
+                c.model, c.accession, c.score
+            ));
+            // Leave existing curation flags alone
+            if self.alignment.sequence_flag(id).is_none() {
+                flags.push((id, format!("contam:{}", c.model)));
+            }
+        }
+        let flagged = flags.len();
+        if flagged > 0 {
+            if !self.begin_edit() {
+                return;
+            }
+            for (id, flag) in flags {
+                self.alignment.set_sequence_flag(id, &flag);
+            }
+            self.mark_modified();
+            self.refresh_row_filter();
         }
+        self.report = Some(Report {
+            title: format!("Contamination screen ({})", tblout.display()),
+            lines,
+        });
+        self.set_status(format!(
+            "Flagged {flagged} sequence(s) as contam:<family> (:filter flagged to review)"
+        ));
     }
 
     /// Disable clustering and restore original order.
@@ -1501,6 +7147,7 @@ impl App {
         self.tree_width = 0;
         self.show_tree = false;
         self.cluster_group_order = None;
+        self.refresh_row_filter();
     }
 
     /// Toggle dendrogram tree visibility.
@@ -1508,7 +7155,7 @@ impl App {
         if self.cluster_tree.is_some() {
             self.show_tree = !self.show_tree;
         } else {
-            self.status_message = Some("No tree available. Run :cluster first.".to_string());
+            self.set_error("No tree available. Run :cluster first.");
         }
     }
 
@@ -1516,6 +7163,7 @@ impl App {
 
     /// Pre-compute collapse groups by grouping sequences with identical content.
     /// Called during load since sequences don't change during viewing.
+    /// With an identity threshold set, groups are greedy identity clusters instead.
     pub fn precompute_collapse_groups(&mut self) {
         use std::collections::HashMap;
         self.collapse_groups.clear();
@@ -1524,6 +7172,18 @@ impl App {
             return;
         }
 
+        if let Some(threshold) = self.identity_threshold {
+            let seq_chars: Vec<Vec<char>> = self
+                .alignment
+                .sequences
+                .iter()
+                .map(|s| s.chars().to_vec())
+                .collect();
+            self.collapse_groups =
+                crate::clustering::greedy_identity_clusters(&seq_chars, &self.gap_chars, threshold);
+            return;
+        }
+
         // Group by sequence content (chars as String for hashing)
         let mut content_map: HashMap<String, Vec<usize>> = HashMap::new();
         for (idx, seq) in self.alignment.sequences.iter().enumerate() {
@@ -1543,7 +7203,9 @@ impl App {
 
     /// Get collapse count for a display row (1 if not collapsed or unique).
     pub fn get_collapse_count(&self, display_row: usize) -> usize {
-        if self.collapse_identical && !self.collapse_groups.is_empty() {
+        if self.row_filter.is_some() {
+            1
+        } else if self.collapse_identical && !self.collapse_groups.is_empty() {
             // When clustering is also enabled, use group_order to find correct group
             let group_idx = if let Some(ref group_order) = self.cluster_group_order {
                 group_order.get(display_row).copied().unwrap_or(display_row)
@@ -1563,7 +7225,7 @@ impl App {
 
     /// Get the maximum collapse count across all groups.
     pub fn max_collapse_count(&self) -> usize {
-        if self.collapse_identical {
+        if self.collapse_identical && self.row_filter.is_none() {
             self.collapse_groups
                 .iter()
                 .map(|(_, g)| g.len())
@@ -1596,6 +7258,78 @@ impl App {
         self.status_message = Some(msg);
     }
 
+    // === Row filter ===
+
+    /// Restrict the display to sequences matching a filter.
+    pub fn set_row_filter(&mut self, filter: RowFilter) {
+        let rows = self.compute_filtered_rows(filter);
+        if rows.is_empty() {
+            self.set_status("No sequences match filter");
+            return;
+        }
+        let count = rows.len();
+        self.row_filter = Some(filter);
+        self.filtered_rows = rows;
+        self.cursor_row = 0;
+        self.viewport_row = 0;
+        self.set_status(format!(
+            "Showing {count} of {} sequences (:filter off to clear)",
+            self.alignment.num_sequences()
+        ));
+    }
+
+    /// Remove the active row filter.
+    pub fn clear_row_filter(&mut self) {
+        self.row_filter = None;
+        self.filtered_rows.clear();
+        self.clamp_cursor();
+    }
+
+    /// Recompute filtered rows after the alignment changes.
+    /// Clears the filter if no sequences match anymore.
+    pub fn refresh_row_filter(&mut self) {
+        let Some(filter) = self.row_filter else {
+            return;
+        };
+        self.filtered_rows = self.compute_filtered_rows(filter);
+        if self.filtered_rows.is_empty() {
+            self.row_filter = None;
+        }
+        self.clamp_cursor();
+    }
+
+    /// Collect sequence indices passing a filter, following cluster order if active.
+    fn compute_filtered_rows(&self, filter: RowFilter) -> Vec<usize> {
+        let order: Vec<usize> = match self.cluster_order {
+            Some(ref order) => order.clone(),
+            None => (0..self.alignment.num_sequences()).collect(),
+        };
+        order
+            .into_iter()
+            .filter(|&idx| match filter {
+                RowFilter::Flagged => self
+                    .alignment
+                    .sequences
+                    .get(idx)
+                    .is_some_and(|s| self.alignment.sequence_flag(&s.id).is_some()),
+            })
+            .collect()
+    }
+
+    /// Statistics of the cursor column, computed again only after the cursor
+    /// moves to another column or the alignment is edited.
+    pub fn cursor_column_stats(&self) -> ColumnStats {
+        let mut cache = self.column_stats_cache.borrow_mut();
+        match *cache {
+            Some((edits, col, stats)) if edits == self.edits && col == self.cursor_col => stats,
+            _ => {
+                let stats = column_stats(self.cursor_col, &self.alignment, &self.gap_chars);
+                *cache = Some((self.edits, self.cursor_col, stats));
+                stats
+            }
+        }
+    }
+
     // === Sequence type detection ===
 
     /// Detect sequence type from alignment content.
@@ -1647,3 +7381,785 @@ impl App {
         }
     }
 }
+
+/// Text histogram of member and other hit scores, highest scores first.
+fn score_histogram(members: &[f64], others: &[f64], bins: usize) -> Vec<String> {
+    let all = || members.iter().chain(others).copied();
+    let (Some(lo), Some(hi)) = (all().reduce(f64::min), all().reduce(f64::max)) else {
+        return Vec::new();
+    };
+    let width = ((hi - lo) / bins as f64).max(f64::EPSILON);
+    let bin_of = |score: f64| (((score - lo) / width) as usize).min(bins - 1);
+
+    let mut counts = vec![(0usize, 0usize); bins];
+    for &score in members {
+        counts[bin_of(score)].0 += 1;
+    }
+    for &score in others {
+        counts[bin_of(score)].1 += 1;
+    }
+
+    counts
+        .iter()
+        .enumerate()
+        .rev()
+        .filter(|(_, (m, o))| m + o > 0)
+        .map(|(i, &(m, o))| {
+            format!(
+                "{:>8.1}  {m:>7}  {o:>6}  {}{}",
+                lo + i as f64 * width,
+                "█".repeat(m.min(30)),
+                "░".repeat(o.min(30))
+            )
+        })
+        .collect()
+}
+
+/// MFE z-scores of `(id, residues)` pairs, split across the available cores.
+/// Sequences not yet started when `stop` is set are left unscored.
+fn fold_in_parallel(
+    inputs: &[(String, String)],
+    shuffles: usize,
+    stop: &AtomicBool,
+) -> Vec<(String, Option<FoldZScore>)> {
+    // Seeding from the residues keeps results order-independent
+    let threads = std::thread::available_parallelism().map_or(1, |n| n.get());
+    let chunk = inputs.len().div_ceil(threads).max(1);
+    std::thread::scope(|scope| {
+        let handles: Vec<_> = inputs
+            .chunks(chunk)
+            .map(|batch| {
+                scope.spawn(move || {
+                    batch
+                        .iter()
+                        .map_while(|(id, residues)| {
+                            if stop.load(Ordering::Relaxed) {
+                                return None;
+                            }
+                            let seed = residues.bytes().fold(0xcbf2_9ce4_8422_2325_u64, |h, b| {
+                                (h ^ b as u64).wrapping_mul(0x100_0000_01b3)
+                            });
+                            Some((id.clone(), mfe_zscore(residues, shuffles, seed)))
+                        })
+                        .collect::<Vec<_>>()
+                })
+            })
+            .collect();
+        handles
+            .into_iter()
+            .flat_map(|h| h.join().expect("fold thread panicked"))
+            .collect()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{app_with, rows, type_keys};
+
+    const THREE: &str = "# STOCKHOLM 1.0\nseq1 ACGUACGU\nseq2 ACGUACGU\nseq3 ACGAACGU\n//\n";
+
+    #[test]
+    fn test_cursor_column_stats_follow_edits() {
+        let mut app = app_with(THREE);
+        app.cursor_col = 3;
+        assert_eq!(app.cursor_column_stats().consensus, 'U');
+        assert_eq!(app.column_stats_cache.borrow().unwrap().1, 3);
+
+        // A gap column inserted at the cursor
+        app.insert_gap_column();
+        let stats = app.cursor_column_stats();
+        assert_eq!((stats.consensus, stats.gap_fraction), ('.', 1.0));
+        app.undo();
+        assert_eq!(app.cursor_column_stats().consensus, 'U');
+
+        // Another file opened after as many edits
+        let path = std::env::temp_dir().join(format!("aform-stats-{}.sto", std::process::id()));
+        std::fs::write(&path, "# STOCKHOLM 1.0\nseq1 ACGC\n//\n").unwrap();
+        let edits = app.edits;
+        app.load_file(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        (app.edits, app.cursor_col) = (edits, 3);
+        assert_eq!(app.cursor_column_stats().consensus, 'C');
+    }
+
+    #[test]
+    fn test_hits_align_adds_marked_hits() {
+        use std::os::unix::fs::PermissionsExt;
+
+        // Stand-ins for esl-sfetch and cmalign that check their arguments
+        let dir = std::env::temp_dir().join(format!("aform-hitalign-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let sfetch = dir.join("esl-sfetch");
+        std::fs::write(
+            &sfetch,
+            "#!/bin/sh
+             [ \"$1 $3\" = \"-Cf -\" ] || exit 1
+             read name from to target
+             printf '>%s\\nACGUUCGU\\n' \"$name\"\n",
+        )
+        .unwrap();
+        let aligner = dir.join("cmalign");
+        std::fs::write(
+            &aligner,
+            "#!/bin/sh
+             [ \"$1\" = --mapali ] || { echo 'no --mapali' >&2; exit 1; }
+             grep -q '^>chr1/1000-1072' || exit 1
+             grep -v '^//' \"$2\"
+             echo 'chr1/1000-1072 ACGUUCGU'
+             echo '//'
+",
+        )
+        .unwrap();
+        for program in [&sfetch, &aligner] {
+            std::fs::set_permissions(program, std::fs::Permissions::from_mode(0o755)).unwrap();
+        }
+        let tblout = dir.join("hits.tblout");
+        std::fs::write(
+            &tblout,
+            "chr1 - tRNA RF00005 cm 1 71 1000 1072 + no 1 0.55 0.0 62.5 1.2e-12 ! -\n",
+        )
+        .unwrap();
+
+        let mut app = app_with(
+            "# STOCKHOLM 1.0\n#=GF ID tRNA\n#=GS seq1 AC X1\nseq1 ACGUACGU\nseq2 ACGUACGU\n//\n",
+        );
+        app.run_command(&format!("set hits.sfetch={}", sfetch.display()))
+            .unwrap();
+        app.run_command(&format!("set hits.aligner={}", aligner.display()))
+            .unwrap();
+        app.run_command(&format!("hits {}", tblout.display()))
+            .unwrap();
+        assert!(app.run_command("hits align db.fa tRNA.cm").is_err());
+
+        app.toggle_hit_mark();
+        app.run_command("hits align db.fa tRNA.cm").unwrap();
+        app.finish_hit_align_job(true);
+        assert_eq!(app.alignment.num_sequences(), 3);
+        assert_eq!(app.alignment.sequences[2].id, "chr1/1000-1072");
+        assert_eq!(app.alignment.file_annotations.len(), 1);
+        assert!(app.alignment.sequence_annotations.contains_key("seq1"));
+        assert_eq!(app.hit_members, [Some(2)]);
+        assert_eq!(app.hit_marks, [false]);
+        assert_eq!(
+            app.script_log.last().unwrap().command,
+            "hits align db.fa tRNA.cm"
+        );
+        app.undo();
+        assert_eq!(app.alignment.num_sequences(), 2);
+
+        // The aligner's complaint is passed on
+        app.hit_marks[0] = true;
+        app.run_command(&format!("set hits.aligner={}", sfetch.display()))
+            .unwrap();
+        app.run_command("hits align db.fa tRNA.cm").unwrap();
+        app.finish_hit_align_job(true);
+        assert_eq!(app.alignment.num_sequences(), 2);
+        assert!(app.status_message.as_deref().unwrap().contains("failed"));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_contam_flags_once() {
+        let dir = std::env::temp_dir().join(format!("aform-contam-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let tblout = dir.join("scan.tbl");
+        std::fs::write(
+            &tblout,
+            "tRNA  RF00005 seq1 - cm 1 71 1 8 + no 1 0.5 0.0 40.0 1e-8 ! -\n\
+             tmRNA RF00023 seq1 - cm 1 71 1 8 + no 1 0.5 0.0 55.0 1e-9 ! -\n",
+        )
+        .unwrap();
+        let command = format!("contam {}", tblout.display());
+
+        let mut app = app_with("# STOCKHOLM 1.0\n#=GF ID tRNA\nseq1 ACGUACGU\nseq2 ACGUACGU\n//\n");
+        app.run_command(&command).unwrap();
+        assert_eq!(app.alignment.sequence_flag("seq1"), Some("contam:tmRNA"));
+        assert!(app.report.is_some());
+
+        // Screening again flags nothing new, so there is nothing to undo
+        let edits = app.edits;
+        app.run_command(&command).unwrap();
+        assert_eq!(app.edits, edits);
+        app.undo();
+        assert_eq!(app.alignment.sequence_flag("seq1"), None);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_column_popup_groups_and_scrolls() {
+        let mut app = app_with("# STOCKHOLM 1.0\nseq1 ACG\nseq2 ACG\nseq3 AUG\nseq4 A-G\n//\n");
+        // Most common first, ties by character
+        assert_eq!(
+            app.column_groups(1),
+            vec![('C', vec![0, 1], 2), ('-', vec![3], 1), ('U', vec![2], 1)]
+        );
+        // Collapsed rows count every sequence they stand for
+        app.run_command("collapse").unwrap();
+        assert_eq!(
+            app.column_groups(1),
+            vec![('C', vec![0], 2), ('-', vec![2], 1), ('U', vec![1], 1)]
+        );
+        app.run_command("collapse").unwrap();
+
+        type_keys(&mut app, "lKjjj");
+        assert!(app.show_column_popup);
+        assert_eq!(app.column_popup_top, 3);
+        type_keys(&mut app, "jk");
+        assert_eq!(app.column_popup_top, 2);
+        type_keys(&mut app, "x");
+        assert!(!app.show_column_popup);
+        assert_eq!(rows(&app), ["ACG", "ACG", "AUG", "A-G"]);
+        type_keys(&mut app, "K");
+        assert_eq!(app.column_popup_top, 0);
+    }
+
+    #[test]
+    fn test_cluster_by_identity_in_background() {
+        let mut app = app_with(
+            "# STOCKHOLM 1.0\nseq1 --GUAC--\nseq2 ACGUACGU\nseq3 UUUUUUUU\nseq4 ACGAACGA\n//\n",
+        );
+        app.run_command("cluster 90").unwrap();
+        assert!(app.cluster_job.is_some());
+        assert!(app.show_clusters);
+        app.run_command("uncluster").unwrap();
+        assert!(app.cluster_job.is_none());
+        assert_eq!(app.status_message.as_deref(), Some("Clustering cancelled"));
+        assert_eq!(app.identity_threshold, None);
+
+        app.run_command("cluster 90").unwrap();
+        app.finish_cluster_job(true);
+        assert!(app.cluster_job.is_none());
+        assert_eq!(app.identity_threshold, Some(0.9));
+        assert_eq!(
+            app.collapse_groups,
+            vec![(1, vec![0, 1]), (2, vec![2]), (3, vec![3])]
+        );
+        assert!(app.cluster_order.is_some());
+
+        // An edit while clustering discards the result
+        app.run_command("cluster 70").unwrap();
+        app.edits += 1;
+        app.finish_cluster_job(true);
+        assert_eq!(app.identity_threshold, Some(0.9));
+        assert_eq!(
+            app.status_message.as_deref(),
+            Some("The alignment changed while clustering; :cluster again")
+        );
+    }
+
+    #[test]
+    fn test_zscore_folds_in_background() {
+        use std::time::{Duration, Instant};
+
+        let hairpin = "GGGGAAACCCCAGGGAAACCCU";
+        let text = format!("# STOCKHOLM 1.0\nseq1 {hairpin}\nseq2 {hairpin}\n//\n");
+        let mut app = app_with(&text);
+        app.run_command("zscore 4").unwrap();
+        app.run_command("zscore off").unwrap();
+        assert!(app.fold_job.is_none());
+        assert_eq!(app.status_message.as_deref(), Some("Folding cancelled"));
+
+        app.run_command("zscore 4").unwrap();
+        let start = Instant::now();
+        while app.fold_job.is_some() && start.elapsed() < Duration::from_secs(30) {
+            std::thread::sleep(Duration::from_millis(10));
+            app.poll_fold_job();
+        }
+        assert!(app.show_zscores);
+        assert_eq!(app.fold_zscores.len(), 2);
+    }
+
+    #[test]
+    fn test_grepall_fills_the_quickfix_list() {
+        let dir = std::env::temp_dir().join(format!("aform-grepall-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let (a, b) = (dir.join("a.sto"), dir.join("b.sto"));
+        std::fs::write(
+            &a,
+            "# STOCKHOLM 1.0\n#=GF DE GAUC family\nseq1 ACGAUCGU\nseq2 ACGUACGU\n//\n",
+        )
+        .unwrap();
+        std::fs::write(
+            &b,
+            "# STOCKHOLM 1.0\nGAUC_like ACGUACGU\nother GGAUCAAA\n//\n",
+        )
+        .unwrap();
+
+        let mut app = App::new();
+        app.load_arg_files(vec![a.clone(), b.clone(), dir.join("missing.sto")]);
+        app.run_command("grepall GAUC").unwrap();
+        let locations: Vec<String> = app
+            .quickfix
+            .iter()
+            .map(|e| app.quickfix_location(e))
+            .collect();
+        let (a, b) = (a.display(), b.display());
+        assert_eq!(
+            locations,
+            [
+                format!("{a}:GF"),
+                format!("{a}:1:3"),
+                format!("{b}:1:1"),
+                format!("{b}:2:2")
+            ]
+        );
+        assert!(app.show_quickfix);
+        assert!(
+            app.status_message
+                .as_deref()
+                .unwrap()
+                .starts_with("4 matches for 'GAUC' in 2 file(s) (could not read")
+        );
+
+        // Stepping through the list opens the file of each match
+        app.run_command("cnext").unwrap();
+        assert_eq!((app.arg_index, app.cursor_row, app.cursor_col), (0, 0, 2));
+        app.run_command("cnext").unwrap();
+        app.run_command("cnext").unwrap();
+        assert_eq!((app.arg_index, app.cursor_row, app.cursor_col), (1, 1, 1));
+        app.run_command("cnext").unwrap();
+        assert_eq!(app.status_message.as_deref(), Some("No more items"));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_alifold_sets_ss_cons_and_mis() {
+        use std::os::unix::fs::PermissionsExt;
+
+        // A stand-in for RNAalifold that checks its options
+        let dir = std::env::temp_dir().join(format!("aform-alifold-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let program = dir.join("RNAalifold");
+        std::fs::write(
+            &program,
+            "#!/bin/sh\n\
+             [ \"$*\" = \"--noPS -r --mis\" ] || exit 1\n\
+             cat > /dev/null\n\
+             echo ACGRACGU\n\
+             echo '((....)) ( -1.50 = -1.00 + -0.50)'\n",
+        )
+        .unwrap();
+        std::fs::set_permissions(&program, std::fs::Permissions::from_mode(0o755)).unwrap();
+
+        let mut app = app_with(THREE);
+        app.run_command(&format!("set alifold.program={}", program.display()))
+            .unwrap();
+        app.run_command("set alifold.ribosum=on").unwrap();
+        app.run_command("set alifold.mis=on").unwrap();
+        app.run_command("alifold").unwrap();
+        app.finish_alifold_job(true);
+        assert_eq!(app.alignment.ss_cons(), Some("((....))"));
+        assert_eq!(app.alignment.column_annotation("MIS"), Some("ACGRACGU"));
+        assert_eq!(app.structure_cache.pairs().len(), 2);
+        assert_eq!(app.script_log.last().unwrap().command, "alifold");
+        app.undo();
+        assert_eq!(app.alignment.ss_cons(), None);
+
+        // RNAalifold's complaint is passed on
+        app.run_command("set alifold.mis=off").unwrap();
+        app.run_command("alifold").unwrap();
+        app.finish_alifold_job(true);
+        assert_eq!(app.alignment.ss_cons(), None);
+        assert!(app.status_message.as_deref().unwrap().contains("failed"));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_marks_and_jumps() {
+        let mut app = app_with(THREE);
+        type_keys(&mut app, "jlllma");
+        assert_eq!(app.marks[&'a'].id, "seq2");
+        type_keys(&mut app, "G0");
+        assert_eq!((app.cursor_row, app.cursor_col), (2, 0));
+        type_keys(&mut app, "`a");
+        assert_eq!((app.cursor_row, app.cursor_col), (1, 3));
+        type_keys(&mut app, "<C-o>");
+        assert_eq!((app.cursor_row, app.cursor_col), (2, 0));
+        type_keys(&mut app, "<C-o>");
+        assert_eq!((app.cursor_row, app.cursor_col), (1, 3));
+        type_keys(&mut app, "<C-o>");
+        assert_eq!(
+            app.status_message.as_deref(),
+            Some("Already at the oldest jump")
+        );
+        type_keys(&mut app, "<Tab><Tab>");
+        assert_eq!((app.cursor_row, app.cursor_col), (1, 3));
+
+        // A mark follows its sequence when rows move, and says so once it is gone
+        type_keys(&mut app, "<A-j>gg`a");
+        assert_eq!((app.cursor_row, app.cursor_col), (2, 3));
+        type_keys(&mut app, "dd`a");
+        assert_eq!(
+            app.status_message.as_deref(),
+            Some("seq2 is deleted or hidden")
+        );
+    }
+
+    #[test]
+    fn test_helix_view() {
+        let text = "# STOCKHOLM 1.0\nseq1 GGACAACC\nseq2 GGAUAACC\nseq3 GAAUAAUC\n\
+                    #=GC SS_cons <<.<>.>>\n//\n";
+        let mut app = app_with(text);
+        app.cursor_col = 2;
+        assert_eq!(
+            app.run_command("helix"),
+            Err("Cursor is not on a helix (:helix N opens helix N)".to_string())
+        );
+        assert_eq!(
+            app.run_command("helix P3"),
+            Err("Invalid helix: P3 (1-2)".to_string())
+        );
+        assert!(app.helix_view.is_none());
+
+        // The helix at the cursor, from the cursor's sequence
+        app.cursor_row = 1;
+        app.cursor_col = 6;
+        app.run_command("helix").unwrap();
+        let view = app.helix_view.as_ref().unwrap();
+        assert_eq!((view.helix, view.top), (0, 1));
+        app.shift_helix_view(5);
+        assert_eq!(app.helix_view.as_ref().unwrap().helix, 1);
+        app.shift_helix_view(-5);
+        app.scroll_helix_view(10);
+        assert_eq!(app.helix_view.as_ref().unwrap().top, 2);
+
+        app.helix_view_enter();
+        assert!(app.helix_view.is_none());
+        assert_eq!((app.cursor_row, app.cursor_col), (2, 0));
+        app.run_command("helix p2").unwrap();
+        assert_eq!(app.helix_view.as_ref().unwrap().helix, 1);
+    }
+
+    #[test]
+    fn test_edits_drop_column_confidence() {
+        let mut app = app_with(THREE);
+        app.run_command("confidence").unwrap();
+        assert!(app.column_confidence.is_some() && app.show_confidence_bar);
+
+        app.run_command("lower").unwrap();
+        assert!(app.column_confidence.is_none() && !app.show_confidence_bar);
+        assert_eq!(
+            app.run_command("confidence mask 0.5"),
+            Err("No confidence scores (run :confidence first)".to_string())
+        );
+
+        app.run_command("confidence").unwrap();
+        app.undo();
+        assert!(app.column_confidence.is_none());
+    }
+
+    #[test]
+    fn test_key_edits_replay_from_script() {
+        let mut app = app_with(THREE);
+        type_keys(&mut app, "llji..<Esc>j:upper<CR>ggxyu");
+        let commands: Vec<&str> = app.script_log.iter().map(|e| e.command.as_str()).collect();
+        assert_eq!(
+            commands,
+            ["normal i..<Esc>", "upper", "normal xy", "normal u"]
+        );
+
+        let mut replay = app_with(THREE);
+        for entry in &app.script_log {
+            replay
+                .run_recorded(&format!("goto {} {}", entry.id, entry.column))
+                .unwrap();
+            replay.run_recorded(&entry.command).unwrap();
+        }
+        assert_eq!(rows(&replay), rows(&app));
+        assert_eq!(replay.script_log, app.script_log);
+    }
+
+    #[test]
+    fn test_failed_commands_return_errors() {
+        let mut app = app_with(THREE);
+        assert!(app.run_command("sort bogus").is_err());
+        assert!(app.run_command("e /nonexistent/file.sto").is_err());
+        assert!(app.run_command("normal <Bogus>").is_err());
+        assert!(app.run_command("normal u").is_err());
+        assert!(app.run_command("sort name").is_ok());
+    }
+
+    #[test]
+    fn test_gap_settings_are_validated_and_reach_writers() {
+        let mut app = app_with(THREE);
+        assert!(app.run_command("set gap=A").is_err());
+        assert!(app.run_command("set endgap=7").is_err());
+        assert!(app.run_command("set endgap=").is_err());
+        assert_eq!(app.gap_char, '.');
+        app.run_command("set gap=*").unwrap();
+        assert!(app.gap_chars.contains(&'*'));
+        assert_eq!(app.write_options.gap_chars, app.gap_chars);
+
+        let config = EditorConfig {
+            gap: Some('x'),
+            endgap: None,
+            gapchars: Some(".-".to_string()),
+        };
+        assert!(app.apply_editor_config(&config).is_some());
+        assert_eq!(app.gap_char, '*');
+        assert_eq!(app.write_options.gap_chars, ['.', '-', '*', '~']);
+    }
+
+    #[test]
+    fn test_counted_edit_is_one_modification() {
+        let mut app = app_with(THREE);
+        let edits = app.edits;
+        type_keys(&mut app, "3I");
+        assert_eq!(app.edits, edits + 1);
+        assert_eq!(rows(&app)[0], "~~~ACGUACGU");
+        assert_eq!(app.history.undo_count(), 1);
+        app.undo();
+        assert_eq!(rows(&app)[0], "ACGUACGU");
+    }
+
+    #[test]
+    fn test_counted_shift_is_one_step() {
+        // A #=GC row the wrong width is left alone, and a shift doesn't look at it
+        let mut app = app_with("# STOCKHOLM 1.0\nseq1 ACGU....\nseq2 ACGUACGU\n#=GC RF xxx\n//\n");
+        let edits = app.edits;
+        type_keys(&mut app, "2>");
+        assert_eq!(rows(&app)[0], "~~ACGU..");
+        assert_eq!(app.edits, edits + 1);
+        assert!(app.integrity_problems.is_empty());
+        assert_eq!(app.alignment.rf(), Some("xxx"));
+
+        // Shifting from another column is another step
+        type_keys(&mut app, "ll>");
+        assert_eq!(rows(&app)[0], "~~~ACGU.");
+        app.undo();
+        assert_eq!(rows(&app)[0], "~~ACGU..");
+        app.undo();
+        assert_eq!(rows(&app)[0], "ACGU....");
+    }
+
+    #[test]
+    fn test_read_only_refuses_edits_but_undoes_settings() {
+        let mut app = app_with(THREE);
+        app.run_command("set readonly").unwrap();
+        assert!(app.read_only);
+        let refused = Err("Read-only: editing is off (:set noreadonly to edit)".to_string());
+        assert_eq!(app.run_command("lower"), refused);
+        type_keys(&mut app, "I");
+        assert_eq!(rows(&app)[0], "ACGUACGU");
+        assert!(
+            app.save_file()
+                .unwrap_err()
+                .starts_with("Read-only: not saved")
+        );
+
+        // Settings still change, and undo and redo
+        app.run_command("set gap=-").unwrap();
+        app.undo();
+        assert_eq!(app.gap_char, '.');
+        app.redo();
+        assert_eq!(app.gap_char, '-');
+
+        app.run_command("set noreadonly").unwrap();
+        app.run_command("lower").unwrap();
+        assert_eq!(rows(&app)[0], "acguacgu");
+    }
+
+    #[test]
+    fn test_load_unaligned_fasta_waits_for_the_pad_answer() {
+        let dir = std::env::temp_dir().join(format!("aform-pad-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("ragged.fa");
+        std::fs::write(&path, ">a\nACGUAC\n>b\nACG\n").unwrap();
+
+        let mut app = App::new();
+        assert_eq!(app.load_file(&path), Ok(Loaded::PadPrompt));
+        assert!(app.file_path.is_none());
+        assert!(matches!(app.confirm, Some(Confirm::PadAlignment { .. })));
+        app.answer_confirm(true);
+        assert_eq!(app.file_path.as_deref(), Some(path.as_path()));
+        assert_eq!(app.alignment.width(), 6);
+        assert!(app.modified);
+
+        std::fs::write(&path, ">a\nACGUAC\n>b\nACG---\n").unwrap();
+        assert_eq!(app.load_file(&path), Ok(Loaded::Opened));
+        assert!(app.confirm.is_none());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_pileup_groups_its_own_column() {
+        let mut app = app_with(THREE);
+        app.open_pileup(3);
+        let pileup = app.pileup.as_ref().unwrap();
+        assert_eq!(pileup.col, 3);
+        assert_eq!(pileup.groups, vec![('U', vec![0, 1], 2), ('A', vec![2], 1)]);
+        assert_eq!(app.cursor_col, 0);
+
+        app.toggle_pileup_group();
+        assert_eq!(app.pileup.as_ref().unwrap().lines().len(), 4);
+        app.shift_pileup(1);
+        let pileup = app.pileup.as_ref().unwrap();
+        assert_eq!(pileup.col, 4);
+        assert_eq!(pileup.groups, vec![('A', vec![0, 1, 2], 3)]);
+        assert_eq!(pileup.lines().len(), 1);
+        // Past the last column stays on it
+        app.shift_pileup(10);
+        assert_eq!(app.pileup.as_ref().unwrap().col, 7);
+        assert_eq!(app.cursor_col, 0);
+    }
+
+    #[test]
+    fn test_export_selected_columns() {
+        let dir = std::env::temp_dir().join(format!("aform-columns-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("columns.sto");
+
+        let mut app =
+            app_with("# STOCKHOLM 1.0\nseq1/1-8 ACGUACGU\nseq2 AC--ACGU\nseq3 ACGAACGU\n//\n");
+        app.cursor_col = 2;
+        app.enter_visual_mode();
+        app.cursor_col = 5;
+        app.run_command(&format!("export columns {}", path.display()))
+            .unwrap();
+        let (alignments, ..) = crate::formats::read_file(&path, None).unwrap();
+        let ids: Vec<&str> = alignments[0]
+            .sequences
+            .iter()
+            .map(|s| s.id.as_str())
+            .collect();
+        assert_eq!(ids, ["seq1/3-6", "seq2", "seq3"]);
+        let data: Vec<String> = alignments[0].sequences.iter().map(|s| s.data()).collect();
+        assert_eq!(data, ["GUAC", "--AC", "GAAC"]);
+        // The open alignment is untouched
+        assert_eq!(app.alignment.width(), 8);
+        assert!(!app.modified);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_repeat_last_edit() {
+        let mut app = app_with(THREE);
+        type_keys(&mut app, ".");
+        assert_eq!(app.status_message.as_deref(), Some("No edit to repeat"));
+
+        type_keys(&mut app, "2I.");
+        assert_eq!(rows(&app)[0], "~~~~ACGUACGU");
+        // A count replaces the one the edit was made with
+        type_keys(&mut app, "3.");
+        assert_eq!(rows(&app)[0], "~~~~~~~ACGUACGU");
+        app.undo();
+        assert_eq!(rows(&app)[0], "~~~~ACGUACGU");
+
+        // A refused edit leaves the last one to repeat
+        type_keys(&mut app, "$X");
+        assert_eq!(app.alignment.width(), 12);
+        assert_eq!(app.last_edit.unwrap().edit, Edit::InsertGapColumn);
+
+        // An edit on a selection repeats on one of the same size at the cursor
+        let mut app = app_with(THREE);
+        type_keys(&mut app, "vju");
+        type_keys(&mut app, "gglllll.");
+        assert_eq!(rows(&app), ["aCGUAcGU", "aCGUAcGU", "ACGAACGU"]);
+    }
+
+    #[test]
+    fn test_focus_follows_rows_and_columns() {
+        let mut app = app_with(THREE);
+        app.cursor_col = 2;
+        app.enter_visual_mode();
+        app.cursor_row = 1;
+        app.cursor_col = 4;
+        app.enter_command_mode();
+        app.run_command("focus").unwrap();
+        app.exit_visual_mode();
+        let focused = |app: &App, row: usize| -> Vec<usize> {
+            (0..app.alignment.width())
+                .filter(|&col| app.in_color_focus(row, col))
+                .collect()
+        };
+        assert_eq!(focused(&app, 0), [2, 3, 4]);
+        assert!(focused(&app, 2).is_empty());
+
+        // seq1 moves to the bottom, and a gap column goes in before the block
+        app.cursor_row = 0;
+        app.cursor_col = 0;
+        app.run_command("move 3").unwrap();
+        type_keys(&mut app, "I");
+        assert_eq!(focused(&app, 0), [3, 4, 5]);
+        assert!(focused(&app, 1).is_empty());
+        assert_eq!(focused(&app, 2), [3, 4, 5]);
+        // ...and one inside it
+        app.cursor_col = 4;
+        type_keys(&mut app, "I");
+        assert_eq!(focused(&app, 2), [3, 4, 5, 6]);
+        type_keys(&mut app, "X");
+        assert_eq!(focused(&app, 2), [3, 4, 5]);
+
+        let text = "# STOCKHOLM 1.0\nseq1 GGACAACC\nseq2 GGAUAACC\n#=GC SS_cons <<.<>.>>\n//\n";
+        let mut app = app_with(text);
+        app.run_command("focus helix 2").unwrap();
+        assert_eq!(focused(&app, 0), [3, 4]);
+        type_keys(&mut app, "I");
+        assert_eq!(focused(&app, 0), [4, 5]);
+        app.cursor_col = 4;
+        app.run_command("focus helix").unwrap();
+        assert!(app.color_focus.is_none());
+    }
+
+    #[test]
+    fn test_bookmarks_follow_their_sequences() {
+        let mut app = app_with(THREE);
+        app.run_command("bookmark").unwrap();
+        app.cursor_row = 2;
+        app.cursor_col = 3;
+        app.enter_visual_mode();
+        app.cursor_col = 5;
+        app.enter_command_mode();
+        app.run_command("bookmark").unwrap();
+        assert_eq!(
+            app.status_message.as_deref(),
+            Some("Bookmark 2 at seq3, column 4")
+        );
+        app.exit_visual_mode();
+
+        // seq3 moves to the top, and seq1 to the middle
+        app.run_command("move 1").unwrap();
+        app.run_command("present").unwrap();
+        assert_eq!((app.cursor_row, app.cursor_col), (1, 0));
+        type_keys(&mut app, " ");
+        assert_eq!((app.cursor_row, app.cursor_col), (0, 3));
+        assert_eq!((app.viewport_row, app.viewport_col), (0, 3));
+        type_keys(&mut app, " ");
+        assert_eq!(app.status_message.as_deref(), Some("Bookmark 1/2"));
+        assert_eq!(app.cursor_row, 1);
+        type_keys(&mut app, "<BS>");
+        assert_eq!(app.cursor_row, 0);
+    }
+
+    #[test]
+    fn test_preview_leaves_pins_focus_and_confidence() {
+        let mut app = app_with("# STOCKHOLM 1.0\ns --ACGU\nt --AC-U\n//\n");
+        app.run_command("pin 4").unwrap();
+        app.color_focus = Some(ColorFocus::Region {
+            ids: vec!["s".to_string()],
+            cols: (2, 4),
+        });
+        app.column_confidence = Some(vec![Some(0.5); 6]);
+        app.show_confidence_bar = true;
+
+        type_keys(&mut app, ":trim --preview<CR>n");
+        assert_eq!(app.pinned_columns, [3]);
+        assert_eq!(
+            app.color_focus,
+            Some(ColorFocus::Region {
+                ids: vec!["s".to_string()],
+                cols: (2, 4),
+            })
+        );
+        assert_eq!(app.column_confidence(0), Some(0.5));
+        assert!(app.show_confidence_bar);
+
+        // Applying it moves them as the edit itself does
+        type_keys(&mut app, ":trim --preview<CR>y");
+        assert_eq!(rows(&app), ["ACGU", "AC-U"]);
+        assert_eq!(app.pinned_columns, [1]);
+        assert!(app.column_confidence.is_none());
+    }
+}
@@ -0,0 +1,901 @@
+//! Headless subcommands (`aform convert`, `aform stats`, `aform validate`,
+//! `aform render`) that run the file formats and analysis code without starting
+//! the editor, for scripts, makefiles and pre-commit hooks.
+
+use std::io::{self, Write};
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+
+use clap::{Subcommand, ValueEnum};
+use serde::Serialize;
+use thiserror::Error;
+
+use crate::app::{App, ColorScheme};
+use crate::clustering::sequence_identity;
+use crate::color::{
+    CONSERVATION_HIGH, CONSERVATION_LOW, CONSERVATION_MED, column_stats, detect_sequence_type,
+};
+use crate::formats::{self, Format, FormatError, LineEnding, WriteOptions};
+use crate::stockholm::validate::{self, Problem};
+use crate::stockholm::writer::StockholmStyle;
+use crate::stockholm::{Alignment, DEFAULT_GAP_CHARS as GAP_CHARS};
+
+#[derive(Error, Debug)]
+pub enum CliError {
+    #[error("{path}: {source}")]
+    Read { path: String, source: FormatError },
+    #[error("{path}: {source}")]
+    Write { path: String, source: io::Error },
+    #[error(
+        "Unknown format: {0} (stockholm, fasta, clustal, a2m, phylip, msf, selex, nexus or json)"
+    )]
+    UnknownFormat(String),
+    #[error("{path} holds {count} alignments; only Stockholm output keeps more than one")]
+    MultipleAlignments { path: String, count: usize },
+    #[error("{problems} problem(s) found in {files} file(s)")]
+    Invalid { problems: usize, files: usize },
+    #[error("Unknown color scheme: {0}")]
+    UnknownColor(String),
+    #[error("{path}: {message}")]
+    Render { path: String, message: String },
+    #[error("{0} figure(s) not rendered")]
+    NotRendered(usize),
+}
+
+#[derive(Subcommand, Debug)]
+pub enum Command {
+    /// Convert an alignment to another format without opening the editor.
+    Convert(ConvertArgs),
+    /// Print summary statistics of alignments.
+    Stats(StatsArgs),
+    /// Check alignments for problems, exiting non-zero if any are found.
+    Validate(ValidateArgs),
+    /// Render alignments to HTML or SVG figures, colored as in the editor.
+    Render(RenderArgs),
+}
+
+#[derive(clap::Args, Debug)]
+pub struct ConvertArgs {
+    /// Alignment to read (`-` for stdin).
+    input: PathBuf,
+
+    /// File to write (`-` for stdout).
+    output: PathBuf,
+
+    /// Input format (default: from the extension or contents).
+    #[arg(long, value_name = "FORMAT")]
+    from: Option<String>,
+
+    /// Output format: stockholm, fasta, clustal, a2m, phylip, msf, selex, nexus
+    /// or json (default: from the extension, else Stockholm).
+    #[arg(long, value_name = "FORMAT")]
+    to: Option<String>,
+
+    /// Write Stockholm in interleaved blocks of N columns.
+    #[arg(long, value_name = "N")]
+    wrap: Option<usize>,
+
+    /// Write compact Pfam-style Stockholm (one line per sequence).
+    #[arg(long)]
+    pfam: bool,
+
+    /// Write Windows (CRLF) line endings.
+    #[arg(long)]
+    crlf: bool,
+}
+
+#[derive(clap::Args, Debug)]
+pub struct StatsArgs {
+    /// Alignment files (`-` for stdin).
+    #[arg(required = true)]
+    files: Vec<PathBuf>,
+
+    /// Output format.
+    #[arg(long, value_enum, default_value_t = StatsFormat::Text)]
+    format: StatsFormat,
+
+    /// List every column's consensus residue, conservation and gap fraction.
+    #[arg(long)]
+    columns: bool,
+}
+
+#[derive(clap::Args, Debug)]
+pub struct ValidateArgs {
+    /// Alignment files (`-` for stdin).
+    #[arg(required = true)]
+    files: Vec<PathBuf>,
+}
+
+#[derive(clap::Args, Debug)]
+pub struct RenderArgs {
+    /// Alignment files.
+    #[arg(required = true)]
+    files: Vec<PathBuf>,
+
+    /// Directory to write the figures to (created if missing).
+    #[arg(short, long, value_name = "DIR")]
+    output: PathBuf,
+
+    /// Color scheme, as for `:color` (e.g. base, cons, structure, base+cons).
+    #[arg(long, default_value = "base")]
+    color: String,
+
+    /// Only render columns START-END (1-based, inclusive).
+    #[arg(long, value_name = "START-END", value_parser = parse_region)]
+    region: Option<Range<usize>>,
+
+    /// Figure format.
+    #[arg(long, value_enum, default_value_t = RenderFormat::Html)]
+    format: RenderFormat,
+
+    /// Use colors for a light page background.
+    #[arg(long)]
+    light: bool,
+}
+
+/// Figure format of `aform render`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum RenderFormat {
+    /// A standalone HTML page.
+    Html,
+    /// An SVG vector figure.
+    Svg,
+}
+
+/// Parse a `--region` such as `1-200` into 0-based columns.
+fn parse_region(s: &str) -> Result<Range<usize>, String> {
+    let (start, end) = s
+        .split_once('-')
+        .ok_or_else(|| "expected START-END, e.g. 1-200".to_string())?;
+    let start: usize = start
+        .trim()
+        .parse()
+        .map_err(|_| format!("bad start: {start}"))?;
+    let end: usize = end.trim().parse().map_err(|_| format!("bad end: {end}"))?;
+    if start == 0 || end < start {
+        return Err("expected 1 <= START <= END".to_string());
+    }
+    Ok(start - 1..end)
+}
+
+/// Output format of `aform stats`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum StatsFormat {
+    /// Aligned `label: value` lines.
+    Text,
+    /// Tab-separated with a header line, one row per alignment (or column).
+    Tsv,
+    /// An array with one object per alignment.
+    Json,
+}
+
+/// Format written by `convert`: an alignment format, or export-only NEXUS or
+/// JSON.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Output {
+    Alignment(Format),
+    Nexus,
+    Json,
+}
+
+impl Output {
+    fn from_name(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "nexus" | "nex" | "nxs" => Some(Self::Nexus),
+            "json" => Some(Self::Json),
+            _ => Format::from_name(name).map(Self::Alignment),
+        }
+    }
+
+    /// Output format implied by a file name (Stockholm if unknown).
+    fn from_path(path: &Path) -> Self {
+        let export = path
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .and_then(Self::from_name)
+            .filter(|output| !matches!(output, Self::Alignment(_)));
+        export.unwrap_or_else(|| {
+            Self::Alignment(Format::from_path(path).unwrap_or(Format::Stockholm))
+        })
+    }
+}
+
+/// Run a subcommand.
+pub fn run(command: Command) -> Result<(), CliError> {
+    match command {
+        Command::Convert(args) => convert(&args),
+        Command::Stats(args) => stats(&args),
+        Command::Validate(args) => validate(&args),
+        Command::Render(args) => render(&args),
+    }
+}
+
+/// Whether a path argument means stdin/stdout.
+fn is_stdio(path: &Path) -> bool {
+    path.as_os_str() == "-"
+}
+
+/// Read every alignment in `path` (stdin for `-`), as `format` if given.
+fn read_input(path: &Path, format: Option<Format>) -> Result<Vec<Alignment>, CliError> {
+    let result = if is_stdio(path) {
+        formats::read_stdin(format)
+    } else {
+        formats::read_file(path, format)
+    };
+    result
+        .map(|(alignments, ..)| alignments)
+        .map_err(|source| CliError::Read {
+            path: path.display().to_string(),
+            source,
+        })
+}
+
+fn convert(args: &ConvertArgs) -> Result<(), CliError> {
+    let from = match &args.from {
+        Some(name) => Some(Format::from_name(name).ok_or(CliError::UnknownFormat(name.clone()))?),
+        None => None,
+    };
+    let output = match &args.to {
+        Some(name) => Output::from_name(name).ok_or(CliError::UnknownFormat(name.clone()))?,
+        None if is_stdio(&args.output) => Output::Alignment(Format::Stockholm),
+        None => Output::from_path(&args.output),
+    };
+    let options = WriteOptions {
+        wrap: args.wrap,
+        style: if args.pfam {
+            StockholmStyle::Pfam
+        } else {
+            StockholmStyle::Stockholm
+        },
+        line_ending: if args.crlf {
+            LineEnding::Crlf
+        } else {
+            LineEnding::Lf
+        },
+        ..WriteOptions::default()
+    };
+
+    let alignments = read_input(&args.input, from)?;
+    if alignments.len() > 1 && output != Output::Alignment(Format::Stockholm) {
+        return Err(CliError::MultipleAlignments {
+            path: args.input.display().to_string(),
+            count: alignments.len(),
+        });
+    }
+
+    let write_error = |source| CliError::Write {
+        path: args.output.display().to_string(),
+        source,
+    };
+    if is_stdio(&args.output) {
+        // A closed pipe (`aform convert ... - | head`) is not an error
+        write_alignments(&alignments, output, &options, io::stdout().lock()).or_else(|e| {
+            if e.kind() == io::ErrorKind::BrokenPipe {
+                Ok(())
+            } else {
+                Err(e)
+            }
+        })
+    } else {
+        formats::write_safely(&args.output, false, |file| {
+            write_alignments(&alignments, output, &options, file)
+        })
+    }
+    .map_err(write_error)
+}
+
+/// Write the alignments in `output` format (several only as Stockholm).
+fn write_alignments<W: Write>(
+    alignments: &[Alignment],
+    output: Output,
+    options: &WriteOptions,
+    writer: W,
+) -> io::Result<()> {
+    let mut writer = formats::LineEndingWriter::new(writer, options.line_ending);
+    match (output, alignments) {
+        (Output::Nexus, [alignment]) => {
+            let sequence_type = detect_sequence_type(alignment, &options.gap_chars);
+            formats::nexus::write(alignment, sequence_type, &options.gap_chars, &mut writer)?;
+        }
+        (Output::Json, [alignment]) => formats::json::write(alignment, &mut writer)?,
+        (Output::Alignment(format), [alignment]) => {
+            format.write(alignment, options, &mut writer)?;
+        }
+        _ => {
+            let alignments: Vec<&Alignment> = alignments.iter().collect();
+            crate::stockholm::writer::write_records(
+                &alignments,
+                options.style,
+                options.wrap,
+                &mut writer,
+            )?;
+        }
+    }
+    writer.flush()
+}
+
+/// Number of columns in each conservation band (most common residue frequency).
+#[derive(Debug, Default, PartialEq, Serialize)]
+struct ConservationBands {
+    mean: f64,
+    /// Columns at or above 80%.
+    high: usize,
+    /// Columns from 60% to 80%.
+    medium: usize,
+    /// Columns from 40% to 60%.
+    low: usize,
+    /// Columns below 40%.
+    variable: usize,
+}
+
+/// One column of `aform stats --columns` (1-based).
+#[derive(Debug, PartialEq, Serialize)]
+struct ColumnSummary {
+    column: usize,
+    consensus: char,
+    conservation: f64,
+    gap_fraction: f64,
+}
+
+/// Most sequences compared pairwise for the mean identity; larger alignments
+/// are estimated from this many, evenly spaced through the file.
+const IDENTITY_SAMPLE: usize = 1000;
+
+/// Summary statistics of one alignment.
+#[derive(Debug, PartialEq, Serialize)]
+struct Stats {
+    /// File name, with the record number for multi-alignment files.
+    file: String,
+    /// `#=GF ID`, if any.
+    id: Option<String>,
+    sequences: usize,
+    columns: usize,
+    /// Percentage of all cells that are gaps.
+    gap_percent: f64,
+    /// Mean identity over all pairs of sequences, as a percentage (None for a
+    /// single sequence).
+    mean_identity: Option<f64>,
+    /// Sequences the mean identity was estimated from, when there were more
+    /// than `IDENTITY_SAMPLE`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    identity_sample: Option<usize>,
+    /// Base pairs in SS_cons (None without one, or if it doesn't parse).
+    ss_pairs: Option<usize>,
+    conservation: ConservationBands,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    column_stats: Option<Vec<ColumnSummary>>,
+}
+
+impl Stats {
+    fn new(file: String, alignment: &Alignment, with_columns: bool) -> Self {
+        let width = alignment.width();
+        let rows: Vec<&[char]> = alignment.sequences.iter().map(|s| s.chars()).collect();
+
+        let gaps: usize = rows
+            .iter()
+            .map(|row| row.iter().filter(|c| GAP_CHARS.contains(c)).count())
+            .sum();
+        let cells = rows.len() * width;
+
+        // Every pair is quadratic in the sequences, so big alignments are sampled
+        let sample: Vec<&[char]> = if rows.len() > IDENTITY_SAMPLE {
+            (0..IDENTITY_SAMPLE)
+                .map(|i| rows[i * rows.len() / IDENTITY_SAMPLE])
+                .collect()
+        } else {
+            rows.clone()
+        };
+        let mut identity_sum = 0.0;
+        let mut pairs = 0;
+        for (i, a) in sample.iter().enumerate() {
+            for b in &sample[i + 1..] {
+                identity_sum += sequence_identity(a, b, &GAP_CHARS);
+                pairs += 1;
+            }
+        }
+
+        let ss_pairs = alignment.ss_cons().and_then(|ss| {
+            let mut cache = crate::structure::StructureCache::new();
+            cache.update(ss).ok().map(|()| cache.pairs().len())
+        });
+
+        let mut conservation = ConservationBands::default();
+        let mut column_summaries = Vec::new();
+        for col in 0..width {
+            let stats = column_stats(col, alignment, &GAP_CHARS);
+            conservation.mean += stats.conservation;
+            match stats.conservation {
+                c if c >= CONSERVATION_HIGH => conservation.high += 1,
+                c if c >= CONSERVATION_MED => conservation.medium += 1,
+                c if c >= CONSERVATION_LOW => conservation.low += 1,
+                _ => conservation.variable += 1,
+            }
+            if with_columns {
+                let column_gaps = rows
+                    .iter()
+                    .filter(|row| row.get(col).is_none_or(|c| GAP_CHARS.contains(c)))
+                    .count();
+                column_summaries.push(ColumnSummary {
+                    column: col + 1,
+                    consensus: stats.consensus,
+                    conservation: stats.conservation,
+                    gap_fraction: column_gaps as f64 / rows.len().max(1) as f64,
+                });
+            }
+        }
+        if width > 0 {
+            conservation.mean /= width as f64;
+        }
+
+        Self {
+            file,
+            id: alignment.get_file_annotation("ID").map(str::to_string),
+            sequences: rows.len(),
+            columns: width,
+            gap_percent: if cells > 0 {
+                100.0 * gaps as f64 / cells as f64
+            } else {
+                0.0
+            },
+            mean_identity: (pairs > 0).then(|| 100.0 * identity_sum / pairs as f64),
+            identity_sample: (sample.len() < rows.len()).then_some(sample.len()),
+            ss_pairs,
+            conservation,
+            column_stats: with_columns.then_some(column_summaries),
+        }
+    }
+}
+
+fn stats(args: &StatsArgs) -> Result<(), CliError> {
+    let mut all = Vec::new();
+    for path in &args.files {
+        let alignments = read_input(path, None)?;
+        let name = if is_stdio(path) {
+            "stdin".to_string()
+        } else {
+            path.display().to_string()
+        };
+        for (i, alignment) in alignments.iter().enumerate() {
+            let file = if alignments.len() > 1 {
+                format!("{name}[{}]", i + 1)
+            } else {
+                name.clone()
+            };
+            all.push(Stats::new(file, alignment, args.columns));
+        }
+    }
+
+    let mut writer = io::stdout().lock();
+    write_stats(&all, args.format, &mut writer)
+        .and_then(|()| writer.flush())
+        .or_else(|e| {
+            if e.kind() == io::ErrorKind::BrokenPipe {
+                Ok(())
+            } else {
+                Err(e)
+            }
+        })
+        .map_err(|source| CliError::Write {
+            path: "stdout".to_string(),
+            source,
+        })
+}
+
+/// Write statistics of several alignments in `format`.
+fn write_stats<W: Write>(all: &[Stats], format: StatsFormat, mut writer: W) -> io::Result<()> {
+    match format {
+        StatsFormat::Json => {
+            serde_json::to_writer_pretty(&mut writer, all)?;
+            writeln!(writer)
+        }
+        StatsFormat::Tsv if all.iter().any(|s| s.column_stats.is_some()) => {
+            writeln!(
+                writer,
+                "file\tcolumn\tconsensus\tconservation\tgap_fraction"
+            )?;
+            for stats in all {
+                for column in stats.column_stats.iter().flatten() {
+                    writeln!(
+                        writer,
+                        "{}\t{}\t{}\t{:.4}\t{:.4}",
+                        stats.file,
+                        column.column,
+                        column.consensus,
+                        column.conservation,
+                        column.gap_fraction
+                    )?;
+                }
+            }
+            Ok(())
+        }
+        StatsFormat::Tsv => {
+            writeln!(
+                writer,
+                "file\tid\tsequences\tcolumns\tgap_percent\tmean_identity\tss_pairs\t\
+                 mean_conservation\thigh\tmedium\tlow\tvariable"
+            )?;
+            for stats in all {
+                let c = &stats.conservation;
+                writeln!(
+                    writer,
+                    "{}\t{}\t{}\t{}\t{:.2}\t{}\t{}\t{:.4}\t{}\t{}\t{}\t{}",
+                    stats.file,
+                    stats.id.as_deref().unwrap_or(""),
+                    stats.sequences,
+                    stats.columns,
+                    stats.gap_percent,
+                    stats
+                        .mean_identity
+                        .map_or(String::new(), |i| format!("{i:.2}")),
+                    stats.ss_pairs.map_or(String::new(), |p| p.to_string()),
+                    c.mean,
+                    c.high,
+                    c.medium,
+                    c.low,
+                    c.variable
+                )?;
+            }
+            Ok(())
+        }
+        StatsFormat::Text => {
+            for (i, stats) in all.iter().enumerate() {
+                if i > 0 {
+                    writeln!(writer)?;
+                }
+                write_stats_text(stats, &mut writer)?;
+            }
+            Ok(())
+        }
+    }
+}
+
+fn write_stats_text<W: Write>(stats: &Stats, mut writer: W) -> io::Result<()> {
+    let c = &stats.conservation;
+    writeln!(writer, "File:           {}", stats.file)?;
+    if let Some(id) = &stats.id {
+        writeln!(writer, "ID:             {id}")?;
+    }
+    writeln!(writer, "Sequences:      {}", stats.sequences)?;
+    writeln!(writer, "Columns:        {}", stats.columns)?;
+    writeln!(writer, "Gaps:           {:.1}%", stats.gap_percent)?;
+    match (stats.mean_identity, stats.identity_sample) {
+        (Some(identity), Some(n)) => writeln!(
+            writer,
+            "Mean identity:  {identity:.1}% (sample of {n} sequences)"
+        )?,
+        (Some(identity), None) => writeln!(writer, "Mean identity:  {identity:.1}%")?,
+        (None, _) => writeln!(writer, "Mean identity:  -")?,
+    }
+    match stats.ss_pairs {
+        Some(pairs) => writeln!(writer, "SS_cons pairs:  {pairs}")?,
+        None => writeln!(writer, "SS_cons pairs:  -")?,
+    }
+    writeln!(
+        writer,
+        "Conservation:   mean {:.1}%; {} columns >=80%, {} >=60%, {} >=40%, {} below",
+        100.0 * c.mean,
+        c.high,
+        c.medium,
+        c.low,
+        c.variable
+    )?;
+    if let Some(columns) = &stats.column_stats {
+        writeln!(writer)?;
+        writeln!(writer, "Column  Consensus  Conservation  Gaps")?;
+        for column in columns {
+            writeln!(
+                writer,
+                "{:>6}  {:^9}  {:>11.1}%  {:>3.0}%",
+                column.column,
+                column.consensus,
+                100.0 * column.conservation,
+                100.0 * column.gap_fraction
+            )?;
+        }
+    }
+    Ok(())
+}
+
+/// Problems in an alignment file: line-level checks for Stockholm, and for other
+/// formats the parser's verdict and duplicate IDs (line 0 when there is no line).
+fn check_file(path: &Path) -> io::Result<Vec<Problem>> {
+    let contents = if is_stdio(path) {
+        let mut raw = Vec::new();
+        io::Read::read_to_end(&mut io::stdin().lock(), &mut raw)?;
+        formats::decompress(raw)?
+    } else {
+        formats::read_contents(path)?
+    };
+    let contents = formats::normalize_line_endings(contents);
+    let format = Format::from_path(path).unwrap_or_else(|| Format::sniff(&contents));
+    if format == Format::Stockholm {
+        return Ok(validate::validate(&String::from_utf8_lossy(&contents)));
+    }
+    let alignment = match format.parse(contents.as_slice()) {
+        Ok(alignment) => alignment,
+        Err(e) => {
+            return Ok(vec![Problem {
+                line: 0,
+                message: e.to_string(),
+            }]);
+        }
+    };
+    let mut seen = std::collections::HashSet::new();
+    Ok(alignment
+        .sequences
+        .iter()
+        .filter(|seq| !seen.insert(seq.id.as_str()))
+        .map(|seq| Problem {
+            line: 0,
+            message: format!("duplicate sequence ID {}", seq.id),
+        })
+        .collect())
+}
+
+fn validate(args: &ValidateArgs) -> Result<(), CliError> {
+    let mut problems = 0;
+    let mut files = 0;
+    for path in &args.files {
+        let name = if is_stdio(path) {
+            "stdin".to_string()
+        } else {
+            path.display().to_string()
+        };
+        let found = check_file(path).map_err(|source| CliError::Read {
+            path: name.clone(),
+            source: source.into(),
+        })?;
+        for problem in &found {
+            match problem.line {
+                0 => println!("{name}: {}", problem.message),
+                line => println!("{name}:{line}: {}", problem.message),
+            }
+        }
+        problems += found.len();
+        files += usize::from(!found.is_empty());
+    }
+    if problems > 0 {
+        return Err(CliError::Invalid { problems, files });
+    }
+    Ok(())
+}
+
+/// File name of the figure for record `index` of `count` read from `path`,
+/// e.g. `RF00005.html`, or `Rfam.2.html` for the second alignment of a file.
+fn figure_name(path: &Path, index: usize, count: usize, format: RenderFormat) -> String {
+    let stem = path
+        .file_stem()
+        .map_or_else(|| "alignment".into(), |s| s.to_string_lossy());
+    let extension = match format {
+        RenderFormat::Html => "html",
+        RenderFormat::Svg => "svg",
+    };
+    if count > 1 {
+        format!("{stem}.{}.{extension}", index + 1)
+    } else {
+        format!("{stem}.{extension}")
+    }
+}
+
+fn render(args: &RenderArgs) -> Result<(), CliError> {
+    let (scheme, overlay) =
+        ColorScheme::parse_layers(&args.color).ok_or(CliError::UnknownColor(args.color.clone()))?;
+    std::fs::create_dir_all(&args.output).map_err(|source| CliError::Write {
+        path: args.output.display().to_string(),
+        source,
+    })?;
+
+    // A file that can't be read or a figure that can't be drawn is reported and
+    // skipped, so one bad alignment doesn't stop the rest of the batch
+    let mut failed = 0;
+    for path in &args.files {
+        let alignments = match read_input(path, None) {
+            Ok(alignments) => alignments,
+            Err(e) => {
+                eprintln!("aform: {e}");
+                failed += 1;
+                continue;
+            }
+        };
+        let count = alignments.len();
+        for (index, alignment) in alignments.into_iter().enumerate() {
+            let mut app = App::new();
+            app.load_records(vec![alignment], Some(path.clone()));
+            app.color_scheme = scheme;
+            app.color_overlay = overlay;
+            match render_figure(args, &app, path, index, count) {
+                Ok(figure) => println!("{}", figure.display()),
+                Err(e) => {
+                    eprintln!("aform: {e}");
+                    failed += 1;
+                }
+            }
+        }
+    }
+    if failed > 0 {
+        return Err(CliError::NotRendered(failed));
+    }
+    Ok(())
+}
+
+/// Write the figure of record `index` of `count` read from `path`, loaded in
+/// `app`. Returns the figure's path.
+fn render_figure(
+    args: &RenderArgs,
+    app: &App,
+    path: &Path,
+    index: usize,
+    count: usize,
+) -> Result<PathBuf, CliError> {
+    let buffer =
+        crate::ui::render_alignment_buffer(app, args.region.clone()).map_err(|message| {
+            CliError::Render {
+                path: path.display().to_string(),
+                message,
+            }
+        })?;
+    let dark = !args.light;
+    let figure = args
+        .output
+        .join(figure_name(path, index, count, args.format));
+    match args.format {
+        RenderFormat::Html => {
+            let title = match count {
+                1 => path.display().to_string(),
+                _ => format!("{}[{}]", path.display(), index + 1),
+            };
+            crate::screenshot::write_html(&buffer, &title, dark, &figure)
+        }
+        RenderFormat::Svg => crate::screenshot::write_svg(&buffer, dark, &figure),
+    }
+    .map_err(|source| CliError::Write {
+        path: figure.display().to_string(),
+        source,
+    })?;
+    Ok(figure)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const STOCKHOLM: &str = "\
+# STOCKHOLM 1.0
+seq1  ACGU.ACGU
+seq2  ACGUUACG-
+#=GC SS_cons  ((.....))
+//
+";
+
+    #[test]
+    fn test_render_names() {
+        assert_eq!(parse_region("1-200"), Ok(0..200));
+        assert!(parse_region("0-5").is_err());
+        assert!(parse_region("9-5").is_err());
+        assert!(parse_region("12").is_err());
+
+        let path = Path::new("data/RF00005.sto");
+        assert_eq!(figure_name(path, 0, 1, RenderFormat::Html), "RF00005.html");
+        assert_eq!(figure_name(path, 1, 3, RenderFormat::Svg), "RF00005.2.svg");
+    }
+
+    #[test]
+    fn test_output_format() {
+        assert_eq!(
+            Output::from_path(Path::new("out.fa")),
+            Output::Alignment(Format::Fasta)
+        );
+        assert_eq!(Output::from_path(Path::new("out.nex")), Output::Nexus);
+        assert_eq!(Output::from_path(Path::new("out.json")), Output::Json);
+        assert_eq!(
+            Output::from_path(Path::new("out")),
+            Output::Alignment(Format::Stockholm)
+        );
+        assert_eq!(Output::from_name("NEXUS"), Some(Output::Nexus));
+        assert_eq!(Output::from_name("xml"), None);
+    }
+
+    #[test]
+    fn test_write_alignments() {
+        let alignment = crate::stockholm::parser::parse(STOCKHOLM.as_bytes()).unwrap();
+        let options = WriteOptions::default();
+
+        let mut buffer = Vec::new();
+        write_alignments(
+            std::slice::from_ref(&alignment),
+            Output::Alignment(Format::Fasta),
+            &options,
+            &mut buffer,
+        )
+        .unwrap();
+        let text = String::from_utf8(buffer).unwrap();
+        assert!(text.starts_with(">seq1\nACGU-ACGU\n"));
+
+        let mut buffer = Vec::new();
+        write_alignments(
+            &[alignment.clone(), alignment],
+            Output::Alignment(Format::Stockholm),
+            &options,
+            &mut buffer,
+        )
+        .unwrap();
+        let text = String::from_utf8(buffer).unwrap();
+        assert_eq!(text.matches("# STOCKHOLM 1.0").count(), 2);
+    }
+
+    #[test]
+    fn test_stats() {
+        let alignment = crate::stockholm::parser::parse(STOCKHOLM.as_bytes()).unwrap();
+        let stats = Stats::new("test.sto".to_string(), &alignment, true);
+        assert_eq!(stats.sequences, 2);
+        assert_eq!(stats.columns, 9);
+        assert!((stats.gap_percent - 100.0 * 2.0 / 18.0).abs() < 1e-9);
+        // 7 identical residues out of the shorter sequence's 8
+        assert!((stats.mean_identity.unwrap() - 87.5).abs() < 1e-9);
+        assert_eq!(stats.ss_pairs, Some(2));
+        // Gaps don't count against conservation
+        assert_eq!(stats.conservation.high, 9);
+        assert_eq!(stats.conservation.mean, 1.0);
+        let columns = stats.column_stats.as_ref().unwrap();
+        assert_eq!(columns[4].consensus, 'U');
+        assert_eq!(columns[4].gap_fraction, 0.5);
+
+        let mut buffer = Vec::new();
+        write_stats(std::slice::from_ref(&stats), StatsFormat::Json, &mut buffer).unwrap();
+        let json: serde_json::Value = serde_json::from_slice(&buffer).unwrap();
+        assert_eq!(json[0]["ss_pairs"], 2);
+        assert_eq!(json[0]["column_stats"][4]["consensus"], "U");
+        assert!(json[0].get("identity_sample").is_none());
+    }
+
+    #[test]
+    fn test_stats_samples_identity_of_large_alignments() {
+        let mut text = String::from("# STOCKHOLM 1.0\n");
+        for i in 0..IDENTITY_SAMPLE + 500 {
+            let residues = if i % 2 == 0 { "ACGU" } else { "ACGA" };
+            text.push_str(&format!("seq{i} {residues}\n"));
+        }
+        text.push_str("//\n");
+        let alignment = crate::stockholm::parser::parse(text.as_bytes()).unwrap();
+        let stats = Stats::new("big.sto".to_string(), &alignment, false);
+        assert_eq!(stats.sequences, IDENTITY_SAMPLE + 500);
+        assert_eq!(stats.identity_sample, Some(IDENTITY_SAMPLE));
+        let identity = stats.mean_identity.unwrap();
+        assert!(identity > 75.0 && identity < 100.0);
+    }
+
+    #[test]
+    fn test_render_refuses_oversized_figures() {
+        let mut app = App::new();
+        let alignment = crate::stockholm::parser::parse(STOCKHOLM.as_bytes()).unwrap();
+        app.load_records(vec![alignment], None);
+        let buffer = crate::ui::render_alignment_buffer(&app, None).unwrap();
+        // seq1, seq2 and SS_cons below an 8-wide ID column
+        assert_eq!((buffer.area.width, buffer.area.height), (8 + 9, 3));
+
+        let wide = format!("# STOCKHOLM 1.0\nseq1 {}\n//\n", "A".repeat(70_000));
+        let alignment = crate::stockholm::parser::parse(wide.as_bytes()).unwrap();
+        app.load_records(vec![alignment], None);
+        assert!(crate::ui::render_alignment_buffer(&app, None).is_err());
+        assert!(crate::ui::render_alignment_buffer(&app, Some(0..1000)).is_ok());
+    }
+
+    #[test]
+    fn test_render_continues_past_bad_files() {
+        let dir = std::env::temp_dir().join(format!("aform-render-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let good = dir.join("good.sto");
+        std::fs::write(&good, STOCKHOLM).unwrap();
+        let args = RenderArgs {
+            files: vec![dir.join("missing.sto"), good],
+            output: dir.join("figures"),
+            color: "base".to_string(),
+            region: None,
+            format: RenderFormat::Svg,
+            light: false,
+        };
+        assert!(matches!(render(&args), Err(CliError::NotRendered(1))));
+        assert!(dir.join("figures").join("good.svg").exists());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
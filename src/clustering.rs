@@ -3,6 +3,8 @@
 //! Uses Hamming distance and UPGMA (average linkage) to group similar sequences.
 //! Identical sequences are collapsed before clustering to reduce O(n²) distance computation.
 
+use std::collections::HashMap;
+
 use kodama::{Method, linkage};
 
 /// Result of clustering: leaf order and optional tree visualization.
@@ -39,6 +41,115 @@ pub fn hamming_distance(seq1: &[char], seq2: &[char], gap_chars: &[char]) -> usi
         .count()
 }
 
+/// Fraction of identical residues between two aligned sequences (0.0 to 1.0).
+///
+/// Counts columns where both sequences have the same residue, relative to the shorter
+/// ungapped length (as CD-HIT does), so a fragment can match its full-length parent.
+pub fn sequence_identity(seq1: &[char], seq2: &[char], gap_chars: &[char]) -> f64 {
+    let residues = |seq: &[char]| seq.iter().filter(|c| !gap_chars.contains(c)).count();
+    let shorter = residues(seq1).min(residues(seq2));
+    if shorter == 0 {
+        return 0.0;
+    }
+    let matches = seq1
+        .iter()
+        .zip(seq2.iter())
+        .filter(|(a, b)| !gap_chars.contains(a) && a.eq_ignore_ascii_case(b))
+        .count();
+    matches as f64 / shorter as f64
+}
+
+/// Greedy identity clustering (CD-HIT-like).
+///
+/// Sequences are visited longest first; each joins the first representative it matches
+/// at `threshold` identity or better, otherwise it starts a new cluster. Returns
+/// (representative, members) pairs ordered by representative index, with members in
+/// original order.
+pub fn greedy_identity_clusters(
+    sequences: &[Vec<char>],
+    gap_chars: &[char],
+    threshold: f64,
+) -> Vec<(usize, Vec<usize>)> {
+    greedy_identity_clusters_with_progress(sequences, gap_chars, threshold, |_, _| true)
+        .unwrap_or_default()
+}
+
+/// [`greedy_identity_clusters`], calling `progress` after each sequence with
+/// the clusters so far (in the order they were started, members unsorted) and
+/// how many sequences they hold. Returns None if `progress` returns false,
+/// which stops clustering.
+pub fn greedy_identity_clusters_with_progress(
+    sequences: &[Vec<char>],
+    gap_chars: &[char],
+    threshold: f64,
+    mut progress: impl FnMut(&[(usize, Vec<usize>)], usize) -> bool,
+) -> Option<Vec<(usize, Vec<usize>)>> {
+    let residues = |seq: &[char]| seq.iter().filter(|c| !gap_chars.contains(c)).count();
+    let mut by_length: Vec<usize> = (0..sequences.len()).collect();
+    by_length.sort_by_key(|&i| std::cmp::Reverse(residues(&sequences[i])));
+
+    let mut clusters: Vec<(usize, Vec<usize>)> = Vec::new();
+    for (done, idx) in by_length.into_iter().enumerate() {
+        let cluster = clusters.iter_mut().find(|(rep, _)| {
+            sequence_identity(&sequences[*rep], &sequences[idx], gap_chars) >= threshold
+        });
+        match cluster {
+            Some((_, members)) => members.push(idx),
+            None => clusters.push((idx, vec![idx])),
+        }
+        if !progress(&clusters, done + 1) {
+            return None;
+        }
+    }
+
+    for (_, members) in &mut clusters {
+        members.sort_unstable();
+    }
+    clusters.sort_by_key(|(rep, _)| *rep);
+    Some(clusters)
+}
+
+/// Sequences redundant with another, for `:dedup`: exact duplicates of an
+/// earlier sequence (the same residues, ignoring gaps and case), and with a
+/// `threshold`, the members of each greedy identity cluster other than its
+/// representative. Each is returned with the sequence it is kept in favor of
+/// and their identity (None for an exact duplicate), in alignment order.
+pub fn redundant_sequences(
+    sequences: &[Vec<char>],
+    gap_chars: &[char],
+    threshold: Option<f64>,
+) -> Vec<(usize, usize, Option<f64>)> {
+    let mut first: HashMap<String, usize> = HashMap::new();
+    let mut redundant = Vec::new();
+    let mut unique = Vec::new();
+    for (idx, seq) in sequences.iter().enumerate() {
+        let residues: String = seq
+            .iter()
+            .filter(|c| !gap_chars.contains(c))
+            .map(|c| c.to_ascii_uppercase())
+            .collect();
+        match first.get(&residues) {
+            Some(&kept) => redundant.push((idx, kept, None)),
+            None => {
+                first.insert(residues, idx);
+                unique.push(idx);
+            }
+        }
+    }
+
+    if let Some(threshold) = threshold {
+        let remaining: Vec<Vec<char>> = unique.iter().map(|&i| sequences[i].clone()).collect();
+        for (rep, members) in greedy_identity_clusters(&remaining, gap_chars, threshold) {
+            for member in members.into_iter().filter(|&m| m != rep) {
+                let identity = sequence_identity(&remaining[rep], &remaining[member], gap_chars);
+                redundant.push((unique[member], unique[rep], Some(identity)));
+            }
+        }
+    }
+    redundant.sort_by_key(|&(idx, ..)| idx);
+    redundant
+}
+
 /// Compute condensed distance matrix for all sequence pairs.
 /// Returns distances in row-major condensed form for kodama.
 pub fn compute_distance_matrix(sequences: &[Vec<char>], gap_chars: &[char]) -> Vec<f64> {
@@ -368,6 +479,87 @@ mod tests {
         assert_eq!(hamming_distance(&seq1, &seq2, &gaps), 4);
     }
 
+    #[test]
+    fn test_sequence_identity() {
+        let gaps = ['-', '.'];
+        let full: Vec<char> = "ACGUACGU".chars().collect();
+        let fragment: Vec<char> = "--GUAC--".chars().collect();
+        let variant: Vec<char> = "ACGAACGA".chars().collect();
+        assert_eq!(sequence_identity(&full, &fragment, &gaps), 1.0);
+        assert_eq!(sequence_identity(&full, &variant, &gaps), 0.75);
+        assert_eq!(sequence_identity(&full, &['-'; 8], &gaps), 0.0);
+    }
+
+    #[test]
+    fn test_greedy_identity_clusters() {
+        let gaps = ['-', '.'];
+        let seqs: Vec<Vec<char>> = ["--GUAC--", "ACGUACGU", "UUUUUUUU", "ACGAACGA"]
+            .iter()
+            .map(|s| s.chars().collect())
+            .collect();
+
+        // Longest sequence becomes the representative of the fragment
+        let clusters = greedy_identity_clusters(&seqs, &gaps, 0.9);
+        assert_eq!(clusters, vec![(1, vec![0, 1]), (2, vec![2]), (3, vec![3])]);
+
+        let clusters = greedy_identity_clusters(&seqs, &gaps, 0.7);
+        assert_eq!(clusters, vec![(1, vec![0, 1, 3]), (2, vec![2])]);
+    }
+
+    #[test]
+    fn test_greedy_identity_clusters_with_progress() {
+        let gaps = ['-', '.'];
+        let seqs: Vec<Vec<char>> = ["--GUAC--", "ACGUACGU", "UUUUUUUU", "ACGAACGA"]
+            .iter()
+            .map(|s| s.chars().collect())
+            .collect();
+
+        let mut seen = Vec::new();
+        let clusters = greedy_identity_clusters_with_progress(&seqs, &gaps, 0.9, |groups, done| {
+            seen.push((groups.len(), done));
+            true
+        });
+        assert_eq!(clusters, Some(greedy_identity_clusters(&seqs, &gaps, 0.9)));
+        // Longest first: ACGUACGU, UUUUUUUU, ACGAACGA start clusters, the fragment joins
+        assert_eq!(seen, vec![(1, 1), (2, 2), (3, 3), (3, 4)]);
+
+        let mut calls = 0;
+        let stopped = greedy_identity_clusters_with_progress(&seqs, &gaps, 0.9, |_, _| {
+            calls += 1;
+            calls < 2
+        });
+        assert_eq!(stopped, None);
+        assert_eq!(calls, 2);
+    }
+
+    #[test]
+    fn test_redundant_sequences() {
+        let gaps = ['-', '.'];
+        let seqs: Vec<Vec<char>> = [
+            "ACGUACGU-",
+            "--GUAC---",
+            "acgu-acgu",
+            "ACGAACGA-",
+            "UUUUUUUU-",
+        ]
+        .iter()
+        .map(|s| s.chars().collect())
+        .collect();
+
+        // Only the re-gapped lowercase copy is an exact duplicate
+        assert_eq!(redundant_sequences(&seqs, &gaps, None), vec![(2, 0, None)]);
+
+        // The fragment is contained in the full sequence, the variant 75% identical
+        assert_eq!(
+            redundant_sequences(&seqs, &gaps, Some(0.9)),
+            vec![(1, 0, Some(1.0)), (2, 0, None)]
+        );
+        assert_eq!(
+            redundant_sequences(&seqs, &gaps, Some(0.7)),
+            vec![(1, 0, Some(1.0)), (2, 0, None), (3, 0, Some(0.75))]
+        );
+    }
+
     #[test]
     fn test_hamming_distance_with_gaps() {
         let seq1: Vec<char> = "AC-U".chars().collect();
@@ -493,7 +685,7 @@ mod tests {
         let pos3 = result.order.iter().position(|&x| x == 3).unwrap();
 
         // Check they're consecutive
-        let a_positions = vec![pos0, pos1, pos3];
+        let a_positions = [pos0, pos1, pos3];
         let min_pos = *a_positions.iter().min().unwrap();
         let max_pos = *a_positions.iter().max().unwrap();
         assert_eq!(
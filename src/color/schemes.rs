@@ -2,9 +2,11 @@
 
 use ratatui::style::Color;
 
-use crate::app::ColorScheme;
+use crate::app::{ColorOverlay, ColorScheme};
 use crate::stockholm::{Alignment, SequenceType};
-use crate::structure::{CompensatoryChange, StructureCache, analyze_compensatory};
+use crate::structure::{
+    CompensatoryChange, SsConflict, StructureCache, analyze_compensatory, ss_conflict,
+};
 
 /// Colors for helix highlighting (cycling through these).
 pub const HELIX_COLORS: &[Color] = &[
@@ -154,6 +156,101 @@ pub fn get_color(
             get_compensatory_color(col, row, alignment, cache, gap_chars, reference_seq)
         }
         ColorScheme::PP => get_pp_color(ch, col, row, alignment, gap_chars),
+        ColorScheme::SsDiff => get_ss_conflict_color(col, row, alignment, cache, gap_chars),
+    }
+}
+
+/// How dark a dimming overlay leaves a color at weight 0 (fraction of full brightness).
+const DIM_FLOOR: f64 = 0.25;
+
+/// Darken an RGB color by `weight` (1.0 keeps it, 0.0 leaves `DIM_FLOOR`).
+/// Named terminal colors can't be scaled and are returned unchanged.
+pub fn dim_color(color: Color, weight: f64) -> Color {
+    let factor = DIM_FLOOR + (1.0 - DIM_FLOOR) * weight.clamp(0.0, 1.0);
+    let scale = |c: u8| (f64::from(c) * factor).round() as u8;
+    match color {
+        Color::Rgb(r, g, b) => Color::Rgb(scale(r), scale(g), scale(b)),
+        other => other,
+    }
+}
+
+/// Background and foreground colors for a character with an optional overlay layer
+/// stacked on the color scheme. Foreground overlays color the letter; the others
+/// dim the scheme's background by a per-cell weight.
+#[allow(clippy::too_many_arguments)]
+pub fn get_layer_colors(
+    scheme: ColorScheme,
+    overlay: Option<ColorOverlay>,
+    ch: char,
+    col: usize,
+    row: usize,
+    alignment: &Alignment,
+    cache: &StructureCache,
+    gap_chars: &[char],
+    reference_seq: usize,
+    sequence_type: SequenceType,
+) -> (Option<Color>, Option<Color>) {
+    let color = |scheme| {
+        get_color(
+            scheme,
+            ch,
+            col,
+            row,
+            alignment,
+            cache,
+            gap_chars,
+            reference_seq,
+            sequence_type,
+        )
+    };
+    let background = color(scheme);
+    let weight = match overlay {
+        None => return (background, None),
+        Some(ColorOverlay::Foreground(fg_scheme)) => return (background, color(fg_scheme)),
+        Some(ColorOverlay::Conservation) => Some(calculate_conservation(col, alignment, gap_chars)),
+        Some(ColorOverlay::PP) => residue_pp(col, row, alignment).and_then(pp_weight),
+        Some(ColorOverlay::Gaps) => Some(column_occupancy(col, alignment, gap_chars)),
+    };
+    match weight {
+        Some(weight) => (background.map(|c| dim_color(c, weight)), None),
+        None => (background, None),
+    }
+}
+
+/// Fraction of sequences with a residue (not a gap) at a column.
+fn column_occupancy(col: usize, alignment: &Alignment, gap_chars: &[char]) -> f64 {
+    if alignment.sequences.is_empty() {
+        return 0.0;
+    }
+    let residues = alignment
+        .sequences
+        .iter()
+        .filter(|s| s.get(col).is_some_and(|c| !gap_chars.contains(&c)))
+        .count();
+    residues as f64 / alignment.sequences.len() as f64
+}
+
+/// #=GR PP character of a residue, if the sequence has one.
+fn residue_pp(col: usize, row: usize, alignment: &Alignment) -> Option<char> {
+    residue_annotation(row, alignment, "PP")?.chars().nth(col)
+}
+
+/// A sequence's #=GR row with this tag.
+fn residue_annotation<'a>(row: usize, alignment: &'a Alignment, tag: &str) -> Option<&'a str> {
+    let seq = alignment.sequences.get(row)?;
+    alignment
+        .residue_annotations
+        .get(&seq.id)?
+        .iter()
+        .find(|ann| ann.tag == tag)
+        .map(|ann| ann.data.as_str())
+}
+
+/// Posterior probability of a PP character as a weight (`*` is 1.0, `0` is 0.0).
+fn pp_weight(pp: char) -> Option<f64> {
+    match pp {
+        '*' => Some(1.0),
+        _ => pp.to_digit(10).map(|d| f64::from(d) / 10.0),
     }
 }
 
@@ -258,6 +355,76 @@ pub fn calculate_conservation(col: usize, alignment: &Alignment, gap_chars: &[ch
     max_count as f64 / total as f64
 }
 
+/// Summary statistics for a single alignment column.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ColumnStats {
+    /// Frequency of the most common residue among non-gap characters (0.0 to 1.0).
+    pub conservation: f64,
+    /// Fraction of sequences covering this column that have an internal gap (0.0 to 1.0).
+    pub gap_fraction: f64,
+    /// Fraction of all sequences with a terminal (end) gap at this column (0.0 to 1.0).
+    pub end_gap_fraction: f64,
+    /// Most common residue (uppercase), or '.' if the column is all gaps.
+    pub consensus: char,
+}
+
+/// Compute conservation, gap fractions, and consensus for a column in one pass.
+///
+/// Gaps before a sequence's first residue or after its last are end gaps: they mark
+/// missing data, so they are counted separately instead of as deletions.
+pub fn column_stats(col: usize, alignment: &Alignment, gap_chars: &[char]) -> ColumnStats {
+    let mut counts: std::collections::HashMap<char, usize> = std::collections::HashMap::new();
+    let mut residues = 0;
+    let mut gaps = 0;
+    let mut end_gaps = 0;
+
+    for seq in &alignment.sequences {
+        match seq.get(col) {
+            Some(ch) if !gap_chars.contains(&ch) => {
+                *counts.entry(ch.to_ascii_uppercase()).or_insert(0) += 1;
+                residues += 1;
+            }
+            Some(_)
+                if seq
+                    .residue_span(gap_chars)
+                    .is_some_and(|(first, last)| (first..=last).contains(&col)) =>
+            {
+                gaps += 1
+            }
+            _ => end_gaps += 1,
+        }
+    }
+
+    // Break ties deterministically by character (matches get_consensus_char)
+    let (consensus, max_count) = counts
+        .into_iter()
+        .max_by(|(ch_a, count_a), (ch_b, count_b)| {
+            count_a.cmp(count_b).then_with(|| ch_a.cmp(ch_b))
+        })
+        .unwrap_or(('.', 0));
+
+    let covered = residues + gaps;
+    let total = covered + end_gaps;
+    ColumnStats {
+        conservation: if residues > 0 {
+            max_count as f64 / residues as f64
+        } else {
+            0.0
+        },
+        gap_fraction: if covered > 0 {
+            gaps as f64 / covered as f64
+        } else {
+            0.0
+        },
+        end_gap_fraction: if total > 0 {
+            end_gaps as f64 / total as f64
+        } else {
+            0.0
+        },
+        consensus,
+    }
+}
+
 /// Get color based on compensatory changes.
 fn get_compensatory_color(
     col: usize,
@@ -302,18 +469,29 @@ fn get_pp_color(
     }
 
     // Look up PP annotation for this sequence
-    let seq = alignment.sequences.get(row)?;
-    if let Some(annotations) = alignment.residue_annotations.get(&seq.id) {
-        for ann in annotations {
-            if ann.tag == "PP"
-                && let Some(pp_char) = ann.data.chars().nth(col)
-            {
-                return Some(pp_to_color(pp_char));
-            }
-        }
-    }
+    residue_pp(col, row, alignment).map(pp_to_color)
+}
 
-    None // No PP annotation for this residue
+/// Colors for disagreements between a sequence's #=GR SS and SS_cons.
+const SS_EXTRA_COLOR: Color = Color::Rgb(255, 165, 0); // orange: pair not in SS_cons
+const SS_MISSING_COLOR: Color = Color::Rgb(135, 206, 235); // skyblue: SS_cons pair not formed
+const SS_PARTNER_COLOR: Color = Color::Rgb(220, 20, 60); // crimson: different partner
+
+/// Get color where the sequence's own #=GR SS conflicts with SS_cons.
+fn get_ss_conflict_color(
+    col: usize,
+    row: usize,
+    alignment: &Alignment,
+    cache: &StructureCache,
+    gap_chars: &[char],
+) -> Option<Color> {
+    let own_ss = residue_annotation(row, alignment, "SS")?;
+    let residues = alignment.sequences.get(row)?.chars();
+    ss_conflict(residues, own_ss, col, cache, gap_chars).map(|conflict| match conflict {
+        SsConflict::Extra => SS_EXTRA_COLOR,
+        SsConflict::Missing => SS_MISSING_COLOR,
+        SsConflict::Partner => SS_PARTNER_COLOR,
+    })
 }
 
 /// Get consensus character for a column.
@@ -385,6 +563,42 @@ pub fn conservation_to_block(conservation: f64) -> (char, Color) {
     }
 }
 
+/// A small picture of an alignment: `rows` evenly spaced sequences by `cols`
+/// evenly spaced columns (fewer if the alignment is smaller). Each cell holds the
+/// column's conservation color where the sequence has a residue, None at gaps.
+pub fn conservation_thumbnail(
+    alignment: &Alignment,
+    rows: usize,
+    cols: usize,
+    gap_chars: &[char],
+) -> Vec<Vec<Option<Color>>> {
+    let sample = |count: usize, total: usize| -> Vec<usize> {
+        let count = count.min(total);
+        (0..count).map(|i| i * total / count).collect()
+    };
+    let columns: Vec<(usize, Color)> = sample(cols, alignment.width())
+        .into_iter()
+        .map(|col| {
+            let conservation = calculate_conservation(col, alignment, gap_chars);
+            (col, conservation_to_block(conservation).1)
+        })
+        .collect();
+    sample(rows, alignment.num_sequences())
+        .into_iter()
+        .map(|row| {
+            let seq = &alignment.sequences[row];
+            columns
+                .iter()
+                .map(|&(col, color)| {
+                    seq.get(col)
+                        .filter(|ch| !gap_chars.contains(ch))
+                        .map(|_| color)
+                })
+                .collect()
+        })
+        .collect()
+}
+
 /// Detect sequence type from alignment content.
 /// Checks for protein-specific amino acids, then distinguishes RNA (U) from DNA (T).
 pub fn detect_sequence_type(alignment: &Alignment, gap_chars: &[char]) -> SequenceType {
@@ -485,6 +699,125 @@ mod tests {
         assert!((cons - 0.666).abs() < 0.01);
     }
 
+    #[test]
+    fn test_conservation_thumbnail() {
+        let mut alignment = Alignment::new();
+        for (id, data) in [("s1", "AAAAAAAA"), ("s2", "AAAA--AA"), ("s3", "CAAA--AC")] {
+            alignment.sequences.push(Rc::new(Sequence::new(id, data)));
+        }
+        let gap_chars = ['.', '-'];
+
+        // Every other column of two of the three sequences
+        let thumbnail = conservation_thumbnail(&alignment, 2, 4, &gap_chars);
+        assert_eq!(thumbnail.len(), 2);
+        assert_eq!(thumbnail[0].len(), 4);
+        assert!(thumbnail[0].iter().all(Option::is_some));
+        assert_eq!(thumbnail[1][2], None);
+        // Column 0 is less conserved than column 2
+        assert_ne!(thumbnail[0][0], thumbnail[0][1]);
+
+        // Never larger than the alignment
+        let thumbnail = conservation_thumbnail(&alignment, 10, 100, &gap_chars);
+        assert_eq!((thumbnail.len(), thumbnail[0].len()), (3, 8));
+    }
+
+    #[test]
+    fn test_column_stats() {
+        let mut alignment = Alignment::new();
+        alignment
+            .sequences
+            .push(Rc::new(Sequence::new("s1", "AAA.")));
+        alignment
+            .sequences
+            .push(Rc::new(Sequence::new("s2", "Ac-.")));
+        alignment
+            .sequences
+            .push(Rc::new(Sequence::new("s3", "A-C.")));
+        alignment
+            .sequences
+            .push(Rc::new(Sequence::new("s4", "-CC.")));
+
+        let gap_chars = ['.', '-'];
+
+        // Column 0: 3 A + 1 leading end gap
+        let stats = column_stats(0, &alignment, &gap_chars);
+        assert!((stats.conservation - 1.0).abs() < 0.01);
+        assert_eq!(stats.gap_fraction, 0.0);
+        assert!((stats.end_gap_fraction - 0.25).abs() < 0.01);
+        assert_eq!(stats.consensus, 'A');
+
+        // Column 1: case-insensitive, 2 C vs 1 A, 1 internal gap
+        let stats = column_stats(1, &alignment, &gap_chars);
+        assert!((stats.conservation - 0.666).abs() < 0.01);
+        assert!((stats.gap_fraction - 0.25).abs() < 0.01);
+        assert_eq!(stats.end_gap_fraction, 0.0);
+        assert_eq!(stats.consensus, 'C');
+
+        // Column 2: s2 gap is trailing, so it does not count as a deletion
+        let stats = column_stats(2, &alignment, &gap_chars);
+        assert_eq!(stats.gap_fraction, 0.0);
+        assert!((stats.end_gap_fraction - 0.25).abs() < 0.01);
+
+        // Column 3: all gaps
+        let stats = column_stats(3, &alignment, &gap_chars);
+        assert_eq!(stats.conservation, 0.0);
+        assert_eq!(stats.end_gap_fraction, 1.0);
+        assert_eq!(stats.consensus, '.');
+    }
+
+    #[test]
+    fn test_layer_colors() {
+        let mut alignment = Alignment::new();
+        for (id, data) in [("s1", "AA"), ("s2", "A-"), ("s3", "C-"), ("s4", "A-")] {
+            alignment.sequences.push(Rc::new(Sequence::new(id, data)));
+        }
+        let mut cache = StructureCache::new();
+        cache.update("<>").unwrap();
+        let gap_chars = ['.', '-'];
+        let colors = |overlay, col, row: usize| {
+            get_layer_colors(
+                ColorScheme::Base,
+                overlay,
+                alignment.sequences[row].get(col).unwrap(),
+                col,
+                row,
+                &alignment,
+                &cache,
+                &gap_chars,
+                0,
+                SequenceType::RNA,
+            )
+        };
+
+        let (base, fg) = colors(None, 0, 0);
+        assert_eq!(base, get_base_color('A', &gap_chars, SequenceType::RNA));
+        assert_eq!(fg, None);
+
+        // Structure letters over base backgrounds
+        let (bg, fg) = colors(Some(ColorOverlay::Foreground(ColorScheme::Structure)), 0, 0);
+        assert_eq!(bg, base);
+        assert_eq!(fg, get_structure_color(0, &cache));
+
+        // Column 0 is 75% conserved, column 1 a quarter occupied
+        let (bg, _) = colors(Some(ColorOverlay::Conservation), 0, 0);
+        assert_eq!(bg, base.map(|c| dim_color(c, 0.75)));
+        let (bg, _) = colors(Some(ColorOverlay::Gaps), 1, 0);
+        assert_eq!(bg, base.map(|c| dim_color(c, 0.25)));
+
+        // No PP annotation leaves the background alone
+        assert_eq!(colors(Some(ColorOverlay::PP), 0, 0).0, base);
+
+        assert_eq!(
+            dim_color(Color::Rgb(200, 100, 0), 1.0),
+            Color::Rgb(200, 100, 0)
+        );
+        assert_eq!(
+            dim_color(Color::Rgb(200, 100, 0), 0.0),
+            Color::Rgb(50, 25, 0)
+        );
+        assert_eq!(dim_color(Color::DarkGray, 0.0), Color::DarkGray);
+    }
+
     #[test]
     fn test_structure_colors() {
         let mut cache = StructureCache::new();
@@ -194,6 +194,8 @@ pub struct ModeColors {
     pub search_fg: Rgb,
     pub visual_bg: Rgb,
     pub visual_fg: Rgb,
+    pub track_bg: Rgb,
+    pub track_fg: Rgb,
 }
 
 impl Default for ModeColors {
@@ -209,6 +211,8 @@ impl Default for ModeColors {
             search_fg: Rgb::new(255, 255, 255), // White
             visual_bg: Rgb::new(100, 100, 180), // Purple-ish
             visual_fg: Rgb::new(255, 255, 255), // White
+            track_bg: Rgb::new(0, 160, 160),    // Teal
+            track_fg: Rgb::new(0, 0, 0),        // Black
         }
     }
 }
@@ -224,6 +228,7 @@ pub struct StatusBarColors {
     pub color_scheme: Rgb,
     pub structure_info: Rgb,
     pub selection_info: Rgb,
+    pub column_stats: Rgb,
     #[serde(flatten)]
     pub modes: ModeColors,
 }
@@ -238,6 +243,7 @@ impl Default for StatusBarColors {
             color_scheme: Rgb::new(255, 0, 255),     // Magenta
             structure_info: Rgb::new(255, 255, 0),   // Yellow
             selection_info: Rgb::new(173, 216, 230), // LightBlue
+            column_stats: Rgb::new(144, 238, 144),   // LightGreen
             modes: ModeColors::default(),
         }
     }
@@ -250,6 +256,9 @@ pub struct IdColumnColors {
     pub text: Rgb,
     pub selected_bg: Rgb,
     pub selected_fg: Rgb,
+    pub flag: Rgb,
+    pub zscore: Rgb,
+    pub zscore_weak: Rgb,
 }
 
 impl Default for IdColumnColors {
@@ -258,6 +267,9 @@ impl Default for IdColumnColors {
             text: Rgb::new(0, 255, 255),          // Cyan
             selected_bg: Rgb::new(80, 80, 140),   // Purple-ish
             selected_fg: Rgb::new(255, 255, 255), // White
+            flag: Rgb::new(255, 165, 0),          // Orange
+            zscore: Rgb::new(128, 128, 128),      // Gray
+            zscore_weak: Rgb::new(255, 100, 100), // Light red
         }
     }
 }
@@ -278,11 +290,15 @@ pub struct AnnotationColors {
     pub consensus_fg: Rgb,
     pub consensus_bg: Rgb,
     pub conservation_bg: Rgb,
+    pub confidence_bg: Rgb,
+    pub track_bg: Rgb,
     pub label_ss_cons_fg: Rgb,
     pub label_rf_fg: Rgb,
     pub label_pp_cons_fg: Rgb,
     pub label_consensus_fg: Rgb,
     pub label_conservation_fg: Rgb,
+    pub label_confidence_fg: Rgb,
+    pub label_track_fg: Rgb,
 }
 
 impl Default for AnnotationColors {
@@ -300,11 +316,15 @@ impl Default for AnnotationColors {
             consensus_fg: Rgb::new(0, 255, 255), // Cyan
             consensus_bg: Rgb::new(30, 40, 30),
             conservation_bg: Rgb::new(40, 30, 40),
+            confidence_bg: Rgb::new(30, 35, 45),
+            track_bg: Rgb::new(30, 40, 40),
             label_ss_cons_fg: Rgb::new(255, 255, 0), // Yellow
             label_rf_fg: Rgb::new(0, 128, 0),        // Green
             label_pp_cons_fg: Rgb::new(255, 255, 0), // Yellow
             label_consensus_fg: Rgb::new(0, 255, 255), // Cyan
             label_conservation_fg: Rgb::new(255, 0, 255), // Magenta
+            label_confidence_fg: Rgb::new(100, 180, 255), // Light blue
+            label_track_fg: Rgb::new(0, 200, 200),   // Teal
         }
     }
 }
@@ -364,6 +384,7 @@ impl Default for CommandLineColors {
 #[serde(default)]
 pub struct MiscColors {
     pub separator: Rgb,
+    pub end_gap: Rgb,
     pub tree_dark_theme: Rgb,
     pub tree_light_theme: Rgb,
 }
@@ -372,6 +393,7 @@ impl Default for MiscColors {
     fn default() -> Self {
         Self {
             separator: Rgb::new(128, 128, 128),       // DarkGray
+            end_gap: Rgb::new(70, 70, 70),            // Dim gray
             tree_dark_theme: Rgb::new(255, 255, 255), // White
             tree_light_theme: Rgb::new(0, 0, 0),      // Black
         }
@@ -0,0 +1,266 @@
+//! Column reliability from pairwise consistency (TCS-style).
+//!
+//! Pairs of sequences are realigned on their own; a column scores the fraction of
+//! the residue pairs it aligns that the pairwise alignments reproduce. Columns an
+//! aligner placed arbitrarily score low, columns every pairwise alignment agrees
+//! with score 1.
+
+/// Upper bound on realigned sequence pairs; larger alignments are sampled.
+pub const MAX_PAIRS: usize = 2000;
+
+const MATCH: i32 = 2;
+const MISMATCH: i32 = -1;
+/// Cost of the first gap position (open + extend) and of each further one.
+const GAP_OPEN: i32 = -5;
+const GAP_EXTEND: i32 = -1;
+
+const NEG: i32 = i32::MIN / 4;
+
+/// Traceback states.
+const M: u8 = 0;
+const X: u8 = 1;
+const Y: u8 = 2;
+
+fn normalize(c: char) -> char {
+    match c.to_ascii_uppercase() {
+        'T' => 'U',
+        c => c,
+    }
+}
+
+fn substitution(a: char, b: char) -> i32 {
+    if a == b && a != 'N' && a != 'X' {
+        MATCH
+    } else if matches!(a, 'N' | 'X') || matches!(b, 'N' | 'X') {
+        0
+    } else {
+        MISMATCH
+    }
+}
+
+/// Align two ungapped sequences (affine gaps, free end gaps) and map each residue
+/// of `a` to the residue of `b` it is aligned with.
+fn pairwise_map(a: &[char], b: &[char]) -> Vec<Option<usize>> {
+    let (n, m) = (a.len(), b.len());
+    let mut map = vec![None; n];
+    if n == 0 || m == 0 {
+        return map;
+    }
+    let w = m + 1;
+    // mm: a[i-1] with b[j-1]; xx: a[i-1] against a gap; yy: b[j-1] against a gap
+    let mut mm = vec![NEG; (n + 1) * w];
+    let mut xx = vec![NEG; (n + 1) * w];
+    let mut yy = vec![NEG; (n + 1) * w];
+    let mut tm = vec![M; (n + 1) * w];
+    let mut tx = vec![M; (n + 1) * w];
+    let mut ty = vec![M; (n + 1) * w];
+
+    mm[0] = 0;
+    for i in 1..=n {
+        xx[i * w] = 0;
+    }
+    yy[1..=m].fill(0);
+
+    let best_of = |scores: [i32; 3]| {
+        let mut state = M;
+        for s in [X, Y] {
+            if scores[s as usize] > scores[state as usize] {
+                state = s;
+            }
+        }
+        (scores[state as usize], state)
+    };
+
+    for i in 1..=n {
+        for j in 1..=m {
+            let k = i * w + j;
+            let diag = k - w - 1;
+            let (score, state) = best_of([mm[diag], xx[diag], yy[diag]]);
+            mm[k] = score + substitution(a[i - 1], b[j - 1]);
+            tm[k] = state;
+
+            let up = k - w;
+            let (score, state) =
+                best_of([mm[up] + GAP_OPEN, xx[up] + GAP_EXTEND, yy[up] + GAP_OPEN]);
+            xx[k] = score;
+            tx[k] = state;
+
+            let left = k - 1;
+            let (score, state) = best_of([
+                mm[left] + GAP_OPEN,
+                xx[left] + GAP_OPEN,
+                yy[left] + GAP_EXTEND,
+            ]);
+            yy[k] = score;
+            ty[k] = state;
+        }
+    }
+
+    // Free trailing gaps: best cell in the last row or column
+    let ends = (0..=m).map(|j| (n, j)).chain((0..n).map(|i| (i, m)));
+    let (mut i, mut j, mut state) = (0, 0, M);
+    let mut best = NEG;
+    for (ei, ej) in ends {
+        let (score, s) = best_of([mm[ei * w + ej], xx[ei * w + ej], yy[ei * w + ej]]);
+        if score > best {
+            (best, i, j, state) = (score, ei, ej, s);
+        }
+    }
+
+    while i > 0 && j > 0 {
+        let k = i * w + j;
+        match state {
+            M => {
+                map[i - 1] = Some(j - 1);
+                state = tm[k];
+                i -= 1;
+                j -= 1;
+            }
+            X => {
+                state = tx[k];
+                i -= 1;
+            }
+            _ => {
+                state = ty[k];
+                j -= 1;
+            }
+        }
+    }
+    map
+}
+
+/// Sequence pairs to realign: all pairs, or an even sample of `MAX_PAIRS`.
+fn sample_pairs(n: usize) -> Vec<(usize, usize)> {
+    if n * n.saturating_sub(1) / 2 <= MAX_PAIRS {
+        return (0..n)
+            .flat_map(|i| ((i + 1)..n).map(move |j| (i, j)))
+            .collect();
+    }
+    let per_sequence = (MAX_PAIRS / n).max(1);
+    let stride = (n / (per_sequence + 1)).max(1);
+    (0..n)
+        .flat_map(|i| {
+            (1..=per_sequence)
+                .map(move |d| (i, (i + d * stride) % n))
+                .filter(|(i, j)| i != j)
+        })
+        .collect()
+}
+
+/// Per-column confidence in [0, 1]; None for columns with fewer than two residues.
+pub fn column_confidence(sequences: &[&[char]], gap_chars: &[char]) -> Vec<Option<f64>> {
+    let width = sequences.iter().map(|s| s.len()).max().unwrap_or(0);
+
+    // Residues and the column of each residue, per sequence
+    let residues: Vec<(Vec<char>, Vec<usize>)> = sequences
+        .iter()
+        .map(|seq| {
+            seq.iter()
+                .enumerate()
+                .filter(|(_, c)| !gap_chars.contains(c))
+                .map(|(col, &c)| (normalize(c), col))
+                .unzip()
+        })
+        .collect();
+    // Residue index at each column, per sequence
+    let index_at: Vec<Vec<Option<usize>>> = residues
+        .iter()
+        .map(|(_, cols)| {
+            let mut at = vec![None; width];
+            for (r, &col) in cols.iter().enumerate() {
+                at[col] = Some(r);
+            }
+            at
+        })
+        .collect();
+
+    let pairs = sample_pairs(sequences.len());
+    let threads = std::thread::available_parallelism().map_or(1, |n| n.get());
+    let chunk = pairs.len().div_ceil(threads).max(1);
+
+    // (supported, total) aligned residue pairs per column
+    let counts = std::thread::scope(|scope| {
+        let handles: Vec<_> = pairs
+            .chunks(chunk)
+            .map(|batch| {
+                let (residues, index_at) = (&residues, &index_at);
+                scope.spawn(move || {
+                    let mut counts = vec![(0u32, 0u32); width];
+                    for &(a, b) in batch {
+                        let map = pairwise_map(&residues[a].0, &residues[b].0);
+                        for (col, count) in counts.iter_mut().enumerate() {
+                            if let (Some(ra), Some(rb)) = (index_at[a][col], index_at[b][col]) {
+                                count.1 += 1;
+                                if map[ra] == Some(rb) {
+                                    count.0 += 1;
+                                }
+                            }
+                        }
+                    }
+                    counts
+                })
+            })
+            .collect();
+        handles.into_iter().fold(vec![(0, 0); width], |mut acc, h| {
+            for (sum, c) in acc
+                .iter_mut()
+                .zip(h.join().expect("alignment thread panicked"))
+            {
+                sum.0 += c.0;
+                sum.1 += c.1;
+            }
+            acc
+        })
+    });
+
+    counts
+        .into_iter()
+        .map(|(supported, total)| (total > 0).then(|| supported as f64 / total as f64))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn chars(s: &str) -> Vec<char> {
+        s.chars().collect()
+    }
+
+    #[test]
+    fn test_pairwise_map() {
+        let a = chars("GGGAAACCC");
+        let map = pairwise_map(&a, &chars("GGGAAACCC"));
+        assert!(map.iter().enumerate().all(|(i, m)| *m == Some(i)));
+
+        // b lacks the first residue; free end gaps align the rest in register
+        let map = pairwise_map(&a, &chars("GGAAACCC"));
+        assert_eq!(map[0], None);
+        assert_eq!(map[3], Some(2));
+    }
+
+    #[test]
+    fn test_column_confidence() {
+        let gaps = ['-', '.'];
+        let a = chars("GGGAAACCCUUU-");
+        let b = chars("GGGAAACCCUUU-");
+        // Same residues shifted one column: only the a-b pair is consistent
+        let c = chars("-GGGAAACCCUUU");
+        let scores = column_confidence(&[&a, &b], &gaps);
+        assert_eq!(scores[5], Some(1.0));
+        assert_eq!(scores[12], None);
+
+        let scores = column_confidence(&[&a, &b, &c], &gaps);
+        assert!((scores[5].unwrap() - 1.0 / 3.0).abs() < 1e-9);
+        // Only c has a residue in the last column
+        assert_eq!(scores[12], None);
+    }
+
+    #[test]
+    fn test_sample_pairs() {
+        assert_eq!(sample_pairs(3), vec![(0, 1), (0, 2), (1, 2)]);
+        let pairs = sample_pairs(1000);
+        assert!(pairs.len() <= MAX_PAIRS);
+        assert!(pairs.iter().all(|(i, j)| i != j));
+    }
+}
@@ -15,6 +15,21 @@ use crate::color::Theme;
 pub struct Config {
     /// UI theme colors.
     pub theme: Theme,
+    /// Editing defaults, as set with `:set`.
+    pub editor: EditorConfig,
+}
+
+/// The `[editor]` section: starting values for the gap settings. Unset keys
+/// keep the built-in defaults.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct EditorConfig {
+    /// Gap character inserted by edits (`:set gap=`).
+    pub gap: Option<char>,
+    /// Gap before the first and after the last residue (`:set endgap=`).
+    pub endgap: Option<char>,
+    /// Every character read as a gap (`:set gapchars=`).
+    pub gapchars: Option<String>,
 }
 
 impl Config {
@@ -0,0 +1,124 @@
+//! Crash handling for the editor.
+//!
+//! A panic hook puts the terminal back in a usable state before the panic
+//! message is printed, and after a crash the main loop writes a report with the
+//! panic, the last commands and the alignment as it was, so unsaved edits are
+//! not lost.
+
+use std::fmt::Write as _;
+use std::fs;
+use std::io::{self, Write};
+use std::panic;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::thread;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use ratatui::crossterm::{
+    event::DisableMouseCapture,
+    execute,
+    terminal::{LeaveAlternateScreen, disable_raw_mode},
+};
+
+use crate::app::App;
+use crate::stockholm::writer;
+
+/// Commands listed in a crash report.
+const REPORT_COMMANDS: usize = 50;
+
+/// Message, location and backtrace of the last panic, for the report.
+static PANIC_INFO: Mutex<Option<String>> = Mutex::new(None);
+
+/// Leave raw mode and the alternate screen. Safe to call more than once.
+pub fn restore_terminal() {
+    let _ = disable_raw_mode();
+    let _ = execute!(io::stdout(), LeaveAlternateScreen, DisableMouseCapture);
+    let _ = execute!(io::stdout(), ratatui::crossterm::cursor::Show);
+}
+
+/// Restore the terminal before any panic message is printed, and remember the
+/// panic for `write_report`. Only a panic on the thread that installs the hook
+/// ends the editor; one on a background job's thread leaves the terminal as it
+/// is, and the job reports the failure.
+pub fn install_panic_hook() {
+    let default_hook = panic::take_hook();
+    let main_thread = thread::current().id();
+    panic::set_hook(Box::new(move |info| {
+        if thread::current().id() == main_thread {
+            restore_terminal();
+            let backtrace = std::backtrace::Backtrace::force_capture();
+            if let Ok(mut last) = PANIC_INFO.lock() {
+                *last = Some(format!("{info}\n\n{backtrace}"));
+            }
+        }
+        default_hook(info);
+    }));
+}
+
+/// Directory crash reports go to: `<data dir>/aform/crash`, or the system
+/// temporary directory without one.
+fn report_dir() -> PathBuf {
+    dirs::data_dir()
+        .unwrap_or_else(std::env::temp_dir)
+        .join("aform")
+        .join("crash")
+}
+
+/// Write a crash report for `reason` (the panic, or the error that ended the
+/// editor) and the alignment next to it as `crash-<time>.sto`. Returns the
+/// report's path.
+pub fn write_report(app: &App, reason: &str) -> io::Result<PathBuf> {
+    let dir = report_dir();
+    fs::create_dir_all(&dir)?;
+    let stamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs());
+    let report_path = dir.join(format!("crash-{stamp}.txt"));
+    let alignment_path = dir.join(format!("crash-{stamp}.sto"));
+
+    // The alignment first, so it is saved even if the report can't be
+    let alignments: Vec<_> = if app.records.len() < 2 {
+        vec![&app.alignment]
+    } else {
+        (0..app.records.len()).map(|i| app.record(i)).collect()
+    };
+    let saved = fs::File::create(&alignment_path)
+        .map_err(|e| e.to_string())
+        .and_then(|file| {
+            let options = &app.write_options;
+            writer::write_records(&alignments, options.style, options.wrap, file)
+                .map_err(|e| e.to_string())
+        });
+
+    let mut report = String::new();
+    let _ = writeln!(report, "aform {} crash report", env!("CARGO_PKG_VERSION"));
+    let _ = writeln!(report, "Time: {stamp} (seconds since 1970-01-01 UTC)");
+    let file = app
+        .file_path
+        .as_ref()
+        .map_or_else(|| "[No file]".to_string(), |p| p.display().to_string());
+    let state = if app.modified { "modified" } else { "saved" };
+    let _ = writeln!(report, "File: {file} ({state})");
+    match &saved {
+        Ok(()) => {
+            let _ = writeln!(report, "Alignment: {}", alignment_path.display());
+        }
+        Err(e) => {
+            let _ = writeln!(report, "Alignment: not saved ({e})");
+        }
+    }
+    if let Some(log) = &app.log_path {
+        let _ = writeln!(report, "Session log: {}", log.display());
+    }
+    let _ = writeln!(report, "\n{reason}");
+    if let Some(panic) = PANIC_INFO.lock().ok().and_then(|mut last| last.take()) {
+        let _ = writeln!(report, "\n{panic}");
+    }
+    let _ = writeln!(report, "\nLast commands (oldest first):");
+    for command in app.command_history.recent(REPORT_COMMANDS) {
+        let _ = writeln!(report, "  :{command}");
+    }
+
+    fs::File::create(&report_path)?.write_all(report.as_bytes())?;
+    Ok(report_path)
+}
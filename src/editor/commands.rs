@@ -1,21 +1,121 @@
 //! Editor commands for alignment manipulation.
 
+use std::cmp::Reverse;
+use std::path::Path;
 use std::rc::Rc;
 
-use crate::app::App;
-use crate::stockholm::ShiftDirection;
+use std::collections::{HashMap, HashSet};
+
+use crate::app::{
+    App, ColumnOp, ColumnPrompt, Confirm, Register, Report, SortKey, SubstitutePrompt,
+};
+use crate::clustering::{redundant_sequences, sequence_identity};
+use crate::color::get_consensus_char;
+use crate::formats::FormatError;
+use crate::formats::fasta::FastaError;
+use crate::newick::{leaf_order, tree_order};
+use crate::search::Substitution;
+use crate::stockholm::{
+    DetachedSequence, MaskStyle, Sequence, SequenceEnd, SequenceType, ShiftDirection, TRACK_MAX,
+    adjust_coords, locate_coords, parse_coords, set_coords, short_id, track_char,
+};
+
+/// Where `:fixcoords` takes the true coordinates from.
+enum CoordSource {
+    /// Each sequence's own start and ungapped length.
+    Residues,
+    /// Full-length source sequences by name, searched for the aligned residues.
+    Fasta(HashMap<String, String>),
+    /// `id start end` lines (such as `:coords` or esl-sfetch output).
+    Table(HashMap<String, (usize, usize)>),
+}
+
+impl CoordSource {
+    /// Read a FASTA file of source sequences, or else a table of coordinates.
+    fn read(path: &Path) -> Result<Self, FormatError> {
+        let contents = crate::formats::normalize_line_endings(crate::formats::read_contents(path)?);
+        let text = String::from_utf8_lossy(&contents);
+        if text.trim_start().starts_with('>') {
+            let sources = crate::formats::fasta::parse_unaligned(contents.as_slice())?;
+            return Ok(Self::Fasta(
+                sources
+                    .sequences
+                    .iter()
+                    .map(|seq| (seq.id.clone(), seq.data()))
+                    .collect(),
+            ));
+        }
+        // Header and comment lines have no numbers where the coordinates go
+        let table = text
+            .lines()
+            .filter_map(|line| {
+                let fields: Vec<&str> = line.split_whitespace().collect();
+                match fields.as_slice() {
+                    [id, start, end, ..] => {
+                        Some((id.to_string(), (start.parse().ok()?, end.parse().ok()?)))
+                    }
+                    _ => None,
+                }
+            })
+            .collect();
+        Ok(Self::Table(table))
+    }
+
+    /// The coordinates of a sequence's residues, if the source knows them.
+    fn coords(&self, id: &str, residues: &str) -> Option<(usize, usize)> {
+        let declared = parse_coords(id).map(|(_, start, end)| (start, end));
+        match self {
+            Self::Residues => {
+                let (start, end) = declared?;
+                let span = residues.chars().count().checked_sub(1)?;
+                if start <= end {
+                    Some((start, start + span))
+                } else {
+                    Some((start, start.checked_sub(span).filter(|&e| e >= 1)?))
+                }
+            }
+            Self::Fasta(sources) => {
+                let source = sources.get(id).or_else(|| sources.get(short_id(id)))?;
+                locate_coords(residues, source, declared.map(|(start, _)| start))
+            }
+            Self::Table(table) => table.get(id).or_else(|| table.get(short_id(id))).copied(),
+        }
+    }
+}
 
 impl App {
     /// Insert a gap at the cursor position in the current sequence.
+    ///
+    /// The sequence stays flush with the alignment by consuming a trailing gap; if it ends
+    /// in a residue a gap column is added at the end of the alignment first. Gaps outside
+    /// the residues use the end-gap character.
     pub fn insert_gap(&mut self) {
-        self.save_undo_state();
+        if !self.begin_edit() {
+            return;
+        }
 
         // Translate display row to actual sequence index (for clustering support)
         let actual_row = self.display_to_actual_row(self.cursor_row);
+        let ends_in_residue = self.alignment.sequences.get(actual_row).is_some_and(|seq| {
+            seq.chars()
+                .last()
+                .is_some_and(|c| !self.gap_chars.contains(c))
+        });
+        if ends_in_residue {
+            self.append_gap_columns(1);
+        }
+        let width = self.flush_width(actual_row);
 
         if let Some(seq_rc) = self.alignment.sequences.get_mut(actual_row) {
             let seq = Rc::make_mut(seq_rc);
             seq.insert_gap(self.cursor_col, self.gap_char);
+            let delta = seq.fit_trailing_gaps(width, self.end_gap_char, &self.gap_chars);
+            seq.fix_end_gaps(
+                self.cursor_col,
+                self.gap_char,
+                self.end_gap_char,
+                &self.gap_chars,
+            );
 
             // Also update associated #=GR annotations
             if let Some(annotations) = self.alignment.residue_annotations.get_mut(&seq.id) {
@@ -23,6 +123,7 @@ impl App {
                     if self.cursor_col <= ann.data.len() {
                         ann.data.insert(self.cursor_col, self.gap_char);
                     }
+                    adjust_annotation_end(&mut ann.data, delta, self.gap_char);
                 }
             }
         }
@@ -32,60 +133,187 @@ impl App {
     }
 
     /// Delete a gap at the cursor position in the current sequence.
+    ///
+    /// The sequence is padded with an end gap to stay flush with the alignment. A residue
+    /// is only deleted after confirmation, or directly with `:set force-delete=on`.
     pub fn delete_gap(&mut self) -> bool {
-        if !self.is_current_gap() {
-            self.set_status("Not a gap character");
+        if !self.check_writable() {
             return false;
         }
+        let actual_row = self.display_to_actual_row(self.cursor_row);
+        let col = self.cursor_col;
+        let Some(ch) = self.current_char() else {
+            return false;
+        };
 
-        self.save_undo_state();
+        if !self.is_current_gap() {
+            if self.force_delete {
+                self.delete_residue(actual_row, col);
+            } else {
+                let id = &self.alignment.sequences[actual_row].id;
+                let message = format!(
+                    "Delete residue '{ch}' from {id} at column {}? (y/n)",
+                    col + 1
+                );
+                self.confirm = Some(Confirm::DeleteResidue {
+                    row: actual_row,
+                    col,
+                });
+                self.set_status(message);
+            }
+            return false;
+        }
 
-        // Translate display row to actual sequence index (for clustering support)
-        let actual_row = self.display_to_actual_row(self.cursor_row);
+        if !self.begin_edit() {
+            return false;
+        }
+        self.remove_char(actual_row, col);
+        self.mark_modified();
+        true
+    }
 
-        let seq_id = self
-            .alignment
-            .sequences
-            .get(actual_row)
-            .map(|s| s.id.clone());
+    /// Delete the residue at a cell, along with its #=GR characters.
+    ///
+    /// Removing the first or last residue shrinks the ID's coordinates to match.
+    pub fn delete_residue(&mut self, row: usize, col: usize) {
+        let Some(seq) = self.alignment.sequences.get(row) else {
+            return;
+        };
+        let Some((first, last)) = seq.residue_span(&self.gap_chars) else {
+            return;
+        };
+        let new_id = if first == last {
+            None
+        } else if col == first {
+            adjust_coords(&seq.id, -1, 0)
+        } else if col == last {
+            adjust_coords(&seq.id, 0, -1)
+        } else {
+            None
+        };
+        let ch = seq.get(col).unwrap_or(' ');
+        let id = seq.id.clone();
 
-        if let Some(seq_rc) = self.alignment.sequences.get_mut(actual_row) {
+        if !self.begin_edit() {
+            return;
+        }
+        self.remove_char(row, col);
+        if let Some(seq_rc) = self.alignment.sequences.get_mut(row) {
             let seq = Rc::make_mut(seq_rc);
-            if seq.delete_gap(self.cursor_col, &self.gap_chars) {
-                // Also update associated #=GR annotations
-                if let Some(id) = seq_id
-                    && let Some(annotations) = self.alignment.residue_annotations.get_mut(&id)
-                {
-                    for ann in annotations {
-                        if self.cursor_col < ann.data.len() {
-                            ann.data.remove(self.cursor_col);
-                        }
-                    }
+            // Gaps left outside the residues by removing an end residue become end gaps
+            if let Some((first, last)) = seq.residue_span(&self.gap_chars) {
+                for pos in (col..first).chain((last + 1)..=col) {
+                    seq.fix_end_gaps(pos, self.gap_char, self.end_gap_char, &self.gap_chars);
+                }
+            }
+        }
+        if let Some(new_id) = new_id {
+            self.alignment.rename_sequence(row, &new_id);
+        }
+        self.mark_modified();
+        self.set_status(format!("Deleted residue '{ch}' from {id}"));
+    }
+
+    /// Remove one character from a sequence and its #=GR rows, keeping it flush.
+    fn remove_char(&mut self, actual_row: usize, col: usize) {
+        let width = self.flush_width(actual_row);
+        let Some(seq_rc) = self.alignment.sequences.get_mut(actual_row) else {
+            return;
+        };
+        if col >= seq_rc.len() {
+            return;
+        }
+        let seq = Rc::make_mut(seq_rc);
+        seq.remove(col);
+        let delta = seq.fit_trailing_gaps(width, self.end_gap_char, &self.gap_chars);
+        // Also update associated #=GR annotations
+        if let Some(annotations) = self.alignment.residue_annotations.get_mut(&seq.id) {
+            for ann in annotations {
+                if col < ann.data.len() {
+                    ann.data.remove(col);
                 }
-                self.mark_modified();
-                return true;
+                adjust_annotation_end(&mut ann.data, delta, self.gap_char);
+            }
+        }
+    }
+
+    /// Answer a pending yes/no confirmation.
+    pub fn answer_confirm(&mut self, yes: bool) {
+        let Some(confirm) = self.confirm.take() else {
+            return;
+        };
+        if !yes {
+            self.set_status("Cancelled");
+            return;
+        }
+        match confirm {
+            Confirm::DeleteResidue { row, col } => self.delete_residue(row, col),
+            Confirm::PadAlignment { alignment, path } => self.load_padded(*alignment, path),
+            Confirm::ApplyCommand { command, selection } => {
+                self.selection_anchor = selection;
+                let _ = self.run_recorded(&command);
+                self.selection_anchor = None;
             }
         }
+    }
 
-        false
+    /// Width a single-sequence edit should keep the sequence at: the longest other
+    /// sequence, or the sequence's own length if it is alone.
+    fn flush_width(&self, actual_row: usize) -> usize {
+        self.alignment
+            .sequences
+            .iter()
+            .enumerate()
+            .filter(|(i, _)| *i != actual_row)
+            .map(|(_, s)| s.len())
+            .max()
+            .or_else(|| self.alignment.sequences.get(actual_row).map(|s| s.len()))
+            .unwrap_or(0)
     }
 
     /// Insert a gap column at the cursor position.
-    pub fn insert_gap_column(&mut self) {
-        self.save_undo_state();
+    ///
+    /// With a #=GC RF line the new column is an insert state, and the status
+    /// line says which model position it follows. `:set rf-snap=on` moves it
+    /// out of a run of insert columns to just before the next match column.
+    pub fn insert_gap_column(&mut self) -> bool {
+        if !self.begin_edit() {
+            return false;
+        }
+        let col = if self.rf_snap {
+            self.alignment
+                .rf_insert_boundary(self.cursor_col, &self.gap_chars)
+                .unwrap_or(self.cursor_col)
+        } else {
+            self.cursor_col
+        };
+        self.alignment.insert_gap_column(col, self.gap_char);
         self.alignment
-            .insert_gap_column(self.cursor_col, self.gap_char);
+            .fix_end_gaps(col, self.gap_char, self.end_gap_char, &self.gap_chars);
+        self.columns_inserted(col, 1);
         self.mark_modified();
         self.update_structure_cache();
+        if let Some(matches) = self.alignment.rf_match_count(col, &self.gap_chars) {
+            let after = match matches {
+                0 => "before model position 1".to_string(),
+                n => format!("after model position {n}"),
+            };
+            self.set_status(format!("Inserted column {}: insert state {after}", col + 1));
+        }
+        true
     }
 
     /// Delete a gap column at the cursor position.
     pub fn delete_gap_column(&mut self) -> bool {
+        if !self.check_writable() {
+            return false;
+        }
         if self
             .alignment
             .delete_gap_column(self.cursor_col, &self.gap_chars)
         {
             self.save_undo_state();
+            self.columns_removed(&[self.cursor_col]);
             self.mark_modified();
             self.clamp_cursor();
             self.update_structure_cache();
@@ -96,77 +324,233 @@ impl App {
         }
     }
 
+    /// Insert as many gap columns as the visual selection is wide, before it.
+    pub fn insert_selection_gap_columns(&mut self) {
+        let Some((_, min_col, _, max_col)) = self.get_selection_bounds() else {
+            return;
+        };
+        if !self.begin_edit() {
+            return;
+        }
+        let count = max_col - min_col + 1;
+        for _ in 0..count {
+            self.alignment.insert_gap_column(min_col, self.gap_char);
+        }
+        self.columns_inserted(min_col, count);
+        for col in min_col..min_col + count {
+            self.alignment
+                .fix_end_gaps(col, self.gap_char, self.end_gap_char, &self.gap_chars);
+        }
+        self.exit_visual_mode();
+        self.cursor_col = min_col;
+        self.mark_modified();
+        self.update_structure_cache();
+        self.set_status(format!(
+            "Inserted {count} gap columns before column {}",
+            min_col + 1
+        ));
+    }
+
+    /// Delete the gap-only columns within the visual selection's columns.
+    pub fn delete_selection_gap_columns(&mut self) {
+        let Some((_, min_col, _, max_col)) = self.get_selection_bounds() else {
+            return;
+        };
+        if !self.check_writable() {
+            return;
+        }
+        self.exit_visual_mode();
+        let cols: Vec<usize> = (min_col..=max_col)
+            .filter(|&col| self.alignment.is_empty_column(col, &self.gap_chars))
+            .collect();
+        let range = format!("columns {}-{}", min_col + 1, max_col + 1);
+        if cols.is_empty() {
+            self.set_status(format!("No gap-only columns in {range}"));
+            return;
+        }
+        self.save_undo_state();
+        self.alignment.remove_columns(&cols);
+        self.columns_removed(&cols);
+        self.cursor_col = min_col;
+        self.mark_modified();
+        self.clamp_cursor();
+        self.update_structure_cache();
+        self.set_status(format!(
+            "Deleted {} gap-only columns in {range}",
+            cols.len()
+        ));
+    }
+
+    /// Insert as many gaps as the block selection (`Ctrl-v`) is wide before it,
+    /// in its sequences only. Their residues move right into the first gaps they
+    /// meet; the alignment grows at the end if a sequence has too few.
+    pub fn insert_block_gaps(&mut self) {
+        let Some((_, min_col, _, max_col)) = self.get_selection_bounds() else {
+            return;
+        };
+        if !self.begin_edit() {
+            return;
+        }
+        let rows = self.target_rows();
+        let count = max_col - min_col + 1;
+        let width = self.alignment.width();
+        let added = self
+            .alignment
+            .push_gaps(&rows, min_col, count, self.gap_char, &self.gap_chars);
+        for col in width..width + added {
+            self.alignment
+                .fix_end_gaps(col, self.gap_char, self.end_gap_char, &self.gap_chars);
+        }
+        self.fix_block_end_gaps(&rows, min_col..min_col + count);
+        self.exit_visual_mode();
+        self.cursor_col = min_col;
+        self.mark_modified();
+        self.update_structure_cache();
+        let mut message = format!(
+            "Inserted {count} gaps before column {} in {} sequences",
+            min_col + 1,
+            rows.len()
+        );
+        if added > 0 {
+            message.push_str(&format!(" (added {added} columns at the end)"));
+        }
+        self.set_status(message);
+    }
+
+    /// Close up the gaps inside the block selection (`Ctrl-v`), moving each
+    /// sequence's residues to the left of the block and its gaps to the right.
+    pub fn close_block_gaps(&mut self) {
+        let Some((_, min_col, _, max_col)) = self.get_selection_bounds() else {
+            return;
+        };
+        if !self.check_writable() {
+            return;
+        }
+        let rows = self.target_rows();
+        self.exit_visual_mode();
+        let mut packed = self.alignment.clone();
+        let changed = packed.pack_left(&rows, min_col..max_col + 1, &self.gap_chars);
+        let range = format!("columns {}-{}", min_col + 1, max_col + 1);
+        if changed == 0 {
+            self.set_status(format!("No gaps to close up in {range}"));
+            return;
+        }
+        self.save_undo_state();
+        self.alignment = packed;
+        self.fix_block_end_gaps(&rows, min_col..max_col + 1);
+        self.mark_modified();
+        self.set_status(format!(
+            "Closed up gaps in {changed} sequences over {range}"
+        ));
+    }
+
+    /// Mark the gaps of `rows` within `cols` as end gaps or internal gaps.
+    fn fix_block_end_gaps(&mut self, rows: &[usize], cols: std::ops::Range<usize>) {
+        for &row in rows {
+            let seq = Rc::make_mut(&mut self.alignment.sequences[row]);
+            for col in cols.clone() {
+                seq.fix_end_gaps(col, self.gap_char, self.end_gap_char, &self.gap_chars);
+            }
+        }
+    }
+
     /// Internal shift without undo/status - consolidated implementation.
     fn shift_sequence_internal(&mut self, direction: ShiftDirection) -> bool {
         // Translate display row to actual sequence index (for clustering support)
         let actual_row = self.display_to_actual_row(self.cursor_row);
+        let col = self.cursor_col;
 
-        let seq_id = self
-            .alignment
-            .sequences
-            .get(actual_row)
-            .map(|s| s.id.clone());
+        let Some(seq_rc) = self.alignment.sequences.get_mut(actual_row) else {
+            return false;
+        };
+        // Check first so a failed shift doesn't copy a sequence shared with undo history
+        if !seq_rc.can_shift(col, direction, &self.gap_chars) {
+            return false;
+        }
+        let seq = Rc::make_mut(seq_rc);
+        seq.shift(col, direction, &self.gap_chars);
+        seq.fix_end_gaps(col, self.gap_char, self.end_gap_char, &self.gap_chars);
 
-        if let Some(seq_rc) = self.alignment.sequences.get_mut(actual_row) {
-            let seq = Rc::make_mut(seq_rc);
-            if seq.shift(self.cursor_col, direction, &self.gap_chars) {
-                // Also shift associated #=GR annotations
-                if let Some(id) = seq_id
-                    && let Some(annotations) = self.alignment.residue_annotations.get_mut(&id)
-                {
-                    for ann in annotations {
-                        let mut temp = crate::stockholm::Sequence::new("temp", ann.data.clone());
-                        temp.shift(self.cursor_col, direction, &self.gap_chars);
-                        ann.data = temp.data();
-                    }
-                }
-                return true;
+        // Also shift associated #=GR annotations
+        if let Some(annotations) = self.alignment.residue_annotations.get_mut(&seq.id) {
+            for ann in annotations {
+                let mut temp =
+                    crate::stockholm::Sequence::new("temp", std::mem::take(&mut ann.data));
+                temp.shift(col, direction, &self.gap_chars);
+                ann.data = temp.data();
             }
         }
-        false
+        true
     }
 
-    /// Shift current sequence in the given direction with undo support.
-    fn shift_sequence_with_undo(&mut self, direction: ShiftDirection) -> bool {
-        self.save_undo_state();
-        if self.shift_sequence_internal(direction) {
-            self.mark_modified();
-            true
-        } else {
-            let dir_str = match direction {
-                ShiftDirection::Left => "left",
-                ShiftDirection::Right => "right",
-            };
-            self.set_status(format!("Cannot shift {dir_str} (no gap found)"));
-            false
+    /// Shift current sequence in the given direction `count` times, or until
+    /// no gap is left, with undo support.
+    ///
+    /// Repeated shifts of the same residue (e.g. a held `<`/`>`) undo as one step.
+    /// A shift keeps every row its width, so it skips the annotation width checks
+    /// of `mark_modified` that would otherwise run on every repeat of the key.
+    pub fn shift_sequence(&mut self, direction: ShiftDirection, count: usize) -> bool {
+        let actual_row = self.display_to_actual_row(self.cursor_row);
+        let (kind, dir_str) = match direction {
+            ShiftDirection::Left => ("shift-left", "left"),
+            ShiftDirection::Right => ("shift-right", "right"),
+        };
+        let can_shift = self
+            .alignment
+            .sequences
+            .get(actual_row)
+            .is_some_and(|seq| seq.can_shift(self.cursor_col, direction, &self.gap_chars));
+        if !can_shift {
+            self.set_error(format!("Cannot shift {dir_str} (no gap found)"));
+            return false;
+        }
+
+        if !self.check_writable() {
+            return false;
         }
+        let columns = self.column_state();
+        self.history.save_repeat(
+            &self.alignment,
+            &columns,
+            self.cursor_row,
+            self.cursor_col,
+            (kind, actual_row, self.cursor_col),
+        );
+        let mut shifted = 0;
+        while shifted < count && self.shift_sequence_internal(direction) {
+            shifted += 1;
+        }
+        self.mark_residues_modified();
+        true
     }
 
     /// Shift current sequence left.
     pub fn shift_sequence_left(&mut self) -> bool {
-        self.shift_sequence_with_undo(ShiftDirection::Left)
+        self.shift_sequence(ShiftDirection::Left, 1)
     }
 
     /// Shift current sequence right.
     pub fn shift_sequence_right(&mut self) -> bool {
-        self.shift_sequence_with_undo(ShiftDirection::Right)
+        self.shift_sequence(ShiftDirection::Right, 1)
     }
 
     /// Throw sequence in the given direction (shift as far as possible).
     fn throw_sequence(&mut self, direction: ShiftDirection) {
-        self.save_undo_state();
+        if !self.begin_edit() {
+            return;
+        }
         let mut shifted = false;
         while self.shift_sequence_internal(direction) {
             shifted = true;
         }
         if shifted {
-            self.mark_modified();
+            self.mark_residues_modified();
         } else {
             let dir_str = match direction {
                 ShiftDirection::Left => "left",
                 ShiftDirection::Right => "right",
             };
-            self.set_status(format!("Cannot throw {dir_str} (no gaps found)"));
+            self.set_error(format!("Cannot throw {dir_str} (no gaps found)"));
         }
     }
 
@@ -180,165 +564,1828 @@ impl App {
         self.throw_sequence(ShiftDirection::Right);
     }
 
-    /// Undo the last action.
-    pub fn undo(&mut self) {
-        if let Some(snapshot) = self
-            .history
-            .undo(&self.alignment, self.cursor_row, self.cursor_col)
-        {
-            self.alignment = snapshot.alignment;
-            self.cursor_row = snapshot.cursor_row;
-            self.cursor_col = snapshot.cursor_col;
-            self.modified = true; // Still modified from original save
-            self.update_structure_cache();
-            self.set_status("Undo");
-        } else {
-            self.set_status("Nothing to undo");
+    /// Throw a column's residues as far as possible in the given direction.
+    pub fn throw_column(&mut self, col: usize, direction: ShiftDirection) {
+        let dir_str = match direction {
+            ShiftDirection::Left => "left",
+            ShiftDirection::Right => "right",
+        };
+        if col >= self.alignment.width() {
+            self.set_status(format!("Column {} out of range", col + 1));
+            return;
         }
-    }
-
-    /// Redo the last undone action.
-    pub fn redo(&mut self) {
-        if let Some(snapshot) = self
-            .history
-            .redo(&self.alignment, self.cursor_row, self.cursor_col)
+        if self
+            .alignment
+            .column_throw_distance(col, direction, &self.gap_chars)
+            == 0
         {
-            self.alignment = snapshot.alignment;
-            self.cursor_row = snapshot.cursor_row;
-            self.cursor_col = snapshot.cursor_col;
-            self.modified = true;
-            self.update_structure_cache();
-            self.set_status("Redo");
-        } else {
-            self.set_status("Nothing to redo");
+            self.set_error(format!("Cannot throw column {dir_str} (no gaps found)"));
+            return;
         }
-    }
 
-    /// Save current state for undo.
-    fn save_undo_state(&mut self) {
-        self.history
-            .save(&self.alignment, self.cursor_row, self.cursor_col);
-    }
-
-    /// Delete the current sequence.
-    pub fn delete_sequence(&mut self) {
-        if self.alignment.sequences.is_empty() {
+        if !self.begin_edit() {
             return;
         }
-
-        self.save_undo_state();
-
-        // Translate display row to actual sequence index (for clustering support)
-        let actual_row = self.display_to_actual_row(self.cursor_row);
-
-        let seq_id = self.alignment.sequences[actual_row].id.clone();
-        self.alignment.sequences.remove(actual_row);
-
-        // Remove associated annotations
-        self.alignment.sequence_annotations.remove(&seq_id);
-        self.alignment.residue_annotations.remove(&seq_id);
-
+        let distance = self.alignment.throw_column(col, direction, &self.gap_chars);
+        self.alignment
+            .fix_end_gaps(col, self.gap_char, self.end_gap_char, &self.gap_chars);
         self.mark_modified();
-        self.clamp_cursor();
 
-        // Recompute clustering if active (indices become stale after deletion)
-        if self.cluster_order.is_some() {
-            self.precompute_collapse_groups(); // Refresh group indices first
-            self.cluster_sequences();
+        // Follow the thrown column with the cursor
+        if col == self.cursor_col {
+            self.cursor_col = match direction {
+                ShiftDirection::Left => col - distance,
+                ShiftDirection::Right => col + distance,
+            };
         }
+        self.set_status(format!("Threw column {} {dir_str} by {distance}", col + 1));
     }
 
-    /// Delete all sequences in the current visual selection.
-    pub fn delete_selected_sequences(&mut self) {
-        let Some((min_row, _, max_row, _)) = self.get_selection_bounds() else {
+    /// Merge the cursor column with its neighbour in `direction`.
+    ///
+    /// The column with fewer residues is emptied into the other: each of its residues
+    /// moves across where the other column has a gap. Rows with residues in both columns
+    /// stay put. With `confirm`, each move is asked about first.
+    pub fn merge_columns(&mut self, direction: ShiftDirection, confirm: bool) {
+        let col = self.cursor_col;
+        let neighbour = match direction {
+            ShiftDirection::Left => col.checked_sub(1),
+            ShiftDirection::Right => Some(col + 1),
+        };
+        let Some(neighbour) = neighbour.filter(|&n| n < self.alignment.width()) else {
+            self.set_status("No adjacent column to merge with");
             return;
         };
 
-        if self.alignment.sequences.is_empty() {
+        let has_residue = |row: usize, c: usize| {
+            self.alignment.sequences[row]
+                .get(c)
+                .is_some_and(|ch| !self.gap_chars.contains(&ch))
+        };
+        let rows = 0..self.alignment.sequences.len();
+        let count = |c: usize| rows.clone().filter(|&row| has_residue(row, c)).count();
+        let (from, into) = if count(neighbour) <= count(col) {
+            (neighbour, col)
+        } else {
+            (col, neighbour)
+        };
+        let movers: Vec<usize> = rows
+            .clone()
+            .filter(|&row| has_residue(row, from) && !has_residue(row, into))
+            .collect();
+        let conflicts = rows
+            .filter(|&row| has_residue(row, from) && has_residue(row, into))
+            .count();
+
+        if movers.is_empty() {
+            self.set_status(format!(
+                "Nothing to merge from column {} ({conflicts} rows occupy both columns)",
+                from + 1
+            ));
             return;
         }
+        let op = ColumnOp::Merge {
+            from,
+            into,
+            conflicts,
+        };
+        if confirm {
+            self.start_column_prompt(op, movers);
+        } else {
+            self.apply_column_op(op, &movers);
+        }
+    }
 
-        self.save_undo_state();
-
-        // Collect actual row indices for all selected display rows
-        let mut actual_rows: Vec<usize> = (min_row..=max_row)
-            .map(|display_row| self.display_to_actual_row(display_row))
-            .collect();
+    /// Split the cursor column, moving some sequences' residues into a new column
+    /// inserted on the `direction` side.
+    ///
+    /// Rows of a visual selection carried into command mode are moved directly;
+    /// otherwise each sequence with a residue in the column is asked about.
+    pub fn split_column(&mut self, direction: ShiftDirection) {
+        let col = self.cursor_col;
+        if col >= self.alignment.width() {
+            self.set_status(format!("Column {} out of range", col + 1));
+            return;
+        }
+        let has_residue = |row: usize| {
+            self.alignment.sequences[row]
+                .get(col)
+                .is_some_and(|ch| !self.gap_chars.contains(&ch))
+        };
+        let op = ColumnOp::Split { col, direction };
 
-        // Sort in reverse order to delete from end first (preserves earlier indices)
-        actual_rows.sort_unstable();
-        actual_rows.dedup();
-        actual_rows.reverse();
+        if self.selection_anchor.is_some() {
+            let rows: Vec<usize> = self
+                .target_rows()
+                .into_iter()
+                .filter(|&row| has_residue(row))
+                .collect();
+            if rows.is_empty() {
+                self.set_status("Selected rows have no residues in this column");
+                return;
+            }
+            self.apply_column_op(op, &rows);
+        } else {
+            let rows: Vec<usize> = (0..self.alignment.sequences.len())
+                .filter(|&row| has_residue(row))
+                .collect();
+            if rows.is_empty() {
+                self.set_status("Column has no residues to split");
+                return;
+            }
+            self.start_column_prompt(op, rows);
+        }
+    }
 
-        let count = actual_rows.len();
+    /// Begin asking about each row of a column edit in turn.
+    fn start_column_prompt(&mut self, op: ColumnOp, rows: Vec<usize>) {
+        self.column_prompt = Some(ColumnPrompt {
+            op,
+            pending: rows,
+            accepted: Vec::new(),
+        });
+        self.show_column_prompt();
+    }
 
-        // Delete each sequence
-        for actual_row in actual_rows {
-            if actual_row < self.alignment.sequences.len() {
-                let seq_id = self.alignment.sequences[actual_row].id.clone();
-                self.alignment.sequences.remove(actual_row);
-                self.alignment.sequence_annotations.remove(&seq_id);
-                self.alignment.residue_annotations.remove(&seq_id);
+    /// Put the cursor on the row being asked about and show the question.
+    fn show_column_prompt(&mut self) {
+        let Some(prompt) = &self.column_prompt else {
+            return;
+        };
+        let Some(&row) = prompt.pending.first() else {
+            return;
+        };
+        let (col, target) = match prompt.op {
+            ColumnOp::Merge { from, into, .. } => (from, format!("column {}", into + 1)),
+            ColumnOp::Split { col, direction } => {
+                let side = match direction {
+                    ShiftDirection::Left => "left",
+                    ShiftDirection::Right => "right",
+                };
+                (col, format!("new column on the {side}"))
             }
+        };
+        let seq = &self.alignment.sequences[row];
+        let message = format!(
+            "Move {} '{}' at column {} to {target}? (y/n/a/q, {} left)",
+            seq.id,
+            seq.get(col).unwrap_or(' '),
+            col + 1,
+            prompt.pending.len()
+        );
+        if let Some(display_row) = self.actual_to_display_row(row) {
+            self.cursor_row = display_row;
         }
+        self.cursor_col = col;
+        self.set_status(message);
+    }
 
-        self.mark_modified();
+    /// Answer the pending column prompt: `y` moves this row, `n` skips it, `a` moves
+    /// it and all remaining rows, `q` abandons the edit.
+    pub fn answer_column_prompt(&mut self, answer: char) {
+        let Some(mut prompt) = self.column_prompt.take() else {
+            return;
+        };
+        match answer {
+            'y' | 'n' => {
+                let row = prompt.pending.remove(0);
+                if answer == 'y' {
+                    prompt.accepted.push(row);
+                }
+            }
+            'a' => prompt.accepted.append(&mut prompt.pending),
+            'q' => {
+                self.set_status("Column edit cancelled");
+                return;
+            }
+            _ => {
+                self.column_prompt = Some(prompt);
+                self.show_column_prompt();
+                return;
+            }
+        }
+
+        if prompt.pending.is_empty() {
+            if prompt.accepted.is_empty() {
+                self.set_status("No residues moved");
+            } else {
+                self.apply_column_op(prompt.op, &prompt.accepted);
+            }
+        } else {
+            self.column_prompt = Some(prompt);
+            self.show_column_prompt();
+        }
+    }
+
+    /// Carry out a merge or split for the given rows as one undoable edit.
+    fn apply_column_op(&mut self, op: ColumnOp, rows: &[usize]) {
+        if !self.begin_edit() {
+            return;
+        }
+        let (gap, end_gap) = (self.gap_char, self.end_gap_char);
+        let message = match op {
+            ColumnOp::Merge {
+                from,
+                into,
+                conflicts,
+            } => {
+                for &row in rows {
+                    self.alignment.swap_residues(row, from, into);
+                }
+                self.alignment
+                    .fix_end_gaps(from, gap, end_gap, &self.gap_chars);
+                self.alignment
+                    .fix_end_gaps(into, gap, end_gap, &self.gap_chars);
+                // A base-paired column keeps its place in SS_cons even when emptied
+                let removed = self.structure_cache.get_pair(from).is_none()
+                    && self.alignment.delete_gap_column(from, &self.gap_chars);
+                if removed {
+                    self.columns_removed(&[from]);
+                }
+                self.cursor_col = if removed && from < into {
+                    into - 1
+                } else {
+                    into
+                };
+                let mut message = format!(
+                    "Merged {} residues from column {} into column {}",
+                    rows.len(),
+                    from + 1,
+                    into + 1
+                );
+                if removed {
+                    message.push_str(" (emptied column removed)");
+                }
+                if conflicts > 0 {
+                    message.push_str(&format!("; {conflicts} rows occupy both columns"));
+                }
+                message
+            }
+            ColumnOp::Split { col, direction } => {
+                let (old, new) = match direction {
+                    ShiftDirection::Left => (col + 1, col),
+                    ShiftDirection::Right => (col, col + 1),
+                };
+                self.alignment.insert_gap_column(new, gap);
+                self.columns_inserted(new, 1);
+                for &row in rows {
+                    self.alignment.swap_residues(row, old, new);
+                }
+                self.alignment
+                    .fix_end_gaps(old, gap, end_gap, &self.gap_chars);
+                self.alignment
+                    .fix_end_gaps(new, gap, end_gap, &self.gap_chars);
+                self.cursor_col = new;
+                format!(
+                    "Split column {}: moved {} residues to new column {}",
+                    col + 1,
+                    rows.len(),
+                    new + 1
+                )
+            }
+        };
+        self.mark_modified();
+        self.update_structure_cache();
+        self.clamp_cursor();
+        self.set_status(message);
+    }
+
+    /// Undo the last action. A `:set` changes no residues, so it can be undone
+    /// in read-only mode too.
+    pub fn undo(&mut self) {
+        if !self.history.undoes_settings() && !self.check_writable() {
+            return;
+        }
+        let settings = self.settings();
+        let columns = self.column_state();
+        if let Some(snapshot) = self.history.undo(
+            &self.alignment,
+            &columns,
+            &settings,
+            self.cursor_row,
+            self.cursor_col,
+        ) {
+            self.alignment = snapshot.alignment;
+            self.pinned_columns = snapshot.columns.pinned_columns;
+            self.color_focus = snapshot.columns.color_focus;
+            self.cursor_row = snapshot.cursor_row;
+            self.cursor_col = snapshot.cursor_col;
+            if let Some(previous) = snapshot.settings {
+                self.set_status(format!("Undo :set {}", settings.changes(&previous)));
+                self.apply_settings(previous);
+                return;
+            }
+            self.modified = true; // Still modified from original save
+            self.edits += 1;
+            self.clear_column_confidence();
+            self.update_structure_cache();
+            self.refresh_row_filter();
+            self.set_status("Undo");
+        } else {
+            self.set_error("Nothing to undo");
+        }
+    }
+
+    /// Redo the last undone action, in read-only mode too if it is a `:set`.
+    pub fn redo(&mut self) {
+        if !self.history.redoes_settings() && !self.check_writable() {
+            return;
+        }
+        let settings = self.settings();
+        let columns = self.column_state();
+        if let Some(snapshot) = self.history.redo(
+            &self.alignment,
+            &columns,
+            &settings,
+            self.cursor_row,
+            self.cursor_col,
+        ) {
+            self.alignment = snapshot.alignment;
+            self.pinned_columns = snapshot.columns.pinned_columns;
+            self.color_focus = snapshot.columns.color_focus;
+            self.cursor_row = snapshot.cursor_row;
+            self.cursor_col = snapshot.cursor_col;
+            if let Some(next) = snapshot.settings {
+                self.set_status(format!("Redo :set {}", settings.changes(&next)));
+                self.apply_settings(next);
+                return;
+            }
+            self.modified = true;
+            self.edits += 1;
+            self.clear_column_confidence();
+            self.update_structure_cache();
+            self.refresh_row_filter();
+            self.set_status("Redo");
+        } else {
+            self.set_error("Nothing to redo");
+        }
+    }
+
+    /// Save current state for undo.
+    pub(crate) fn save_undo_state(&mut self) {
+        let columns = self.column_state();
+        self.history
+            .save(&self.alignment, &columns, self.cursor_row, self.cursor_col);
+    }
+
+    /// Whether the alignment may be edited; in read-only mode (`aform -R`,
+    /// `:set readonly`) says why not on the status line.
+    pub(crate) fn check_writable(&mut self) -> bool {
+        if self.read_only {
+            self.set_error("Read-only: editing is off (:set noreadonly to edit)");
+        }
+        !self.read_only
+    }
+
+    /// Start an edit: save the undo state, or refuse in read-only mode.
+    pub(crate) fn begin_edit(&mut self) -> bool {
+        if !self.check_writable() {
+            return false;
+        }
+        self.save_undo_state();
+        true
+    }
+
+    /// Delete the current sequence.
+    pub fn delete_sequence(&mut self) {
+        if self.alignment.sequences.is_empty() {
+            return;
+        }
+
+        if !self.begin_edit() {
+            return;
+        }
+
+        // Translate display row to actual sequence index (for clustering support)
+        let actual_row = self.display_to_actual_row(self.cursor_row);
+
+        // Keep the sequence and its annotations in the register for `p`
+        let Some(detached) = self.alignment.take_sequence(actual_row) else {
+            return;
+        };
+        let seq_id = detached.sequence.id.clone();
+        let into = self.store_register(Register::Sequences(vec![detached]));
+
+        self.mark_modified();
+        self.clamp_cursor();
+
+        // Recompute clustering if active (indices become stale after deletion)
+        if self.cluster_order.is_some() {
+            self.precompute_collapse_groups(); // Refresh group indices first
+            self.cluster_sequences();
+        }
+        self.refresh_row_filter();
+        if into.is_empty() {
+            self.set_status(format!("Deleted {seq_id} (p puts it back)"));
+        } else {
+            self.set_status(format!("Deleted {seq_id}{into}"));
+        }
+    }
+
+    /// Copy the current sequence and its annotations to the register (`yy`); `p`
+    /// puts the copy under a new ID.
+    pub fn yank_sequence(&mut self) {
+        if self.alignment.sequences.is_empty() {
+            return;
+        }
+        let actual_row = self.display_to_actual_row(self.cursor_row);
+        let Some(copy) = self.alignment.copy_sequence(actual_row) else {
+            return;
+        };
+        let seq_id = copy.sequence.id.clone();
+        let into = self.store_register(Register::Sequences(vec![copy]));
+        if into.is_empty() {
+            self.set_status(format!("Yanked {seq_id} (p puts a copy)"));
+        } else {
+            self.set_status(format!("Yanked {seq_id}{into}"));
+        }
+    }
+
+    /// Rename a sequence (`:rename [old] <new>`; the cursor sequence without
+    /// `old`). Its #=GS and #=GR annotations and any marks on it follow it to the
+    /// new ID.
+    pub fn rename_id(&mut self, old: Option<&str>, new: &str) {
+        let row = match old {
+            Some(old) => match self.alignment.sequences.iter().position(|s| s.id == old) {
+                Some(row) => row,
+                None => {
+                    self.set_error(format!("No sequence {old}"));
+                    return;
+                }
+            },
+            None if self.alignment.sequences.is_empty() => return,
+            None => self.display_to_actual_row(self.cursor_row),
+        };
+        let old = self.alignment.sequences[row].id.clone();
+        if new.starts_with('#') {
+            self.set_error(format!("Cannot rename {old}: an ID can't start with #"));
+            return;
+        }
+        if new == old {
+            return;
+        }
+        if self.alignment.sequences.iter().any(|s| s.id == new) {
+            self.set_error(format!(
+                "Cannot rename {old}: {new} is already in the alignment"
+            ));
+            return;
+        }
+        if !self.begin_edit() {
+            return;
+        }
+        self.alignment.rename_sequence(row, new);
+        for mark in self.marks.values_mut().chain(&mut self.jumps) {
+            if mark.id == old {
+                mark.id = new.to_string();
+            }
+        }
+        self.mark_modified();
+        self.set_status(format!("Renamed {old} to {new}"));
+    }
+
+    /// Open the command line as `:rename <id> ` for the cursor sequence (`cw`).
+    pub fn start_rename(&mut self) {
+        if !self.check_writable() {
+            return;
+        }
+        let actual_row = self.display_to_actual_row(self.cursor_row);
+        let Some(id) = self
+            .alignment
+            .sequences
+            .get(actual_row)
+            .map(|s| s.id.clone())
+        else {
+            return;
+        };
+        self.enter_command_mode();
+        self.command_buffer = format!("rename {id} ");
+    }
+
+    /// Move the cursor sequence to row `to` (0-based) of the file order
+    /// (`:move <N>`); the cursor moves with it.
+    pub fn move_sequence(&mut self, to: usize) {
+        if self.alignment.sequences.is_empty() {
+            return;
+        }
+        if self.cluster_order.is_some() {
+            self.set_status("Rows are in cluster order (:uncluster to move them)");
+            return;
+        }
+        let len = self.alignment.sequences.len();
+        let from = self.display_to_actual_row(self.cursor_row);
+        let to = to.min(len - 1);
+        let id = self.alignment.sequences[from].id.clone();
+        if from == to {
+            self.set_status(format!("{id} is already row {} of {len}", to + 1));
+            return;
+        }
+        if !self.begin_edit() {
+            return;
+        }
+        let mut order: Vec<usize> = (0..len).collect();
+        let row = order.remove(from);
+        order.insert(to, row);
+        self.alignment.reorder_sequences(&order);
+        self.reference_seq = order
+            .iter()
+            .position(|&r| r == self.reference_seq)
+            .unwrap_or(self.reference_seq);
+        self.precompute_collapse_groups();
+        self.refresh_row_filter();
+        if let Some(display_row) = self.actual_to_display_row(to) {
+            self.cursor_row = display_row;
+        }
+        self.mark_modified();
+        self.clamp_cursor();
+        self.set_status(format!("Moved {id} to row {} of {len}", to + 1));
+    }
+
+    /// Move the cursor sequence `n` rows down, or up if `up` (`Alt-j`/`Alt-k`),
+    /// stopping at the first or last row.
+    pub fn move_sequence_by(&mut self, n: usize, up: bool) {
+        if self.alignment.sequences.is_empty() {
+            return;
+        }
+        let from = self.display_to_actual_row(self.cursor_row);
+        let to = if up {
+            from.saturating_sub(n)
+        } else {
+            from.saturating_add(n)
+        };
+        self.move_sequence(to);
+    }
+
+    /// Copy the sequences in the visual selection to the register (visual `Y`).
+    pub fn yank_selected_sequences(&mut self) {
+        let Some((min_row, _, max_row, _)) = self.get_selection_bounds() else {
+            return;
+        };
+        let mut actual_rows: Vec<usize> = (min_row..=max_row)
+            .map(|display_row| self.display_to_actual_row(display_row))
+            .collect();
+        actual_rows.sort_unstable();
+        actual_rows.dedup();
+        let copies: Vec<_> = actual_rows
+            .into_iter()
+            .filter_map(|row| self.alignment.copy_sequence(row))
+            .collect();
+        let count = copies.len();
+        let into = self.store_register(Register::Sequences(copies));
+        self.exit_visual_mode();
+        self.set_status(format!("Yanked {count} sequence(s){into}"));
+    }
+
+    /// Delete all sequences in the current visual selection.
+    pub fn delete_selected_sequences(&mut self) {
+        let Some((min_row, _, max_row, _)) = self.get_selection_bounds() else {
+            return;
+        };
+
+        if self.alignment.sequences.is_empty() {
+            return;
+        }
+
+        if !self.begin_edit() {
+            return;
+        }
+
+        // Collect actual row indices for all selected display rows
+        let mut actual_rows: Vec<usize> = (min_row..=max_row)
+            .map(|display_row| self.display_to_actual_row(display_row))
+            .collect();
+
+        // Sort in reverse order to delete from end first (preserves earlier indices)
+        actual_rows.sort_unstable();
+        actual_rows.dedup();
+        actual_rows.reverse();
+
+        // Delete each sequence, keeping them in the register in alignment order
+        let mut deleted: Vec<_> = actual_rows
+            .into_iter()
+            .filter_map(|actual_row| self.alignment.take_sequence(actual_row))
+            .collect();
+        deleted.reverse();
+        let count = deleted.len();
+        let into = self.store_register(Register::Sequences(deleted));
+
+        self.mark_modified();
         self.exit_visual_mode();
         self.clamp_cursor();
 
-        // Recompute clustering if active
-        if self.cluster_order.is_some() {
-            self.precompute_collapse_groups();
-            self.cluster_sequences();
+        // Recompute clustering if active
+        if self.cluster_order.is_some() {
+            self.precompute_collapse_groups();
+            self.cluster_sequences();
+        }
+        self.refresh_row_filter();
+
+        if into.is_empty() {
+            self.set_status(format!("Deleted {count} sequence(s) (p puts them back)"));
+        } else {
+            self.set_status(format!("Deleted {count} sequence(s){into}"));
+        }
+    }
+
+    /// Remove exact duplicate sequences, and with a `threshold` (0.0 to 1.0)
+    /// those at least that identical to the sequence their greedy identity
+    /// cluster keeps (`:dedup`), listing what went in a report.
+    pub fn dedup(&mut self, threshold: Option<f64>) {
+        let seq_chars: Vec<Vec<char>> = self
+            .alignment
+            .sequences
+            .iter()
+            .map(|s| s.chars().to_vec())
+            .collect();
+        let redundant = redundant_sequences(&seq_chars, &self.gap_chars, threshold);
+        if redundant.is_empty() {
+            self.set_status(match threshold {
+                Some(t) => format!(
+                    "No sequences {:.0}% or more identical to another",
+                    t * 100.0
+                ),
+                None => "No duplicate sequences".to_string(),
+            });
+            return;
+        }
+        if !self.begin_edit() {
+            return;
+        }
+
+        let ids: Vec<String> = self
+            .alignment
+            .sequences
+            .iter()
+            .map(|s| s.id.clone())
+            .collect();
+        let width = ids.iter().map(|id| id.len()).max().unwrap_or(0);
+        let mut lines: Vec<String> = redundant
+            .iter()
+            .map(|&(row, kept, identity)| {
+                let reason = match identity {
+                    Some(identity) => format!("{:.1}% identical to", identity * 100.0),
+                    None => "duplicate of".to_string(),
+                };
+                format!("{:<width$}  {reason} {}", ids[row], ids[kept])
+            })
+            .collect();
+        for &(row, ..) in redundant.iter().rev() {
+            self.alignment.take_sequence(row);
+        }
+        let total = ids.len();
+        let count = redundant.len();
+
+        self.mark_modified();
+        self.clamp_cursor();
+        if self.cluster_order.is_some() {
+            self.precompute_collapse_groups();
+            self.cluster_sequences();
+        }
+        self.refresh_row_filter();
+
+        lines.push(String::new());
+        lines.push("u puts them back".to_string());
+        self.report = Some(Report {
+            title: format!("Removed {count} of {total} sequences"),
+            lines,
+        });
+        self.set_status(format!(
+            "Removed {count} redundant sequences, {} left",
+            total - count
+        ));
+    }
+
+    /// Put sequences deleted with `dd` (or read with `:read`) below or above the
+    /// cursor row. Returns false, leaving the alignment alone, if one of them has
+    /// the wrong width or an ID already in the alignment.
+    pub(crate) fn put_sequences(&mut self, sequences: Vec<DetachedSequence>, above: bool) -> bool {
+        let width = self.alignment.width();
+        if let Some(seq) = sequences
+            .iter()
+            .find(|d| !self.alignment.sequences.is_empty() && d.sequence.len() != width)
+        {
+            self.set_error(format!(
+                "Cannot put {}: it is {} columns wide, the alignment is {width}",
+                seq.sequence.id,
+                seq.sequence.len()
+            ));
+            return false;
+        }
+        if let Some(seq) = sequences.iter().find(|d| {
+            self.alignment
+                .sequences
+                .iter()
+                .any(|s| s.id == d.sequence.id)
+        }) {
+            self.set_error(format!(
+                "Cannot put {}: it is already in the alignment",
+                seq.sequence.id
+            ));
+            return false;
+        }
+
+        if !self.begin_edit() {
+            return false;
+        }
+        let index = if self.alignment.sequences.is_empty() {
+            0
+        } else {
+            self.display_to_actual_row(self.cursor_row) + usize::from(!above)
+        };
+        let count = sequences.len();
+        for (offset, detached) in sequences.into_iter().enumerate() {
+            self.alignment.insert_sequence(index + offset, detached);
+        }
+        self.mark_modified();
+
+        // Recompute clustering if active (indices shift after insertion)
+        if self.cluster_order.is_some() {
+            self.precompute_collapse_groups();
+            self.cluster_sequences();
+        }
+        self.refresh_row_filter();
+        if let Some(row) = self.actual_to_display_row(index) {
+            self.cursor_row = row;
+        }
+        self.clamp_cursor();
+        self.set_status(format!("Put {count} sequence(s)"));
+        true
+    }
+
+    /// Append the sequences of another file below the cursor row (`:read`), with
+    /// their #=GS and #=GR annotations. Shorter sequences (including those of an
+    /// unaligned FASTA file) are padded with end gaps to the alignment width;
+    /// wider ones are refused.
+    pub fn read_sequences(&mut self, path: &Path) {
+        let read = match crate::formats::read_file(path, None) {
+            Err(FormatError::Fasta(FastaError::InconsistentLengths)) => {
+                crate::formats::read_unaligned_fasta(path).map(|alignment| vec![alignment])
+            }
+            result => result.map(|(alignments, ..)| alignments),
+        };
+        let mut alignments = match read {
+            Ok(alignments) => alignments,
+            Err(e) => {
+                self.set_error(format!("Failed to read {}: {e}", path.display()));
+                return;
+            }
+        };
+        let mut other = alignments.swap_remove(0);
+        let width = self.alignment.width();
+        let mut padded = 0;
+        let mut sequences = Vec::new();
+        while let Some(mut detached) = other.take_sequence(0) {
+            if !self.alignment.sequences.is_empty() && detached.pad_to(width, self.end_gap_char) {
+                padded += 1;
+            }
+            sequences.push(detached);
+        }
+        let count = sequences.len();
+        if count == 0 {
+            self.set_status(format!("No sequences in {}", path.display()));
+            return;
+        }
+        if self.put_sequences(sequences, false) {
+            let padded = if padded > 0 {
+                format!(" ({padded} padded to {width} columns)")
+            } else {
+                String::new()
+            };
+            self.set_status(format!(
+                "Read {count} sequence(s) from {}{padded}",
+                path.display()
+            ));
+        }
+    }
+
+    /// Add a sequence called `id` below the cursor row (`:new-seq`): `residues`
+    /// left-aligned and padded with end gaps to the alignment width, or, with no
+    /// residues, a row of gaps to type them into from insert mode. Gap columns
+    /// are added at the end when the residues don't fit.
+    pub fn new_sequence(&mut self, id: &str, residues: &str) {
+        let residues: Vec<char> = residues
+            .chars()
+            .filter(|c| !c.is_whitespace() && !self.gap_chars.contains(c))
+            .collect();
+        if let Some(c) = residues
+            .iter()
+            .find(|c| !c.is_ascii_alphabetic() && **c != '*')
+        {
+            self.set_status(format!("Not a residue: '{c}'"));
+            return;
+        }
+        if self.alignment.sequences.iter().any(|s| s.id == id) {
+            self.set_error(format!("Cannot add {id}: it is already in the alignment"));
+            return;
+        }
+        if !self.check_writable() {
+            return;
+        }
+
+        // One undo step for the added columns and the new row
+        self.history.begin_group();
+        self.save_undo_state();
+        let width = self.alignment.width();
+        let added = self.append_gap_columns(residues.len().saturating_sub(width));
+        let mut data: Vec<char> = residues.clone();
+        data.resize(width.max(residues.len()), self.end_gap_char);
+        let detached = DetachedSequence {
+            sequence: Rc::new(Sequence::new(id, data.into_iter().collect::<String>())),
+            annotations: Vec::new(),
+            residue_annotations: Vec::new(),
+        };
+        let put = self.put_sequences(vec![detached], false);
+        self.history.end_group();
+        if !put {
+            return;
+        }
+
+        if residues.is_empty() {
+            self.cursor_col = 0;
+            self.enter_insert_mode();
+            self.typing_sequence = Some(id.to_string());
+            self.set_status(format!("Added {id}: type its residues, Esc when done"));
+        } else {
+            let added = if added > 0 {
+                format!(" ({added} columns added)")
+            } else {
+                String::new()
+            };
+            self.set_status(format!("Added {id}: {} residues{added}", residues.len()));
+        }
+    }
+
+    /// Add `n` gap columns at the end of the alignment, as end gaps where they
+    /// follow a sequence's last residue. Returns `n`.
+    fn append_gap_columns(&mut self, n: usize) -> usize {
+        for _ in 0..n {
+            let col = self.alignment.width();
+            self.alignment.insert_gap_column(col, self.gap_char);
+            self.alignment
+                .fix_end_gaps(col, self.gap_char, self.end_gap_char, &self.gap_chars);
+        }
+        if n > 0 {
+            self.update_structure_cache();
+        }
+        n
+    }
+
+    /// Type a residue into the sequence added with `:new-seq` (insert mode):
+    /// it goes after the residues typed so far, adding a column at the end
+    /// when the row is full.
+    pub fn type_residue(&mut self, residue: char) {
+        if !self.on_typing_sequence() {
+            self.set_status("Residues can only be typed into a sequence added with :new-seq");
+            return;
+        }
+        let actual_row = self.display_to_actual_row(self.cursor_row);
+        let seq = &self.alignment.sequences[actual_row];
+        let col = seq
+            .residue_span(&self.gap_chars)
+            .map_or(0, |(_, last)| last + 1);
+        if !self.begin_edit() {
+            return;
+        }
+        self.history.begin_group();
+        self.append_gap_columns((col + 1).saturating_sub(self.alignment.width()));
+        if let Some(seq_rc) = self.alignment.sequences.get_mut(actual_row) {
+            let seq = Rc::make_mut(seq_rc);
+            seq.set(col, residue);
+            let fixes = seq.end_gap_fixes(col, self.gap_char, self.end_gap_char, &self.gap_chars);
+            for (i, ch) in fixes {
+                seq.set(i, ch);
+            }
+        }
+        self.history.end_group();
+        self.mark_modified();
+        self.cursor_col = col;
+        self.cursor_right();
+    }
+
+    /// Remove the last residue typed into the sequence added with `:new-seq`
+    /// (insert mode `Backspace`).
+    pub fn untype_residue(&mut self) {
+        if !self.on_typing_sequence() {
+            return;
+        }
+        let actual_row = self.display_to_actual_row(self.cursor_row);
+        let Some((_, last)) = self.alignment.sequences[actual_row].residue_span(&self.gap_chars)
+        else {
+            return;
+        };
+        if !self.begin_edit() {
+            return;
+        }
+        Rc::make_mut(&mut self.alignment.sequences[actual_row]).set(last, self.end_gap_char);
+        self.mark_modified();
+        self.cursor_col = last;
+    }
+
+    /// The cursor is on the sequence added with `:new-seq` that insert mode is
+    /// typing.
+    pub fn on_typing_sequence(&self) -> bool {
+        let actual_row = self.display_to_actual_row(self.cursor_row);
+        self.alignment
+            .sequences
+            .get(actual_row)
+            .is_some_and(|seq| self.typing_sequence.as_deref() == Some(seq.id.as_str()))
+    }
+
+    /// Pad the current (or selected) sequences with `n` unknown residues at one end.
+    ///
+    /// Uses the sequence's flanking gaps first; gap columns are added to the alignment
+    /// when there is not enough room. ID coordinates are extended to match.
+    pub fn pad_sequences(&mut self, end: SequenceEnd, n: usize) {
+        let rows = self.target_rows();
+        if n == 0 || rows.is_empty() {
+            return;
+        }
+        let width = self.alignment.width();
+        let mut shortfall = 0;
+        for &row in &rows {
+            let seq = &self.alignment.sequences[row];
+            let Some((first, last)) = seq.residue_span(&self.gap_chars) else {
+                self.set_error(format!("Cannot pad {}: no residues", seq.id));
+                return;
+            };
+            let (delta5, delta3, room) = match end {
+                SequenceEnd::FivePrime => (n as isize, 0, first),
+                SequenceEnd::ThreePrime => (0, n as isize, width - 1 - last),
+            };
+            if parse_coords(&seq.id).is_some() && adjust_coords(&seq.id, delta5, delta3).is_none() {
+                self.set_error(format!("Cannot pad {} past position 1", seq.id));
+                return;
+            }
+            shortfall = shortfall.max(n.saturating_sub(room));
+        }
+
+        if !self.begin_edit() {
+            return;
+        }
+
+        // Make room for the longest pad with new gap columns at that end
+        for _ in 0..shortfall {
+            let col = match end {
+                SequenceEnd::FivePrime => 0,
+                SequenceEnd::ThreePrime => self.alignment.width(),
+            };
+            self.alignment.insert_gap_column(col, self.gap_char);
+            self.alignment
+                .fix_end_gaps(col, self.gap_char, self.end_gap_char, &self.gap_chars);
+            self.columns_inserted(col, 1);
+        }
+        if end == SequenceEnd::FivePrime {
+            self.cursor_col += shortfall;
+        }
+
+        let fill = if self.sequence_type == SequenceType::Protein {
+            'X'
+        } else {
+            'N'
+        };
+        for &row in &rows {
+            let seq = Rc::make_mut(&mut self.alignment.sequences[row]);
+            let Some((first, last)) = seq.residue_span(&self.gap_chars) else {
+                continue;
+            };
+            let (cols, delta5, delta3) = match end {
+                SequenceEnd::FivePrime => (first - n..first, n as isize, 0),
+                SequenceEnd::ThreePrime => (last + 1..last + 1 + n, 0, n as isize),
+            };
+            for col in cols {
+                seq.set(col, fill);
+            }
+            if let Some(new_id) = adjust_coords(&seq.id, delta5, delta3) {
+                self.alignment.rename_sequence(row, &new_id);
+            }
+        }
+
+        self.mark_modified();
+        self.update_structure_cache();
+        self.set_status(format!(
+            "Padded {} sequence(s) by {n} at {} end",
+            rows.len(),
+            end_label(end)
+        ));
+    }
+
+    /// Remove `n` residues from one end of the current (or selected) sequences.
+    ///
+    /// Removed residues become end gaps and ID coordinates are shrunk to match.
+    pub fn truncate_sequences(&mut self, end: SequenceEnd, n: usize) {
+        let rows = self.target_rows();
+        if n == 0 || rows.is_empty() {
+            return;
+        }
+        for &row in &rows {
+            let seq = &self.alignment.sequences[row];
+            let residues = seq
+                .chars()
+                .iter()
+                .filter(|c| !self.gap_chars.contains(c))
+                .count();
+            if n >= residues {
+                self.set_error(format!(
+                    "Cannot truncate {}: only {residues} residue(s)",
+                    seq.id
+                ));
+                return;
+            }
+        }
+
+        if !self.begin_edit() {
+            return;
+        }
+
+        for &row in &rows {
+            let seq = Rc::make_mut(&mut self.alignment.sequences[row]);
+            let len = seq.len();
+            let positions: Vec<usize> = match end {
+                SequenceEnd::FivePrime => (0..len).collect(),
+                SequenceEnd::ThreePrime => (0..len).rev().collect(),
+            };
+
+            // Blank everything up to and including the n-th residue from this end
+            let mut removed = 0;
+            let mut blanked = Vec::new();
+            for col in positions {
+                if removed == n {
+                    break;
+                }
+                if seq.get(col).is_some_and(|c| !self.gap_chars.contains(&c)) {
+                    removed += 1;
+                }
+                seq.set(col, self.end_gap_char);
+                blanked.push(col);
+            }
+
+            // Also blank associated #=GR annotations
+            if let Some(annotations) = self.alignment.residue_annotations.get_mut(&seq.id) {
+                for ann in annotations {
+                    let mut data: Vec<char> = ann.data.chars().collect();
+                    for &col in &blanked {
+                        if let Some(ch) = data.get_mut(col) {
+                            *ch = self.gap_char;
+                        }
+                    }
+                    ann.data = data.into_iter().collect();
+                }
+            }
+
+            let (delta5, delta3) = match end {
+                SequenceEnd::FivePrime => (-(n as isize), 0),
+                SequenceEnd::ThreePrime => (0, -(n as isize)),
+            };
+            if let Some(new_id) = adjust_coords(&seq.id, delta5, delta3) {
+                self.alignment.rename_sequence(row, &new_id);
+            }
+        }
+
+        self.mark_modified();
+        self.set_status(format!(
+            "Truncated {} sequence(s) by {n} at {} end",
+            rows.len(),
+            end_label(end)
+        ));
+    }
+
+    /// Rewrite `/start-end` ID coordinates (`:fixcoords`) to match the residues:
+    /// from each sequence's start and ungapped length, or from `path`, a FASTA file
+    /// of the full-length source sequences or a table of `id start end` lines.
+    /// Annotations move to the new IDs.
+    pub fn fix_coords(&mut self, path: Option<&Path>) {
+        let source = match path {
+            None => CoordSource::Residues,
+            Some(path) => match CoordSource::read(path) {
+                Ok(source) => source,
+                Err(e) => {
+                    self.set_error(format!("Failed to read {}: {e}", path.display()));
+                    return;
+                }
+            },
+        };
+
+        let mut renames: Vec<(usize, String)> = Vec::new();
+        let mut unresolved: Vec<String> = Vec::new();
+        for (row, seq) in self.alignment.sequences.iter().enumerate() {
+            if matches!(source, CoordSource::Residues) && parse_coords(&seq.id).is_none() {
+                continue;
+            }
+            let residues: String = seq
+                .chars()
+                .iter()
+                .filter(|c| !self.gap_chars.contains(c))
+                .collect();
+            match source.coords(&seq.id, &residues) {
+                Some((start, end)) => {
+                    let new_id = set_coords(&seq.id, start, end);
+                    if new_id != seq.id {
+                        renames.push((row, new_id));
+                    }
+                }
+                None => unresolved.push(seq.id.clone()),
+            }
+        }
+        // Two sequences may not end up with the same ID
+        let mut new_ids: HashMap<&str, usize> = HashMap::new();
+        for (row, id) in &renames {
+            if let Some(&first) = new_ids.get(id.as_str()) {
+                let seqs = &self.alignment.sequences;
+                self.set_error(format!(
+                    "Cannot fix coordinates: {} and {} would both become {id}",
+                    seqs[first].id, seqs[*row].id
+                ));
+                return;
+            }
+            new_ids.insert(id, *row);
+        }
+        // A new ID may not take the place of another sequence's
+        let (renames, clashes): (Vec<_>, Vec<_>) = renames.into_iter().partition(|(row, id)| {
+            !self
+                .alignment
+                .sequences
+                .iter()
+                .enumerate()
+                .any(|(other, seq)| other != *row && seq.id == *id)
+        });
+        unresolved.extend(clashes.into_iter().map(|(row, id)| {
+            format!("{} ({id} already exists)", self.alignment.sequences[row].id)
+        }));
+
+        if renames.is_empty() {
+            let message = if unresolved.is_empty() {
+                "All /start-end coordinates already match".to_string()
+            } else {
+                format!(
+                    "No coordinates changed; {} sequences not found in the source",
+                    unresolved.len()
+                )
+            };
+            self.set_status(message);
+            return;
+        }
+
+        if !self.begin_edit() {
+            return;
+        }
+        let mut lines = vec![
+            format!("Rewrote the coordinates of {} sequences", renames.len()),
+            String::new(),
+        ];
+        for (row, new_id) in &renames {
+            lines.push(format!("{} -> {new_id}", self.alignment.sequences[*row].id));
+            self.alignment.rename_sequence(*row, new_id);
+        }
+        if !unresolved.is_empty() {
+            lines.push(String::new());
+            lines.push("Left unchanged (not found in the source):".to_string());
+            lines.extend(unresolved.iter().cloned());
+        }
+        self.report = Some(Report {
+            title: "Fixed coordinates".to_string(),
+            lines,
+        });
+        self.mark_modified();
+        self.set_status(format!(
+            "Fixed the coordinates of {} sequences ({} left unchanged)",
+            renames.len(),
+            unresolved.len()
+        ));
+    }
+
+    /// Sort the sequences by their residue in the cursor column (`:sortcol`): A, C,
+    /// G and U/T first, then other residues alphabetically, then gaps. The sort is
+    /// stable, so each group keeps its order, and the cursor stays on its sequence.
+    pub fn sort_by_column(&mut self) {
+        let col = self.cursor_col;
+        let group = |c: Option<char>| match c.map(|c| c.to_ascii_uppercase()) {
+            Some(c) if self.gap_chars.contains(&c) => (5, '-'),
+            None => (5, '-'),
+            Some('A') => (0, 'A'),
+            Some('C') => (1, 'C'),
+            Some('G') => (2, 'G'),
+            Some(c @ ('U' | 'T')) => (3, c),
+            Some(c) => (4, c),
+        };
+        let keys: Vec<(u8, char)> = self
+            .alignment
+            .sequences
+            .iter()
+            .map(|seq| group(seq.get(col)))
+            .collect();
+        let mut order: Vec<usize> = (0..keys.len()).collect();
+        order.sort_by_key(|&row| keys[row]);
+
+        // Residue counts in sorted order, e.g. "A 12, G 40, gap 2"
+        let mut counts: Vec<((u8, char), usize)> = Vec::new();
+        for &row in &order {
+            match counts.last_mut() {
+                Some((key, count)) if *key == keys[row] => *count += 1,
+                _ => counts.push((keys[row], 1)),
+            }
+        }
+        let summary = counts
+            .iter()
+            .map(|&((rank, c), count)| {
+                if rank == 5 {
+                    format!("gap {count}")
+                } else {
+                    format!("{c} {count}")
+                }
+            })
+            .collect::<Vec<_>>()
+            .join(", ");
+        if order.iter().enumerate().all(|(i, &row)| i == row) {
+            self.set_status(format!("Already sorted by column {}: {summary}", col + 1));
+            return;
+        }
+        if self.apply_sequence_order(&order) {
+            self.set_status(format!(
+                "Sorted by column {}: {summary} (u restores the order)",
+                col + 1
+            ));
+        }
+    }
+
+    /// Sort the sequences by `key` (`:sort name|length|gaps|identity [asc|desc]`),
+    /// with their annotations. Ties keep their order and the cursor stays on its
+    /// sequence.
+    pub fn sort_sequences(&mut self, key: SortKey, descending: bool) {
+        let sequences = &self.alignment.sequences;
+        let width = self.alignment.width().max(1);
+        let gaps = |chars: &[char]| chars.iter().filter(|c| self.gap_chars.contains(c)).count();
+        let (by, values): (String, Vec<f64>) = match key {
+            SortKey::Name => ("name".to_string(), Vec::new()),
+            SortKey::Length => (
+                "length".to_string(),
+                sequences
+                    .iter()
+                    .map(|s| (s.len() - gaps(s.chars())) as f64)
+                    .collect(),
+            ),
+            SortKey::Gaps => (
+                "gap fraction".to_string(),
+                sequences
+                    .iter()
+                    .map(|s| {
+                        (gaps(s.chars()) + width.saturating_sub(s.len())) as f64 / width as f64
+                    })
+                    .collect(),
+            ),
+            SortKey::Identity => {
+                let (name, reference): (String, Vec<char>) = if self.compare_to_consensus {
+                    let consensus = (0..self.alignment.width())
+                        .map(|col| get_consensus_char(col, &self.alignment, &self.gap_chars))
+                        .collect();
+                    ("the consensus".to_string(), consensus)
+                } else {
+                    let Some(reference) = sequences.get(self.reference_seq) else {
+                        return;
+                    };
+                    (reference.id.clone(), reference.chars().to_vec())
+                };
+                (
+                    format!("identity to {name}"),
+                    sequences
+                        .iter()
+                        .map(|s| sequence_identity(s.chars(), &reference, &self.gap_chars))
+                        .collect(),
+                )
+            }
+        };
+        let mut order: Vec<usize> = (0..sequences.len()).collect();
+        let name = |row: &usize| sequences[*row].id.to_lowercase();
+        match key {
+            SortKey::Name if descending => order.sort_by_cached_key(|row| Reverse(name(row))),
+            SortKey::Name => order.sort_by_cached_key(name),
+            _ => order.sort_by(|&a, &b| {
+                let ordering = values[a].total_cmp(&values[b]);
+                if descending {
+                    ordering.reverse()
+                } else {
+                    ordering
+                }
+            }),
+        }
+
+        let direction = if descending { "desc" } else { "asc" };
+        if order.iter().enumerate().all(|(i, &row)| i == row) {
+            self.set_status(format!("Already sorted by {by} ({direction})"));
+            return;
+        }
+        if self.apply_sequence_order(&order) {
+            self.set_status(format!(
+                "Sorted {} sequences by {by} ({direction}; u restores the order)",
+                order.len()
+            ));
+        }
+    }
+
+    /// Order the sequences as the leaves of a Newick tree (`:sort tree [file]`):
+    /// the alignment's `#=GF NH` tree, or the first tree in `path`. Sequences
+    /// not in the tree go last, in their current order.
+    pub fn sort_by_tree(&mut self, path: Option<&Path>) {
+        let (source, text) = match path {
+            Some(path) => match std::fs::read_to_string(path) {
+                Ok(text) => (path.display().to_string(), text),
+                Err(e) => {
+                    self.set_error(format!("Cannot read {}: {e}", path.display()));
+                    return;
+                }
+            },
+            None => {
+                let lines = self.alignment.get_file_annotations("NH");
+                if lines.is_empty() {
+                    self.set_status("No #=GF NH tree (:sort tree <file> reads a Newick file)");
+                    return;
+                }
+                ("#=GF NH".to_string(), lines.concat())
+            }
+        };
+        let leaves = match leaf_order(&text) {
+            Ok(leaves) => leaves,
+            Err(e) => {
+                self.set_error(format!("Cannot read the tree in {source}: {e}"));
+                return;
+            }
+        };
+        let ids: Vec<&str> = self
+            .alignment
+            .sequences
+            .iter()
+            .map(|s| s.id.as_str())
+            .collect();
+        let (order, missing) = tree_order(&ids, &leaves);
+        if missing == ids.len() {
+            self.set_error(format!("No sequence IDs match the leaves of {source}"));
+            return;
+        }
+        let note = match missing {
+            0 => String::new(),
+            n => format!(", {n} not in the tree left at the end"),
+        };
+        if order.iter().enumerate().all(|(i, &row)| i == row) {
+            self.set_status(format!("Already in the order of {source}{note}"));
+            return;
+        }
+        if self.apply_sequence_order(&order) {
+            self.set_status(format!(
+                "Sorted {} sequences by {source}{note} (u restores the order)",
+                order.len()
+            ));
+        }
+    }
+
+    /// Put the sequences in `order` (old row indices, new order first) as one
+    /// undo step, leaving cluster order and keeping the cursor on its sequence.
+    fn apply_sequence_order(&mut self, order: &[usize]) -> bool {
+        let current = self.display_to_actual_row(self.cursor_row);
+        if !self.begin_edit() {
+            return false;
+        }
+        if self.cluster_order.is_some() {
+            self.uncluster();
+        }
+        self.alignment.reorder_sequences(order);
+        let new_row = |row: usize| order.iter().position(|&r| r == row).unwrap_or(row);
+        self.reference_seq = new_row(self.reference_seq);
+        self.precompute_collapse_groups();
+        self.refresh_row_filter();
+        if let Some(display_row) = self.actual_to_display_row(new_row(current)) {
+            self.cursor_row = display_row;
+        }
+        self.mark_modified();
+        self.clamp_cursor();
+        true
+    }
+
+    /// Flag the current sequence with a curation label (stored as `#=GS <id> FL <label>`).
+    pub fn flag_sequence(&mut self, label: &str) {
+        let actual_row = self.display_to_actual_row(self.cursor_row);
+        let Some(seq_id) = self
+            .alignment
+            .sequences
+            .get(actual_row)
+            .map(|s| s.id.clone())
+        else {
+            return;
+        };
+
+        if !self.begin_edit() {
+            return;
+        }
+        self.alignment.set_sequence_flag(&seq_id, label);
+        self.mark_modified();
+        self.refresh_row_filter();
+        self.set_status(format!("Flagged {seq_id}: {label}"));
+    }
+
+    /// Remove the curation flag from the current sequence.
+    pub fn unflag_sequence(&mut self) {
+        let actual_row = self.display_to_actual_row(self.cursor_row);
+        let Some(seq_id) = self
+            .alignment
+            .sequences
+            .get(actual_row)
+            .map(|s| s.id.clone())
+        else {
+            return;
+        };
+
+        if self.alignment.sequence_flag(&seq_id).is_none() {
+            self.set_status("Sequence is not flagged");
+            return;
+        }
+
+        if !self.begin_edit() {
+            return;
+        }
+        self.alignment.clear_sequence_flag(&seq_id);
+        self.mark_modified();
+        self.refresh_row_filter();
+        self.set_status(format!("Unflagged {seq_id}"));
+    }
+
+    /// Set the numeric track at the cursor column (None clears it to `.`).
+    pub fn set_track_value(&mut self, level: Option<u8>) {
+        let Some((tag, data)) = self.track_row() else {
+            self.set_error("No track shown (use :track <tag>)");
+            return;
+        };
+        let ch = level.map_or('.', track_char);
+        if data.chars().nth(self.cursor_col) == Some(ch) {
+            return;
         }
+        let tag = tag.to_string();
 
-        self.set_status(format!("Deleted {count} sequence(s)"));
+        if !self.begin_edit() {
+            return;
+        }
+        self.alignment
+            .set_column_annotation_char(&tag, self.cursor_col, ch);
+        self.mark_modified();
+        self.set_status(format!("{tag} column {}: {ch}", self.cursor_col + 1));
+    }
+
+    /// Raise or lower the numeric track at the cursor column by `delta`, within
+    /// 0 and `*`. A column with no value starts from 0.
+    pub fn adjust_track_value(&mut self, delta: i8) {
+        let level = match self.track_value(self.cursor_col) {
+            Some(level) => level.saturating_add_signed(delta).min(TRACK_MAX),
+            None => 0,
+        };
+        self.set_track_value(Some(level));
     }
 
     /// Convert alignment to uppercase.
     pub fn uppercase_alignment(&mut self) {
-        self.save_undo_state();
+        if self.selection_anchor.is_some() {
+            self.convert_selection(|c| c.to_ascii_uppercase(), "Converted to uppercase");
+            return;
+        }
+        if !self.begin_edit() {
+            return;
+        }
         for seq in &mut self.alignment.sequences {
             Rc::make_mut(seq).make_uppercase();
         }
         self.mark_modified();
+        self.set_status("Converted to uppercase");
     }
 
     /// Convert alignment to lowercase.
     pub fn lowercase_alignment(&mut self) {
-        self.save_undo_state();
+        if self.selection_anchor.is_some() {
+            self.convert_selection(|c| c.to_ascii_lowercase(), "Converted to lowercase");
+            return;
+        }
+        if !self.begin_edit() {
+            return;
+        }
         for seq in &mut self.alignment.sequences {
             Rc::make_mut(seq).make_lowercase();
         }
         self.mark_modified();
+        self.set_status("Converted to lowercase");
     }
 
-    /// Convert T to U in all sequences.
-    pub fn convert_t_to_u(&mut self) {
-        self.save_undo_state();
-        for seq in &mut self.alignment.sequences {
-            let seq = Rc::make_mut(seq);
-            seq.replace_char('T', 'U');
-            seq.replace_char('t', 'u');
+    /// Apply `convert` to every character of the visual selection (or of one
+    /// carried into command mode), leaving the visual mode.
+    fn convert_selection(&mut self, convert: impl Fn(char) -> char, done: &str) {
+        let Some((_, min_col, _, max_col)) = self.get_selection_bounds() else {
+            return;
+        };
+        let rows = self.target_rows();
+        self.exit_visual_mode();
+        if !self.begin_edit() {
+            return;
         }
+        for &row in &rows {
+            let chars = Rc::make_mut(&mut self.alignment.sequences[row]).chars_mut();
+            let end = (max_col + 1).min(chars.len());
+            for ch in &mut chars[min_col.min(end)..end] {
+                *ch = convert(*ch);
+            }
+        }
+        self.mark_modified();
+        self.set_status(format!(
+            "{done} in {} sequences x columns {}-{}",
+            rows.len(),
+            min_col + 1,
+            max_col + 1
+        ));
+    }
+
+    /// Mask residues whose #=GR PP is below `threshold`, as `:set mask=` says.
+    pub fn mask_low_pp(&mut self, threshold: u8) {
+        let has_pp = self
+            .alignment
+            .residue_annotations
+            .values()
+            .flatten()
+            .any(|ann| ann.tag == "PP");
+        if !has_pp {
+            self.set_status("No #=GR PP annotations to mask by");
+            return;
+        }
+        let (style, gap_char, gap_chars) = (self.mask_style, self.gap_char, &self.gap_chars);
+        let mut masked = self.alignment.clone();
+        // A visual selection carried into command mode limits masking to its block
+        let (count, scope) = match self.get_selection_bounds() {
+            Some((_, min_col, _, max_col)) => {
+                let rows = self.target_rows();
+                let cols = min_col..max_col + 1;
+                let count =
+                    masked.mask_low_pp_region(&rows, cols, threshold, style, gap_char, gap_chars);
+                (count, " in the selection")
+            }
+            None => (
+                masked.mask_low_pp(threshold, style, gap_char, gap_chars),
+                "",
+            ),
+        };
+        let action = match self.mask_style {
+            MaskStyle::Lowercase => "Lowercased",
+            MaskStyle::Gap => "Gapped",
+        };
+        if count == 0 {
+            self.set_status(format!(
+                "No residues to mask with PP below {threshold}{scope}"
+            ));
+            return;
+        }
+        if !self.begin_edit() {
+            return;
+        }
+        self.alignment = masked;
         self.mark_modified();
+        self.set_status(format!(
+            "{action} {count} residues with PP below {threshold}{scope} (:set mask=lower|gap)"
+        ));
+    }
+
+    /// Convert T to U in all sequences (or the selection), keeping case.
+    pub fn convert_t_to_u(&mut self) {
+        self.convert_base('T', 'U');
     }
 
-    /// Convert U to T in all sequences.
+    /// Convert U to T in all sequences (or the selection), keeping case.
     pub fn convert_u_to_t(&mut self) {
-        self.save_undo_state();
-        for seq in &mut self.alignment.sequences {
-            let seq = Rc::make_mut(seq);
-            seq.replace_char('U', 'T');
-            seq.replace_char('u', 't');
+        self.convert_base('U', 'T');
+    }
+
+    /// `:t2u`/`:u2t`: a `:%s/from/to/gi`, or `:s` on a selection.
+    fn convert_base(&mut self, from: char, to: char) {
+        let sub = Substitution {
+            from: vec![from],
+            to: vec![to],
+            global: true,
+            confirm: false,
+            ignore_case: true,
+        };
+        let all = self.selection_anchor.is_none();
+        let (matches, scope) = self.substitution_targets(&sub, all);
+        self.exit_visual_mode();
+        if matches.is_empty() {
+            self.set_status(format!("No {from} to convert{scope}"));
+            return;
+        }
+        self.apply_substitution(&sub, &matches, &format!("Converted {from} to {to}{scope}"));
+    }
+
+    /// `:s/from/to/flags`: replace text in the current sequence, in a visual
+    /// selection carried into command mode, or with `all` (`:%s`) everywhere.
+    pub fn substitute(&mut self, sub: Substitution, all: bool) {
+        let (matches, scope) = self.substitution_targets(&sub, all);
+        self.exit_visual_mode();
+        let from: String = sub.from.iter().collect();
+        if matches.is_empty() {
+            self.set_error(format!("Pattern not found: {from}{scope}"));
+            return;
+        }
+        if !self.check_writable() {
+            return;
+        }
+        if sub.confirm {
+            self.substitute_prompt = Some(SubstitutePrompt {
+                sub,
+                pending: matches,
+                accepted: Vec::new(),
+            });
+            self.show_substitute_prompt();
+        } else {
+            let to: String = sub.to.iter().collect();
+            self.apply_substitution(&sub, &matches, &format!("Replaced {from} with {to}{scope}"));
+        }
+    }
+
+    /// Matches of a substitution as (actual row, start column), and the scope
+    /// for the status line.
+    fn substitution_targets(&self, sub: &Substitution, all: bool) -> (Vec<(usize, usize)>, String) {
+        if self.alignment.sequences.is_empty() {
+            return (Vec::new(), " (no sequences)".to_string());
+        }
+        let width = self.alignment.width();
+        let (rows, cols, scope) = match self.get_selection_bounds() {
+            Some((_, min_col, _, max_col)) if !all => (
+                self.target_rows(),
+                min_col..max_col + 1,
+                " in the selection".to_string(),
+            ),
+            _ if all => (
+                (0..self.alignment.sequences.len()).collect(),
+                0..width,
+                String::new(),
+            ),
+            _ => {
+                let row = self.display_to_actual_row(self.cursor_row);
+                let id = &self.alignment.sequences[row].id;
+                (vec![row], 0..width, format!(" in {id}"))
+            }
+        };
+        let matches = rows
+            .into_iter()
+            .flat_map(|row| {
+                sub.find(self.alignment.sequences[row].chars(), cols.clone())
+                    .into_iter()
+                    .map(move |col| (row, col))
+            })
+            .collect();
+        (matches, scope)
+    }
+
+    /// Put the cursor on the match being asked about and show the question.
+    fn show_substitute_prompt(&mut self) {
+        let Some(prompt) = &self.substitute_prompt else {
+            return;
+        };
+        let Some(&(row, col)) = prompt.pending.first() else {
+            return;
+        };
+        let seq = &self.alignment.sequences[row];
+        let found: String = seq.chars()[col..col + prompt.sub.from.len()]
+            .iter()
+            .collect();
+        let message = format!(
+            "Replace '{found}' with '{}' in {} at column {}? (y/n/a/q, {} left)",
+            prompt.sub.to.iter().collect::<String>(),
+            seq.id,
+            col + 1,
+            prompt.pending.len()
+        );
+        if let Some(display_row) = self.actual_to_display_row(row) {
+            self.cursor_row = display_row;
+        }
+        self.cursor_col = col;
+        self.set_status(message);
+    }
+
+    /// Answer the pending `:s///c` prompt: `y` replaces this match, `n` skips
+    /// it, `a` replaces it and all remaining ones, `q` stops, keeping the
+    /// replacements confirmed so far.
+    pub fn answer_substitute_prompt(&mut self, answer: char) {
+        let Some(mut prompt) = self.substitute_prompt.take() else {
+            return;
+        };
+        match answer {
+            'y' | 'n' => {
+                let target = prompt.pending.remove(0);
+                if answer == 'y' {
+                    prompt.accepted.push(target);
+                }
+            }
+            'a' => prompt.accepted.append(&mut prompt.pending),
+            'q' => prompt.pending.clear(),
+            _ => {
+                self.substitute_prompt = Some(prompt);
+                self.show_substitute_prompt();
+                return;
+            }
+        }
+
+        if !prompt.pending.is_empty() {
+            self.substitute_prompt = Some(prompt);
+            self.show_substitute_prompt();
+        } else if prompt.accepted.is_empty() {
+            self.set_status("Nothing replaced");
+        } else {
+            let from: String = prompt.sub.from.iter().collect();
+            let to: String = prompt.sub.to.iter().collect();
+            self.apply_substitution(
+                &prompt.sub,
+                &prompt.accepted,
+                &format!("Replaced {from} with {to}"),
+            );
+        }
+    }
+
+    /// Set the cursor column to `ch` in the sequences at actual `rows`, as one
+    /// undoable edit (`:colset`). End gaps are kept apart from internal ones, and
+    /// a residue added or removed at either end of a sequence moves its
+    /// `/start-end` coordinates.
+    pub fn set_column(&mut self, ch: char, rows: &[usize]) {
+        let col = self.cursor_col;
+        let changed: Vec<usize> = rows
+            .iter()
+            .copied()
+            .filter(|&row| self.alignment.get_char(row, col).is_some_and(|c| c != ch))
+            .collect();
+        if changed.is_empty() {
+            self.set_status(format!(
+                "Column {} is already {ch} in those sequences",
+                col + 1
+            ));
+            return;
+        }
+        if !self.begin_edit() {
+            return;
+        }
+        let gap = |c: char| self.gap_chars.contains(&c);
+        let mut renames = Vec::new();
+        for &row in &changed {
+            let seq = &self.alignment.sequences[row];
+            let was_gap = seq.get(col).is_some_and(gap);
+            let new_id = match (seq.residue_span(&self.gap_chars), was_gap, gap(ch)) {
+                // A residue before the first or after the last
+                (Some((first, _)), true, false) if col < first => adjust_coords(&seq.id, 1, 0),
+                (Some((_, last)), true, false) if col > last => adjust_coords(&seq.id, 0, 1),
+                // The first or last residue gapped
+                (Some((first, last)), false, true) if first != last && col == first => {
+                    adjust_coords(&seq.id, -1, 0)
+                }
+                (Some((first, last)), false, true) if first != last && col == last => {
+                    adjust_coords(&seq.id, 0, -1)
+                }
+                _ => None,
+            };
+            let seq = Rc::make_mut(&mut self.alignment.sequences[row]);
+            seq.set(col, ch);
+            if let Some((first, last)) = seq.residue_span(&self.gap_chars) {
+                for pos in (col..first).chain((last + 1)..=col) {
+                    seq.fix_end_gaps(pos, self.gap_char, self.end_gap_char, &self.gap_chars);
+                }
+            }
+            renames.extend(new_id.map(|id| (row, id)));
+        }
+        for (row, id) in renames {
+            self.alignment.rename_sequence(row, &id);
+        }
+        self.mark_modified();
+        self.set_status(format!(
+            "Set column {} to {ch} in {} of {} sequences",
+            col + 1,
+            changed.len(),
+            rows.len()
+        ));
+    }
+
+    /// Replace the given matches as one undoable edit.
+    fn apply_substitution(&mut self, sub: &Substitution, matches: &[(usize, usize)], done: &str) {
+        if !self.begin_edit() {
+            return;
+        }
+        for &(row, col) in matches {
+            sub.replace(
+                Rc::make_mut(&mut self.alignment.sequences[row]).chars_mut(),
+                col,
+            );
         }
+        let mut rows: Vec<usize> = matches.iter().map(|&(row, _)| row).collect();
+        rows.dedup();
         self.mark_modified();
+        self.set_status(format!(
+            "{done}: {} matches in {} sequences",
+            matches.len(),
+            rows.len()
+        ));
     }
 
     /// Trim leading gap-only columns from the alignment.
     pub fn trim_left(&mut self) {
-        self.save_undo_state();
+        if !self.begin_edit() {
+            return;
+        }
         let removed = self.alignment.trim_left(&self.gap_chars);
+        self.columns_removed(&(0..removed).collect::<Vec<_>>());
         if removed > 0 {
             self.mark_modified();
             self.clamp_cursor();
@@ -351,8 +2398,12 @@ impl App {
 
     /// Trim trailing gap-only columns from the alignment.
     pub fn trim_right(&mut self) {
-        self.save_undo_state();
+        if !self.begin_edit() {
+            return;
+        }
+        let width = self.alignment.width();
         let removed = self.alignment.trim_right(&self.gap_chars);
+        self.columns_removed(&(width - removed..width).collect::<Vec<_>>());
         if removed > 0 {
             self.mark_modified();
             self.clamp_cursor();
@@ -363,11 +2414,21 @@ impl App {
         }
     }
 
-    /// Trim both leading and trailing gap-only columns.
+    /// Trim both leading and trailing gap-only columns, or with a visual
+    /// selection carried into command mode, every column outside it.
     pub fn trim(&mut self) {
-        self.save_undo_state();
+        if self.selection_anchor.is_some() {
+            self.trim_to_selection();
+            return;
+        }
+        if !self.begin_edit() {
+            return;
+        }
+        let width = self.alignment.width();
         let left = self.alignment.trim_left(&self.gap_chars);
         let right = self.alignment.trim_right(&self.gap_chars);
+        let removed: Vec<usize> = (0..left).chain(width - right..width).collect();
+        self.columns_removed(&removed);
         let total = left + right;
         if total > 0 {
             self.mark_modified();
@@ -380,4 +2441,475 @@ impl App {
             self.set_status("No gap-only columns to trim");
         }
     }
+
+    /// Keep only the columns of the visual selection, unpairing SS_cons bases
+    /// whose partner is cut away.
+    fn trim_to_selection(&mut self) {
+        let Some((_, min_col, _, max_col)) = self.get_selection_bounds() else {
+            return;
+        };
+        let width = self.alignment.width();
+        let removed: Vec<usize> = (0..min_col).chain(max_col + 1..width).collect();
+        if removed.is_empty() {
+            self.set_status("Selection already spans every column");
+            return;
+        }
+        if !self.begin_edit() {
+            return;
+        }
+        for col in self.cut_pairs(min_col, max_col) {
+            self.alignment
+                .set_column_annotation_char("SS_cons", col, '.');
+        }
+        self.alignment.remove_columns(&removed);
+        self.columns_removed(&removed);
+        self.cursor_col = self.cursor_col.saturating_sub(min_col);
+        self.mark_modified();
+        self.clamp_cursor();
+        self.update_structure_cache();
+        self.set_status(format!(
+            "Trimmed to columns {}-{} ({} columns removed)",
+            min_col + 1,
+            max_col + 1,
+            removed.len()
+        ));
+    }
+
+    /// Remove, or mask with `:set mask`, the columns where more than `threshold`
+    /// of the sequences have a gap (`:filter-cols gaps>0.8`). SS_cons bases
+    /// paired to a removed column are unpaired.
+    pub fn filter_gappy_columns(&mut self, threshold: f64, mask: bool) {
+        let cols = self.alignment.gappy_columns(threshold, &self.gap_chars);
+        let width = self.alignment.width();
+        let percent = (threshold * 1000.0).round() / 10.0;
+        if cols.is_empty() {
+            self.set_status(format!("No columns with more than {percent}% gaps"));
+            return;
+        }
+        if mask {
+            let mut masked = self.alignment.clone();
+            let count = masked.mask_columns(&cols, self.mask_style, self.gap_char, &self.gap_chars);
+            if count == 0 {
+                self.set_status(format!(
+                    "No residues to mask in the {} columns with more than {percent}% gaps",
+                    cols.len()
+                ));
+                return;
+            }
+            if !self.begin_edit() {
+                return;
+            }
+            self.alignment = masked;
+            self.mark_modified();
+            let action = match self.mask_style {
+                MaskStyle::Lowercase => "Lowercased",
+                MaskStyle::Gap => "Gapped",
+            };
+            self.set_status(format!(
+                "{action} {count} residues in {} of {width} columns with more than {percent}% gaps",
+                cols.len()
+            ));
+            return;
+        }
+        if !self.begin_edit() {
+            return;
+        }
+        let removed: HashSet<usize> = cols.iter().copied().collect();
+        for &col in &cols {
+            if let Some(partner) = self.structure_cache.get_pair(col)
+                && !removed.contains(&partner)
+            {
+                self.alignment
+                    .set_column_annotation_char("SS_cons", partner, '.');
+            }
+        }
+        self.alignment.remove_columns(&cols);
+        self.columns_removed(&cols);
+        self.cursor_col -= cols.iter().filter(|&&col| col < self.cursor_col).count();
+        self.mark_modified();
+        self.clamp_cursor();
+        self.update_structure_cache();
+        self.set_status(format!(
+            "Removed {} of {width} columns with more than {percent}% gaps",
+            cols.len()
+        ));
+    }
+}
+
+/// Pad or trim the end of a `#=GR` line to follow its sequence.
+fn adjust_annotation_end(data: &mut String, delta: isize, gap_char: char) {
+    if delta > 0 {
+        data.extend(std::iter::repeat_n(gap_char, delta as usize));
+    } else {
+        for _ in 0..delta.unsigned_abs() {
+            data.pop();
+        }
+    }
+}
+
+/// Short label for a sequence end in status messages.
+fn end_label(end: SequenceEnd) -> &'static str {
+    match end {
+        SequenceEnd::FivePrime => "5'",
+        SequenceEnd::ThreePrime => "3'",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::app::{App, ColorFocus};
+    use crate::testing::{app_with, rows};
+
+    #[test]
+    fn test_mergecol_empties_the_sparser_column() {
+        let mut app = app_with("# STOCKHOLM 1.0\nseq1 AC-U\nseq2 A-GU\nseq3 A-GU\n//\n");
+        app.cursor_col = 1;
+        app.run_command("mergecol! right").unwrap();
+        assert_eq!(rows(&app), ["ACU", "AGU", "AGU"]);
+        assert_eq!(app.cursor_col, 1);
+        app.undo();
+        assert_eq!(rows(&app), ["AC-U", "A-GU", "A-GU"]);
+
+        // A row with residues in both columns keeps the column
+        let mut app = app_with("# STOCKHOLM 1.0\nseq1 AC-U\nseq2 A-GU\nseq3 A-GU\nseq4 ACGU\n//\n");
+        app.cursor_col = 1;
+        app.run_command("mergecol! right").unwrap();
+        assert_eq!(rows(&app), ["A-CU", "A-GU", "A-GU", "ACGU"]);
+        assert_eq!(app.cursor_col, 2);
+        assert!(
+            app.status_message
+                .as_deref()
+                .unwrap()
+                .ends_with("1 rows occupy both columns")
+        );
+    }
+
+    #[test]
+    fn test_splitcol_asks_about_each_row() {
+        let text = "# STOCKHOLM 1.0\nseq1 ACGU\nseq2 ACGU\nseq3 ACGU\n//\n";
+        let mut app = app_with(text);
+        app.cursor_col = 1;
+        app.run_command("splitcol").unwrap();
+        assert!(app.column_prompt.is_some());
+        assert_eq!(rows(&app), ["ACGU", "ACGU", "ACGU"]);
+        for answer in ['y', 'n', 'y'] {
+            app.answer_column_prompt(answer);
+        }
+        assert!(app.column_prompt.is_none());
+        assert_eq!(rows(&app), ["A.CGU", "AC.GU", "A.CGU"]);
+        assert_eq!(app.cursor_col, 2);
+        app.undo();
+        assert_eq!(rows(&app), ["ACGU", "ACGU", "ACGU"]);
+
+        // q drops the whole edit
+        app.cursor_col = 1;
+        app.run_command("splitcol left").unwrap();
+        app.answer_column_prompt('y');
+        app.answer_column_prompt('q');
+        assert_eq!(rows(&app), ["ACGU", "ACGU", "ACGU"]);
+        assert!(!app.history.can_undo());
+    }
+
+    #[test]
+    fn test_deleting_a_residue_asks_first() {
+        let text = "# STOCKHOLM 1.0\nseq1/1-4 ACGU\nseq2 AC-U\n#=GR seq1/1-4 PP 9876\n//\n";
+        let mut app = app_with(text);
+        assert!(!app.delete_gap());
+        assert!(app.confirm.is_some());
+        app.answer_confirm(false);
+        assert_eq!(rows(&app), ["ACGU", "AC-U"]);
+        assert!(!app.history.can_undo());
+
+        // The first residue goes, and the coordinates and #=GR row follow it
+        app.delete_gap();
+        app.answer_confirm(true);
+        assert_eq!(rows(&app), ["CGU~", "AC-U"]);
+        assert_eq!(app.alignment.sequences[0].id, "seq1/2-4");
+        assert_eq!(
+            app.alignment.residue_annotations["seq1/2-4"][0].data,
+            "876."
+        );
+        app.undo();
+        assert_eq!(rows(&app), ["ACGU", "AC-U"]);
+        assert_eq!(app.alignment.sequences[0].id, "seq1/1-4");
+
+        app.run_command("set force-delete=on").unwrap();
+        app.cursor_row = 1;
+        app.cursor_col = 1;
+        app.delete_gap();
+        assert!(app.confirm.is_none());
+        assert_eq!(rows(&app), ["ACGU", "A-U~"]);
+    }
+
+    /// The sequence IDs, top to bottom.
+    fn ids(app: &App) -> Vec<&str> {
+        app.alignment
+            .sequences
+            .iter()
+            .map(|s| s.id.as_str())
+            .collect()
+    }
+
+    #[test]
+    fn test_dedup_removes_redundant_sequences() {
+        let mut app = app_with(
+            "# STOCKHOLM 1.0\n#=GS b AC X1\n\
+             a ACGU-ACGU\nb acgua-cgu\nc -CGU-ACG-\nd UUUUUUUUU\nb2 ACGUACGU-\n//\n",
+        );
+        app.run_command("dedup").unwrap();
+        assert_eq!(ids(&app), ["a", "c", "d"]);
+        assert!(!app.alignment.sequence_annotations.contains_key("b"));
+        let report = app.report.take().unwrap();
+        assert_eq!(report.title, "Removed 2 of 5 sequences");
+        assert!(report.lines[0].starts_with("b ") && report.lines[0].ends_with("duplicate of a"));
+        app.undo();
+        assert_eq!(ids(&app), ["a", "b", "c", "d", "b2"]);
+        assert!(app.alignment.sequence_annotations.contains_key("b"));
+
+        // c is a fragment of a, which the longest-first pass keeps
+        app.run_command("dedup 90").unwrap();
+        assert_eq!(ids(&app), ["a", "d"]);
+        let report = app.report.take().unwrap();
+        assert!(report.lines[1].ends_with("100.0% identical to a"));
+        app.undo();
+        assert_eq!(app.alignment.num_sequences(), 5);
+    }
+
+    #[test]
+    fn test_move_sequence() {
+        let text = "# STOCKHOLM 1.0\nseq1 AAAA\nseq2 CCCC\nseq3 GGGG\nseq4 UUUU\n//\n";
+        let mut app = app_with(text);
+        app.reference_seq = 2;
+        app.run_command("move 4").unwrap();
+        assert_eq!(ids(&app), ["seq2", "seq3", "seq4", "seq1"]);
+        assert_eq!(app.cursor_row, 3);
+        // The reference stays on its sequence
+        assert_eq!(app.reference_seq, 1);
+
+        // Alt-k stops at the top
+        app.move_sequence_by(10, true);
+        assert_eq!(ids(&app), ["seq1", "seq2", "seq3", "seq4"]);
+        assert_eq!(app.cursor_row, 0);
+        app.move_sequence_by(1, false);
+        assert_eq!(ids(&app), ["seq2", "seq1", "seq3", "seq4"]);
+        app.undo();
+        assert_eq!(ids(&app), ["seq1", "seq2", "seq3", "seq4"]);
+
+        assert_eq!(app.run_command("move 0"), Err("Invalid row: 0".to_string()));
+        app.run_command("move 1").unwrap();
+        assert_eq!(
+            app.status_message.as_deref(),
+            Some("seq1 is already row 1 of 4")
+        );
+    }
+
+    #[test]
+    fn test_new_sequence_undoes_in_one_step() {
+        let mut app = app_with("# STOCKHOLM 1.0\nseq1 ACGU\nseq2 AC-U\n//\n");
+        app.run_command("new-seq seq3 GGACCA").unwrap();
+        assert_eq!(rows(&app), ["ACGU~~", "GGACCA", "AC-U~~"]);
+        app.undo();
+        assert_eq!(rows(&app), ["ACGU", "AC-U"]);
+        assert_eq!(app.alignment.width(), 4);
+        assert!(!app.history.can_undo());
+    }
+
+    #[test]
+    fn test_fix_coords_refuses_duplicate_ids() {
+        let text = "# STOCKHOLM 1.0\n#=GS x/1-12 DE first\n#=GS x/1-11 DE second\n\
+                    x/1-12 ACGUACGUAC--\nx/1-11 ACGUACGUAC--\ny/5-6 AC----------\n//\n";
+        let mut app = app_with(text);
+        assert_eq!(
+            app.run_command("fixcoords"),
+            Err("Cannot fix coordinates: x/1-12 and x/1-11 would both become x/1-10".to_string())
+        );
+        let ids: Vec<&str> = app
+            .alignment
+            .sequences
+            .iter()
+            .map(|s| s.id.as_str())
+            .collect();
+        assert_eq!(ids, ["x/1-12", "x/1-11", "y/5-6"]);
+        assert!(!app.modified);
+    }
+
+    #[test]
+    fn test_substitute_without_sequences() {
+        let mut app = App::new();
+        assert_eq!(
+            app.run_command("s/A/U/"),
+            Err("Pattern not found: A (no sequences)".to_string())
+        );
+        assert!(app.run_command("%s/A/U/").is_err());
+        assert!(app.alignment.sequences.is_empty());
+    }
+
+    #[test]
+    fn test_substitute_current_sequence() {
+        let mut app = app_with("# STOCKHOLM 1.0\nseq1 ACGA\nseq2 ACGA\n//\n");
+        app.run_command("s/A/U/g").unwrap();
+        assert_eq!(rows(&app), ["UCGU", "ACGA"]);
+        app.undo();
+        assert_eq!(rows(&app), ["ACGA", "ACGA"]);
+    }
+
+    #[test]
+    fn test_colset_keeps_end_gaps_and_coordinates() {
+        let mut app = app_with("# STOCKHOLM 1.0\ns/5-8 ~~ACGU~~\nt/1-4 ~ACGU~~~\n//\n");
+        app.cursor_col = 1;
+        app.run_command("colset N").unwrap();
+        assert_eq!(rows(&app), ["~NACGU~~", "~NCGU~~~"]);
+        app.cursor_col = 5;
+        app.run_command("colset - 1").unwrap();
+        assert_eq!(rows(&app), ["~NACG~~~", "~NCGU~~~"]);
+        let ids: Vec<&str> = app
+            .alignment
+            .sequences
+            .iter()
+            .map(|s| s.id.as_str())
+            .collect();
+        assert_eq!(ids, ["s/4-7", "t/1-4"]);
+        app.undo();
+        app.undo();
+        assert_eq!(app.alignment.sequences[0].id, "s/5-8");
+    }
+
+    #[test]
+    fn test_pins_follow_inserted_and_removed_columns() {
+        let mut app = app_with("# STOCKHOLM 1.0\ns -ACG-U\nt -AC--U\n//\n");
+        app.run_command("pin 3 5 6").unwrap();
+        app.run_command("trim").unwrap();
+        assert_eq!(app.pinned_columns, [1, 3, 4]);
+        app.run_command("filter-cols gaps>0.8").unwrap();
+        assert_eq!(app.pinned_columns, [1, 3]);
+        app.cursor_col = 0;
+        app.insert_gap_column();
+        assert_eq!(app.pinned_columns, [2, 4]);
+        app.cursor_col = 2;
+        app.insert_gap_column();
+        assert_eq!(app.pinned_columns, [3, 5]);
+        app.delete_gap_column();
+        app.cursor_col = 0;
+        app.delete_gap_column();
+        assert_eq!(app.pinned_columns, [1, 3]);
+    }
+
+    #[test]
+    fn test_undo_restores_pins_and_focus() {
+        let mut app = app_with("# STOCKHOLM 1.0\ns -ACG-U\nt -AC--U\n//\n");
+        app.run_command("pin 4").unwrap();
+        app.color_focus = Some(ColorFocus::Helices(vec![5]));
+        app.run_command("trim").unwrap();
+        assert_eq!(app.pinned_columns, [2]);
+        app.undo();
+        assert_eq!(app.pinned_columns, [3]);
+        assert_eq!(app.color_focus, Some(ColorFocus::Helices(vec![5])));
+        app.redo();
+        assert_eq!(app.pinned_columns, [2]);
+        assert_eq!(app.color_focus, Some(ColorFocus::Helices(vec![4])));
+    }
+
+    /// Select display rows `rows` and columns `cols` in visual mode.
+    fn select(app: &mut App, rows: (usize, usize), cols: (usize, usize)) {
+        app.cursor_row = rows.0;
+        app.cursor_col = cols.0;
+        app.enter_visual_mode();
+        app.cursor_row = rows.1;
+        app.cursor_col = cols.1;
+    }
+
+    #[test]
+    fn test_selection_gap_columns() {
+        let mut app = app_with("# STOCKHOLM 1.0\nseq1 AC--GU\nseq2 A---GU\n//\n");
+        select(&mut app, (0, 0), (2, 3));
+        app.insert_selection_gap_columns();
+        assert_eq!(rows(&app), ["AC..--GU", "A-..--GU"]);
+        assert_eq!(app.cursor_col, 2);
+        assert!(app.selection_anchor.is_none());
+
+        // Only the gap-only columns of the range go, whichever rows are selected
+        select(&mut app, (1, 1), (1, 7));
+        app.delete_selection_gap_columns();
+        assert_eq!(rows(&app), ["ACGU", "A-GU"]);
+        app.undo();
+        assert_eq!(rows(&app), ["AC..--GU", "A-..--GU"]);
+
+        select(&mut app, (0, 0), (1, 1));
+        app.delete_selection_gap_columns();
+        assert_eq!(
+            app.status_message.as_deref(),
+            Some("No gap-only columns in columns 2-2")
+        );
+    }
+
+    #[test]
+    fn test_case_of_selection() {
+        let mut app = app_with("# STOCKHOLM 1.0\nseq1 acgu\nseq2 acgu\nseq3 acgu\n//\n");
+        select(&mut app, (0, 1), (1, 2));
+        app.uppercase_alignment();
+        assert_eq!(rows(&app), ["aCGu", "aCGu", "acgu"]);
+        assert!(app.selection_anchor.is_none());
+
+        // Carried into command mode
+        select(&mut app, (1, 2), (2, 3));
+        app.enter_command_mode();
+        app.run_command("lower").unwrap();
+        assert_eq!(rows(&app), ["aCGu", "aCgu", "acgu"]);
+        app.undo();
+        assert_eq!(rows(&app), ["aCGu", "aCGu", "acgu"]);
+    }
+
+    #[test]
+    fn test_trim_to_selection() {
+        let text = "# STOCKHOLM 1.0\nseq1 GGACAACC\nseq2 GGAUAACC\n#=GC SS_cons <<.<>.>>\n//\n";
+        let mut app = app_with(text);
+        select(&mut app, (0, 0), (2, 4));
+        app.enter_command_mode();
+        app.run_command("trim").unwrap();
+        assert_eq!(rows(&app), ["ACA", "AUA"]);
+        // The bracket at column 4 lost its partner at column 7
+        assert_eq!(app.alignment.ss_cons(), Some(".<>"));
+        assert_eq!(app.cursor_col, 2);
+        app.undo();
+        assert_eq!(rows(&app), ["GGACAACC", "GGAUAACC"]);
+
+        select(&mut app, (0, 0), (0, 7));
+        app.trim();
+        assert_eq!(
+            app.status_message.as_deref(),
+            Some("Selection already spans every column")
+        );
+    }
+
+    #[test]
+    fn test_sort_sequences() {
+        let text = "# STOCKHOLM 1.0\nb ACGUAC\nC ACG---\na ACGUAG\nD A-----\n//\n";
+        let mut app = app_with(text);
+        app.cursor_row = 1;
+        app.run_command("sort name").unwrap();
+        assert_eq!(ids(&app), ["a", "b", "C", "D"]);
+        // The cursor stays on its sequence
+        assert_eq!(app.cursor_row, 2);
+        app.run_command("sort name desc").unwrap();
+        assert_eq!(ids(&app), ["D", "C", "b", "a"]);
+        app.run_command("sort name desc").unwrap();
+        assert_eq!(
+            app.status_message.as_deref(),
+            Some("Already sorted by name (desc)")
+        );
+
+        // Ties keep their order
+        app.run_command("sort length").unwrap();
+        assert_eq!(ids(&app), ["b", "a", "C", "D"]);
+        app.run_command("sort gaps asc").unwrap();
+        assert_eq!(ids(&app), ["b", "a", "C", "D"]);
+        app.reference_seq = 1;
+        // Identity counts the columns where both have residues
+        app.run_command("sort identity").unwrap();
+        assert_eq!(ids(&app), ["a", "C", "D", "b"]);
+        app.undo();
+        assert_eq!(ids(&app), ["b", "a", "C", "D"]);
+
+        assert!(app.run_command("sort colour").is_err());
+    }
 }
@@ -1,13 +1,26 @@
 //! Undo/redo history.
 
+use std::time::{Duration, Instant};
+
+use crate::app::{ColumnState, Settings};
 use crate::stockholm::Alignment;
 
+/// Repeats of the same edit closer together than this undo as one step.
+const REPEAT_WINDOW: Duration = Duration::from_millis(700);
+
+/// Identifies a repeatable edit: its kind plus the row and column it applies to.
+pub type RepeatKey = (&'static str, usize, usize);
+
 /// A snapshot of the alignment state for undo/redo.
 #[derive(Debug, Clone)]
 pub struct Snapshot {
     pub alignment: Alignment,
+    /// Pins and `:focus`, which edits move with the columns.
+    pub columns: ColumnState,
     pub cursor_row: usize,
     pub cursor_col: usize,
+    /// Settings to restore, for a step saved by `:set` (None = an edit).
+    pub settings: Option<Settings>,
 }
 
 /// Undo/redo history manager.
@@ -19,6 +32,11 @@ pub struct History {
     redo_stack: Vec<Snapshot>,
     /// Maximum history size.
     max_size: usize,
+    /// Last repeatable edit and when it happened.
+    last_repeat: Option<(RepeatKey, Instant)>,
+    /// Inside a group of edits that undo as one step: whether its snapshot has
+    /// been saved yet.
+    group: Option<bool>,
 }
 
 impl History {
@@ -28,6 +46,8 @@ impl History {
             undo_stack: Vec::new(),
             redo_stack: Vec::new(),
             max_size: 100,
+            last_repeat: None,
+            group: None,
         }
     }
 
@@ -38,20 +58,106 @@ impl History {
             undo_stack: Vec::new(),
             redo_stack: Vec::new(),
             max_size,
+            last_repeat: None,
+            group: None,
+        }
+    }
+
+    /// Save a snapshot before a repeatable edit, unless it continues a run of the
+    /// same edit (e.g. a held key), which then undoes as a single step.
+    pub fn save_repeat(
+        &mut self,
+        alignment: &Alignment,
+        columns: &ColumnState,
+        cursor_row: usize,
+        cursor_col: usize,
+        key: RepeatKey,
+    ) {
+        let now = Instant::now();
+        let continues = self
+            .last_repeat
+            .is_some_and(|(last, at)| last == key && now.duration_since(at) < REPEAT_WINDOW);
+        if !continues {
+            self.save(alignment, columns, cursor_row, cursor_col);
         }
+        self.last_repeat = Some((key, now));
     }
 
     /// Save a snapshot before making changes.
-    pub fn save(&mut self, alignment: &Alignment, cursor_row: usize, cursor_col: usize) {
-        // Clear redo stack when making new changes
-        self.redo_stack.clear();
+    pub fn save(
+        &mut self,
+        alignment: &Alignment,
+        columns: &ColumnState,
+        cursor_row: usize,
+        cursor_col: usize,
+    ) {
+        // A group keeps only its first snapshot, so don't copy the alignment again
+        if self.group == Some(true) {
+            return;
+        }
+        self.push(Snapshot {
+            alignment: alignment.clone(),
+            columns: columns.clone(),
+            cursor_row,
+            cursor_col,
+            settings: None,
+        });
+    }
 
-        // Add snapshot to undo stack
-        self.undo_stack.push(Snapshot {
+    /// Save the settings in effect before a `:set` changes them, so that undo
+    /// restores them like an edit.
+    pub fn save_settings(
+        &mut self,
+        alignment: &Alignment,
+        columns: &ColumnState,
+        cursor_row: usize,
+        cursor_col: usize,
+        settings: Settings,
+    ) {
+        self.push(Snapshot {
             alignment: alignment.clone(),
+            columns: columns.clone(),
             cursor_row,
             cursor_col,
+            settings: Some(settings),
         });
+    }
+
+    /// Start a group of edits (e.g. `5>`) that undo as a single step: only the
+    /// first snapshot saved before `end_group` is kept.
+    pub fn begin_group(&mut self) {
+        self.group = Some(false);
+    }
+
+    /// End the group started by `begin_group`.
+    pub fn end_group(&mut self) {
+        self.group = None;
+    }
+
+    /// Whether the next undo restores settings (a `:set`) rather than an edit.
+    pub fn undoes_settings(&self) -> bool {
+        self.undo_stack.last().is_some_and(|s| s.settings.is_some())
+    }
+
+    /// Whether the next redo reapplies settings (a `:set`) rather than an edit.
+    pub fn redoes_settings(&self) -> bool {
+        self.redo_stack.last().is_some_and(|s| s.settings.is_some())
+    }
+
+    fn push(&mut self, snapshot: Snapshot) {
+        self.last_repeat = None;
+        if let Some(saved) = &mut self.group {
+            if *saved {
+                return;
+            }
+            *saved = true;
+        }
+
+        // Clear redo stack when making new changes
+        self.redo_stack.clear();
+
+        // Add snapshot to undo stack
+        self.undo_stack.push(snapshot);
 
         // Trim if exceeds max size
         while self.undo_stack.len() > self.max_size {
@@ -59,19 +165,25 @@ impl History {
         }
     }
 
-    /// Undo the last change, returning the previous state.
+    /// Undo the last change, returning the previous state. Undoing a `:set`
+    /// keeps the current `settings` for redo.
     pub fn undo(
         &mut self,
         current: &Alignment,
+        columns: &ColumnState,
+        settings: &Settings,
         cursor_row: usize,
         cursor_col: usize,
     ) -> Option<Snapshot> {
+        self.last_repeat = None;
         if let Some(snapshot) = self.undo_stack.pop() {
             // Save current state to redo stack
             self.redo_stack.push(Snapshot {
                 alignment: current.clone(),
+                columns: columns.clone(),
                 cursor_row,
                 cursor_col,
+                settings: snapshot.settings.as_ref().map(|_| settings.clone()),
             });
             Some(snapshot)
         } else {
@@ -83,15 +195,20 @@ impl History {
     pub fn redo(
         &mut self,
         current: &Alignment,
+        columns: &ColumnState,
+        settings: &Settings,
         cursor_row: usize,
         cursor_col: usize,
     ) -> Option<Snapshot> {
+        self.last_repeat = None;
         if let Some(snapshot) = self.redo_stack.pop() {
             // Save current state to undo stack
             self.undo_stack.push(Snapshot {
                 alignment: current.clone(),
+                columns: columns.clone(),
                 cursor_row,
                 cursor_col,
+                settings: snapshot.settings.as_ref().map(|_| settings.clone()),
             });
             Some(snapshot)
         } else {
@@ -113,18 +230,17 @@ impl History {
 
     /// Clear all history.
     pub fn clear(&mut self) {
+        self.last_repeat = None;
         self.undo_stack.clear();
         self.redo_stack.clear();
     }
 
     /// Get the number of undo steps available.
-    #[allow(dead_code)] // API for status display
     pub fn undo_count(&self) -> usize {
         self.undo_stack.len()
     }
 
     /// Get the number of redo steps available.
-    #[allow(dead_code)] // API for status display
     pub fn redo_count(&self) -> usize {
         self.redo_stack.len()
     }
@@ -153,27 +269,109 @@ mod tests {
         let state3 = make_alignment("ACGU..");
 
         // Save state1
-        history.save(&state1, 0, 0);
+        history.save(&state1, &ColumnState::default(), 0, 0);
 
         // Save state2
-        history.save(&state2, 0, 1);
+        history.save(&state2, &ColumnState::default(), 0, 1);
 
         // Undo to state2
-        let snapshot = history.undo(&state3, 0, 2);
+        let snapshot = history.undo(&state3, &ColumnState::default(), &Settings::default(), 0, 2);
         assert!(snapshot.is_some());
         assert_eq!(snapshot.unwrap().alignment.sequences[0].data(), "ACGU.");
 
         // Undo to state1
-        let snapshot = history.undo(&state2, 0, 1);
+        let snapshot = history.undo(&state2, &ColumnState::default(), &Settings::default(), 0, 1);
         assert!(snapshot.is_some());
         assert_eq!(snapshot.unwrap().alignment.sequences[0].data(), "ACGU");
 
         // Redo to state2
-        let snapshot = history.redo(&state1, 0, 0);
+        let snapshot = history.redo(&state1, &ColumnState::default(), &Settings::default(), 0, 0);
         assert!(snapshot.is_some());
         assert_eq!(snapshot.unwrap().alignment.sequences[0].data(), "ACGU.");
     }
 
+    #[test]
+    fn test_repeat_coalescing() {
+        let mut history = History::new();
+        let state = make_alignment("ACGU");
+
+        history.save_repeat(&state, &ColumnState::default(), 0, 1, ("shift-left", 0, 1));
+        history.save_repeat(&state, &ColumnState::default(), 0, 1, ("shift-left", 0, 1));
+        assert_eq!(history.undo_count(), 1);
+
+        // A different edit or position starts a new step
+        history.save_repeat(&state, &ColumnState::default(), 0, 1, ("shift-right", 0, 1));
+        history.save_repeat(&state, &ColumnState::default(), 1, 1, ("shift-right", 1, 1));
+        assert_eq!(history.undo_count(), 3);
+
+        // Any other change ends the run
+        history.save(&state, &ColumnState::default(), 1, 1);
+        history.save_repeat(&state, &ColumnState::default(), 1, 1, ("shift-right", 1, 1));
+        assert_eq!(history.undo_count(), 5);
+    }
+
+    #[test]
+    fn test_group() {
+        let mut history = History::new();
+        let state1 = make_alignment("ACGU");
+        let state2 = make_alignment("ACGU.");
+
+        history.begin_group();
+        history.save(&state1, &ColumnState::default(), 0, 0);
+        history.save(&state2, &ColumnState::default(), 0, 1);
+        history.end_group();
+        assert_eq!(history.undo_count(), 1);
+        let snapshot = history
+            .undo(&state2, &ColumnState::default(), &Settings::default(), 0, 1)
+            .unwrap();
+        assert_eq!(snapshot.alignment.sequences[0].data(), "ACGU");
+
+        // Saves after the group are separate steps again
+        history.save(&state1, &ColumnState::default(), 0, 0);
+        history.save(&state2, &ColumnState::default(), 0, 1);
+        assert_eq!(history.undo_count(), 2);
+    }
+
+    #[test]
+    fn test_settings_undo_redo() {
+        let mut history = History::new();
+        let state = make_alignment("ACGU");
+        let before = Settings::default();
+        let after = Settings {
+            gap_char: '-',
+            ..Settings::default()
+        };
+
+        history.save_settings(&state, &ColumnState::default(), 0, 0, before.clone());
+        let snapshot = history
+            .undo(&state, &ColumnState::default(), &after, 0, 0)
+            .unwrap();
+        assert_eq!(snapshot.settings, Some(before.clone()));
+
+        // Redo brings back the settings that were current when undoing
+        let snapshot = history
+            .redo(&state, &ColumnState::default(), &before, 0, 0)
+            .unwrap();
+        assert_eq!(snapshot.settings, Some(after));
+
+        // Edits carry no settings
+        history.save(&state, &ColumnState::default(), 0, 0);
+        assert_eq!(
+            history
+                .undo(&state, &ColumnState::default(), &before, 0, 0)
+                .unwrap()
+                .settings,
+            None
+        );
+        assert_eq!(
+            history
+                .redo(&state, &ColumnState::default(), &before, 0, 0)
+                .unwrap()
+                .settings,
+            None
+        );
+    }
+
     #[test]
     fn test_redo_cleared_on_new_change() {
         let mut history = History::new();
@@ -181,12 +379,12 @@ mod tests {
         let state1 = make_alignment("ACGU");
         let state2 = make_alignment("ACGU.");
 
-        history.save(&state1, 0, 0);
-        history.undo(&state2, 0, 1);
+        history.save(&state1, &ColumnState::default(), 0, 0);
+        history.undo(&state2, &ColumnState::default(), &Settings::default(), 0, 1);
         assert!(history.can_redo());
 
         // Make new change
-        history.save(&state2, 0, 1);
+        history.save(&state2, &ColumnState::default(), 0, 1);
         assert!(!history.can_redo());
     }
 }
@@ -2,5 +2,7 @@
 
 mod commands;
 mod history;
+mod preview;
 
 pub use history::*;
+pub use preview::describe_changes;
@@ -0,0 +1,248 @@
+//! What an edit would change, for `:<command> --preview`.
+//!
+//! The command runs on the alignment as usual and the result is compared with
+//! the alignment before it; the comparison is what the preview lists.
+
+use std::collections::HashSet;
+
+use crate::stockholm::{Alignment, Sequence};
+
+/// IDs listed per group before the rest are counted.
+const MAX_LISTED: usize = 50;
+
+/// Lines describing how `after` differs from `before`; empty if it doesn't.
+///
+/// Sequences are compared row by row when the count is unchanged (so a renamed
+/// ID shows as a rename), and by ID otherwise. At the same width every changed
+/// cell counts; across a width change only residues do, since every row's gaps
+/// move with the columns.
+pub fn describe_changes(before: &Alignment, after: &Alignment, gap_chars: &[char]) -> Vec<String> {
+    let mut lines = Vec::new();
+    let (old_width, new_width) = (before.width(), after.width());
+    if old_width != new_width {
+        let (verb, n) = if new_width < old_width {
+            ("removed", old_width - new_width)
+        } else {
+            ("added", new_width - old_width)
+        };
+        lines.push(format!(
+            "Width {old_width} -> {new_width} ({n} columns {verb})"
+        ));
+    }
+
+    let mut changed = Vec::new();
+    let mut renamed = Vec::new();
+    if before.sequences.len() == after.sequences.len() {
+        for (old, new) in before.sequences.iter().zip(&after.sequences) {
+            if old.id != new.id {
+                renamed.push(format!("  {} -> {}", old.id, new.id));
+            }
+            if let Some(detail) = sequence_change(old, new, old_width == new_width, gap_chars) {
+                changed.push(format!("  {}  {detail}", new.id));
+            }
+        }
+    } else {
+        let old_ids: HashSet<&str> = before.sequences.iter().map(|s| s.id.as_str()).collect();
+        let new_ids: HashSet<&str> = after.sequences.iter().map(|s| s.id.as_str()).collect();
+        let removed: Vec<&str> = before
+            .sequences
+            .iter()
+            .map(|s| s.id.as_str())
+            .filter(|id| !new_ids.contains(id))
+            .collect();
+        let added: Vec<&str> = after
+            .sequences
+            .iter()
+            .map(|s| s.id.as_str())
+            .filter(|id| !old_ids.contains(id))
+            .collect();
+        push_group(&mut lines, "Removes", "sequences", &removed);
+        push_group(&mut lines, "Adds", "sequences", &added);
+        for new in &after.sequences {
+            let old = before.sequences.iter().find(|s| s.id == new.id);
+            if let Some(detail) =
+                old.and_then(|old| sequence_change(old, new, old_width == new_width, gap_chars))
+            {
+                changed.push(format!("  {}  {detail}", new.id));
+            }
+        }
+    }
+    if !renamed.is_empty() {
+        lines.push(format!("Renames {} sequences:", renamed.len()));
+        push_listed(&mut lines, renamed);
+    }
+    if !changed.is_empty() {
+        lines.push(format!(
+            "Changes {} of {} sequences:",
+            changed.len(),
+            after.sequences.len()
+        ));
+        push_listed(&mut lines, changed);
+    }
+
+    let mut annotations: Vec<String> = after
+        .column_annotations
+        .iter()
+        .filter(|new| {
+            before
+                .column_annotations
+                .iter()
+                .find(|old| old.tag == new.tag)
+                .is_none_or(|old| old.data != new.data)
+        })
+        .map(|ann| format!("#=GC {}", ann.tag))
+        .collect();
+    annotations.extend(
+        before
+            .column_annotations
+            .iter()
+            .filter(|old| after.column_annotation(&old.tag).is_none())
+            .map(|ann| format!("#=GC {} (removed)", ann.tag)),
+    );
+    let residue_rows = |alignment: &Alignment| -> HashSet<(String, String, String)> {
+        alignment
+            .residue_annotations
+            .iter()
+            .flat_map(|(id, anns)| {
+                anns.iter()
+                    .map(move |ann| (id.clone(), ann.tag.clone(), ann.data.clone()))
+            })
+            .collect()
+    };
+    let (old_gr, new_gr) = (residue_rows(before), residue_rows(after));
+    let gr_changed = new_gr.difference(&old_gr).count();
+    if gr_changed > 0 {
+        annotations.push(format!("{gr_changed} #=GR rows"));
+    }
+    if !annotations.is_empty() {
+        lines.push(format!("Annotations: {}", annotations.join(", ")));
+    }
+    lines
+}
+
+/// How a sequence changed: the cells that differ at the same width, or else the
+/// change in its residues. None if it didn't.
+fn sequence_change(
+    old: &Sequence,
+    new: &Sequence,
+    same_width: bool,
+    gap_chars: &[char],
+) -> Option<String> {
+    if same_width {
+        let cells = old
+            .chars()
+            .iter()
+            .zip(new.chars())
+            .filter(|(a, b)| a != b)
+            .count()
+            + old.len().abs_diff(new.len());
+        return (cells > 0).then(|| format!("{cells} cells"));
+    }
+    let residues = |seq: &Sequence| -> Vec<char> {
+        seq.chars()
+            .iter()
+            .copied()
+            .filter(|c| !gap_chars.contains(c))
+            .collect()
+    };
+    let (old_residues, new_residues) = (residues(old), residues(new));
+    if old_residues == new_residues {
+        return None;
+    }
+    let delta = new_residues.len() as isize - old_residues.len() as isize;
+    Some(match delta {
+        0 => "residues changed".to_string(),
+        d if d < 0 => format!("{} residues removed", -d),
+        d => format!("{d} residues added"),
+    })
+}
+
+/// Add "<verb> N <noun>:" and the listed IDs, if there are any.
+fn push_group(lines: &mut Vec<String>, verb: &str, noun: &str, ids: &[&str]) {
+    if ids.is_empty() {
+        return;
+    }
+    lines.push(format!("{verb} {} {noun}:", ids.len()));
+    push_listed(lines, ids.iter().map(|id| format!("  {id}")).collect());
+}
+
+/// Add up to `MAX_LISTED` entries and a count of the rest.
+fn push_listed(lines: &mut Vec<String>, entries: Vec<String>) {
+    let rest = entries.len().saturating_sub(MAX_LISTED);
+    lines.extend(entries.into_iter().take(MAX_LISTED));
+    if rest > 0 {
+        lines.push(format!("  ... and {rest} more"));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::stockholm::ColumnAnnotation;
+    use std::rc::Rc;
+
+    const GAPS: [char; 2] = ['.', '-'];
+
+    fn alignment(rows: &[(&str, &str)]) -> Alignment {
+        let mut alignment = Alignment::new();
+        for (id, data) in rows {
+            alignment.sequences.push(Rc::new(Sequence::new(*id, *data)));
+        }
+        alignment
+    }
+
+    #[test]
+    fn test_unchanged() {
+        let a = alignment(&[("a", "AC-GU"), ("b", "ACGGU")]);
+        assert!(describe_changes(&a, &a.clone(), &GAPS).is_empty());
+    }
+
+    #[test]
+    fn test_same_width_cells() {
+        let before = alignment(&[("a", "ACGU"), ("b", "ACGU")]);
+        let after = alignment(&[("a", "acGU"), ("b", "ACGU")]);
+        assert_eq!(
+            describe_changes(&before, &after, &GAPS),
+            vec!["Changes 1 of 2 sequences:", "  a  2 cells"]
+        );
+    }
+
+    #[test]
+    fn test_width_change_counts_residues_only() {
+        let mut before = alignment(&[("a", "..ACGU"), ("b", ".-ACGU")]);
+        before.column_annotations.push(ColumnAnnotation {
+            tag: "SS_cons".to_string(),
+            data: "..<..>".to_string(),
+        });
+        let mut after = alignment(&[("a", "ACGU"), ("b", "CGU.")]);
+        after.column_annotations.push(ColumnAnnotation {
+            tag: "SS_cons".to_string(),
+            data: "<..>".to_string(),
+        });
+        assert_eq!(
+            describe_changes(&before, &after, &GAPS),
+            vec![
+                "Width 6 -> 4 (2 columns removed)",
+                "Changes 1 of 2 sequences:",
+                "  b  1 residues removed",
+                "Annotations: #=GC SS_cons",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_removed_and_renamed() {
+        let before = alignment(&[("a", "ACGU"), ("b", "ACGU"), ("c", "ACGU")]);
+        let after = alignment(&[("a", "ACGU"), ("c", "ACGU")]);
+        assert_eq!(
+            describe_changes(&before, &after, &GAPS),
+            vec!["Removes 1 sequences:", "  b"]
+        );
+
+        let renamed = alignment(&[("a/1-4", "ACGU"), ("b", "ACGU"), ("c", "ACGU")]);
+        assert_eq!(
+            describe_changes(&before, &renamed, &GAPS),
+            vec!["Renames 1 sequences:", "  a -> a/1-4"]
+        );
+    }
+}
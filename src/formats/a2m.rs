@@ -0,0 +1,222 @@
+//! A2M format (SAM/HMMER aligned FASTA with case-coded states).
+//!
+//! Uppercase residues and `-` are match columns; lowercase residues and `.` are
+//! inserts. On import the match/insert split becomes `#=GC RF` (`x` for match, `.`
+//! for insert); on export case and gap characters are rebuilt from RF.
+
+use std::io::{Read, Write};
+use std::rc::Rc;
+
+use thiserror::Error;
+
+use super::fasta::{self, FastaError, Record};
+use crate::stockholm::{Alignment, ColumnAnnotation, Sequence};
+
+#[derive(Error, Debug)]
+pub enum A2mError {
+    #[error(transparent)]
+    Fasta(#[from] FastaError),
+    #[error("{id} has {found} match columns, expected {expected}")]
+    MatchCount {
+        id: String,
+        expected: usize,
+        found: usize,
+    },
+}
+
+fn is_match_char(c: char) -> bool {
+    c.is_ascii_uppercase() || c == '-' || c == '*'
+}
+
+/// Match states of every column if the records already form a consistent dotted
+/// alignment (same length, each column either all match or all insert).
+fn dotted_columns(records: &[Record]) -> Option<Vec<bool>> {
+    let width = records.first()?.2.chars().count();
+    let rows: Vec<Vec<char>> = records.iter().map(|r| r.2.chars().collect()).collect();
+    if rows.iter().any(|row| row.len() != width) {
+        return None;
+    }
+    (0..width)
+        .map(|col| {
+            let is_match = is_match_char(rows[0][col]);
+            rows.iter()
+                .all(|row| is_match_char(row[col]) == is_match)
+                .then_some(is_match)
+        })
+        .collect()
+}
+
+/// Align dotless A2M records on their match columns, left-justifying inserts.
+fn expand_inserts(records: &mut [Record]) -> Result<Vec<bool>, A2mError> {
+    // Per record: insert runs before each match column (and after the last), and
+    // the match characters
+    let mut parsed: Vec<(Vec<Vec<char>>, Vec<char>)> = Vec::new();
+    for (id, _, data) in records.iter() {
+        let mut inserts = vec![Vec::new()];
+        let mut matches = Vec::new();
+        for c in data.chars() {
+            if is_match_char(c) {
+                matches.push(c);
+                inserts.push(Vec::new());
+            } else if c != '.' {
+                inserts.last_mut().expect("one run per match").push(c);
+            }
+        }
+        if let Some((_, first)) = parsed.first()
+            && first.len() != matches.len()
+        {
+            return Err(A2mError::MatchCount {
+                id: id.clone(),
+                expected: first.len(),
+                found: matches.len(),
+            });
+        }
+        parsed.push((inserts, matches));
+    }
+
+    let Some((first_inserts, first_matches)) = parsed.first() else {
+        return Ok(Vec::new());
+    };
+    let slots: Vec<usize> = (0..first_inserts.len())
+        .map(|k| {
+            parsed
+                .iter()
+                .map(|(ins, _)| ins[k].len())
+                .max()
+                .unwrap_or(0)
+        })
+        .collect();
+    let mut columns = Vec::new();
+    for (k, &slot) in slots.iter().enumerate() {
+        columns.extend(std::iter::repeat_n(false, slot));
+        if k < first_matches.len() {
+            columns.push(true);
+        }
+    }
+
+    for (record, (inserts, matches)) in records.iter_mut().zip(parsed) {
+        let mut data = String::with_capacity(columns.len());
+        for (k, run) in inserts.iter().enumerate() {
+            data.extend(run);
+            data.extend(std::iter::repeat_n('.', slots[k] - run.len()));
+            if let Some(&c) = matches.get(k) {
+                data.push(c);
+            }
+        }
+        record.2 = data;
+    }
+    Ok(columns)
+}
+
+/// Parse an A2M file, recording match columns in `#=GC RF`.
+pub fn parse<R: Read>(reader: R) -> Result<Alignment, A2mError> {
+    let mut records = fasta::read_records(reader)?;
+    let columns = match dotted_columns(&records) {
+        Some(columns) => columns,
+        None => expand_inserts(&mut records)?,
+    };
+    let mut alignment = fasta::to_alignment(records);
+    alignment.column_annotations.push(ColumnAnnotation {
+        tag: "RF".to_string(),
+        data: columns
+            .iter()
+            .map(|&is_match| if is_match { 'x' } else { '.' })
+            .collect(),
+    });
+    Ok(alignment)
+}
+
+/// Match state of each column: from `#=GC RF` if present, otherwise columns where
+/// at least half the sequences have a residue.
+fn match_columns(alignment: &Alignment, gap_chars: &[char]) -> Vec<bool> {
+    if let Some(rf) = alignment.rf() {
+        return rf.chars().map(|c| !gap_chars.contains(&c)).collect();
+    }
+    let rows = alignment.sequences.len();
+    (0..alignment.width())
+        .map(|col| {
+            let residues = alignment
+                .sequences
+                .iter()
+                .filter(|s| s.get(col).is_some_and(|c| !gap_chars.contains(&c)))
+                .count();
+            2 * residues >= rows
+        })
+        .collect()
+}
+
+/// Write an alignment as A2M, setting case and gap characters (any of
+/// `gap_chars`) from match columns.
+pub fn write<W: Write>(
+    alignment: &Alignment,
+    gap_chars: &[char],
+    writer: W,
+) -> std::io::Result<()> {
+    let columns = match_columns(alignment, gap_chars);
+    let mut coded = alignment.clone();
+    for seq_rc in &mut coded.sequences {
+        let data: String = seq_rc
+            .chars()
+            .iter()
+            .enumerate()
+            .map(|(col, &c)| {
+                let is_match = columns.get(col).copied().unwrap_or(true);
+                match (gap_chars.contains(&c), is_match) {
+                    (true, true) => '-',
+                    (true, false) => '.',
+                    (false, true) => c.to_ascii_uppercase(),
+                    (false, false) => c.to_ascii_lowercase(),
+                }
+            })
+            .collect();
+        *seq_rc = Rc::new(Sequence::new(seq_rc.id.clone(), data));
+    }
+    // Gaps are already coded as `-` or `.`
+    fasta::write(&coded, &[], writer)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::stockholm::DEFAULT_GAP_CHARS;
+
+    #[test]
+    fn test_parse_dotted_a2m() {
+        let alignment = parse(">a\nAc.-GU\n>b\nAcgUGU\n".as_bytes()).unwrap();
+        assert_eq!(alignment.sequences[0].data(), "Ac.-GU");
+        assert_eq!(alignment.rf(), Some("x..xxx"));
+    }
+
+    #[test]
+    fn test_parse_dotless_a2m() {
+        let alignment = parse(">a\nACGU\n>b\nAuuC-U\n>c\nAaC-U\n".as_bytes()).unwrap();
+        assert_eq!(alignment.sequences[0].data(), "A..CGU");
+        assert_eq!(alignment.sequences[1].data(), "AuuC-U");
+        assert_eq!(alignment.sequences[2].data(), "Aa.C-U");
+        assert_eq!(alignment.rf(), Some("x..xxx"));
+
+        assert!(matches!(
+            parse(">a\nACGU\n>b\nACG\n".as_bytes()),
+            Err(A2mError::MatchCount { found: 3, .. })
+        ));
+    }
+
+    #[test]
+    fn test_write_a2m_from_rf() {
+        let mut alignment = Alignment::new();
+        for (id, data) in [("a", "ac..gu"), ("b", "AcgU-u")] {
+            alignment.sequences.push(Rc::new(Sequence::new(id, data)));
+        }
+        alignment.column_annotations.push(ColumnAnnotation {
+            tag: "RF".to_string(),
+            data: "x..xxx".to_string(),
+        });
+        let mut buffer = Vec::new();
+        write(&alignment, &DEFAULT_GAP_CHARS, &mut buffer).unwrap();
+        let text = String::from_utf8(buffer).unwrap();
+        assert_eq!(text, ">a\nAc.-GU\n>b\nAcgU-U\n");
+
+        let reparsed = parse(text.as_bytes()).unwrap();
+        assert_eq!(reparsed.rf(), Some("x..xxx"));
+    }
+}
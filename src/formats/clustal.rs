@@ -0,0 +1,267 @@
+//! Clustal (`.aln`) format.
+//!
+//! The conservation line under each block maps to a `#=GC CLUSTAL_cons` track, with
+//! `_` standing in for unconserved (blank) columns since GC data cannot hold spaces.
+
+use std::io::{BufRead, BufReader, Read, Write};
+use std::rc::Rc;
+
+use thiserror::Error;
+
+use crate::stockholm::{Alignment, ColumnAnnotation, Sequence};
+
+/// #=GC tag holding the Clustal conservation line.
+pub const CONSERVATION_TAG: &str = "CLUSTAL_cons";
+
+/// Residues per block when writing.
+const BLOCK_WIDTH: usize = 60;
+
+/// Clustal's strong and weak residue groups (`:` and `.` in the conservation line).
+const STRONG_GROUPS: &[&str] = &[
+    "STA", "NEQK", "NHQK", "NDEQ", "QHRK", "MILV", "MILF", "HY", "FYW",
+];
+const WEAK_GROUPS: &[&str] = &[
+    "CSA", "ATV", "SAG", "STNK", "STPA", "SGND", "SNDEQK", "NDEQHK", "NEQHRK", "FVLIM", "HFY",
+];
+
+#[derive(Error, Debug)]
+pub enum ClustalError {
+    #[error("IO error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Invalid Clustal header (expected a line starting with CLUSTAL)")]
+    InvalidHeader,
+    #[error("No sequences found")]
+    Empty,
+    #[error("Inconsistent sequence lengths")]
+    InconsistentLengths,
+}
+
+/// Whether a line is a Clustal header (Clustal, MUSCLE and other tools' variants).
+pub fn is_header(line: &str) -> bool {
+    line.starts_with("CLUSTAL") || line.starts_with("MUSCLE") || line.starts_with("PROBCONS")
+}
+
+/// Parse a Clustal alignment from a reader.
+pub fn parse<R: Read>(reader: R) -> Result<Alignment, ClustalError> {
+    let mut lines = BufReader::new(reader).lines();
+    let header = loop {
+        match lines.next() {
+            Some(line) => {
+                let line = line?;
+                if !line.trim().is_empty() {
+                    break line;
+                }
+            }
+            None => return Err(ClustalError::Empty),
+        }
+    };
+    if !is_header(&header) {
+        return Err(ClustalError::InvalidHeader);
+    }
+
+    let mut order: Vec<String> = Vec::new();
+    let mut data: Vec<String> = Vec::new();
+    let mut conservation = String::new();
+    let mut has_conservation = false;
+    // Sequence column offset and width of the block being read
+    let mut block: Option<(usize, usize)> = None;
+
+    for line in lines {
+        let line = line?;
+        let line = line.trim_end();
+        let starts_blank = line.starts_with(char::is_whitespace);
+
+        if line.is_empty() || starts_blank {
+            if let Some((offset, width)) = block.take() {
+                // A blank line may be an all-unconserved conservation line
+                let symbols: String = line
+                    .chars()
+                    .skip(offset)
+                    .chain(std::iter::repeat(' '))
+                    .take(width)
+                    .map(|c| if c == ' ' { '_' } else { c })
+                    .collect();
+                has_conservation |= starts_blank;
+                conservation.push_str(&symbols);
+            }
+            continue;
+        }
+
+        let mut parts = line.split_whitespace();
+        let (Some(id), Some(residues)) = (parts.next(), parts.next()) else {
+            continue;
+        };
+        if block.is_none() {
+            let offset = line.len() - line[id.len()..].trim_start().len();
+            block = Some((offset, residues.chars().count()));
+        }
+        match order.iter().position(|existing| existing == id) {
+            Some(i) => data[i].push_str(residues),
+            None => {
+                order.push(id.to_string());
+                data.push(residues.to_string());
+            }
+        }
+    }
+    if let Some((_, width)) = block {
+        conservation.extend(std::iter::repeat_n('_', width));
+    }
+
+    if order.is_empty() {
+        return Err(ClustalError::Empty);
+    }
+    let mut alignment = Alignment::new();
+    for (id, residues) in order.into_iter().zip(data) {
+        alignment
+            .sequences
+            .push(Rc::new(Sequence::new(id, residues)));
+    }
+    if !alignment.is_valid() {
+        return Err(ClustalError::InconsistentLengths);
+    }
+    if has_conservation && conservation.chars().count() == alignment.width() {
+        alignment.column_annotations.push(ColumnAnnotation {
+            tag: CONSERVATION_TAG.to_string(),
+            data: conservation,
+        });
+    }
+    Ok(alignment)
+}
+
+/// Clustal conservation symbol for a column.
+fn conservation_symbol(alignment: &Alignment, col: usize, gap_chars: &[char]) -> char {
+    let residues: Vec<char> = alignment
+        .sequences
+        .iter()
+        .map(|s| s.get(col).unwrap_or('-').to_ascii_uppercase())
+        .collect();
+    if residues.iter().any(|c| gap_chars.contains(c)) || residues.is_empty() {
+        return ' ';
+    }
+    let in_one_group = |groups: &[&str]| {
+        groups
+            .iter()
+            .any(|group| residues.iter().all(|c| group.contains(*c)))
+    };
+    if residues.iter().all(|&c| c == residues[0]) {
+        '*'
+    } else if in_one_group(STRONG_GROUPS) {
+        ':'
+    } else if in_one_group(WEAK_GROUPS) {
+        '.'
+    } else {
+        ' '
+    }
+}
+
+/// Write an alignment in Clustal format.
+///
+/// The conservation line is computed from the residues, with `gap_chars` as
+/// gaps, rather than taken from `#=GC CLUSTAL_cons`, which edits leave stale.
+pub fn write<W: Write>(
+    alignment: &Alignment,
+    gap_chars: &[char],
+    mut writer: W,
+) -> std::io::Result<()> {
+    writeln!(writer, "CLUSTAL W multiple sequence alignment")?;
+    writeln!(writer)?;
+
+    let conservation: Vec<char> = (0..alignment.width())
+        .map(|col| conservation_symbol(alignment, col, gap_chars))
+        .collect();
+
+    let id_width = alignment.max_id_len() + 6;
+    let mut start = 0;
+    while start < alignment.width() {
+        let end = (start + BLOCK_WIDTH).min(alignment.width());
+        writeln!(writer)?;
+        for seq in &alignment.sequences {
+            let block: String = seq.chars()[start.min(seq.len())..end.min(seq.len())]
+                .iter()
+                .collect();
+            writeln!(writer, "{:id_width$}{}", seq.id, block)?;
+        }
+        let symbols: String = conservation
+            .get(start..end)
+            .map(|s| s.iter().collect())
+            .unwrap_or_default();
+        writeln!(writer, "{:id_width$}{}", "", symbols)?;
+        start = end;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::stockholm::DEFAULT_GAP_CHARS;
+
+    const CLUSTAL: &str = "\
+CLUSTAL W (1.83) multiple sequence alignment
+
+
+seq1      ACGUAC 6
+seq2      ACGAAC 6
+          *** **
+
+seq1      GU-
+seq2      GUA
+          **
+";
+
+    #[test]
+    fn test_parse_clustal() {
+        let alignment = parse(CLUSTAL.as_bytes()).unwrap();
+        assert_eq!(alignment.sequences.len(), 2);
+        assert_eq!(alignment.sequences[0].data(), "ACGUACGU-");
+        assert_eq!(alignment.sequences[1].data(), "ACGAACGUA");
+        let cons = &alignment.column_annotations[0];
+        assert_eq!(cons.tag, CONSERVATION_TAG);
+        assert_eq!(cons.data, "***_****_");
+    }
+
+    #[test]
+    fn test_parse_clustal_errors() {
+        assert!(matches!(
+            parse("# STOCKHOLM 1.0\n".as_bytes()),
+            Err(ClustalError::InvalidHeader)
+        ));
+        assert!(matches!(
+            parse("CLUSTAL W\n\na ACGU\nb ACG\n".as_bytes()),
+            Err(ClustalError::InconsistentLengths)
+        ));
+    }
+
+    #[test]
+    fn test_clustal_roundtrip() {
+        let alignment = parse(CLUSTAL.as_bytes()).unwrap();
+        let mut buffer = Vec::new();
+        write(&alignment, &DEFAULT_GAP_CHARS, &mut buffer).unwrap();
+        let reparsed = parse(buffer.as_slice()).unwrap();
+        assert_eq!(reparsed.sequences[1].data(), "ACGAACGUA");
+        assert_eq!(reparsed.column_annotations[0].data, "***_****_");
+    }
+
+    #[test]
+    fn test_conservation_follows_edits() {
+        let mut alignment = parse(CLUSTAL.as_bytes()).unwrap();
+        Rc::make_mut(&mut alignment.sequences[1]).set(3, 'U');
+        let mut buffer = Vec::new();
+        write(&alignment, &DEFAULT_GAP_CHARS, &mut buffer).unwrap();
+        let reparsed = parse(buffer.as_slice()).unwrap();
+        assert_eq!(reparsed.column_annotations[0].data, "********_");
+    }
+
+    #[test]
+    fn test_computed_conservation() {
+        let mut alignment = Alignment::new();
+        for (id, data) in [("a", "MSLV-"), ("b", "MTIV-"), ("c", "MAMVW")] {
+            alignment.sequences.push(Rc::new(Sequence::new(id, data)));
+        }
+        let mut buffer = Vec::new();
+        write(&alignment, &DEFAULT_GAP_CHARS, &mut buffer).unwrap();
+        let text = String::from_utf8(buffer).unwrap();
+        let last = text.lines().last().unwrap();
+        assert_eq!(last.trim(), "*::*");
+    }
+}
@@ -0,0 +1,102 @@
+//! Dot-bracket export of the consensus sequence and SS_cons, for structure tools
+//! such as forna and RNApdbee (export only).
+
+use std::io::Write;
+
+use crate::color::get_consensus_char;
+use crate::stockholm::Alignment;
+use crate::structure::{StructureCache, StructureError};
+
+/// The consensus and its structure over the columns that are not mostly gaps.
+/// Pairs that lose a partner to a dropped column are written unpaired, so the
+/// brackets always balance. WUSS pseudoknot letters are not pairs here.
+pub fn dot_bracket(
+    alignment: &Alignment,
+    ss_cons: &str,
+    gap_chars: &[char],
+) -> Result<(String, String), StructureError> {
+    let mut cache = StructureCache::new();
+    cache.update(ss_cons)?;
+    let width = alignment.width();
+    let kept: Vec<bool> = (0..width)
+        .map(|col| {
+            let gaps = alignment
+                .sequences
+                .iter()
+                .filter(|s| s.get(col).is_none_or(|c| gap_chars.contains(&c)))
+                .count();
+            gaps * 2 <= alignment.sequences.len()
+        })
+        .collect();
+
+    let mut structure = vec!['.'; width];
+    for pair in cache.pairs() {
+        if pair.right < width && kept[pair.left] && kept[pair.right] {
+            structure[pair.left] = '(';
+            structure[pair.right] = ')';
+        }
+    }
+    let (sequence, structure) = (0..width)
+        .filter(|&col| kept[col])
+        .map(|col| {
+            (
+                get_consensus_char(col, alignment, gap_chars),
+                structure[col],
+            )
+        })
+        .unzip();
+    Ok((sequence, structure))
+}
+
+/// Write a dot-bracket file: a `>name` line, the sequence and the structure.
+pub fn write<W: Write>(
+    name: &str,
+    sequence: &str,
+    structure: &str,
+    mut writer: W,
+) -> std::io::Result<()> {
+    writeln!(writer, ">{name}")?;
+    writeln!(writer, "{sequence}")?;
+    writeln!(writer, "{structure}")?;
+    writer.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::rc::Rc;
+
+    use super::*;
+    use crate::stockholm::{DEFAULT_GAP_CHARS as GAPS, Sequence};
+
+    #[test]
+    fn test_dot_bracket_drops_gap_columns() {
+        let mut alignment = Alignment::new();
+        for (id, data) in [
+            ("seq1", "GAC.AAGUC"),
+            ("seq2", "GAC-AAGUC"),
+            ("seq3", "GcCUAAGU."),
+        ] {
+            alignment.sequences.push(Rc::new(Sequence::new(id, data)));
+        }
+        // Column 4 is mostly gaps, so its partner, column 6, is left unpaired
+        let (sequence, structure) = dot_bracket(&alignment, "<<<<.>>>>", &GAPS).unwrap();
+        assert_eq!(sequence, "GACAAGUC");
+        assert_eq!(structure, "(((..)))");
+
+        let mut buffer = Vec::new();
+        write("tRNA", &sequence, &structure, &mut buffer).unwrap();
+        assert_eq!(
+            String::from_utf8(buffer).unwrap(),
+            ">tRNA\nGACAAGUC\n(((..)))\n"
+        );
+    }
+
+    #[test]
+    fn test_dot_bracket_rejects_unbalanced_structure() {
+        let mut alignment = Alignment::new();
+        alignment
+            .sequences
+            .push(Rc::new(Sequence::new("seq1", "GAC")));
+        assert!(dot_bracket(&alignment, "<..", &GAPS).is_err());
+    }
+}
@@ -0,0 +1,183 @@
+//! Aligned FASTA format.
+//!
+//! Header descriptions map to `#=GS <id> DE` annotations; other annotations are not
+//! representable and are dropped on write.
+
+use std::io::{BufRead, BufReader, Read, Write};
+use std::rc::Rc;
+
+use thiserror::Error;
+
+use crate::stockholm::{Alignment, Sequence, SequenceAnnotation};
+
+/// Residues per line when writing.
+const LINE_WIDTH: usize = 60;
+
+#[derive(Error, Debug)]
+pub enum FastaError {
+    #[error("IO error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Sequence data before the first '>' header (line {0})")]
+    MissingHeader(usize),
+    #[error("No sequences found")]
+    Empty,
+    #[error("Sequences have different lengths (not an aligned FASTA)")]
+    InconsistentLengths,
+}
+
+/// One FASTA record: ID, optional header description, and residues.
+pub(super) type Record = (String, Option<String>, String);
+
+/// Read FASTA records without checking that they form an alignment.
+pub(super) fn read_records<R: Read>(reader: R) -> Result<Vec<Record>, FastaError> {
+    let mut records: Vec<Record> = Vec::new();
+    for (line_no, line) in BufReader::new(reader).lines().enumerate() {
+        let line = line?;
+        let line = line.trim_end();
+        if let Some(header) = line.strip_prefix('>') {
+            let mut parts = header.trim().splitn(2, char::is_whitespace);
+            let id = parts.next().unwrap_or_default().to_string();
+            let description = parts
+                .next()
+                .map(str::trim)
+                .filter(|d| !d.is_empty())
+                .map(str::to_string);
+            records.push((id, description, String::new()));
+        } else if !line.is_empty() {
+            let Some((_, _, data)) = records.last_mut() else {
+                return Err(FastaError::MissingHeader(line_no + 1));
+            };
+            data.extend(line.chars().filter(|c| !c.is_whitespace()));
+        }
+    }
+    if records.is_empty() {
+        return Err(FastaError::Empty);
+    }
+    Ok(records)
+}
+
+/// Build an alignment from records, keeping descriptions as `#=GS <id> DE`.
+pub(super) fn to_alignment(records: Vec<Record>) -> Alignment {
+    let mut alignment = Alignment::new();
+    for (id, description, data) in records {
+        if let Some(description) = description {
+            alignment
+                .sequence_annotations
+                .entry(id.clone())
+                .or_default()
+                .push(SequenceAnnotation {
+                    tag: "DE".to_string(),
+                    value: description,
+                });
+        }
+        alignment.sequences.push(Rc::new(Sequence::new(id, data)));
+    }
+    alignment
+}
+
+/// Parse an aligned FASTA file from a reader.
+pub fn parse<R: Read>(reader: R) -> Result<Alignment, FastaError> {
+    let alignment = to_alignment(read_records(reader)?);
+    if !alignment.is_valid() {
+        return Err(FastaError::InconsistentLengths);
+    }
+    Ok(alignment)
+}
+
+/// Parse a FASTA file whose sequences may differ in length (unaligned), leaving
+/// them as they are.
+pub fn parse_unaligned<R: Read>(reader: R) -> Result<Alignment, FastaError> {
+    Ok(to_alignment(read_records(reader)?))
+}
+
+/// Write an alignment as aligned FASTA, with `gap_chars` written as `-`.
+pub fn write<W: Write>(
+    alignment: &Alignment,
+    gap_chars: &[char],
+    mut writer: W,
+) -> std::io::Result<()> {
+    for seq in &alignment.sequences {
+        let description = alignment
+            .sequence_annotations
+            .get(&seq.id)
+            .and_then(|anns| anns.iter().find(|a| a.tag == "DE"));
+        match description {
+            Some(de) => writeln!(writer, ">{} {}", seq.id, de.value)?,
+            None => writeln!(writer, ">{}", seq.id)?,
+        }
+        for chunk in seq.chars().chunks(LINE_WIDTH) {
+            let line: String = chunk
+                .iter()
+                .map(|&c| if gap_chars.contains(&c) { '-' } else { c })
+                .collect();
+            writeln!(writer, "{line}")?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FASTA: &str = "\
+>seq1/1-10 first sequence
+ACGU..
+ACGU
+>seq2/1-10
+ACGU..ACGU
+";
+
+    #[test]
+    fn test_parse_fasta() {
+        let alignment = parse(FASTA.as_bytes()).unwrap();
+        assert_eq!(alignment.sequences.len(), 2);
+        assert_eq!(alignment.sequences[0].id, "seq1/1-10");
+        assert_eq!(alignment.sequences[0].data(), "ACGU..ACGU");
+        assert_eq!(
+            alignment.sequence_annotations["seq1/1-10"][0].value,
+            "first sequence"
+        );
+        assert!(!alignment.sequence_annotations.contains_key("seq2/1-10"));
+    }
+
+    #[test]
+    fn test_parse_fasta_errors() {
+        assert!(matches!(
+            parse("ACGU\n>seq1\nACGU\n".as_bytes()),
+            Err(FastaError::MissingHeader(1))
+        ));
+        assert!(matches!(
+            parse(">a\nACGU\n>b\nACG\n".as_bytes()),
+            Err(FastaError::InconsistentLengths)
+        ));
+        assert!(matches!(parse("".as_bytes()), Err(FastaError::Empty)));
+        assert_eq!(
+            parse_unaligned(">a\nACGU\n>b\nACG\n".as_bytes())
+                .unwrap()
+                .width(),
+            4
+        );
+    }
+
+    #[test]
+    fn test_write_fasta_roundtrip() {
+        let mut alignment = parse(FASTA.as_bytes()).unwrap();
+        Rc::make_mut(&mut alignment.sequences[1]).set(0, 'a');
+
+        let mut buffer = Vec::new();
+        write(&alignment, &['.', '-'], &mut buffer).unwrap();
+        let text = String::from_utf8(buffer).unwrap();
+        assert!(text.starts_with(">seq1/1-10 first sequence\nACGU--ACGU\n"));
+
+        let reparsed = parse(text.as_bytes()).unwrap();
+        assert_eq!(reparsed.sequences[1].data(), "aCGU--ACGU");
+
+        let mut wrapped = Alignment::new();
+        let long = "A".repeat(LINE_WIDTH + 5);
+        wrapped.sequences.push(Rc::new(Sequence::new("long", long)));
+        let mut buffer = Vec::new();
+        write(&wrapped, &[], &mut buffer).unwrap();
+        assert_eq!(String::from_utf8(buffer).unwrap().lines().count(), 3);
+    }
+}
@@ -0,0 +1,51 @@
+//! JSON export of the alignment model, for analyses in Python, R and the like.
+//!
+//! The document mirrors [`Alignment`]: `file_annotations` (#=GF),
+//! `sequences`, `sequence_annotations` (#=GS, by sequence ID),
+//! `column_annotations` (#=GC) and `residue_annotations` (#=GR, by sequence ID).
+
+use std::io::Write;
+
+use crate::stockholm::Alignment;
+
+/// Write an alignment as a pretty-printed JSON object.
+pub fn write<W: Write>(alignment: &Alignment, mut writer: W) -> std::io::Result<()> {
+    serde_json::to_writer_pretty(&mut writer, alignment)?;
+    writeln!(writer)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::stockholm::parser;
+
+    #[test]
+    fn test_json_export() {
+        let text = "\
+# STOCKHOLM 1.0
+#=GF ID test
+#=GS seq2 DE second
+seq2  AC-U
+seq1  ACGU
+#=GR seq1 PP  99*8
+#=GC SS_cons  (..)
+//
+";
+        let alignment = parser::parse(text.as_bytes()).unwrap();
+        let mut buffer = Vec::new();
+        write(&alignment, &mut buffer).unwrap();
+        let json: serde_json::Value = serde_json::from_slice(&buffer).unwrap();
+
+        assert_eq!(json["file_annotations"][0]["value"], "test");
+        assert_eq!(json["sequences"][0]["id"], "seq2");
+        assert_eq!(json["sequences"][1]["data"], "ACGU");
+        assert_eq!(json["sequence_annotations"]["seq2"][0]["value"], "second");
+        assert_eq!(json["column_annotations"][0]["tag"], "SS_cons");
+        assert_eq!(json["residue_annotations"]["seq1"][0]["data"], "99*8");
+
+        // The document reads back as the same alignment
+        let read: Alignment = serde_json::from_slice(&buffer).unwrap();
+        assert_eq!(read.sequences[0].data(), "AC-U");
+        assert_eq!(read.residue_annotations["seq1"][0].tag, "PP");
+    }
+}
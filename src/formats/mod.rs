@@ -0,0 +1,614 @@
+//! Alignment file formats and format detection.
+//!
+//! Stockholm is the native format (see [`crate::stockholm`]); other formats are
+//! converted to and from the same [`Alignment`] model.
+
+pub mod a2m;
+pub mod clustal;
+pub mod dbn;
+pub mod fasta;
+pub mod json;
+pub mod msf;
+pub mod nexus;
+pub mod phylip;
+pub mod selex;
+
+use std::io::{Read, Write};
+use std::path::Path;
+
+use thiserror::Error;
+
+use crate::stockholm::parser::ParseError;
+use crate::stockholm::writer::StockholmStyle;
+use crate::stockholm::{Alignment, DEFAULT_GAP_CHARS, SequenceType};
+
+#[derive(Error, Debug)]
+pub enum FormatError {
+    #[error("IO error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error(transparent)]
+    Stockholm(#[from] ParseError),
+    #[error(transparent)]
+    Fasta(#[from] fasta::FastaError),
+    #[error(transparent)]
+    Clustal(#[from] clustal::ClustalError),
+    #[error(transparent)]
+    A2m(#[from] a2m::A2mError),
+    #[error(transparent)]
+    Phylip(#[from] phylip::PhylipError),
+    #[error(transparent)]
+    Msf(#[from] msf::MsfError),
+    #[error(transparent)]
+    Selex(#[from] selex::SelexError),
+}
+
+/// Layout and safety options applied when writing alignments.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WriteOptions {
+    /// Stockholm block width in columns (None = one line per sequence).
+    pub wrap: Option<usize>,
+    /// Stockholm layout variant (classic or Pfam).
+    pub style: StockholmStyle,
+    /// Line ending of written files.
+    pub line_ending: LineEnding,
+    /// Keep the previous contents of an overwritten file as `<file>~`.
+    pub backup: bool,
+    /// Characters written as the gap symbol of formats that have only one
+    /// (`:set gapchars=`).
+    pub gap_chars: Vec<char>,
+}
+
+impl Default for WriteOptions {
+    fn default() -> Self {
+        Self {
+            wrap: None,
+            style: StockholmStyle::default(),
+            line_ending: LineEnding::default(),
+            backup: false,
+            gap_chars: DEFAULT_GAP_CHARS.to_vec(),
+        }
+    }
+}
+
+/// Line ending of a file: Unix `\n` or Windows `\r\n`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum LineEnding {
+    #[default]
+    Lf,
+    Crlf,
+}
+
+impl LineEnding {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "lf" | "unix" => Some(Self::Lf),
+            "crlf" | "dos" | "windows" => Some(Self::Crlf),
+            _ => None,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Lf => "LF",
+            Self::Crlf => "CRLF",
+        }
+    }
+
+    /// The line ending of text, from its first line.
+    pub fn detect(contents: &[u8]) -> Self {
+        match contents.iter().position(|&b| b == b'\n') {
+            Some(i) if i > 0 && contents[i - 1] == b'\r' => Self::Crlf,
+            _ => Self::Lf,
+        }
+    }
+}
+
+/// Writer that turns `\n` into the chosen line ending (lines already ending in
+/// `\r\n` are left alone).
+pub struct LineEndingWriter<W: Write> {
+    inner: W,
+    line_ending: LineEnding,
+    last: u8,
+}
+
+impl<W: Write> LineEndingWriter<W> {
+    pub fn new(inner: W, line_ending: LineEnding) -> Self {
+        Self {
+            inner,
+            line_ending,
+            last: 0,
+        }
+    }
+}
+
+impl<W: Write> Write for LineEndingWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        if self.line_ending == LineEnding::Lf {
+            return self.inner.write(buf);
+        }
+        let mut converted = Vec::with_capacity(buf.len() + buf.len() / 32);
+        for &b in buf {
+            if b == b'\n' && self.last != b'\r' {
+                converted.push(b'\r');
+            }
+            converted.push(b);
+            self.last = b;
+        }
+        self.inner.write_all(&converted)?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// Strip a UTF-8 byte order mark and turn `\r\n` line endings into `\n`, so
+/// files saved on Windows parse like any other.
+pub fn normalize_line_endings(mut contents: Vec<u8>) -> Vec<u8> {
+    if contents.starts_with(b"\xef\xbb\xbf") {
+        contents.drain(..3);
+    }
+    if !contents.contains(&b'\r') {
+        return contents;
+    }
+    let mut normalized = Vec::with_capacity(contents.len());
+    for (i, &b) in contents.iter().enumerate() {
+        if b != b'\r' || contents.get(i + 1) != Some(&b'\n') {
+            normalized.push(b);
+        }
+    }
+    normalized
+}
+
+/// A supported alignment file format.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Stockholm,
+    Fasta,
+    Clustal,
+    A2m,
+    Phylip,
+    Msf,
+    Selex,
+}
+
+impl Format {
+    /// Detect the format from a file extension, ignoring a trailing `.gz` or `.bz2`.
+    pub fn from_path(path: &Path) -> Option<Self> {
+        let name = path.file_name()?.to_str()?.to_ascii_lowercase();
+        let name = name
+            .strip_suffix(".gz")
+            .or_else(|| name.strip_suffix(".bz2"))
+            .unwrap_or(&name);
+        let ext = name.rsplit_once('.')?.1;
+        match ext {
+            "sto" | "stk" | "sth" | "stockholm" => Some(Self::Stockholm),
+            "fa" | "fasta" | "fas" | "afa" | "afasta" | "fna" | "faa" | "mfa" => Some(Self::Fasta),
+            "aln" | "clustal" | "clw" => Some(Self::Clustal),
+            "a2m" => Some(Self::A2m),
+            "phy" | "phylip" => Some(Self::Phylip),
+            "msf" => Some(Self::Msf),
+            "slx" | "selex" => Some(Self::Selex),
+            _ => None,
+        }
+    }
+
+    /// Look up a format by name (as used by `:set format=`).
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "stockholm" | "sto" | "stk" => Some(Self::Stockholm),
+            "fasta" | "afa" | "fa" => Some(Self::Fasta),
+            "clustal" | "aln" => Some(Self::Clustal),
+            "a2m" => Some(Self::A2m),
+            "phylip" | "phy" => Some(Self::Phylip),
+            "msf" | "gcg" => Some(Self::Msf),
+            "selex" | "slx" => Some(Self::Selex),
+            _ => None,
+        }
+    }
+
+    /// Guess the format from file contents.
+    pub fn sniff(contents: &[u8]) -> Self {
+        let contents = contents.strip_prefix(b"\xef\xbb\xbf").unwrap_or(contents);
+        let text = String::from_utf8_lossy(&contents[..contents.len().min(256)]);
+        let first_line = text.trim_start().lines().next().unwrap_or_default();
+        if first_line.starts_with('>') {
+            Self::Fasta
+        } else if selex::is_header(&text) {
+            Self::Selex
+        } else if msf::is_header(&text) {
+            Self::Msf
+        } else if clustal::is_header(first_line) {
+            Self::Clustal
+        } else if phylip::parse_header(first_line).is_some() {
+            Self::Phylip
+        } else {
+            Self::Stockholm
+        }
+    }
+
+    /// Human-readable format name.
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Stockholm => "Stockholm",
+            Self::Fasta => "FASTA",
+            Self::Clustal => "Clustal",
+            Self::A2m => "A2M",
+            Self::Phylip => "PHYLIP",
+            Self::Msf => "MSF",
+            Self::Selex => "SELEX",
+        }
+    }
+
+    /// Whether the format stores #=GF/#=GC/#=GR annotations.
+    pub fn keeps_annotations(self) -> bool {
+        matches!(self, Self::Stockholm)
+    }
+
+    /// Parse an alignment in this format.
+    pub fn parse<R: Read>(self, reader: R) -> Result<Alignment, FormatError> {
+        Ok(match self {
+            Self::Stockholm => crate::stockholm::parser::parse(reader)?,
+            Self::Fasta => fasta::parse(reader)?,
+            Self::Clustal => clustal::parse(reader)?,
+            Self::A2m => a2m::parse(reader)?,
+            Self::Phylip => phylip::parse(reader)?,
+            Self::Msf => msf::parse(reader)?,
+            Self::Selex => selex::parse(reader)?,
+        })
+    }
+
+    /// Parse every alignment in the input. Only Stockholm files hold more than one.
+    pub fn parse_all<R: Read>(self, reader: R) -> Result<Vec<Alignment>, FormatError> {
+        match self {
+            Self::Stockholm => Ok(crate::stockholm::parser::parse_all(reader)?),
+            _ => Ok(vec![self.parse(reader)?]),
+        }
+    }
+
+    /// Write an alignment in this format.
+    pub fn write<W: Write>(
+        self,
+        alignment: &Alignment,
+        options: &WriteOptions,
+        writer: W,
+    ) -> std::io::Result<()> {
+        let writer = LineEndingWriter::new(writer, options.line_ending);
+        match self {
+            Self::Stockholm => crate::stockholm::writer::write_styled(
+                alignment,
+                options.style,
+                options.wrap,
+                writer,
+            ),
+            Self::Fasta => fasta::write(alignment, &options.gap_chars, writer),
+            Self::Clustal => clustal::write(alignment, &options.gap_chars, writer),
+            Self::A2m => a2m::write(alignment, &options.gap_chars, writer),
+            Self::Phylip => phylip::write(alignment, &options.gap_chars, writer),
+            Self::Msf => msf::write(alignment, &options.gap_chars, writer),
+            Self::Selex => selex::write(alignment, &options.gap_chars, writer),
+        }
+    }
+}
+
+/// Decompress gzip (including bgzip) or bzip2 data, detected from its magic bytes.
+/// Anything else is returned unchanged.
+pub fn decompress(raw: Vec<u8>) -> std::io::Result<Vec<u8>> {
+    let mut contents = Vec::new();
+    if raw.starts_with(&[0x1f, 0x8b]) {
+        flate2::read::MultiGzDecoder::new(raw.as_slice()).read_to_end(&mut contents)?;
+    } else if raw.starts_with(b"BZh") {
+        bzip2::read::MultiBzDecoder::new(raw.as_slice()).read_to_end(&mut contents)?;
+    } else {
+        return Ok(raw);
+    }
+    Ok(contents)
+}
+
+/// Read a file's contents, decompressing gzip or bzip2 data.
+pub fn read_contents(path: &Path) -> std::io::Result<Vec<u8>> {
+    decompress(std::fs::read(path)?)
+}
+
+/// Read every alignment in a file (several for multi-record Stockholm files such
+/// as Rfam.seed) in `format`, or else the format detected from the extension or
+/// contents, with the file's line ending. Gzip- and bzip2-compressed files are
+/// decompressed transparently, and Windows line endings and byte order marks
+/// are removed.
+pub fn read_file(
+    path: &Path,
+    format: Option<Format>,
+) -> Result<(Vec<Alignment>, Format, LineEnding), FormatError> {
+    let contents = read_contents(path)?;
+    let format = format
+        .or_else(|| Format::from_path(path))
+        .unwrap_or_else(|| Format::sniff(&contents));
+    parse_contents(contents, format)
+}
+
+/// Parse every alignment in decompressed file contents.
+fn parse_contents(
+    contents: Vec<u8>,
+    format: Format,
+) -> Result<(Vec<Alignment>, Format, LineEnding), FormatError> {
+    let line_ending = LineEnding::detect(&contents);
+    let contents = normalize_line_endings(contents);
+    Ok((format.parse_all(contents.as_slice())?, format, line_ending))
+}
+
+/// Read an unaligned FASTA file (sequences of different lengths) as it is, for
+/// the editor to pad into an alignment.
+pub fn read_unaligned_fasta(path: &Path) -> Result<Alignment, FormatError> {
+    let contents = normalize_line_endings(read_contents(path)?);
+    Ok(fasta::parse_unaligned(contents.as_slice())?)
+}
+
+/// Read every alignment from standard input in `format`, or else the format
+/// detected (with any compression) from the contents.
+pub fn read_stdin(
+    format: Option<Format>,
+) -> Result<(Vec<Alignment>, Format, LineEnding), FormatError> {
+    let mut raw = Vec::new();
+    std::io::stdin().lock().read_to_end(&mut raw)?;
+    let contents = decompress(raw)?;
+    let format = format.unwrap_or_else(|| Format::sniff(&contents));
+    parse_contents(contents, format)
+}
+
+/// Write an alignment file in `format`, or else the format implied by its extension
+/// (Stockholm by default).
+pub fn write_file(
+    alignment: &Alignment,
+    path: &Path,
+    format: Option<Format>,
+    options: &WriteOptions,
+) -> std::io::Result<Format> {
+    let format = format
+        .or_else(|| Format::from_path(path))
+        .unwrap_or(Format::Stockholm);
+    write_safely(path, options.backup, |file| {
+        format.write(alignment, options, file)
+    })?;
+    Ok(format)
+}
+
+/// Path of the backup kept by `:set backup=on`: the file name with `~` appended.
+pub fn backup_path(path: &Path) -> std::path::PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push("~");
+    name.into()
+}
+
+/// Write a file through a temporary file beside it, renamed over `path` only once
+/// it is complete, so a crash or full disk mid-write leaves the old file intact.
+/// The old file's permissions carry over, a symlinked path writes to its target,
+/// and with `backup` the old contents are kept at [`backup_path`]. A `.gz` or
+/// `.bz2` path is written compressed.
+pub fn write_safely<F>(path: &Path, backup: bool, write: F) -> std::io::Result<()>
+where
+    F: FnOnce(&mut dyn Write) -> std::io::Result<()>,
+{
+    let path = match std::fs::canonicalize(path) {
+        Ok(target) => target,
+        Err(_) => path.to_path_buf(),
+    };
+    let existing = std::fs::metadata(&path).ok();
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+    let temp = path.with_file_name(format!(".{name}.{}.tmp", std::process::id()));
+
+    let result = (|| {
+        let mut file = std::io::BufWriter::new(std::fs::File::create(&temp)?);
+        let extension = path.extension().and_then(|e| e.to_str());
+        match extension.map(str::to_ascii_lowercase).as_deref() {
+            Some("gz") => {
+                let mut gz =
+                    flate2::write::GzEncoder::new(&mut file, flate2::Compression::default());
+                write(&mut gz)?;
+                gz.finish()?;
+            }
+            Some("bz2") => {
+                let mut bz = bzip2::write::BzEncoder::new(&mut file, bzip2::Compression::default());
+                write(&mut bz)?;
+                bz.finish()?;
+            }
+            _ => write(&mut file)?,
+        }
+        let file = file.into_inner().map_err(|e| e.into_error())?;
+        file.sync_all()?;
+        if let Some(metadata) = &existing {
+            std::fs::set_permissions(&temp, metadata.permissions())?;
+            if backup {
+                std::fs::copy(&path, backup_path(&path))?;
+            }
+        }
+        std::fs::rename(&temp, &path)
+    })();
+    if result.is_err() {
+        let _ = std::fs::remove_file(&temp);
+    }
+    result
+}
+
+/// Write several alignments to one multi-record Stockholm file.
+pub fn write_records_file(
+    alignments: &[&Alignment],
+    path: &Path,
+    options: &WriteOptions,
+) -> std::io::Result<()> {
+    write_safely(path, options.backup, |file| {
+        let writer = LineEndingWriter::new(file, options.line_ending);
+        crate::stockholm::writer::write_records(alignments, options.style, options.wrap, writer)
+    })
+}
+
+/// Write an alignment as a JSON file of the data model (export only).
+pub fn write_json_file(alignment: &Alignment, path: &Path) -> std::io::Result<()> {
+    write_safely(path, false, |file| json::write(alignment, file))
+}
+
+/// Write a consensus sequence and its dot-bracket structure (export only).
+pub fn write_dbn_file(
+    name: &str,
+    sequence: &str,
+    structure: &str,
+    path: &Path,
+) -> std::io::Result<()> {
+    write_safely(path, false, |file| {
+        dbn::write(name, sequence, structure, file)
+    })
+}
+
+/// Write an alignment as a NEXUS file (export only).
+pub fn write_nexus_file(
+    alignment: &Alignment,
+    sequence_type: SequenceType,
+    options: &WriteOptions,
+    path: &Path,
+) -> std::io::Result<()> {
+    write_safely(path, false, |file| {
+        nexus::write(alignment, sequence_type, &options.gap_chars, file)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_detection() {
+        assert_eq!(
+            Format::from_path(Path::new("a.sto")),
+            Some(Format::Stockholm)
+        );
+        assert_eq!(Format::from_path(Path::new("a.FASTA")), Some(Format::Fasta));
+        assert_eq!(
+            Format::from_path(Path::new("a.afa.gz")),
+            Some(Format::Fasta)
+        );
+        assert_eq!(
+            Format::from_path(Path::new("RF00005.seed.sto.bz2")),
+            Some(Format::Stockholm)
+        );
+        assert_eq!(Format::from_path(Path::new("alignment")), None);
+        assert_eq!(Format::from_path(Path::new("a.txt")), None);
+
+        assert_eq!(Format::sniff(b"\n>seq1\nACGU\n"), Format::Fasta);
+        assert_eq!(Format::sniff(b"# STOCKHOLM 1.0\n"), Format::Stockholm);
+        assert_eq!(Format::from_path(Path::new("a.aln")), Some(Format::Clustal));
+        assert_eq!(Format::from_path(Path::new("a.a2m")), Some(Format::A2m));
+        assert_eq!(Format::sniff(b" 3 120\nseq1 ACGU"), Format::Phylip);
+        assert_eq!(Format::from_name("PHYLIP"), Some(Format::Phylip));
+        assert_eq!(Format::from_path(Path::new("a.msf")), Some(Format::Msf));
+        assert_eq!(
+            Format::sniff(b"PileUp\n\n MSF: 40  Type: P  Check: 12  ..\n"),
+            Format::Msf
+        );
+        assert_eq!(Format::from_name("nexus"), None);
+        assert_eq!(Format::from_path(Path::new("a.slx")), Some(Format::Selex));
+        assert_eq!(Format::sniff(b"#=ID trna\n\nseq1 ACGU\n"), Format::Selex);
+        assert_eq!(Format::sniff(b"#=GF ID trna\n"), Format::Stockholm);
+        assert_eq!(
+            Format::sniff(b"CLUSTAL W (1.83) multiple sequence alignment\n"),
+            Format::Clustal
+        );
+    }
+
+    #[test]
+    fn test_line_endings() {
+        let fasta = b"\xef\xbb\xbf>seq1\r\nAC-GU\r\n>seq2\r\nACCGU\r\n".to_vec();
+        assert_eq!(LineEnding::detect(&fasta), LineEnding::Crlf);
+        assert_eq!(Format::sniff(&fasta), Format::Fasta);
+        let (alignments, _, line_ending) = parse_contents(fasta, Format::Fasta).unwrap();
+        assert_eq!(line_ending, LineEnding::Crlf);
+        assert_eq!(alignments[0].width(), 5);
+        assert_eq!(alignments[0].sequences[0].data(), "AC-GU");
+
+        let mut buffer = Vec::new();
+        let options = WriteOptions {
+            line_ending: LineEnding::Crlf,
+            ..WriteOptions::default()
+        };
+        Format::Fasta
+            .write(&alignments[0], &options, &mut buffer)
+            .unwrap();
+        assert_eq!(buffer, b">seq1\r\nAC-GU\r\n>seq2\r\nACCGU\r\n");
+        assert_eq!(LineEnding::detect(b">seq1\nACGU\n"), LineEnding::Lf);
+    }
+
+    #[test]
+    fn test_writers_use_the_gap_chars_option() {
+        let alignment = crate::stockholm::parser::parse(
+            "# STOCKHOLM 1.0\nseq1 AC*GU\nseq2 A.CGU\n//\n".as_bytes(),
+        )
+        .unwrap();
+        let mut options = WriteOptions::default();
+        options.gap_chars.push('*');
+        let mut buffer = Vec::new();
+        Format::Phylip
+            .write(&alignment, &options, &mut buffer)
+            .unwrap();
+        assert_eq!(buffer, b"2 5\nseq1  AC-GU\nseq2  A-CGU\n");
+
+        let mut buffer = Vec::new();
+        Format::Fasta
+            .write(&alignment, &options, &mut buffer)
+            .unwrap();
+        assert_eq!(buffer, b">seq1\nAC-GU\n>seq2\nA-CGU\n");
+    }
+
+    #[test]
+    fn test_write_safely() {
+        let dir = std::env::temp_dir().join(format!("aform-write-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("seed.sto");
+        std::fs::write(&path, "old\n").unwrap();
+
+        write_safely(&path, true, |file| file.write_all(b"new\n")).unwrap();
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "new\n");
+        assert_eq!(
+            std::fs::read_to_string(backup_path(&path)).unwrap(),
+            "old\n"
+        );
+
+        // A failed write leaves the file as it was, and no temporary file behind
+        let failed = write_safely(&path, false, |file| {
+            file.write_all(b"partial")?;
+            Err(std::io::Error::other("disk full"))
+        });
+        assert!(failed.is_err());
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "new\n");
+        assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 2);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_decompress() {
+        use std::io::Write;
+
+        let text = b"# STOCKHOLM 1.0\nseq1 ACGU\n//\n";
+        let mut gz = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+        gz.write_all(text).unwrap();
+        assert_eq!(decompress(gz.finish().unwrap()).unwrap(), text);
+
+        let mut bz = bzip2::write::BzEncoder::new(Vec::new(), bzip2::Compression::default());
+        bz.write_all(text).unwrap();
+        assert_eq!(decompress(bz.finish().unwrap()).unwrap(), text);
+
+        assert_eq!(decompress(text.to_vec()).unwrap(), text);
+    }
+
+    #[test]
+    fn test_write_compressed() {
+        let dir = std::env::temp_dir().join(format!("aform-compress-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let text = b"# STOCKHOLM 1.0\nseq1 ACGU\n//\n";
+        for (name, magic) in [("seed.sto.gz", &b"\x1f\x8b"[..]), ("seed.sto.bz2", b"BZh")] {
+            let path = dir.join(name);
+            write_safely(&path, false, |file| file.write_all(text)).unwrap();
+            assert!(std::fs::read(&path).unwrap().starts_with(magic));
+            assert_eq!(read_contents(&path).unwrap(), text);
+        }
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,264 @@
+//! GCG MSF format.
+//!
+//! The header lists each sequence with its length, GCG checksum and weight, and
+//! `//` separates it from interleaved blocks of residues. Weights other than 1.00
+//! map to `#=GS <id> WT`. Gaps are written as `.`.
+
+use std::io::{BufRead, BufReader, Read, Write};
+use std::rc::Rc;
+
+use thiserror::Error;
+
+use crate::color::detect_sequence_type;
+use crate::stockholm::{Alignment, Sequence, SequenceAnnotation, SequenceType};
+
+/// Residues per block and per space-separated group when writing.
+const BLOCK_WIDTH: usize = 50;
+const GROUP_WIDTH: usize = 10;
+
+#[derive(Error, Debug)]
+pub enum MsfError {
+    #[error("IO error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Invalid MSF header (expected Name: lines followed by //)")]
+    InvalidHeader,
+    #[error("{id} has {found} columns, expected {expected}")]
+    Length {
+        id: String,
+        expected: usize,
+        found: usize,
+    },
+}
+
+/// Whether text looks like the start of an MSF file: a `!!NA_MULTIPLE_ALIGNMENT`
+/// style first line, or a header line with `MSF:` ending in `..`.
+pub fn is_header(text: &str) -> bool {
+    let lines: Vec<&str> = text
+        .lines()
+        .map(str::trim)
+        .filter(|l| !l.is_empty())
+        .take(4)
+        .collect();
+    lines
+        .first()
+        .is_some_and(|l| l.starts_with("!!") && l.contains("MULTIPLE_ALIGNMENT"))
+        || lines
+            .iter()
+            .any(|l| l.contains("MSF:") && l.ends_with(".."))
+}
+
+/// GCG checksum of a sequence: position-weighted sum of uppercase characters.
+pub fn checksum(residues: &str) -> u32 {
+    residues
+        .chars()
+        .enumerate()
+        .map(|(i, c)| (i as u32 % 57 + 1) * c.to_ascii_uppercase() as u32)
+        .sum::<u32>()
+        % 10000
+}
+
+/// Value following `key` among whitespace-separated header fields.
+fn field<'a>(line: &'a str, key: &str) -> Option<&'a str> {
+    let mut fields = line.split_whitespace();
+    fields.find(|f| *f == key)?;
+    fields.next()
+}
+
+/// Parse an MSF alignment from a reader.
+pub fn parse<R: Read>(reader: R) -> Result<Alignment, MsfError> {
+    // Sequence names, lengths and weights from the header
+    let mut entries: Vec<(String, Option<usize>, Option<String>)> = Vec::new();
+    let mut data: Vec<String> = Vec::new();
+    let mut in_body = false;
+
+    for line in BufReader::new(reader).lines() {
+        let line = line?;
+        if !in_body {
+            if line.trim_start().starts_with("//") {
+                in_body = true;
+            } else if let Some(name) = field(&line, "Name:") {
+                let length = field(&line, "Len:").and_then(|l| l.parse().ok());
+                let weight = field(&line, "Weight:").map(str::to_string);
+                entries.push((name.to_string(), length, weight));
+                data.push(String::new());
+            }
+            continue;
+        }
+        let mut parts = line.split_whitespace();
+        let Some(name) = parts.next() else {
+            continue;
+        };
+        // Position-number lines and anything else not naming a sequence are skipped
+        if let Some(i) = entries.iter().position(|(n, _, _)| n == name) {
+            data[i].extend(parts.flat_map(str::chars));
+        }
+    }
+    if !in_body || entries.is_empty() {
+        return Err(MsfError::InvalidHeader);
+    }
+
+    let width = data[0].chars().count();
+    let mut alignment = Alignment::new();
+    for ((id, length, weight), residues) in entries.into_iter().zip(data) {
+        let found = residues.chars().count();
+        let expected = length.filter(|&l| l != found).unwrap_or(width);
+        if found != expected {
+            return Err(MsfError::Length {
+                id,
+                expected,
+                found,
+            });
+        }
+        if let Some(weight) = weight.filter(|w| w.parse::<f64>().is_ok_and(|w| w != 1.0)) {
+            alignment
+                .sequence_annotations
+                .entry(id.clone())
+                .or_default()
+                .push(SequenceAnnotation {
+                    tag: "WT".to_string(),
+                    value: weight,
+                });
+        }
+        alignment
+            .sequences
+            .push(Rc::new(Sequence::new(id, residues)));
+    }
+    Ok(alignment)
+}
+
+/// Write an alignment in MSF format, with GCG checksums in the header and
+/// `gap_chars` written as `.`.
+pub fn write<W: Write>(
+    alignment: &Alignment,
+    gap_chars: &[char],
+    mut writer: W,
+) -> std::io::Result<()> {
+    let rows: Vec<String> = alignment
+        .sequences
+        .iter()
+        .map(|seq| {
+            seq.chars()
+                .iter()
+                .map(|&c| if gap_chars.contains(&c) { '.' } else { c })
+                .collect()
+        })
+        .collect();
+    let checks: Vec<u32> = rows.iter().map(|row| checksum(row)).collect();
+    let total = checks.iter().sum::<u32>() % 10000;
+    let width = alignment.width();
+    let (kind, type_code) = match detect_sequence_type(alignment, gap_chars) {
+        SequenceType::Protein => ("AA", 'P'),
+        SequenceType::RNA | SequenceType::DNA => ("NA", 'N'),
+    };
+
+    writeln!(writer, "!!{kind}_MULTIPLE_ALIGNMENT 1.0")?;
+    writeln!(writer)?;
+    writeln!(
+        writer,
+        " MSF: {width}  Type: {type_code}  Check: {total:4}  .."
+    )?;
+    writeln!(writer)?;
+    let id_width = alignment.max_id_len();
+    for (seq, check) in alignment.sequences.iter().zip(&checks) {
+        let weight = alignment
+            .sequence_annotations
+            .get(&seq.id)
+            .and_then(|anns| anns.iter().find(|a| a.tag == "WT"))
+            .and_then(|a| a.value.parse::<f64>().ok())
+            .unwrap_or(1.0);
+        writeln!(
+            writer,
+            " Name: {:id_width$}  Len: {width:5}  Check: {check:4}  Weight: {weight:.2}",
+            seq.id
+        )?;
+    }
+    writeln!(writer)?;
+    writeln!(writer, "//")?;
+
+    let mut start = 0;
+    while start < width {
+        let end = (start + BLOCK_WIDTH).min(width);
+        writeln!(writer)?;
+        for (seq, row) in alignment.sequences.iter().zip(&rows) {
+            let chars: Vec<char> = row.chars().collect();
+            let groups: Vec<String> = chars[start.min(chars.len())..end.min(chars.len())]
+                .chunks(GROUP_WIDTH)
+                .map(|group| group.iter().collect())
+                .collect();
+            writeln!(writer, "{:id_width$}  {}", seq.id, groups.join(" "))?;
+        }
+        start = end;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::stockholm::DEFAULT_GAP_CHARS;
+
+    const MSF: &str = "\
+PileUp
+
+   MSF: 12  Type: N  Check: 1234  ..
+
+ Name: seq1  Len: 12  Check: 1000  Weight: 1.00
+ Name: seq2  Len: 12  Check: 2000  Weight: 0.50
+
+//
+
+           1                12
+seq1  ACGUACGUAC GU
+seq2  ACG.ACGU~~ ~~
+";
+
+    #[test]
+    fn test_parse_msf() {
+        assert!(is_header(MSF));
+        assert!(is_header("!!NA_MULTIPLE_ALIGNMENT 1.0\n"));
+        assert!(!is_header("# STOCKHOLM 1.0\n"));
+
+        let alignment = parse(MSF.as_bytes()).unwrap();
+        assert_eq!(alignment.sequences[0].data(), "ACGUACGUACGU");
+        assert_eq!(alignment.sequences[1].data(), "ACG.ACGU~~~~");
+        assert!(!alignment.sequence_annotations.contains_key("seq1"));
+        assert_eq!(alignment.sequence_annotations["seq2"][0].value, "0.50");
+
+        assert!(matches!(
+            parse(
+                MSF.replace("Len: 12  Check: 1000", "Len: 13  Check: 1000")
+                    .as_bytes()
+            ),
+            Err(MsfError::Length { expected: 13, .. })
+        ));
+        assert!(matches!(
+            parse("seq1 ACGU\n".as_bytes()),
+            Err(MsfError::InvalidHeader)
+        ));
+    }
+
+    #[test]
+    fn test_write_msf() {
+        assert_eq!(checksum("ACGU"), 65 + 2 * 67 + 3 * 71 + 4 * 85);
+        assert_eq!(checksum("acgu"), checksum("ACGU"));
+
+        let alignment = parse(MSF.as_bytes()).unwrap();
+        let mut buffer = Vec::new();
+        write(&alignment, &DEFAULT_GAP_CHARS, &mut buffer).unwrap();
+        let text = String::from_utf8(buffer).unwrap();
+        let check1 = checksum("ACGUACGUACGU");
+        let check2 = checksum("ACG.ACGU....");
+        assert!(text.starts_with("!!NA_MULTIPLE_ALIGNMENT 1.0\n"));
+        assert!(text.contains(&format!(
+            " MSF: 12  Type: N  Check: {:4}  ..\n",
+            (check1 + check2) % 10000
+        )));
+        assert!(text.contains(&format!(
+            " Name: seq2  Len:    12  Check: {check2:4}  Weight: 0.50\n"
+        )));
+        assert!(text.ends_with("seq1  ACGUACGUAC GU\nseq2  ACG.ACGU.. ..\n"));
+
+        let reparsed = parse(text.as_bytes()).unwrap();
+        assert_eq!(reparsed.sequences[1].data(), "ACG.ACGU....");
+    }
+}
@@ -0,0 +1,102 @@
+//! NEXUS export (DATA block) for phylogenetics programs such as MrBayes and PAUP*.
+
+use std::io::Write;
+
+use crate::stockholm::{Alignment, SequenceType};
+
+/// NEXUS `DATATYPE` for a sequence type.
+fn datatype(sequence_type: SequenceType) -> &'static str {
+    match sequence_type {
+        SequenceType::RNA => "RNA",
+        SequenceType::DNA => "DNA",
+        SequenceType::Protein => "PROTEIN",
+    }
+}
+
+/// Quote a taxon name if it contains whitespace or NEXUS punctuation.
+fn taxon_name(id: &str) -> String {
+    let plain = id
+        .chars()
+        .all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '.' | '|' | '#' | '@' | '!'));
+    if plain && !id.is_empty() {
+        id.to_string()
+    } else {
+        format!("'{}'", id.replace('\'', "''"))
+    }
+}
+
+/// Write an alignment as a NEXUS DATA block, one sequence per matrix line, with
+/// `gap_chars` written as the NEXUS gap symbol.
+pub fn write<W: Write>(
+    alignment: &Alignment,
+    sequence_type: SequenceType,
+    gap_chars: &[char],
+    mut writer: W,
+) -> std::io::Result<()> {
+    let names: Vec<String> = alignment
+        .sequences
+        .iter()
+        .map(|s| taxon_name(&s.id))
+        .collect();
+    let name_width = names.iter().map(String::len).max().unwrap_or(0) + 2;
+
+    writeln!(writer, "#NEXUS")?;
+    writeln!(writer)?;
+    writeln!(writer, "BEGIN DATA;")?;
+    writeln!(
+        writer,
+        "  DIMENSIONS NTAX={} NCHAR={};",
+        alignment.num_sequences(),
+        alignment.width()
+    )?;
+    writeln!(
+        writer,
+        "  FORMAT DATATYPE={} MISSING=? GAP=-;",
+        datatype(sequence_type)
+    )?;
+    writeln!(writer, "  MATRIX")?;
+    for (name, seq) in names.iter().zip(&alignment.sequences) {
+        let data: String = seq
+            .chars()
+            .iter()
+            .map(|&c| if gap_chars.contains(&c) { '-' } else { c })
+            .collect();
+        writeln!(writer, "    {name:name_width$}{data}")?;
+    }
+    writeln!(writer, "  ;")?;
+    writeln!(writer, "END;")?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::rc::Rc;
+
+    use super::*;
+    use crate::stockholm::{DEFAULT_GAP_CHARS, Sequence};
+
+    #[test]
+    fn test_write_nexus() {
+        let mut alignment = Alignment::new();
+        for (id, data) in [("seq1/1-4", "AC.GU"), ("seq_2", "~ACGU")] {
+            alignment.sequences.push(Rc::new(Sequence::new(id, data)));
+        }
+        let mut buffer = Vec::new();
+        write(
+            &alignment,
+            SequenceType::RNA,
+            &DEFAULT_GAP_CHARS,
+            &mut buffer,
+        )
+        .unwrap();
+        let text = String::from_utf8(buffer).unwrap();
+        assert!(text.starts_with("#NEXUS\n"));
+        assert!(text.contains("DIMENSIONS NTAX=2 NCHAR=5;"));
+        assert!(text.contains("FORMAT DATATYPE=RNA MISSING=? GAP=-;"));
+        assert!(text.contains("    'seq1/1-4'  AC-GU\n"));
+        assert!(text.contains("    seq_2       -ACGU\n"));
+        assert!(text.ends_with("  ;\nEND;\n"));
+
+        assert_eq!(taxon_name("it's"), "'it''s'");
+    }
+}
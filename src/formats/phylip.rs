@@ -0,0 +1,178 @@
+//! Relaxed PHYLIP format.
+//!
+//! Reads sequential (one sequence after another, possibly over several lines) and
+//! interleaved layouts. IDs may be any length but cannot contain whitespace. Writes
+//! sequential relaxed PHYLIP, one line per sequence, with `-` for every gap.
+
+use std::io::{BufRead, BufReader, Read, Write};
+use std::rc::Rc;
+
+use thiserror::Error;
+
+use crate::stockholm::{Alignment, Sequence};
+
+#[derive(Error, Debug)]
+pub enum PhylipError {
+    #[error("IO error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Invalid PHYLIP header (expected sequence and column counts)")]
+    InvalidHeader,
+    #[error(
+        "Expected {expected} sequences of length {length}, could not read them as sequential or interleaved"
+    )]
+    Layout { expected: usize, length: usize },
+}
+
+/// Sequence and column counts from a PHYLIP header line (`<sequences> <columns>`).
+pub fn parse_header(line: &str) -> Option<(usize, usize)> {
+    let mut fields = line.split_whitespace();
+    let count = fields.next()?.parse().ok()?;
+    let length = fields.next()?.parse().ok()?;
+    fields.next().is_none().then_some((count, length))
+}
+
+/// Split a line into its ID and residues (whitespace inside residues is ignored).
+fn id_line(line: &str) -> Option<(String, String)> {
+    let mut fields = line.split_whitespace();
+    let id = fields.next()?.to_string();
+    Some((id, fields.collect()))
+}
+
+/// Sequential layout: each ID line is followed by continuation lines until the
+/// sequence reaches `length`.
+fn read_sequential(lines: &[String], count: usize, length: usize) -> Option<Vec<(String, String)>> {
+    let mut records: Vec<(String, String)> = Vec::new();
+    for line in lines {
+        match records.last_mut() {
+            Some((_, data)) if data.chars().count() < length => {
+                data.extend(line.chars().filter(|c| !c.is_whitespace()));
+            }
+            _ => records.push(id_line(line)?),
+        }
+    }
+    (records.len() == count && records.iter().all(|(_, d)| d.chars().count() == length))
+        .then_some(records)
+}
+
+/// Interleaved layout: the first block has IDs, later blocks continue each
+/// sequence in the same order.
+fn read_interleaved(
+    lines: &[String],
+    count: usize,
+    length: usize,
+) -> Option<Vec<(String, String)>> {
+    if count == 0 || lines.len() < count {
+        return None;
+    }
+    let mut records = lines[..count]
+        .iter()
+        .map(|line| id_line(line))
+        .collect::<Option<Vec<_>>>()?;
+    for (i, line) in lines[count..].iter().enumerate() {
+        records[i % count]
+            .1
+            .extend(line.chars().filter(|c| !c.is_whitespace()));
+    }
+    records
+        .iter()
+        .all(|(_, d)| d.chars().count() == length)
+        .then_some(records)
+}
+
+/// Parse a relaxed PHYLIP alignment from a reader.
+pub fn parse<R: Read>(reader: R) -> Result<Alignment, PhylipError> {
+    let mut lines = Vec::new();
+    for line in BufReader::new(reader).lines() {
+        let line = line?;
+        if !line.trim().is_empty() {
+            lines.push(line);
+        }
+    }
+    let (count, length) = lines
+        .first()
+        .and_then(|header| parse_header(header))
+        .ok_or(PhylipError::InvalidHeader)?;
+
+    let body = &lines[1..];
+    let records = read_sequential(body, count, length)
+        .or_else(|| read_interleaved(body, count, length))
+        .ok_or(PhylipError::Layout {
+            expected: count,
+            length,
+        })?;
+
+    let mut alignment = Alignment::new();
+    for (id, data) in records {
+        alignment.sequences.push(Rc::new(Sequence::new(id, data)));
+    }
+    Ok(alignment)
+}
+
+/// Write an alignment as sequential relaxed PHYLIP, with `gap_chars` written as `-`.
+pub fn write<W: Write>(
+    alignment: &Alignment,
+    gap_chars: &[char],
+    mut writer: W,
+) -> std::io::Result<()> {
+    writeln!(
+        writer,
+        "{} {}",
+        alignment.num_sequences(),
+        alignment.width()
+    )?;
+    let id_width = alignment.max_id_len() + 2;
+    for seq in &alignment.sequences {
+        let data: String = seq
+            .chars()
+            .iter()
+            .map(|&c| if gap_chars.contains(&c) { '-' } else { c })
+            .collect();
+        writeln!(writer, "{:id_width$}{data}", seq.id)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::stockholm::DEFAULT_GAP_CHARS;
+
+    #[test]
+    fn test_parse_sequential() {
+        let text = " 2 12\nseq_one_long_id ACGUACGU\nACGU\nseq2 ACGU-CGU ACGU\n";
+        let alignment = parse(text.as_bytes()).unwrap();
+        assert_eq!(alignment.sequences[0].id, "seq_one_long_id");
+        assert_eq!(alignment.sequences[0].data(), "ACGUACGUACGU");
+        assert_eq!(alignment.sequences[1].data(), "ACGU-CGUACGU");
+    }
+
+    #[test]
+    fn test_parse_interleaved() {
+        let text = "2 10\na ACGUA\nb ACG-A\n\nCGUAC\nCGU-C\n";
+        let alignment = parse(text.as_bytes()).unwrap();
+        assert_eq!(alignment.sequences[0].data(), "ACGUACGUAC");
+        assert_eq!(alignment.sequences[1].data(), "ACG-ACGU-C");
+
+        assert!(matches!(
+            parse("2 10\na ACGUA\n".as_bytes()),
+            Err(PhylipError::Layout { expected: 2, .. })
+        ));
+        assert!(matches!(
+            parse(">a\nACGU\n".as_bytes()),
+            Err(PhylipError::InvalidHeader)
+        ));
+    }
+
+    #[test]
+    fn test_write_phylip() {
+        let mut alignment = Alignment::new();
+        for (id, data) in [("a", "AC.GU"), ("bb", "A~CGU")] {
+            alignment.sequences.push(Rc::new(Sequence::new(id, data)));
+        }
+        let mut buffer = Vec::new();
+        write(&alignment, &DEFAULT_GAP_CHARS, &mut buffer).unwrap();
+        let text = String::from_utf8(buffer).unwrap();
+        assert_eq!(text, "2 5\na   AC-GU\nbb  A-CGU\n");
+        assert_eq!(parse(text.as_bytes()).unwrap().sequences[1].data(), "A-CGU");
+    }
+}
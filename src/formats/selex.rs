@@ -0,0 +1,476 @@
+//! SELEX format (HMMER 2 and older alignment archives).
+//!
+//! Blocks of `name  residues` lines separated by blank lines, with markup lines
+//! instead of Stockholm's `#=G*` tags: `#=RF` becomes `#=GC RF`, `#=CS` becomes
+//! `#=GC SS_cons`, and `#=SS`/`#=SA` after a sequence become its `#=GR SS`/`SA`.
+//! `#=ID`, `#=AC`, `#=DE`, `#=AU`, `#=GA`, `#=TC` and `#=NC` become `#=GF` lines,
+//! and the weight and description of `#=SQ` lines become `#=GS WT` and `DE`.
+//!
+//! Residues may be split by spaces, which are gaps: columns are placed by their
+//! position in the line, starting where the block's sequence data starts.
+
+use std::collections::HashMap;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::rc::Rc;
+
+use thiserror::Error;
+
+use crate::stockholm::{
+    Alignment, ColumnAnnotation, FileAnnotation, ResidueAnnotation, Sequence, SequenceAnnotation,
+};
+
+/// Columns per block when writing.
+const BLOCK_WIDTH: usize = 50;
+
+/// Markup lines copied to `#=GF` lines of the same tag.
+const FILE_TAGS: [&str; 7] = ["ID", "AC", "DE", "AU", "GA", "TC", "NC"];
+
+#[derive(Error, Debug)]
+pub enum SelexError {
+    #[error("IO error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("No sequences found")]
+    Empty,
+    #[error("#={0} line without a sequence above it (line {1})")]
+    OrphanMarkup(String, usize),
+    #[error("{id} has {found} columns, expected {expected}")]
+    Length {
+        id: String,
+        expected: usize,
+        found: usize,
+    },
+}
+
+/// Whether text looks like the start of a SELEX file: its first line is a SELEX
+/// markup line such as `#=ID` or `#=RF` (not a Stockholm `#=GF`-style tag).
+pub fn is_header(text: &str) -> bool {
+    text.lines()
+        .map(str::trim_end)
+        .find(|l| !l.is_empty())
+        .and_then(|l| l.strip_prefix("#="))
+        .is_some_and(|rest| {
+            let tag = rest.split_whitespace().next().unwrap_or_default();
+            matches!(tag, "RF" | "CS" | "SQ") || FILE_TAGS.contains(&tag)
+        })
+}
+
+/// What a line of a block holds.
+enum Row {
+    /// Residues of the named sequence.
+    Sequence(String),
+    /// `#=RF`, `#=CS`, `#=SS` or `#=SA` markup (tag without `#=`).
+    Markup(String),
+}
+
+/// One line of a block, with the data after its name field.
+struct BlockLine {
+    row: Row,
+    number: usize,
+    chars: Vec<char>,
+    /// Character index where the name field ends and where the data starts.
+    name_end: usize,
+    data_start: usize,
+}
+
+/// Alignment being assembled block by block.
+#[derive(Default)]
+struct Builder {
+    alignment: Alignment,
+    index: HashMap<String, usize>,
+    /// Per-sequence markup, as (sequence, tag) -> data.
+    residue: Vec<((usize, String), String)>,
+    rf: String,
+    cs: String,
+}
+
+impl Builder {
+    fn add_block(&mut self, block: &[BlockLine]) -> Result<(), SelexError> {
+        // Lines whose data is a single token are read token by token; otherwise
+        // columns are placed by position, starting at the leftmost data column
+        let tokens = block.iter().all(|line| {
+            !line.chars[line.data_start..]
+                .iter()
+                .any(|c| c.is_whitespace())
+        });
+        let start = block.iter().map(|l| l.data_start).min().unwrap_or(0);
+        let positional = |line: &BlockLine| -> Vec<char> {
+            line.chars[start.max(line.name_end)..]
+                .iter()
+                .map(|&c| if c.is_whitespace() { '.' } else { c })
+                .collect()
+        };
+        let rows: Vec<Vec<char>> = block
+            .iter()
+            .map(|line| {
+                if tokens {
+                    line.chars[line.data_start..].to_vec()
+                } else {
+                    positional(line)
+                }
+            })
+            .collect();
+        let width = rows.iter().map(Vec::len).max().unwrap_or(0);
+
+        let mut previous: Option<usize> = None;
+        for (line, mut data) in block.iter().zip(rows) {
+            // Trailing spaces were trimmed from positional lines, and markup may
+            // stop short, but a short token is a sequence of the wrong length
+            if !tokens || matches!(line.row, Row::Markup(_)) {
+                data.resize(width, '.');
+            }
+            match &line.row {
+                Row::Sequence(id) => {
+                    let row = match self.index.get(id) {
+                        Some(&row) => row,
+                        None => {
+                            self.alignment
+                                .sequences
+                                .push(Rc::new(Sequence::new(id.clone(), "")));
+                            self.index
+                                .insert(id.clone(), self.alignment.sequences.len() - 1);
+                            self.alignment.sequences.len() - 1
+                        }
+                    };
+                    Rc::make_mut(&mut self.alignment.sequences[row])
+                        .chars_mut()
+                        .extend(data);
+                    previous = Some(row);
+                }
+                Row::Markup(tag) if tag == "RF" => self.rf.extend(data),
+                Row::Markup(tag) if tag == "CS" => self.cs.extend(data),
+                Row::Markup(tag) => {
+                    let row = previous
+                        .ok_or_else(|| SelexError::OrphanMarkup(tag.clone(), line.number))?;
+                    let end = self.alignment.sequences[row].len();
+                    let key = (row, tag.clone());
+                    let i = match self.residue.iter().position(|(k, _)| *k == key) {
+                        Some(i) => i,
+                        None => {
+                            self.residue.push((key, String::new()));
+                            self.residue.len() - 1
+                        }
+                    };
+                    // Blocks without this markup for the sequence count as gaps
+                    let text = &mut self.residue[i].1;
+                    let have = text.chars().count();
+                    let before = end - width;
+                    text.extend(std::iter::repeat_n('.', before.saturating_sub(have)));
+                    text.extend(data);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Record the weight and description from a `#=SQ name weight source id
+    /// start end length description` line.
+    fn add_sequence_header(&mut self, value: &str) {
+        let fields: Vec<&str> = value.split_whitespace().collect();
+        let Some(&id) = fields.first() else {
+            return;
+        };
+        let mut annotations = Vec::new();
+        if let Some(weight) = fields
+            .get(1)
+            .filter(|w| w.parse::<f64>().is_ok_and(|w| w != 1.0))
+        {
+            annotations.push(("WT", weight.to_string()));
+        }
+        let description = fields.get(7..).unwrap_or_default().join(" ");
+        if !description.is_empty() && description != "-" {
+            annotations.push(("DE", description));
+        }
+        for (tag, value) in annotations {
+            self.alignment
+                .sequence_annotations
+                .entry(id.to_string())
+                .or_default()
+                .push(SequenceAnnotation {
+                    tag: tag.to_string(),
+                    value,
+                });
+        }
+    }
+
+    fn finish(mut self) -> Result<Alignment, SelexError> {
+        let width = self.alignment.width();
+        if self.alignment.sequences.is_empty() {
+            return Err(SelexError::Empty);
+        }
+        if let Some(seq) = self.alignment.sequences.iter().find(|s| s.len() != width) {
+            return Err(SelexError::Length {
+                id: seq.id.clone(),
+                expected: width,
+                found: seq.len(),
+            });
+        }
+        for ((row, tag), mut data) in self.residue {
+            let len = data.chars().count();
+            data.extend(std::iter::repeat_n('.', width.saturating_sub(len)));
+            let id = self.alignment.sequences[row].id.clone();
+            self.alignment
+                .residue_annotations
+                .entry(id)
+                .or_default()
+                .push(ResidueAnnotation { tag, data });
+        }
+        for (tag, data) in [("SS_cons", self.cs), ("RF", self.rf)] {
+            if !data.is_empty() {
+                self.alignment.column_annotations.push(ColumnAnnotation {
+                    tag: tag.to_string(),
+                    data,
+                });
+            }
+        }
+        Ok(self.alignment)
+    }
+}
+
+/// Parse a SELEX alignment from a reader.
+pub fn parse<R: Read>(reader: R) -> Result<Alignment, SelexError> {
+    let mut builder = Builder::default();
+    let mut block: Vec<BlockLine> = Vec::new();
+    for (i, line) in BufReader::new(reader).lines().enumerate() {
+        let line = line?;
+        let line = line.trim_end();
+        if line.is_empty() {
+            builder.add_block(&block)?;
+            block.clear();
+            continue;
+        }
+        let (row, name_len) = if let Some(rest) = line.strip_prefix("#=") {
+            let tag = rest.split_whitespace().next().unwrap_or_default();
+            let value = rest[tag.len()..].trim();
+            match tag {
+                "RF" | "CS" | "SS" | "SA" => (Row::Markup(tag.to_string()), tag.len() + 2),
+                _ if FILE_TAGS.contains(&tag) => {
+                    builder.alignment.file_annotations.push(FileAnnotation {
+                        tag: tag.to_string(),
+                        value: value.to_string(),
+                    });
+                    continue;
+                }
+                "SQ" => {
+                    builder.add_sequence_header(value);
+                    continue;
+                }
+                _ => continue,
+            }
+        } else if line.starts_with('#') {
+            continue;
+        } else {
+            let name = line.split_whitespace().next().unwrap_or_default();
+            (Row::Sequence(name.to_string()), name.chars().count())
+        };
+        let chars: Vec<char> = line.chars().collect();
+        let data_start = (name_len..chars.len())
+            .find(|&c| !chars[c].is_whitespace())
+            .unwrap_or(chars.len());
+        block.push(BlockLine {
+            row,
+            number: i + 1,
+            chars,
+            name_end: name_len,
+            data_start,
+        });
+    }
+    builder.add_block(&block)?;
+    builder.finish()
+}
+
+/// Write an alignment as SELEX: `#=GF` ID/AC/DE/AU lines as header markup, then
+/// blocks of 50 columns with `#=RF`, each sequence with its `#=SS` and `#=SA`,
+/// and `#=CS`. `gap_chars` in sequences are written as `.`; markup is written
+/// as it is. A row that isn't the alignment width is an error.
+pub fn write<W: Write>(
+    alignment: &Alignment,
+    gap_chars: &[char],
+    mut writer: W,
+) -> std::io::Result<()> {
+    let width = alignment.width();
+    let markup_rows = [("RF", alignment.rf()), ("SS_cons", alignment.ss_cons())]
+        .into_iter()
+        .filter_map(|(tag, data)| Some((tag.to_string(), data?)))
+        .chain(alignment.sequences.iter().flat_map(|seq| {
+            residue_markup(alignment, &seq.id)
+                .map(|ann| (format!("{} {}", seq.id, ann.tag), ann.data.as_str()))
+        }));
+    let rows = alignment
+        .sequences
+        .iter()
+        .map(|seq| (seq.id.clone(), seq.len()))
+        .chain(markup_rows.map(|(id, data)| (id, data.chars().count())));
+    for (id, found) in rows {
+        if found != width {
+            let error = SelexError::Length {
+                id,
+                expected: width,
+                found,
+            };
+            return Err(std::io::Error::new(std::io::ErrorKind::InvalidData, error));
+        }
+    }
+
+    for ann in &alignment.file_annotations {
+        if FILE_TAGS.contains(&ann.tag.as_str()) {
+            writeln!(writer, "#={} {}", ann.tag, ann.value)?;
+        }
+    }
+    if !alignment.file_annotations.is_empty() {
+        writeln!(writer)?;
+    }
+
+    let name_width = alignment.max_id_len().max("#=RF".len());
+    let chunk = |data: &str, start: usize| -> String {
+        data.chars().skip(start).take(BLOCK_WIDTH).collect()
+    };
+    let markup = |tag: &str, data: &str, start: usize| {
+        format!("{:name_width$} {}", format!("#={tag}"), chunk(data, start))
+    };
+    for start in (0..width).step_by(BLOCK_WIDTH) {
+        if start > 0 {
+            writeln!(writer)?;
+        }
+        if let Some(rf) = alignment.rf() {
+            writeln!(writer, "{}", markup("RF", rf, start))?;
+        }
+        for seq in &alignment.sequences {
+            let residues: String = chunk(&seq.data(), start)
+                .chars()
+                .map(|c| if gap_chars.contains(&c) { '.' } else { c })
+                .collect();
+            writeln!(writer, "{:name_width$} {residues}", seq.id)?;
+            for ann in residue_markup(alignment, &seq.id) {
+                writeln!(writer, "{}", markup(&ann.tag, &ann.data, start))?;
+            }
+        }
+        if let Some(cs) = alignment.ss_cons() {
+            writeln!(writer, "{}", markup("CS", cs, start))?;
+        }
+    }
+    Ok(())
+}
+
+/// A sequence's `#=GR` lines that SELEX has markup for: SS, then SA.
+fn residue_markup<'a>(
+    alignment: &'a Alignment,
+    id: &str,
+) -> impl Iterator<Item = &'a ResidueAnnotation> {
+    let annotations = alignment.residue_annotations.get(id);
+    ["SS", "SA"]
+        .into_iter()
+        .filter_map(move |tag| annotations.and_then(|anns| anns.iter().find(|a| a.tag == tag)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::stockholm::DEFAULT_GAP_CHARS;
+
+    const SELEX: &str = "\
+#=ID trna
+#=AU hand curated
+#=SQ seq1 0.50 - - 1 10 10 first tRNA
+#=SQ seq2 1.00 - - 1 9 9 -
+# a comment
+
+#=RF      xxxxx.xx
+seq1      ACGUA.GC
+#=SS      >>.<<...
+seq2      AC-UAAGC
+#=CS      >>.<<...
+
+#=RF      xx
+seq1      UU
+seq2      U.
+#=SS      ..
+#=CS      ..
+";
+
+    #[test]
+    fn test_parse_selex() {
+        let alignment = parse(SELEX.as_bytes()).unwrap();
+        assert_eq!(alignment.num_sequences(), 2);
+        assert_eq!(alignment.sequences[0].data(), "ACGUA.GCUU");
+        assert_eq!(alignment.sequences[1].data(), "AC-UAAGCU.");
+        assert_eq!(alignment.get_file_annotation("ID"), Some("trna"));
+        assert_eq!(alignment.get_file_annotation("AU"), Some("hand curated"));
+        assert_eq!(alignment.rf(), Some("xxxxx.xxxx"));
+        let seq1 = &alignment.sequence_annotations["seq1"];
+        assert_eq!(
+            (seq1[0].tag.as_str(), seq1[0].value.as_str()),
+            ("WT", "0.50")
+        );
+        assert_eq!(seq1[1].value, "first tRNA");
+        assert!(!alignment.sequence_annotations.contains_key("seq2"));
+        assert_eq!(alignment.ss_cons(), Some(">>.<<....."));
+        // seq1's structure, and seq2's from the second block only
+        assert_eq!(alignment.residue_annotations["seq1"][0].data, ">>.<<.....");
+        assert_eq!(alignment.residue_annotations["seq2"][0].data, "..........");
+        assert!(is_header(SELEX));
+        assert!(!is_header("# STOCKHOLM 1.0\n#=GF ID x\n"));
+    }
+
+    #[test]
+    fn test_parse_selex_spaces() {
+        // Spaces inside the data are gaps, placed by column
+        let alignment = parse("a    AC GU\nbb   ACCGU\n#=CS >> <<\n".as_bytes()).unwrap();
+        assert_eq!(alignment.sequences[0].data(), "AC.GU");
+        assert_eq!(alignment.sequences[1].data(), "ACCGU");
+        assert_eq!(alignment.ss_cons(), Some(">>.<<"));
+
+        assert!(matches!(
+            parse("#=SS  ..\nseq ACGU\n".as_bytes()),
+            Err(SelexError::OrphanMarkup(_, 1))
+        ));
+        assert!(matches!(
+            parse("a ACGU\nb ACGU\n\na AC\n".as_bytes()),
+            Err(SelexError::Length { .. })
+        ));
+    }
+
+    #[test]
+    fn test_write_selex_roundtrip() {
+        let alignment = parse(SELEX.as_bytes()).unwrap();
+        let mut buffer = Vec::new();
+        write(&alignment, &DEFAULT_GAP_CHARS, &mut buffer).unwrap();
+        let text = String::from_utf8(buffer).unwrap();
+        assert!(text.starts_with("#=ID trna\n#=AU hand curated\n\n#=RF "));
+
+        let read = parse(text.as_bytes()).unwrap();
+        assert_eq!(read.sequences[1].data(), "AC.UAAGCU.");
+        assert_eq!(read.ss_cons(), alignment.ss_cons());
+        assert_eq!(
+            read.residue_annotations["seq1"][0].data,
+            alignment.residue_annotations["seq1"][0].data
+        );
+    }
+
+    #[test]
+    fn test_write_selex_markup() {
+        let mut alignment = parse(
+            "a AC-U
+#=SS <-->
+#=SA 1-23
+#=CS <-->
+"
+            .as_bytes(),
+        )
+        .unwrap();
+        let mut buffer = Vec::new();
+        write(&alignment, &DEFAULT_GAP_CHARS, &mut buffer).unwrap();
+        // Markup keeps its '-', which is only a gap in the sequence
+        assert_eq!(
+            String::from_utf8(buffer).unwrap(),
+            "a    AC.U\n#=SS <-->\n#=SA 1-23\n#=CS <-->\n"
+        );
+
+        alignment.column_annotations[0].data.pop();
+        let error = write(&alignment, &DEFAULT_GAP_CHARS, Vec::new()).unwrap_err();
+        assert_eq!(error.to_string(), "SS_cons has 3 columns, expected 4");
+        assert!(matches!(
+            parse("a ACGU\nb ACG\n".as_bytes()),
+            Err(SelexError::Length { found: 3, .. })
+        ));
+    }
+}
@@ -71,6 +71,11 @@ impl InputHistory {
         }
     }
 
+    /// The most recent `n` entries, oldest first.
+    pub fn recent(&self, n: usize) -> &[String] {
+        &self.entries[self.entries.len().saturating_sub(n)..]
+    }
+
     /// Reset history navigation state (call when entering the mode).
     pub fn reset_navigation(&mut self) {
         self.index = None;
@@ -128,4 +133,15 @@ mod tests {
         assert_eq!(history.prev(""), Some("same"));
         assert!(history.prev("").is_some()); // stays at oldest
     }
+
+    #[test]
+    fn test_recent() {
+        let mut history = InputHistory::new();
+        assert!(history.recent(2).is_empty());
+        for entry in ["w", "set gap=-", "trim"] {
+            history.push(entry.to_string());
+        }
+        assert_eq!(history.recent(2), ["set gap=-", "trim"]);
+        assert_eq!(history.recent(10).len(), 3);
+    }
 }
@@ -0,0 +1,503 @@
+//! Infernal/HMMER hit tables (`--tblout`) and score thresholds.
+//!
+//! Reads `cmsearch`/`cmscan` and `nhmmer` tabular output and relates hits to the
+//! sequences of the current alignment. Marked hits are fetched with `esl-sfetch`
+//! and aligned into the family with `cmalign` (or `hmmalign`) `--mapali`.
+
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::path::Path;
+use std::process::{Command, Stdio};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use thiserror::Error;
+
+use crate::stockholm::parse_coords;
+
+#[derive(Error, Debug)]
+pub enum HitTableError {
+    #[error("IO error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Unrecognized tblout line {0}: expected cmsearch or nhmmer columns")]
+    InvalidLine(usize),
+}
+
+/// A single hit from a tabular search result.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Hit {
+    /// Target sequence name.
+    pub target: String,
+    /// Target accession ("-" if none).
+    pub target_acc: String,
+    /// Query name.
+    pub query: String,
+    /// Query accession ("-" if none).
+    pub query_acc: String,
+    /// Start of the hit on the target (greater than `seq_to` on the minus strand).
+    pub seq_from: usize,
+    /// End of the hit on the target.
+    pub seq_to: usize,
+    /// Strand ('+' or '-').
+    pub strand: char,
+    /// Bit score.
+    pub score: f64,
+    /// E-value.
+    pub evalue: f64,
+    /// Whether the hit met the search's inclusion threshold.
+    pub included: bool,
+    /// Target description.
+    pub description: String,
+}
+
+impl Hit {
+    /// Alignment-style ID for the hit ("target/from-to").
+    pub fn id(&self) -> String {
+        format!("{}/{}-{}", self.target, self.seq_from, self.seq_to)
+    }
+
+    /// Whether an alignment sequence ID refers to this hit.
+    ///
+    /// IDs with coordinates must overlap the hit on the same target; bare IDs match
+    /// the target name.
+    pub fn matches_id(&self, id: &str) -> bool {
+        match parse_coords(id) {
+            Some((name, start, end)) => {
+                let (lo, hi) = (start.min(end), start.max(end));
+                let (hit_lo, hit_hi) = (
+                    self.seq_from.min(self.seq_to),
+                    self.seq_from.max(self.seq_to),
+                );
+                name == self.target && lo <= hit_hi && hit_lo <= hi
+            }
+            None => id == self.target,
+        }
+    }
+}
+
+/// Parse a `--tblout` table from `cmsearch`, `cmscan` or `nhmmer`.
+///
+/// Comment lines (starting with `#`) and blank lines are skipped.
+pub fn parse_tblout<R: Read>(reader: R) -> Result<Vec<Hit>, HitTableError> {
+    let mut hits = Vec::new();
+    for (line_no, line) in BufReader::new(reader).lines().enumerate() {
+        let line = line?;
+        if line.trim().is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let hit = parse_hit_line(&line).ok_or(HitTableError::InvalidLine(line_no + 1))?;
+        hits.push(hit);
+    }
+    Ok(hits)
+}
+
+/// Parse a `--tblout` file.
+pub fn parse_tblout_file(path: &Path) -> Result<Vec<Hit>, HitTableError> {
+    parse_tblout(std::fs::File::open(path)?)
+}
+
+/// Parse one table row, detecting the program from its columns.
+fn parse_hit_line(line: &str) -> Option<Hit> {
+    let fields: Vec<&str> = line.split_whitespace().collect();
+    let description = |from: usize| fields.get(from..).map(|d| d.join(" ")).unwrap_or_default();
+
+    // cmsearch/cmscan: target acc query acc mdl mdl_from mdl_to seq_from seq_to strand
+    //                  trunc pass gc bias score E-value inc description
+    if fields.len() >= 17 && matches!(fields[4], "cm" | "hmm") {
+        return Some(Hit {
+            target: fields[0].to_string(),
+            target_acc: fields[1].to_string(),
+            query: fields[2].to_string(),
+            query_acc: fields[3].to_string(),
+            seq_from: fields[7].parse().ok()?,
+            seq_to: fields[8].parse().ok()?,
+            strand: fields[9].chars().next()?,
+            score: fields[14].parse().ok()?,
+            evalue: fields[15].parse().ok()?,
+            included: fields[16] == "!",
+            description: description(17),
+        });
+    }
+
+    // nhmmer: target acc query acc hmm_from hmm_to ali_from ali_to env_from env_to
+    //         sq_len strand E-value score bias description
+    if fields.len() >= 15 && matches!(fields[11], "+" | "-") {
+        return Some(Hit {
+            target: fields[0].to_string(),
+            target_acc: fields[1].to_string(),
+            query: fields[2].to_string(),
+            query_acc: fields[3].to_string(),
+            seq_from: fields[6].parse().ok()?,
+            seq_to: fields[7].parse().ok()?,
+            strand: fields[11].chars().next()?,
+            score: fields[13].parse().ok()?,
+            evalue: fields[12].parse().ok()?,
+            included: true,
+            description: description(15),
+        });
+    }
+
+    None
+}
+
+/// A model that scores a sequence better than its own family does.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Competitor {
+    /// Competing model name.
+    pub model: String,
+    /// Competing model accession ("-" if none).
+    pub accession: String,
+    /// Best score of the competing model on the sequence.
+    pub score: f64,
+    /// Best score of the sequence's own family, if it was hit at all.
+    pub own_score: Option<f64>,
+}
+
+/// Find the best model that outscores `family` on the alignment sequence `id`.
+///
+/// `cmscan` tables name the sequence in the query column and must match `id` exactly;
+/// `cmsearch` tables name it in the target column and match by coordinate overlap.
+/// `family` lists the names and accessions of the alignment's own family.
+pub fn better_family(hits: &[Hit], id: &str, family: &[&str]) -> Option<Competitor> {
+    let mut own_score: Option<f64> = None;
+    let mut best: Option<(&str, &str, f64)> = None;
+    for hit in hits {
+        let (model, accession) = if hit.query == id {
+            (&hit.target, &hit.target_acc)
+        } else if hit.matches_id(id) {
+            (&hit.query, &hit.query_acc)
+        } else {
+            continue;
+        };
+        if family.contains(&model.as_str()) || family.contains(&accession.as_str()) {
+            own_score = Some(own_score.map_or(hit.score, |s| s.max(hit.score)));
+        } else if best.is_none_or(|(_, _, score)| hit.score > score) {
+            best = Some((model, accession, hit.score));
+        }
+    }
+    let (model, accession, score) = best?;
+    if own_score.is_some_and(|own| own >= score) {
+        return None;
+    }
+    Some(Competitor {
+        model: model.to_string(),
+        accession: accession.to_string(),
+        score,
+        own_score,
+    })
+}
+
+/// Parse an Rfam `Rfam.clanin` file into a family accession to clan accession map.
+///
+/// Each line holds a clan accession followed by its member family accessions.
+pub fn parse_clanin<R: Read>(reader: R) -> Result<HashMap<String, String>, HitTableError> {
+    let mut clans = HashMap::new();
+    for line in BufReader::new(reader).lines() {
+        let line = line?;
+        let mut fields = line.split_whitespace();
+        let Some(clan) = fields.next() else {
+            continue;
+        };
+        for family in fields {
+            clans.insert(family.to_string(), clan.to_string());
+        }
+    }
+    Ok(clans)
+}
+
+/// Suggested Rfam-style score thresholds (bits).
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Thresholds {
+    /// Gathering threshold: lowest-scoring family member.
+    pub ga: f64,
+    /// Trusted cutoff: lowest member score above NC, if any member outscores
+    /// every non-member.
+    pub tc: Option<f64>,
+    /// Noise cutoff: highest non-member score, if there are non-members.
+    pub nc: Option<f64>,
+}
+
+/// Suggest thresholds from the scores of family members and all other hits.
+///
+/// GA is placed at the lowest member score (rounded down to 0.01 bits) so every member
+/// is gathered. NC is the best non-member and TC the weakest member above it, so
+/// a non-member scoring between members raises TC past it. Returns None if
+/// there are no member scores.
+pub fn suggest_thresholds(member_scores: &[f64], other_scores: &[f64]) -> Option<Thresholds> {
+    let lowest_member = member_scores.iter().copied().reduce(f64::min)?;
+    let ga = (lowest_member * 100.0).floor() / 100.0;
+    let nc = other_scores.iter().copied().reduce(f64::max);
+    let tc = member_scores
+        .iter()
+        .copied()
+        .filter(|&s| nc.is_none_or(|nc| s > nc))
+        .reduce(f64::min);
+    Some(Thresholds { ga, tc, nc })
+}
+
+/// Programs `:hits align` runs (`:set hits.sfetch`, `:set hits.aligner`).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Programs {
+    /// Cuts the hits out of the sequence database (`esl-sfetch -Cf`).
+    pub sfetch: String,
+    /// Aligns them to the family's model: `cmalign`, or `hmmalign` for a
+    /// profile HMM.
+    pub aligner: String,
+}
+
+impl Default for Programs {
+    fn default() -> Self {
+        Self {
+            sfetch: "esl-sfetch".to_string(),
+            aligner: "cmalign".to_string(),
+        }
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum AlignError {
+    #[error("Could not run {program}: {source}")]
+    Spawn { program: String, source: io::Error },
+    #[error("IO error: {0}")]
+    Io(#[from] io::Error),
+    #[error("{program} failed: {message}")]
+    Failed { program: String, message: String },
+    #[error("No alignment from {0}")]
+    Output(String),
+}
+
+/// Hits as an `esl-sfetch -Cf` list: `newname from to source` per line.
+pub fn fetch_list<'a>(hits: impl IntoIterator<Item = &'a Hit>) -> String {
+    hits.into_iter()
+        .map(|hit| {
+            format!(
+                "{} {} {} {}\n",
+                hit.id(),
+                hit.seq_from,
+                hit.seq_to,
+                hit.target
+            )
+        })
+        .collect()
+}
+
+/// Numbers the `--mapali` files of this process, so runs never share one.
+static MAPALI_FILES: AtomicUsize = AtomicUsize::new(0);
+
+/// Fetch the hits of `list` (see [`fetch_list`]) from `seqdb` and align them to
+/// the model `model` together with the family alignment `family` (Stockholm),
+/// returning the aligner's Stockholm output.
+pub fn fetch_and_align(
+    programs: &Programs,
+    seqdb: &Path,
+    model: &Path,
+    list: &str,
+    family: &[u8],
+) -> Result<Vec<u8>, AlignError> {
+    let mut args = vec!["-Cf".as_ref(), seqdb.as_os_str(), "-".as_ref()];
+    let fasta = run(&programs.sfetch, &args, list.as_bytes())?;
+
+    // --mapali reads the family from a file
+    let n = MAPALI_FILES.fetch_add(1, Ordering::Relaxed);
+    let path = std::env::temp_dir().join(format!("aform-mapali-{}-{n}.sto", std::process::id()));
+    let mut file = File::options().write(true).create_new(true).open(&path)?;
+    if let Err(e) = file.write_all(family) {
+        let _ = std::fs::remove_file(&path);
+        return Err(e.into());
+    }
+    drop(file);
+    args = vec![
+        "--mapali".as_ref(),
+        path.as_os_str(),
+        model.as_os_str(),
+        "-".as_ref(),
+    ];
+    let aligned = run(&programs.aligner, &args, &fasta);
+    let _ = std::fs::remove_file(&path);
+    aligned
+}
+
+/// Run `program` with `input` on its stdin, returning its stdout.
+fn run(program: &str, args: &[&std::ffi::OsStr], input: &[u8]) -> Result<Vec<u8>, AlignError> {
+    let mut child = Command::new(program)
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|source| AlignError::Spawn {
+            program: program.to_string(),
+            source,
+        })?;
+    // esl-sfetch writes each sequence as soon as it reads its name, so the
+    // input goes in from another thread while the output is read; writing it
+    // all first would block once both pipes are full. A program that stops
+    // early closes its stdin, and its exit status then says why.
+    let stdin = child.stdin.take();
+    let (written, output) = std::thread::scope(|scope| {
+        let writer = scope.spawn(move || match stdin {
+            Some(mut stdin) => stdin.write_all(input),
+            None => Ok(()),
+        });
+        let output = child.wait_with_output();
+        (writer.join().expect("stdin writer panicked"), output)
+    });
+    if let Err(e) = written
+        && e.kind() != io::ErrorKind::BrokenPipe
+    {
+        return Err(e.into());
+    }
+    let output = output?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(AlignError::Failed {
+            program: program.to_string(),
+            message: stderr
+                .lines()
+                .find(|l| !l.trim().is_empty())
+                .map_or_else(|| output.status.to_string(), str::to_string),
+        });
+    }
+    Ok(output.stdout)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CMSEARCH: &str = "\
+#target name         accession query name           accession mdl mdl from   mdl to seq from   seq to strand trunc pass   gc  bias  score   E-value inc description of target
+#------------------- --------- -------------------- --------- --- -------- -------- -------- -------- ------ ----- ---- ---- ----- ------ --------- --- ---------------------
+chr1                 -         tRNA                 RF00005    cm        1       71     1000     1072      +    no    1 0.55   0.0   62.5   1.2e-12 !   Homo sapiens chr1
+chr2                 -         tRNA                 RF00005    cm        1       71     5072     5000      -    no    1 0.50   0.0   18.1   0.00031 ?   -
+";
+
+    const NHMMER: &str = "\
+# target name  accession  query name  accession  hmmfrom hmm to alifrom  ali to envfrom  env to  sq len strand   E-value  score  bias  description of target
+chr3           -          tRNA        -                1     71     200     270     199     271  100000    +     3.1e-10   40.2   0.1  -
+";
+
+    #[test]
+    fn test_parse_cmsearch_tblout() {
+        let hits = parse_tblout(CMSEARCH.as_bytes()).unwrap();
+        assert_eq!(hits.len(), 2);
+        assert_eq!(hits[0].target, "chr1");
+        assert_eq!(hits[0].query, "tRNA");
+        assert_eq!((hits[0].seq_from, hits[0].seq_to), (1000, 1072));
+        assert_eq!(hits[0].score, 62.5);
+        assert!(hits[0].included);
+        assert_eq!(hits[0].description, "Homo sapiens chr1");
+        assert_eq!(hits[1].strand, '-');
+        assert!(!hits[1].included);
+    }
+
+    #[test]
+    fn test_parse_nhmmer_tblout() {
+        let hits = parse_tblout(NHMMER.as_bytes()).unwrap();
+        assert_eq!(hits.len(), 1);
+        assert_eq!((hits[0].seq_from, hits[0].seq_to), (200, 270));
+        assert_eq!(hits[0].score, 40.2);
+        assert_eq!(hits[0].evalue, 3.1e-10);
+    }
+
+    #[test]
+    fn test_parse_tblout_invalid() {
+        assert!(matches!(
+            parse_tblout("chr1 too few columns\n".as_bytes()),
+            Err(HitTableError::InvalidLine(1))
+        ));
+    }
+
+    #[test]
+    fn test_fetch_list() {
+        let hits = parse_tblout(CMSEARCH.as_bytes()).unwrap();
+        assert_eq!(
+            fetch_list(&hits),
+            "chr1/1000-1072 1000 1072 chr1\nchr2/5072-5000 5072 5000 chr2\n"
+        );
+    }
+
+    #[test]
+    fn test_hit_matches_id() {
+        let hits = parse_tblout(CMSEARCH.as_bytes()).unwrap();
+        assert!(hits[0].matches_id("chr1/1010-1050"));
+        assert!(hits[0].matches_id("chr1"));
+        assert!(!hits[0].matches_id("chr1/2000-2050"));
+        assert!(hits[1].matches_id("chr2/5050-4990"));
+        assert_eq!(hits[1].id(), "chr2/5072-5000");
+    }
+
+    #[test]
+    fn test_better_family() {
+        // cmscan: sequences in the query column, models in the target column
+        let cmscan = "\
+tRNA       RF00005 seq1/1-72 - cm 1 71 1 72 + no 1 0.5 0.0 40.0 1e-8 ! -
+tmRNA      RF00023 seq1/1-72 - cm 1 71 1 72 + no 1 0.5 0.0 55.0 1e-9 ! -
+tRNA       RF00005 seq2/1-72 - cm 1 71 1 72 + no 1 0.5 0.0 60.0 1e-9 ! -
+tmRNA      RF00023 seq2/1-72 - cm 1 71 1 72 + no 1 0.5 0.0 20.0 1e-2 ? -
+SRP_bact   RF00169 seq3/1-72 - cm 1 71 1 72 + no 1 0.5 0.0 30.0 1e-5 ! -
+";
+        let hits = parse_tblout(cmscan.as_bytes()).unwrap();
+        let family = ["tRNA", "RF00005"];
+        let c = better_family(&hits, "seq1/1-72", &family).unwrap();
+        assert_eq!(
+            (c.model.as_str(), c.accession.as_str()),
+            ("tmRNA", "RF00023")
+        );
+        assert_eq!((c.score, c.own_score), (55.0, Some(40.0)));
+        assert!(better_family(&hits, "seq2/1-72", &family).is_none());
+        assert_eq!(
+            better_family(&hits, "seq3/1-72", &family)
+                .unwrap()
+                .own_score,
+            None
+        );
+
+        // cmsearch: genome coordinates in the target column
+        let hits = parse_tblout(CMSEARCH.as_bytes()).unwrap();
+        let c = better_family(&hits, "chr1/1010-1050", &["RF00001"]).unwrap();
+        assert_eq!(c.model, "tRNA");
+    }
+
+    #[test]
+    fn test_parse_clanin() {
+        let clans =
+            parse_clanin("CL00001\tRF00005 RF00023\nCL00002\tRF00169\n".as_bytes()).unwrap();
+        assert_eq!(clans["RF00023"], "CL00001");
+        assert_eq!(clans["RF00169"], "CL00002");
+        assert!(!clans.contains_key("RF00001"));
+    }
+
+    #[test]
+    fn test_suggest_thresholds() {
+        let t = suggest_thresholds(&[62.5, 40.238], &[30.0, 18.1]).unwrap();
+        assert_eq!(t.ga, 40.23);
+        assert_eq!(t.tc, Some(40.238));
+        assert_eq!(t.nc, Some(30.0));
+
+        let t = suggest_thresholds(&[50.0], &[]).unwrap();
+        assert_eq!(t.tc, Some(50.0));
+        assert_eq!(t.nc, None);
+        assert!(suggest_thresholds(&[], &[10.0]).is_none());
+    }
+
+    #[test]
+    fn test_suggest_thresholds_with_a_non_member_between_members() {
+        let t = suggest_thresholds(&[62.5, 48.0, 40.238], &[55.0, 30.0]).unwrap();
+        assert_eq!(t.ga, 40.23);
+        assert_eq!(t.nc, Some(55.0));
+        assert_eq!(t.tc, Some(62.5));
+
+        // No member above the best non-member: no trusted cutoff
+        let t = suggest_thresholds(&[48.0], &[55.0]).unwrap();
+        assert_eq!(t.tc, None);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_run_streams_large_input() {
+        // cat writes while it reads, like esl-sfetch: more than a pipe holds
+        let input = b">seq\nACGU\n".repeat(100_000);
+        assert_eq!(run("cat", &[], &input).unwrap(), input);
+    }
+}
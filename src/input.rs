@@ -2,27 +2,40 @@
 
 use ratatui::crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
 
-use crate::app::{App, Mode};
+use crate::app::{App, Edit, Mode};
+use crate::stockholm::TRACK_MAX;
 
 /// Handle movement keys common to normal and visual modes.
 /// Returns true if the key was handled as a movement.
-fn handle_movement_keys(app: &mut App, key: KeyEvent, page_size: usize) -> bool {
+///
+/// `count` is the count typed before the key in normal mode (`5j`), if any.
+fn handle_movement_keys(
+    app: &mut App,
+    key: KeyEvent,
+    page_size: usize,
+    count: Option<usize>,
+) -> bool {
+    let times = count.unwrap_or(1);
     match (key.modifiers, key.code) {
         // Basic movement (hjkl and arrows)
         (KeyModifiers::NONE, KeyCode::Char('h') | KeyCode::Left) => {
-            app.cursor_left();
+            app.scroll_left(times);
             true
         }
         (KeyModifiers::NONE, KeyCode::Char('j') | KeyCode::Down) => {
-            app.cursor_down();
+            for _ in 0..times.min(app.visible_sequence_count()) {
+                app.cursor_down();
+            }
             true
         }
         (KeyModifiers::NONE, KeyCode::Char('k') | KeyCode::Up) => {
-            app.cursor_up();
+            for _ in 0..times.min(app.visible_sequence_count()) {
+                app.cursor_up();
+            }
             true
         }
         (KeyModifiers::NONE, KeyCode::Char('l') | KeyCode::Right) => {
-            app.cursor_right();
+            app.scroll_right(times);
             true
         }
 
@@ -44,19 +57,22 @@ fn handle_movement_keys(app: &mut App, key: KeyEvent, page_size: usize) -> bool
             true
         }
 
-        // Document movement
+        // Document movement (NG goes to sequence N)
         (KeyModifiers::SHIFT, KeyCode::Char('G')) => {
-            app.cursor_last_sequence();
+            match count {
+                Some(row) => app.goto_row(row),
+                None => app.cursor_last_sequence(),
+            }
             true
         }
 
         // Page movement
         (KeyModifiers::CONTROL, KeyCode::Char('f')) | (KeyModifiers::NONE, KeyCode::PageDown) => {
-            app.page_down(page_size);
+            app.page_down(page_size.saturating_mul(times));
             true
         }
         (KeyModifiers::CONTROL, KeyCode::Char('b')) | (KeyModifiers::NONE, KeyCode::PageUp) => {
-            app.page_up(page_size);
+            app.page_up(page_size.saturating_mul(times));
             true
         }
         (KeyModifiers::CONTROL, KeyCode::Char('d')) => {
@@ -70,11 +86,11 @@ fn handle_movement_keys(app: &mut App, key: KeyEvent, page_size: usize) -> bool
 
         // Word-like movement (jump by 10 columns)
         (KeyModifiers::NONE, KeyCode::Char('w')) => {
-            app.scroll_right(10);
+            app.scroll_right(times.saturating_mul(10));
             true
         }
         (KeyModifiers::NONE, KeyCode::Char('b')) => {
-            app.scroll_left(10);
+            app.scroll_left(times.saturating_mul(10));
             true
         }
 
@@ -96,17 +112,145 @@ pub fn handle_key(app: &mut App, key: KeyEvent, page_size: usize) {
         return;
     }
 
+    // The column popup scrolls with j/k and closes on any other key
+    if app.show_column_popup {
+        match (key.modifiers, key.code) {
+            (KeyModifiers::NONE, KeyCode::Char('j') | KeyCode::Down) => {
+                app.scroll_column_popup(1);
+            }
+            (KeyModifiers::NONE, KeyCode::Char('k') | KeyCode::Up) => {
+                app.scroll_column_popup(-1);
+            }
+            (KeyModifiers::CONTROL, KeyCode::Char('f'))
+            | (KeyModifiers::NONE, KeyCode::PageDown) => {
+                app.scroll_column_popup(page_size as isize);
+            }
+            (KeyModifiers::CONTROL, KeyCode::Char('b')) | (KeyModifiers::NONE, KeyCode::PageUp) => {
+                app.scroll_column_popup(-(page_size as isize));
+            }
+            _ => app.show_column_popup = false,
+        }
+        return;
+    }
+
+    // Close cluster panel on any keypress
+    if app.show_clusters {
+        app.show_clusters = false;
+        return;
+    }
+
+    // Close report overlay on any keypress; a `--preview` report's key also
+    // answers its confirmation
+    if app.report.is_some() {
+        app.report = None;
+        if app.confirm.is_none() {
+            return;
+        }
+    }
+
+    // The settings panel takes all keys while open
+    if app.settings_panel.is_some() {
+        handle_settings_panel(app, key);
+        return;
+    }
+
+    // The helix view takes all keys while open
+    if app.helix_view.is_some() {
+        handle_helix_view(app, key, page_size);
+        return;
+    }
+
+    // The pileup view takes all keys while open
+    if app.pileup.is_some() {
+        handle_pileup(app, key, page_size);
+        return;
+    }
+
+    // The hit viewer takes all keys while open
+    if app.show_hits {
+        handle_hit_viewer(app, key, page_size);
+        return;
+    }
+
+    // The quickfix list takes all keys while open
+    if app.show_quickfix {
+        handle_quickfix(app, key, page_size);
+        return;
+    }
+
+    // The alignment picker takes all keys while open
+    if app.show_record_list {
+        handle_record_list(app, key, page_size);
+        return;
+    }
+
+    // The command palette takes all keys while open
+    if app.palette.is_some() {
+        handle_palette(app, key, page_size);
+        return;
+    }
+
+    // A yes/no confirmation takes the next key; anything but y cancels
+    if app.confirm.is_some() {
+        app.answer_confirm(matches!(key.code, KeyCode::Char('y' | 'Y')));
+        return;
+    }
+
+    // A :mergecol/:splitcol confirmation takes all keys until answered
+    if app.column_prompt.is_some() {
+        handle_column_prompt(app, key);
+        return;
+    }
+
+    // So does a :s///c confirmation
+    if app.substitute_prompt.is_some() {
+        handle_substitute_prompt(app, key);
+        return;
+    }
+
+    // Presentation mode steps through bookmarks; other keys work as usual
+    if app.presentation.is_some() && app.mode == Mode::Normal && handle_presentation_key(app, key) {
+        return;
+    }
+
     match app.mode {
         Mode::Normal => handle_normal_mode(app, key, page_size),
         Mode::Insert => handle_insert_mode(app, key),
         Mode::Command => handle_command_mode(app, key),
         Mode::Search => handle_search_mode(app, key),
         Mode::Visual => handle_visual_mode(app, key, page_size),
+        Mode::Track => handle_track_mode(app, key, page_size),
+    }
+
+    // A register chosen with "a lasts until the yank, delete or put it is for
+    let pending = app.status_message.as_deref();
+    if !matches!(pending, Some(s) if s.starts_with('"') || s == "y..." || s == "d...") {
+        app.pending_register = None;
     }
 }
 
-/// Handle keys in normal mode.
+/// Handle keys in normal mode, remembering where jumps (`gg`, `G`, `n`, marks...)
+/// started for `Ctrl-o`.
 fn handle_normal_mode(app: &mut App, key: KeyEvent, page_size: usize) {
+    let from = app.cursor_mark();
+    let pending_status = app.status_message.clone();
+    handle_normal_key(app, key, page_size);
+
+    let is_jump = match (pending_status.as_deref(), key.code) {
+        (Some("m..."), _) => false,
+        (Some("g..."), KeyCode::Char('g' | 'p'))
+        | (Some("[..." | "]..."), KeyCode::Char('d' | 'c'))
+        | (Some("`..." | "'..."), _) => true,
+        (_, KeyCode::Char('G' | 'n' | 'N' | '|')) => key.modifiers != KeyModifiers::CONTROL,
+        _ => false,
+    };
+    if is_jump && let Some(from) = from {
+        app.record_jump(from);
+    }
+}
+
+/// Handle a key in normal mode.
+fn handle_normal_key(app: &mut App, key: KeyEvent, page_size: usize) {
     // Save pending status for two-key sequences before clearing
     let pending_status = app.status_message.clone();
     app.clear_status();
@@ -118,13 +262,49 @@ fn handle_normal_mode(app: &mut App, key: KeyEvent, page_size: usize) {
     ) || (matches!(key.code, KeyCode::Char('0'))
         && !app.count_buffer.is_empty());
 
-    // Clear count for non-digit keys (except | which consumes it)
-    let is_pipe = matches!(
-        (key.modifiers, key.code),
-        (KeyModifiers::NONE | KeyModifiers::SHIFT, KeyCode::Char('|'))
-    );
-    if !is_count_digit && !is_pipe {
-        app.clear_count();
+    // Any other key consumes the count (`15l`, `3>`)
+    let count = if is_count_digit || app.count_buffer.is_empty() {
+        None
+    } else {
+        Some(app.take_count())
+    };
+    let times = count.unwrap_or(1);
+
+    // Bracket motions ([d / ]d, [c / ]c) take precedence over single-key bindings
+    if let Some(prefix @ ("[..." | "]...")) = pending_status.as_deref() {
+        match key.code {
+            KeyCode::Char('d') => app.goto_difference(prefix == "]..."),
+            KeyCode::Char('c') => app.goto_compensatory(prefix == "]..."),
+            _ => {}
+        }
+        return;
+    }
+
+    // So do marks: m{a-z} sets one, `{a-z} jumps to it, '{a-z} to its row
+    if let Some(prefix @ ("m..." | "`..." | "'...")) = pending_status.as_deref() {
+        if let KeyCode::Char(name) = key.code {
+            match prefix {
+                "m..." => app.set_mark(name),
+                _ => app.goto_mark(name, prefix == "'..."),
+            }
+        }
+        return;
+    }
+
+    // And cw, which renames the sequence
+    if pending_status.as_deref() == Some("c...") {
+        if key.code == KeyCode::Char('w') {
+            app.start_rename();
+        }
+        return;
+    }
+
+    // And registers: "{a-z} names the register for the next yank, delete or put
+    if pending_status.as_deref() == Some("\"...") {
+        if let KeyCode::Char(name) = key.code {
+            app.select_register(name);
+        }
+        return;
     }
 
     // Try shared movement keys first (unless it's a key with special normal-mode handling)
@@ -133,7 +313,7 @@ fn handle_normal_mode(app: &mut App, key: KeyEvent, page_size: usize) {
         (KeyModifiers::NONE, KeyCode::Char('0' | 'g' | 'w' | 'b'))
             | (KeyModifiers::CONTROL, KeyCode::Char('w'))
     );
-    if !is_special_normal_key && handle_movement_keys(app, key, page_size) {
+    if !is_special_normal_key && handle_movement_keys(app, key, page_size, count) {
         return;
     }
 
@@ -157,8 +337,7 @@ fn handle_normal_mode(app: &mut App, key: KeyEvent, page_size: usize) {
 
         // Go to column (vim |)
         (KeyModifiers::NONE | KeyModifiers::SHIFT, KeyCode::Char('|')) => {
-            let col = app.take_count();
-            app.goto_column(col);
+            app.goto_column(times);
         }
 
         // Movement - line start (0 only when not building count)
@@ -167,7 +346,7 @@ fn handle_normal_mode(app: &mut App, key: KeyEvent, page_size: usize) {
         }
 
         // Movement - document (g starts two-key sequence)
-        (KeyModifiers::NONE, KeyCode::Char('g')) => {
+        (KeyModifiers::NONE, KeyCode::Char('g')) if pending_status.as_deref() != Some("g...") => {
             app.set_status("g...");
         }
 
@@ -178,20 +357,33 @@ fn handle_normal_mode(app: &mut App, key: KeyEvent, page_size: usize) {
 
         // Movement - word-like (jump by 10 columns)
         (KeyModifiers::NONE, KeyCode::Char('w')) => {
-            app.scroll_right(10);
+            app.scroll_right(times.saturating_mul(10));
         }
         (KeyModifiers::NONE, KeyCode::Char('b')) => {
-            app.scroll_left(10);
+            app.scroll_left(times.saturating_mul(10));
         }
 
         // Go to pair (gp) or paste
         (KeyModifiers::NONE, KeyCode::Char('p')) => {
             if pending_status.as_deref() == Some("g...") {
                 app.goto_pair();
+            } else if app.block_move.is_some() {
+                app.drop_block();
             } else {
-                app.paste();
+                app.paste(false);
             }
         }
+        (KeyModifiers::SHIFT, KeyCode::Char('P')) => {
+            app.paste(true);
+        }
+
+        // Drop or cancel a block picked up in visual mode
+        (KeyModifiers::NONE, KeyCode::Enter) if app.block_move.is_some() => {
+            app.drop_block();
+        }
+        (KeyModifiers::NONE, KeyCode::Esc) if app.block_move.is_some() => {
+            app.cancel_block_move();
+        }
 
         // Insert mode
         (KeyModifiers::NONE, KeyCode::Char('i')) => {
@@ -200,41 +392,58 @@ fn handle_normal_mode(app: &mut App, key: KeyEvent, page_size: usize) {
 
         // Delete gap
         (KeyModifiers::NONE, KeyCode::Char('x')) => {
-            app.delete_gap();
+            app.run_edit(Edit::DeleteGap, times);
         }
 
         // Insert gap column
         (KeyModifiers::SHIFT, KeyCode::Char('I')) => {
-            app.insert_gap_column();
+            app.run_edit(Edit::InsertGapColumn, times);
         }
 
         // Delete gap column
         (KeyModifiers::SHIFT, KeyCode::Char('X')) => {
-            app.delete_gap_column();
+            app.run_edit(Edit::DeleteGapColumn, times);
         }
 
         // Shift sequence
-        (KeyModifiers::SHIFT, KeyCode::Char('<')) => {
-            app.shift_sequence_left();
+        (KeyModifiers::NONE | KeyModifiers::SHIFT, KeyCode::Char('<')) => {
+            app.run_edit(Edit::ShiftLeft, times);
         }
-        (KeyModifiers::SHIFT, KeyCode::Char('>')) => {
-            app.shift_sequence_right();
+        (KeyModifiers::NONE | KeyModifiers::SHIFT, KeyCode::Char('>')) => {
+            app.run_edit(Edit::ShiftRight, times);
         }
 
         // Throw sequence
-        (KeyModifiers::SHIFT, KeyCode::Char('{')) => {
-            app.throw_sequence_left();
+        (KeyModifiers::NONE | KeyModifiers::SHIFT, KeyCode::Char('{')) => {
+            app.run_edit(Edit::ThrowLeft, 1);
         }
-        (KeyModifiers::SHIFT, KeyCode::Char('}')) => {
-            app.throw_sequence_right();
+        (KeyModifiers::NONE | KeyModifiers::SHIFT, KeyCode::Char('}')) => {
+            app.run_edit(Edit::ThrowRight, 1);
+        }
+
+        // Move the sequence down/up a row
+        (KeyModifiers::ALT, KeyCode::Char('j') | KeyCode::Down) => {
+            app.move_sequence_by(times, false);
+        }
+        (KeyModifiers::ALT, KeyCode::Char('k') | KeyCode::Up) => {
+            app.move_sequence_by(times, true);
+        }
+
+        // Repeat the last edit
+        (KeyModifiers::NONE, KeyCode::Char('.')) => {
+            app.repeat_last_edit(count);
         }
 
         // Undo/Redo
         (KeyModifiers::NONE, KeyCode::Char('u')) => {
-            app.undo();
+            for _ in 0..times.min(app.history.undo_count()).max(1) {
+                app.undo();
+            }
         }
         (KeyModifiers::CONTROL, KeyCode::Char('r')) => {
-            app.redo();
+            for _ in 0..times.min(app.history.redo_count()).max(1) {
+                app.redo();
+            }
         }
 
         // Command mode
@@ -242,26 +451,94 @@ fn handle_normal_mode(app: &mut App, key: KeyEvent, page_size: usize) {
             app.enter_command_mode();
         }
 
+        // Suspend to the shell (raw mode delivers Ctrl-Z as a key)
+        (KeyModifiers::CONTROL, KeyCode::Char('z')) => {
+            app.suspend();
+        }
+
+        // Command palette (some terminals send Ctrl-Space as NUL)
+        (KeyModifiers::CONTROL, KeyCode::Char(' ')) | (_, KeyCode::Null) => {
+            app.open_palette();
+        }
+
         // Delete line
         (KeyModifiers::NONE, KeyCode::Char('d')) => {
             app.set_status("d...");
         }
 
+        // Yank line
+        (KeyModifiers::NONE, KeyCode::Char('y')) => {
+            app.set_status("y...");
+        }
+
+        // Change (cw renames the sequence)
+        (KeyModifiers::NONE, KeyCode::Char('c')) => {
+            app.set_status("c...");
+        }
+
+        // Register prefix
+        (KeyModifiers::NONE | KeyModifiers::SHIFT, KeyCode::Char('"')) => {
+            app.set_status("\"...");
+        }
+
+        // Marks
+        (KeyModifiers::NONE, KeyCode::Char('m')) => {
+            app.set_status("m...");
+        }
+        (KeyModifiers::NONE, KeyCode::Char('`')) => {
+            app.set_status("`...");
+        }
+        (KeyModifiers::NONE, KeyCode::Char('\'')) => {
+            app.set_status("'...");
+        }
+
+        // Jump list (terminals send Ctrl-i as Tab)
+        (KeyModifiers::CONTROL, KeyCode::Char('o')) => {
+            for _ in 0..times.min(app.jumps.len()).max(1) {
+                app.jump_back();
+            }
+        }
+        (KeyModifiers::NONE, KeyCode::Tab) | (KeyModifiers::CONTROL, KeyCode::Char('i')) => {
+            for _ in 0..times.min(app.jumps.len()).max(1) {
+                app.jump_forward();
+            }
+        }
+
         // Search
         (KeyModifiers::NONE, KeyCode::Char('/')) => {
             app.enter_search_mode();
         }
         (KeyModifiers::NONE, KeyCode::Char('n')) => {
-            app.search_next();
+            for _ in 0..times.min(app.search.matches.len()).max(1) {
+                app.search_next();
+            }
         }
         (KeyModifiers::SHIFT, KeyCode::Char('N')) => {
-            app.search_prev();
+            for _ in 0..times.min(app.search.matches.len()).max(1) {
+                app.search_prev();
+            }
         }
 
         // Visual mode
         (KeyModifiers::NONE, KeyCode::Char('v')) => {
             app.enter_visual_mode();
         }
+        (KeyModifiers::CONTROL, KeyCode::Char('v')) => {
+            app.enter_visual_block_mode();
+        }
+
+        // Bracket motion prefixes
+        (KeyModifiers::NONE, KeyCode::Char('[')) => {
+            app.set_status("[...");
+        }
+        (KeyModifiers::NONE, KeyCode::Char(']')) => {
+            app.set_status("]...");
+        }
+
+        // Inspect cursor column
+        (KeyModifiers::SHIFT, KeyCode::Char('K')) => {
+            app.toggle_column_popup();
+        }
 
         // Help (some terminals send ? without SHIFT modifier)
         (KeyModifiers::SHIFT | KeyModifiers::NONE, KeyCode::Char('?')) => {
@@ -280,6 +557,9 @@ fn handle_normal_mode(app: &mut App, key: KeyEvent, page_size: usize) {
             ("d...", KeyCode::Char('d')) => {
                 app.delete_sequence();
             }
+            ("y...", KeyCode::Char('y')) => {
+                app.yank_sequence();
+            }
             // Ctrl-w sequences for split management
             ("Ctrl-w...", KeyCode::Char('s')) => {
                 app.horizontal_split();
@@ -305,6 +585,291 @@ fn handle_normal_mode(app: &mut App, key: KeyEvent, page_size: usize) {
     }
 }
 
+/// Handle keys in the search hit viewer.
+fn handle_hit_viewer(app: &mut App, key: KeyEvent, page_size: usize) {
+    match (key.modifiers, key.code) {
+        (KeyModifiers::NONE, KeyCode::Esc | KeyCode::Char('q')) => {
+            app.show_hits = false;
+        }
+        (KeyModifiers::NONE, KeyCode::Char('j') | KeyCode::Down) => {
+            app.move_hit_cursor(1);
+        }
+        (KeyModifiers::NONE, KeyCode::Char('k') | KeyCode::Up) => {
+            app.move_hit_cursor(-1);
+        }
+        (KeyModifiers::CONTROL, KeyCode::Char('f')) | (KeyModifiers::NONE, KeyCode::PageDown) => {
+            app.move_hit_cursor(page_size as isize);
+        }
+        (KeyModifiers::CONTROL, KeyCode::Char('b')) | (KeyModifiers::NONE, KeyCode::PageUp) => {
+            app.move_hit_cursor(-(page_size as isize));
+        }
+        (KeyModifiers::NONE, KeyCode::Char('g') | KeyCode::Home) => {
+            app.hit_cursor = 0;
+        }
+        (KeyModifiers::SHIFT, KeyCode::Char('G')) | (KeyModifiers::NONE, KeyCode::End) => {
+            app.move_hit_cursor(isize::MAX);
+        }
+        (KeyModifiers::NONE, KeyCode::Char(' ' | 'm')) => {
+            app.toggle_hit_mark();
+        }
+        (KeyModifiers::NONE, KeyCode::Char('a')) => {
+            app.mark_new_hits();
+        }
+        (KeyModifiers::NONE, KeyCode::Enter) => {
+            app.goto_hit_sequence();
+        }
+        _ => {}
+    }
+}
+
+/// Handle keys in the quickfix list.
+fn handle_quickfix(app: &mut App, key: KeyEvent, page_size: usize) {
+    match (key.modifiers, key.code) {
+        (KeyModifiers::NONE, KeyCode::Esc | KeyCode::Char('q')) => {
+            app.show_quickfix = false;
+        }
+        (KeyModifiers::NONE, KeyCode::Char('j') | KeyCode::Down) => {
+            app.move_quickfix_cursor(1);
+        }
+        (KeyModifiers::NONE, KeyCode::Char('k') | KeyCode::Up) => {
+            app.move_quickfix_cursor(-1);
+        }
+        (KeyModifiers::CONTROL, KeyCode::Char('f')) | (KeyModifiers::NONE, KeyCode::PageDown) => {
+            app.move_quickfix_cursor(page_size as isize);
+        }
+        (KeyModifiers::CONTROL, KeyCode::Char('b')) | (KeyModifiers::NONE, KeyCode::PageUp) => {
+            app.move_quickfix_cursor(-(page_size as isize));
+        }
+        (KeyModifiers::NONE, KeyCode::Char('g') | KeyCode::Home) => {
+            app.quickfix_cursor = 0;
+        }
+        (KeyModifiers::SHIFT, KeyCode::Char('G')) | (KeyModifiers::NONE, KeyCode::End) => {
+            app.move_quickfix_cursor(isize::MAX);
+        }
+        (KeyModifiers::NONE, KeyCode::Enter) => {
+            app.goto_quickfix_entry();
+        }
+        _ => {}
+    }
+}
+
+/// Handle keys in the settings panel, typing into the value being edited.
+fn handle_settings_panel(app: &mut App, key: KeyEvent) {
+    if let Some(value) = app
+        .settings_panel
+        .as_mut()
+        .and_then(|panel| panel.editing.as_mut())
+    {
+        match key.code {
+            KeyCode::Enter => app.apply_setting_edit(),
+            KeyCode::Esc => {
+                if let Some(panel) = &mut app.settings_panel {
+                    panel.editing = None;
+                }
+            }
+            KeyCode::Backspace => {
+                value.pop();
+            }
+            KeyCode::Char(c) => value.push(c),
+            _ => {}
+        }
+        return;
+    }
+    match (key.modifiers, key.code) {
+        (KeyModifiers::NONE, KeyCode::Esc | KeyCode::Char('q')) => {
+            app.settings_panel = None;
+        }
+        (KeyModifiers::NONE, KeyCode::Char('j') | KeyCode::Down) => {
+            app.move_settings_cursor(1);
+        }
+        (KeyModifiers::NONE, KeyCode::Char('k') | KeyCode::Up) => {
+            app.move_settings_cursor(-1);
+        }
+        (KeyModifiers::NONE, KeyCode::Char('g') | KeyCode::Home) => {
+            app.move_settings_cursor(isize::MIN);
+        }
+        (KeyModifiers::SHIFT, KeyCode::Char('G')) | (KeyModifiers::NONE, KeyCode::End) => {
+            app.move_settings_cursor(isize::MAX);
+        }
+        (KeyModifiers::NONE, KeyCode::Enter | KeyCode::Char('i' | 'c')) => {
+            app.edit_setting();
+        }
+        (KeyModifiers::NONE, KeyCode::Char('u')) => {
+            app.undo();
+        }
+        (KeyModifiers::CONTROL, KeyCode::Char('r')) => {
+            app.redo();
+        }
+        _ => {}
+    }
+}
+
+/// Handle keys in the column pileup view.
+fn handle_pileup(app: &mut App, key: KeyEvent, page_size: usize) {
+    match (key.modifiers, key.code) {
+        (KeyModifiers::NONE, KeyCode::Esc | KeyCode::Char('q')) => {
+            app.pileup = None;
+        }
+        (KeyModifiers::NONE, KeyCode::Char('j') | KeyCode::Down) => {
+            app.move_pileup_cursor(1);
+        }
+        (KeyModifiers::NONE, KeyCode::Char('k') | KeyCode::Up) => {
+            app.move_pileup_cursor(-1);
+        }
+        (KeyModifiers::CONTROL, KeyCode::Char('f')) | (KeyModifiers::NONE, KeyCode::PageDown) => {
+            app.move_pileup_cursor(page_size as isize);
+        }
+        (KeyModifiers::CONTROL, KeyCode::Char('b')) | (KeyModifiers::NONE, KeyCode::PageUp) => {
+            app.move_pileup_cursor(-(page_size as isize));
+        }
+        (KeyModifiers::NONE, KeyCode::Char('g') | KeyCode::Home) => {
+            app.move_pileup_cursor(isize::MIN);
+        }
+        (KeyModifiers::SHIFT, KeyCode::Char('G')) | (KeyModifiers::NONE, KeyCode::End) => {
+            app.move_pileup_cursor(isize::MAX);
+        }
+        (KeyModifiers::NONE, KeyCode::Char('h') | KeyCode::Left) => {
+            app.shift_pileup(-1);
+        }
+        (KeyModifiers::NONE, KeyCode::Char('l') | KeyCode::Right) => {
+            app.shift_pileup(1);
+        }
+        (KeyModifiers::NONE, KeyCode::Char(' ') | KeyCode::Tab) => {
+            app.toggle_pileup_group();
+        }
+        (KeyModifiers::NONE, KeyCode::Char('a')) => {
+            app.toggle_pileup_all();
+        }
+        (KeyModifiers::NONE, KeyCode::Enter) => {
+            app.pileup_enter();
+        }
+        _ => {}
+    }
+}
+
+/// Handle keys in the helix view.
+fn handle_helix_view(app: &mut App, key: KeyEvent, page_size: usize) {
+    match (key.modifiers, key.code) {
+        (KeyModifiers::NONE, KeyCode::Esc | KeyCode::Char('q')) => {
+            app.helix_view = None;
+        }
+        (KeyModifiers::NONE, KeyCode::Char('j') | KeyCode::Down) => {
+            app.scroll_helix_view(1);
+        }
+        (KeyModifiers::NONE, KeyCode::Char('k') | KeyCode::Up) => {
+            app.scroll_helix_view(-1);
+        }
+        (KeyModifiers::CONTROL, KeyCode::Char('f')) | (KeyModifiers::NONE, KeyCode::PageDown) => {
+            app.scroll_helix_view(page_size as isize);
+        }
+        (KeyModifiers::CONTROL, KeyCode::Char('b')) | (KeyModifiers::NONE, KeyCode::PageUp) => {
+            app.scroll_helix_view(-(page_size as isize));
+        }
+        (KeyModifiers::NONE, KeyCode::Char('g') | KeyCode::Home) => {
+            app.scroll_helix_view(isize::MIN);
+        }
+        (KeyModifiers::SHIFT, KeyCode::Char('G')) | (KeyModifiers::NONE, KeyCode::End) => {
+            app.scroll_helix_view(isize::MAX);
+        }
+        (KeyModifiers::NONE, KeyCode::Char('h') | KeyCode::Left) => {
+            app.shift_helix_view(-1);
+        }
+        (KeyModifiers::NONE, KeyCode::Char('l') | KeyCode::Right) => {
+            app.shift_helix_view(1);
+        }
+        (KeyModifiers::NONE, KeyCode::Enter) => {
+            app.helix_view_enter();
+        }
+        _ => {}
+    }
+}
+
+/// Handle keys in the alignment picker of a multi-record file.
+fn handle_record_list(app: &mut App, key: KeyEvent, page_size: usize) {
+    match (key.modifiers, key.code) {
+        (KeyModifiers::NONE, KeyCode::Esc | KeyCode::Char('q')) => {
+            app.show_record_list = false;
+        }
+        (KeyModifiers::NONE, KeyCode::Char('j') | KeyCode::Down) => {
+            app.move_record_cursor(1);
+        }
+        (KeyModifiers::NONE, KeyCode::Char('k') | KeyCode::Up) => {
+            app.move_record_cursor(-1);
+        }
+        (KeyModifiers::CONTROL, KeyCode::Char('f')) | (KeyModifiers::NONE, KeyCode::PageDown) => {
+            app.move_record_cursor(page_size as isize);
+        }
+        (KeyModifiers::CONTROL, KeyCode::Char('b')) | (KeyModifiers::NONE, KeyCode::PageUp) => {
+            app.move_record_cursor(-(page_size as isize));
+        }
+        (KeyModifiers::NONE, KeyCode::Char('g') | KeyCode::Home) => {
+            app.record_cursor = 0;
+        }
+        (KeyModifiers::SHIFT, KeyCode::Char('G')) | (KeyModifiers::NONE, KeyCode::End) => {
+            app.move_record_cursor(isize::MAX);
+        }
+        (KeyModifiers::NONE, KeyCode::Enter) => {
+            app.goto_record(app.record_cursor);
+        }
+        _ => {}
+    }
+}
+
+/// Handle keys in the command palette: typing filters, Enter runs the selection.
+fn handle_palette(app: &mut App, key: KeyEvent, page_size: usize) {
+    let Some(palette) = app.palette.as_mut() else {
+        return;
+    };
+    match (key.modifiers, key.code) {
+        (_, KeyCode::Esc) | (KeyModifiers::CONTROL, KeyCode::Char(' ')) | (_, KeyCode::Null) => {
+            app.palette = None;
+        }
+        (_, KeyCode::Enter) => {
+            app.run_palette_entry();
+        }
+        (KeyModifiers::NONE, KeyCode::Down) | (KeyModifiers::CONTROL, KeyCode::Char('n')) => {
+            palette.move_cursor(1);
+        }
+        (KeyModifiers::NONE, KeyCode::Up) | (KeyModifiers::CONTROL, KeyCode::Char('p')) => {
+            palette.move_cursor(-1);
+        }
+        (_, KeyCode::PageDown) => {
+            palette.move_cursor(page_size as isize);
+        }
+        (_, KeyCode::PageUp) => {
+            palette.move_cursor(-(page_size as isize));
+        }
+        (_, KeyCode::Backspace) => {
+            let mut query = palette.query.clone();
+            query.pop();
+            palette.set_query(query);
+        }
+        (KeyModifiers::NONE | KeyModifiers::SHIFT, KeyCode::Char(c)) => {
+            let query = format!("{}{c}", palette.query);
+            palette.set_query(query);
+        }
+        _ => {}
+    }
+}
+
+/// Handle keys while a column merge/split asks about each sequence.
+fn handle_column_prompt(app: &mut App, key: KeyEvent) {
+    match key.code {
+        KeyCode::Char(c @ ('y' | 'n' | 'a' | 'q')) => app.answer_column_prompt(c),
+        KeyCode::Esc => app.answer_column_prompt('q'),
+        _ => app.answer_column_prompt('?'),
+    }
+}
+
+/// Handle keys while `:s///c` asks about each match.
+fn handle_substitute_prompt(app: &mut App, key: KeyEvent) {
+    match key.code {
+        KeyCode::Char(c @ ('y' | 'n' | 'a' | 'q')) => app.answer_substitute_prompt(c),
+        KeyCode::Esc => app.answer_substitute_prompt('q'),
+        _ => app.answer_substitute_prompt('?'),
+    }
+}
+
 /// Handle keys in insert mode.
 fn handle_insert_mode(app: &mut App, key: KeyEvent) {
     match key.code {
@@ -314,12 +879,17 @@ fn handle_insert_mode(app: &mut App, key: KeyEvent) {
         KeyCode::Char('.' | '-') => {
             app.insert_gap();
         }
-        KeyCode::Backspace => {
-            // Delete gap behind cursor
-            if app.cursor_col > 0 {
-                app.cursor_left();
-                app.delete_gap();
-            }
+        // Residues of a sequence added with :new-seq
+        KeyCode::Char(c) if c.is_ascii_alphabetic() || c == '*' => {
+            app.type_residue(c);
+        }
+        KeyCode::Backspace if app.on_typing_sequence() => {
+            app.untype_residue();
+        }
+        // Delete gap behind cursor
+        KeyCode::Backspace if app.cursor_col > 0 => {
+            app.cursor_left();
+            app.delete_gap();
         }
         KeyCode::Left => {
             app.cursor_left();
@@ -337,6 +907,52 @@ fn handle_insert_mode(app: &mut App, key: KeyEvent) {
     }
 }
 
+/// Handle keys while editing a numeric #=GC track.
+fn handle_track_mode(app: &mut App, key: KeyEvent, page_size: usize) {
+    match (key.modifiers, key.code) {
+        (KeyModifiers::NONE, KeyCode::Esc | KeyCode::Char('q')) => {
+            app.enter_normal_mode();
+            app.clear_status();
+        }
+        (KeyModifiers::NONE | KeyModifiers::SHIFT, KeyCode::Char('+' | '=')) => {
+            app.adjust_track_value(1);
+        }
+        (KeyModifiers::NONE, KeyCode::Char('-')) => {
+            app.adjust_track_value(-1);
+        }
+        (KeyModifiers::NONE, KeyCode::Char(c @ '0'..='9')) => {
+            app.set_track_value(c.to_digit(10).map(|d| d as u8));
+        }
+        (KeyModifiers::NONE | KeyModifiers::SHIFT, KeyCode::Char('*')) => {
+            app.set_track_value(Some(TRACK_MAX));
+        }
+        (KeyModifiers::NONE, KeyCode::Char('.' | 'x') | KeyCode::Delete) => {
+            app.set_track_value(None);
+        }
+        (KeyModifiers::NONE, KeyCode::Char('u')) => {
+            app.undo();
+        }
+        (KeyModifiers::CONTROL, KeyCode::Char('r')) => {
+            app.redo();
+        }
+        _ => {
+            handle_movement_keys(app, key, page_size, None);
+        }
+    }
+}
+
+/// Handle presentation-mode keys: Space and Backspace step through bookmarks, Esc
+/// leaves. Returns false for keys handled as in normal mode.
+fn handle_presentation_key(app: &mut App, key: KeyEvent) -> bool {
+    match (key.modifiers, key.code) {
+        (KeyModifiers::NONE, KeyCode::Char(' ')) => app.step_bookmark(true),
+        (KeyModifiers::NONE, KeyCode::Backspace) => app.step_bookmark(false),
+        (KeyModifiers::NONE, KeyCode::Esc) => app.stop_presentation(),
+        _ => return false,
+    }
+    true
+}
+
 /// Handle keys in command mode.
 fn handle_command_mode(app: &mut App, key: KeyEvent) {
     match key.code {
@@ -346,7 +962,16 @@ fn handle_command_mode(app: &mut App, key: KeyEvent) {
         }
         KeyCode::Enter => {
             app.completion = None;
+            // A command that moves within the same alignment (:120, :pair, :cn)
+            // is a jump
+            let from = app.cursor_mark();
+            let file = (app.file_path.clone(), app.record_index);
             app.execute_command();
+            if let Some(from) = from
+                && (app.file_path.clone(), app.record_index) == file
+            {
+                app.record_jump(from);
+            }
         }
         KeyCode::Backspace => {
             app.completion = None;
@@ -383,9 +1008,18 @@ fn handle_tab_completion(app: &mut App) {
         ("w ", rest)
     } else if let Some(rest) = buffer.strip_prefix("write ") {
         ("write ", rest)
+    } else if let Some(rest) = buffer.strip_prefix("r ") {
+        ("r ", rest)
+    } else if let Some(rest) = buffer.strip_prefix("read ") {
+        ("read ", rest)
     } else {
         return; // Not a file command
     };
+    // Keep a `++ft=<format>` option in front of the path
+    let (cmd, partial_path) = match partial_path.split_once(' ') {
+        Some((option, path)) if option.starts_with("++") => (format!("{cmd}{option} "), path),
+        _ => (cmd.to_string(), partial_path),
+    };
 
     // If we have existing completion state, cycle through candidates
     if let Some(ref mut state) = app.completion
@@ -494,8 +1128,12 @@ fn handle_search_mode(app: &mut App, key: KeyEvent) {
             app.enter_normal_mode();
         }
         KeyCode::Enter => {
+            let from = app.cursor_mark();
             app.execute_search();
             app.enter_normal_mode();
+            if let Some(from) = from {
+                app.record_jump(from);
+            }
         }
         KeyCode::Backspace => {
             app.search.pattern.pop();
@@ -542,6 +1180,10 @@ fn handle_visual_mode(app: &mut App, key: KeyEvent, page_size: usize) {
                 app.delete_selected_sequences();
                 return;
             }
+            ("\"...", KeyCode::Char(name)) => {
+                app.select_register(name);
+                return;
+            }
             _ => {
                 // Clear pending status on unrecognized sequence
                 app.clear_status();
@@ -553,7 +1195,7 @@ fn handle_visual_mode(app: &mut App, key: KeyEvent, page_size: usize) {
     if !matches!(
         (key.modifiers, key.code),
         (KeyModifiers::NONE, KeyCode::Char('g'))
-    ) && handle_movement_keys(app, key, page_size)
+    ) && handle_movement_keys(app, key, page_size, None)
     {
         return;
     }
@@ -565,14 +1207,27 @@ fn handle_visual_mode(app: &mut App, key: KeyEvent, page_size: usize) {
         }
 
         // Document movement (g starts two-key sequence for gg)
-        (KeyModifiers::NONE, KeyCode::Char('g')) => {
+        (KeyModifiers::NONE, KeyCode::Char('g')) if pending_status.as_deref() != Some("g...") => {
             app.set_status("g...");
         }
 
-        // Yank (copy) selection
+        // Command mode applying to the selected sequences
+        (KeyModifiers::NONE | KeyModifiers::SHIFT, KeyCode::Char(':')) => {
+            app.enter_command_mode();
+        }
+
+        // Yank (copy) selection, or the selected sequences whole
         (KeyModifiers::NONE, KeyCode::Char('y')) => {
             app.yank_selection();
         }
+        (KeyModifiers::SHIFT, KeyCode::Char('Y')) => {
+            app.yank_selected_sequences();
+        }
+
+        // Register prefix
+        (KeyModifiers::NONE | KeyModifiers::SHIFT, KeyCode::Char('"')) => {
+            app.set_status("\"...");
+        }
 
         // Delete: first 'd' starts sequence, 'x' deletes cells immediately
         (KeyModifiers::NONE, KeyCode::Char('d')) => {
@@ -582,6 +1237,38 @@ fn handle_visual_mode(app: &mut App, key: KeyEvent, page_size: usize) {
             app.delete_selection();
         }
 
+        // Pick up the block to move it sideways
+        (KeyModifiers::NONE, KeyCode::Char('m')) => {
+            app.pick_up_block();
+        }
+
+        // Switch between column range (v) and block (Ctrl-v) selection
+        (KeyModifiers::CONTROL, KeyCode::Char('v')) => {
+            app.toggle_visual_block();
+        }
+
+        // Gaps across the selected columns, or within the block's sequences
+        (KeyModifiers::SHIFT, KeyCode::Char('I')) if app.block_selection => {
+            app.run_edit(Edit::InsertBlockGaps, 1);
+        }
+        (KeyModifiers::SHIFT, KeyCode::Char('X')) if app.block_selection => {
+            app.run_edit(Edit::CloseBlockGaps, 1);
+        }
+        (KeyModifiers::SHIFT, KeyCode::Char('I')) => {
+            app.run_edit(Edit::InsertSelectionGapColumns, 1);
+        }
+        (KeyModifiers::SHIFT, KeyCode::Char('X')) => {
+            app.run_edit(Edit::DeleteSelectionGapColumns, 1);
+        }
+
+        // Case of the selected residues
+        (KeyModifiers::SHIFT, KeyCode::Char('U')) => {
+            app.run_edit(Edit::Uppercase, 1);
+        }
+        (KeyModifiers::NONE, KeyCode::Char('u')) => {
+            app.run_edit(Edit::Lowercase, 1);
+        }
+
         _ => {}
     }
 }
@@ -0,0 +1,73 @@
+//! Session log (`aform --log`).
+//!
+//! Commands, file reads and writes and their timings are recorded with
+//! `tracing` as one `key=value` line each, so that a failed integration (a
+//! tblout that doesn't parse, a save that is refused) can be traced after the
+//! fact. `:log` shows the file in the editor. Without `--log` no subscriber is
+//! installed and the events cost next to nothing.
+
+use std::fs::{self, File};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Entries `:log` shows, newest first.
+pub const VIEW_LINES: usize = 500;
+
+/// Directory session logs go to: `<data dir>/aform/log`, or the system
+/// temporary directory without one.
+fn log_dir() -> PathBuf {
+    dirs::data_dir()
+        .unwrap_or_else(std::env::temp_dir)
+        .join("aform")
+        .join("log")
+}
+
+/// Start logging to a new `session-<time>.log` file and return its path.
+pub fn init() -> io::Result<PathBuf> {
+    let dir = log_dir();
+    fs::create_dir_all(&dir)?;
+    let stamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs());
+    let path = dir.join(format!("session-{stamp}-{}.log", std::process::id()));
+    let file = File::create(&path)?;
+    tracing_subscriber::fmt()
+        .with_writer(Mutex::new(file))
+        .with_ansi(false)
+        .with_target(false)
+        .with_max_level(tracing::Level::DEBUG)
+        .try_init()
+        .map_err(io::Error::other)?;
+    tracing::info!(version = env!("CARGO_PKG_VERSION"), "session started");
+    Ok(path)
+}
+
+/// The last `VIEW_LINES` lines of the log at `path`, newest first.
+pub fn read_recent(path: &Path) -> io::Result<Vec<String>> {
+    let text = fs::read_to_string(path)?;
+    Ok(text
+        .lines()
+        .rev()
+        .take(VIEW_LINES)
+        .map(str::to_string)
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_read_recent() {
+        let path = std::env::temp_dir().join(format!("aform-log-test-{}.log", std::process::id()));
+        let lines: Vec<String> = (0..VIEW_LINES + 10).map(|i| format!("line {i}")).collect();
+        fs::write(&path, lines.join("\n")).unwrap();
+        let recent = read_recent(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(recent.len(), VIEW_LINES);
+        assert_eq!(recent[0], format!("line {}", VIEW_LINES + 9));
+        assert_eq!(recent[VIEW_LINES - 1], "line 10");
+    }
+}
@@ -3,18 +3,35 @@
 //! A vim-style terminal editor for RNA sequence alignments in Stockholm format,
 //! inspired by Emacs ralee mode.
 
+mod alifold;
 mod app;
+mod cli;
 mod clustering;
 mod color;
+mod confidence;
 mod config;
+mod crash;
 mod editor;
+mod formats;
 mod history;
+mod hits;
 mod input;
+mod logging;
+mod newick;
+mod palette;
+mod screenshot;
+mod script;
+mod search;
+mod shape;
 mod stockholm;
 mod structure;
+#[cfg(test)]
+mod testing;
+mod tutor;
 mod ui;
 
-use std::io;
+use std::io::{self, IsTerminal};
+use std::panic::{self, AssertUnwindSafe};
 use std::path::PathBuf;
 use std::time::Duration;
 
@@ -23,7 +40,10 @@ use ratatui::{
     Terminal,
     backend::CrosstermBackend,
     crossterm::{
-        event::{self, DisableMouseCapture, EnableMouseCapture, Event, MouseEventKind},
+        event::{
+            self, DisableMouseCapture, EnableMouseCapture, Event, KeyModifiers, MouseButton,
+            MouseEventKind,
+        },
         execute,
         terminal::{EnterAlternateScreen, LeaveAlternateScreen, disable_raw_mode, enable_raw_mode},
     },
@@ -36,12 +56,29 @@ use app::{App, TerminalTheme};
 #[command(name = "aform-rs")]
 #[command(author, version, about, long_about = None)]
 #[command(after_help = AFTER_HELP)]
+#[command(args_conflicts_with_subcommands = true, disable_help_subcommand = true)]
 struct Args {
-    /// Stockholm alignment file to open.
+    #[command(subcommand)]
+    command: Option<cli::Command>,
+
+    /// Alignment files to open (Stockholm, aligned FASTA, A2M, Clustal, PHYLIP,
+    /// MSF or SELEX); step through several with :n and :N. Use `-`, or pipe into
+    /// aform, to read from stdin.
     #[arg(value_name = "FILE")]
-    file: Option<PathBuf>,
+    files: Vec<PathBuf>,
+
+    /// Read the files (or stdin) as this format instead of detecting it from the
+    /// extension or contents: stockholm, fasta, clustal, a2m, phylip, msf or selex.
+    #[arg(long, value_name = "FORMAT", value_parser = parse_format)]
+    format: Option<formats::Format>,
 
-    /// Initial color scheme (none, structure, base, conservation, compensatory).
+    /// Open read-only: navigation, coloring and search work, editing and :w do not
+    /// (:set noreadonly to edit).
+    #[arg(short = 'R', long)]
+    readonly: bool,
+
+    /// Initial color scheme (none, structure, base, conservation, compensatory),
+    /// optionally with an overlay layer, e.g. base+cons.
     #[arg(short, long, default_value = "none")]
     color: String,
 
@@ -76,12 +113,65 @@ struct Args {
     /// Show short IDs (strip coordinate suffix like /10000-20000).
     #[arg(long)]
     shortid: bool,
+
+    /// Start the interactive tutorial on a bundled toy alignment.
+    #[arg(long)]
+    tutor: bool,
+
+    /// Replay a session script written with `:w session.aform` on the opened
+    /// file; a script ending in :wq saves and exits without the editor.
+    #[arg(long, value_name = "SCRIPT")]
+    script: Option<PathBuf>,
+
+    /// Log commands, file reads and writes and their timings to a session log
+    /// file (`:log` shows it).
+    #[arg(long)]
+    log: bool,
 }
 
 const AFTER_HELP: &str = "\
 INTERACTIVE COMMANDS:
   Press ':' to enter command mode, then type a command and press Enter.
-  Press '?' for interactive help overlay.
+  Press '?' for interactive help overlay. New users: try aform --tutor.
+  Press Ctrl-Space for a searchable palette of every action and its key.
+  Press Ctrl-z (or :suspend) to drop to the shell; fg brings the editor back.
+
+FILES:
+  :w [path]       Save (format from extension: .sto .fa .aln .a2m .phy .msf .slx)
+  :w <name>.aform  Write this session's editing commands as a script
+                  (replay with aform --script <name>.aform <file>)
+  :goto <id|row> [col]  Move to a sequence by ID or row number, and a column
+  :n / :N         Next/previous file given on the command line (:n! discards changes)
+  :args           List the command-line files, current one in brackets
+  :r <path>       Append the file's sequences below the cursor (padded to the width)
+  :new-seq <id> [residues]  Add a sequence below the cursor: residues left-aligned, or
+                  gaps to type residues into in insert mode
+  :e ++ft=<f> <path>  Open a file as format f whatever its name (also aform --format f)
+  :next-msa / :prev-msa  Step through the alignments of a multi-record file
+  :msa [N]        List the file's alignments, or open the Nth
+  /<motif>        Find and highlight a motif in the sequences (n/N step, :noh clears)
+  :set search-gaps=exact  Match gaps in the aligned text instead of skipping them
+  :set search=iupac|regex  Read patterns as IUPAC codes (RRACH) or regexes (literal resets)
+  /re:<regex>     Regex over residues (gap-stripped) and sequence IDs, whatever :set search is
+  :grepall <pat>  Search sequences, IDs and #=GF lines of all files (:copen, :cn, :cp)
+  :set            List all settings, editable in place (also :settings; u undoes a :set)
+  :set format=<f> Always save as stockholm/fasta/clustal/a2m/phylip/msf/selex (auto resets)
+  :set wrap=<n>   Write Stockholm in blocks of n columns (:set wrap=off for one line)
+  :set style=pfam Write compact Pfam-style Stockholm (:set style=stockholm for classic)
+  :set eol=crlf   Save with Windows line endings (:set eol=lf for Unix; default: the file's)
+  :set backup=on  Keep the previous file as <file>~ when saving (saves are atomic)
+  :set readonly   Refuse all edits and :w, like aform -R (:set noreadonly to edit)
+  :export <f> <path>  Write a copy as nexus/json/stockholm/fasta/clustal/a2m/phylip/msf/selex
+  :export html <path>  Write the colored alignment as a standalone HTML page
+  :export svg <path>   Write the colored alignment as an SVG figure
+  :export ansi <path>  Write the colored alignment as ANSI text
+  :export dbn <path>   Write the consensus and SS_cons as dot-bracket (gap columns dropped)
+  :export region <path>  Write only the visual selection (also :w! <path> from v)
+  :export columns <path>  Write the selected columns of every sequence
+  :screenshot <path>  Save the screen as ANSI-colored text (view with cat or less -R)
+  :fixann         Pad/truncate #=GC/#=GR rows to the alignment width
+  :log            Show the session log, newest first (start aform with --log)
+  :history        Graph alignment width and sequence count since loading
 
 VISUALIZATION:
   :ruler          Toggle column ruler
@@ -91,101 +181,261 @@ VISUALIZATION:
   :vsplit / :vs   Vertical split view
   :only           Close split view
   :tree           Toggle dendrogram tree (requires :cluster)
+  :pair <H>:<N>   Go to base pair N of helix H, counted from its outer end (P3:5)
+  :helix [H]      Each sequence's 5' strand over its reversed 3' strand for the helix
+                  at the cursor (or helix H), one base pair per column; h/l change helix
+  :pin [N-M..]    Keep the cursor/selected/given columns on screen while scrolling
+                  (:unpin N-M.. releases some, :pin off all)
+  :marks          List the marks set with m{a-z} (`a jumps back; Ctrl-o/Ctrl-i step through jumps)
+  :pileup         Distinct residues of the cursor column with counts; Space lists
+                  the IDs carrying one, h/l change column, Enter jumps to a sequence
+  :bookmark       Bookmark the view (or selection) for presentation (:bookmark clear)
+  :present [secs] Large-cell presentation view; Space/Backspace step through
+                  bookmarks (every secs if given), Esc or :present off leaves
 
 CONSERVATION:
   :conservation   Toggle conservation bar (shows column-wise identity)
   :consbar        Alias for :conservation
+  :confidence     Toggle column confidence bar (pairwise consistency)
+  :confidence mask <t>  Remove columns with confidence below t (0-1 or %)
+  :track <tag>    Graph and edit a numeric #=GC row (+/- change, 0-9/* set, Esc leaves)
+  :track mask <n> Remove columns whose track value is below n (:track off hides)
+  :mask pp<n      Mask residues whose #=GR PP is below n (10 = *)
+  :colset <c> [rows]  Set the cursor column to c in every sequence (or rows, e.g. 3-7)
+  :filter-cols gaps>f [mask]  Remove (or mask) columns with more than fraction f gaps
+  :set mask=lower|gap  Masking lowercases residues (default) or replaces them with gaps
+
+EDITING:
+  :throwcol left|right [N]  Move column residues as far as possible
+  :mergecol [left|right]    Merge cursor column with a neighbour (y/n/a/q per residue)
+  :mergecol! [left|right]   Merge without prompts
+  :splitcol [left|right]    Move selected or confirmed residues into a new column
+  :sort name|length|gaps|identity [asc|desc]  Sort sequences (identity to the :ref sequence)
+  :sort tree [file]         Order sequences as the leaves of the #=GF NH (or a Newick file) tree
+  :sortcol                  Sort sequences by their residue in the cursor column
+  :<command> --preview      List what a bulk edit (:trim, :mask, :%s...) would change; y applies
+  v ... I / X               Insert gap columns before / delete gap columns in the selection
+  v ... :trim               Keep only the selected columns (:upper/:lower/:t2u/:u2t the block)
+  :s/from/to/[gci]          Replace in the current sequence or selection (:%s everywhere;
+                            g all matches, c confirm each, i ignore case)
+  Ctrl-v ... I / X          Push gaps into / close up gaps within the selected sequences only
+  .                         Repeat the last x, I, X, <, >, {, } or visual-mode edit at the cursor
+  yy / p                    Copy the sequence with its #=GS/#=GR lines, put the copy (ID_copy)
+  \"ayy / \"ap / v ... \"ay    Yank into / put from register a-z (:registers lists them)
+  :set force-delete=on|off  Let x delete residues without confirmation
+  :set rf-snap=on|off       Make I insert before the next #=GC RF match column (new columns are RF .)
+  :set paranoid=on|off      Check every row is the alignment width after each edit
+  :set hover=on|off         Show residue info under the mouse (Ctrl-click always does)
+  :set gapchars=<chars>     Characters read as gaps (default .-_~:; also [editor] in aform.toml)
 
 CONSENSUS:
   :consensus      Toggle consensus sequence display
 
 CLUSTERING:
   :cluster        Cluster sequences by similarity (UPGMA)
+  :cluster <N>    Cluster at N% identity (greedy, CD-HIT-like)
+  :clusters       Show cluster sizes
+  :wreps <path>   Write representative-only alignment
+  :dedup [N]      Remove duplicate sequences (and those N% identical to another)
   :uncluster      Restore original sequence order
   :collapse       Toggle collapsing identical sequences
   :tree           Show/hide dendrogram tree
 
+CURATION:
+  :thresholds <tblout>  Suggest GA/TC/NC from cmsearch/nhmmer scores
+  :hits <tblout>  Browse search hits; Space marks, a marks new hits
+  :hits fetch <path>  Write marked hits as an esl-sfetch -Cf list
+  :hits align <seqdb> <model>  Fetch marked hits and align them into the family
+                  (esl-sfetch, cmalign --mapali; u restores)
+  :contam <tblout> [clanin]  Flag sequences another family scores better
+  :zscore [N]     MFE z-score per sequence vs N shuffles (:zscore off hides)
+  :alifold        Replace SS_cons with RNAalifold's consensus structure (u restores;
+                  :set alifold.ribosum=on for -r, alifold.mis=on adds #=GC MIS)
+  :rename [old] <new>  Rename a sequence (cw: the cursor one) with its #=GS/#=GR lines
+  Alt-j / Alt-k   Move the sequence down/up a row (a count moves it further)
+  :move <row>     Move the sequence to row N of the file
+  :flag <label>   Flag current sequence (stored as #=GS <id> FL <label>)
+  :unflag         Remove flag from current sequence
+  :coords [path]  List IDs whose /start-end disagrees with the residues (path: TSV)
+  :fixcoords [path]  Rewrite /start-end from the residues, or a source FASTA or table
+  :filter flagged Show only flagged sequences (:filter off to clear)
+
 COLOR SCHEMES:
   :color none         No coloring
   :color structure    Color by secondary structure (helix pairs)
   :color base         Color by nucleotide/amino acid identity
   :color conservation Color by column conservation
   :color compensatory Color by compensatory mutations (requires SS_cons)
-
-  Aliases: ss=structure, nt/residue/aa/protein=base, cons=conservation, comp=compensatory
+  :color ssdiff       Color where a sequence's #=GR SS disagrees with SS_cons
+  :ssdiff             List sequences whose #=GR SS disagrees with SS_cons
+  :color <a>+<b>      Stack an overlay on scheme a: ss/base/comp color the letters,
+                      cons/pp/gaps dim the background (e.g. :color base+cons)
+  :focus              Color only the visual selection (:focus off colors everything)
+  :focus helix [N..]  Color only helices N.. (none: toggle the helix at the cursor)
+
+  Aliases: ss=structure, nt/residue/aa/protein=base, cons=conservation, comp=compensatory,
+           grss=ssdiff
 ";
 
+/// Parse a `--format` name.
+fn parse_format(name: &str) -> Result<formats::Format, String> {
+    formats::Format::from_name(name)
+        .ok_or_else(|| "expected stockholm, fasta, clustal, a2m, phylip, msf or selex".to_string())
+}
+
 fn main() -> Result<(), Box<dyn std::error::Error>> {
     let args = Args::parse();
 
+    // Subcommands run without the editor
+    if let Some(command) = args.command {
+        if let Err(e) = cli::run(command) {
+            eprintln!("aform: {e}");
+            std::process::exit(1);
+        }
+        return Ok(());
+    }
+
     // Load configuration
     let config = config::Config::load();
 
+    let log_path = if args.log {
+        match logging::init() {
+            Ok(path) => Some(path),
+            Err(e) => {
+                eprintln!("aform: could not start the session log: {e}");
+                None
+            }
+        }
+    } else {
+        None
+    };
+
+    // Read piped input before the TUI takes over the terminal; keyboard input then
+    // comes from the controlling terminal instead of stdin
+    let from_stdin = !args.tutor
+        && match args.files.as_slice() {
+            [] => !io::stdin().is_terminal(),
+            [path] => path.as_os_str() == "-",
+            _ => false,
+        };
+    let stdin_alignment = from_stdin.then(|| formats::read_stdin(args.format));
+
     // Detect terminal theme before entering raw mode
     let terminal_theme = detect_terminal_theme();
 
     // Setup terminal
+    crash::install_panic_hook();
     enable_raw_mode()?;
     let mut stdout = io::stdout();
     execute!(stdout, EnterAlternateScreen, EnableMouseCapture)?;
     let backend = CrosstermBackend::new(stdout);
     let mut terminal = Terminal::new(backend)?;
 
-    // Create app
+    // Create app; a panic from here on, while loading files, replaying a script
+    // or editing, has restored the terminal already (see crash.rs)
     let mut app = App::new();
-    app.terminal_theme = terminal_theme;
-    app.theme = config.theme;
-
-    // Set color scheme
-    if let Some(scheme) = app::ColorScheme::from_str(&args.color) {
-        app.color_scheme = scheme;
-    }
+    let res = panic::catch_unwind(AssertUnwindSafe(|| -> io::Result<()> {
+        app.read_only = args.readonly;
+        app.log_path = log_path;
+        app.terminal_theme = terminal_theme;
+        app.theme = config.theme;
+        let config_error = app.apply_editor_config(&config.editor);
+
+        // Set color scheme
+        if let Some((scheme, overlay)) = app::ColorScheme::parse_layers(&args.color) {
+            app.color_scheme = scheme;
+            app.color_overlay = overlay;
+        }
 
-    // Load file if provided
-    if let Some(path) = args.file {
-        if let Err(e) = app.load_file(&path) {
-            app.set_status(format!("Error: {}", e));
+        // Load files (or stdin) if provided
+        if args.tutor {
+            app.start_tutor();
+        } else if let Some(result) = stdin_alignment {
+            match result {
+                Ok((alignments, ..)) => app.load_stdin(alignments),
+                Err(e) => app.set_status(format!("Error: Failed to parse stdin: {e}")),
+            }
         } else {
-            // Auto-detect sequence type and precompute collapse groups
-            app.detect_sequence_type();
-            app.precompute_collapse_groups();
+            app.arg_format = args.format;
+            app.load_arg_files(args.files);
+        }
+        if let Some(error) = config_error {
+            app.set_status(error);
         }
-    }
-
-    // Apply display options from CLI (only enable, don't disable defaults)
-    if args.consensus {
-        app.show_consensus = true;
-    }
-    if args.conservation {
-        app.show_conservation_bar = true;
-    }
-    if args.ruler {
-        app.show_ruler = true;
-    }
-    if args.rownum {
-        app.show_row_numbers = true;
-    }
-    if args.shortid {
-        app.show_short_ids = true;
-    }
 
-    // Apply clustering options (only if file loaded)
-    if app.alignment.num_sequences() > 0 {
-        if args.collapse {
-            app.toggle_collapse_identical();
+        // Apply display options from CLI (only enable, don't disable defaults)
+        if args.consensus {
+            app.show_consensus = true;
         }
-        if args.cluster || args.tree {
-            app.cluster_sequences();
+        if args.conservation {
+            app.show_conservation_bar = true;
         }
-        if args.tree {
-            app.show_tree = true;
+        if args.ruler {
+            app.show_ruler = true;
+        }
+        if args.rownum {
+            app.show_row_numbers = true;
+        }
+        if args.shortid {
+            app.show_short_ids = true;
+        }
+
+        // Apply clustering options (only if file loaded)
+        if app.alignment.num_sequences() > 0 {
+            if args.collapse {
+                app.toggle_collapse_identical();
+            }
+            if args.cluster || args.tree {
+                app.cluster_sequences();
+            }
+            if args.tree {
+                app.show_tree = true;
+            }
+        }
+
+        // Replay a session script; one that ends by quitting never shows the editor
+        if let Some(path) = &args.script {
+            match app.run_script(path) {
+                Ok(count) if app.should_quit => {
+                    crash::restore_terminal();
+                    eprintln!("aform: replayed {count} commands from {}", path.display());
+                    return Ok(());
+                }
+                Ok(count) => {
+                    app.set_status(format!("Replayed {count} commands from {}", path.display()))
+                }
+                Err(e) => app.set_status(format!("Script stopped at {e}")),
+            }
         }
-    }
 
-    // Run main loop
-    let res = run_app(&mut terminal, &mut app);
+        // Run main loop
+        run_app(&mut terminal, &mut app)
+    }));
 
     // Restore terminal
+    crash::restore_terminal();
+
+    // Save a report and the alignment when the editor didn't end normally
+    let reason = match res {
+        Ok(Ok(())) => return Ok(()),
+        Ok(Err(err)) => {
+            eprintln!("Error: {err}");
+            format!("Error: {err}")
+        }
+        Err(_) => "Panic".to_string(),
+    };
+    tracing::error!(reason = %reason, "editor ended abnormally");
+    match crash::write_report(&app, &reason) {
+        Ok(path) => eprintln!("aform: crash report saved to {}", path.display()),
+        Err(e) => eprintln!("aform: could not write a crash report: {e}"),
+    }
+    std::process::exit(1);
+}
+
+/// Give the terminal back to the shell and stop the process (Ctrl-Z), then
+/// take it over again and redraw everything when the shell resumes it with `fg`.
+#[cfg(unix)]
+fn suspend(terminal: &mut Terminal<CrosstermBackend<io::Stdout>>) -> io::Result<()> {
     disable_raw_mode()?;
     execute!(
         terminal.backend_mut(),
@@ -194,15 +444,36 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
     )?;
     terminal.show_cursor()?;
 
-    if let Err(err) = res {
-        eprintln!("Error: {}", err);
+    // SAFETY: raise only sends a signal to this process; it returns once the
+    // shell continues the stopped process.
+    unsafe {
+        libc::raise(libc::SIGTSTP);
     }
 
+    enable_raw_mode()?;
+    execute!(
+        terminal.backend_mut(),
+        EnterAlternateScreen,
+        EnableMouseCapture
+    )?;
+    terminal.clear()
+}
+
+#[cfg(not(unix))]
+fn suspend(_terminal: &mut Terminal<CrosstermBackend<io::Stdout>>) -> io::Result<()> {
     Ok(())
 }
 
+/// Most queued input events handled between two redraws.
+const MAX_EVENTS_PER_FRAME: usize = 32;
+
 fn run_app(terminal: &mut Terminal<CrosstermBackend<io::Stdout>>, app: &mut App) -> io::Result<()> {
+    let mut last_size = None;
     loop {
+        app.poll_hit_align_job();
+        app.poll_fold_job();
+        app.poll_alifold_job();
+        app.poll_cluster_job();
         // Calculate visible dimensions for viewport adjustment
         let size = terminal.size()?;
         let area = ratatui::layout::Rect::new(0, 0, size.width, size.height);
@@ -216,50 +487,101 @@ fn run_app(terminal: &mut Terminal<CrosstermBackend<io::Stdout>>, app: &mut App)
         } else {
             app.alignment.max_id_len()
         };
-        let (visible_rows, visible_cols) = ui::visible_dimensions(
-            area,
-            app.visible_sequence_count(),
-            max_id_len,
-            app.show_ruler,
-            app.show_row_numbers,
-            app.show_short_ids,
-            app.split_mode,
-            app.alignment.ss_cons().is_some(),
-            app.alignment.rf().is_some(),
-            app.show_rf_bar,
-            app.alignment.pp_cons().is_some(),
-            app.show_pp_cons,
-            app.show_consensus,
-            app.show_conservation_bar,
-            app.max_collapse_count(),
-            tree_display_width,
-            app.alignment.width(),
-        );
-
-        // Adjust viewport to keep cursor visible
-        app.adjust_viewport(visible_rows, visible_cols);
-
-        // Draw UI
-        terminal.draw(|f| ui::render(f, app))?;
-
-        // Handle events
+        app.tick_presentation();
+        let screen = ui::ScreenSize::of(area);
+        let (visible_rows, visible_cols) = if screen != ui::ScreenSize::Full {
+            ui::compact_dimensions(area, app)
+        } else if app.presentation.is_some() {
+            ui::presentation_dimensions(area, app)
+        } else {
+            ui::visible_dimensions(
+                area,
+                app.visible_sequence_count(),
+                max_id_len,
+                app.show_ruler,
+                app.show_row_numbers,
+                app.show_short_ids,
+                app.split_mode,
+                app.alignment.ss_cons().is_some(),
+                app.alignment.rf().is_some(),
+                app.show_rf_bar,
+                app.alignment.pp_cons().is_some(),
+                app.show_pp_cons,
+                app.show_consensus,
+                app.show_conservation_bar,
+                app.show_confidence_bar,
+                app.track_row().is_some(),
+                app.max_collapse_count(),
+                app.alignment.has_sequence_flags(),
+                app.show_zscores,
+                tree_display_width,
+                app.pinned_display_width(),
+                app.alignment.width(),
+            )
+        };
+
+        // Adjust viewport to keep cursor visible, refitting it after a resize
+        if last_size.replace(size) != Some(size) {
+            app.clamp_viewport(visible_rows, visible_cols);
+        } else {
+            app.adjust_viewport(visible_rows, visible_cols);
+        }
+
+        // Draw UI, saving the frame if a screenshot was requested
+        let frame = terminal.draw(|f| ui::render(f, app))?;
+        if let Some(path) = app.screenshot_path.take() {
+            match screenshot::write_ansi(frame.buffer, &path) {
+                Ok(()) => app.set_status(format!("Saved screenshot to {}", path.display())),
+                Err(e) => app.set_status(format!("Failed to save screenshot: {e}")),
+            }
+        }
+
+        // Handle events, draining any that queued up during the last frame (e.g.
+        // held-key auto-repeat) so a slow redraw doesn't fall behind the keyboard
         if event::poll(Duration::from_millis(100))? {
-            match event::read()? {
-                Event::Key(key) => input::handle_key(app, key, visible_rows),
-                Event::Mouse(mouse) => match mouse.kind {
-                    MouseEventKind::ScrollUp => app.cursor_up(),
-                    MouseEventKind::ScrollDown => app.cursor_down(),
-                    MouseEventKind::ScrollLeft => app.cursor_left(),
-                    MouseEventKind::ScrollRight => app.cursor_right(),
+            for _ in 0..MAX_EVENTS_PER_FRAME {
+                match event::read()? {
+                    Event::Key(key) => {
+                        app.hover = None;
+                        app.begin_key(&key);
+                        input::handle_key(app, key, visible_rows);
+                        app.end_key();
+                        app.check_integrity();
+                        app.record_shape();
+                        app.check_tutor();
+                    }
+                    Event::Mouse(mouse) => match mouse.kind {
+                        MouseEventKind::ScrollUp => app.cursor_up(),
+                        MouseEventKind::ScrollDown => app.cursor_down(),
+                        MouseEventKind::ScrollLeft => app.cursor_left(),
+                        MouseEventKind::ScrollRight => app.cursor_right(),
+                        MouseEventKind::Moved => app.mouse_hover(mouse.column, mouse.row, false),
+                        MouseEventKind::Down(MouseButton::Left)
+                            if mouse
+                                .modifiers
+                                .intersects(KeyModifiers::CONTROL | KeyModifiers::ALT) =>
+                        {
+                            app.mouse_hover(mouse.column, mouse.row, true);
+                        }
+                        _ => {}
+                    },
+                    // Redraw at the new size before handling anything else
+                    Event::Resize(..) => break,
                     _ => {}
-                },
-                _ => {}
+                }
+                if app.should_quit || !event::poll(Duration::ZERO)? {
+                    break;
+                }
             }
         }
 
         if app.should_quit {
             return Ok(());
         }
+        if app.suspend_requested {
+            app.suspend_requested = false;
+            suspend(terminal)?;
+        }
     }
 }
 
@@ -0,0 +1,136 @@
+//! Leaf order of Newick trees, from `#=GF NH` lines or a tree file, for
+//! `:sort tree`.
+
+use std::collections::HashMap;
+
+use crate::stockholm::short_id;
+
+/// Leaf names of the first tree in `text`, left to right. Branch lengths,
+/// internal node labels and `[comments]` are skipped; `'quoted'` names keep
+/// their spaces and punctuation.
+pub fn leaf_order(text: &str) -> Result<Vec<String>, String> {
+    let mut leaves = Vec::new();
+    let mut depth = 0usize;
+    // Whether the next label names a leaf (after `(` or `,`) rather than the
+    // node just closed
+    let mut expect_leaf = true;
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '(' => {
+                depth += 1;
+                expect_leaf = true;
+            }
+            ')' => {
+                depth = depth
+                    .checked_sub(1)
+                    .ok_or_else(|| "unbalanced ')'".to_string())?;
+                expect_leaf = false;
+            }
+            ',' => expect_leaf = true,
+            ';' => break,
+            '[' => {
+                if !chars.by_ref().any(|c| c == ']') {
+                    return Err("unclosed [comment]".to_string());
+                }
+            }
+            ':' => {
+                while chars
+                    .next_if(|c| !matches!(c, ',' | ')' | '(' | ';' | '['))
+                    .is_some()
+                {}
+            }
+            c if c.is_whitespace() => {}
+            '\'' => {
+                let mut name = String::new();
+                loop {
+                    match chars.next() {
+                        // '' inside quotes is a literal quote
+                        Some('\'') if chars.next_if_eq(&'\'').is_some() => name.push('\''),
+                        Some('\'') => break,
+                        Some(c) => name.push(c),
+                        None => return Err("unclosed quoted name".to_string()),
+                    }
+                }
+                if expect_leaf {
+                    leaves.push(name);
+                }
+            }
+            c => {
+                let mut name = c.to_string();
+                while let Some(c) = chars.next_if(|c| {
+                    !c.is_whitespace() && !matches!(c, ',' | ')' | '(' | ';' | ':' | '[')
+                }) {
+                    name.push(c);
+                }
+                if expect_leaf {
+                    leaves.push(name);
+                }
+            }
+        }
+    }
+    if depth > 0 {
+        return Err("unbalanced '('".to_string());
+    }
+    if leaves.is_empty() {
+        return Err("no leaves".to_string());
+    }
+    Ok(leaves)
+}
+
+/// Rows of `ids` in the order their leaves appear in `leaves`, followed by rows
+/// not in the tree in their current order. Leaves match an ID exactly, or
+/// without its `/start-end` suffix. Also returns how many rows were not found.
+pub fn tree_order(ids: &[&str], leaves: &[String]) -> (Vec<usize>, usize) {
+    let mut exact: HashMap<&str, usize> = HashMap::new();
+    let mut short: HashMap<&str, usize> = HashMap::new();
+    for (row, id) in ids.iter().enumerate().rev() {
+        exact.insert(id, row);
+        short.insert(short_id(id), row);
+    }
+    let mut placed = vec![false; ids.len()];
+    let mut order = Vec::with_capacity(ids.len());
+    for leaf in leaves {
+        let row = exact
+            .get(leaf.as_str())
+            .or_else(|| short.get(short_id(leaf)))
+            .copied();
+        if let Some(row) = row
+            && !placed[row]
+        {
+            placed[row] = true;
+            order.push(row);
+        }
+    }
+    let missing = placed.iter().filter(|&&p| !p).count();
+    order.extend((0..ids.len()).filter(|&row| !placed[row]));
+    (order, missing)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_leaf_order() {
+        assert_eq!(
+            leaf_order("((B:0.1,'seq C':0.2)90:0.05,[note]A/1-20);").unwrap(),
+            vec!["B", "seq C", "A/1-20"]
+        );
+        assert_eq!(leaf_order("(a,b);(c,d);").unwrap(), vec!["a", "b"]);
+        assert_eq!(leaf_order("('it''s',x);").unwrap(), vec!["it's", "x"]);
+        assert!(leaf_order("((a,b);").is_err());
+        assert!(leaf_order("(a,b));").is_err());
+        assert!(leaf_order("").is_err());
+    }
+
+    #[test]
+    fn test_tree_order() {
+        let ids = ["A/1-20", "B/5-30", "C", "D"];
+        let leaves: Vec<String> = ["C", "B", "A/1-20", "X"]
+            .iter()
+            .map(|s| s.to_string())
+            .collect();
+        assert_eq!(tree_order(&ids, &leaves), (vec![2, 1, 0, 3], 1));
+    }
+}
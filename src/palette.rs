@@ -0,0 +1,315 @@
+//! Command palette (Ctrl-Space): every editor action with its key, filtered by a
+//! fuzzy search and run with Enter.
+
+use crate::app::App;
+
+/// What a palette entry does when chosen.
+#[derive(Clone, Copy)]
+pub enum Action {
+    /// Run a colon command as typed.
+    Command(&'static str),
+    /// Open the command line with this text, for commands that need an argument.
+    Prompt(&'static str),
+    /// Call an editor method, as its key would.
+    Run(fn(&mut App)),
+}
+
+/// One palette entry: a description, the key or command bound to it, and the
+/// action.
+pub struct Entry {
+    pub name: &'static str,
+    pub key: &'static str,
+    pub action: Action,
+}
+
+// Entry constructors, so the table below fits one action per line
+const fn command(name: &'static str, key: &'static str, command: &'static str) -> Entry {
+    Entry {
+        name,
+        key,
+        action: Action::Command(command),
+    }
+}
+
+const fn prompt(name: &'static str, key: &'static str, text: &'static str) -> Entry {
+    Entry {
+        name,
+        key,
+        action: Action::Prompt(text),
+    }
+}
+
+const fn run(name: &'static str, key: &'static str, run: fn(&mut App)) -> Entry {
+    Entry {
+        name,
+        key,
+        action: Action::Run(run),
+    }
+}
+
+/// Every action offered by the palette.
+#[rustfmt::skip]
+pub const ENTRIES: &[Entry] = &[
+    // Files
+    command("Save file", ":w", "w"),
+    prompt("Save as...", ":w <path>", "w "),
+    prompt("Open file...", ":e <path>", "e "),
+    prompt("Append sequences from file...", ":r <path>", "r "),
+    prompt("New sequence...", ":new-seq <id> [residues]", "new-seq "),
+    prompt("Move sequence to row...", ":move <row>", "move "),
+    command("Quit", ":q", "q"),
+    command("Save and quit", ":wq", "wq"),
+    prompt("Write session script...", ":w <name>.aform", "w session.aform"),
+    prompt("Go to sequence...", ":goto <id|row> [column]", "goto "),
+    run("Suspend to shell", "Ctrl-z", App::suspend),
+    command("Next file", ":n", "n"),
+    command("Previous file", ":N", "N"),
+    command("Next alignment in file", ":next-msa", "next-msa"),
+    command("Previous alignment in file", ":prev-msa", "prev-msa"),
+    command("List alignments in file", ":msa", "msa"),
+    prompt("Export as...", ":export <f> <path>", "export "),
+    prompt("Export dot-bracket consensus...", ":export dbn <path>", "export dbn "),
+    prompt("Save screenshot...", ":screenshot <path>", "screenshot "),
+    command("Settings", ":settings", "settings"),
+    command("Show session log", ":log", "log"),
+    command("Graph alignment size this session", ":history", "history"),
+    prompt("Set gap character...", ":set gap=<char>", "set gap="),
+    prompt("Set characters read as gaps...", ":set gapchars=<chars>", "set gapchars="),
+    command("Read-only mode", ":set readonly", "set readonly"),
+    command("Allow editing", ":set noreadonly", "set noreadonly"),
+    // Navigation
+    run("First sequence", "gg", App::cursor_first_sequence),
+    run("Last sequence", "G", App::cursor_last_sequence),
+    run("Start of line", "0", App::cursor_line_start),
+    run("End of line", "$", App::cursor_line_end),
+    run("Go to base pair partner", "gp", App::goto_pair),
+    run("Jump back", "Ctrl-o", App::jump_back),
+    run("Jump forward", "Ctrl-i", App::jump_forward),
+    command("List marks", ":marks", "marks"),
+    command("List registers", ":registers", "registers"),
+    prompt("Go to helix pair...", ":pair <helix>:<n>", "pair "),
+    command("Helix view", ":helix", "helix"),
+    run("Show column residues", "K", App::toggle_column_popup),
+    command("Column pileup", ":pileup", "pileup"),
+    run("Search...", "/", App::enter_search_mode),
+    run("Next search match", "n", App::search_next),
+    run("Previous search match", "N", App::search_prev),
+    command("Clear search highlighting", ":noh", "noh"),
+    command("Search: literal residues", ":set search=literal", "set search=literal"),
+    command("Search: IUPAC codes", ":set search=iupac", "set search=iupac"),
+    command("Search: regular expressions", ":set search=regex", "set search=regex"),
+    command("Search: skip gaps", ":set search-gaps=skip", "set search-gaps=skip"),
+    command("Search: match gaps exactly", ":set search-gaps=exact", "set search-gaps=exact"),
+    prompt("Search all files...", ":grepall <pattern>", "grepall "),
+    // Editing
+    run("Undo", "u", App::undo),
+    run("Redo", "Ctrl-r", App::redo),
+    run("Repeat last edit", ".", |app| app.repeat_last_edit(None)),
+    run("Insert mode", "i", App::enter_insert_mode),
+    run("Visual selection", "v", App::enter_visual_mode),
+    run("Visual block selection", "Ctrl-v", App::enter_visual_block_mode),
+    run("Insert gap column", "I", |app| { app.insert_gap_column(); }),
+    run("Delete gap column", "X", |app| { app.delete_gap_column(); }),
+    run("Delete gap at cursor", "x", |app| { app.delete_gap(); }),
+    run("Shift sequence left", "<", |app| { app.shift_sequence_left(); }),
+    run("Shift sequence right", ">", |app| { app.shift_sequence_right(); }),
+    run("Throw sequence left", "{", App::throw_sequence_left),
+    run("Throw sequence right", "}", App::throw_sequence_right),
+    run("Delete sequence", "dd", App::delete_sequence),
+    run("Yank sequence", "yy", App::yank_sequence),
+    run("Rename sequence", "cw", App::start_rename),
+    run("Move sequence down", "Alt-j", |app| app.move_sequence_by(1, false)),
+    run("Move sequence up", "Alt-k", |app| app.move_sequence_by(1, true)),
+    run("Put register below", "p", |app| app.paste(false)),
+    run("Put register above", "P", |app| app.paste(true)),
+    command("Trim gap-only end columns", ":trim", "trim"),
+    command("Preview trimming gap-only end columns", ":trim --preview", "trim --preview"),
+    command("Fix annotation lengths", ":fixann", "fixann"),
+    command("Insert columns before the next RF match column", ":set rf-snap=on", "set rf-snap=on"),
+    command("Insert columns at the cursor", ":set rf-snap=off", "set rf-snap=off"),
+    command("Show residue info under the mouse", ":set hover=on", "set hover=on"),
+    command("Show residue info on Ctrl-click only", ":set hover=off", "set hover=off"),
+    command("Uppercase", ":upper", "upper"),
+    command("Lowercase", ":lower", "lower"),
+    command("Convert T to U", ":t2u", "t2u"),
+    command("Convert U to T", ":u2t", "u2t"),
+    prompt("Replace in every sequence...", ":%s/<from>/<to>/g", "%s/"),
+    prompt("Replace in current sequence...", ":s/<from>/<to>/g", "s/"),
+    command("Use sequence as reference", ":ref", "ref"),
+    prompt("Mask residues by PP...", ":mask pp<n", "mask pp<"),
+    prompt("Set the column in every sequence...", ":colset <char> [rows]", "colset "),
+    prompt("Remove or mask gappy columns...", ":filter-cols gaps>f [mask]", "filter-cols gaps>"),
+    // Display
+    run("Help", "?", App::toggle_help),
+    command("Color: none", ":color none", "color none"),
+    command("Color: structure", ":color ss", "color ss"),
+    command("Color: base", ":color base", "color base"),
+    command("Color: conservation", ":color cons", "color cons"),
+    command("Color: compensatory", ":color comp", "color comp"),
+    command("Color: #=GR SS vs SS_cons", ":color ssdiff", "color ssdiff"),
+    command("List #=GR SS conflicts", ":ssdiff", "ssdiff"),
+    command("Color only the selection", ":focus", "focus"),
+    command("Color everything", ":focus off", "focus off"),
+    command("Toggle ruler", ":ruler", "ruler"),
+    command("Pin cursor column", ":pin", "pin"),
+    prompt("Pin columns...", ":pin <N-M>", "pin "),
+    command("Unpin all columns", ":pin off", "pin off"),
+    command("Toggle row numbers", ":rownum", "rownum"),
+    command("Toggle short IDs", ":shortid", "shortid"),
+    command("Toggle consensus bar", ":consensus", "consensus"),
+    command("Toggle conservation bar", ":conservation", "conservation"),
+    command("Toggle confidence bar", ":confidence", "confidence"),
+    run("Horizontal split", "Ctrl-w s", App::horizontal_split),
+    run("Vertical split", "Ctrl-w v", App::vertical_split),
+    run("Close split", "Ctrl-w q", App::close_split),
+    command("Bookmark view", ":bookmark", "bookmark"),
+    command("Presentation mode", ":present", "present"),
+    command("Tutorial", ":tutor", "tutor"),
+    // Clustering
+    command("Cluster sequences", ":cluster", "cluster"),
+    command("Restore original order", ":uncluster", "uncluster"),
+    command("Sort sequences by name", ":sort name", "sort name"),
+    command("Sort sequences by length", ":sort length", "sort length"),
+    command("Sort sequences by gap fraction", ":sort gaps", "sort gaps"),
+    command("Sort sequences by identity to reference", ":sort identity", "sort identity"),
+    command("Sort sequences by #=GF NH tree", ":sort tree", "sort tree"),
+    prompt("Sort sequences by Newick tree file...", ":sort tree <file>", "sort tree "),
+    command("Sort sequences by cursor column", ":sortcol", "sortcol"),
+    command("Toggle dendrogram", ":tree", "tree"),
+    command("Collapse identical sequences", ":collapse", "collapse"),
+    command("Show cluster sizes", ":clusters", "clusters"),
+    command("Remove duplicate sequences", ":dedup", "dedup"),
+    prompt("Remove sequences above an identity...", ":dedup <identity%>", "dedup "),
+    // Curation
+    prompt("Flag sequence...", ":flag <label>", "flag "),
+    command("Unflag sequence", ":unflag", "unflag"),
+    command("Show only flagged sequences", ":filter flagged", "filter flagged"),
+    command("Check ID coordinates", ":coords", "coords"),
+    prompt("Write ID coordinates as TSV...", ":coords <path>", "coords "),
+    command("Fix ID coordinates", ":fixcoords", "fixcoords"),
+    prompt("Fix ID coordinates from file...", ":fixcoords <path>", "fixcoords "),
+    command("Clear filter", ":filter off", "filter off"),
+    prompt(
+        "Align marked hits into the family...",
+        ":hits align <seqdb> <model>",
+        "hits align ",
+    ),
+    command("Fold the consensus with RNAalifold", ":alifold", "alifold"),
+];
+
+/// Score of `query` as a fuzzy (in-order, case-insensitive) match against `text`,
+/// or None if it doesn't match. Matches at word starts and runs of consecutive
+/// characters score higher; skipped characters cost a little.
+pub fn fuzzy_score(query: &str, text: &str) -> Option<i32> {
+    let text: Vec<char> = text.chars().collect();
+    let mut score = 0;
+    let mut pos = 0;
+    let mut previous: Option<usize> = None;
+    for q in query.chars().filter(|c| !c.is_whitespace()) {
+        let q = q.to_ascii_lowercase();
+        let found = (pos..text.len()).find(|&i| text[i].to_ascii_lowercase() == q)?;
+        score += 1;
+        if found == 0 || !text[found - 1].is_alphanumeric() {
+            score += 8;
+        }
+        match previous {
+            Some(p) if p + 1 == found => score += 5,
+            Some(p) => score -= (found - p - 1).min(5) as i32,
+            None => score -= found.min(5) as i32,
+        }
+        previous = Some(found);
+        pos = found + 1;
+    }
+    Some(score)
+}
+
+/// Indices of the entries matching `query`, best first (all of them, in order,
+/// for an empty query).
+pub fn filter(query: &str) -> Vec<usize> {
+    let mut scored: Vec<(i32, usize)> = ENTRIES
+        .iter()
+        .enumerate()
+        .filter_map(|(i, entry)| {
+            let name = fuzzy_score(query, entry.name);
+            let key = fuzzy_score(query, entry.key).map(|s| s - 2);
+            name.max(key).map(|score| (score, i))
+        })
+        .collect();
+    scored.sort_by(|a, b| b.0.cmp(&a.0).then(a.1.cmp(&b.1)));
+    scored.into_iter().map(|(_, i)| i).collect()
+}
+
+/// Open palette: the query typed so far, the matching entries and the selected
+/// one.
+#[derive(Debug, Clone)]
+pub struct Palette {
+    pub query: String,
+    pub matches: Vec<usize>,
+    pub cursor: usize,
+}
+
+impl Palette {
+    pub fn new() -> Self {
+        Self {
+            query: String::new(),
+            matches: filter(""),
+            cursor: 0,
+        }
+    }
+
+    /// Replace the query and refilter, selecting the best match.
+    pub fn set_query(&mut self, query: String) {
+        self.matches = filter(&query);
+        self.query = query;
+        self.cursor = 0;
+    }
+
+    /// Move the selection by `delta`, staying within the matches.
+    pub fn move_cursor(&mut self, delta: isize) {
+        let last = self.matches.len().saturating_sub(1);
+        self.cursor = self.cursor.saturating_add_signed(delta).min(last);
+    }
+
+    /// The selected entry, if anything matches.
+    pub fn selected(&self) -> Option<&'static Entry> {
+        self.matches.get(self.cursor).map(|&i| &ENTRIES[i])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fuzzy_score() {
+        assert!(fuzzy_score("", "Undo").is_some());
+        assert!(fuzzy_score("uno", "Undo").is_some());
+        assert!(fuzzy_score("ou", "Undo").is_none());
+        // Word starts beat letters in the middle of a word
+        assert!(fuzzy_score("cs", "Color: structure") > fuzzy_score("cs", "Collapse"));
+        // Consecutive letters beat scattered ones
+        assert!(
+            fuzzy_score("tree", "Toggle dendrogram tree")
+                > fuzzy_score("tree", "Throw sequence right left")
+        );
+    }
+
+    #[test]
+    fn test_palette_filter() {
+        let mut palette = Palette::new();
+        assert_eq!(palette.matches.len(), ENTRIES.len());
+
+        palette.set_query("undo".to_string());
+        assert_eq!(palette.selected().unwrap().name, "Undo");
+        palette.set_query("color ss".to_string());
+        assert_eq!(palette.selected().unwrap().key, ":color ss");
+        palette.set_query("zzzz".to_string());
+        assert!(palette.selected().is_none());
+
+        palette.set_query(String::new());
+        palette.move_cursor(-3);
+        assert_eq!(palette.cursor, 0);
+        palette.move_cursor(isize::MAX);
+        assert_eq!(palette.cursor, ENTRIES.len() - 1);
+    }
+}
@@ -0,0 +1,405 @@
+//! Rendered buffers saved as ANSI-colored text (`:screenshot`, `:export ansi`),
+//! standalone HTML (`:export html`) or an SVG figure (`:export svg`).
+
+use std::fmt::Write as _;
+use std::path::Path;
+
+use ratatui::buffer::Buffer;
+use ratatui::style::{Color, Modifier};
+
+/// SGR codes for text attributes, in the order they are emitted.
+const MODIFIER_CODES: [(Modifier, u8); 6] = [
+    (Modifier::BOLD, 1),
+    (Modifier::DIM, 2),
+    (Modifier::ITALIC, 3),
+    (Modifier::UNDERLINED, 4),
+    (Modifier::REVERSED, 7),
+    (Modifier::CROSSED_OUT, 9),
+];
+
+/// SGR parameters selecting a color, for the foreground (`background = false`)
+/// or background.
+fn color_code(color: Color, background: bool) -> String {
+    let base = if background { 40 } else { 30 };
+    let named = |n: u8| (base + n).to_string();
+    let bright = |n: u8| (base + 60 + n).to_string();
+    match color {
+        Color::Reset => (base + 9).to_string(),
+        Color::Black => named(0),
+        Color::Red => named(1),
+        Color::Green => named(2),
+        Color::Yellow => named(3),
+        Color::Blue => named(4),
+        Color::Magenta => named(5),
+        Color::Cyan => named(6),
+        Color::Gray => named(7),
+        Color::DarkGray => bright(0),
+        Color::LightRed => bright(1),
+        Color::LightGreen => bright(2),
+        Color::LightYellow => bright(3),
+        Color::LightBlue => bright(4),
+        Color::LightMagenta => bright(5),
+        Color::LightCyan => bright(6),
+        Color::White => bright(7),
+        Color::Indexed(i) => format!("{};5;{i}", base + 8),
+        Color::Rgb(r, g, b) => format!("{};2;{r};{g};{b}", base + 8),
+    }
+}
+
+/// Page foreground and background for HTML on dark and light terminals.
+const DARK_PAGE: (&str, &str) = ("#d4d4d4", "#1e1e1e");
+const LIGHT_PAGE: (&str, &str) = ("#1e1e1e", "#ffffff");
+
+/// RGB values of the 16 named terminal colors (xterm defaults), in ANSI order.
+const NAMED_RGB: [(u8, u8, u8); 16] = [
+    (0, 0, 0),
+    (205, 0, 0),
+    (0, 205, 0),
+    (205, 205, 0),
+    (0, 0, 238),
+    (205, 0, 205),
+    (0, 205, 205),
+    (229, 229, 229),
+    (127, 127, 127),
+    (255, 0, 0),
+    (0, 255, 0),
+    (255, 255, 0),
+    (92, 92, 255),
+    (255, 0, 255),
+    (0, 255, 255),
+    (255, 255, 255),
+];
+
+/// RGB value of a terminal color, or None for the terminal default.
+pub fn color_rgb(color: Color) -> Option<(u8, u8, u8)> {
+    let index = match color {
+        Color::Reset => return None,
+        Color::Rgb(r, g, b) => return Some((r, g, b)),
+        Color::Black => 0,
+        Color::Red => 1,
+        Color::Green => 2,
+        Color::Yellow => 3,
+        Color::Blue => 4,
+        Color::Magenta => 5,
+        Color::Cyan => 6,
+        Color::Gray => 7,
+        Color::DarkGray => 8,
+        Color::LightRed => 9,
+        Color::LightGreen => 10,
+        Color::LightYellow => 11,
+        Color::LightBlue => 12,
+        Color::LightMagenta => 13,
+        Color::LightCyan => 14,
+        Color::White => 15,
+        Color::Indexed(i) => i,
+    };
+    Some(match index {
+        0..=15 => NAMED_RGB[usize::from(index)],
+        // 6x6x6 color cube
+        16..=231 => {
+            let level = |n: u8| if n == 0 { 0 } else { 55 + 40 * n };
+            let n = index - 16;
+            (level(n / 36), level(n / 6 % 6), level(n % 6))
+        }
+        // Grayscale ramp
+        _ => {
+            let gray = 8 + 10 * (index - 232);
+            (gray, gray, gray)
+        }
+    })
+}
+
+/// CSS hex for a color, falling back to `default` for the terminal default.
+fn css_color(color: Color, default: &str) -> String {
+    color_rgb(color).map_or_else(
+        || default.to_string(),
+        |(r, g, b)| format!("#{r:02x}{g:02x}{b:02x}"),
+    )
+}
+
+/// Hex foreground and background of a cell style, with the page colors standing
+/// in for terminal defaults and reversed video applied.
+fn hex_colors(fg: Color, bg: Color, modifier: Modifier, page: (&str, &str)) -> (String, String) {
+    let (fg, bg) = (css_color(fg, page.0), css_color(bg, page.1));
+    if modifier.contains(Modifier::REVERSED) {
+        (bg, fg)
+    } else {
+        (fg, bg)
+    }
+}
+
+/// Inline CSS for a cell style (empty for the page default).
+fn css_style(fg: Color, bg: Color, modifier: Modifier, page: (&str, &str)) -> String {
+    let (fg, bg) = hex_colors(fg, bg, modifier, page);
+    let mut css = Vec::new();
+    if fg != page.0 {
+        css.push(format!("color:{fg}"));
+    }
+    if bg != page.1 {
+        css.push(format!("background:{bg}"));
+    }
+    if modifier.contains(Modifier::BOLD) {
+        css.push("font-weight:bold".to_string());
+    }
+    if modifier.contains(Modifier::DIM) {
+        css.push("opacity:0.6".to_string());
+    }
+    if modifier.contains(Modifier::ITALIC) {
+        css.push("font-style:italic".to_string());
+    }
+    if modifier.contains(Modifier::UNDERLINED) {
+        css.push("text-decoration:underline".to_string());
+    }
+    css.join(";")
+}
+
+/// Escape text for HTML.
+pub fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// Render a buffer as a standalone HTML page: one `<pre>` line per buffer row,
+/// with a `<span>` for each run of identically styled cells. `dark` picks the page
+/// colors used for the terminal's default foreground and background.
+pub fn buffer_to_html(buffer: &Buffer, title: &str, dark: bool) -> String {
+    let page = if dark { DARK_PAGE } else { LIGHT_PAGE };
+    let area = buffer.area;
+    let mut out = String::new();
+    let _ = writeln!(out, "<!DOCTYPE html>");
+    let _ = writeln!(out, "<html>");
+    let _ = writeln!(out, "<head>");
+    let _ = writeln!(out, "<meta charset=\"utf-8\">");
+    let _ = writeln!(out, "<title>{}</title>", escape_html(title));
+    let _ = writeln!(
+        out,
+        "<style>body {{ color: {}; background: {}; }} pre {{ font-family: monospace; line-height: 1.2; }}</style>",
+        page.0, page.1
+    );
+    let _ = writeln!(out, "</head>");
+    let _ = writeln!(out, "<body>");
+    out.push_str("<pre>");
+    for y in area.top()..area.bottom() {
+        let mut x = area.left();
+        while x < area.right() {
+            let cell = &buffer[(x, y)];
+            let style = (cell.fg, cell.bg, cell.modifier);
+            let mut text = String::new();
+            while x < area.right() {
+                let next = &buffer[(x, y)];
+                if (next.fg, next.bg, next.modifier) != style {
+                    break;
+                }
+                text.push_str(next.symbol());
+                x += 1;
+            }
+            let css = css_style(style.0, style.1, style.2, page);
+            if css.is_empty() {
+                out.push_str(&escape_html(&text));
+            } else {
+                let _ = write!(out, "<span style=\"{css}\">{}</span>", escape_html(&text));
+            }
+        }
+        out.push('\n');
+    }
+    out.push_str("</pre>\n");
+    let _ = writeln!(out, "</body>");
+    let _ = writeln!(out, "</html>");
+    out
+}
+
+/// Render a buffer as text with ANSI escape codes, one line per buffer row. Escape
+/// codes are only emitted where the style changes, and each line ends reset.
+pub fn buffer_to_ansi(buffer: &Buffer) -> String {
+    let area = buffer.area;
+    let mut out = String::new();
+    for y in area.top()..area.bottom() {
+        let mut current = None;
+        for x in area.left()..area.right() {
+            let cell = &buffer[(x, y)];
+            let style = (cell.fg, cell.bg, cell.modifier);
+            if current != Some(style) {
+                let mut codes = vec!["0".to_string()];
+                codes.extend(
+                    MODIFIER_CODES
+                        .iter()
+                        .filter(|(modifier, _)| cell.modifier.contains(*modifier))
+                        .map(|(_, code)| code.to_string()),
+                );
+                if cell.fg != Color::Reset {
+                    codes.push(color_code(cell.fg, false));
+                }
+                if cell.bg != Color::Reset {
+                    codes.push(color_code(cell.bg, true));
+                }
+                let _ = write!(out, "\x1b[{}m", codes.join(";"));
+                current = Some(style);
+            }
+            out.push_str(cell.symbol());
+        }
+        out.push_str("\x1b[0m\n");
+    }
+    out
+}
+
+/// Write a buffer to a file as ANSI-colored text.
+pub fn write_ansi(buffer: &Buffer, path: &Path) -> std::io::Result<()> {
+    std::fs::write(path, buffer_to_ansi(buffer))
+}
+
+/// Write a buffer to a file as a standalone HTML page.
+pub fn write_html(buffer: &Buffer, title: &str, dark: bool, path: &Path) -> std::io::Result<()> {
+    std::fs::write(path, buffer_to_html(buffer, title, dark))
+}
+
+/// Size of one buffer cell in an SVG figure, and the font size, in pixels.
+const SVG_CELL_WIDTH: usize = 9;
+const SVG_CELL_HEIGHT: usize = 16;
+const SVG_FONT_SIZE: usize = 14;
+
+/// Render a buffer as an SVG figure: a rectangle for each run of cells with a
+/// background color, and each character centred in its cell so columns line up
+/// whatever monospace font the viewer substitutes.
+pub fn buffer_to_svg(buffer: &Buffer, dark: bool) -> String {
+    let page = if dark { DARK_PAGE } else { LIGHT_PAGE };
+    let area = buffer.area;
+    let width = usize::from(area.width) * SVG_CELL_WIDTH;
+    let height = usize::from(area.height) * SVG_CELL_HEIGHT;
+    let mut out = String::new();
+    let _ = writeln!(
+        out,
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{width}\" height=\"{height}\" \
+         viewBox=\"0 0 {width} {height}\" font-family=\"monospace\" font-size=\"{SVG_FONT_SIZE}\" \
+         text-anchor=\"middle\">"
+    );
+    let _ = writeln!(
+        out,
+        "<rect width=\"{width}\" height=\"{height}\" fill=\"{}\"/>",
+        page.1
+    );
+    for (row, y) in (area.top()..area.bottom()).enumerate() {
+        let top = row * SVG_CELL_HEIGHT;
+        let baseline = top + SVG_CELL_HEIGHT * 3 / 4;
+        let mut x = area.left();
+        while x < area.right() {
+            let first = &buffer[(x, y)];
+            let style = (first.fg, first.bg, first.modifier);
+            let start = usize::from(x - area.left());
+            let mut positions = Vec::new();
+            let mut text = String::new();
+            while x < area.right() {
+                let cell = &buffer[(x, y)];
+                if (cell.fg, cell.bg, cell.modifier) != style {
+                    break;
+                }
+                let symbol = cell.symbol();
+                if !symbol.trim().is_empty() {
+                    let col = usize::from(x - area.left());
+                    positions.push((col * SVG_CELL_WIDTH + SVG_CELL_WIDTH / 2).to_string());
+                    text.push_str(symbol);
+                }
+                x += 1;
+            }
+            let end = usize::from(x - area.left());
+            let (fg, bg) = hex_colors(style.0, style.1, style.2, page);
+            if bg != page.1 {
+                let _ = writeln!(
+                    out,
+                    "<rect x=\"{}\" y=\"{top}\" width=\"{}\" height=\"{SVG_CELL_HEIGHT}\" fill=\"{bg}\"/>",
+                    start * SVG_CELL_WIDTH,
+                    (end - start) * SVG_CELL_WIDTH
+                );
+            }
+            if !text.is_empty() {
+                let weight = if style.2.contains(Modifier::BOLD) {
+                    " font-weight=\"bold\""
+                } else {
+                    ""
+                };
+                let _ = writeln!(
+                    out,
+                    "<text x=\"{}\" y=\"{baseline}\" fill=\"{fg}\"{weight}>{}</text>",
+                    positions.join(" "),
+                    escape_html(&text)
+                );
+            }
+        }
+    }
+    out.push_str("</svg>\n");
+    out
+}
+
+/// Write a buffer to a file as an SVG figure.
+pub fn write_svg(buffer: &Buffer, dark: bool, path: &Path) -> std::io::Result<()> {
+    std::fs::write(path, buffer_to_svg(buffer, dark))
+}
+
+#[cfg(test)]
+mod tests {
+    use ratatui::layout::Rect;
+    use ratatui::style::Style;
+
+    use super::*;
+
+    #[test]
+    fn test_buffer_to_ansi() {
+        let mut buffer = Buffer::empty(Rect::new(0, 0, 4, 2));
+        buffer.set_string(0, 0, "AC", Style::default().bg(Color::Rgb(1, 2, 3)));
+        buffer.set_string(
+            2,
+            0,
+            "G",
+            Style::default().fg(Color::Red).add_modifier(Modifier::BOLD),
+        );
+        buffer.set_string(0, 1, "UU", Style::default());
+
+        assert_eq!(
+            buffer_to_ansi(&buffer),
+            "\x1b[0;48;2;1;2;3mAC\x1b[0;1;31mG\x1b[0m \x1b[0m\n\x1b[0mUU  \x1b[0m\n"
+        );
+    }
+
+    #[test]
+    fn test_buffer_to_html() {
+        let mut buffer = Buffer::empty(Rect::new(0, 0, 4, 1));
+        buffer.set_string(0, 0, "<A", Style::default());
+        buffer.set_string(2, 0, "CG", Style::default().bg(Color::Red).fg(Color::Black));
+
+        let html = buffer_to_html(&buffer, "a & b", true);
+        assert!(html.contains("<title>a &amp; b</title>"));
+        assert!(html.contains(
+            "<pre>&lt;A<span style=\"color:#000000;background:#cd0000\">CG</span>\n</pre>"
+        ));
+
+        assert_eq!(color_rgb(Color::Indexed(196)), Some((255, 0, 0)));
+        assert_eq!(color_rgb(Color::Indexed(232)), Some((8, 8, 8)));
+        assert_eq!(color_rgb(Color::Reset), None);
+    }
+
+    #[test]
+    fn test_buffer_to_svg() {
+        let mut buffer = Buffer::empty(Rect::new(0, 0, 4, 1));
+        buffer.set_string(0, 0, "a ", Style::default());
+        buffer.set_string(
+            2,
+            0,
+            "<G",
+            Style::default()
+                .bg(Color::Rgb(0, 0, 255))
+                .add_modifier(Modifier::BOLD),
+        );
+
+        let svg = buffer_to_svg(&buffer, false);
+        assert!(svg.contains("width=\"36\" height=\"16\""));
+        assert!(svg.contains("<text x=\"4\" y=\"12\" fill=\"#1e1e1e\">a</text>"));
+        assert!(
+            svg.contains("<rect x=\"18\" y=\"0\" width=\"18\" height=\"16\" fill=\"#0000ff\"/>")
+        );
+        assert!(svg.contains(
+            "<text x=\"22 31\" y=\"12\" fill=\"#1e1e1e\" font-weight=\"bold\">&lt;G</text>"
+        ));
+        assert!(svg.ends_with("</svg>\n"));
+    }
+}
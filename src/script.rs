@@ -0,0 +1,232 @@
+//! Session scripts: the editing commands of a session, written by
+//! `:w session.aform` and replayed on another alignment with `aform --script`.
+
+use ratatui::crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+
+/// Extension of session script files.
+pub const EXTENSION: &str = "aform";
+
+/// An editing command as it was run, with where the cursor was so that
+/// commands acting on the current sequence or column replay in the same place.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Entry {
+    pub command: String,
+    /// Sequence ID of the cursor row.
+    pub id: String,
+    /// Cursor column, 1-based.
+    pub column: usize,
+    /// Other corner of a visual selection the command applied to.
+    pub anchor: Option<(String, usize)>,
+}
+
+/// Script text for `entries`: each command on its own line, preceded by a
+/// `goto` when the cursor had moved since the previous one and an `anchor`
+/// when it ran on a selection.
+pub fn render(entries: &[Entry], source: &str) -> String {
+    let mut text = format!(
+        "# aform session script, recorded on {source}\n\
+         # Replay with: aform --script <this file> <alignment>\n"
+    );
+    let mut position: Option<(&str, usize)> = None;
+    for entry in entries {
+        if position != Some((entry.id.as_str(), entry.column)) {
+            text.push_str(&format!("goto {} {}\n", entry.id, entry.column));
+            position = Some((entry.id.as_str(), entry.column));
+        }
+        if let Some((id, column)) = &entry.anchor {
+            text.push_str(&format!("anchor {id} {column}\n"));
+        }
+        text.push_str(&entry.command);
+        text.push('\n');
+    }
+    text
+}
+
+/// Commands of a script with their line numbers. Blank lines and `#` comments
+/// are skipped, and a leading `:` is optional.
+pub fn parse(text: &str) -> Vec<(usize, String)> {
+    text.lines()
+        .enumerate()
+        .filter_map(|(i, line)| {
+            let line = line.trim();
+            let command = line.strip_prefix(':').unwrap_or(line).trim();
+            (!command.is_empty() && !command.starts_with('#')).then(|| (i + 1, command.to_string()))
+        })
+        .collect()
+}
+
+/// Names of keys written in `<...>`, as in Vim.
+const KEY_NAMES: &[(&str, KeyCode)] = &[
+    ("CR", KeyCode::Enter),
+    ("Esc", KeyCode::Esc),
+    ("BS", KeyCode::Backspace),
+    ("Del", KeyCode::Delete),
+    ("Tab", KeyCode::Tab),
+    ("BackTab", KeyCode::BackTab),
+    ("Space", KeyCode::Char(' ')),
+    ("lt", KeyCode::Char('<')),
+    ("gt", KeyCode::Char('>')),
+    ("Left", KeyCode::Left),
+    ("Right", KeyCode::Right),
+    ("Up", KeyCode::Up),
+    ("Down", KeyCode::Down),
+    ("Home", KeyCode::Home),
+    ("End", KeyCode::End),
+    ("PageUp", KeyCode::PageUp),
+    ("PageDown", KeyCode::PageDown),
+    ("Insert", KeyCode::Insert),
+];
+
+/// A typed key in the notation of `:normal`: a character as itself, other
+/// keys and modifiers in `<...>` (`<Esc>`, `<lt>`, `<C-v>`, `<S-Down>`).
+/// None for keys it has no name for.
+pub fn key_name(key: &KeyEvent) -> Option<String> {
+    let name = match key.code {
+        KeyCode::F(n) => format!("F{n}"),
+        code => match KEY_NAMES.iter().find(|(_, c)| *c == code) {
+            Some((name, _)) => name.to_string(),
+            None => match code {
+                KeyCode::Char(c) => c.to_string(),
+                _ => return None,
+            },
+        },
+    };
+    let mut modifiers = String::new();
+    if key.modifiers.contains(KeyModifiers::CONTROL) {
+        modifiers.push_str("C-");
+    }
+    if key.modifiers.contains(KeyModifiers::ALT) {
+        modifiers.push_str("A-");
+    }
+    // A character carries its own Shift
+    if key.modifiers.contains(KeyModifiers::SHIFT) && !matches!(key.code, KeyCode::Char(_)) {
+        modifiers.push_str("S-");
+    }
+    Some(if modifiers.is_empty() && name.chars().count() == 1 {
+        name
+    } else {
+        format!("<{modifiers}{name}>")
+    })
+}
+
+/// Keys written as by `key_name`.
+pub fn parse_keys(text: &str) -> Result<Vec<KeyEvent>, String> {
+    let mut keys = Vec::new();
+    let mut rest = text;
+    while let Some(c) = rest.chars().next() {
+        if c != '<' {
+            let modifiers = if c.is_uppercase() {
+                KeyModifiers::SHIFT
+            } else {
+                KeyModifiers::NONE
+            };
+            keys.push(KeyEvent::new(KeyCode::Char(c), modifiers));
+            rest = &rest[c.len_utf8()..];
+            continue;
+        }
+        let end = rest
+            .find('>')
+            .ok_or_else(|| format!("Unclosed key name: {rest}"))?;
+        let (mut name, mut modifiers) = (&rest[1..end], KeyModifiers::NONE);
+        while name.len() > 2 && name.as_bytes()[1] == b'-' {
+            modifiers |= match name.as_bytes()[0] {
+                b'C' => KeyModifiers::CONTROL,
+                b'A' => KeyModifiers::ALT,
+                b'S' => KeyModifiers::SHIFT,
+                _ => break,
+            };
+            name = &name[2..];
+        }
+        let code = match KEY_NAMES.iter().find(|(n, _)| *n == name) {
+            Some(&(_, code)) => code,
+            None => match (name.strip_prefix('F').map(str::parse), name.chars().count()) {
+                (Some(Ok(n)), _) => KeyCode::F(n),
+                (_, 1) => KeyCode::Char(name.chars().next().unwrap_or_default()),
+                _ => return Err(format!("Unknown key: <{}>", &rest[1..end])),
+            },
+        };
+        if matches!(code, KeyCode::Char(c) if c.is_uppercase()) {
+            modifiers |= KeyModifiers::SHIFT;
+        }
+        keys.push(KeyEvent::new(code, modifiers));
+        rest = &rest[end + 1..];
+    }
+    Ok(keys)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(command: &str, id: &str, column: usize) -> Entry {
+        Entry {
+            command: command.to_string(),
+            id: id.to_string(),
+            column,
+            anchor: None,
+        }
+    }
+
+    #[test]
+    fn test_render_moves_only_when_the_cursor_did() {
+        let mut selected = entry("upper", "seq2", 5);
+        selected.anchor = Some(("seq1".to_string(), 1));
+        let text = render(
+            &[
+                entry("trim", "seq1", 1),
+                entry("rename seqA", "seq1", 1),
+                selected,
+            ],
+            "tRNA.sto",
+        );
+        let lines: Vec<&str> = text.lines().skip(2).collect();
+        assert_eq!(
+            lines,
+            [
+                "goto seq1 1",
+                "trim",
+                "rename seqA",
+                "goto seq2 5",
+                "anchor seq1 1",
+                "upper"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_skips_comments_and_colons() {
+        let commands = parse("# header\n\n:goto seq1 3\n  trim  \n# done\n");
+        assert_eq!(
+            commands,
+            vec![(3, "goto seq1 3".to_string()), (4, "trim".to_string())]
+        );
+    }
+
+    #[test]
+    fn test_render_round_trips() {
+        let entries = [entry("sort name", "a/1-10", 2)];
+        let commands: Vec<String> = parse(&render(&entries, "x.sto"))
+            .into_iter()
+            .map(|(_, c)| c)
+            .collect();
+        assert_eq!(commands, ["goto a/1-10 2", "sort name"]);
+    }
+
+    #[test]
+    fn test_key_names_round_trip() {
+        let keys = [
+            KeyEvent::new(KeyCode::Char('x'), KeyModifiers::NONE),
+            KeyEvent::new(KeyCode::Char('I'), KeyModifiers::SHIFT),
+            KeyEvent::new(KeyCode::Char('<'), KeyModifiers::NONE),
+            KeyEvent::new(KeyCode::Char(' '), KeyModifiers::NONE),
+            KeyEvent::new(KeyCode::Char('v'), KeyModifiers::CONTROL),
+            KeyEvent::new(KeyCode::Down, KeyModifiers::ALT),
+            KeyEvent::new(KeyCode::Esc, KeyModifiers::NONE),
+        ];
+        let text: String = keys.iter().filter_map(key_name).collect();
+        assert_eq!(text, "xI<lt><Space><C-v><A-Down><Esc>");
+        assert_eq!(parse_keys(&text), Ok(keys.to_vec()));
+        assert!(parse_keys("x<Esc").is_err());
+        assert!(parse_keys("<Nope>").is_err());
+    }
+}
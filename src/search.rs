@@ -0,0 +1,516 @@
+//! Sequence search for `/` and `:grepall`, and `:s` substitution.
+//!
+//! Patterns are matched case-insensitively with T and U equal, either as typed,
+//! with IUPAC degenerate codes, or as regular expressions (`:set search=`, or a
+//! `re:` prefix). Gaps inside a sequence are skipped unless
+//! `:set search-gaps=exact`. Regular expressions also search sequence IDs.
+
+use std::ops::Range;
+
+use regex::{Regex, RegexBuilder};
+
+/// Prefix that makes a pattern a regular expression whatever `:set search` is.
+pub const REGEX_PREFIX: &str = "re:";
+
+/// How `/` reads a pattern (`:set search=`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SearchKind {
+    /// Residues as typed.
+    #[default]
+    Literal,
+    /// Nucleotide IUPAC codes (`RYSWKMBDHVN`) match the bases they stand for.
+    Iupac,
+    /// A regular expression over the residues.
+    Regex,
+}
+
+impl SearchKind {
+    pub fn from_str(s: &str) -> Option<Self> {
+        match s.to_lowercase().as_str() {
+            "literal" | "plain" => Some(SearchKind::Literal),
+            "iupac" => Some(SearchKind::Iupac),
+            "regex" | "re" => Some(SearchKind::Regex),
+            _ => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            SearchKind::Literal => "literal",
+            SearchKind::Iupac => "iupac",
+            SearchKind::Regex => "regex",
+        }
+    }
+}
+
+/// Bases an IUPAC nucleotide code stands for (U for T), or None for a code
+/// that only matches itself.
+pub fn iupac_bases(code: char) -> Option<&'static [char]> {
+    Some(match code.to_ascii_uppercase() {
+        'R' => &['A', 'G'],
+        'Y' => &['C', 'U'],
+        'S' => &['C', 'G'],
+        'W' => &['A', 'U'],
+        'K' => &['G', 'U'],
+        'M' => &['A', 'C'],
+        'B' => &['C', 'G', 'U'],
+        'D' => &['A', 'G', 'U'],
+        'H' => &['A', 'C', 'U'],
+        'V' => &['A', 'C', 'G'],
+        'N' => &['A', 'C', 'G', 'U'],
+        _ => return None,
+    })
+}
+
+/// Normalize a single character for search: uppercase and T→U.
+fn normalize_char(c: char) -> char {
+    match c.to_ascii_uppercase() {
+        'T' => 'U',
+        other => other,
+    }
+}
+
+/// A compiled `/` pattern.
+#[derive(Debug, Clone)]
+pub struct SearchPattern {
+    kind: SearchKind,
+    skip_gaps: bool,
+    gap_chars: Vec<char>,
+    /// Normalized pattern characters (literal and IUPAC searches).
+    chars: Vec<char>,
+    regex: Option<Regex>,
+    /// The regular expression as typed, for sequence IDs.
+    id_regex: Option<Regex>,
+}
+
+impl SearchPattern {
+    /// Compile `pattern`, or describe why it is not a valid regular expression.
+    pub fn new(
+        pattern: &str,
+        kind: SearchKind,
+        skip_gaps: bool,
+        gap_chars: &[char],
+    ) -> Result<Self, String> {
+        let (pattern, kind) = match pattern.strip_prefix(REGEX_PREFIX) {
+            Some(rest) => (rest, SearchKind::Regex),
+            None => (pattern, kind),
+        };
+        let (regex, id_regex) = match kind {
+            SearchKind::Regex => (
+                Some(Self::compile_regex(&Self::residue_regex(pattern))?),
+                Some(Self::compile_regex(pattern)?),
+            ),
+            _ => (None, None),
+        };
+        let chars = pattern
+            .chars()
+            .filter(|c| !skip_gaps || !gap_chars.contains(c))
+            .map(normalize_char)
+            .collect();
+        Ok(Self {
+            kind,
+            skip_gaps,
+            gap_chars: gap_chars.to_vec(),
+            chars,
+            regex,
+            id_regex,
+        })
+    }
+
+    /// A regex for residues: T read as U, leaving escapes such as `\t` and
+    /// names (`[[:digit:]]`, `(?P<tail>...)`, `\p{Latin}`) alone.
+    fn residue_regex(pattern: &str) -> String {
+        let chars: Vec<char> = pattern.chars().collect();
+        let mut source = String::with_capacity(pattern.len());
+        let mut i = 0;
+        while i < chars.len() {
+            let rest = &chars[i..];
+            // Escapes and names are copied as they are, up to their last character
+            let end = match rest {
+                ['\\', 'p' | 'P', '{', ..] => rest.iter().position(|&c| c == '}'),
+                ['\\', _, ..] => Some(1),
+                ['[', ':', ..] => rest.iter().position(|&c| c == ']'),
+                ['(', '?', 'P', '<', ..] | ['(', '?', '<', ..] => {
+                    rest.iter().position(|&c| c == '>')
+                }
+                _ => None,
+            };
+            match end {
+                Some(end) => {
+                    source.extend(&rest[..=end]);
+                    i += end + 1;
+                }
+                None => {
+                    source.push(match rest[0] {
+                        'T' | 't' => 'U',
+                        c => c,
+                    });
+                    i += 1;
+                }
+            }
+        }
+        source
+    }
+
+    /// Build a case-insensitive regex, or the last line of its error.
+    fn compile_regex(source: &str) -> Result<Regex, String> {
+        RegexBuilder::new(source)
+            .case_insensitive(true)
+            .build()
+            .map_err(|e| e.to_string().lines().last().unwrap_or_default().to_string())
+    }
+
+    /// Whether a sequence ID matches, or None for patterns that only search
+    /// residues.
+    pub fn matches_id(&self, id: &str) -> Option<bool> {
+        self.id_regex.as_ref().map(|regex| regex.is_match(id))
+    }
+
+    /// Whether the pattern can match anything.
+    pub fn is_empty(&self) -> bool {
+        self.regex.is_none() && self.chars.is_empty()
+    }
+
+    /// Matches in one sequence as (start_col, end_col), end exclusive.
+    /// Overlapping matches are found too, except for regular expressions.
+    pub fn find(&self, seq: &[char]) -> Vec<(usize, usize)> {
+        if let Some(regex) = &self.regex {
+            return self.find_regex(regex, seq);
+        }
+        let mut matches = Vec::new();
+        if self.chars.is_empty() {
+            return matches;
+        }
+        for col in 0..seq.len() {
+            if !self.skip_gaps {
+                if self.matches_exactly_at(seq, col) {
+                    matches.push((col, col + self.chars.len()));
+                }
+            } else if !self.is_gap(seq[col])
+                && let Some(end_col) = self.try_match_at(seq, col)
+            {
+                matches.push((col, end_col));
+            }
+        }
+        matches
+    }
+
+    fn is_gap(&self, c: char) -> bool {
+        self.gap_chars.contains(&c)
+    }
+
+    /// Whether a residue matches a pattern character.
+    fn residue_matches(&self, pattern: char, residue: char) -> bool {
+        let residue = normalize_char(residue);
+        residue == pattern
+            || (self.kind == SearchKind::Iupac
+                && iupac_bases(pattern).is_some_and(|bases| bases.contains(&residue)))
+    }
+
+    /// Try to match the pattern starting at the given column, skipping gaps.
+    /// Returns the end column (exclusive) if it matches.
+    fn try_match_at(&self, seq: &[char], start_col: usize) -> Option<usize> {
+        let mut seq_idx = start_col;
+        for &p in &self.chars {
+            // Skip gaps in sequence
+            while seq_idx < seq.len() && self.is_gap(seq[seq_idx]) {
+                seq_idx += 1;
+            }
+            if seq_idx >= seq.len() || !self.residue_matches(p, seq[seq_idx]) {
+                return None;
+            }
+            seq_idx += 1;
+        }
+        Some(seq_idx)
+    }
+
+    /// Whether the pattern matches the aligned text starting at the given column,
+    /// with any gap character in the pattern matching any gap.
+    fn matches_exactly_at(&self, seq: &[char], start_col: usize) -> bool {
+        let Some(window) = seq.get(start_col..start_col + self.chars.len()) else {
+            return false;
+        };
+        window.iter().zip(&self.chars).all(|(&c, &p)| {
+            if self.is_gap(p) {
+                self.is_gap(c)
+            } else {
+                !self.is_gap(c) && self.residue_matches(p, c)
+            }
+        })
+    }
+
+    /// Regex matches over the residues (or the aligned text with
+    /// `search-gaps=exact`), mapped back to alignment columns.
+    fn find_regex(&self, regex: &Regex, seq: &[char]) -> Vec<(usize, usize)> {
+        let mut text = String::with_capacity(seq.len());
+        // Column of every byte of `text`
+        let mut cols = Vec::with_capacity(seq.len());
+        for (col, &c) in seq.iter().enumerate() {
+            if self.skip_gaps && self.is_gap(c) {
+                continue;
+            }
+            let c = normalize_char(c);
+            text.push(c);
+            cols.extend(std::iter::repeat_n(col, c.len_utf8()));
+        }
+        regex
+            .find_iter(&text)
+            .filter(|m| !m.is_empty())
+            .map(|m| (cols[m.start()], cols[m.end() - 1] + 1))
+            .collect()
+    }
+}
+
+/// A `:s/from/to/flags` substitution over the aligned text, gaps included.
+/// The replacement is as long as the pattern, so columns stay aligned.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Substitution {
+    pub from: Vec<char>,
+    pub to: Vec<char>,
+    /// Replace every match in a sequence, not just the first (`g`).
+    pub global: bool,
+    /// Ask before each replacement (`c`).
+    pub confirm: bool,
+    /// Match either case, keeping the case of the replaced residues (`i`).
+    pub ignore_case: bool,
+}
+
+impl Substitution {
+    /// Parse what follows `s`: `/from/to/` and any of the flags `g`, `c` and
+    /// `i`. Any punctuation can stand in for `/`, and the last one is optional.
+    pub fn parse(spec: &str) -> Result<Self, String> {
+        let usage = "Usage: :s/from/to/[gci] (:%s for every sequence)";
+        let mut chars = spec.chars();
+        let delimiter = chars
+            .next()
+            .filter(|c| !c.is_alphanumeric() && !c.is_whitespace())
+            .ok_or(usage)?;
+        let parts: Vec<&str> = chars.as_str().split(delimiter).collect();
+        let (from, to, flags) = match parts.as_slice() {
+            [from, to] => (from, to, ""),
+            [from, to, flags] => (from, to, *flags),
+            _ => return Err(usage.to_string()),
+        };
+        let from: Vec<char> = from.chars().collect();
+        let to: Vec<char> = to.chars().collect();
+        if from.is_empty() {
+            return Err("Nothing to replace: the pattern is empty".to_string());
+        }
+        if from.len() != to.len() {
+            return Err(format!(
+                "The replacement must be as long as the pattern ({}) to keep the columns aligned",
+                from.len()
+            ));
+        }
+        let mut sub = Self {
+            from,
+            to,
+            global: false,
+            confirm: false,
+            ignore_case: false,
+        };
+        for flag in flags.chars() {
+            match flag {
+                'g' => sub.global = true,
+                'c' => sub.confirm = true,
+                'i' => sub.ignore_case = true,
+                _ => return Err(format!("Unknown flag '{flag}' (g, c or i)")),
+            }
+        }
+        Ok(sub)
+    }
+
+    /// Whether the pattern matches at `start`.
+    fn matches_at(&self, seq: &[char], start: usize) -> bool {
+        let Some(window) = seq.get(start..start + self.from.len()) else {
+            return false;
+        };
+        window
+            .iter()
+            .zip(&self.from)
+            .all(|(&c, &p)| c == p || (self.ignore_case && c.eq_ignore_ascii_case(&p)))
+    }
+
+    /// Start columns of the matches lying within `cols`, left to right and not
+    /// overlapping; just the first unless the substitution is global.
+    pub fn find(&self, seq: &[char], cols: Range<usize>) -> Vec<usize> {
+        let end = cols.end.min(seq.len());
+        let mut starts = Vec::new();
+        let mut col = cols.start;
+        while col + self.from.len() <= end {
+            if self.matches_at(seq, col) {
+                starts.push(col);
+                if !self.global {
+                    break;
+                }
+                col += self.from.len();
+            } else {
+                col += 1;
+            }
+        }
+        starts
+    }
+
+    /// Replace the match at `start`. Ignoring case, a lowercase residue stays
+    /// lowercase.
+    pub fn replace(&self, seq: &mut [char], start: usize) {
+        for (c, &to) in seq[start..start + self.to.len()].iter_mut().zip(&self.to) {
+            *c = if self.ignore_case && c.is_ascii_lowercase() {
+                to.to_ascii_lowercase()
+            } else {
+                to
+            };
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::stockholm::DEFAULT_GAP_CHARS;
+
+    const GAPS: &[char] = &DEFAULT_GAP_CHARS;
+
+    fn find(pattern: &str, kind: SearchKind, skip_gaps: bool, seq: &str) -> Vec<(usize, usize)> {
+        let seq: Vec<char> = seq.chars().collect();
+        SearchPattern::new(pattern, kind, skip_gaps, GAPS)
+            .unwrap()
+            .find(&seq)
+    }
+
+    #[test]
+    fn test_literal_search() {
+        // Case-insensitive and T/U tolerant, skipping gaps
+        assert_eq!(
+            find("ggac", SearchKind::Literal, true, "GG..ACGGAC"),
+            vec![(0, 6), (6, 10)]
+        );
+        assert_eq!(
+            find("GGAT", SearchKind::Literal, true, "GGAU"),
+            vec![(0, 4)]
+        );
+        // A gap in the pattern is ignored when gaps are skipped
+        assert_eq!(find("GG-A", SearchKind::Literal, true, "GGA"), vec![(0, 3)]);
+        // Exact: residues must be adjacent, pattern gaps match any gap
+        assert_eq!(
+            find("GGAC", SearchKind::Literal, false, "GG..ACGGAC"),
+            vec![(6, 10)]
+        );
+        assert_eq!(
+            find("GG-.AC", SearchKind::Literal, false, "GG..ACGGAC"),
+            vec![(0, 6)]
+        );
+        // IUPAC codes are literal characters
+        assert!(find("RRACH", SearchKind::Literal, true, "GGACU").is_empty());
+    }
+
+    #[test]
+    fn test_iupac_search() {
+        assert_eq!(
+            find("RRACH", SearchKind::Iupac, true, "GGACUAAACA"),
+            vec![(0, 5), (5, 10)]
+        );
+        assert_eq!(
+            find("rrach", SearchKind::Iupac, true, "gg.act"),
+            vec![(0, 6)]
+        );
+        // N matches any base but not a gap
+        assert_eq!(
+            find("ANA", SearchKind::Iupac, false, "ACA-A.A"),
+            vec![(0, 3)]
+        );
+        assert!(find("Y", SearchKind::Iupac, true, "AG").is_empty());
+        assert_eq!(iupac_bases('n').map(|b| b.len()), Some(4));
+        assert_eq!(iupac_bases('A'), None);
+    }
+
+    #[test]
+    fn test_regex_search() {
+        // Matches over residues map back to columns spanning gaps
+        assert_eq!(
+            find("G+AC", SearchKind::Regex, true, "AGG..ACU"),
+            vec![(1, 7)]
+        );
+        assert_eq!(
+            find("g{2}a", SearchKind::Regex, false, "AGG..AGGA"),
+            vec![(6, 9)]
+        );
+        assert_eq!(find("UU", SearchKind::Regex, true, "ATT"), vec![(1, 3)]);
+        // Empty matches are dropped
+        assert!(find("X*", SearchKind::Regex, true, "ACGU").is_empty());
+        assert!(SearchPattern::new("G(", SearchKind::Regex, true, GAPS).is_err());
+    }
+
+    #[test]
+    fn test_residue_regex_keeps_names() {
+        assert_eq!(SearchPattern::residue_regex("GT\\tt"), "GU\\tU");
+        assert_eq!(
+            SearchPattern::residue_regex("[[:digit:]T]+(?P<tail>TT)(?<t>t)"),
+            "[[:digit:]U]+(?P<tail>UU)(?<t>U)"
+        );
+        assert_eq!(
+            SearchPattern::residue_regex("\\p{Latin}T\\PLt"),
+            "\\p{Latin}U\\PLU"
+        );
+        assert_eq!(
+            find("(?P<stem>G+)TT", SearchKind::Regex, true, "AGG.UUC"),
+            vec![(1, 6)]
+        );
+    }
+
+    #[test]
+    fn test_regex_prefix() {
+        // re: makes any search a regex, over residues and IDs
+        assert_eq!(
+            find("re:GG[AC]+U", SearchKind::Literal, true, "AGG.CAT"),
+            vec![(1, 7)]
+        );
+        let motif = SearchPattern::new("re:^tRNA-\\d+", SearchKind::Iupac, true, GAPS).unwrap();
+        // T stays T in IDs
+        assert_eq!(motif.matches_id("tRNA-12/1-73"), Some(true));
+        assert_eq!(motif.matches_id("AB001721.1/2-73"), Some(false));
+        let literal = SearchPattern::new("GGAC", SearchKind::Literal, true, GAPS).unwrap();
+        assert_eq!(literal.matches_id("GGAC"), None);
+    }
+
+    #[test]
+    fn test_substitution_parse() {
+        let sub = Substitution::parse("/T/U/g").unwrap();
+        assert_eq!((sub.from, sub.to), (vec!['T'], vec!['U']));
+        assert!(sub.global && !sub.confirm && !sub.ignore_case);
+        // Other delimiters, and the last one left out
+        let sub = Substitution::parse("#GA#AG").unwrap();
+        assert_eq!(sub.to, vec!['A', 'G']);
+        assert!(!sub.global);
+        assert!(Substitution::parse("/t/u/gci").unwrap().confirm);
+
+        assert!(Substitution::parse("/T/UU/").is_err());
+        assert!(Substitution::parse("//U/").is_err());
+        assert!(Substitution::parse("/T/U/x").is_err());
+        assert!(Substitution::parse("aTaUa").is_err());
+        assert!(Substitution::parse("/T/U/g/").is_err());
+    }
+
+    #[test]
+    fn test_substitution_find_and_replace() {
+        let mut seq: Vec<char> = "ACUUt.GUU".chars().collect();
+        let sub = Substitution::parse("/UU/TT/g").unwrap();
+        assert_eq!(sub.find(&seq, 0..seq.len()), vec![2, 7]);
+        // Only matches wholly inside the columns count
+        assert_eq!(sub.find(&seq, 0..8), vec![2]);
+        assert_eq!(
+            Substitution::parse("/UU/TT/").unwrap().find(&seq, 0..9),
+            vec![2]
+        );
+
+        let sub = Substitution::parse("/T/U/gi").unwrap();
+        assert_eq!(sub.find(&seq, 0..seq.len()), vec![4]);
+        sub.replace(&mut seq, 4);
+        assert_eq!(seq.iter().collect::<String>(), "ACUUu.GUU");
+
+        // Gaps are text like any other
+        let sub = Substitution::parse("/./-/g").unwrap();
+        sub.replace(&mut seq, 5);
+        assert_eq!(seq.iter().collect::<String>(), "ACUUu-GUU");
+    }
+}
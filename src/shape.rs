@@ -0,0 +1,151 @@
+//! Alignment width and sequence count over the editing session, graphed by
+//! `:history`.
+
+/// Columns of the `:history` graphs; longer sessions are bucketed to fit.
+pub const GRAPH_WIDTH: usize = 60;
+
+const BARS: [char; 8] = ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+
+/// Size of the alignment at one point in the session.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Shape {
+    pub width: usize,
+    pub sequences: usize,
+}
+
+/// Every shape the alignment has had since it was loaded, oldest first.
+#[derive(Debug, Clone, Default)]
+pub struct ShapeHistory {
+    samples: Vec<Shape>,
+}
+
+impl ShapeHistory {
+    /// Start over from a newly loaded alignment.
+    pub fn reset(&mut self, shape: Shape) {
+        self.samples = vec![shape];
+    }
+
+    /// Add `shape` if it differs from the last one recorded.
+    pub fn record(&mut self, shape: Shape) {
+        if self.samples.last() != Some(&shape) {
+            self.samples.push(shape);
+        }
+    }
+
+    /// Number of changes recorded since loading.
+    pub fn changes(&self) -> usize {
+        self.samples.len().saturating_sub(1)
+    }
+
+    /// Lines of the `:history` report: a graph of each measure with where it
+    /// started and where it is now.
+    pub fn report_lines(&self) -> Vec<String> {
+        let (Some(first), Some(last)) = (self.samples.first(), self.samples.last()) else {
+            return Vec::new();
+        };
+        let widths: Vec<usize> = self.samples.iter().map(|s| s.width).collect();
+        let counts: Vec<usize> = self.samples.iter().map(|s| s.sequences).collect();
+        let mut lines = Vec::new();
+        for (label, unit, values, from, to) in [
+            ("Width", "columns", &widths, first.width, last.width),
+            (
+                "Sequences",
+                "sequences",
+                &counts,
+                first.sequences,
+                last.sequences,
+            ),
+        ] {
+            let min = values.iter().min().copied().unwrap_or(0);
+            let max = values.iter().max().copied().unwrap_or(0);
+            lines.push(format!(
+                "{label:<10} {from} -> {to} {unit} ({})",
+                signed(to, from)
+            ));
+            lines.push(format!("{:<10} {}", "", sparkline(values, GRAPH_WIDTH)));
+            lines.push(format!("{:<10} min {min}, max {max}", ""));
+            lines.push(String::new());
+        }
+        lines.push(match self.changes() {
+            1 => "1 change since loading".to_string(),
+            n => format!("{n} changes since loading"),
+        });
+        lines
+    }
+}
+
+/// `to - from` with its sign, e.g. `-355` or `+2`.
+fn signed(to: usize, from: usize) -> String {
+    if to >= from {
+        format!("+{}", to - from)
+    } else {
+        format!("-{}", from - to)
+    }
+}
+
+/// One bar per value, scaled from the smallest value to the largest. More than
+/// `width` values are split into `width` buckets, each shown by its last value.
+pub fn sparkline(values: &[usize], width: usize) -> String {
+    if values.is_empty() || width == 0 {
+        return String::new();
+    }
+    let buckets = values.len().min(width);
+    let sampled: Vec<usize> = (1..=buckets)
+        .map(|b| values[b * values.len() / buckets - 1])
+        .collect();
+    let min = sampled.iter().min().copied().unwrap_or(0);
+    let max = sampled.iter().max().copied().unwrap_or(0);
+    sampled
+        .iter()
+        .map(|&v| {
+            if max == min {
+                BARS[BARS.len() - 1]
+            } else {
+                BARS[(v - min) * (BARS.len() - 1) / (max - min)]
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn shape(width: usize, sequences: usize) -> Shape {
+        Shape { width, sequences }
+    }
+
+    #[test]
+    fn test_sparkline_scales_min_to_max() {
+        assert_eq!(sparkline(&[10, 5, 0], 60), "█▄▁");
+        assert_eq!(sparkline(&[7, 7], 60), "██");
+        assert_eq!(sparkline(&[], 60), "");
+    }
+
+    #[test]
+    fn test_sparkline_buckets_long_sessions() {
+        let values: Vec<usize> = (0..100).rev().collect();
+        let line = sparkline(&values, 10);
+        assert_eq!(line.chars().count(), 10);
+        assert_eq!(line.chars().next(), Some('█'));
+        assert_eq!(line.chars().last(), Some('▁'));
+    }
+
+    #[test]
+    fn test_record_skips_unchanged_shapes() {
+        let mut history = ShapeHistory::default();
+        history.reset(shape(100, 5));
+        history.record(shape(100, 5));
+        history.record(shape(90, 5));
+        history.record(shape(90, 4));
+        assert_eq!(history.changes(), 2);
+
+        let lines = history.report_lines();
+        assert_eq!(lines[0], "Width      100 -> 90 columns (-10)");
+        assert_eq!(lines[4], "Sequences  5 -> 4 sequences (-1)");
+        assert_eq!(lines.last().unwrap(), "2 changes since loading");
+
+        history.reset(shape(50, 2));
+        assert_eq!(history.changes(), 0);
+    }
+}
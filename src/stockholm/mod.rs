@@ -2,6 +2,7 @@
 
 pub mod parser;
 pub mod types;
+pub mod validate;
 pub mod writer;
 
 pub use types::*;
@@ -1,11 +1,12 @@
 //! Stockholm format parser.
 
 use std::collections::HashMap;
-use std::io::{BufRead, BufReader, Read};
+use std::io::Read;
 use std::rc::Rc;
 use thiserror::Error;
 
 use super::types::*;
+use super::writer;
 
 #[derive(Error, Debug)]
 pub enum ParseError {
@@ -22,32 +23,87 @@ pub enum ParseError {
     InconsistentLengths,
 }
 
-/// Parse a Stockholm format alignment from a reader.
-pub fn parse<R: Read>(reader: R) -> Result<Alignment, ParseError> {
-    let buf_reader = BufReader::new(reader);
-    let mut lines = buf_reader.lines();
+/// Parse a Stockholm format alignment from a reader (the first one, if the input
+/// holds several).
+pub fn parse<R: Read>(mut reader: R) -> Result<Alignment, ParseError> {
+    let mut input = String::new();
+    reader.read_to_string(&mut input)?;
+    Ok(parse_record(&strip_bom(input), "")?.0)
+}
+
+/// Parse every alignment in a multi-record Stockholm file (e.g. Rfam.seed), each
+/// ending with `//`.
+pub fn parse_all<R: Read>(mut reader: R) -> Result<Vec<Alignment>, ParseError> {
+    let mut input = String::new();
+    reader.read_to_string(&mut input)?;
+    let input = strip_bom(input);
+    let mut rest = input.as_str();
+    let mut alignments = Vec::new();
+    loop {
+        // Blank lines before a record are kept with it, and anything after the
+        // last record ignored
+        let record = rest.trim_start();
+        if !alignments.is_empty() && !record.starts_with("# STOCKHOLM") {
+            return Ok(alignments);
+        }
+        let separator = &rest[..rest.len() - record.len()];
+        let (alignment, len) = parse_record(record, separator)?;
+        alignments.push(alignment);
+        rest = &record[len..];
+    }
+}
+
+/// Drop a UTF-8 byte order mark, which would hide the `# STOCKHOLM` header.
+/// Windows `\r\n` line endings are trimmed line by line (and kept in the source
+/// text, so an unedited file is written back with them).
+fn strip_bom(input: String) -> String {
+    match input.strip_prefix('\u{feff}') {
+        Some(rest) => rest.to_string(),
+        None => input,
+    }
+}
+
+/// Parse one alignment from the start of `input`, returning it with the number
+/// of bytes read (through the `//` line). `separator` is the blank lines that
+/// came before it.
+fn parse_record(input: &str, separator: &str) -> Result<(Alignment, usize), ParseError> {
+    let mut lines = input.split_inclusive('\n');
 
     // Check header
-    let header = lines.next().ok_or(ParseError::UnexpectedEof)??;
+    let header = lines.next().ok_or(ParseError::UnexpectedEof)?;
     if !header.starts_with("# STOCKHOLM") {
         return Err(ParseError::InvalidHeader);
     }
+    let mut text_len = header.len();
 
     let mut alignment = Alignment::new();
 
+    // Comments, blank lines and #=GF/#=GS lines before the first alignment line
+    let mut header_lines: Vec<String> = Vec::new();
+    let mut in_header = true;
+
     // For blocked format: accumulate sequence data across blocks
     let mut seq_data: HashMap<String, String> = HashMap::new();
     let mut seq_order: Vec<String> = Vec::new();
 
-    // For blocked residue annotations
+    // For blocked residue and column annotations, in order of first appearance
     let mut gr_data: HashMap<(String, String), String> = HashMap::new();
+    let mut gr_order: Vec<(String, String)> = Vec::new();
     let mut gc_data: HashMap<String, String> = HashMap::new();
+    let mut gc_order: Vec<String> = Vec::new();
 
-    for line_result in lines {
-        let line = line_result?;
+    for raw_line in lines {
+        text_len += raw_line.len();
+        let line = raw_line.trim_end_matches(['\n', '\r']);
+
+        in_header &= line.trim().is_empty()
+            || (line.starts_with('#') && !line.starts_with("#=GC") && !line.starts_with("#=GR"));
+        if in_header {
+            header_lines.push(line.to_string());
+        }
 
         // Skip empty lines
-        if line.is_empty() {
+        if line.trim().is_empty() {
             continue;
         }
 
@@ -64,36 +120,28 @@ pub fn parse<R: Read>(reader: R) -> Result<Alignment, ParseError> {
         // File annotation: #=GF tag value
         if line.starts_with("#=GF") {
             if let Some(rest) = line.strip_prefix("#=GF") {
-                let parts: Vec<&str> = rest.trim().splitn(2, char::is_whitespace).collect();
-                if parts.len() >= 2 {
-                    alignment.file_annotations.push(FileAnnotation {
-                        tag: parts[0].to_string(),
-                        value: parts[1].trim().to_string(),
-                    });
-                } else if parts.len() == 1 {
-                    alignment.file_annotations.push(FileAnnotation {
-                        tag: parts[0].to_string(),
-                        value: String::new(),
-                    });
-                }
+                let (tag, value) = tag_value(rest);
+                alignment.file_annotations.push(FileAnnotation {
+                    tag: tag.to_string(),
+                    value: value.to_string(),
+                });
             }
             continue;
         }
 
         // Sequence annotation: #=GS seqid tag value
         if line.starts_with("#=GS") {
-            if let Some(rest) = line.strip_prefix("#=GS") {
-                let parts: Vec<&str> = rest.trim().splitn(3, char::is_whitespace).collect();
-                if parts.len() >= 3 {
-                    let seqid = parts[0].to_string();
-                    let tag = parts[1].to_string();
-                    let value = parts[2].trim().to_string();
-                    alignment
-                        .sequence_annotations
-                        .entry(seqid)
-                        .or_default()
-                        .push(SequenceAnnotation { tag, value });
-                }
+            if let Some(rest) = line.strip_prefix("#=GS")
+                && let Some((seqid, tag, value)) = id_tag_data(rest)
+            {
+                alignment
+                    .sequence_annotations
+                    .entry(seqid.to_string())
+                    .or_default()
+                    .push(SequenceAnnotation {
+                        tag: tag.to_string(),
+                        value: value.to_string(),
+                    });
             }
             continue;
         }
@@ -106,6 +154,9 @@ pub fn parse<R: Read>(reader: R) -> Result<Alignment, ParseError> {
                     let tag = parts[0].to_string();
                     let data = parts[1].trim().to_string();
                     // Accumulate for blocked format
+                    if !gc_data.contains_key(&tag) {
+                        gc_order.push(tag.clone());
+                    }
                     gc_data
                         .entry(tag)
                         .and_modify(|s| s.push_str(&data))
@@ -117,18 +168,18 @@ pub fn parse<R: Read>(reader: R) -> Result<Alignment, ParseError> {
 
         // Residue annotation: #=GR seqid tag data
         if line.starts_with("#=GR") {
-            if let Some(rest) = line.strip_prefix("#=GR") {
-                let parts: Vec<&str> = rest.trim().splitn(3, char::is_whitespace).collect();
-                if parts.len() >= 3 {
-                    let seqid = parts[0].to_string();
-                    let tag = parts[1].to_string();
-                    let data = parts[2].trim().to_string();
-                    // Accumulate for blocked format
-                    gr_data
-                        .entry((seqid, tag))
-                        .and_modify(|s| s.push_str(&data))
-                        .or_insert(data);
+            if let Some(rest) = line.strip_prefix("#=GR")
+                && let Some((seqid, tag, data)) = id_tag_data(rest)
+            {
+                // Accumulate for blocked format
+                let key = (seqid.to_string(), tag.to_string());
+                if !gr_data.contains_key(&key) {
+                    gr_order.push(key.clone());
                 }
+                gr_data
+                    .entry(key)
+                    .and_modify(|s| s.push_str(data))
+                    .or_insert_with(|| data.to_string());
             }
             continue;
         }
@@ -159,19 +210,23 @@ pub fn parse<R: Read>(reader: R) -> Result<Alignment, ParseError> {
     }
 
     // Build column annotations
-    for (tag, data) in gc_data {
-        alignment
-            .column_annotations
-            .push(ColumnAnnotation { tag, data });
+    for tag in gc_order {
+        if let Some(data) = gc_data.remove(&tag) {
+            alignment
+                .column_annotations
+                .push(ColumnAnnotation { tag, data });
+        }
     }
 
     // Build residue annotations
-    for ((seqid, tag), data) in gr_data {
-        alignment
-            .residue_annotations
-            .entry(seqid)
-            .or_default()
-            .push(ResidueAnnotation { tag, data });
+    for key in gr_order {
+        if let Some(data) = gr_data.remove(&key) {
+            alignment
+                .residue_annotations
+                .entry(key.0)
+                .or_default()
+                .push(ResidueAnnotation { tag: key.1, data });
+        }
     }
 
     // Validate lengths
@@ -179,7 +234,34 @@ pub fn parse<R: Read>(reader: R) -> Result<Alignment, ParseError> {
         return Err(ParseError::InconsistentLengths);
     }
 
-    Ok(alignment)
+    // Remember the original text, if the writer wouldn't reproduce it, so an
+    // unedited alignment is saved as it was read
+    let mut generated = Vec::new();
+    writer::generate(&alignment, &header_lines, None, &mut generated)?;
+    let text = &input[..text_len];
+    alignment.source = Some(Rc::new(StockholmSource {
+        header: header_lines,
+        separator: separator.to_string(),
+        text: (generated != text.as_bytes()).then(|| text.to_string()),
+        generated: writer::fingerprint(&generated),
+    }));
+
+    Ok((alignment, text_len))
+}
+
+/// Split the rest of a `#=GF` line into tag and value (which may be empty).
+pub(super) fn tag_value(rest: &str) -> (&str, &str) {
+    let rest = rest.trim();
+    rest.split_once(char::is_whitespace)
+        .map_or((rest, ""), |(tag, value)| (tag, value.trim()))
+}
+
+/// Split the rest of a `#=GS`/`#=GR` line into sequence ID, tag and data. Fields
+/// may be separated by any amount of whitespace (IDs are often padded).
+pub(super) fn id_tag_data(rest: &str) -> Option<(&str, &str, &str)> {
+    let (seqid, rest) = rest.trim().split_once(char::is_whitespace)?;
+    let (tag, data) = rest.trim_start().split_once(char::is_whitespace)?;
+    Some((seqid, tag, data.trim()))
 }
 
 /// Parse a Stockholm alignment from a string.
@@ -188,26 +270,6 @@ pub fn parse_str(s: &str) -> Result<Alignment, ParseError> {
     parse(s.as_bytes())
 }
 
-/// Parse a Stockholm alignment from a file path.
-/// Automatically handles gzip-compressed files (.gz extension).
-pub fn parse_file(path: &std::path::Path) -> Result<Alignment, ParseError> {
-    use flate2::read::GzDecoder;
-
-    let file = std::fs::File::open(path)?;
-
-    // Check if file is gzip-compressed by extension
-    let is_gzip = path
-        .extension()
-        .is_some_and(|ext| ext.eq_ignore_ascii_case("gz"));
-
-    if is_gzip {
-        let decoder = GzDecoder::new(file);
-        parse(decoder)
-    } else {
-        parse(file)
-    }
-}
-
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -258,12 +320,35 @@ seq2    WXYZ
         assert_eq!(alignment.width(), 8);
     }
 
+    #[test]
+    fn test_parse_crlf_and_bom() {
+        let windows = format!("\u{feff}{}", SIMPLE_ALIGNMENT.replace('\n', "\r\n"));
+        let alignment = parse_str(&windows).unwrap();
+        assert_eq!(alignment.width(), 10);
+        assert_eq!(alignment.sequences[1].data(), "ACGU..ACGU");
+        assert_eq!(alignment.ss_cons(), Some("<<<<..>>>>"));
+        assert_eq!(alignment.sequences[0].id, "seq1/1-10");
+        let text = alignment.source.unwrap().text.clone().unwrap();
+        assert!(!text.starts_with('\u{feff}'));
+    }
+
     #[test]
     fn test_invalid_header() {
         let result = parse_str("not a stockholm file\n//\n");
         assert!(matches!(result, Err(ParseError::InvalidHeader)));
     }
 
+    #[test]
+    fn test_parse_mismatched_annotation_length() {
+        // Short annotation rows load; the editor offers to repair them
+        let alignment = parse_str("# STOCKHOLM 1.0\nseq1 ACGU\n#=GC SS_cons <>\n//\n").unwrap();
+        assert_eq!(alignment.annotation_length_mismatches().len(), 1);
+        assert!(matches!(
+            parse_str("# STOCKHOLM 1.0\nseq1 ACGU\nseq2 ACG\n//\n"),
+            Err(ParseError::InconsistentLengths)
+        ));
+    }
+
     const R2R_ALIGNMENT: &str = r#"# STOCKHOLM 1.0
 #=GF R2R var_hairpin [ ]
 #=GF R2R var_backbone_range 1 2
@@ -299,4 +384,29 @@ venusian       CGU.UUCG.ACGUA...AGGA
             .collect();
         assert_eq!(r2r_commands.len(), 2);
     }
+
+    #[test]
+    fn test_parse_all_records() {
+        let input = "# STOCKHOLM 1.0\n#=GF ID one\na ACGU\n//\n\n# STOCKHOLM 1.0\n#=GF ID two\nb AC\nc GU\n//\n";
+        let alignments = parse_all(input.as_bytes()).unwrap();
+        assert_eq!(alignments.len(), 2);
+        assert_eq!(alignments[0].sequences[0].data(), "ACGU");
+        assert_eq!(alignments[1].num_sequences(), 2);
+        assert_eq!(alignments[1].file_annotations[0].value, "two");
+
+        // parse() still reads just the first record
+        assert_eq!(parse_str(input).unwrap().num_sequences(), 1);
+
+        let mut output = Vec::new();
+        let records: Vec<&Alignment> = alignments.iter().collect();
+        writer::write_records(
+            &records,
+            writer::StockholmStyle::Stockholm,
+            None,
+            &mut output,
+        )
+        .unwrap();
+        // The blank line between the records is kept
+        assert_eq!(String::from_utf8(output).unwrap(), input);
+    }
 }
@@ -1,7 +1,7 @@
 //! Core types for Stockholm format alignments.
 
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use std::rc::Rc;
 
 /// Extract short ID from a Stockholm ID (strips coordinate suffix like /10000-20000).
@@ -9,6 +9,91 @@ pub fn short_id(id: &str) -> &str {
     id.split('/').next().unwrap_or(id)
 }
 
+/// Split an ID like "name/start-end" into its name and coordinates.
+pub fn parse_coords(id: &str) -> Option<(&str, usize, usize)> {
+    let (name, range) = id.rsplit_once('/')?;
+    let (start, end) = range.split_once('-')?;
+    Some((name, start.parse().ok()?, end.parse().ok()?))
+}
+
+/// Extend (positive) or shrink (negative) the coordinates of an ID at its 5' and 3' ends.
+///
+/// Reverse-strand ranges (start > end) are handled. Returns None if the ID has no
+/// coordinates or the new range would fall below position 1.
+pub fn adjust_coords(id: &str, delta5: isize, delta3: isize) -> Option<String> {
+    let (name, start, end) = parse_coords(id)?;
+    let (start, end) = (start as isize, end as isize);
+    let (new_start, new_end) = if start <= end {
+        (start - delta5, end + delta3)
+    } else {
+        (start + delta5, end - delta3)
+    };
+    if new_start < 1 || new_end < 1 {
+        return None;
+    }
+    Some(format!("{name}/{new_start}-{new_end}"))
+}
+
+/// Number of residues an ID's `/start-end` coordinates span (on either strand).
+pub fn coords_length(id: &str) -> Option<usize> {
+    let (_, start, end) = parse_coords(id)?;
+    Some(start.abs_diff(end) + 1)
+}
+
+/// Replace an ID's `/start-end` coordinates, or add them to a bare ID.
+pub fn set_coords(id: &str, start: usize, end: usize) -> String {
+    let name = parse_coords(id).map_or(id, |(name, ..)| name);
+    format!("{name}/{start}-{end}")
+}
+
+/// Where `residues` lie in a full-length `source` sequence, as 1-based start and
+/// end coordinates; start > end when the reverse complement matches. Residues are
+/// compared ignoring case, with T and U equal. Of several matches, the one
+/// starting nearest `near` wins.
+pub fn locate_coords(residues: &str, source: &str, near: Option<usize>) -> Option<(usize, usize)> {
+    let normalize = |c: char| match c.to_ascii_uppercase() {
+        'T' => 'U',
+        c => c,
+    };
+    let complement = |c: char| match c {
+        'A' => 'U',
+        'U' => 'A',
+        'G' => 'C',
+        'C' => 'G',
+        c => c,
+    };
+    let source: Vec<char> = source.chars().map(normalize).collect();
+    let forward: Vec<char> = residues.chars().map(normalize).collect();
+    let reverse: Vec<char> = forward.iter().rev().map(|&c| complement(c)).collect();
+    let len = forward.len();
+    if len == 0 || len > source.len() {
+        return None;
+    }
+
+    let mut found = Vec::new();
+    for (i, window) in source.windows(len).enumerate() {
+        if window == forward.as_slice() {
+            found.push((i + 1, i + len));
+        }
+        if window == reverse.as_slice() {
+            found.push((i + len, i + 1));
+        }
+    }
+    match near {
+        Some(near) => found
+            .into_iter()
+            .min_by_key(|&(start, _)| start.abs_diff(near)),
+        None => found.into_iter().next(),
+    }
+}
+
+/// Characters read as gaps unless `:set gapchars=` says otherwise: `.` and `-`,
+/// and the `_`, `~` and `:` of WUSS and Infernal output.
+pub const DEFAULT_GAP_CHARS: [char; 5] = ['.', '-', '_', '~', ':'];
+
+/// #=GS tag used to store per-sequence curation flags (e.g. `#=GS seq1 FL suspicious`).
+pub const FLAG_TAG: &str = "FL";
+
 /// Direction for shift operations.
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum ShiftDirection {
@@ -16,6 +101,13 @@ pub enum ShiftDirection {
     Right,
 }
 
+/// End of a sequence, for padding and truncation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SequenceEnd {
+    FivePrime,
+    ThreePrime,
+}
+
 /// Type of sequences in the alignment.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
 #[allow(clippy::upper_case_acronyms)]
@@ -53,6 +145,28 @@ pub struct Alignment {
     pub column_annotations: Vec<ColumnAnnotation>,
     /// Per-residue annotations (#=GR)
     pub residue_annotations: HashMap<String, Vec<ResidueAnnotation>>,
+    /// Original text of a parsed Stockholm file, used to write it back unchanged.
+    pub source: Option<Rc<StockholmSource>>,
+}
+
+/// What a Stockholm file looked like when it was read.
+///
+/// The writer keeps the header's comments, blank lines and #=GF/#=GS lines in
+/// their original order and spacing, and writes `text` itself while the alignment
+/// still produces the output fingerprinted by `generated` (i.e. it has not been
+/// edited).
+#[derive(Debug)]
+pub struct StockholmSource {
+    /// Lines between `# STOCKHOLM 1.0` and the first alignment line.
+    pub header: Vec<String>,
+    /// Blank lines before `# STOCKHOLM 1.0`, written back between the records
+    /// of a multi-record file.
+    pub separator: String,
+    /// The file as read, through the `//` line; None when it is exactly what the
+    /// writer produces, so there is nothing to keep.
+    pub text: Option<String>,
+    /// Fingerprint of the writer output for the alignment as it was parsed.
+    pub generated: u64,
 }
 
 // Custom Serde for Alignment - unwrap Rc for serialization
@@ -67,9 +181,12 @@ impl serde::Serialize for Alignment {
         // Serialize sequences by dereferencing Rc
         let seqs: Vec<&Sequence> = self.sequences.iter().map(|rc| rc.as_ref()).collect();
         state.serialize_field("sequences", &seqs)?;
-        state.serialize_field("sequence_annotations", &self.sequence_annotations)?;
+        // Sort the per-sequence maps by ID so the output is stable
+        let sequence_annotations: BTreeMap<_, _> = self.sequence_annotations.iter().collect();
+        state.serialize_field("sequence_annotations", &sequence_annotations)?;
         state.serialize_field("column_annotations", &self.column_annotations)?;
-        state.serialize_field("residue_annotations", &self.residue_annotations)?;
+        let residue_annotations: BTreeMap<_, _> = self.residue_annotations.iter().collect();
+        state.serialize_field("residue_annotations", &residue_annotations)?;
         state.end()
     }
 }
@@ -94,6 +211,7 @@ impl<'de> serde::Deserialize<'de> for Alignment {
             sequence_annotations: helper.sequence_annotations,
             column_annotations: helper.column_annotations,
             residue_annotations: helper.residue_annotations,
+            source: None,
         })
     }
 }
@@ -157,6 +275,37 @@ pub struct ColumnAnnotation {
     pub data: String,
 }
 
+/// Highest value of a numeric #=GC track, written as `*`.
+pub const TRACK_MAX: u8 = 10;
+
+/// Value of a numeric #=GC track character: `0`-`9`, and `*` for the top of the
+/// scale as in PP_cons. Other characters (gaps) have no value.
+pub fn track_level(c: char) -> Option<u8> {
+    match c {
+        '*' => Some(TRACK_MAX),
+        _ => c.to_digit(10).map(|d| d as u8),
+    }
+}
+
+/// Character for a numeric #=GC track value (see [`track_level`]).
+pub fn track_char(level: u8) -> char {
+    if level >= TRACK_MAX {
+        '*'
+    } else {
+        char::from(b'0' + level)
+    }
+}
+
+/// What masking a residue does to it (`:set mask=`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum MaskStyle {
+    /// Lowercase the residue, keeping it in the alignment.
+    #[default]
+    Lowercase,
+    /// Replace the residue with a gap.
+    Gap,
+}
+
 /// Per-residue annotation (#=GR seqid tag data).
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ResidueAnnotation {
@@ -164,6 +313,34 @@ pub struct ResidueAnnotation {
     pub data: String,
 }
 
+/// A sequence taken out of an alignment together with its #=GS and #=GR annotations.
+#[derive(Debug, Clone)]
+pub struct DetachedSequence {
+    pub sequence: Rc<Sequence>,
+    pub annotations: Vec<SequenceAnnotation>,
+    pub residue_annotations: Vec<ResidueAnnotation>,
+}
+
+impl DetachedSequence {
+    /// Right-pad the sequence with `gap_char`, and its #=GR rows with `.`, to
+    /// `width` columns. Returns false if it is already that wide or wider.
+    pub fn pad_to(&mut self, width: usize, gap_char: char) -> bool {
+        if self.sequence.len() >= width {
+            return false;
+        }
+        Rc::make_mut(&mut self.sequence)
+            .chars_mut()
+            .resize(width, gap_char);
+        for ann in &mut self.residue_annotations {
+            let len = ann.data.chars().count();
+            if len < width {
+                ann.data.extend(std::iter::repeat_n('.', width - len));
+            }
+        }
+        true
+    }
+}
+
 impl Alignment {
     /// Create a new empty alignment.
     pub fn new() -> Self {
@@ -199,7 +376,6 @@ impl Alignment {
     }
 
     /// Get a mutable reference to the consensus secondary structure.
-    #[allow(dead_code)] // API for structure editing
     pub fn ss_cons_mut(&mut self) -> Option<&mut String> {
         self.column_annotations
             .iter_mut()
@@ -215,6 +391,31 @@ impl Alignment {
             .map(|a| a.data.as_str())
     }
 
+    /// Model position an insert at `col` follows: the number of match (non-gap
+    /// #=GC RF) columns before it. None without an RF line.
+    pub fn rf_match_count(&self, col: usize, gap_chars: &[char]) -> Option<usize> {
+        let rf = self.rf()?;
+        Some(
+            rf.chars()
+                .take(col)
+                .filter(|c| !gap_chars.contains(c))
+                .count(),
+        )
+    }
+
+    /// Where a new column at `col` joins the insert states between two match
+    /// columns: the first match column at or after `col` (the new column goes
+    /// just before it), or the end of the RF line. None without an RF line.
+    pub fn rf_insert_boundary(&self, col: usize, gap_chars: &[char]) -> Option<usize> {
+        let rf: Vec<char> = self.rf()?.chars().collect();
+        let col = col.min(rf.len());
+        Some(
+            (col..rf.len())
+                .find(|&i| !gap_chars.contains(&rf[i]))
+                .unwrap_or(rf.len()),
+        )
+    }
+
     /// Get the posterior probability consensus annotation if present.
     pub fn pp_cons(&self) -> Option<&str> {
         self.column_annotations
@@ -223,6 +424,37 @@ impl Alignment {
             .map(|a| a.data.as_str())
     }
 
+    /// Get a #=GC annotation row by tag.
+    pub fn column_annotation(&self, tag: &str) -> Option<&str> {
+        self.column_annotations
+            .iter()
+            .find(|a| a.tag == tag)
+            .map(|a| a.data.as_str())
+    }
+
+    /// Get a mutable reference to a #=GC annotation row.
+    pub fn column_annotation_mut(&mut self, tag: &str) -> Option<&mut String> {
+        self.column_annotations
+            .iter_mut()
+            .find(|a| a.tag == tag)
+            .map(|a| &mut a.data)
+    }
+
+    /// Set one column of a #=GC row, padding a short row with `.`. Returns false if
+    /// there is no row with that tag.
+    pub fn set_column_annotation_char(&mut self, tag: &str, col: usize, ch: char) -> bool {
+        let Some(data) = self.column_annotation_mut(tag) else {
+            return false;
+        };
+        let mut chars: Vec<char> = data.chars().collect();
+        if chars.len() <= col {
+            chars.resize(col + 1, '.');
+        }
+        chars[col] = ch;
+        *data = chars.into_iter().collect();
+        true
+    }
+
     /// Get a file-level annotation value by tag.
     pub fn get_file_annotation(&self, tag: &str) -> Option<&str> {
         self.file_annotations
@@ -231,6 +463,17 @@ impl Alignment {
             .map(|a| a.value.as_str())
     }
 
+    /// Set a file-level annotation, replacing the first existing line with that tag.
+    pub fn set_file_annotation(&mut self, tag: &str, value: &str) {
+        match self.file_annotations.iter_mut().find(|a| a.tag == tag) {
+            Some(ann) => ann.value = value.to_string(),
+            None => self.file_annotations.push(FileAnnotation {
+                tag: tag.to_string(),
+                value: value.to_string(),
+            }),
+        }
+    }
+
     /// Get all file-level annotations with a given tag (for multi-line annotations like CC).
     pub fn get_file_annotations(&self, tag: &str) -> Vec<&str> {
         self.file_annotations
@@ -240,6 +483,127 @@ impl Alignment {
             .collect()
     }
 
+    /// Get the curation flag label for a sequence, if flagged.
+    pub fn sequence_flag(&self, id: &str) -> Option<&str> {
+        self.sequence_annotations
+            .get(id)?
+            .iter()
+            .find(|a| a.tag == FLAG_TAG)
+            .map(|a| a.value.as_str())
+    }
+
+    /// Copy of the alignment containing only the given rows, with their annotations.
+    pub fn select_rows(&self, rows: &[usize]) -> Alignment {
+        let sequences: Vec<Rc<Sequence>> = rows
+            .iter()
+            .filter_map(|&row| self.sequences.get(row).cloned())
+            .collect();
+        let keep = |id: &String| sequences.iter().any(|s| &s.id == id);
+        Alignment {
+            file_annotations: self.file_annotations.clone(),
+            source: self.source.clone(),
+            column_annotations: self.column_annotations.clone(),
+            sequence_annotations: self
+                .sequence_annotations
+                .iter()
+                .filter(|(id, _)| keep(id))
+                .map(|(id, anns)| (id.clone(), anns.clone()))
+                .collect(),
+            residue_annotations: self
+                .residue_annotations
+                .iter()
+                .filter(|(id, _)| keep(id))
+                .map(|(id, anns)| (id.clone(), anns.clone()))
+                .collect(),
+            sequences,
+        }
+    }
+
+    /// Copy a block of the alignment: the given rows and columns, with #=GC and #=GR
+    /// rows sliced to match. IDs with `/start-end` coordinates are renumbered to the
+    /// residues kept; a sequence with no residues in the block keeps its ID.
+    pub fn select_region(
+        &self,
+        rows: &[usize],
+        cols: std::ops::Range<usize>,
+        gap_chars: &[char],
+    ) -> Alignment {
+        let mut region = self.select_rows(rows);
+        let width = region.width();
+        let cols = cols.start.min(width)..cols.end.min(width);
+
+        for row in 0..region.sequences.len() {
+            let chars = region.sequences[row].chars();
+            let residues = |range: std::ops::Range<usize>| {
+                let range = range.start.min(chars.len())..range.end.min(chars.len());
+                chars[range]
+                    .iter()
+                    .filter(|c| !gap_chars.contains(c))
+                    .count() as isize
+            };
+            if residues(cols.clone()) == 0 {
+                continue;
+            }
+            let trimmed5 = residues(0..cols.start);
+            let trimmed3 = residues(cols.end..chars.len());
+            if let Some(id) = adjust_coords(&region.sequences[row].id, -trimmed5, -trimmed3) {
+                region.rename_sequence(row, &id);
+            }
+        }
+
+        let outside: Vec<usize> = (0..cols.start).chain(cols.end..width).collect();
+        region.remove_columns(&outside);
+        region
+    }
+
+    /// Rename a sequence, carrying its `#=GS` and `#=GR` annotations over to the new ID.
+    pub fn rename_sequence(&mut self, row: usize, new_id: &str) {
+        let Some(seq_rc) = self.sequences.get_mut(row) else {
+            return;
+        };
+        let old_id = std::mem::replace(&mut Rc::make_mut(seq_rc).id, new_id.to_string());
+        if let Some(anns) = self.sequence_annotations.remove(&old_id) {
+            self.sequence_annotations.insert(new_id.to_string(), anns);
+        }
+        if let Some(anns) = self.residue_annotations.remove(&old_id) {
+            self.residue_annotations.insert(new_id.to_string(), anns);
+        }
+    }
+
+    /// Set (or replace) the curation flag for a sequence.
+    pub fn set_sequence_flag(&mut self, id: &str, label: &str) {
+        let annotations = self.sequence_annotations.entry(id.to_string()).or_default();
+        if let Some(ann) = annotations.iter_mut().find(|a| a.tag == FLAG_TAG) {
+            ann.value = label.to_string();
+        } else {
+            annotations.push(SequenceAnnotation {
+                tag: FLAG_TAG.to_string(),
+                value: label.to_string(),
+            });
+        }
+    }
+
+    /// Remove the curation flag from a sequence. Returns true if a flag was removed.
+    pub fn clear_sequence_flag(&mut self, id: &str) -> bool {
+        let Some(annotations) = self.sequence_annotations.get_mut(id) else {
+            return false;
+        };
+        let before = annotations.len();
+        annotations.retain(|a| a.tag != FLAG_TAG);
+        let removed = annotations.len() != before;
+        if annotations.is_empty() {
+            self.sequence_annotations.remove(id);
+        }
+        removed
+    }
+
+    /// Check if any sequence carries a curation flag.
+    pub fn has_sequence_flags(&self) -> bool {
+        self.sequences
+            .iter()
+            .any(|s| self.sequence_flag(&s.id).is_some())
+    }
+
     /// Check if all sequences have the same length.
     pub fn is_valid(&self) -> bool {
         if self.sequences.is_empty() {
@@ -247,10 +611,98 @@ impl Alignment {
         }
         let width = self.sequences[0].len();
         self.sequences.iter().all(|s| s.len() == width)
-            && self
-                .column_annotations
-                .iter()
-                .all(|a| a.data.len() == width)
+    }
+
+    /// Right-pad sequences shorter than the longest one with `gap_char`, making an
+    /// unaligned set of sequences rectangular. Returns the number padded.
+    pub fn pad_to_longest(&mut self, gap_char: char) -> usize {
+        let width = self.sequences.iter().map(|s| s.len()).max().unwrap_or(0);
+        let mut padded = 0;
+        for seq in &mut self.sequences {
+            if seq.len() < width {
+                Rc::make_mut(seq).chars_mut().resize(width, gap_char);
+                padded += 1;
+            }
+        }
+        padded
+    }
+
+    /// #=GC and #=GR rows whose length differs from the alignment width, as labels
+    /// (e.g. `#=GC SS_cons`) with their lengths.
+    pub fn annotation_length_mismatches(&self) -> Vec<(String, usize)> {
+        let width = self.width();
+        let mut mismatches: Vec<(String, usize)> = self
+            .column_annotations
+            .iter()
+            .map(|a| (format!("#=GC {}", a.tag), a.data.chars().count()))
+            .filter(|(_, len)| *len != width)
+            .collect();
+        for seq in &self.sequences {
+            for ann in self.residue_annotations.get(&seq.id).into_iter().flatten() {
+                let len = ann.data.chars().count();
+                if len != width {
+                    mismatches.push((format!("#=GR {} {}", seq.id, ann.tag), len));
+                }
+            }
+        }
+        mismatches
+    }
+
+    /// Rows whose length differs from the alignment width, e.g. `seq2 is 41
+    /// columns`: sequences, and #=GC and #=GR rows if `annotations`. Without
+    /// sequences there is no width to differ from.
+    pub fn width_problems(&self, annotations: bool) -> Vec<String> {
+        if self.sequences.is_empty() {
+            return Vec::new();
+        }
+        let width = self.width();
+        let mut problems: Vec<String> = self
+            .sequences
+            .iter()
+            .filter(|s| s.len() != width)
+            .map(|s| format!("{} is {} columns", s.id, s.len()))
+            .collect();
+        if annotations {
+            problems.extend(
+                self.annotation_length_mismatches()
+                    .into_iter()
+                    .map(|(label, len)| format!("{label} is {len} columns")),
+            );
+        }
+        problems
+    }
+
+    /// Pad (with `.`) or truncate #=GC and #=GR rows to the alignment width.
+    /// Returns the number of rows changed.
+    pub fn fit_annotations_to_width(&mut self) -> usize {
+        fn fit(data: &mut String, width: usize) -> bool {
+            // Annotations are ASCII, so the byte length is a cheap first check
+            if data.len() == width {
+                return false;
+            }
+            let len = data.chars().count();
+            if len == width {
+                return false;
+            }
+            if len < width {
+                data.extend(std::iter::repeat_n('.', width - len));
+            } else {
+                *data = data.chars().take(width).collect();
+            }
+            true
+        }
+
+        let width = self.width();
+        let mut changed = 0;
+        for ann in &mut self.column_annotations {
+            changed += usize::from(fit(&mut ann.data, width));
+        }
+        for annotations in self.residue_annotations.values_mut() {
+            for ann in annotations {
+                changed += usize::from(fit(&mut ann.data, width));
+            }
+        }
+        changed
     }
 
     /// Get the maximum sequence ID length (for formatting).
@@ -268,13 +720,15 @@ impl Alignment {
     }
 
     /// Insert a gap at a specific position in all sequences and annotations.
+    /// The #=GC RF line gets `.`, marking the new column as an insert state.
     pub fn insert_gap_column(&mut self, col: usize, gap_char: char) {
         for seq in &mut self.sequences {
             Rc::make_mut(seq).insert_gap(col, gap_char);
         }
         for ann in &mut self.column_annotations {
             if col <= ann.data.len() {
-                ann.data.insert(col, gap_char);
+                ann.data
+                    .insert(col, if ann.tag == "RF" { '.' } else { gap_char });
             }
         }
         for annotations in self.residue_annotations.values_mut() {
@@ -286,6 +740,242 @@ impl Alignment {
         }
     }
 
+    /// Mark gaps at `col` as end gaps where they lie outside a sequence's residues.
+    ///
+    /// Also turns end gaps that ended up between residues back into `gap_char`.
+    pub fn fix_end_gaps(&mut self, col: usize, gap_char: char, end_gap: char, gap_chars: &[char]) {
+        for seq_rc in &mut self.sequences {
+            let fixes = seq_rc.end_gap_fixes(col, gap_char, end_gap, gap_chars);
+            if !fixes.is_empty() {
+                let seq = Rc::make_mut(seq_rc);
+                for (i, ch) in fixes {
+                    seq.set(i, ch);
+                }
+            }
+        }
+    }
+
+    /// Distance the residues in a column can be thrown in the given direction.
+    ///
+    /// Limited by the shortest run of gaps next to the column among sequences that have a
+    /// residue there. Returns 0 if the column holds no residues.
+    pub fn column_throw_distance(
+        &self,
+        col: usize,
+        direction: ShiftDirection,
+        gap_chars: &[char],
+    ) -> usize {
+        let is_gap = |seq: &Sequence, i: usize| seq.get(i).is_none_or(|c| gap_chars.contains(&c));
+        self.sequences
+            .iter()
+            .filter(|seq| !is_gap(seq, col))
+            .map(|seq| match direction {
+                ShiftDirection::Left => (0..col).rev().take_while(|&i| is_gap(seq, i)).count(),
+                ShiftDirection::Right => ((col + 1)..seq.len())
+                    .take_while(|&i| is_gap(seq, i))
+                    .count(),
+            })
+            .min()
+            .unwrap_or(0)
+    }
+
+    /// Throw the residues in a column as far as possible in the given direction.
+    ///
+    /// Every sequence with a residue at `col` moves it the same distance, so the column
+    /// stays together. `#=GR` annotations follow their residues. Returns the distance moved.
+    pub fn throw_column(
+        &mut self,
+        col: usize,
+        direction: ShiftDirection,
+        gap_chars: &[char],
+    ) -> usize {
+        let distance = self.column_throw_distance(col, direction, gap_chars);
+        if distance == 0 {
+            return 0;
+        }
+        let target = match direction {
+            ShiftDirection::Left => col - distance,
+            ShiftDirection::Right => col + distance,
+        };
+
+        for row in 0..self.sequences.len() {
+            if self.sequences[row]
+                .get(col)
+                .is_none_or(|c| gap_chars.contains(&c))
+            {
+                continue;
+            }
+            self.swap_residues(row, col, target);
+        }
+
+        distance
+    }
+
+    /// Remove the sequence at `index` along with its #=GS and #=GR annotations.
+    pub fn take_sequence(&mut self, index: usize) -> Option<DetachedSequence> {
+        if index >= self.sequences.len() {
+            return None;
+        }
+        let sequence = self.sequences.remove(index);
+        Some(DetachedSequence {
+            annotations: self
+                .sequence_annotations
+                .remove(&sequence.id)
+                .unwrap_or_default(),
+            residue_annotations: self
+                .residue_annotations
+                .remove(&sequence.id)
+                .unwrap_or_default(),
+            sequence,
+        })
+    }
+
+    /// Copy the sequence at `index` along with its #=GS and #=GR annotations.
+    pub fn copy_sequence(&self, index: usize) -> Option<DetachedSequence> {
+        let sequence = self.sequences.get(index)?.clone();
+        Some(DetachedSequence {
+            annotations: self
+                .sequence_annotations
+                .get(&sequence.id)
+                .cloned()
+                .unwrap_or_default(),
+            residue_annotations: self
+                .residue_annotations
+                .get(&sequence.id)
+                .cloned()
+                .unwrap_or_default(),
+            sequence,
+        })
+    }
+
+    /// `id` if no sequence has it yet, otherwise the first free `name_copy`,
+    /// `name_copy2`, ... keeping any `/start-end` coordinates.
+    pub fn unused_id(&self, id: &str) -> String {
+        let taken = |id: &str| self.sequences.iter().any(|s| s.id == id);
+        if !taken(id) {
+            return id.to_string();
+        }
+        let (name, coords) = match parse_coords(id) {
+            Some((name, ..)) => (name, &id[name.len()..]),
+            None => (id, ""),
+        };
+        // A copy of a copy is numbered like the other copies
+        let name = name
+            .rsplit_once("_copy")
+            .filter(|(_, n)| n.chars().all(|c| c.is_ascii_digit()))
+            .map_or(name, |(base, _)| base);
+        (1..)
+            .map(|n| match n {
+                1 => format!("{name}_copy{coords}"),
+                n => format!("{name}_copy{n}{coords}"),
+            })
+            .find(|candidate| !taken(candidate))
+            .unwrap()
+    }
+
+    /// Insert a detached sequence at `index`, restoring its annotations.
+    pub fn insert_sequence(&mut self, index: usize, detached: DetachedSequence) {
+        let id = detached.sequence.id.clone();
+        if !detached.annotations.is_empty() {
+            self.sequence_annotations
+                .insert(id.clone(), detached.annotations);
+        }
+        if !detached.residue_annotations.is_empty() {
+            self.residue_annotations
+                .insert(id, detached.residue_annotations);
+        }
+        let index = index.min(self.sequences.len());
+        self.sequences.insert(index, detached.sequence);
+    }
+
+    /// Put the sequences in `order`, a permutation of their row indices. Annotations
+    /// are keyed by ID, so they stay with their sequences.
+    pub fn reorder_sequences(&mut self, order: &[usize]) {
+        debug_assert_eq!(order.len(), self.sequences.len());
+        self.sequences = order.iter().map(|&i| self.sequences[i].clone()).collect();
+    }
+
+    /// Swap two columns within one sequence, carrying its #=GR annotations along.
+    pub fn swap_residues(&mut self, row: usize, a: usize, b: usize) {
+        let Some(seq_rc) = self.sequences.get_mut(row) else {
+            return;
+        };
+        if a >= seq_rc.len() || b >= seq_rc.len() {
+            return;
+        }
+        let seq = Rc::make_mut(seq_rc);
+        seq.chars_mut().swap(a, b);
+        if let Some(annotations) = self.residue_annotations.get_mut(&seq.id) {
+            for ann in annotations {
+                let mut data: Vec<char> = ann.data.chars().collect();
+                if a < data.len() && b < data.len() {
+                    data.swap(a, b);
+                    ann.data = data.into_iter().collect();
+                }
+            }
+        }
+    }
+
+    /// First row whose residues would be overwritten or jumped over by moving
+    /// columns `cols` of `rows` so that they start at `to`, or None if the move is
+    /// possible. Every column the block passes over must be a gap, so a move never
+    /// reorders a sequence's residues.
+    pub fn block_move_conflict(
+        &self,
+        rows: &[usize],
+        cols: std::ops::Range<usize>,
+        to: usize,
+        gap_chars: &[char],
+    ) -> Option<usize> {
+        let end = to + cols.len();
+        let swept = cols.start.min(to)..cols.end.max(end);
+        rows.iter().copied().find(|&row| {
+            let Some(seq) = self.sequences.get(row) else {
+                return true;
+            };
+            end > seq.len()
+                || swept.clone().any(|col| {
+                    !cols.contains(&col) && seq.get(col).is_some_and(|c| !gap_chars.contains(&c))
+                })
+        })
+    }
+
+    /// Move columns `cols` of `rows` so that they start at `to`, leaving gaps behind.
+    ///
+    /// #=GR annotations move with their residues. Check [`Self::block_move_conflict`]
+    /// first; cells in the way are overwritten.
+    pub fn move_block(
+        &mut self,
+        rows: &[usize],
+        cols: std::ops::Range<usize>,
+        to: usize,
+        gap_char: char,
+    ) {
+        fn move_span(data: &mut [char], cols: &std::ops::Range<usize>, to: usize, fill: char) {
+            if cols.end > data.len() || to + cols.len() > data.len() {
+                return;
+            }
+            let block = data[cols.clone()].to_vec();
+            data[cols.clone()].fill(fill);
+            data[to..to + block.len()].copy_from_slice(&block);
+        }
+
+        for &row in rows {
+            let Some(seq_rc) = self.sequences.get_mut(row) else {
+                continue;
+            };
+            let seq = Rc::make_mut(seq_rc);
+            move_span(seq.chars_mut(), &cols, to, gap_char);
+            if let Some(annotations) = self.residue_annotations.get_mut(&seq.id) {
+                for ann in annotations {
+                    let mut data: Vec<char> = ann.data.chars().collect();
+                    move_span(&mut data, &cols, to, '.');
+                    ann.data = data.into_iter().collect();
+                }
+            }
+        }
+    }
+
     /// Delete a column if it contains only gaps in all sequences.
     pub fn delete_gap_column(&mut self, col: usize, gap_chars: &[char]) -> bool {
         // Check if column is all gaps (O(1) per sequence now)
@@ -320,6 +1010,262 @@ impl Alignment {
         true
     }
 
+    /// Remove columns from all sequences and column/residue annotations.
+    pub fn remove_columns(&mut self, cols: &[usize]) {
+        let width = self.width();
+        let mut removed = vec![false; width];
+        for &col in cols {
+            if col < width {
+                removed[col] = true;
+            }
+        }
+        let keep = |data: &str| -> String {
+            data.chars()
+                .enumerate()
+                .filter(|(i, _)| !removed.get(*i).copied().unwrap_or(false))
+                .map(|(_, c)| c)
+                .collect()
+        };
+
+        for seq in &mut self.sequences {
+            let mut col = 0;
+            Rc::make_mut(seq).chars_mut().retain(|_| {
+                col += 1;
+                !removed.get(col - 1).copied().unwrap_or(false)
+            });
+        }
+        for ann in &mut self.column_annotations {
+            ann.data = keep(&ann.data);
+        }
+        for annotations in self.residue_annotations.values_mut() {
+            for ann in annotations {
+                ann.data = keep(&ann.data);
+            }
+        }
+    }
+
+    /// Mask residues whose #=GR PP is below `threshold` (on the track scale,
+    /// `*` = 10): lowercase them, or replace them with `gap_char` and clear their
+    /// PP. Residues without a PP value are left alone. Returns how many changed.
+    pub fn mask_low_pp(
+        &mut self,
+        threshold: u8,
+        style: MaskStyle,
+        gap_char: char,
+        gap_chars: &[char],
+    ) -> usize {
+        let rows: Vec<usize> = (0..self.sequences.len()).collect();
+        let width = self.width();
+        self.mask_low_pp_region(&rows, 0..width, threshold, style, gap_char, gap_chars)
+    }
+
+    /// [`Self::mask_low_pp`] limited to the `cols` of `rows`.
+    pub fn mask_low_pp_region(
+        &mut self,
+        rows: &[usize],
+        cols: std::ops::Range<usize>,
+        threshold: u8,
+        style: MaskStyle,
+        gap_char: char,
+        gap_chars: &[char],
+    ) -> usize {
+        let mut masked = 0;
+        for &row in rows {
+            let Some(seq) = self.sequences.get_mut(row) else {
+                continue;
+            };
+            let Some(pp) = self
+                .residue_annotations
+                .get_mut(&seq.id)
+                .and_then(|anns| anns.iter_mut().find(|ann| ann.tag == "PP"))
+            else {
+                continue;
+            };
+            let mut pp_chars: Vec<char> = pp.data.chars().collect();
+            let chars = Rc::make_mut(seq).chars_mut();
+            let cells = chars.iter_mut().zip(pp_chars.iter_mut());
+            for (c, p) in cells.skip(cols.start).take(cols.len()) {
+                if gap_chars.contains(c) || track_level(*p).is_none_or(|l| l >= threshold) {
+                    continue;
+                }
+                match style {
+                    MaskStyle::Lowercase if c.is_ascii_lowercase() => continue,
+                    MaskStyle::Lowercase => *c = c.to_ascii_lowercase(),
+                    MaskStyle::Gap => {
+                        *c = gap_char;
+                        *p = gap_char;
+                    }
+                }
+                masked += 1;
+            }
+            pp.data = pp_chars.into_iter().collect();
+        }
+        masked
+    }
+
+    /// Columns where more than `threshold` (0 to 1) of the sequences have a gap.
+    pub fn gappy_columns(&self, threshold: f64, gap_chars: &[char]) -> Vec<usize> {
+        let count = self.sequences.len();
+        if count == 0 {
+            return Vec::new();
+        }
+        (0..self.width())
+            .filter(|&col| {
+                let gaps = self
+                    .sequences
+                    .iter()
+                    .filter(|s| s.get(col).is_none_or(|c| gap_chars.contains(&c)))
+                    .count();
+                gaps as f64 / count as f64 > threshold
+            })
+            .collect()
+    }
+
+    /// Mask the residues of every sequence in `cols`: lowercase them, or replace
+    /// them with `gap_char` and clear their PP. Returns how many changed.
+    pub fn mask_columns(
+        &mut self,
+        cols: &[usize],
+        style: MaskStyle,
+        gap_char: char,
+        gap_chars: &[char],
+    ) -> usize {
+        let mut masked = 0;
+        for seq in &mut self.sequences {
+            let mut pp = self
+                .residue_annotations
+                .get_mut(&seq.id)
+                .and_then(|anns| anns.iter_mut().find(|ann| ann.tag == "PP"));
+            let mut pp_chars: Option<Vec<char>> = pp.as_ref().map(|p| p.data.chars().collect());
+            let chars = Rc::make_mut(seq).chars_mut();
+            for &col in cols {
+                let Some(c) = chars.get_mut(col) else {
+                    continue;
+                };
+                if gap_chars.contains(c) {
+                    continue;
+                }
+                match style {
+                    MaskStyle::Lowercase if c.is_ascii_lowercase() => continue,
+                    MaskStyle::Lowercase => *c = c.to_ascii_lowercase(),
+                    MaskStyle::Gap => {
+                        *c = gap_char;
+                        if let Some(p) = pp_chars.as_mut().and_then(|p| p.get_mut(col)) {
+                            *p = gap_char;
+                        }
+                    }
+                }
+                masked += 1;
+            }
+            if let (Some(pp), Some(pp_chars)) = (pp.as_mut(), pp_chars) {
+                pp.data = pp_chars.into_iter().collect();
+            }
+        }
+        masked
+    }
+
+    /// Push the residues of `rows` from `col` on to the right by `count` gaps:
+    /// each row absorbs the first `count` gaps it has from `col` on, as a shift
+    /// does, so the rest of the row stays put. Gap columns are added at the end
+    /// when a row has too few gaps to spare; returns how many were added.
+    pub fn push_gaps(
+        &mut self,
+        rows: &[usize],
+        col: usize,
+        count: usize,
+        gap_char: char,
+        gap_chars: &[char],
+    ) -> usize {
+        let spare = |seq: &Sequence| {
+            seq.chars()
+                .iter()
+                .skip(col)
+                .filter(|c| gap_chars.contains(c))
+                .count()
+        };
+        let added = rows
+            .iter()
+            .filter_map(|&row| self.sequences.get(row))
+            .map(|seq| count.saturating_sub(spare(seq)))
+            .max()
+            .unwrap_or(0);
+        for _ in 0..added {
+            self.insert_gap_column(self.width(), gap_char);
+        }
+
+        for &row in rows {
+            let Some(seq_rc) = self.sequences.get_mut(row) else {
+                continue;
+            };
+            let absorbed: Vec<usize> = (col..seq_rc.len())
+                .filter(|&i| gap_chars.contains(&seq_rc.chars()[i]))
+                .take(count)
+                .collect();
+            let push = |data: &[char]| -> Vec<char> {
+                let mut pushed = Vec::with_capacity(data.len());
+                for (i, &c) in data.iter().enumerate() {
+                    if i == col {
+                        pushed.extend(std::iter::repeat_n(gap_char, count));
+                    }
+                    if absorbed.binary_search(&i).is_err() {
+                        pushed.push(c);
+                    }
+                }
+                pushed
+            };
+            let seq = Rc::make_mut(seq_rc);
+            *seq.chars_mut() = push(seq.chars());
+            if let Some(annotations) = self.residue_annotations.get_mut(&seq.id) {
+                for ann in annotations {
+                    let chars: Vec<char> = ann.data.chars().collect();
+                    ann.data = push(&chars).into_iter().collect();
+                }
+            }
+        }
+        added
+    }
+
+    /// Close up the gaps of `rows` within `cols`, moving their residues to the
+    /// left end of the range and the gaps to its right end. Returns how many
+    /// rows changed.
+    pub fn pack_left(
+        &mut self,
+        rows: &[usize],
+        cols: std::ops::Range<usize>,
+        gap_chars: &[char],
+    ) -> usize {
+        let mut changed = 0;
+        for &row in rows {
+            let Some(seq_rc) = self.sequences.get_mut(row) else {
+                continue;
+            };
+            let end = cols.end.min(seq_rc.len());
+            let (mut order, gaps): (Vec<usize>, Vec<usize>) =
+                (cols.start..end).partition(|&i| !gap_chars.contains(&seq_rc.chars()[i]));
+            order.extend(gaps);
+            if order.iter().copied().eq(cols.start..end) {
+                continue;
+            }
+            let pack = |data: &mut [char]| {
+                let packed: Vec<char> = order.iter().map(|&i| data[i]).collect();
+                data[cols.start..end].copy_from_slice(&packed);
+            };
+            let seq = Rc::make_mut(seq_rc);
+            pack(seq.chars_mut());
+            if let Some(annotations) = self.residue_annotations.get_mut(&seq.id) {
+                for ann in annotations {
+                    let mut chars: Vec<char> = ann.data.chars().collect();
+                    if chars.len() >= end {
+                        pack(&mut chars);
+                        ann.data = chars.into_iter().collect();
+                    }
+                }
+            }
+            changed += 1;
+        }
+        changed
+    }
+
     /// Get character at a specific position (O(1)).
     pub fn get_char(&self, row: usize, col: usize) -> Option<char> {
         self.sequences.get(row)?.get(col)
@@ -452,6 +1398,11 @@ impl Sequence {
         self.chars.len()
     }
 
+    /// Number of residues (non-gap characters).
+    pub fn ungapped_len(&self, gap_chars: &[char]) -> usize {
+        self.chars.iter().filter(|c| !gap_chars.contains(c)).count()
+    }
+
     /// Check if the sequence is empty.
     #[allow(dead_code)] // API completeness
     pub fn is_empty(&self) -> bool {
@@ -482,28 +1433,93 @@ impl Sequence {
         }
     }
 
-    /// Delete a character at a specific position if it's a gap.
-    pub fn delete_gap(&mut self, pos: usize, gap_chars: &[char]) -> bool {
+    /// Delete the character at a specific position, returning it.
+    pub fn remove(&mut self, pos: usize) -> Option<char> {
+        (pos < self.chars.len()).then(|| self.chars.remove(pos))
+    }
+
+    /// Positions of the first and last residue, or None if the sequence is all gaps.
+    pub fn residue_span(&self, gap_chars: &[char]) -> Option<(usize, usize)> {
+        let first = self.chars.iter().position(|c| !gap_chars.contains(c))?;
+        let last = self.chars.iter().rposition(|c| !gap_chars.contains(c))?;
+        Some((first, last))
+    }
+
+    /// Gap replacements needed to keep terminal and internal gaps distinct.
+    ///
+    /// A gap at `pos` before the first or after the last residue should be `end_gap`;
+    /// any `end_gap` inside the residue span should be `gap_char`.
+    pub fn end_gap_fixes(
+        &self,
+        pos: usize,
+        gap_char: char,
+        end_gap: char,
+        gap_chars: &[char],
+    ) -> Vec<(usize, char)> {
+        let Some((first, last)) = self.residue_span(gap_chars) else {
+            return Vec::new();
+        };
+        let mut fixes: Vec<(usize, char)> = (first..=last)
+            .filter(|&i| self.chars[i] == end_gap)
+            .map(|i| (i, gap_char))
+            .collect();
         if let Some(&ch) = self.chars.get(pos)
+            && (pos < first || pos > last)
+            && ch != end_gap
             && gap_chars.contains(&ch)
         {
-            self.chars.remove(pos);
-            return true;
+            fixes.push((pos, end_gap));
         }
-        false
+        fixes
     }
 
-    /// Shift sequence in the given direction (moves content to next gap).
-    pub fn shift(&mut self, col: usize, direction: ShiftDirection, gap_chars: &[char]) -> bool {
-        // Find the nearest gap in the specified direction
-        let gap_pos = match direction {
+    /// Apply [`Sequence::end_gap_fixes`] in place.
+    pub fn fix_end_gaps(&mut self, pos: usize, gap_char: char, end_gap: char, gap_chars: &[char]) {
+        for (i, ch) in self.end_gap_fixes(pos, gap_char, end_gap, gap_chars) {
+            self.chars[i] = ch;
+        }
+    }
+
+    /// Pad or trim trailing gaps so the sequence spans `width` columns.
+    ///
+    /// Padding uses `end_gap`. Only trailing gaps are removed, so residues are never
+    /// pushed out of the alignment. Returns the number of columns added (negative if removed).
+    pub fn fit_trailing_gaps(&mut self, width: usize, end_gap: char, gap_chars: &[char]) -> isize {
+        let mut delta = 0;
+        while self.chars.len() < width {
+            self.chars.push(end_gap);
+            delta += 1;
+        }
+        while self.chars.len() > width && self.chars.last().is_some_and(|c| gap_chars.contains(c)) {
+            self.chars.pop();
+            delta -= 1;
+        }
+        delta
+    }
+
+    /// Nearest gap a shift at `col` would absorb.
+    fn shift_gap(
+        &self,
+        col: usize,
+        direction: ShiftDirection,
+        gap_chars: &[char],
+    ) -> Option<usize> {
+        match direction {
             ShiftDirection::Left => (0..col).rev().find(|&i| gap_chars.contains(&self.chars[i])),
             ShiftDirection::Right => {
                 ((col + 1)..self.chars.len()).find(|&i| gap_chars.contains(&self.chars[i]))
             }
-        };
+        }
+    }
 
-        if let Some(gp) = gap_pos {
+    /// Whether [`Self::shift`] at `col` would change the sequence.
+    pub fn can_shift(&self, col: usize, direction: ShiftDirection, gap_chars: &[char]) -> bool {
+        self.shift_gap(col, direction, gap_chars).is_some()
+    }
+
+    /// Shift sequence in the given direction (moves content to next gap).
+    pub fn shift(&mut self, col: usize, direction: ShiftDirection, gap_chars: &[char]) -> bool {
+        if let Some(gp) = self.shift_gap(col, direction, gap_chars) {
             // Remove gap at gp, insert gap at col
             self.chars.remove(gp);
             self.chars.insert(col, gap_chars[0]);
@@ -538,21 +1554,209 @@ impl Sequence {
             *ch = ch.to_ascii_lowercase();
         }
     }
-
-    /// Replace all occurrences of one character with another.
-    pub fn replace_char(&mut self, from: char, to: char) {
-        for ch in &mut self.chars {
-            if *ch == from {
-                *ch = to;
-            }
-        }
-    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_throw_column() {
+        let mut alignment = Alignment::new();
+        alignment
+            .sequences
+            .push(Rc::new(Sequence::new("seq1", "AC..GU")));
+        alignment
+            .sequences
+            .push(Rc::new(Sequence::new("seq2", "A...GU")));
+        alignment
+            .sequences
+            .push(Rc::new(Sequence::new("seq3", "AC.-.U")));
+        alignment.residue_annotations.insert(
+            "seq1".to_string(),
+            vec![ResidueAnnotation {
+                tag: "PP".to_string(),
+                data: "99..87".to_string(),
+            }],
+        );
+        let gaps = ['.', '-'];
+
+        // seq1 and seq2 have residues at column 4; seq1 limits the throw to 2
+        assert_eq!(
+            alignment.column_throw_distance(4, ShiftDirection::Left, &gaps),
+            2
+        );
+        assert_eq!(alignment.throw_column(4, ShiftDirection::Left, &gaps), 2);
+        assert_eq!(alignment.sequences[0].data(), "ACG..U");
+        assert_eq!(alignment.sequences[1].data(), "A.G..U");
+        assert_eq!(alignment.sequences[2].data(), "AC.-.U");
+        assert_eq!(alignment.residue_annotations["seq1"][0].data, "998..7");
+
+        // Adjacent residue blocks the throw; all-gap column has nothing to throw
+        assert_eq!(alignment.throw_column(2, ShiftDirection::Left, &gaps), 0);
+        assert_eq!(alignment.throw_column(3, ShiftDirection::Right, &gaps), 0);
+    }
+
+    #[test]
+    fn test_move_block() {
+        let mut alignment = Alignment::new();
+        for (id, data) in [("a", "ACG...U"), ("b", "A.G...U"), ("c", "ACGU..U")] {
+            alignment.sequences.push(Rc::new(Sequence::new(id, data)));
+        }
+        alignment.residue_annotations.insert(
+            "a".to_string(),
+            vec![ResidueAnnotation {
+                tag: "PP".to_string(),
+                data: "987...6".to_string(),
+            }],
+        );
+        let gaps = ['.', '-'];
+
+        // Columns 1-2 of a and b can slide right by 2; c has a residue in the way
+        assert_eq!(alignment.block_move_conflict(&[0, 1], 1..3, 3, &gaps), None);
+        assert_eq!(
+            alignment.block_move_conflict(&[0, 2], 1..3, 3, &gaps),
+            Some(2)
+        );
+        assert_eq!(alignment.block_move_conflict(&[0], 1..3, 6, &gaps), Some(0));
+        // Jumping over a residue would reorder the sequence
+        assert_eq!(alignment.block_move_conflict(&[2], 1..3, 4, &gaps), Some(2));
+
+        alignment.move_block(&[0, 1], 1..3, 3, '.');
+        assert_eq!(alignment.sequences[0].data(), "A..CG.U");
+        assert_eq!(alignment.sequences[1].data(), "A...G.U");
+        assert_eq!(alignment.sequences[2].data(), "ACGU..U");
+        assert_eq!(alignment.residue_annotations["a"][0].data, "9..87.6");
+    }
+
+    #[test]
+    fn test_reorder_sequences() {
+        let mut alignment = Alignment::new();
+        for (id, data) in [("a", "AC"), ("b", "GU"), ("c", "UU")] {
+            alignment.sequences.push(Rc::new(Sequence::new(id, data)));
+        }
+        alignment.reorder_sequences(&[2, 0, 1]);
+        let ids: Vec<&str> = alignment.sequences.iter().map(|s| s.id.as_str()).collect();
+        assert_eq!(ids, ["c", "a", "b"]);
+        assert_eq!(alignment.sequences[0].data(), "UU");
+    }
+
+    #[test]
+    fn test_take_and_insert_sequence() {
+        let mut alignment = Alignment::new();
+        for (id, data) in [("a", "AC.GU"), ("b", "A.CGU"), ("c", "ACG.U")] {
+            alignment.sequences.push(Rc::new(Sequence::new(id, data)));
+        }
+        alignment.sequence_annotations.insert(
+            "a".to_string(),
+            vec![SequenceAnnotation {
+                tag: "DE".to_string(),
+                value: "first".to_string(),
+            }],
+        );
+        alignment.residue_annotations.insert(
+            "a".to_string(),
+            vec![ResidueAnnotation {
+                tag: "PP".to_string(),
+                data: "99.87".to_string(),
+            }],
+        );
+
+        let detached = alignment.take_sequence(0).unwrap();
+        assert_eq!(alignment.num_sequences(), 2);
+        assert!(alignment.sequence_annotations.is_empty());
+        assert!(alignment.residue_annotations.is_empty());
+        assert!(alignment.take_sequence(5).is_none());
+
+        let mut padded = detached.clone();
+        assert!(padded.pad_to(7, '~'));
+        assert_eq!(padded.sequence.data(), "AC.GU~~");
+        assert_eq!(padded.residue_annotations[0].data, "99.87..");
+        assert!(!padded.pad_to(5, '~'));
+
+        alignment.insert_sequence(2, detached);
+        assert_eq!(alignment.sequences[2].id, "a");
+        assert_eq!(alignment.sequence_annotations["a"][0].value, "first");
+        assert_eq!(alignment.residue_annotations["a"][0].data, "99.87");
+
+        let copy = alignment.copy_sequence(2).unwrap();
+        assert_eq!(alignment.sequences.len(), 3);
+        assert_eq!(copy.sequence.id, "a");
+        assert_eq!(copy.annotations[0].value, "first");
+        assert_eq!(copy.residue_annotations[0].data, "99.87");
+        assert!(alignment.copy_sequence(3).is_none());
+    }
+
+    #[test]
+    fn test_unused_id() {
+        let mut alignment = Alignment::new();
+        for id in ["a", "b/1-10", "b_copy/1-10"] {
+            alignment.sequences.push(Rc::new(Sequence::new(id, "ACGU")));
+        }
+        assert_eq!(alignment.unused_id("c"), "c");
+        assert_eq!(alignment.unused_id("a"), "a_copy");
+        assert_eq!(alignment.unused_id("b/1-10"), "b_copy2/1-10");
+        assert_eq!(alignment.unused_id("b_copy/1-10"), "b_copy2/1-10");
+    }
+
+    #[test]
+    fn test_fit_annotations_to_width() {
+        let mut alignment = Alignment::new();
+        alignment
+            .sequences
+            .push(Rc::new(Sequence::new("seq1", "ACGUA")));
+        alignment.column_annotations.push(ColumnAnnotation {
+            tag: "SS_cons".to_string(),
+            data: "<..>".to_string(),
+        });
+        alignment.column_annotations.push(ColumnAnnotation {
+            tag: "RF".to_string(),
+            data: "xxxxx".to_string(),
+        });
+        alignment.residue_annotations.insert(
+            "seq1".to_string(),
+            vec![ResidueAnnotation {
+                tag: "PP".to_string(),
+                data: "9999999".to_string(),
+            }],
+        );
+        assert!(alignment.is_valid());
+        assert_eq!(
+            alignment.annotation_length_mismatches(),
+            vec![
+                ("#=GC SS_cons".to_string(), 4),
+                ("#=GR seq1 PP".to_string(), 7)
+            ]
+        );
+
+        assert_eq!(alignment.fit_annotations_to_width(), 2);
+        assert_eq!(alignment.ss_cons(), Some("<..>."));
+        assert_eq!(alignment.residue_annotations["seq1"][0].data, "99999");
+        assert!(alignment.annotation_length_mismatches().is_empty());
+        assert_eq!(alignment.fit_annotations_to_width(), 0);
+    }
+
+    #[test]
+    fn test_width_problems() {
+        let mut alignment = Alignment::new();
+        assert!(alignment.width_problems(true).is_empty());
+        alignment
+            .sequences
+            .push(Rc::new(Sequence::new("seq1", "ACGU")));
+        alignment
+            .sequences
+            .push(Rc::new(Sequence::new("seq2", "ACG")));
+        alignment.column_annotations.push(ColumnAnnotation {
+            tag: "SS_cons".to_string(),
+            data: "<..>.".to_string(),
+        });
+        assert_eq!(alignment.width_problems(false), vec!["seq2 is 3 columns"]);
+        assert_eq!(
+            alignment.width_problems(true),
+            vec!["seq2 is 3 columns", "#=GC SS_cons is 5 columns"]
+        );
+    }
+
     #[test]
     fn test_alignment_width() {
         let mut alignment = Alignment::new();
@@ -565,6 +1769,22 @@ mod tests {
         assert_eq!(alignment.width(), 10);
     }
 
+    #[test]
+    fn test_pad_to_longest() {
+        let mut alignment = Alignment::new();
+        alignment.sequences = vec![
+            Rc::new(Sequence::new("a", "ACGU")),
+            Rc::new(Sequence::new("b", "ACGUAC")),
+            Rc::new(Sequence::new("c", "AC")),
+        ];
+        assert!(!alignment.is_valid());
+        assert_eq!(alignment.pad_to_longest('~'), 2);
+        assert!(alignment.is_valid());
+        assert_eq!(alignment.sequences[0].data(), "ACGU~~");
+        assert_eq!(alignment.sequences[2].data(), "AC~~~~");
+        assert_eq!(alignment.pad_to_longest('~'), 0);
+    }
+
     #[test]
     fn test_insert_gap_column() {
         let mut alignment = Alignment::new();
@@ -573,6 +1793,282 @@ mod tests {
             .push(Rc::new(Sequence::new("seq1", "ACGU")));
         alignment.insert_gap_column(2, '.');
         assert_eq!(alignment.sequences[0].data(), "AC.GU");
+
+        // RF labels the new column an insert state whatever the gap character
+        alignment.column_annotations.push(ColumnAnnotation {
+            tag: "RF".to_string(),
+            data: "xx.xx".to_string(),
+        });
+        alignment.insert_gap_column(1, '-');
+        assert_eq!(alignment.sequences[0].data(), "A-C.GU");
+        assert_eq!(alignment.rf(), Some("x.x.xx"));
+    }
+
+    #[test]
+    fn test_rf_insert_boundary() {
+        let gaps = ['.', '-'];
+        let mut alignment = Alignment::new();
+        alignment
+            .sequences
+            .push(Rc::new(Sequence::new("seq1", "ACgaGU")));
+        assert_eq!(alignment.rf_insert_boundary(2, &gaps), None);
+        alignment.column_annotations.push(ColumnAnnotation {
+            tag: "RF".to_string(),
+            data: "xx..xx".to_string(),
+        });
+        // Inside an insert run, the boundary is the next match column
+        assert_eq!(alignment.rf_insert_boundary(2, &gaps), Some(4));
+        assert_eq!(alignment.rf_insert_boundary(3, &gaps), Some(4));
+        // A match column is already on one
+        assert_eq!(alignment.rf_insert_boundary(1, &gaps), Some(1));
+        assert_eq!(alignment.rf_insert_boundary(9, &gaps), Some(6));
+        assert_eq!(alignment.rf_match_count(3, &gaps), Some(2));
+        assert_eq!(alignment.rf_match_count(0, &gaps), Some(0));
+    }
+
+    #[test]
+    fn test_sequence_flags() {
+        let mut alignment = Alignment::new();
+        alignment
+            .sequences
+            .push(Rc::new(Sequence::new("seq1", "ACGU")));
+        assert!(!alignment.has_sequence_flags());
+
+        alignment.set_sequence_flag("seq1", "suspicious");
+        assert_eq!(alignment.sequence_flag("seq1"), Some("suspicious"));
+        assert!(alignment.has_sequence_flags());
+
+        // Re-flagging replaces the label instead of adding a second annotation
+        alignment.set_sequence_flag("seq1", "chimera");
+        assert_eq!(alignment.sequence_annotations["seq1"].len(), 1);
+        assert_eq!(alignment.sequence_flag("seq1"), Some("chimera"));
+
+        assert!(alignment.clear_sequence_flag("seq1"));
+        assert!(!alignment.clear_sequence_flag("seq1"));
+        assert!(alignment.sequence_annotations.is_empty());
+    }
+
+    #[test]
+    fn test_end_gaps() {
+        let gaps = ['.', '-', '~'];
+        let mut seq = Sequence::new("test", "..AC~GU..");
+        assert_eq!(seq.residue_span(&gaps), Some((2, 6)));
+
+        // Internal end gap becomes a regular gap; terminal gap at pos becomes an end gap
+        seq.fix_end_gaps(0, '.', '~', &gaps);
+        assert_eq!(seq.data(), "~.AC.GU..");
+        seq.fix_end_gaps(8, '.', '~', &gaps);
+        assert_eq!(seq.data(), "~.AC.GU.~");
+
+        // Trailing gaps absorb growth; residues are never removed
+        let mut seq = Sequence::new("test", "A.CGU..");
+        seq.insert_gap(1, '.');
+        assert_eq!(seq.fit_trailing_gaps(7, '~', &gaps), -1);
+        assert_eq!(seq.data(), "A..CGU.");
+        let mut seq = Sequence::new("test", "A.CGU");
+        seq.insert_gap(1, '.');
+        assert_eq!(seq.fit_trailing_gaps(5, '~', &gaps), 0);
+        assert_eq!(seq.data(), "A..CGU");
+        assert_eq!(seq.fit_trailing_gaps(8, '~', &gaps), 2);
+        assert_eq!(seq.data(), "A..CGU~~");
+    }
+
+    #[test]
+    fn test_coords() {
+        assert_eq!(parse_coords("seq1/10-50"), Some(("seq1", 10, 50)));
+        assert_eq!(parse_coords("seq1"), None);
+        assert_eq!(parse_coords("seq1/a-b"), None);
+
+        assert_eq!(
+            adjust_coords("seq1/10-50", 3, -5),
+            Some("seq1/7-45".to_string())
+        );
+        // Reverse strand: 5' end is the larger coordinate
+        assert_eq!(
+            adjust_coords("seq1/50-10", 3, -5),
+            Some("seq1/53-15".to_string())
+        );
+        assert_eq!(adjust_coords("seq1/2-50", 3, 0), None);
+        assert_eq!(adjust_coords("seq1", 3, 0), None);
+
+        assert_eq!(coords_length("seq1/10-50"), Some(41));
+        assert_eq!(coords_length("seq1/50-10"), Some(41));
+        assert_eq!(coords_length("seq1"), None);
+        let seq = Sequence::new("seq1/3-6", "..AC-GU~");
+        assert_eq!(seq.ungapped_len(&['.', '-', '~']), 4);
+
+        assert_eq!(set_coords("seq1/3-6", 4, 9), "seq1/4-9");
+        assert_eq!(set_coords("seq1", 1, 5), "seq1/1-5");
+        let source = "ggACGTtccACGUaa";
+        assert_eq!(locate_coords("acgu", source, None), Some((3, 6)));
+        assert_eq!(locate_coords("ACGU", source, Some(9)), Some((10, 13)));
+        // Reverse complement of GGAA... (TTCC) is found on the minus strand
+        assert_eq!(locate_coords("GGAA", source, None), Some((9, 6)));
+        assert_eq!(locate_coords("CCCC", source, None), None);
+    }
+
+    #[test]
+    fn test_set_file_annotation() {
+        let mut alignment = Alignment::new();
+        alignment.set_file_annotation("GA", "40.00");
+        alignment.set_file_annotation("TC", "41.00");
+        alignment.set_file_annotation("GA", "38.50");
+        assert_eq!(alignment.file_annotations.len(), 2);
+        assert_eq!(alignment.get_file_annotation("GA"), Some("38.50"));
+    }
+
+    #[test]
+    fn test_block_move_cannot_jump_residues() {
+        let mut alignment = Alignment::new();
+        alignment
+            .sequences
+            .push(Rc::new(Sequence::new("a", "ACGU....")));
+        let gaps = ['.', '-'];
+        // CG would land past U at column 5, giving A..U.CG.
+        assert_eq!(alignment.block_move_conflict(&[0], 1..3, 5, &gaps), Some(0));
+        // Moving U left over the gaps is fine, and so is moving onto them
+        assert_eq!(alignment.block_move_conflict(&[0], 3..4, 6, &gaps), None);
+        assert_eq!(alignment.block_move_conflict(&[0], 3..4, 4, &gaps), None);
+    }
+
+    #[test]
+    fn test_gappy_columns_and_mask_columns() {
+        let mut alignment = Alignment::new();
+        for (id, data) in [("seq1", "AC-G."), ("seq2", "A--g."), ("seq3", "A-Cg-")] {
+            alignment.sequences.push(Rc::new(Sequence::new(id, data)));
+        }
+        let gaps = ['.', '-'];
+        assert_eq!(alignment.gappy_columns(0.5, &gaps), vec![1, 2, 4]);
+        assert_eq!(alignment.gappy_columns(0.7, &gaps), vec![4]);
+
+        let mut gapped = alignment.clone();
+        assert_eq!(
+            alignment.mask_columns(&[1, 2, 3], MaskStyle::Lowercase, '-', &gaps),
+            3
+        );
+        assert_eq!(alignment.sequences[0].data(), "Ac-g.");
+        assert_eq!(alignment.sequences[2].data(), "A-cg-");
+        assert_eq!(gapped.mask_columns(&[1], MaskStyle::Gap, '-', &gaps), 1);
+        assert_eq!(gapped.sequences[0].data(), "A--G.");
+    }
+
+    #[test]
+    fn test_remove_columns() {
+        let mut alignment = Alignment::new();
+        alignment
+            .sequences
+            .push(Rc::new(Sequence::new("seq1", "ACGUA")));
+        alignment
+            .sequences
+            .push(Rc::new(Sequence::new("seq2", "A-GU-")));
+        alignment.column_annotations.push(ColumnAnnotation {
+            tag: "SS_cons".to_string(),
+            data: "<.:.>".to_string(),
+        });
+        alignment
+            .residue_annotations
+            .entry("seq1".to_string())
+            .or_default()
+            .push(ResidueAnnotation {
+                tag: "PP".to_string(),
+                data: "12345".to_string(),
+            });
+
+        alignment.remove_columns(&[1, 4, 9]);
+        assert_eq!(alignment.sequences[0].data(), "AGU");
+        assert_eq!(alignment.sequences[1].data(), "AGU");
+        assert_eq!(alignment.ss_cons(), Some("<:."));
+        assert_eq!(alignment.residue_annotations["seq1"][0].data, "134");
+    }
+
+    #[test]
+    fn test_select_rows() {
+        let mut alignment = Alignment::new();
+        for id in ["seq1", "seq2", "seq3"] {
+            alignment.sequences.push(Rc::new(Sequence::new(id, "ACGU")));
+        }
+        alignment.set_sequence_flag("seq1", "check");
+        alignment.set_sequence_flag("seq2", "check");
+
+        let subset = alignment.select_rows(&[2, 1]);
+        let ids: Vec<&str> = subset.sequences.iter().map(|s| s.id.as_str()).collect();
+        assert_eq!(ids, ["seq3", "seq2"]);
+        assert!(subset.sequence_annotations.contains_key("seq2"));
+        assert!(!subset.sequence_annotations.contains_key("seq1"));
+    }
+
+    #[test]
+    fn test_select_region() {
+        let mut alignment = Alignment::new();
+        for (id, data) in [
+            ("a/11-18", "AC.GUA.CGU"),
+            ("b/30-23", "ACG-UACGU."),
+            ("c", ".........."),
+        ] {
+            alignment.sequences.push(Rc::new(Sequence::new(id, data)));
+        }
+        alignment.residue_annotations.insert(
+            "a/11-18".to_string(),
+            vec![ResidueAnnotation {
+                tag: "PP".to_string(),
+                data: "99.999.999".to_string(),
+            }],
+        );
+        alignment.set_sequence_flag("a/11-18", "check");
+        alignment.column_annotations.push(ColumnAnnotation {
+            tag: "RF".to_string(),
+            data: "xxxxxxxxxx".to_string(),
+        });
+
+        let region = alignment.select_region(&[0, 1, 2], 3..7, &['.', '-']);
+        let ids: Vec<&str> = region.sequences.iter().map(|s| s.id.as_str()).collect();
+        // a keeps residues 13-15; b is reverse strand and keeps 27-25
+        assert_eq!(ids, ["a/13-15", "b/27-25", "c"]);
+        assert_eq!(region.sequences[0].data(), "GUA.");
+        assert_eq!(region.sequences[1].data(), "-UAC");
+        assert_eq!(region.residue_annotations["a/13-15"][0].data, "999.");
+        assert_eq!(region.sequence_flag("a/13-15"), Some("check"));
+        assert_eq!(region.rf(), Some("xxxx"));
+
+        // Rows are chosen too
+        let region = alignment.select_region(&[1], 0..2, &['.', '-']);
+        assert_eq!(region.num_sequences(), 1);
+        assert_eq!(region.sequences[0].id, "b/30-29");
+    }
+
+    #[test]
+    fn test_rename_sequence() {
+        let mut alignment = Alignment::new();
+        alignment
+            .sequences
+            .push(Rc::new(Sequence::new("seq1/1-4", "ACGU")));
+        alignment.set_sequence_flag("seq1/1-4", "check");
+
+        alignment.rename_sequence(0, "seq1/2-4");
+        assert_eq!(alignment.sequences[0].id, "seq1/2-4");
+        assert_eq!(alignment.sequence_flag("seq1/2-4"), Some("check"));
+        assert!(!alignment.sequence_annotations.contains_key("seq1/1-4"));
+    }
+
+    #[test]
+    fn test_numeric_track() {
+        assert_eq!(track_level('0'), Some(0));
+        assert_eq!(track_level('7'), Some(7));
+        assert_eq!(track_level('*'), Some(TRACK_MAX));
+        assert_eq!(track_level('.'), None);
+        for level in 0..=TRACK_MAX {
+            assert_eq!(track_level(track_char(level)), Some(level));
+        }
+
+        let mut alignment = Alignment::new();
+        alignment.column_annotations.push(ColumnAnnotation {
+            tag: "SCORE".to_string(),
+            data: "12".to_string(),
+        });
+        assert!(alignment.set_column_annotation_char("SCORE", 0, '9'));
+        assert!(alignment.set_column_annotation_char("SCORE", 3, '*'));
+        assert_eq!(alignment.column_annotation("SCORE"), Some("92.*"));
+        assert!(!alignment.set_column_annotation_char("MASK", 0, '1'));
     }
 
     #[test]
@@ -588,4 +2084,84 @@ mod tests {
         assert!(seq.shift_right(2, &['.']));
         assert_eq!(seq.data(), "AC.GU");
     }
+
+    #[test]
+    fn test_mask_low_pp() {
+        let mut alignment = Alignment::new();
+        alignment.sequences = vec![
+            Rc::new(Sequence::new("seq1", "ACG-U")),
+            Rc::new(Sequence::new("seq2", "ACGUU")),
+        ];
+        alignment.residue_annotations.insert(
+            "seq1".to_string(),
+            vec![ResidueAnnotation {
+                tag: "PP".to_string(),
+                data: "9*3.6".to_string(),
+            }],
+        );
+        let gap_chars = ['.', '-'];
+
+        let mut lowered = alignment.clone();
+        assert_eq!(
+            lowered.mask_low_pp(7, MaskStyle::Lowercase, '-', &gap_chars),
+            2
+        );
+        assert_eq!(lowered.sequences[0].data(), "ACg-u");
+        // Sequences without PP are untouched
+        assert_eq!(lowered.sequences[1].data(), "ACGUU");
+        // Already masked residues don't count again
+        assert_eq!(
+            lowered.mask_low_pp(7, MaskStyle::Lowercase, '-', &gap_chars),
+            0
+        );
+
+        assert_eq!(alignment.mask_low_pp(4, MaskStyle::Gap, '.', &gap_chars), 1);
+        assert_eq!(alignment.sequences[0].data(), "AC.-U");
+        assert_eq!(alignment.residue_annotations["seq1"][0].data, "9*..6");
+
+        // A region leaves residues outside it alone
+        let mut region = lowered.clone();
+        region.sequences[0] = Rc::new(Sequence::new("seq1", "ACG-U"));
+        assert_eq!(
+            region.mask_low_pp_region(&[0], 3..5, 7, MaskStyle::Lowercase, '-', &gap_chars),
+            1
+        );
+        assert_eq!(region.sequences[0].data(), "ACG-u");
+    }
+
+    #[test]
+    fn test_push_gaps_and_pack_left() {
+        let mut alignment = Alignment::new();
+        alignment.sequences = vec![
+            Rc::new(Sequence::new("seq1", "AC..GU.U")),
+            Rc::new(Sequence::new("seq2", "ACGUACGU")),
+        ];
+        alignment.residue_annotations.insert(
+            "seq1".to_string(),
+            vec![ResidueAnnotation {
+                tag: "SS".to_string(),
+                data: "<<..>>.>".to_string(),
+            }],
+        );
+        let gap_chars = ['.', '-'];
+
+        // seq1 has gaps to absorb; seq2 has none, so two columns are added
+        let mut pushed = alignment.clone();
+        assert_eq!(pushed.push_gaps(&[0, 1], 1, 2, '.', &gap_chars), 2);
+        assert_eq!(pushed.sequences[0].data(), "A..CGU.U..");
+        assert_eq!(pushed.sequences[1].data(), "A..CGUACGU");
+        assert_eq!(pushed.residue_annotations["seq1"][0].data, "<..<>>.>..");
+
+        // Only the given rows move
+        let mut pushed = alignment.clone();
+        assert_eq!(pushed.push_gaps(&[0], 0, 1, '.', &gap_chars), 0);
+        assert_eq!(pushed.sequences[0].data(), ".AC.GU.U");
+        assert_eq!(pushed.sequences[1].data(), "ACGUACGU");
+
+        assert_eq!(alignment.pack_left(&[0, 1], 1..7, &gap_chars), 1);
+        assert_eq!(alignment.sequences[0].data(), "ACGU...U");
+        assert_eq!(alignment.sequences[1].data(), "ACGUACGU");
+        assert_eq!(alignment.residue_annotations["seq1"][0].data, "<<>>...>");
+        assert_eq!(alignment.pack_left(&[0], 1..7, &gap_chars), 0);
+    }
 }
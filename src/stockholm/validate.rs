@@ -0,0 +1,326 @@
+//! Line-level checks of Stockholm files (`aform validate`).
+//!
+//! The parser concatenates rows across blocks and only reports that lengths
+//! differ; this walks the text itself so each problem points at a line.
+
+use std::collections::HashMap;
+
+use super::parser::id_tag_data;
+use crate::structure::{StructureCache, StructureError};
+
+/// A problem found in a file, at a 1-based line number.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Problem {
+    pub line: usize,
+    pub message: String,
+}
+
+impl Problem {
+    fn new(line: usize, message: impl Into<String>) -> Self {
+        Self {
+            line,
+            message: message.into(),
+        }
+    }
+}
+
+/// A sequence, #=GC or #=GR row accumulated across blocks.
+struct Row {
+    label: String,
+    first_line: usize,
+    length: usize,
+    /// Whether a length problem was already reported in one of its blocks.
+    reported: bool,
+}
+
+/// State of the record being checked.
+#[derive(Default)]
+struct Record {
+    rows: Vec<Row>,
+    index: HashMap<String, usize>,
+    /// Sequence IDs seen in the current block, with their lines.
+    block_ids: HashMap<String, usize>,
+    /// Width and line of the first data line of the current block.
+    block_width: Option<(usize, usize)>,
+    sequence_ids: Vec<String>,
+    /// #=GR rows, as (sequence ID, line).
+    residue_rows: Vec<(String, usize)>,
+    /// SS_cons data and the line each piece came from, with its first column.
+    ss_cons: String,
+    ss_cons_lines: Vec<(usize, usize)>,
+}
+
+impl Record {
+    /// Add a data line's residues to its row, checking the width against the
+    /// block's first line.
+    fn add(
+        &mut self,
+        key: String,
+        label: String,
+        line: usize,
+        length: usize,
+        problems: &mut Vec<Problem>,
+    ) {
+        let i = *self.index.entry(key).or_insert_with(|| {
+            self.rows.push(Row {
+                label,
+                first_line: line,
+                length: 0,
+                reported: false,
+            });
+            self.rows.len() - 1
+        });
+        let row = &mut self.rows[i];
+        row.length += length;
+        match self.block_width {
+            None => self.block_width = Some((length, line)),
+            Some((width, first)) if width != length && !row.reported => {
+                problems.push(Problem::new(
+                    line,
+                    format!(
+                        "{} has {length} columns in this block, expected {width} as on line {first}",
+                        row.label
+                    ),
+                ));
+                row.reported = true;
+            }
+            Some(_) => {}
+        }
+    }
+
+    fn end_block(&mut self) {
+        self.block_ids.clear();
+        self.block_width = None;
+    }
+
+    /// Checks that need the whole record: total widths, SS_cons brackets and
+    /// #=GR rows of missing sequences.
+    fn finish(self, end_line: usize, problems: &mut Vec<Problem>) {
+        let Some(first) = self.sequence_ids.first() else {
+            problems.push(Problem::new(end_line, "alignment has no sequences"));
+            return;
+        };
+        let width = self.rows[self.index[&format!("seq {first}")]].length;
+        for row in &self.rows {
+            if row.length != width && !row.reported {
+                problems.push(Problem::new(
+                    row.first_line,
+                    format!(
+                        "{} has {} columns, expected {width} as {first}",
+                        row.label, row.length
+                    ),
+                ));
+            }
+        }
+
+        let mut cache = StructureCache::new();
+        if let Err(e) = cache.update(&self.ss_cons) {
+            let (position, kind) = match e {
+                StructureError::UnmatchedOpen(p) => (p, "opening"),
+                StructureError::UnmatchedClose(p) | StructureError::BracketMismatch(p) => {
+                    (p, "closing")
+                }
+            };
+            let line = self
+                .ss_cons_lines
+                .iter()
+                .rev()
+                .find(|(_, start)| *start <= position)
+                .map_or(end_line, |(line, _)| *line);
+            let bracket = self.ss_cons.chars().nth(position).unwrap_or('?');
+            problems.push(Problem::new(
+                line,
+                format!(
+                    "#=GC SS_cons has an unmatched {kind} bracket '{bracket}' at column {}",
+                    position + 1
+                ),
+            ));
+        }
+
+        for (id, line) in &self.residue_rows {
+            if !self.sequence_ids.contains(id) {
+                problems.push(Problem::new(
+                    *line,
+                    format!("#=GR for unknown sequence {id}"),
+                ));
+            }
+        }
+    }
+}
+
+/// Check every alignment in a Stockholm file for rows of different lengths,
+/// duplicate sequence IDs, unbalanced SS_cons brackets and a missing header or
+/// `//`, returning the problems in line order.
+pub fn validate(text: &str) -> Vec<Problem> {
+    let mut problems = Vec::new();
+    let mut record: Option<Record> = None;
+    let mut last_line = 0;
+
+    for (i, raw) in text.lines().enumerate() {
+        let line_number = i + 1;
+        last_line = line_number;
+        let line = raw.trim_end_matches('\r');
+
+        if line.trim().is_empty() {
+            if let Some(record) = &mut record {
+                record.end_block();
+            }
+            continue;
+        }
+        let Some(current) = &mut record else {
+            // Outside a record only a header may start the next one
+            let mut current = Record::default();
+            if !line.starts_with("# STOCKHOLM") {
+                problems.push(Problem::new(line_number, "missing # STOCKHOLM 1.0 header"));
+                check_line(&mut current, line, line_number, &mut problems);
+            }
+            record = Some(current);
+            continue;
+        };
+        if line.starts_with("//") {
+            if let Some(done) = record.take() {
+                done.finish(line_number, &mut problems);
+            }
+            continue;
+        }
+        check_line(current, line, line_number, &mut problems);
+    }
+
+    if let Some(unfinished) = record {
+        problems.push(Problem::new(last_line, "alignment does not end with //"));
+        unfinished.finish(last_line, &mut problems);
+    }
+    if last_line == 0 {
+        problems.push(Problem::new(1, "file is empty"));
+    }
+    problems.sort_by_key(|p| p.line);
+    problems
+}
+
+/// Check one non-blank line inside a record.
+fn check_line(record: &mut Record, line: &str, number: usize, problems: &mut Vec<Problem>) {
+    if let Some(rest) = line.strip_prefix("#=GC") {
+        let Some((tag, data)) = rest.trim().split_once(char::is_whitespace) else {
+            problems.push(Problem::new(number, "#=GC line has no data"));
+            return;
+        };
+        let data = data.trim();
+        if tag == "SS_cons" {
+            record
+                .ss_cons_lines
+                .push((number, record.ss_cons.chars().count()));
+            record.ss_cons.push_str(data);
+        }
+        let length = data.chars().count();
+        record.add(
+            format!("GC {tag}"),
+            format!("#=GC {tag}"),
+            number,
+            length,
+            problems,
+        );
+    } else if let Some(rest) = line.strip_prefix("#=GR") {
+        let Some((id, tag, data)) = id_tag_data(rest) else {
+            problems.push(Problem::new(number, "#=GR line has no data"));
+            return;
+        };
+        record.residue_rows.push((id.to_string(), number));
+        let length = data.chars().count();
+        record.add(
+            format!("GR {id} {tag}"),
+            format!("#=GR {id} {tag}"),
+            number,
+            length,
+            problems,
+        );
+    } else if line.starts_with('#') {
+        // Comments, #=GF and #=GS lines
+    } else {
+        let (id, data) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+        let data: String = data.chars().filter(|c| !c.is_whitespace()).collect();
+        if data.is_empty() {
+            problems.push(Problem::new(
+                number,
+                format!("sequence {id} has no residues"),
+            ));
+            return;
+        }
+        if let Some(first) = record.block_ids.insert(id.to_string(), number) {
+            problems.push(Problem::new(
+                number,
+                format!("duplicate sequence ID {id} (also on line {first})"),
+            ));
+            return;
+        }
+        if !record.sequence_ids.iter().any(|s| s == id) {
+            record.sequence_ids.push(id.to_string());
+        }
+        let length = data.chars().count();
+        record.add(
+            format!("seq {id}"),
+            format!("sequence {id}"),
+            number,
+            length,
+            problems,
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_validate_clean() {
+        let text = "\
+# STOCKHOLM 1.0
+#=GF ID test
+
+seq1  ACGU
+seq2  AC-U
+#=GC SS_cons  (..)
+
+seq1  AAA
+seq2  A.A
+#=GC SS_cons  ...
+//
+";
+        assert!(validate(text).is_empty());
+    }
+
+    #[test]
+    fn test_validate_problems() {
+        let text = "\
+# STOCKHOLM 1.0
+seq1  ACGUAC
+seq2  ACGU
+seq1  ACGUAC
+#=GR seq3 PP  999999
+#=GC SS_cons  ((..).
+//
+seq1 ACGU
+";
+        let problems = validate(text);
+        let found: Vec<(usize, &str)> = problems
+            .iter()
+            .map(|p| (p.line, p.message.as_str()))
+            .collect();
+        assert_eq!(
+            found,
+            vec![
+                (
+                    3,
+                    "sequence seq2 has 4 columns in this block, expected 6 as on line 2"
+                ),
+                (4, "duplicate sequence ID seq1 (also on line 2)"),
+                (5, "#=GR for unknown sequence seq3"),
+                (
+                    6,
+                    "#=GC SS_cons has an unmatched opening bracket '(' at column 1"
+                ),
+                (8, "missing # STOCKHOLM 1.0 header"),
+                (8, "alignment does not end with //"),
+            ]
+        );
+    }
+}
@@ -1,14 +1,185 @@
 //! Stockholm format writer.
 
+use std::collections::HashMap;
+use std::hash::{DefaultHasher, Hash, Hasher};
 use std::io::{Result, Write};
 
+use super::parser;
 use super::types::*;
 
-/// Write a Stockholm format alignment to a writer.
-pub fn write<W: Write>(alignment: &Alignment, mut writer: W) -> Result<()> {
+/// Layout variant of written Stockholm files.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum StockholmStyle {
+    /// Classic Stockholm: the original header, blank lines between sections, and
+    /// interleaved blocks with `wrap`.
+    #[default]
+    Stockholm,
+    /// Pfam style: one line per sequence, no blank lines, and every sequence,
+    /// #=GR and #=GC line padded so the alignment starts in the same column.
+    Pfam,
+}
+
+impl StockholmStyle {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_lowercase().as_str() {
+            "stockholm" | "sto" | "classic" => Some(Self::Stockholm),
+            "pfam" => Some(Self::Pfam),
+            _ => None,
+        }
+    }
+}
+
+/// Write a Stockholm format alignment to a writer, one line per sequence.
+pub fn write<W: Write>(alignment: &Alignment, writer: W) -> Result<()> {
+    write_wrapped(alignment, None, writer)
+}
+
+/// Write an alignment in the given style (`wrap` only applies to classic Stockholm).
+pub fn write_styled<W: Write>(
+    alignment: &Alignment,
+    style: StockholmStyle,
+    wrap: Option<usize>,
+    writer: W,
+) -> Result<()> {
+    match style {
+        StockholmStyle::Stockholm => write_wrapped(alignment, wrap, writer),
+        StockholmStyle::Pfam => write_pfam(alignment, writer),
+    }
+}
+
+/// Write several alignments one after the other, as in a multi-record file,
+/// each after the blank lines it was read with.
+pub fn write_records<W: Write>(
+    alignments: &[&Alignment],
+    style: StockholmStyle,
+    wrap: Option<usize>,
+    mut writer: W,
+) -> Result<()> {
+    for alignment in alignments {
+        if let Some(source) = &alignment.source {
+            writer.write_all(source.separator.as_bytes())?;
+        }
+        write_styled(alignment, style, wrap, &mut writer)?;
+    }
+    Ok(())
+}
+
+/// Characters `range` of `data`, clamped to its length.
+fn columns(data: &str, range: std::ops::Range<usize>) -> &str {
+    let mut indices = data.char_indices().map(|(i, _)| i).chain([data.len()]);
+    let start = indices.nth(range.start).unwrap_or(data.len());
+    let end = indices
+        .nth(range.end - range.start - 1)
+        .unwrap_or(data.len());
+    &data[start..end]
+}
+
+/// Write a Stockholm format alignment to a writer. With `wrap`, the alignment is
+/// written in interleaved blocks of that many columns (as Rfam does), each block
+/// repeating the sequence, #=GR and #=GC lines.
+///
+/// A parsed alignment keeps its header comments and #=GF/#=GS line order, and is
+/// written back byte for byte if it has not been edited (and is not rewrapped).
+pub fn write_wrapped<W: Write>(
+    alignment: &Alignment,
+    wrap: Option<usize>,
+    mut writer: W,
+) -> Result<()> {
+    let Some(source) = &alignment.source else {
+        return generate(alignment, &[], wrap, writer);
+    };
+    let mut buffer = Vec::new();
+    generate(alignment, &source.header, wrap, &mut buffer)?;
+    match &source.text {
+        Some(text) if wrap.is_none() && fingerprint(&buffer) == source.generated => {
+            writer.write_all(text.as_bytes())
+        }
+        _ => writer.write_all(&buffer),
+    }
+}
+
+/// Fingerprint of writer output, to tell whether an alignment still writes what
+/// it did when parsed without keeping a copy of that output.
+pub(super) fn fingerprint(output: &[u8]) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    output.hash(&mut hasher);
+    hasher.finish()
+}
+
+/// Write the alignment, laying out the annotation header after `header` (the
+/// original header lines, if any).
+pub(super) fn generate<W: Write>(
+    alignment: &Alignment,
+    header: &[String],
+    wrap: Option<usize>,
+    mut writer: W,
+) -> Result<()> {
     // Header
     writeln!(writer, "# STOCKHOLM 1.0")?;
 
+    // Calculate padding for alignment
+    let max_id_len = alignment.max_id_len();
+    let padding = max_id_len.max(10);
+
+    if header.is_empty() {
+        write_annotations(alignment, padding, &mut writer)?;
+    } else {
+        write_original_header(alignment, header, padding, &mut writer)?;
+    }
+
+    let width = alignment.width();
+    let block_width = wrap.filter(|&w| w > 0 && w < width).unwrap_or(width.max(1));
+    let rows: Vec<String> = alignment.sequences.iter().map(|seq| seq.data()).collect();
+
+    for start in (0..width.max(1)).step_by(block_width) {
+        let block = start..(start + block_width);
+        if start > 0 {
+            writeln!(writer)?;
+        }
+
+        // Sequences and their residue annotations (#=GR)
+        for (seq, data) in alignment.sequences.iter().zip(&rows) {
+            writeln!(
+                writer,
+                "{:padding$} {}",
+                seq.id,
+                columns(data, block.clone())
+            )?;
+
+            // Per-residue annotations for this sequence
+            if let Some(annotations) = alignment.residue_annotations.get(&seq.id) {
+                for ann in annotations {
+                    writeln!(
+                        writer,
+                        "#=GR {:padding$} {} {}",
+                        seq.id,
+                        ann.tag,
+                        columns(&ann.data, block.clone())
+                    )?;
+                }
+            }
+        }
+
+        // Column annotations (#=GC)
+        for ann in &alignment.column_annotations {
+            writeln!(
+                writer,
+                "#=GC {:padding$} {}",
+                ann.tag,
+                columns(&ann.data, block.clone())
+            )?;
+        }
+    }
+
+    // Terminator
+    writeln!(writer, "//")?;
+
+    Ok(())
+}
+
+/// Write #=GF then #=GS annotations (grouped by sequence), each followed by a
+/// blank line.
+fn write_annotations<W: Write>(alignment: &Alignment, padding: usize, mut writer: W) -> Result<()> {
     // File annotations (#=GF)
     for ann in &alignment.file_annotations {
         writeln!(writer, "#=GF {} {}", ann.tag, ann.value)?;
@@ -18,10 +189,6 @@ pub fn write<W: Write>(alignment: &Alignment, mut writer: W) -> Result<()> {
         writeln!(writer)?;
     }
 
-    // Calculate padding for alignment
-    let max_id_len = alignment.max_id_len();
-    let padding = max_id_len.max(10);
-
     // Sequence annotations (#=GS) - group by sequence
     for seq in &alignment.sequences {
         if let Some(annotations) = alignment.sequence_annotations.get(&seq.id) {
@@ -34,27 +201,149 @@ pub fn write<W: Write>(alignment: &Alignment, mut writer: W) -> Result<()> {
     if !alignment.sequence_annotations.is_empty() {
         writeln!(writer)?;
     }
+    Ok(())
+}
 
-    // Sequences and their residue annotations (#=GR)
-    for seq in &alignment.sequences {
-        writeln!(writer, "{:padding$} {}", seq.id, seq.data())?;
+/// Write the original header lines as they were, dropping #=GF/#=GS lines whose
+/// annotation is gone, then any annotations added since.
+fn write_original_header<W: Write>(
+    alignment: &Alignment,
+    header: &[String],
+    padding: usize,
+    mut writer: W,
+) -> Result<()> {
+    let mut gf_written = vec![false; alignment.file_annotations.len()];
+    let mut gs_written: HashMap<&str, Vec<bool>> = HashMap::new();
 
-        // Per-residue annotations for this sequence
-        if let Some(annotations) = alignment.residue_annotations.get(&seq.id) {
-            for ann in annotations {
-                writeln!(writer, "#=GR {:padding$} {} {}", seq.id, ann.tag, ann.data)?;
+    for line in header {
+        if let Some(rest) = line.strip_prefix("#=GF") {
+            let (tag, value) = parser::tag_value(rest);
+            let found = (0..gf_written.len()).find(|&i| {
+                let ann = &alignment.file_annotations[i];
+                !gf_written[i] && ann.tag == tag && ann.value == value
+            });
+            if let Some(i) = found {
+                gf_written[i] = true;
+                writeln!(writer, "{line}")?;
             }
+        } else if let Some(rest) = line.strip_prefix("#=GS") {
+            let Some((id, tag, value)) = parser::id_tag_data(rest) else {
+                continue;
+            };
+            let Some(annotations) = alignment.sequence_annotations.get(id) else {
+                continue;
+            };
+            let written = gs_written
+                .entry(id)
+                .or_insert_with(|| vec![false; annotations.len()]);
+            let found = (0..annotations.len()).find(|&i| {
+                !written[i] && annotations[i].tag == tag && annotations[i].value == value
+            });
+            if let Some(i) = found {
+                written[i] = true;
+                writeln!(writer, "{line}")?;
+            }
+        } else {
+            // Comment or blank line
+            writeln!(writer, "{line}")?;
         }
     }
 
-    // Column annotations (#=GC)
-    for ann in &alignment.column_annotations {
-        writeln!(writer, "#=GC {:padding$} {}", ann.tag, ann.data)?;
+    // Annotations added since the file was read
+    let mut added = false;
+    for (ann, written) in alignment.file_annotations.iter().zip(gf_written) {
+        if !written {
+            writeln!(writer, "#=GF {} {}", ann.tag, ann.value)?;
+            added = true;
+        }
     }
+    for seq in &alignment.sequences {
+        let Some(annotations) = alignment.sequence_annotations.get(&seq.id) else {
+            continue;
+        };
+        let written = gs_written.get(seq.id.as_str());
+        for (i, ann) in annotations.iter().enumerate() {
+            if !written.is_some_and(|w| w[i]) {
+                writeln!(writer, "#=GS {:padding$} {} {}", seq.id, ann.tag, ann.value)?;
+                added = true;
+            }
+        }
+    }
+    if added {
+        writeln!(writer)?;
+    }
+    Ok(())
+}
 
-    // Terminator
-    writeln!(writer, "//")?;
+/// Write an alignment in Pfam style: #=GF and #=GS lines, then each sequence
+/// followed by its #=GR lines, then the #=GC lines, with no blank lines.
+pub fn write_pfam<W: Write>(alignment: &Alignment, mut writer: W) -> Result<()> {
+    let name_width = alignment.max_id_len();
+    let gs_tag_width = alignment
+        .sequence_annotations
+        .values()
+        .flatten()
+        .map(|a| a.tag.len())
+        .max()
+        .unwrap_or(0);
+    let gr_tag_width = alignment
+        .residue_annotations
+        .values()
+        .flatten()
+        .map(|a| a.tag.len())
+        .max()
+        .unwrap_or(0);
+    let gc_tag_width = alignment
+        .column_annotations
+        .iter()
+        .map(|a| a.tag.len())
+        .max()
+        .unwrap_or(0);
 
+    // Every aligned line starts its data at the same column
+    let mut margin = name_width + 1;
+    if gr_tag_width > 0 {
+        margin = margin.max(name_width + gr_tag_width + 7);
+    }
+    if gc_tag_width > 0 {
+        margin = margin.max(gc_tag_width + 6);
+    }
+
+    writeln!(writer, "# STOCKHOLM 1.0")?;
+    for ann in &alignment.file_annotations {
+        writeln!(writer, "#=GF {} {}", ann.tag, ann.value)?;
+    }
+    for seq in &alignment.sequences {
+        for ann in alignment
+            .sequence_annotations
+            .get(&seq.id)
+            .into_iter()
+            .flatten()
+        {
+            writeln!(
+                writer,
+                "#=GS {:name_width$} {:gs_tag_width$} {}",
+                seq.id, ann.tag, ann.value
+            )?;
+        }
+    }
+    for seq in &alignment.sequences {
+        writeln!(writer, "{:margin$}{}", seq.id, seq.data())?;
+        for ann in alignment
+            .residue_annotations
+            .get(&seq.id)
+            .into_iter()
+            .flatten()
+        {
+            let label = format!("#=GR {:name_width$} {}", seq.id, ann.tag);
+            writeln!(writer, "{label:margin$}{}", ann.data)?;
+        }
+    }
+    for ann in &alignment.column_annotations {
+        let label = format!("#=GC {}", ann.tag);
+        writeln!(writer, "{label:margin$}{}", ann.data)?;
+    }
+    writeln!(writer, "//")?;
     Ok(())
 }
 
@@ -66,12 +355,6 @@ pub fn write_string(alignment: &Alignment) -> Result<String> {
     Ok(String::from_utf8_lossy(&buffer).to_string())
 }
 
-/// Write a Stockholm alignment to a file.
-pub fn write_file(alignment: &Alignment, path: &std::path::Path) -> Result<()> {
-    let file = std::fs::File::create(path)?;
-    write(alignment, file)
-}
-
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -101,6 +384,134 @@ seq2/1-10  ACGU..ACGU
         assert_eq!(alignment.ss_cons(), reparsed.ss_cons());
     }
 
+    #[test]
+    fn test_write_wrapped() {
+        let input = "# STOCKHOLM 1.0
+seq1/1-10  ACGU..ACGU
+#=GR seq1/1-10 PP 9999..9999
+seq2/1-10  ACGU..ACGU
+#=GC SS_cons   <<<<..>>>>
+//
+";
+        let alignment = parser::parse_str(input).unwrap();
+        let mut buffer = Vec::new();
+        write_wrapped(&alignment, Some(4), &mut buffer).unwrap();
+        let output = String::from_utf8(buffer).unwrap();
+
+        let blocks: Vec<&str> = output.split("\n\n").collect();
+        assert_eq!(blocks.len(), 3);
+        assert!(blocks[0].contains("seq1/1-10  ACGU\n#=GR seq1/1-10  PP 9999\n"));
+        assert!(blocks[1].ends_with("#=GC SS_cons    ..>>"));
+        assert!(blocks[2].contains("seq2/1-10  GU\n"));
+        assert!(blocks[2].ends_with("#=GC SS_cons    >>\n//\n"));
+
+        let reparsed = parser::parse_str(&output).unwrap();
+        assert_eq!(reparsed.sequences[0].data(), "ACGU..ACGU");
+        assert_eq!(
+            reparsed.residue_annotations["seq1/1-10"][0].data,
+            "9999..9999"
+        );
+        assert_eq!(reparsed.ss_cons(), Some("<<<<..>>>>"));
+
+        // A wrap wider than the alignment writes a single block
+        let mut buffer = Vec::new();
+        write_wrapped(&alignment, Some(80), &mut buffer).unwrap();
+        let mut generated = Vec::new();
+        generate(&alignment, &[], None, &mut generated).unwrap();
+        assert_eq!(buffer, generated);
+    }
+
+    #[test]
+    fn test_write_pfam() {
+        let input = "# STOCKHOLM 1.0
+# A comment
+#=GF ID test
+
+#=GS seq1/1-10 AC P12345.1
+#=GS s2 DE second
+
+seq1/1-10  ACGU..ACGU
+s2         ACGU..ACGU
+#=GR s2 PP 9999..9999
+#=GC SS_cons   <<<<..>>>>
+//
+";
+        let alignment = parser::parse_str(input).unwrap();
+        let mut buffer = Vec::new();
+        write_styled(&alignment, StockholmStyle::Pfam, Some(4), &mut buffer).unwrap();
+        let output = String::from_utf8(buffer).unwrap();
+        assert_eq!(
+            output,
+            "# STOCKHOLM 1.0
+#=GF ID test
+#=GS seq1/1-10 AC P12345.1
+#=GS s2        DE second
+seq1/1-10         ACGU..ACGU
+s2                ACGU..ACGU
+#=GR s2        PP 9999..9999
+#=GC SS_cons      <<<<..>>>>
+//
+"
+        );
+        assert_eq!(
+            parser::parse_str(&output).unwrap().sequences[1].data(),
+            "ACGU..ACGU"
+        );
+    }
+
+    #[test]
+    fn test_unedited_roundtrip_is_exact() {
+        let input = "# STOCKHOLM 1.0\r
+# A comment\r
+#=GF ID   tRNA\r
+#=GF AU   Someone\r
+\r
+#=GS seq2/1-4  AC  B\r
+#=GS seq1/1-4  AC  A\r
+#=GS seq1/1-4  DE  first\r
+\r
+seq1/1-4   AC.GU\r
+seq2/1-4   A.CGU\r
+#=GC SS_cons     <...>\r
+#=GC RF          xx.xx\r
+//\r
+";
+        let alignment = parser::parse_str(input).unwrap();
+        assert_eq!(write_string(&alignment).unwrap(), input);
+
+        // A file as the writer lays it out keeps no copy of its text
+        let mut plain = alignment.clone();
+        plain.source = None;
+        let canonical = write_string(&plain).unwrap();
+        let reparsed = parser::parse_str(&canonical).unwrap();
+        assert!(reparsed.source.as_ref().unwrap().text.is_none());
+        assert_eq!(write_string(&reparsed).unwrap(), canonical);
+
+        // After an edit the header lines stay as they were, minus removed records
+        let mut edited = alignment.clone();
+        Rc::make_mut(&mut edited.sequences[0]).set(2, '-');
+        edited.file_annotations.remove(1);
+        edited
+            .sequence_annotations
+            .get_mut("seq1/1-4")
+            .unwrap()
+            .push(SequenceAnnotation {
+                tag: "FL".to_string(),
+                value: "check".to_string(),
+            });
+        let output = write_string(&edited).unwrap();
+        assert!(
+            output.starts_with(
+                "# STOCKHOLM 1.0\n# A comment\n#=GF ID   tRNA\n\n#=GS seq2/1-4  AC  B\n"
+            )
+        );
+        assert!(!output.contains("#=GF AU"));
+        assert!(output.contains("#=GS seq1/1-4  DE  first\n\n#=GS seq1/1-4   FL check\n\n"));
+        assert!(output.contains("seq1/1-4   AC-GU\n"));
+        let ss = output.find("#=GC SS_cons").unwrap();
+        assert!(ss < output.find("#=GC RF").unwrap());
+    }
+
     #[test]
     fn test_write_simple() {
         let mut alignment = Alignment::new();
@@ -0,0 +1,376 @@
+//! Minimum free energy folding and shuffle-based z-scores.
+//!
+//! A compact nearest-neighbor model: Turner 2004 Watson-Crick stacking energies,
+//! approximate G-U stacks, and hairpin/bulge/interior/multiloop initiation terms,
+//! without dangles, terminal mismatches or special loops. Absolute energies differ
+//! from ViennaRNA, but the model is consistent enough to compare a sequence with
+//! shuffles of itself. Energies are computed in dcal/mol.
+
+/// Energy that marks an impossible structure.
+const INF: i32 = i32::MAX / 4;
+
+/// Minimum number of unpaired bases in a hairpin loop.
+const MIN_HAIRPIN: usize = 3;
+
+/// Maximum total size of bulge and interior loops.
+const MAX_LOOP: usize = 30;
+
+/// Sequences longer than this are not folded (O(N^2) memory).
+pub const MAX_FOLD_LEN: usize = 1000;
+
+/// Default number of shuffles per sequence for z-scores.
+pub const DEFAULT_SHUFFLES: usize = 20;
+
+/// z-scores above this are not significantly more stable than shuffles.
+pub const WEAK_FOLD_Z: f64 = -2.0;
+
+/// Terminal penalty for A-U and G-U closing pairs.
+const TERMINAL_AU: i32 = 50;
+
+/// Multiloop closing, per-unpaired and per-branch penalties.
+const ML_CLOSING: i32 = 340;
+const ML_UNPAIRED: i32 = 0;
+const ML_BRANCH: i32 = 40;
+
+/// Stacking energies indexed by the outer pair (i, j) then the inner pair (i+1, j-1).
+/// Pair order: AU, CG, GC, UA, GU, UG.
+const STACK: [[i32; 6]; 6] = [
+    [-93, -224, -208, -110, -60, -60],
+    [-211, -326, -236, -208, -140, -140],
+    [-235, -342, -326, -224, -140, -140],
+    [-133, -235, -211, -93, -60, -60],
+    [-60, -140, -140, -60, -50, -50],
+    [-60, -140, -140, -60, -50, -50],
+];
+
+/// Loop initiation energies by size (index = loop size), extrapolated beyond the table.
+const HAIRPIN: [i32; 10] = [INF, INF, INF, 540, 560, 570, 540, 600, 550, 640];
+const BULGE: [i32; 7] = [INF, 380, 280, 320, 360, 400, 440];
+const INTERIOR: [i32; 7] = [INF, INF, 50, 160, 110, 200, 200];
+
+/// Encode a residue as A=0, C=1, G=2, U=3 (T reads as U); anything else is 4.
+fn encode(c: char) -> u8 {
+    match c.to_ascii_uppercase() {
+        'A' => 0,
+        'C' => 1,
+        'G' => 2,
+        'U' | 'T' => 3,
+        _ => 4,
+    }
+}
+
+/// Pair type index into `STACK`, or None if the bases cannot pair.
+fn pair_type(a: u8, b: u8) -> Option<usize> {
+    match (a, b) {
+        (0, 3) => Some(0),
+        (1, 2) => Some(1),
+        (2, 1) => Some(2),
+        (3, 0) => Some(3),
+        (2, 3) => Some(4),
+        (3, 2) => Some(5),
+        _ => None,
+    }
+}
+
+/// Whether a pair type is A-U or G-U.
+fn is_weak(pair: usize) -> bool {
+    pair != 1 && pair != 2
+}
+
+fn terminal_penalty(pair: usize) -> i32 {
+    if is_weak(pair) { TERMINAL_AU } else { 0 }
+}
+
+/// Look up a loop initiation energy, extrapolating logarithmically past the table.
+fn loop_initiation(table: &[i32], size: usize) -> i32 {
+    let last = table.len() - 1;
+    if size <= last {
+        table[size]
+    } else {
+        // 1.75 RT ln(n / n_max) at 37 degrees
+        table[last] + (107.856 * (size as f64 / last as f64).ln()).round() as i32
+    }
+}
+
+fn hairpin_energy(size: usize, pair: usize) -> i32 {
+    let init = loop_initiation(&HAIRPIN, size);
+    if size == MIN_HAIRPIN {
+        init + terminal_penalty(pair)
+    } else {
+        // Average terminal mismatch bonus
+        init - 80
+    }
+}
+
+/// Energy of a stack, bulge or interior loop closed by `outer` with `inner` inside.
+fn interior_energy(left: usize, right: usize, outer: usize, inner: usize) -> i32 {
+    match (left, right) {
+        (0, 0) => STACK[outer][inner],
+        (0, n) | (n, 0) if n == 1 => BULGE[1] + STACK[outer][inner],
+        (0, n) | (n, 0) => {
+            loop_initiation(&BULGE, n) + terminal_penalty(outer) + terminal_penalty(inner)
+        }
+        _ => {
+            let asymmetry = 60 * left.abs_diff(right) as i32;
+            let closure = if left == 1 && right == 1 {
+                0
+            } else {
+                (is_weak(outer) as i32 + is_weak(inner) as i32) * 70
+            };
+            loop_initiation(&INTERIOR, left + right) + asymmetry + closure
+        }
+    }
+}
+
+/// Zuker recursions over an encoded sequence, returning the MFE in dcal/mol.
+///
+/// Residues other than A/C/G/U/T are left unpaired.
+fn mfe_encoded(seq: &[u8]) -> i32 {
+    let n = seq.len();
+    if n < MIN_HAIRPIN + 2 {
+        return 0;
+    }
+    let idx = |i: usize, j: usize| i * n + j;
+    // v: i and j pair; wm1: one branch starting at i; wm: one or more branches
+    let mut v = vec![INF; n * n];
+    let mut wm1 = vec![INF; n * n];
+    let mut wm = vec![INF; n * n];
+
+    for span in (MIN_HAIRPIN + 1)..n {
+        for i in 0..(n - span) {
+            let j = i + span;
+            if let Some(pair) = pair_type(seq[i], seq[j]) {
+                let mut best = hairpin_energy(span - 1, pair);
+
+                // Stacks, bulges and interior loops
+                for k in (i + 1)..j.min(i + MAX_LOOP + 2) {
+                    let left = k - i - 1;
+                    let min_l = (k + MIN_HAIRPIN + 1).max(j.saturating_sub(MAX_LOOP - left + 1));
+                    for l in (min_l..j).rev() {
+                        let inner_v = v[idx(k, l)];
+                        if inner_v >= INF {
+                            continue;
+                        }
+                        let inner = pair_type(seq[k], seq[l]).expect("v is finite only for pairs");
+                        let e = inner_v + interior_energy(left, j - l - 1, pair, inner);
+                        best = best.min(e);
+                    }
+                }
+
+                // Multiloop: at least two branches inside (i, j)
+                for u in (i + 2)..j {
+                    let (left, right) = (wm[idx(i + 1, u - 1)], wm1[idx(u, j - 1)]);
+                    if left < INF && right < INF {
+                        let e = left + right + ML_CLOSING + ML_BRANCH + terminal_penalty(pair);
+                        best = best.min(e);
+                    }
+                }
+                v[idx(i, j)] = best;
+            }
+
+            let branch = match pair_type(seq[i], seq[j]) {
+                Some(pair) if v[idx(i, j)] < INF => {
+                    v[idx(i, j)] + ML_BRANCH + terminal_penalty(pair)
+                }
+                _ => INF,
+            };
+            let extend = wm1[idx(i, j - 1)].saturating_add(ML_UNPAIRED);
+            wm1[idx(i, j)] = branch.min(extend);
+
+            let mut best = wm1[idx(i, j)].min(wm[idx(i + 1, j)].saturating_add(ML_UNPAIRED));
+            for u in (i + 1)..j {
+                let (left, right) = (wm[idx(i, u - 1)], wm1[idx(u, j)]);
+                if left < INF && right < INF {
+                    best = best.min(left + right);
+                }
+            }
+            wm[idx(i, j)] = best;
+        }
+    }
+
+    // Exterior loop: f[j] is the MFE of the prefix of length j
+    let mut f = vec![0; n + 1];
+    for j in 1..=n {
+        let mut best = f[j - 1];
+        for i in 0..j {
+            let e = v[idx(i, j - 1)];
+            if e < INF {
+                let pair = pair_type(seq[i], seq[j - 1]).expect("v is finite only for pairs");
+                best = best.min(f[i] + e + terminal_penalty(pair));
+            }
+        }
+        f[j] = best;
+    }
+    f[n]
+}
+
+/// Small deterministic xorshift generator, so shuffles are reproducible.
+struct Rng(u64);
+
+impl Rng {
+    fn new(seed: u64) -> Self {
+        Self(seed.wrapping_mul(0x9E37_79B9_7F4A_7C15) | 1)
+    }
+
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+
+    fn below(&mut self, n: usize) -> usize {
+        (self.next() % n as u64) as usize
+    }
+}
+
+/// Shuffle an encoded sequence preserving dinucleotide counts (Altschul-Erickson).
+fn dinucleotide_shuffle(seq: &[u8], rng: &mut Rng) -> Vec<u8> {
+    if seq.len() < 3 {
+        return seq.to_vec();
+    }
+    let mut edges: [Vec<u8>; 5] = Default::default();
+    for w in seq.windows(2) {
+        edges[w[0] as usize].push(w[1]);
+    }
+    let last = seq[seq.len() - 1];
+
+    // Pick each vertex's last exit so they form a tree rooted at the final base
+    let mut last_exit = [None; 5];
+    loop {
+        for v in 0..5u8 {
+            last_exit[v as usize] = if v == last || edges[v as usize].is_empty() {
+                None
+            } else {
+                Some(rng.below(edges[v as usize].len()))
+            };
+        }
+        let reaches_last = |start: u8| {
+            let mut v = start;
+            for _ in 0..5 {
+                match last_exit[v as usize] {
+                    _ if v == last => return true,
+                    Some(e) => v = edges[v as usize][e],
+                    None => return false,
+                }
+            }
+            v == last
+        };
+        if (0..5u8).all(|v| last_exit[v as usize].is_none() || reaches_last(v)) {
+            break;
+        }
+    }
+
+    // Shuffle the remaining exits and keep the chosen last exit at the end
+    for (v, list) in edges.iter_mut().enumerate() {
+        if let Some(e) = last_exit[v] {
+            let end = list.len() - 1;
+            list.swap(e, end);
+        }
+        let free = if last_exit[v].is_some() {
+            list.len() - 1
+        } else {
+            list.len()
+        };
+        for k in (1..free).rev() {
+            let r = rng.below(k + 1);
+            list.swap(k, r);
+        }
+    }
+
+    let mut used = [0; 5];
+    let mut out = Vec::with_capacity(seq.len());
+    let mut v = seq[0];
+    out.push(v);
+    for _ in 1..seq.len() {
+        let next = edges[v as usize][used[v as usize]];
+        used[v as usize] += 1;
+        out.push(next);
+        v = next;
+    }
+    out
+}
+
+/// MFE of a sequence relative to dinucleotide shuffles of itself.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FoldZScore {
+    /// MFE of the sequence (kcal/mol).
+    pub mfe: f64,
+    /// (MFE - mean shuffled MFE) / standard deviation; strongly negative values
+    /// indicate a more stable structure than sequence composition alone explains.
+    pub z: f64,
+}
+
+/// Fold a sequence and `shuffles` dinucleotide shuffles of it.
+///
+/// Returns None for sequences too short or too long to fold, or if the shuffled
+/// energies do not vary.
+pub fn mfe_zscore(seq: &str, shuffles: usize, seed: u64) -> Option<FoldZScore> {
+    let encoded: Vec<u8> = seq.chars().map(encode).collect();
+    if encoded.len() < MIN_HAIRPIN + 2 || encoded.len() > MAX_FOLD_LEN || shuffles < 2 {
+        return None;
+    }
+    let mfe = mfe_encoded(&encoded) as f64;
+    let mut rng = Rng::new(seed);
+    let energies: Vec<f64> = (0..shuffles)
+        .map(|_| mfe_encoded(&dinucleotide_shuffle(&encoded, &mut rng)) as f64)
+        .collect();
+    let mean = energies.iter().sum::<f64>() / shuffles as f64;
+    let variance = energies.iter().map(|e| (e - mean).powi(2)).sum::<f64>() / (shuffles - 1) as f64;
+    let sd = variance.sqrt();
+    (sd > 0.0).then(|| FoldZScore {
+        mfe: mfe / 100.0,
+        z: (mfe - mean) / sd,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn mfe(seq: &str) -> f64 {
+        let encoded: Vec<u8> = seq.chars().map(encode).collect();
+        mfe_encoded(&encoded) as f64 / 100.0
+    }
+
+    #[test]
+    fn test_mfe() {
+        // Nothing can pair
+        assert_eq!(mfe("AAAAAAAAAA"), 0.0);
+        assert_eq!(mfe("GGG"), 0.0);
+        // A GC-rich hairpin is stable
+        let hairpin = mfe("GGGGCGAAAGCCCC");
+        assert!(hairpin < -5.0, "hairpin MFE {hairpin}");
+        // T is read as U
+        assert!(mfe("GGAUCGAAAGAUCC") < 0.0);
+        assert_eq!(mfe("GGAUCGAAAGAUCC"), mfe("GGATCGAAAGATCC"));
+    }
+
+    #[test]
+    fn test_dinucleotide_shuffle() {
+        let seq: Vec<u8> = "GGGGCGAAAGCCCCUUAGCAUGCUAGC".chars().map(encode).collect();
+        let mut rng = Rng::new(7);
+        let shuffled = dinucleotide_shuffle(&seq, &mut rng);
+        let counts = |s: &[u8]| {
+            let mut c = [[0; 5]; 5];
+            for w in s.windows(2) {
+                c[w[0] as usize][w[1] as usize] += 1;
+            }
+            c
+        };
+        assert_eq!(shuffled.len(), seq.len());
+        assert_eq!(shuffled[0], seq[0]);
+        assert_eq!(shuffled.last(), seq.last());
+        assert_eq!(counts(&shuffled), counts(&seq));
+    }
+
+    #[test]
+    fn test_mfe_zscore() {
+        // Tandem hairpins fold far better than their shuffles
+        let structured = "GGGGCCGCAAAGCGGCCCCAAGGGCGCGAAAGCGCGCCC";
+        let z = mfe_zscore(structured, 20, 1).unwrap();
+        assert!(z.mfe < 0.0);
+        assert!(z.z < -1.0, "z-score {}", z.z);
+        assert!(mfe_zscore("ACGU", 20, 1).is_none());
+    }
+}
@@ -1,6 +1,8 @@
 //! RNA secondary structure parsing and analysis.
 
+pub mod fold;
 mod pairs;
 mod parser;
 
 pub use pairs::*;
+pub use parser::StructureError;
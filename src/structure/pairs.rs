@@ -1,6 +1,6 @@
 //! Base pair caching and higher-level structure operations.
 
-use super::parser::{BasePair, StructureError, parse_structure};
+use super::parser::{BasePair, StructureError, parse_structure, scan_partner};
 
 /// Cached structure information for an alignment.
 #[derive(Debug, Default)]
@@ -56,13 +56,11 @@ impl StructureCache {
     }
 
     /// Get all base pairs.
-    #[allow(dead_code)] // API for structure analysis
     pub fn pairs(&self) -> &[BasePair] {
         &self.pairs
     }
 
     /// Get the number of unique helices.
-    #[allow(dead_code)] // API for structure analysis
     pub fn num_helices(&self) -> usize {
         self.pairs
             .iter()
@@ -72,6 +70,43 @@ impl StructureCache {
             .unwrap_or(0)
     }
 
+    /// Helix of the pair at a column and the pair's 0-based position in it,
+    /// counted from the helix's outer end.
+    pub fn helix_position(&self, col: usize) -> Option<(usize, usize)> {
+        let helix = self.get_helix(col)?;
+        let left = col.min(self.get_pair(col)?);
+        let index = self
+            .pairs
+            .iter()
+            .filter(|p| p.helix_id == helix && p.left < left)
+            .count();
+        Some((helix, index))
+    }
+
+    /// Columns of the `index`th pair (0-based, outermost first) of a helix.
+    pub fn helix_pair(&self, helix: usize, index: usize) -> Option<(usize, usize)> {
+        self.pairs
+            .iter()
+            .filter(|p| p.helix_id == helix)
+            .nth(index)
+            .map(|p| (p.left, p.right))
+    }
+
+    /// Columns of every pair of a helix, outermost first: its 5' strand in
+    /// order and its 3' strand reversed.
+    pub fn helix_pairs(&self, helix: usize) -> Vec<(usize, usize)> {
+        self.pairs
+            .iter()
+            .filter(|p| p.helix_id == helix)
+            .map(|p| (p.left, p.right))
+            .collect()
+    }
+
+    /// Number of base pairs in a helix.
+    pub fn helix_len(&self, helix: usize) -> usize {
+        self.pairs.iter().filter(|p| p.helix_id == helix).count()
+    }
+
     /// Check if a column is paired.
     pub fn is_paired(&self, col: usize) -> bool {
         self.pair_lookup.get(col).copied().flatten().is_some()
@@ -174,6 +209,80 @@ pub fn analyze_compensatory(
     }
 }
 
+/// How a sequence's own structure (`#=GR SS`) disagrees with SS_cons at a column.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SsConflict {
+    /// Paired in the sequence's structure, unpaired in SS_cons.
+    Extra,
+    /// Unpaired in the sequence's structure, paired in SS_cons.
+    Missing,
+    /// Paired in both, but to different columns.
+    Partner,
+}
+
+/// Compare the partners of `col` in a sequence's structure and in SS_cons.
+/// Gap residues are skipped, as are consensus pairs whose partner is a gap in
+/// the sequence (it can't pair with nothing).
+fn compare_partners(
+    residues: &[char],
+    col: usize,
+    own: Option<usize>,
+    cons: Option<usize>,
+    gap_chars: &[char],
+) -> Option<SsConflict> {
+    let is_gap = |c: usize| residues.get(c).is_none_or(|ch| gap_chars.contains(ch));
+    if is_gap(col) {
+        return None;
+    }
+    match (own, cons) {
+        (Some(_), None) => Some(SsConflict::Extra),
+        (None, Some(partner)) if !is_gap(partner) => Some(SsConflict::Missing),
+        (Some(a), Some(b)) if a != b => Some(SsConflict::Partner),
+        _ => None,
+    }
+}
+
+/// Conflict between a sequence's `#=GR SS` and SS_cons (in `cache`) at `col`,
+/// or None where they agree. Finds the sequence's partner by scanning from the
+/// column, so it suits per-cell coloring; unmatched brackets count as unpaired.
+pub fn ss_conflict(
+    residues: &[char],
+    own_ss: &str,
+    col: usize,
+    cache: &StructureCache,
+    gap_chars: &[char],
+) -> Option<SsConflict> {
+    if !own_ss.is_ascii() {
+        return None;
+    }
+    let own = scan_partner(own_ss.as_bytes(), col);
+    compare_partners(residues, col, own, cache.get_pair(col), gap_chars)
+}
+
+/// Every column where a sequence's `#=GR SS` disagrees with SS_cons, in order.
+/// Fails if the sequence's structure has unbalanced brackets.
+pub fn ss_conflicts(
+    residues: &[char],
+    own_ss: &str,
+    cache: &StructureCache,
+    gap_chars: &[char],
+) -> Result<Vec<(usize, SsConflict)>, StructureError> {
+    let mut own = StructureCache::new();
+    own.update(own_ss)?;
+    Ok((0..residues.len())
+        .filter_map(|col| {
+            compare_partners(
+                residues,
+                col,
+                own.get_pair(col),
+                cache.get_pair(col),
+                gap_chars,
+            )
+            .map(|conflict| (col, conflict))
+        })
+        .collect())
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -207,6 +316,16 @@ mod tests {
 
         // Unpaired
         assert_eq!(cache.get_helix(2), None);
+
+        // Pairs within a helix, outermost first
+        assert_eq!(cache.helix_len(0), 2);
+        assert_eq!(cache.helix_position(12), Some((0, 1)));
+        assert_eq!(cache.helix_position(4), Some((1, 0)));
+        assert_eq!(cache.helix_position(2), None);
+        assert_eq!(cache.helix_pair(1, 1), Some((5, 8)));
+        assert_eq!(cache.helix_pair(1, 2), None);
+        assert_eq!(cache.helix_pairs(0), vec![(0, 13), (1, 12)]);
+        assert!(cache.helix_pairs(2).is_empty());
     }
 
     #[test]
@@ -234,4 +353,43 @@ mod tests {
         let result = analyze_compensatory("AUUA", "GCGC", 0, &cache, &gap_chars);
         assert_eq!(result, CompensatoryChange::DoubleCompatible);
     }
+
+    #[test]
+    fn test_ss_conflicts() {
+        let mut cache = StructureCache::new();
+        cache.update("((..))..").unwrap();
+        let gap_chars = ['.', '-'];
+        let residues: Vec<char> = "GGAACCA-".chars().collect();
+
+        // Agreeing structure
+        assert!(
+            ss_conflicts(&residues, "((..))..", &cache, &gap_chars)
+                .unwrap()
+                .is_empty()
+        );
+
+        // Shifted inner pair and an extra pair; the gap at column 8 can't conflict
+        let own = "(.(.))()";
+        let found = ss_conflicts(&residues, own, &cache, &gap_chars).unwrap();
+        assert_eq!(
+            found,
+            vec![
+                (1, SsConflict::Missing),
+                (2, SsConflict::Extra),
+                (4, SsConflict::Partner),
+                (6, SsConflict::Extra),
+            ]
+        );
+        // The per-column scan agrees, treating an unmatched bracket as unpaired
+        assert_eq!(
+            ss_conflict(&residues, own, 4, &cache, &gap_chars),
+            Some(SsConflict::Partner)
+        );
+        let unbalanced = "(..))...";
+        assert_eq!(
+            ss_conflict(&residues, unbalanced, 4, &cache, &gap_chars),
+            Some(SsConflict::Missing)
+        );
+        assert!(ss_conflicts(&residues, unbalanced, &cache, &gap_chars).is_err());
+    }
 }
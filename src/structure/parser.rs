@@ -125,6 +125,33 @@ fn assign_helix_ids(pairs: &mut [BasePair]) {
     }
 }
 
+/// Partner of the bracket at `col`, found by scanning from it rather than parsing
+/// the whole structure. None if the column is unpaired or its bracket unmatched.
+/// Expects an ASCII structure (byte index = column).
+pub fn scan_partner(ss: &[u8], col: usize) -> Option<usize> {
+    let ch = *ss.get(col)? as char;
+    let (open, close, forward) = if let Some(close) = matching_close(ch) {
+        (ch, close, true)
+    } else {
+        (matching_open(ch)?, ch, false)
+    };
+    let mut depth = 0usize;
+    let mut step = |i: usize| {
+        let c = ss[i] as char;
+        if c == open {
+            depth = if forward { depth + 1 } else { depth - 1 };
+        } else if c == close {
+            depth = if forward { depth - 1 } else { depth + 1 };
+        }
+        depth == 0
+    };
+    if forward {
+        (col..ss.len()).find(|&i| step(i))
+    } else {
+        (0..=col).rev().find(|&i| step(i))
+    }
+}
+
 /// Find the paired position for a given column.
 #[allow(dead_code)] // API utility, used in tests
 pub fn find_pair(pairs: &[BasePair], col: usize) -> Option<usize> {
@@ -0,0 +1,26 @@
+//! Helpers shared by the unit tests.
+
+use crate::app::App;
+use crate::script;
+use crate::stockholm::parser::parse;
+
+/// An App with `text` (a Stockholm alignment) loaded.
+pub fn app_with(text: &str) -> App {
+    let mut app = App::new();
+    app.load_records(vec![parse(text.as_bytes()).unwrap()], None);
+    app
+}
+
+/// The sequences' residues, top to bottom.
+pub fn rows(app: &App) -> Vec<String> {
+    app.alignment.sequences.iter().map(|s| s.data()).collect()
+}
+
+/// Type `keys`, written as for `:normal`, at the keyboard.
+pub fn type_keys(app: &mut App, keys: &str) {
+    for key in script::parse_keys(keys).unwrap() {
+        app.begin_key(&key);
+        crate::input::handle_key(app, key, 10);
+        app.end_key();
+    }
+}
@@ -0,0 +1,152 @@
+//! Interactive tutorial (`aform --tutor`, `:tutor`).
+//!
+//! A bundled toy alignment and a list of lessons, each with a check run against
+//! the editor state after every key; the panel moves on once a check passes.
+
+use crate::app::{App, ColorScheme};
+
+/// Toy hairpin alignment the tutorial opens. `frog` is shifted one column to the
+/// right for the editing lesson.
+pub const ALIGNMENT: &str = "\
+# STOCKHOLM 1.0
+#=GF ID tutor-hairpin
+#=GF DE Toy hairpin alignment for the aform tutorial
+human/1-22    .AGGACUCGCAAGAGUCCAAUGC.
+mouse/1-22    .AGGACUCGAAAGAGUCCAAUGC.
+chicken/1-21  .AGGAUUCGCAAGAAUCCAAUG..
+frog/1-22     ..AGGACUCGCAAGAGUCCAAUGC
+fish/1-22     .AGGCCUCGCAAGAGGCCAAUGC.
+#=GC SS_cons  ..((((((....))))))......
+//
+";
+
+/// One tutorial lesson: instructions shown in the panel and a check that tells
+/// when the user has done it.
+pub struct Lesson {
+    pub title: &'static str,
+    pub text: &'static [&'static str],
+    pub done: fn(&App) -> bool,
+}
+
+/// Row of the sequence with this ID prefix.
+fn row_of(app: &App, id: &str) -> Option<usize> {
+    app.alignment
+        .sequences
+        .iter()
+        .position(|s| s.id.starts_with(id))
+}
+
+pub const LESSONS: &[Lesson] = &[
+    Lesson {
+        title: "Moving around",
+        text: &[
+            "h, j, k and l move the cursor left, down,",
+            "up and right. The status bar shows the",
+            "cursor as row:column.",
+            "",
+            "Move to row 3 (chicken), column 10.",
+        ],
+        done: |app| app.cursor_row == 2 && app.cursor_col == 9,
+    },
+    Lesson {
+        title: "Jumping",
+        text: &[
+            "0 and $ jump to the start and end of a",
+            "row; gg and G to the first and last",
+            "sequence.",
+            "",
+            "Go to the last column of the last row.",
+        ],
+        done: |app| {
+            app.cursor_row + 1 == app.visible_sequence_count()
+                && app.cursor_col + 1 == app.alignment.width()
+        },
+    },
+    Lesson {
+        title: "Structure coloring",
+        text: &[
+            "Commands start with a colon. The SS_cons",
+            "line under the sequences is the shared",
+            "secondary structure.",
+            "",
+            "Type :color ss and press Enter to color",
+            "the helix.",
+        ],
+        done: |app| app.color_scheme == ColorScheme::Structure,
+    },
+    Lesson {
+        title: "Base pairs",
+        text: &[
+            "gp jumps from a paired column to its",
+            "partner; the status bar shows pair:N.",
+            "",
+            "Move onto a column with ( in SS_cons and",
+            "press gp to land on its ) partner.",
+        ],
+        done: |app| {
+            app.alignment
+                .ss_cons()
+                .and_then(|ss| ss.chars().nth(app.cursor_col))
+                == Some(')')
+        },
+    },
+    Lesson {
+        title: "Fixing a misaligned sequence",
+        text: &[
+            "frog is one column to the right of the",
+            "others, so its helix is out of register.",
+            "",
+            "< shifts the residues up to the cursor one",
+            "column left into a gap. Go to frog's last",
+            "residue ($) and press <. u undoes a change,",
+            "Ctrl-r redoes it.",
+        ],
+        done: |app| {
+            // In register when its 22 residues fill columns 2-23 without gaps
+            row_of(app, "frog").is_some_and(|row| {
+                let seq = &app.alignment.sequences[row];
+                seq.residue_span(&app.gap_chars) == Some((1, 22))
+                    && seq
+                        .chars()
+                        .iter()
+                        .filter(|c| !app.gap_chars.contains(c))
+                        .count()
+                        == 22
+            })
+        },
+    },
+    Lesson {
+        title: "Saving",
+        text: &[
+            ":w <path> saves the alignment; the format",
+            "comes from the extension (.sto, .fa, ...).",
+            "",
+            "Save your work with :w tutor.sto",
+        ],
+        done: |app| app.file_path.is_some() && !app.modified,
+    },
+];
+
+/// Lines shown once every lesson is done.
+pub const FINISHED: &[&str] = &[
+    "That's the basics. Press ? for every key",
+    "and command, :tutor off to close this",
+    "panel, or :q to quit.",
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::stockholm::parser;
+
+    #[test]
+    fn test_tutor_alignment() {
+        let alignment = parser::parse(ALIGNMENT.as_bytes()).unwrap();
+        assert!(alignment.is_valid());
+        assert_eq!(alignment.num_sequences(), 5);
+        assert_eq!(alignment.ss_cons().unwrap().len(), alignment.width());
+        for lesson in LESSONS {
+            assert!(lesson.text.iter().all(|line| line.len() <= 44));
+        }
+    }
+}
@@ -3,17 +3,474 @@
 
 use ratatui::{
     Frame,
+    buffer::Buffer,
     layout::{Constraint, Direction, Layout, Rect},
     style::{Color, Modifier, Style},
     text::{Line, Span},
     widgets::{Block, Borders, Clear, Paragraph},
 };
 
-use crate::app::{ActivePane, App, ColorScheme, Mode, SplitMode, TerminalTheme};
-use crate::color::{Rgb, get_color};
+use crate::app::{
+    ActivePane, App, ColorScheme, DrawnCells, HelixView, Hover, Mode, Pileup, QuickfixKind, Report,
+    SETTING_KEYS, SplitMode, TerminalTheme,
+};
+use crate::color::{Rgb, conservation_thumbnail, get_layer_colors};
+use crate::palette::{self, Palette};
+use crate::structure::fold::WEAK_FOLD_Z;
+
+/// Smallest terminal the editor draws in; anything smaller shows only a notice.
+const MIN_WIDTH: u16 = 20;
+const MIN_HEIGHT: u16 = 4;
+
+/// Below this size the editor switches to the compact view.
+const COMPACT_WIDTH: u16 = 50;
+const COMPACT_HEIGHT: u16 = 10;
+
+/// How much of the editor fits in the terminal.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ScreenSize {
+    /// Panes, IDs, annotation bars and the status bar.
+    Full,
+    /// Residues only, with a single status line.
+    Compact,
+    /// Too small to draw anything but a notice.
+    TooSmall,
+}
+
+impl ScreenSize {
+    pub fn of(area: Rect) -> Self {
+        if area.width < MIN_WIDTH || area.height < MIN_HEIGHT {
+            ScreenSize::TooSmall
+        } else if area.width < COMPACT_WIDTH || area.height < COMPACT_HEIGHT {
+            ScreenSize::Compact
+        } else {
+            ScreenSize::Full
+        }
+    }
+}
 
 /// Render the application UI.
 pub fn render(frame: &mut Frame, app: &App) {
+    app.drawn_cells.borrow_mut().clear();
+    match ScreenSize::of(frame.area()) {
+        ScreenSize::TooSmall => {
+            render_too_small(frame);
+            return;
+        }
+        ScreenSize::Compact => render_compact(frame, app),
+        ScreenSize::Full if app.presentation.is_some() && !app.alignment.sequences.is_empty() => {
+            render_presentation(frame, app);
+        }
+        ScreenSize::Full => {
+            render_editor(frame, app);
+            if app.tutor.is_some() {
+                render_tutor(frame, app);
+            }
+        }
+    }
+
+    // Render help overlay if active
+    if app.show_help {
+        render_help(frame);
+    }
+
+    // Render info overlay if active
+    if app.show_info {
+        render_info(frame, app);
+    }
+
+    // Render column popup if active
+    if app.show_column_popup {
+        render_column_popup(frame, app);
+    }
+
+    // Render cluster panel if active
+    if app.show_clusters {
+        render_clusters(frame, app);
+    }
+
+    // Render settings panel if active
+    if app.settings_panel.is_some() {
+        render_settings(frame, app);
+    }
+
+    // Render pileup view if active
+    if let Some(pileup) = &app.pileup {
+        render_pileup(frame, app, pileup);
+    }
+
+    // Render helix view if active
+    if let Some(view) = &app.helix_view {
+        render_helix_view(frame, app, view);
+    }
+
+    // Render hit viewer if active
+    if app.show_hits {
+        render_hits(frame, app);
+    }
+
+    // Render quickfix list if active
+    if app.show_quickfix {
+        render_quickfix(frame, app);
+    }
+
+    // Render alignment picker if active
+    if app.show_record_list {
+        render_record_list(frame, app);
+    }
+
+    // Render command palette if active
+    if let Some(palette) = &app.palette {
+        render_palette(frame, palette);
+    }
+
+    // Render report overlay if active
+    if let Some(report) = &app.report {
+        render_report(frame, report);
+    }
+
+    // Render the hover popup last, over whatever the mouse is on
+    if let Some(hover) = &app.hover {
+        render_hover(frame, app, hover);
+    }
+}
+
+/// Render the info popup for the cell under the mouse, beside the pointer and
+/// kept on screen.
+fn render_hover(frame: &mut Frame, app: &App, hover: &Hover) {
+    let lines = app.hover_lines(hover);
+    if lines.is_empty() {
+        return;
+    }
+    let area = frame.area();
+    let width =
+        (lines.iter().map(|l| l.chars().count()).max().unwrap_or(0) as u16 + 4).min(area.width);
+    let height = (lines.len() as u16 + 2).min(area.height);
+    // Below and right of the pointer, flipped to the other side near an edge
+    let x = if hover.x + 2 + width <= area.right() {
+        hover.x + 2
+    } else {
+        hover.x.saturating_sub(width + 1)
+    };
+    let y = if hover.y + 1 + height <= area.bottom() {
+        hover.y + 1
+    } else {
+        hover.y.saturating_sub(height)
+    };
+    let popup = Rect::new(x, y, width, height);
+    let mut text: Vec<Line> = lines
+        .into_iter()
+        .map(|l| Line::from(format!(" {l}")))
+        .collect();
+    text[0] = text[0]
+        .clone()
+        .style(Style::default().add_modifier(Modifier::BOLD));
+    frame.render_widget(Clear, popup);
+    frame.render_widget(
+        Paragraph::new(text).block(
+            Block::default()
+                .borders(Borders::ALL)
+                .border_style(Style::default().fg(app.theme.border.active.to_color())),
+        ),
+        popup,
+    );
+}
+
+/// Columns each residue takes in presentation mode.
+const PRESENTATION_CELL_WIDTH: usize = 3;
+
+/// Blank columns on each side of the IDs in presentation mode.
+const PRESENTATION_ID_MARGIN: usize = 2;
+
+/// Lines of the presentation view besides sequences: column numbers and a blank
+/// line above, SS_cons and a blank line below, and the message line.
+fn presentation_chrome_height(app: &App) -> usize {
+    3 + if app.alignment.ss_cons().is_some() {
+        2
+    } else {
+        0
+    }
+}
+
+/// Width of the ID column in presentation mode, margins included.
+fn presentation_id_width(app: &App) -> usize {
+    let max_id_len = if app.show_short_ids {
+        app.alignment.max_short_id_len()
+    } else {
+        app.alignment.max_id_len()
+    };
+    max_id_len.max("SS_cons".len()) + 2 * PRESENTATION_ID_MARGIN
+}
+
+/// Visible sequence rows and alignment columns in presentation mode.
+pub fn presentation_dimensions(area: Rect, app: &App) -> (usize, usize) {
+    let rows = usize::from(area.height).saturating_sub(presentation_chrome_height(app));
+    let cols = usize::from(area.width).saturating_sub(presentation_id_width(app))
+        / PRESENTATION_CELL_WIDTH;
+    (rows.max(1), cols.clamp(1, app.alignment.width().max(1)))
+}
+
+/// Render the presentation view: wide residue cells and a roomy ID column, with
+/// no borders, status bar or annotation bars, and the message line only while it
+/// has something to say.
+fn render_presentation(frame: &mut Frame, app: &App) {
+    use crate::stockholm::short_id;
+
+    let area = frame.area();
+    let (visible_rows, visible_cols) = presentation_dimensions(area, app);
+    let id_width = presentation_id_width(app);
+    let cols: Vec<usize> = if app.hide_gap_columns && !app.visible_columns.is_empty() {
+        app.visible_columns
+            .iter()
+            .skip(app.viewport_col)
+            .take(visible_cols)
+            .copied()
+            .collect()
+    } else {
+        (app.viewport_col..(app.viewport_col + visible_cols).min(app.alignment.width())).collect()
+    };
+    let label = |text: &str, style: Style| {
+        Span::styled(
+            format!(
+                "{:margin$}{text:width$}{:margin$}",
+                "",
+                "",
+                margin = PRESENTATION_ID_MARGIN,
+                width = id_width - 2 * PRESENTATION_ID_MARGIN
+            ),
+            style,
+        )
+    };
+    let cell = |ch: char, style: Style| Span::styled(format!(" {ch} "), style);
+
+    // Column numbers over every tenth column
+    let mut numbers = " ".repeat(id_width);
+    for &col in &cols {
+        let number = if (col + 1) % 10 == 0 || col == app.cursor_col {
+            (col + 1).to_string()
+        } else {
+            String::new()
+        };
+        numbers.push_str(&format!("{number:^PRESENTATION_CELL_WIDTH$}"));
+    }
+    let mut lines = vec![
+        Line::from(Span::styled(
+            numbers,
+            Style::default().fg(app.theme.ruler.numbers.to_color()),
+        )),
+        Line::from(""),
+    ];
+
+    let id_style = Style::default().add_modifier(Modifier::BOLD);
+    let last_row = (app.viewport_row + visible_rows).min(app.visible_sequence_count());
+    for display_row in app.viewport_row..last_row {
+        let actual_row = app.display_to_actual_row(display_row);
+        let seq = &app.alignment.sequences[actual_row];
+        let id = if app.show_short_ids {
+            short_id(&seq.id)
+        } else {
+            &seq.id
+        };
+        let residue_span = seq.residue_span(&app.gap_chars);
+        let mut spans = vec![label(id, id_style)];
+        for &col in &cols {
+            let ch = seq.get(col).unwrap_or(' ');
+            let is_end_gap = app.gap_chars.contains(&ch)
+                && residue_span.is_none_or(|(first, last)| col < first || col > last);
+            let mut style = residue_style(app, ch, col, actual_row, is_end_gap);
+            if app.is_selected(display_row, col) {
+                style = style.bg(app.theme.selection.visual_bg.to_color()).fg(app
+                    .theme
+                    .selection
+                    .visual_fg
+                    .to_color());
+            }
+            if display_row == app.cursor_row && col == app.cursor_col {
+                style = style.add_modifier(Modifier::REVERSED);
+            }
+            spans.push(cell(ch, style));
+        }
+        lines.push(Line::from(spans));
+    }
+
+    if let Some(ss) = app.alignment.ss_cons() {
+        let colors = &app.theme.annotations;
+        let ss: Vec<char> = ss.chars().collect();
+        let paired = app.structure_cache.get_pair(app.cursor_col);
+        let mut spans = vec![label(
+            "SS_cons",
+            Style::default().fg(colors.label_ss_cons_fg.to_color()),
+        )];
+        for &col in &cols {
+            let style = if Some(col) == paired || col == app.cursor_col && paired.is_some() {
+                Style::reset()
+                    .fg(colors.ss_cons_paired_fg.to_color())
+                    .bg(colors.ss_cons_paired_bg.to_color())
+                    .add_modifier(Modifier::BOLD)
+            } else {
+                Style::reset()
+                    .fg(colors.ss_cons_fg.to_color())
+                    .bg(colors.ss_cons_bg.to_color())
+            };
+            spans.push(cell(ss.get(col).copied().unwrap_or(' '), style));
+        }
+        lines.push(Line::from(""));
+        lines.push(Line::from(spans));
+    }
+
+    let message_area = Rect::new(area.x, area.bottom().saturating_sub(1), area.width, 1);
+    let view_area = Rect::new(area.x, area.y, area.width, area.height.saturating_sub(1));
+    frame.render_widget(Paragraph::new(lines), view_area);
+    if app.status_message.is_some() || matches!(app.mode, Mode::Command | Mode::Search) {
+        render_command_line(frame, app, message_area);
+    }
+}
+
+/// Tell the user the terminal is too small to draw the editor in.
+fn render_too_small(frame: &mut Frame) {
+    let area = frame.area();
+    let lines = vec![
+        Line::from(Span::styled(
+            "Terminal too small",
+            Style::default().add_modifier(Modifier::BOLD),
+        )),
+        Line::from(format!("{}x{}", area.width, area.height)),
+        Line::from(format!("need {MIN_WIDTH}x{MIN_HEIGHT}")),
+    ];
+    let top = area.height.saturating_sub(lines.len() as u16) / 2;
+    let text_area = Rect::new(area.x, area.y + top, area.width, area.height - top);
+    frame.render_widget(
+        Paragraph::new(lines).alignment(ratatui::layout::Alignment::Center),
+        text_area,
+    );
+}
+
+/// Visible sequence rows and alignment columns in the compact view.
+pub fn compact_dimensions(area: Rect, app: &App) -> (usize, usize) {
+    let rows = usize::from(area.height).saturating_sub(1);
+    let cols = usize::from(area.width);
+    (rows.max(1), cols.clamp(1, app.alignment.width().max(1)))
+}
+
+/// Render the compact view for small terminals: the residues in the viewport,
+/// with no IDs, borders or annotation bars, above one line that shows the
+/// command being typed, the last message, or the cursor position.
+fn render_compact(frame: &mut Frame, app: &App) {
+    use crate::stockholm::short_id;
+
+    let area = frame.area();
+    let (visible_rows, visible_cols) = compact_dimensions(area, app);
+    let cols: Vec<usize> = if app.hide_gap_columns && !app.visible_columns.is_empty() {
+        app.visible_columns
+            .iter()
+            .skip(app.viewport_col)
+            .take(visible_cols)
+            .copied()
+            .collect()
+    } else {
+        (app.viewport_col..(app.viewport_col + visible_cols).min(app.alignment.width())).collect()
+    };
+
+    let mut lines = Vec::new();
+    let last_row = (app.viewport_row + visible_rows).min(app.visible_sequence_count());
+    for display_row in app.viewport_row..last_row {
+        let actual_row = app.display_to_actual_row(display_row);
+        let seq = &app.alignment.sequences[actual_row];
+        let residue_span = seq.residue_span(&app.gap_chars);
+        let spans: Vec<Span> = cols
+            .iter()
+            .map(|&col| {
+                let ch = seq.get(col).unwrap_or(' ');
+                let is_end_gap = app.gap_chars.contains(&ch)
+                    && residue_span.is_none_or(|(first, last)| col < first || col > last);
+                let mut style = residue_style(app, ch, col, actual_row, is_end_gap);
+                if app.is_selected(display_row, col) {
+                    style = style.bg(app.theme.selection.visual_bg.to_color()).fg(app
+                        .theme
+                        .selection
+                        .visual_fg
+                        .to_color());
+                }
+                if display_row == app.cursor_row && col == app.cursor_col {
+                    style = style.add_modifier(Modifier::REVERSED);
+                }
+                Span::styled(ch.to_string(), style)
+            })
+            .collect();
+        lines.push(Line::from(spans));
+    }
+    let view_area = Rect::new(area.x, area.y, area.width, area.height - 1);
+    app.drawn_cells.borrow_mut().push(DrawnCells {
+        x: view_area.x,
+        y: view_area.y,
+        cell_width: 1,
+        first_row: app.viewport_row,
+        rows: lines.len(),
+        cols: cols.clone(),
+    });
+    frame.render_widget(Paragraph::new(lines), view_area);
+
+    let message_area = Rect::new(area.x, area.bottom() - 1, area.width, 1);
+    if app.status_message.is_some() || matches!(app.mode, Mode::Command | Mode::Search) {
+        render_command_line(frame, app, message_area);
+    } else if !app.alignment.sequences.is_empty() {
+        let seq = &app.alignment.sequences[app.display_to_actual_row(app.cursor_row)];
+        let position = format!(
+            "{} {}/{} {}:{}",
+            app.mode.as_ref(),
+            app.cursor_row + 1,
+            app.visible_sequence_count(),
+            short_id(&seq.id),
+            app.cursor_col + 1
+        );
+        frame.render_widget(
+            Paragraph::new(Span::styled(
+                position,
+                Style::default().add_modifier(Modifier::REVERSED),
+            )),
+            message_area,
+        );
+    }
+}
+
+/// Render the tutorial panel in the top-right corner: the current lesson's
+/// instructions, or a closing note once every lesson is done.
+fn render_tutor(frame: &mut Frame, app: &App) {
+    use crate::tutor::{FINISHED, LESSONS};
+
+    let Some(index) = app.tutor else {
+        return;
+    };
+    let (title, text) = match LESSONS.get(index) {
+        Some(lesson) => (
+            format!(" Lesson {}/{}: {} ", index + 1, LESSONS.len(), lesson.title),
+            lesson.text,
+        ),
+        None => (" Tutorial complete ".to_string(), FINISHED),
+    };
+    let area = frame.area();
+    let width = 48.min(area.width);
+    let height = (text.len() as u16 + 2).min(area.height.saturating_sub(2));
+    let panel = Rect::new(area.right().saturating_sub(width), area.y, width, height);
+    let lines: Vec<Line> = text
+        .iter()
+        .map(|line| Line::from(format!(" {line}")))
+        .collect();
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .border_style(Style::default().fg(Color::Yellow))
+        .title(Span::styled(
+            title,
+            Style::default()
+                .fg(Color::Yellow)
+                .add_modifier(Modifier::BOLD),
+        ));
+    frame.render_widget(Clear, panel);
+    frame.render_widget(Paragraph::new(lines).block(block), panel);
+}
+
+/// Render the alignment panes, status bar and command line.
+fn render_editor(frame: &mut Frame, app: &App) {
     let chunks = Layout::default()
         .direction(Direction::Vertical)
         .constraints([
@@ -93,16 +550,6 @@ pub fn render(frame: &mut Frame, app: &App) {
 
     render_status_bar(frame, app, chunks[1]);
     render_command_line(frame, app, chunks[2]);
-
-    // Render help overlay if active
-    if app.show_help {
-        render_help(frame);
-    }
-
-    // Render info overlay if active
-    if app.show_info {
-        render_info(frame, app);
-    }
 }
 
 /// Height of the ruler in lines.
@@ -115,6 +562,8 @@ struct IdFormatter {
     show_row_numbers: bool,
     show_short_ids: bool,
     collapse_width: usize,
+    flag_width: usize,
+    zscore_width: usize,
 }
 
 /// Format an annotation bar label with consistent styling.
@@ -125,7 +574,8 @@ fn format_annotation_label(
     bg: Color,
 ) -> Line<'static> {
     let label = format!(
-        "{:>row_w$} {:id_w$}",
+        "{}{:>row_w$} {:id_w$}",
+        " ".repeat(id_formatter.flag_width + id_formatter.zscore_width),
         "═",
         name,
         row_w = id_formatter.row_width,
@@ -141,6 +591,8 @@ impl IdFormatter {
         show_row_numbers: bool,
         max_collapse_count: usize,
         show_short_ids: bool,
+        show_flags: bool,
+        show_zscores: bool,
     ) -> Self {
         // Width for collapse count suffix: " (N)" where N is the max count
         let collapse_width = if max_collapse_count > 1 {
@@ -160,6 +612,9 @@ impl IdFormatter {
             show_row_numbers,
             show_short_ids,
             collapse_width,
+            flag_width: if show_flags { 2 } else { 0 },
+            // "-12.3 "
+            zscore_width: if show_zscores { 6 } else { 0 },
         }
     }
 
@@ -172,7 +627,25 @@ impl IdFormatter {
             // Format: "id " with trailing space
             self.id_width + 1
         };
-        base + self.collapse_width
+        base + self.collapse_width + self.flag_width + self.zscore_width
+    }
+
+    /// MFE z-score column shown before the ID (empty when z-scores are hidden).
+    fn zscore_gutter(&self, z: Option<f64>) -> String {
+        match (self.zscore_width, z) {
+            (0, _) => String::new(),
+            (w, Some(z)) => format!("{:>width$.1} ", z, width = w - 1),
+            (w, None) => " ".repeat(w),
+        }
+    }
+
+    /// Gutter marker shown before the ID (empty when no sequence is flagged).
+    fn flag_gutter(&self, flagged: bool) -> &'static str {
+        match (self.flag_width, flagged) {
+            (0, _) => "",
+            (_, true) => "⚑ ",
+            (_, false) => "  ",
+        }
     }
 
     /// Format a row number and ID.
@@ -197,6 +670,156 @@ impl IdFormatter {
     }
 }
 
+/// Style of a residue from the color scheme and any overlay layer (inside the
+/// color focus), with end gaps dimmed. Highlights are applied on top by callers.
+fn residue_style(app: &App, ch: char, col: usize, actual_row: usize, is_end_gap: bool) -> Style {
+    // Dim end gaps: they mark missing data rather than deletions
+    if is_end_gap {
+        return Style::reset().fg(app.theme.misc.end_gap.to_color());
+    }
+    let mut style = Style::reset();
+    if !app.in_color_focus(actual_row, col) {
+        return style;
+    }
+    let (background, foreground) = get_layer_colors(
+        app.color_scheme,
+        app.color_overlay,
+        ch,
+        col,
+        actual_row,
+        &app.alignment,
+        &app.structure_cache,
+        &app.gap_chars,
+        app.reference_seq,
+        app.sequence_type,
+    );
+    if let Some(color) = background {
+        style = style.bg(color).fg(Color::Black);
+    }
+    if let Some(color) = foreground {
+        style = style.fg(color).add_modifier(Modifier::BOLD);
+    }
+    style
+}
+
+/// Style of an RF character: conserved (uppercase or `x`) or variable.
+fn rf_style(app: &App, ch: char) -> Style {
+    let colors = &app.theme.annotations;
+    if ch.is_uppercase() || ch == 'x' || ch == 'X' {
+        Style::reset()
+            .fg(colors.rf_conserved_fg.to_color())
+            .bg(colors.rf_conserved_bg.to_color())
+    } else {
+        Style::reset()
+            .fg(colors.rf_variable_fg.to_color())
+            .bg(colors.rf_variable_bg.to_color())
+    }
+}
+
+/// Largest figure `render_alignment_buffer` makes, in cells (about 50 bytes each).
+pub const MAX_RENDER_CELLS: usize = 10_000_000;
+
+/// Render the whole alignment into a buffer for export: every shown sequence and
+/// column (respecting filters, collapsing and hidden gap columns, and only those
+/// in `region` if given) with the active coloring, followed by the SS_cons and RF
+/// lines. The cursor, selection, search highlights and status lines are left out.
+/// Fails if the figure would have more rows or columns than a buffer holds, or
+/// more than `MAX_RENDER_CELLS` cells.
+pub fn render_alignment_buffer(
+    app: &App,
+    region: Option<std::ops::Range<usize>>,
+) -> Result<Buffer, String> {
+    use crate::stockholm::short_id;
+
+    let mut cols: Vec<usize> = if app.hide_gap_columns && !app.visible_columns.is_empty() {
+        app.visible_columns.clone()
+    } else {
+        (0..app.alignment.width()).collect()
+    };
+    if let Some(region) = region {
+        cols.retain(|col| region.contains(col));
+    }
+    let mut labels: Vec<(String, Style)> = (0..app.visible_sequence_count())
+        .map(|display_row| {
+            let id = &app.alignment.sequences[app.display_to_actual_row(display_row)].id;
+            let id = if app.show_short_ids { short_id(id) } else { id };
+            (id.to_string(), Style::reset())
+        })
+        .collect();
+    let annotations = &app.theme.annotations;
+    let ss_style = Style::reset()
+        .fg(annotations.ss_cons_fg.to_color())
+        .bg(annotations.ss_cons_bg.to_color());
+    if app.alignment.ss_cons().is_some() {
+        labels.push(("SS_cons".to_string(), ss_style));
+    }
+    if app.alignment.rf().is_some() {
+        labels.push(("RF".to_string(), rf_style(app, 'x')));
+    }
+
+    let id_width = labels
+        .iter()
+        .map(|(l, _)| l.chars().count())
+        .max()
+        .unwrap_or(0)
+        + 1;
+    let (width, height) = match (
+        u16::try_from(id_width + cols.len()),
+        u16::try_from(labels.len()),
+    ) {
+        (Ok(width), Ok(height)) if usize::from(width) * usize::from(height) <= MAX_RENDER_CELLS => {
+            (width, height)
+        }
+        _ => {
+            return Err(format!(
+                "{} rows x {} columns is too large to render (at most {MAX_RENDER_CELLS} cells); \
+                 render a region or a filtered view",
+                labels.len(),
+                cols.len()
+            ));
+        }
+    };
+    let mut buffer = Buffer::empty(Rect::new(0, 0, width, height));
+    let mut put = |y: u16, i: usize, ch: char, style: Style| {
+        buffer[((id_width + i) as u16, y)]
+            .set_char(ch)
+            .set_style(style);
+    };
+
+    let num_rows = app.visible_sequence_count();
+    for display_row in 0..num_rows {
+        let actual_row = app.display_to_actual_row(display_row);
+        let seq = &app.alignment.sequences[actual_row];
+        let residue_span = seq.residue_span(&app.gap_chars);
+        for (i, &col) in cols.iter().enumerate() {
+            let ch = seq.get(col).unwrap_or(' ');
+            let is_end_gap = app.gap_chars.contains(&ch)
+                && residue_span.is_none_or(|(first, last)| col < first || col > last);
+            let style = residue_style(app, ch, col, actual_row, is_end_gap);
+            put(display_row as u16, i, ch, style);
+        }
+    }
+    let mut y = num_rows as u16;
+    if let Some(ss) = app.alignment.ss_cons() {
+        let ss: Vec<char> = ss.chars().collect();
+        for (i, &col) in cols.iter().enumerate() {
+            put(y, i, ss.get(col).copied().unwrap_or(' '), ss_style);
+        }
+        y += 1;
+    }
+    if let Some(rf) = app.alignment.rf() {
+        let rf: Vec<char> = rf.chars().collect();
+        for (i, &col) in cols.iter().enumerate() {
+            let ch = rf.get(col).copied().unwrap_or(' ');
+            put(y, i, ch, rf_style(app, ch));
+        }
+    }
+    for (y, (label, style)) in labels.iter().enumerate() {
+        buffer.set_string(0, y as u16, format!("{label:id_width$}"), *style);
+    }
+    Ok(buffer)
+}
+
 /// Render an alignment pane with the given viewport.
 /// Layout: IDs | Alignment (with ruler above, SS_cons below) | Tree
 fn render_alignment_pane(
@@ -209,13 +832,27 @@ fn render_alignment_pane(
     pane_label: Option<&str>,
 ) {
     // Build title with file info and optional pane label
+    let mut file_position = if app.arg_files.len() > 1 {
+        format!("({}/{}) ", app.arg_index + 1, app.arg_files.len())
+    } else {
+        String::new()
+    };
+    if app.records.len() > 1 {
+        file_position.push_str(&format!(
+            "[alignment {}/{}] ",
+            app.record_index + 1,
+            app.records.len()
+        ));
+    }
     let file_info = format!(
-        " {} {} ",
+        " {} {}{}{} ",
         app.file_path
             .as_ref()
             .map(|p| p.display().to_string())
             .unwrap_or_else(|| "[No file]".to_string()),
-        if app.modified { "[+]" } else { "" }
+        file_position,
+        if app.modified { "[+]" } else { "" },
+        if app.read_only { "[RO]" } else { "" }
     );
 
     let title = match pane_label {
@@ -257,6 +894,8 @@ fn render_alignment_pane(
         app.show_row_numbers,
         max_collapse,
         app.show_short_ids,
+        app.alignment.has_sequence_flags(),
+        app.show_zscores,
     );
     let id_width = id_formatter.width();
 
@@ -267,12 +906,23 @@ fn render_alignment_pane(
         0
     };
 
-    // Calculate alignment column width (cap at actual alignment width)
+    // Pinned columns are drawn before the scrolled ones, with a separator
     let alignment_width = app.alignment.width();
+    let pinned: Vec<usize> = app
+        .pinned_columns
+        .iter()
+        .copied()
+        .filter(|&col| col < alignment_width)
+        .collect();
+    let pinned_display_width = app.pinned_display_width();
+
+    // Calculate alignment column width (cap at actual alignment width)
     let available_width = (inner.width as usize)
         .saturating_sub(id_width + 1) // +1 for separator after IDs
-        .saturating_sub(tree_display_width);
+        .saturating_sub(tree_display_width)
+        .saturating_sub(pinned_display_width);
     let seq_width = alignment_width.min(available_width);
+    let strip_width = (pinned_display_width + seq_width) as u16;
 
     // Vertical layout dimensions
     let ruler_height = if app.show_ruler { RULER_HEIGHT } else { 0 };
@@ -288,6 +938,8 @@ fn render_alignment_pane(
     };
     let consensus_height: u16 = if app.show_consensus { 1 } else { 0 };
     let conservation_height: u16 = if app.show_conservation_bar { 1 } else { 0 };
+    let confidence_height: u16 = if app.show_confidence_bar { 1 } else { 0 };
+    let track_height: u16 = if app.track_row().is_some() { 1 } else { 0 };
 
     // Calculate visible rows (inner height minus ruler and annotation bars)
     let visible_rows = (inner.height as usize)
@@ -296,24 +948,26 @@ fn render_alignment_pane(
         .saturating_sub(rf_height as usize)
         .saturating_sub(pp_cons_height as usize)
         .saturating_sub(consensus_height as usize)
-        .saturating_sub(conservation_height as usize);
+        .saturating_sub(conservation_height as usize)
+        .saturating_sub(confidence_height as usize)
+        .saturating_sub(track_height as usize);
 
     // === Split horizontally: IDs | Alignment | Tree | Filler ===
     let h_constraints = if tree_display_width > 0 {
         vec![
             Constraint::Length(id_width as u16),       // IDs column
             Constraint::Length(1),                     // Separator
-            Constraint::Length(seq_width as u16),      // Alignment column (capped)
+            Constraint::Length(strip_width),           // Alignment column (capped)
             Constraint::Length(1),                     // Separator
             Constraint::Length(app.tree_width as u16), // Tree column
             Constraint::Min(0),                        // Filler (absorbs extra space)
         ]
     } else {
         vec![
-            Constraint::Length(id_width as u16),  // IDs column
-            Constraint::Length(1),                // Separator
-            Constraint::Length(seq_width as u16), // Alignment column (capped)
-            Constraint::Min(0),                   // Filler (absorbs extra space)
+            Constraint::Length(id_width as u16), // IDs column
+            Constraint::Length(1),               // Separator
+            Constraint::Length(strip_width),     // Alignment column (capped)
+            Constraint::Min(0),                  // Filler (absorbs extra space)
         ]
     };
 
@@ -331,8 +985,13 @@ fn render_alignment_pane(
     };
 
     // Total annotation bar height
-    let annotation_height =
-        ss_cons_height + rf_height + pp_cons_height + consensus_height + conservation_height;
+    let annotation_height = ss_cons_height
+        + rf_height
+        + pp_cons_height
+        + consensus_height
+        + conservation_height
+        + confidence_height
+        + track_height;
 
     // Calculate actual sequence rows to display (may be less than visible_rows)
     let actual_seq_rows =
@@ -360,21 +1019,64 @@ fn render_alignment_pane(
         actual_seq_rows,
     );
 
+    // === Render pinned columns, then the scrolled ones ===
+    let align_area = if pinned.is_empty() {
+        align_area
+    } else {
+        let strips = Layout::default()
+            .direction(Direction::Horizontal)
+            .constraints([
+                Constraint::Length(pinned.len() as u16),
+                Constraint::Length(1),
+                Constraint::Min(0),
+            ])
+            .split(align_area);
+        render_alignment_column(
+            frame,
+            app,
+            strips[0],
+            viewport_row,
+            Strip::Pinned(&pinned),
+            visible_rows,
+            ruler_height,
+            ss_cons_height,
+            rf_height,
+            pp_cons_height,
+            consensus_height,
+            conservation_height,
+            confidence_height,
+            track_height,
+            is_active,
+        );
+        render_separator(
+            frame,
+            strips[1],
+            ruler_height,
+            annotation_height,
+            actual_seq_rows,
+        );
+        strips[2]
+    };
+
     // === Render alignment column (with ruler above, annotation bars below) ===
     render_alignment_column(
         frame,
         app,
         align_area,
         viewport_row,
-        viewport_col,
+        Strip::Scrolled {
+            viewport_col,
+            width: seq_width,
+        },
         visible_rows,
-        seq_width,
         ruler_height,
         ss_cons_height,
         rf_height,
         pp_cons_height,
         consensus_height,
         conservation_height,
+        confidence_height,
+        track_height,
         is_active,
     );
 
@@ -448,6 +1150,17 @@ fn render_ids_column(
             Style::reset()
                 .bg(app.theme.id_column.selected_bg.to_color())
                 .fg(app.theme.id_column.selected_fg.to_color())
+        } else if let Some(is_current) = app.is_search_id_match(actual_row) {
+            let selection = &app.theme.selection;
+            if is_current {
+                Style::reset()
+                    .bg(selection.search_current_bg.to_color())
+                    .fg(selection.search_current_fg.to_color())
+            } else {
+                Style::reset()
+                    .bg(selection.search_other_bg.to_color())
+                    .fg(selection.search_other_fg.to_color())
+            }
         } else if display_row == app.cursor_row {
             Style::reset().add_modifier(Modifier::BOLD)
         } else {
@@ -465,11 +1178,28 @@ fn render_ids_column(
         } else {
             id_formatter.format(display_row, &seq.id)
         };
-        lines.push(Line::from(Span::styled(id_display, id_style)));
-    }
-
-    let paragraph = Paragraph::new(lines);
-    frame.render_widget(paragraph, ids_seq_area);
+        let flagged = app.alignment.sequence_flag(&seq.id).is_some();
+        let zscore = app.fold_zscore(display_row).map(|z| z.z);
+        let zscore_color = if zscore.is_some_and(|z| z > WEAK_FOLD_Z) {
+            app.theme.id_column.zscore_weak
+        } else {
+            app.theme.id_column.zscore
+        };
+        lines.push(Line::from(vec![
+            Span::styled(
+                id_formatter.flag_gutter(flagged),
+                Style::reset().fg(app.theme.id_column.flag.to_color()),
+            ),
+            Span::styled(
+                id_formatter.zscore_gutter(zscore),
+                Style::reset().fg(zscore_color.to_color()),
+            ),
+            Span::styled(id_display, id_style),
+        ]));
+    }
+
+    let paragraph = Paragraph::new(lines);
+    frame.render_widget(paragraph, ids_seq_area);
 
     // Render annotation labels using helper
     let mut annotation_lines = Vec::new();
@@ -514,6 +1244,22 @@ fn render_ids_column(
             app.theme.annotations.conservation_bg.to_color(),
         ));
     }
+    if app.show_confidence_bar {
+        annotation_lines.push(format_annotation_label(
+            "Confidence",
+            id_formatter,
+            app.theme.annotations.label_confidence_fg.to_color(),
+            app.theme.annotations.confidence_bg.to_color(),
+        ));
+    }
+    if let Some((tag, _)) = app.track_row() {
+        annotation_lines.push(format_annotation_label(
+            &format!("#=GC {tag}"),
+            id_formatter,
+            app.theme.annotations.label_track_fg.to_color(),
+            app.theme.annotations.track_bg.to_color(),
+        ));
+    }
 
     if !annotation_lines.is_empty() {
         let label_para = Paragraph::new(annotation_lines);
@@ -559,6 +1305,14 @@ fn render_separator(
     frame.render_widget(paragraph, area);
 }
 
+/// Columns shown by one strip of the alignment column.
+enum Strip<'a> {
+    /// `width` columns from the viewport on.
+    Scrolled { viewport_col: usize, width: usize },
+    /// Columns pinned with `:pin`, whatever the viewport.
+    Pinned(&'a [usize]),
+}
+
 /// Render the alignment column (ruler + sequences + annotation bars).
 #[allow(clippy::too_many_arguments)]
 fn render_alignment_column(
@@ -566,20 +1320,26 @@ fn render_alignment_column(
     app: &App,
     area: Rect,
     viewport_row: usize,
-    viewport_col: usize,
+    strip: Strip,
     visible_rows: usize,
-    seq_width: usize,
     ruler_height: u16,
     ss_cons_height: u16,
     rf_height: u16,
     pp_cons_height: u16,
     consensus_height: u16,
     conservation_height: u16,
+    confidence_height: u16,
+    track_height: u16,
     is_active: bool,
 ) {
     // Total annotation bar height
-    let annotation_height =
-        ss_cons_height + rf_height + pp_cons_height + consensus_height + conservation_height;
+    let annotation_height = ss_cons_height
+        + rf_height
+        + pp_cons_height
+        + consensus_height
+        + conservation_height
+        + confidence_height
+        + track_height;
 
     // Calculate actual sequence rows to display (may be less than visible_rows)
     let actual_seq_rows =
@@ -610,6 +1370,8 @@ fn render_alignment_column(
             Constraint::Length(pp_cons_height),
             Constraint::Length(consensus_height),
             Constraint::Length(conservation_height),
+            Constraint::Length(confidence_height),
+            Constraint::Length(track_height),
         ])
         .split(annotation_area);
 
@@ -618,6 +1380,8 @@ fn render_alignment_column(
     let pp_cons_area = annotation_chunks[2];
     let consensus_area = annotation_chunks[3];
     let conservation_area = annotation_chunks[4];
+    let confidence_area = annotation_chunks[5];
+    let track_area = annotation_chunks[6];
 
     // Render ruler (no ID padding - ruler is only over alignment)
     if app.show_ruler {
@@ -633,31 +1397,47 @@ fn render_alignment_column(
             app.theme.ruler.ticks,
             app.theme.ruler.pair_line,
         );
-        let ruler_lines = render_ruler(
-            0,
-            seq_width,
-            viewport_col,
-            cursor_col,
-            paired_col,
-            ruler_colors,
-        );
+        let ruler_lines = match strip {
+            Strip::Scrolled {
+                viewport_col,
+                width,
+            } => render_ruler(0, width, viewport_col, cursor_col, paired_col, ruler_colors),
+            Strip::Pinned(cols) => render_pinned_ruler(cols, cursor_col, paired_col, ruler_colors),
+        };
         let ruler_paragraph = Paragraph::new(ruler_lines);
         frame.render_widget(ruler_paragraph, ruler_area);
     }
 
     // Compute columns to render (handles hiding gap columns)
-    let cols_to_render: Vec<usize> = if app.hide_gap_columns && !app.visible_columns.is_empty() {
-        // viewport_col is in display column space when hiding
-        app.visible_columns
-            .iter()
-            .skip(viewport_col)
-            .take(seq_width)
-            .copied()
-            .collect()
-    } else {
-        (viewport_col..(viewport_col + seq_width).min(app.alignment.width())).collect()
+    let cols_to_render: Vec<usize> = match strip {
+        Strip::Pinned(cols) => cols.to_vec(),
+        Strip::Scrolled {
+            viewport_col,
+            width,
+        } if app.hide_gap_columns && !app.visible_columns.is_empty() => {
+            // viewport_col is in display column space when hiding
+            app.visible_columns
+                .iter()
+                .skip(viewport_col)
+                .take(width)
+                .copied()
+                .collect()
+        }
+        Strip::Scrolled {
+            viewport_col,
+            width,
+        } => (viewport_col..(viewport_col + width).min(app.alignment.width())).collect(),
     };
 
+    app.drawn_cells.borrow_mut().push(DrawnCells {
+        x: seq_area.x,
+        y: seq_area.y,
+        cell_width: 1,
+        first_row: viewport_row,
+        rows: usize::from(seq_area.height),
+        cols: cols_to_render.clone(),
+    });
+
     // Render sequences
     let mut lines = Vec::new();
     for display_row in viewport_row..(viewport_row + visible_rows).min(app.visible_sequence_count())
@@ -667,26 +1447,14 @@ fn render_alignment_column(
         let mut spans = Vec::new();
 
         let seq_chars: Vec<char> = seq.chars().to_vec();
+        let residue_span = seq.residue_span(&app.gap_chars);
         for &col in &cols_to_render {
             let ch = seq_chars.get(col).copied().unwrap_or(' ');
             let is_cursor = is_active && display_row == app.cursor_row && col == app.cursor_col;
+            let is_end_gap = app.gap_chars.contains(&ch)
+                && residue_span.is_none_or(|(first, last)| col < first || col > last);
 
-            let mut style = Style::reset();
-
-            // Apply color scheme
-            if let Some(color) = get_color(
-                app.color_scheme,
-                ch,
-                col,
-                actual_row,
-                &app.alignment,
-                &app.structure_cache,
-                &app.gap_chars,
-                app.reference_seq,
-                app.sequence_type,
-            ) {
-                style = style.bg(color).fg(Color::Black);
-            }
+            let mut style = residue_style(app, ch, col, actual_row, is_end_gap);
 
             // Highlight empty (all-gap) columns if enabled
             if app.highlight_gap_columns && app.alignment.is_empty_column(col, &app.gap_chars) {
@@ -707,7 +1475,7 @@ fn render_alignment_column(
             }
 
             // Highlight visual selection
-            if app.is_selected(display_row, col) {
+            if app.is_selected(display_row, col) || app.in_block_move(actual_row, col) {
                 style = style.bg(app.theme.selection.visual_bg.to_color()).fg(app
                     .theme
                     .selection
@@ -817,6 +1585,16 @@ fn render_alignment_column(
     if app.show_conservation_bar {
         render_conservation_bar(frame, app, conservation_area, &cols_to_render, is_active);
     }
+
+    // Render confidence bar
+    if app.show_confidence_bar {
+        render_confidence_bar(frame, app, confidence_area, &cols_to_render, is_active);
+    }
+
+    // Render numeric track graph
+    if let Some((_, data)) = app.track_row() {
+        render_track_bar(frame, app, data, track_area, &cols_to_render, is_active);
+    }
 }
 
 /// Render the consensus bar (showing the most common character at each position).
@@ -898,6 +1676,87 @@ fn render_conservation_bar(
     frame.render_widget(line, area);
 }
 
+/// Render the column confidence bar (pairwise consistency as block characters).
+fn render_confidence_bar(
+    frame: &mut Frame,
+    app: &App,
+    area: Rect,
+    cols_to_render: &[usize],
+    is_active: bool,
+) {
+    use crate::color::conservation_to_block;
+
+    let mut spans = Vec::new();
+
+    for &col in cols_to_render {
+        let (ch, color) = match app.column_confidence(col) {
+            Some(confidence) => conservation_to_block(confidence),
+            None => (' ', Color::Reset),
+        };
+        let is_cursor_col = is_active && col == app.cursor_col;
+
+        let mut style = Style::reset()
+            .fg(color)
+            .bg(app.theme.annotations.confidence_bg.to_color());
+
+        if is_cursor_col {
+            style = style.add_modifier(Modifier::UNDERLINED);
+        }
+
+        spans.push(Span::styled(ch.to_string(), style));
+    }
+
+    let line = Paragraph::new(Line::from(spans));
+    frame.render_widget(line, area);
+}
+
+/// Graph characters for numeric track values 0-9 and `*`.
+const TRACK_BLOCKS: [char; 11] = ['_', '▁', '▂', '▃', '▃', '▄', '▅', '▆', '▆', '▇', '█'];
+
+/// Render a numeric #=GC track as a mini bar graph, one block per column.
+fn render_track_bar(
+    frame: &mut Frame,
+    app: &App,
+    data: &str,
+    area: Rect,
+    cols_to_render: &[usize],
+    is_active: bool,
+) {
+    use crate::color::conservation_to_block;
+    use crate::stockholm::{TRACK_MAX, track_level};
+
+    let track_chars: Vec<char> = data.chars().collect();
+    let mut spans = Vec::new();
+
+    for &col in cols_to_render {
+        let level = track_chars.get(col).copied().and_then(track_level);
+        let (ch, color) = match level {
+            Some(level) => (
+                TRACK_BLOCKS[level as usize],
+                conservation_to_block(f64::from(level) / f64::from(TRACK_MAX)).1,
+            ),
+            None => (' ', Color::Reset),
+        };
+        let is_cursor_col = is_active && col == app.cursor_col;
+
+        let mut style = Style::reset()
+            .fg(color)
+            .bg(app.theme.annotations.track_bg.to_color());
+
+        // The edited column stands out while in track mode
+        if is_cursor_col && app.mode == Mode::Track {
+            style = style.add_modifier(Modifier::REVERSED);
+        } else if is_cursor_col {
+            style = style.add_modifier(Modifier::UNDERLINED);
+        }
+
+        spans.push(Span::styled(ch.to_string(), style));
+    }
+
+    let line = Paragraph::new(Line::from(spans));
+    frame.render_widget(line, area);
+}
+
 /// Render the RF (reference sequence) bar.
 fn render_rf_bar(
     frame: &mut Frame,
@@ -916,15 +1775,7 @@ fn render_rf_bar(
         let is_cursor_col = is_active && col == cursor_col;
 
         // Uppercase or 'x'/'X' = conserved (green), lowercase/gaps = variable (gray)
-        let mut style = if ch.is_uppercase() || ch == 'x' || ch == 'X' {
-            Style::reset()
-                .fg(app.theme.annotations.rf_conserved_fg.to_color())
-                .bg(app.theme.annotations.rf_conserved_bg.to_color())
-        } else {
-            Style::reset()
-                .fg(app.theme.annotations.rf_variable_fg.to_color())
-                .bg(app.theme.annotations.rf_variable_bg.to_color())
-        };
+        let mut style = rf_style(app, ch);
 
         // Highlight empty (all-gap) columns if enabled
         if app.highlight_gap_columns && app.alignment.is_empty_column(col, &app.gap_chars) {
@@ -1013,8 +1864,8 @@ fn render_tree_column(
     // In that case, we skip tree rendering.
     let mut lines = Vec::new();
     if let Some(ref tree_lines) = app.cluster_tree {
-        if app.collapse_identical {
-            // Tree doesn't make sense with collapse - show empty
+        if app.collapse_identical || app.row_filter.is_some() {
+            // Tree doesn't make sense with collapse or filtering - show empty
             for _ in 0..actual_seq_rows {
                 lines.push(Line::from(""));
             }
@@ -1167,6 +2018,54 @@ fn render_ruler(
     lines
 }
 
+/// Render the ruler over pinned columns: the number of each run of adjacent
+/// columns where it fits, and ticks with the cursor and its pair marked.
+fn render_pinned_ruler(
+    cols: &[usize],
+    cursor_col: Option<usize>,
+    paired_col: Option<usize>,
+    ruler_colors: (Rgb, Rgb, Rgb), // (numbers, ticks, pair_line)
+) -> Vec<Line<'static>> {
+    let (numbers_color, ticks_color, pair_color) = ruler_colors;
+
+    let mut number_chars = vec![' '; cols.len()];
+    let mut start = 0;
+    while start < cols.len() {
+        let mut end = start + 1;
+        while end < cols.len() && cols[end] == cols[end - 1] + 1 {
+            end += 1;
+        }
+        let pos_str = (cols[start] + 1).to_string();
+        if pos_str.len() <= end - start {
+            for (i, ch) in pos_str.chars().enumerate() {
+                number_chars[start + i] = ch;
+            }
+        }
+        start = end;
+    }
+
+    let tick_style = Style::reset().fg(ticks_color.to_color());
+    let pair_style = Style::reset().fg(pair_color.to_color());
+    let ticks: Vec<Span<'static>> = cols
+        .iter()
+        .map(|&col| {
+            if Some(col) == cursor_col || (cursor_col.is_some() && Some(col) == paired_col) {
+                Span::styled("↓", pair_style)
+            } else {
+                Span::styled("·", tick_style)
+            }
+        })
+        .collect();
+
+    vec![
+        Line::from(Span::styled(
+            number_chars.into_iter().collect::<String>(),
+            Style::reset().fg(numbers_color.to_color()),
+        )),
+        Line::from(ticks),
+    ]
+}
+
 /// Render the status bar.
 fn render_status_bar(frame: &mut Frame, app: &App, area: Rect) {
     let modes = &app.theme.status_bar.modes;
@@ -1186,9 +2085,17 @@ fn render_status_bar(frame: &mut Frame, app: &App, area: Rect) {
         Mode::Visual => Style::default()
             .bg(modes.visual_bg.to_color())
             .fg(modes.visual_fg.to_color()),
+        Mode::Track => Style::default()
+            .bg(modes.track_bg.to_color())
+            .fg(modes.track_fg.to_color()),
     };
 
-    let mode_span = Span::styled(format!(" {} ", app.mode.as_ref()), mode_style);
+    let mode_name = if app.mode == Mode::Visual && app.block_selection {
+        "V-BLOCK"
+    } else {
+        app.mode.as_ref()
+    };
+    let mode_span = Span::styled(format!(" {mode_name} "), mode_style);
 
     // Position info
     let pos_info = format!(" {}:{} ", app.cursor_row + 1, app.cursor_col + 1);
@@ -1213,8 +2120,13 @@ fn render_status_bar(frame: &mut Frame, app: &App, area: Rect) {
     let type_info = format!(" {} ", app.sequence_type.as_str());
 
     // Color scheme
-    let color_info = if app.color_scheme != ColorScheme::None {
-        format!(" [{}] ", app.color_scheme.as_ref())
+    let color_info = if app.color_scheme != ColorScheme::None || app.color_overlay.is_some() {
+        let focus = if app.color_focus.is_some() {
+            " focus"
+        } else {
+            ""
+        };
+        format!(" [{}{focus}] ", app.color_name())
     } else {
         String::new()
     };
@@ -1222,7 +2134,12 @@ fn render_status_bar(frame: &mut Frame, app: &App, area: Rect) {
     // Structure info
     let structure_info = if app.structure_cache.is_paired(app.cursor_col) {
         if let Some(paired) = app.structure_cache.get_pair(app.cursor_col) {
-            format!(" pair:{} ", paired + 1)
+            match app.structure_cache.helix_position(app.cursor_col) {
+                Some((helix, index)) => {
+                    format!(" pair:{} helix:{}:{} ", paired + 1, helix + 1, index + 1)
+                }
+                None => format!(" pair:{} ", paired + 1),
+            }
         } else {
             String::new()
         }
@@ -1230,12 +2147,60 @@ fn render_status_bar(frame: &mut Frame, app: &App, area: Rect) {
         String::new()
     };
 
+    // Cursor column statistics: consensus base, conservation, internal and end gap percentages
+    let column_info = if app.alignment.num_sequences() > 0 {
+        let stats = app.cursor_column_stats();
+        let end_info = if stats.end_gap_fraction > 0.0 {
+            format!(" end:{:.0}%", stats.end_gap_fraction * 100.0)
+        } else {
+            String::new()
+        };
+        let confidence_info = match app.column_confidence(app.cursor_col) {
+            Some(confidence) if app.show_confidence_bar => {
+                format!(" conf:{:.0}%", confidence * 100.0)
+            }
+            _ => String::new(),
+        };
+        let track_info = match app.track_row() {
+            Some((tag, data)) => {
+                format!(" {tag}:{}", data.chars().nth(app.cursor_col).unwrap_or(' '))
+            }
+            None => String::new(),
+        };
+        format!(
+            " {} {:.0}% gap:{:.0}%{}{}{} ",
+            stats.consensus,
+            stats.conservation * 100.0,
+            stats.gap_fraction * 100.0,
+            end_info,
+            confidence_info,
+            track_info
+        )
+    } else {
+        String::new()
+    };
+
     // Current character
     let char_info = app
         .current_char()
         .map(|c| format!(" '{}' ", c))
         .unwrap_or_default();
 
+    // Curation flag of the current sequence
+    let flag_info = app
+        .alignment
+        .sequences
+        .get(app.display_to_actual_row(app.cursor_row))
+        .and_then(|s| app.alignment.sequence_flag(&s.id))
+        .map(|label| format!(" ⚑ {label} "))
+        .unwrap_or_default();
+
+    // Fold energy of the current sequence
+    let fold_info = app
+        .fold_zscore(app.cursor_row)
+        .map(|z| format!(" MFE {:.1} z {:.1} ", z.mfe, z.z))
+        .unwrap_or_default();
+
     // Selection info (in visual mode)
     let selection_info = app
         .selection_info()
@@ -1264,6 +2229,18 @@ fn render_status_bar(frame: &mut Frame, app: &App, area: Rect) {
             structure_info,
             Style::default().fg(app.theme.status_bar.structure_info.to_color()),
         ),
+        Span::styled(
+            column_info,
+            Style::default().fg(app.theme.status_bar.column_stats.to_color()),
+        ),
+        Span::styled(
+            flag_info,
+            Style::default().fg(app.theme.id_column.flag.to_color()),
+        ),
+        Span::styled(
+            fold_info,
+            Style::default().fg(app.theme.id_column.zscore.to_color()),
+        ),
         Span::styled(
             selection_info,
             Style::default().fg(app.theme.status_bar.selection_info.to_color()),
@@ -1330,8 +2307,13 @@ pub fn visible_dimensions(
     show_pp_cons: bool,
     show_consensus: bool,
     show_conservation_bar: bool,
+    show_confidence_bar: bool,
+    show_track: bool,
     max_collapse_count: usize,
+    has_flags: bool,
+    show_zscores: bool,
     tree_display_width: usize,
+    pinned_display_width: usize,
     alignment_width: usize,
 ) -> (usize, usize) {
     let id_formatter = IdFormatter::new(
@@ -1340,6 +2322,8 @@ pub fn visible_dimensions(
         show_row_numbers,
         max_collapse_count,
         show_short_ids,
+        has_flags,
+        show_zscores,
     );
     let ruler_height = if show_ruler { RULER_HEIGHT } else { 0 };
     let ss_cons_height: u16 = if has_ss_cons { 1 } else { 0 };
@@ -1347,8 +2331,15 @@ pub fn visible_dimensions(
     let pp_cons_height: u16 = if show_pp_cons && has_pp_cons { 1 } else { 0 };
     let consensus_height: u16 = if show_consensus { 1 } else { 0 };
     let conservation_height: u16 = if show_conservation_bar { 1 } else { 0 };
-    let annotation_height =
-        ss_cons_height + rf_height + pp_cons_height + consensus_height + conservation_height;
+    let confidence_height: u16 = if show_confidence_bar { 1 } else { 0 };
+    let track_height: u16 = if show_track { 1 } else { 0 };
+    let annotation_height = ss_cons_height
+        + rf_height
+        + pp_cons_height
+        + consensus_height
+        + conservation_height
+        + confidence_height
+        + track_height;
 
     // Calculate the alignment area (total - status - command)
     let alignment_area_height = area.height.saturating_sub(2); // status + command
@@ -1373,6 +2364,7 @@ pub fn visible_dimensions(
     let inner_width = (pane_width as usize)
         .saturating_sub(id_formatter.width() + 2)
         .saturating_sub(tree_display_width)
+        .saturating_sub(pinned_display_width)
         .min(alignment_width);
 
     (inner_height, inner_width)
@@ -1506,7 +2498,19 @@ fn render_help(frame: &mut Frame) {
         Line::from("  Ctrl-f/b    Page down/up"),
         Line::from("  Ctrl-d/u    Half page down/up"),
         Line::from("  gp          Go to paired base"),
+        Line::from("  :pair H:N   Go to pair N of helix H (P3:5)"),
+        Line::from("  :helix [H]  Helix strands stacked pair by pair"),
         Line::from("  N|          Go to column N"),
+        Line::from("  NG          Go to sequence N"),
+        Line::from("  ma / `a     Set mark a / jump to it ('a: its row)"),
+        Line::from("  Ctrl-o/i    Back/forward through jumps (:marks)"),
+        Line::from("  5j 15l 3>   A count repeats a motion or edit"),
+        Line::from("  K           Show all chars in cursor column"),
+        Line::from("  :pileup     Residues in cursor column, folding IDs"),
+        Line::from("  ]d / [d     Next/prev difference from ref"),
+        Line::from("  ]c / [c     Next/prev compensatory pair"),
+        Line::from("  Ctrl-Space  Command palette (fuzzy search)"),
+        Line::from("  Ctrl-z      Suspend to the shell (fg resumes)"),
         Line::from(""),
         Line::from(Span::styled(
             "Search",
@@ -1516,6 +2520,9 @@ fn render_help(frame: &mut Frame) {
         )),
         Line::from("  /           Search (U/T tolerant)"),
         Line::from("  n / N       Next/previous match"),
+        Line::from("  :set search-gaps=exact  Match gaps too (skip)"),
+        Line::from("  :set search=iupac  IUPAC codes (literal/regex)"),
+        Line::from("  /re:X       Regex over residues and IDs"),
         Line::from(""),
         Line::from(Span::styled(
             "Split Windows",
@@ -1538,8 +2545,17 @@ fn render_help(frame: &mut Frame) {
         Line::from("  x           Delete gap at cursor"),
         Line::from("  I           Insert gap column"),
         Line::from("  X           Delete gap column"),
+        Line::from("  v ... I / X Gap columns across the selection"),
+        Line::from("  Ctrl-v      Block: I / X push in / close up gaps"),
+        Line::from("  dd          Delete sequence (p/P puts it back)"),
+        Line::from("  yy          Yank sequence (p/P puts a copy)"),
+        Line::from("  cw          Rename sequence (:rename old new)"),
+        Line::from("  \"ayy / \"ap  Yank into / put from register a-z"),
+        Line::from("  :%s/A/B/g   Replace A with B (:s current seq; c/i flags)"),
+        Line::from("  :trim --preview  What a bulk edit would change (y applies)"),
         Line::from("  < / >       Shift sequence left/right"),
         Line::from("  { / }       Throw sequence left/right"),
+        Line::from("  .           Repeat last edit at cursor (3. with count)"),
         Line::from("  u           Undo"),
         Line::from("  Ctrl-r      Redo"),
         Line::from(""),
@@ -1550,16 +2566,45 @@ fn render_help(frame: &mut Frame) {
                 .fg(Color::Yellow),
         )),
         Line::from("  :w          Save file"),
+        Line::from("  v ... :w! F Write only the selected block"),
         Line::from("  :q          Quit (:q! to force)"),
         Line::from("  :wq         Save and quit"),
+        Line::from("  :set        Show and edit settings (u undoes a :set)"),
+        Line::from("  :set ro     Read-only, as aform -R (:set noro)"),
+        Line::from("  :set gapchars=.-*  Characters read as gaps"),
+        Line::from("  :r F        Append sequences from file F"),
+        Line::from("  :new-seq ID Add a sequence (type it in insert mode, or give residues)"),
+        Line::from("  :n / :N     Next/previous file (:args lists)"),
+        Line::from("  :next-msa   Next alignment in file (:prev-msa, :msa lists)"),
+        Line::from("  :grepall X  Search all files (:cn/:cp step)"),
+        Line::from("  :screenshot F  Save the screen as ANSI text"),
+        Line::from("  :log        Session log (aform --log)"),
+        Line::from("  :present    Presentation view (Space/Bksp: :bookmark)"),
         Line::from("  :color X    Set color (ss/base/protein/cons)"),
+        Line::from("  :color X+Y  Overlay Y on X (e.g. base+cons, cons+ss)"),
+        Line::from("  :ssdiff     #=GR SS vs SS_cons (:color ssdiff)"),
+        Line::from("  :focus      Color only the selection (helix [N]: helices; off)"),
+        Line::from("  :pin [N-M]  Keep columns on screen while scrolling (off)"),
         Line::from("  :type X     Set seq type (rna/dna/protein/auto)"),
         Line::from("  :collapse   Toggle collapse identical seqs"),
         Line::from("  :consensus  Toggle consensus bar"),
         Line::from("  :conserv..  Toggle conservation bar"),
+        Line::from("  :confidence Toggle column confidence bar"),
+        Line::from("  :track TAG  Graph/edit numeric #=GC row (+/- 0-9)"),
+        Line::from("  :mask pp<N  Mask residues with low PP (:set mask=gap)"),
         Line::from("  :cluster    Cluster sequences by similarity"),
+        Line::from("  :cluster N  Cluster at N% identity"),
+        Line::from("  :clusters   Show cluster sizes"),
         Line::from("  :uncluster  Restore original order"),
+        Line::from("  :sortcol    Sort sequences by cursor column residue"),
         Line::from("  :tree       Toggle dendrogram tree"),
+        Line::from("  :throwcol left|right  Throw column"),
+        Line::from("  :mergecol / :splitcol  Merge/split column"),
+        Line::from("  :pad5/3 N   Pad seq ends (:truncate5/3 N)"),
+        Line::from("  :flag X     Flag sequence (:unflag clears)"),
+        Line::from("  :coords [F] Check /start-end vs residues (F: TSV)"),
+        Line::from("  :fixcoords [F]  Fix /start-end (F: source FASTA/table)"),
+        Line::from("  :filter flagged  Show only flagged seqs"),
         Line::from("  :help       Show this help"),
         Line::from(""),
         Line::from(Span::styled(
@@ -1685,3 +2730,879 @@ fn render_info(frame: &mut Frame, app: &App) {
 
     frame.render_widget(info_paragraph, popup_area);
 }
+
+/// Render popup listing the character each visible row has at the cursor column.
+fn render_column_popup(frame: &mut Frame, app: &App) {
+    use crate::stockholm::short_id;
+
+    let groups = app.column_groups(app.cursor_col);
+    let total: usize = groups.iter().map(|(_, _, count)| count).sum();
+
+    let mut title = format!("Column {}", app.cursor_col + 1);
+    if let Some(paired) = app.structure_cache.get_pair(app.cursor_col) {
+        title.push_str(&format!(" (pairs with {})", paired + 1));
+    }
+
+    let mut lines = vec![
+        Line::from(Span::styled(
+            title,
+            Style::default().add_modifier(Modifier::BOLD),
+        )),
+        Line::from(""),
+    ];
+
+    // Summary: one line per distinct character
+    for (ch, _, count) in &groups {
+        let pct = if total > 0 {
+            *count as f64 * 100.0 / total as f64
+        } else {
+            0.0
+        };
+        lines.push(Line::from(vec![
+            Span::styled(format!("  {ch}"), Style::default().fg(Color::Yellow)),
+            Span::raw(format!(" {count:>6}  {pct:>5.1}%")),
+        ]));
+    }
+    lines.push(Line::from(""));
+
+    // Reserve room for borders, trailing hint, and truncation notice
+    let area = frame.area();
+    let max_lines = (area.height.saturating_sub(4) as usize).saturating_sub(4);
+
+    // Detail: every visible row, grouped by character
+    let rows: Vec<(char, usize)> = groups
+        .iter()
+        .flat_map(|(ch, rows, _)| rows.iter().map(move |&r| (*ch, r)))
+        .collect();
+    let room = max_lines.saturating_sub(lines.len());
+    let top = app.column_popup_top;
+    for &(ch, display_row) in rows.iter().skip(top).take(room) {
+        let seq = &app.alignment.sequences[app.display_to_actual_row(display_row)];
+        let id = if app.show_short_ids {
+            short_id(&seq.id)
+        } else {
+            &seq.id
+        };
+        let count = app.get_collapse_count(display_row);
+        let suffix = if count > 1 {
+            format!(" ({count})")
+        } else {
+            String::new()
+        };
+        lines.push(Line::from(vec![
+            Span::styled(format!("  {ch}  "), Style::default().fg(Color::Yellow)),
+            Span::styled(
+                format!("{id}{suffix}"),
+                Style::default().fg(app.theme.id_column.text.to_color()),
+            ),
+        ]));
+    }
+    let scrolls = rows.len() > room;
+    if scrolls {
+        lines.push(Line::from(Span::styled(
+            format!(
+                "  rows {}-{} of {}",
+                (top + 1).min(rows.len()),
+                (top + room).min(rows.len()),
+                rows.len()
+            ),
+            Style::default().fg(Color::DarkGray),
+        )));
+    }
+
+    lines.push(Line::from(""));
+    lines.push(Line::from(Span::styled(
+        if scrolls {
+            "j/k scroll, any other key closes"
+        } else {
+            "Press any key to close"
+        },
+        Style::default().fg(Color::DarkGray),
+    )));
+
+    // Narrow popup anchored to the right side of the screen
+    let popup_width = 40.min(area.width.saturating_sub(4));
+    let popup_height = (lines.len() as u16 + 2).min(area.height.saturating_sub(4));
+    let popup_x = area.width.saturating_sub(popup_width + 2);
+    let popup_y = (area.height.saturating_sub(popup_height)) / 2;
+    let popup_area = Rect::new(popup_x, popup_y, popup_width, popup_height);
+
+    frame.render_widget(Clear, popup_area);
+
+    let popup_block = Block::default()
+        .borders(Borders::ALL)
+        .border_style(Style::default().fg(Color::Yellow))
+        .style(Style::default().bg(Color::Black));
+
+    let popup_paragraph = Paragraph::new(lines)
+        .block(popup_block)
+        .style(Style::default().bg(Color::Black));
+
+    frame.render_widget(popup_paragraph, popup_area);
+}
+
+/// Render the settings panel: every `:set` option with its value, the selected
+/// one editable in place.
+fn render_settings(frame: &mut Frame, app: &App) {
+    let Some(panel) = &app.settings_panel else {
+        return;
+    };
+    let key_width = SETTING_KEYS
+        .iter()
+        .map(|(key, _)| key.len())
+        .max()
+        .unwrap_or(0);
+    let values: Vec<String> = SETTING_KEYS
+        .iter()
+        .map(|(key, _)| app.setting_value(key))
+        .collect();
+    let value_width = values.iter().map(String::len).max().unwrap_or(0).max(10);
+
+    let mut lines = vec![
+        Line::from(Span::styled(
+            "Settings",
+            Style::default().add_modifier(Modifier::BOLD),
+        )),
+        Line::from(""),
+    ];
+    for (i, ((key, about), value)) in SETTING_KEYS.iter().zip(&values).enumerate() {
+        let selected = i == panel.cursor;
+        let mut style = Style::default();
+        if selected {
+            style = style.add_modifier(Modifier::REVERSED);
+        }
+        let value = match &panel.editing {
+            Some(typed) if selected => Span::styled(
+                format!("{:value_width$}", format!("{typed}_")),
+                Style::default().fg(Color::Black).bg(Color::Yellow),
+            ),
+            _ => Span::styled(format!("{value:value_width$}"), style.fg(Color::Cyan)),
+        };
+        lines.push(Line::from(vec![
+            Span::styled(format!("{key:key_width$}  "), style),
+            value,
+            Span::styled(format!("  {about}"), Style::default().fg(Color::DarkGray)),
+        ]));
+    }
+    lines.push(Line::from(""));
+    lines.push(Line::from(Span::styled(
+        if panel.editing.is_some() {
+            "Enter apply  Esc cancel"
+        } else {
+            "j/k move  Enter edit  u undo a change  q close"
+        },
+        Style::default().fg(Color::DarkGray),
+    )));
+
+    let area = frame.area();
+    let content_width = lines.iter().map(|l| l.width()).max().unwrap_or(0) as u16;
+    let popup_width = (content_width + 4).min(area.width.saturating_sub(4));
+    let popup_height = (lines.len() as u16 + 2).min(area.height.saturating_sub(4));
+    let popup_x = (area.width.saturating_sub(popup_width)) / 2;
+    let popup_y = (area.height.saturating_sub(popup_height)) / 2;
+    let popup_area = Rect::new(popup_x, popup_y, popup_width, popup_height);
+
+    frame.render_widget(Clear, popup_area);
+
+    let popup_block = Block::default()
+        .borders(Borders::ALL)
+        .border_style(Style::default().fg(Color::Yellow))
+        .style(Style::default().bg(Color::Black));
+
+    let popup_paragraph = Paragraph::new(lines)
+        .block(popup_block)
+        .style(Style::default().bg(Color::Black));
+
+    frame.render_widget(popup_paragraph, popup_area);
+}
+
+/// Render the helix view: each sequence's 5' strand over its reversed 3'
+/// strand, so every base pair is a column, colored by whether it pairs.
+fn render_helix_view(frame: &mut Frame, app: &App, view: &HelixView) {
+    use crate::stockholm::short_id;
+    use crate::structure::is_valid_pair;
+
+    let pairs = app.structure_cache.helix_pairs(view.helix);
+    let (Some(&(outer_left, outer_right)), Some(&(inner_left, inner_right))) =
+        (pairs.first(), pairs.last())
+    else {
+        return;
+    };
+    let ids: Vec<&str> = (0..app.visible_sequence_count())
+        .map(|display_row| {
+            let id = &app.alignment.sequences[app.display_to_actual_row(display_row)].id;
+            if app.show_short_ids { short_id(id) } else { id }
+        })
+        .collect();
+    let id_width = ids.iter().map(|id| id.chars().count()).max().unwrap_or(0);
+
+    let title = format!(
+        "Helix P{} of {}: {} pairs, 5' columns {}-{}, 3' columns {}-{}",
+        view.helix + 1,
+        app.structure_cache.num_helices(),
+        pairs.len(),
+        outer_left + 1,
+        inner_left + 1,
+        outer_right + 1,
+        inner_right + 1
+    );
+    let hint = "j/k scroll  h/l helix  Enter go to  q close";
+    let area = frame.area();
+    let content_width = (id_width + 4 + pairs.len())
+        .max(title.chars().count())
+        .max(hint.len());
+    let popup_width = (content_width as u16 + 4).min(area.width.saturating_sub(4));
+    let popup_height = area.height.saturating_sub(4);
+    let popup_area = Rect::new(
+        area.width.saturating_sub(popup_width) / 2,
+        2,
+        popup_width,
+        popup_height,
+    );
+
+    // Title, blank and hint lines plus borders; three lines per sequence
+    let room = ((popup_height as usize).saturating_sub(6) / 3).max(1);
+    let mut lines = vec![
+        Line::from(Span::styled(
+            title,
+            Style::default().add_modifier(Modifier::BOLD),
+        )),
+        Line::from(""),
+    ];
+    for (display_row, id) in ids.iter().enumerate().skip(view.top).take(room) {
+        let seq = &app.alignment.sequences[app.display_to_actual_row(display_row)];
+        let mut five = vec![Span::styled(
+            format!("{id:id_width$} 5' "),
+            Style::default().fg(app.theme.id_column.text.to_color()),
+        )];
+        let mut three = vec![Span::raw(format!("{:id_width$} 3' ", ""))];
+        for &(left, right) in &pairs {
+            let a = seq.get(left).unwrap_or(' ');
+            let b = seq.get(right).unwrap_or(' ');
+            let style = if app.gap_chars.contains(&a) || app.gap_chars.contains(&b) {
+                Style::default().fg(Color::DarkGray)
+            } else if !is_valid_pair(a, b) {
+                Style::default().fg(Color::Red).add_modifier(Modifier::BOLD)
+            } else if matches!(
+                (a.to_ascii_uppercase(), b.to_ascii_uppercase()),
+                ('G', 'U' | 'T') | ('U' | 'T', 'G')
+            ) {
+                Style::default().fg(Color::Yellow)
+            } else {
+                Style::default().fg(Color::Green)
+            };
+            five.push(Span::styled(a.to_string(), style));
+            three.push(Span::styled(b.to_string(), style));
+        }
+        lines.push(Line::from(five));
+        lines.push(Line::from(three));
+        lines.push(Line::from(""));
+    }
+    lines.push(Line::from(Span::styled(
+        hint,
+        Style::default().fg(Color::DarkGray),
+    )));
+
+    frame.render_widget(Clear, popup_area);
+
+    let popup_block = Block::default()
+        .borders(Borders::ALL)
+        .border_style(Style::default().fg(Color::Yellow))
+        .style(Style::default().bg(Color::Black));
+
+    let popup_paragraph = Paragraph::new(lines)
+        .block(popup_block)
+        .style(Style::default().bg(Color::Black));
+
+    frame.render_widget(popup_paragraph, popup_area);
+}
+
+/// Render the column pileup view: one line per distinct residue, unfolding to
+/// the IDs of the sequences carrying it.
+fn render_pileup(frame: &mut Frame, app: &App, pileup: &Pileup) {
+    use crate::stockholm::short_id;
+
+    let area = frame.area();
+    let popup_width = 60.min(area.width.saturating_sub(4));
+    let popup_height = area.height.saturating_sub(4);
+    let popup_x = area.width.saturating_sub(popup_width + 2);
+    let popup_area = Rect::new(popup_x, 2, popup_width, popup_height);
+
+    // Title, blank and hint lines plus borders
+    let room = (popup_height as usize).saturating_sub(6).max(1);
+    let offset = pileup.cursor.saturating_sub(room - 1);
+
+    let total: usize = pileup.groups.iter().map(|(_, _, count)| count).sum();
+    let mut title = format!(
+        "Column {}: {} distinct in {total} sequences",
+        pileup.col + 1,
+        pileup.groups.len()
+    );
+    if let Some(paired) = app.structure_cache.get_pair(pileup.col) {
+        title.push_str(&format!(" (pairs with {})", paired + 1));
+    }
+    let mut lines = vec![
+        Line::from(Span::styled(
+            title,
+            Style::default().add_modifier(Modifier::BOLD),
+        )),
+        Line::from(""),
+    ];
+
+    for (i, &(group, member)) in pileup.lines().iter().enumerate().skip(offset).take(room) {
+        let (ch, rows, count) = &pileup.groups[group];
+        let mut style = Style::default();
+        if i == pileup.cursor {
+            style = style.add_modifier(Modifier::REVERSED);
+        }
+        let line = match member {
+            None => {
+                let pct = if total > 0 {
+                    *count as f64 * 100.0 / total as f64
+                } else {
+                    0.0
+                };
+                let fold = if pileup.expanded[group] { '-' } else { '+' };
+                Line::from(vec![
+                    Span::styled(format!("{fold} "), style.fg(Color::DarkGray)),
+                    Span::styled(format!("{ch}"), style.fg(Color::Yellow)),
+                    Span::styled(format!(" {count:>6}  {pct:>5.1}%"), style),
+                ])
+            }
+            Some(member) => {
+                let display_row = rows[member];
+                let seq = &app.alignment.sequences[app.display_to_actual_row(display_row)];
+                let id = if app.show_short_ids {
+                    short_id(&seq.id)
+                } else {
+                    &seq.id
+                };
+                let collapsed = app.get_collapse_count(display_row);
+                let suffix = if collapsed > 1 {
+                    format!(" ({collapsed})")
+                } else {
+                    String::new()
+                };
+                Line::from(Span::styled(
+                    format!("    {id}{suffix}"),
+                    style.fg(app.theme.id_column.text.to_color()),
+                ))
+            }
+        };
+        lines.push(line);
+    }
+
+    lines.push(Line::from(""));
+    lines.push(Line::from(Span::styled(
+        "j/k move  Space unfold  a all  h/l column  Enter go to  q close",
+        Style::default().fg(Color::DarkGray),
+    )));
+
+    frame.render_widget(Clear, popup_area);
+
+    let popup_block = Block::default()
+        .borders(Borders::ALL)
+        .border_style(Style::default().fg(Color::Yellow))
+        .style(Style::default().bg(Color::Black));
+
+    let popup_paragraph = Paragraph::new(lines)
+        .block(popup_block)
+        .style(Style::default().bg(Color::Black));
+
+    frame.render_widget(popup_paragraph, popup_area);
+}
+
+/// Render panel listing sequence clusters (collapse groups) by size.
+///
+/// While `:cluster <N>` runs, shows the clusters found so far.
+fn render_clusters(frame: &mut Frame, app: &App) {
+    let (groups, title, summary) = match &app.cluster_job {
+        Some(job) => (
+            &job.groups,
+            format!("Clustering at {:.0}% identity...", job.threshold * 100.0),
+            format!(
+                "{} of {} sequences in {} clusters so far",
+                job.clustered,
+                app.alignment.num_sequences(),
+                job.groups.len()
+            ),
+        ),
+        None => (
+            &app.collapse_groups,
+            match app.identity_threshold {
+                Some(t) => format!("Clusters at {:.0}% identity", t * 100.0),
+                None => "Identical sequence groups".to_string(),
+            },
+            format!(
+                "{} sequences in {} groups",
+                app.alignment.num_sequences(),
+                app.collapse_groups.len()
+            ),
+        ),
+    };
+    let mut groups: Vec<&(usize, Vec<usize>)> = groups.iter().collect();
+    groups.sort_by_key(|(rep, members)| (std::cmp::Reverse(members.len()), *rep));
+
+    let mut lines = vec![
+        Line::from(Span::styled(
+            title,
+            Style::default().add_modifier(Modifier::BOLD),
+        )),
+        Line::from(summary),
+        Line::from(""),
+    ];
+
+    let area = frame.area();
+    let room = (area.height.saturating_sub(6) as usize).saturating_sub(lines.len() + 3);
+    for (rep, members) in groups.iter().take(room) {
+        let id = app
+            .alignment
+            .sequences
+            .get(*rep)
+            .map(|s| s.id.as_str())
+            .unwrap_or("");
+        lines.push(Line::from(vec![
+            Span::styled(
+                format!("{:>6}  ", members.len()),
+                Style::default().fg(Color::Yellow),
+            ),
+            Span::styled(
+                id.to_string(),
+                Style::default().fg(app.theme.id_column.text.to_color()),
+            ),
+        ]));
+    }
+    if groups.len() > room {
+        lines.push(Line::from(Span::styled(
+            format!("  ... and {} more", groups.len() - room),
+            Style::default().fg(Color::DarkGray),
+        )));
+    }
+
+    lines.push(Line::from(""));
+    lines.push(Line::from(Span::styled(
+        "Press any key to close",
+        Style::default().fg(Color::DarkGray),
+    )));
+
+    let popup_width = 50.min(area.width.saturating_sub(4));
+    let popup_height = (lines.len() as u16 + 2).min(area.height.saturating_sub(4));
+    let popup_x = (area.width.saturating_sub(popup_width)) / 2;
+    let popup_y = (area.height.saturating_sub(popup_height)) / 2;
+    let popup_area = Rect::new(popup_x, popup_y, popup_width, popup_height);
+
+    frame.render_widget(Clear, popup_area);
+
+    let popup_block = Block::default()
+        .borders(Borders::ALL)
+        .border_style(Style::default().fg(Color::Yellow))
+        .style(Style::default().bg(Color::Black));
+
+    let popup_paragraph = Paragraph::new(lines)
+        .block(popup_block)
+        .style(Style::default().bg(Color::Black));
+
+    frame.render_widget(popup_paragraph, popup_area);
+}
+
+/// Render a read-only text report overlay.
+fn render_report(frame: &mut Frame, report: &Report) {
+    let area = frame.area();
+    let room = (area.height.saturating_sub(6) as usize).saturating_sub(4);
+
+    let mut lines = vec![
+        Line::from(Span::styled(
+            report.title.clone(),
+            Style::default().add_modifier(Modifier::BOLD),
+        )),
+        Line::from(""),
+    ];
+    lines.extend(
+        report
+            .lines
+            .iter()
+            .take(room)
+            .map(|l| Line::from(l.clone())),
+    );
+    if report.lines.len() > room {
+        lines.push(Line::from(Span::styled(
+            format!("... and {} more lines", report.lines.len() - room),
+            Style::default().fg(Color::DarkGray),
+        )));
+    }
+    lines.push(Line::from(""));
+    lines.push(Line::from(Span::styled(
+        "Press any key to close",
+        Style::default().fg(Color::DarkGray),
+    )));
+
+    let content_width = lines.iter().map(|l| l.width()).max().unwrap_or(0) as u16;
+    let popup_width = (content_width + 4)
+        .max(40)
+        .min(area.width.saturating_sub(4));
+    let popup_height = (lines.len() as u16 + 2).min(area.height.saturating_sub(4));
+    let popup_x = (area.width.saturating_sub(popup_width)) / 2;
+    let popup_y = (area.height.saturating_sub(popup_height)) / 2;
+    let popup_area = Rect::new(popup_x, popup_y, popup_width, popup_height);
+
+    frame.render_widget(Clear, popup_area);
+
+    let popup_block = Block::default()
+        .borders(Borders::ALL)
+        .border_style(Style::default().fg(Color::Yellow))
+        .style(Style::default().bg(Color::Black));
+
+    let popup_paragraph = Paragraph::new(lines)
+        .block(popup_block)
+        .style(Style::default().bg(Color::Black));
+
+    frame.render_widget(popup_paragraph, popup_area);
+}
+
+/// Render the search hit viewer.
+fn render_hits(frame: &mut Frame, app: &App) {
+    let area = frame.area();
+    let popup_width = area.width.saturating_sub(4);
+    let popup_height = area.height.saturating_sub(4);
+    let popup_area = Rect::new(2, 2, popup_width, popup_height);
+
+    // Title, header, blank and hint lines plus borders
+    let room = (popup_height as usize).saturating_sub(6).max(1);
+    let offset = app.hit_cursor.saturating_sub(room - 1);
+    let marked = app.hit_marks.iter().filter(|m| **m).count();
+
+    let mut lines = vec![
+        Line::from(Span::styled(
+            format!(
+                "Search hits: {} ({marked} marked, = already aligned)",
+                app.hits.len()
+            ),
+            Style::default().add_modifier(Modifier::BOLD),
+        )),
+        Line::from(Span::styled(
+            format!(
+                "    {:<36} {:>1} {:>8} {:>10} {:>3}  description",
+                "target/from-to", "", "score", "E-value", "inc"
+            ),
+            Style::default().fg(Color::Yellow),
+        )),
+    ];
+
+    for (i, hit) in app.hits.iter().enumerate().skip(offset).take(room) {
+        let mark = if app.hit_marks[i] { '*' } else { ' ' };
+        let member = if app.hit_members[i].is_some() {
+            '='
+        } else {
+            ' '
+        };
+        let inc = if hit.included { "!" } else { "?" };
+        let text = format!(
+            "{mark}{member}  {:<36} {} {:>8.1} {:>10.2e} {:>3}  {}",
+            hit.id(),
+            hit.strand,
+            hit.score,
+            hit.evalue,
+            inc,
+            hit.description
+        );
+        let mut style = Style::default();
+        if app.hit_members[i].is_some() {
+            style = style.fg(Color::DarkGray);
+        }
+        if app.hit_marks[i] {
+            style = style.fg(Color::Green);
+        }
+        if i == app.hit_cursor {
+            style = style.add_modifier(Modifier::REVERSED);
+        }
+        lines.push(Line::from(Span::styled(text, style)));
+    }
+
+    lines.push(Line::from(""));
+    lines.push(Line::from(Span::styled(
+        "j/k move  Space mark  a mark new  Enter go to sequence  q close  (:hits fetch <file>)",
+        Style::default().fg(Color::DarkGray),
+    )));
+
+    frame.render_widget(Clear, popup_area);
+
+    let popup_block = Block::default()
+        .borders(Borders::ALL)
+        .border_style(Style::default().fg(Color::Yellow))
+        .style(Style::default().bg(Color::Black));
+
+    let popup_paragraph = Paragraph::new(lines)
+        .block(popup_block)
+        .style(Style::default().bg(Color::Black));
+
+    frame.render_widget(popup_paragraph, popup_area);
+}
+
+/// Sequences sampled for the alignment thumbnail in the alignment picker.
+const THUMBNAIL_ROWS: usize = 6;
+
+fn render_record_list(frame: &mut Frame, app: &App) {
+    let area = frame.area();
+    let popup_width = area.width.saturating_sub(4);
+    let popup_height = area.height.saturating_sub(4);
+    let popup_area = Rect::new(2, 2, popup_width, popup_height);
+
+    // Title, blank and hint lines plus borders, and a thumbnail of the selected
+    // alignment below the list when there is room for it
+    let mut room = (popup_height as usize).saturating_sub(5).max(1);
+    let thumbnail_height = if room >= THUMBNAIL_ROWS + 1 + 3 {
+        room -= THUMBNAIL_ROWS + 1;
+        THUMBNAIL_ROWS
+    } else {
+        0
+    };
+    let offset = app.record_cursor.saturating_sub(room - 1);
+    let number_width = app.records.len().to_string().len();
+
+    let mut lines = vec![Line::from(Span::styled(
+        format!("Alignments: {}", app.records.len()),
+        Style::default().add_modifier(Modifier::BOLD),
+    ))];
+    for index in (offset..app.records.len()).take(room) {
+        let marker = if index == app.record_index { '*' } else { ' ' };
+        let mut style = Style::default();
+        if index == app.record_cursor {
+            style = style.add_modifier(Modifier::REVERSED);
+        }
+        lines.push(Line::from(vec![
+            Span::styled(
+                format!("{marker}{:>number_width$}  ", index + 1),
+                style.fg(Color::Cyan),
+            ),
+            Span::styled(app.record_summary(app.record(index)), style),
+        ]));
+    }
+
+    if thumbnail_height > 0 {
+        // Keep the thumbnail at the bottom however short the list is
+        let listed = app.records.len().saturating_sub(offset).min(room);
+        lines.extend((listed..room).map(|_| Line::from("")));
+        lines.push(Line::from(""));
+        let cols = popup_width.saturating_sub(2) as usize;
+        let thumbnail = conservation_thumbnail(
+            app.record(app.record_cursor),
+            thumbnail_height,
+            cols,
+            &app.gap_chars,
+        );
+        for row in thumbnail {
+            lines.push(Line::from(
+                row.into_iter()
+                    .map(|cell| match cell {
+                        Some(color) => Span::styled("█", Style::default().fg(color)),
+                        None => Span::styled("·", Style::default().fg(Color::DarkGray)),
+                    })
+                    .collect::<Vec<_>>(),
+            ));
+        }
+    }
+
+    lines.push(Line::from(""));
+    lines.push(Line::from(Span::styled(
+        "j/k move  Enter open  q close  (:next-msa/:prev-msa step through alignments)",
+        Style::default().fg(Color::DarkGray),
+    )));
+
+    frame.render_widget(Clear, popup_area);
+
+    let popup_block = Block::default()
+        .borders(Borders::ALL)
+        .border_style(Style::default().fg(Color::Yellow))
+        .style(Style::default().bg(Color::Black));
+
+    let popup_paragraph = Paragraph::new(lines)
+        .block(popup_block)
+        .style(Style::default().bg(Color::Black));
+
+    frame.render_widget(popup_paragraph, popup_area);
+}
+
+fn render_palette(frame: &mut Frame, palette: &Palette) {
+    let area = frame.area();
+    let popup_width = area.width.saturating_sub(4).min(64);
+    let popup_height = area.height.saturating_sub(4);
+    let popup_area = Rect::new((area.width - popup_width) / 2, 2, popup_width, popup_height);
+
+    // Query, blank and hint lines plus borders
+    let room = (popup_height as usize).saturating_sub(5).max(1);
+    let offset = palette.cursor.saturating_sub(room - 1);
+    let inner_width = popup_width.saturating_sub(2) as usize;
+
+    let mut lines = vec![Line::from(vec![
+        Span::styled("> ", Style::default().fg(Color::Yellow)),
+        Span::styled(
+            format!("{}_", palette.query),
+            Style::default().add_modifier(Modifier::BOLD),
+        ),
+    ])];
+    for (i, &index) in palette.matches.iter().enumerate().skip(offset).take(room) {
+        let entry = &palette::ENTRIES[index];
+        let mut style = Style::default();
+        if i == palette.cursor {
+            style = style.add_modifier(Modifier::REVERSED);
+        }
+        let name_width = inner_width.saturating_sub(entry.key.len() + 1);
+        lines.push(Line::from(vec![
+            Span::styled(format!("{:name_width$} ", entry.name), style),
+            Span::styled(entry.key, style.fg(Color::Cyan)),
+        ]));
+    }
+    if palette.matches.is_empty() {
+        lines.push(Line::from(Span::styled(
+            "No matching actions",
+            Style::default().fg(Color::DarkGray),
+        )));
+    }
+
+    lines.push(Line::from(""));
+    lines.push(Line::from(Span::styled(
+        "type to filter  Up/Down move  Enter run  Esc close",
+        Style::default().fg(Color::DarkGray),
+    )));
+
+    frame.render_widget(Clear, popup_area);
+
+    let popup_block = Block::default()
+        .borders(Borders::ALL)
+        .border_style(Style::default().fg(Color::Yellow))
+        .style(Style::default().bg(Color::Black));
+
+    let popup_paragraph = Paragraph::new(lines)
+        .block(popup_block)
+        .style(Style::default().bg(Color::Black));
+
+    frame.render_widget(popup_paragraph, popup_area);
+}
+
+fn render_quickfix(frame: &mut Frame, app: &App) {
+    let area = frame.area();
+    let popup_width = area.width.saturating_sub(4);
+    let popup_height = area.height.saturating_sub(4);
+    let popup_area = Rect::new(2, 2, popup_width, popup_height);
+
+    // Title, blank and hint lines plus borders
+    let room = (popup_height as usize).saturating_sub(5).max(1);
+    let offset = app.quickfix_cursor.saturating_sub(room - 1);
+
+    let mut lines = vec![Line::from(Span::styled(
+        format!("Quickfix: {} matches", app.quickfix.len()),
+        Style::default().add_modifier(Modifier::BOLD),
+    ))];
+
+    let locations: Vec<String> = app
+        .quickfix
+        .iter()
+        .skip(offset)
+        .take(room)
+        .map(|entry| app.quickfix_location(entry))
+        .collect();
+    let location_width = locations.iter().map(String::len).max().unwrap_or(0);
+    for (i, (entry, location)) in app.quickfix.iter().skip(offset).zip(&locations).enumerate() {
+        let kind = match entry.kind {
+            QuickfixKind::Sequence => "seq",
+            QuickfixKind::Id => "id",
+            QuickfixKind::FileAnnotation => "GF",
+        };
+        let mut style = Style::default();
+        if offset + i == app.quickfix_cursor {
+            style = style.add_modifier(Modifier::REVERSED);
+        }
+        lines.push(Line::from(vec![
+            Span::styled(
+                format!("{location:location_width$}  "),
+                style.fg(Color::Cyan),
+            ),
+            Span::styled(format!("{kind:<3}  {}", entry.text), style),
+        ]));
+    }
+
+    lines.push(Line::from(""));
+    lines.push(Line::from(Span::styled(
+        "j/k move  Enter go to match  q close  (:cn/:cp step through matches)",
+        Style::default().fg(Color::DarkGray),
+    )));
+
+    frame.render_widget(Clear, popup_area);
+
+    let popup_block = Block::default()
+        .borders(Borders::ALL)
+        .border_style(Style::default().fg(Color::Yellow))
+        .style(Style::default().bg(Color::Black));
+
+    let popup_paragraph = Paragraph::new(lines)
+        .block(popup_block)
+        .style(Style::default().bg(Color::Black));
+
+    frame.render_widget(popup_paragraph, popup_area);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::app_with;
+    use ratatui::{Terminal, backend::TestBackend};
+
+    /// The lines drawn for `app` in a `width` x `height` terminal.
+    fn draw(app: &App, width: u16, height: u16) -> Vec<String> {
+        let mut terminal = Terminal::new(TestBackend::new(width, height)).unwrap();
+        terminal.draw(|frame| render(frame, app)).unwrap();
+        let buffer = terminal.backend().buffer();
+        (0..height)
+            .map(|y| {
+                (0..width)
+                    .map(|x| buffer[(x, y)].symbol())
+                    .collect::<String>()
+                    .trim_end()
+                    .to_string()
+            })
+            .collect()
+    }
+
+    const THREE: &str = "# STOCKHOLM 1.0\nseq1 ACGUACGU\nseq2 ACGUACGU\nseq3 ACGAACGU\n//\n";
+
+    #[test]
+    fn test_screen_size_cut_offs() {
+        let size = |width, height| ScreenSize::of(Rect::new(0, 0, width, height));
+        assert_eq!(size(19, 40), ScreenSize::TooSmall);
+        assert_eq!(size(80, 3), ScreenSize::TooSmall);
+        assert_eq!(size(20, 4), ScreenSize::Compact);
+        assert_eq!(size(49, 40), ScreenSize::Compact);
+        assert_eq!(size(80, 9), ScreenSize::Compact);
+        assert_eq!(size(50, 10), ScreenSize::Full);
+    }
+
+    #[test]
+    fn test_compact_dimensions() {
+        let app = app_with(THREE);
+        // One line is left for the status line, and no more columns than the alignment has
+        assert_eq!(compact_dimensions(Rect::new(0, 0, 30, 6), &app), (5, 8));
+        assert_eq!(compact_dimensions(Rect::new(0, 0, 5, 4), &app), (3, 5));
+        assert_eq!(
+            compact_dimensions(Rect::new(0, 0, 0, 0), &App::new()),
+            (1, 1)
+        );
+    }
+
+    #[test]
+    fn test_too_small_terminal_shows_a_notice() {
+        let app = app_with(THREE);
+        let lines = draw(&app, 19, 3);
+        assert_eq!(lines[0].trim(), "Terminal too small");
+        assert_eq!(lines[1].trim(), "19x3");
+        assert_eq!(lines[2].trim(), "need 20x4");
+    }
+
+    #[test]
+    fn test_compact_view_shows_residues_and_position() {
+        let mut app = app_with(THREE);
+        app.status_message = None;
+        let lines = draw(&app, 30, 6);
+        assert_eq!(lines[..4], ["ACGUACGU", "ACGUACGU", "ACGAACGU", ""]);
+        assert_eq!(lines[5], "NORMAL 1/3 seq1:1");
+    }
+}